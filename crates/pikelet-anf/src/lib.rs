@@ -0,0 +1,9 @@
+//! An intermediate representation in A-normal form
+//!
+//! This is the first step on the way from the core syntax to machine code.
+//! Erased core terms are lowered to expressions where the operands of every
+//! computation are atoms, and the order of evaluation is made explicit by a
+//! sequence of bindings.
+
+pub mod lower;
+pub mod syntax;
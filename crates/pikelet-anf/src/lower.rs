@@ -0,0 +1,305 @@
+//! Lowering of core terms to A-normal form
+//!
+//! Terms are erased before they are lowered, so the lowering only has to deal
+//! with the parts of a program that are needed at runtime. Any types that are
+//! left over are lowered to `Atom::Erased`.
+//!
+//! The operands of a computation are lowered from left to right, with the
+//! head of a function application coming before its argument, matching the
+//! order in which `pikelet_core::eval` evaluates them. Computations that are
+//! used as operands are bound to fresh variables, and let bindings that bind
+//! atoms are replaced with the atoms that they bind.
+
+use pikelet_core::erase::erase;
+use pikelet_core::syntax::core::{Pattern, RcPattern, RcTerm, Term};
+use pikelet_core::syntax::NameHint;
+
+use crate::syntax::{self, Atom, Comp, Expr, Var};
+
+/// An error produced while lowering a term
+///
+/// This should only happen if the term has not been fully elaborated.
+#[derive(Debug, Clone, PartialEq, failure::Fail)]
+#[fail(display = "{}", message)]
+pub struct LowerError {
+    pub message: String,
+}
+
+impl LowerError {
+    pub fn new(message: impl Into<String>) -> LowerError {
+        LowerError {
+            message: message.into(),
+        }
+    }
+}
+
+/// The atoms that the local variables in scope were lowered to, in the order
+/// that they were bound
+type Locals = im::Vector<Atom>;
+
+/// Lower a closed term to A-normal form
+pub fn lower_term(term: &RcTerm) -> Result<Expr, LowerError> {
+    Lower::new().lower_expr(&Locals::new(), &erase(term))
+}
+
+/// Keeps track of the variables that have been bound so far
+struct Lower {
+    next_id: usize,
+}
+
+impl Lower {
+    fn new() -> Lower {
+        Lower { next_id: 0 }
+    }
+
+    fn fresh(&mut self, name: &NameHint) -> Var {
+        let var = Var::new(self.next_id, name.clone());
+        self.next_id += 1;
+        var
+    }
+
+    /// Lower a term, along with the computations that it depends on
+    fn lower_expr(&mut self, locals: &Locals, term: &RcTerm) -> Result<Expr, LowerError> {
+        let mut bindings = Vec::new();
+        let body = self.lower_comp(locals, term, &mut bindings)?;
+
+        Ok(Expr::new(bindings, body))
+    }
+
+    /// Lower a term to an atom, binding it to a fresh variable if it needs
+    /// work to compute
+    fn lower_atom(
+        &mut self,
+        locals: &Locals,
+        term: &RcTerm,
+        bindings: &mut Vec<(Var, Comp)>,
+    ) -> Result<Atom, LowerError> {
+        self.lower_named_atom(locals, &NameHint::default(), term, bindings)
+    }
+
+    fn lower_named_atom(
+        &mut self,
+        locals: &Locals,
+        name: &NameHint,
+        term: &RcTerm,
+        bindings: &mut Vec<(Var, Comp)>,
+    ) -> Result<Atom, LowerError> {
+        match self.lower_comp(locals, term, bindings)? {
+            Comp::Atom(atom) => Ok(atom),
+            comp => {
+                let var = self.fresh(name);
+                bindings.push((var.clone(), comp));
+                Ok(Atom::Var(var))
+            },
+        }
+    }
+
+    fn lower_fields<T: Clone>(
+        &mut self,
+        locals: &Locals,
+        fields: &[(T, RcTerm)],
+        bindings: &mut Vec<(Var, Comp)>,
+    ) -> Result<Vec<(T, Atom)>, LowerError> {
+        fields
+            .iter()
+            .map(|&(ref label, ref term)| {
+                Ok((label.clone(), self.lower_atom(locals, term, bindings)?))
+            })
+            .collect()
+    }
+
+    /// Lower a term to a computation, pushing the computations that it
+    /// depends on to `bindings`
+    fn lower_comp(
+        &mut self,
+        locals: &Locals,
+        term: &RcTerm,
+        bindings: &mut Vec<(Var, Comp)>,
+    ) -> Result<Comp, LowerError> {
+        match *term.inner {
+            Term::Ann(ref expr, _) => self.lower_comp(locals, expr, bindings),
+
+            Term::Universe(_)
+            | Term::UniverseAt(_)
+            | Term::UniverseOmega
+            | Term::LevelType
+            | Term::LevelIntro(_)
+            | Term::LevelMax(_, _)
+            | Term::LevelShift(_, _)
+            | Term::FunType(_, _, _, _, _)
+            | Term::RecordType(_)
+            | Term::VariantType(_)
+            | Term::EqType(_, _, _)
+            | Term::EqIntro
+            | Term::Erased => Ok(Comp::Atom(Atom::Erased)),
+
+            Term::Literal(ref literal) => Ok(Comp::Atom(Atom::Literal(literal.clone()))),
+            Term::Var(index, _) => match index.to_level(locals.len()) {
+                Some(level) => Ok(Comp::Atom(locals[level.0].clone())),
+                None => Err(LowerError::new(format!(
+                    "unbound local variable `{}`",
+                    index
+                ))),
+            },
+            Term::Global(ref free_var, _) => Ok(Comp::Atom(Atom::Global(free_var.clone()))),
+            Term::Import(ref name) => Ok(Comp::Atom(Atom::Import(name.clone()))),
+            Term::Meta(meta) => Err(LowerError::new(format!("unsolved metavariable `{}`", meta))),
+
+            Term::FunIntro(_, _, ref name, _, ref body) => {
+                let param = self.fresh(name);
+                let mut locals = locals.clone();
+                locals.push_back(Atom::Var(param.clone()));
+
+                Ok(Comp::FunIntro(
+                    param,
+                    Box::new(self.lower_expr(&locals, body)?),
+                ))
+            },
+            Term::FunApp(ref head, _, ref arg) => {
+                let head = self.lower_atom(locals, head, bindings)?;
+                let arg = self.lower_atom(locals, arg, bindings)?;
+
+                Ok(Comp::FunApp(head, arg))
+            },
+
+            Term::RecordIntro(ref fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|&(ref label, _, ref term)| {
+                        Ok((label.clone(), self.lower_atom(locals, term, bindings)?))
+                    })
+                    .collect::<Result<_, LowerError>>()?;
+
+                Ok(Comp::RecordIntro(fields))
+            },
+            Term::RecordProj(ref expr, ref label, _, _) => {
+                let expr = self.lower_atom(locals, expr, bindings)?;
+
+                Ok(Comp::RecordProj(expr, label.clone()))
+            },
+            Term::RecordExtend(ref expr, ref fields) => {
+                let expr = self.lower_atom(locals, expr, bindings)?;
+                let fields = self.lower_fields(locals, fields, bindings)?;
+
+                Ok(Comp::RecordExtend(expr, fields))
+            },
+
+            Term::VariantIntro(ref label, ref arg) => {
+                let arg = self.lower_atom(locals, arg, bindings)?;
+
+                Ok(Comp::VariantIntro(label.clone(), arg))
+            },
+
+            // Rewriting doesn't change the value of the body
+            Term::EqElim(_, _, ref body) => self.lower_comp(locals, body, bindings),
+
+            Term::Case(ref head, ref clauses) => {
+                let head = self.lower_atom(locals, head, bindings)?;
+                let clauses = clauses
+                    .iter()
+                    .map(|&(ref pattern, ref body)| {
+                        let mut locals = locals.clone();
+                        let pattern = self.lower_pattern(&mut locals, pattern)?;
+                        let body = self.lower_expr(&locals, body)?;
+
+                        Ok((pattern, body))
+                    })
+                    .collect::<Result<_, LowerError>>()?;
+
+                Ok(Comp::Case(head, clauses))
+            },
+            Term::ArrayIntro(ref elems) => Ok(Comp::ArrayIntro(
+                elems
+                    .iter()
+                    .map(|elem| self.lower_atom(locals, elem, bindings))
+                    .collect::<Result<_, LowerError>>()?,
+            )),
+
+            Term::Let(ref let_bindings, ref body) => {
+                let mut locals = locals.clone();
+                for &(ref name, ref term) in let_bindings {
+                    let atom = self.lower_named_atom(&locals, name, term, bindings)?;
+                    locals.push_back(atom);
+                }
+
+                self.lower_comp(&locals, body, bindings)
+            },
+            Term::Fix(_, ref name, _, ref body) => {
+                let var = self.fresh(name);
+                let mut locals = locals.clone();
+                locals.push_back(Atom::Var(var.clone()));
+
+                Ok(Comp::Fix(var, Box::new(self.lower_expr(&locals, body)?)))
+            },
+        }
+    }
+
+    /// Lower a pattern, adding the variables that it binds to `locals`
+    fn lower_pattern(
+        &mut self,
+        locals: &mut Locals,
+        pattern: &RcPattern,
+    ) -> Result<syntax::Pattern, LowerError> {
+        let mut binders = Vec::new();
+        let pattern = self.lower_pattern_binders(locals, pattern, &mut binders)?;
+        for binder in binders {
+            locals.push_back(binder);
+        }
+
+        Ok(pattern)
+    }
+
+    fn lower_pattern_binders(
+        &mut self,
+        locals: &Locals,
+        pattern: &RcPattern,
+        binders: &mut Vec<Atom>,
+    ) -> Result<syntax::Pattern, LowerError> {
+        match *pattern.inner {
+            Pattern::Ann(ref pattern, _) => self.lower_pattern_binders(locals, pattern, binders),
+            Pattern::Binder(ref name) => {
+                let var = self.fresh(name);
+                binders.push(Atom::Var(var.clone()));
+
+                Ok(syntax::Pattern::Binder(var))
+            },
+            // Variables in patterns refer to the scope outside of the pattern
+            Pattern::Var(index, _) => match index.to_level(locals.len()) {
+                Some(level) => Ok(syntax::Pattern::Atom(locals[level.0].clone())),
+                None => Err(LowerError::new(format!(
+                    "unbound local variable `{}`",
+                    index
+                ))),
+            },
+            Pattern::Global(ref free_var, _) => {
+                Ok(syntax::Pattern::Atom(Atom::Global(free_var.clone())))
+            },
+            Pattern::Literal(ref literal) => {
+                Ok(syntax::Pattern::Atom(Atom::Literal(literal.clone())))
+            },
+            Pattern::VariantIntro(ref label, ref pattern) => {
+                let pattern = self.lower_pattern_binders(locals, pattern, binders)?;
+
+                Ok(syntax::Pattern::VariantIntro(
+                    label.clone(),
+                    Box::new(pattern),
+                ))
+            },
+            Pattern::RecordIntro(ref fields) => Ok(syntax::Pattern::RecordIntro(
+                fields
+                    .iter()
+                    .map(|&(ref label, ref pattern)| {
+                        let pattern = self.lower_pattern_binders(locals, pattern, binders)?;
+                        Ok((label.clone(), pattern))
+                    })
+                    .collect::<Result<_, LowerError>>()?,
+            )),
+            Pattern::ArrayIntro(ref patterns) => Ok(syntax::Pattern::ArrayIntro(
+                patterns
+                    .iter()
+                    .map(|pattern| self.lower_pattern_binders(locals, pattern, binders))
+                    .collect::<Result<_, LowerError>>()?,
+            )),
+        }
+    }
+}
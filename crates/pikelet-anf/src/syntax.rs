@@ -0,0 +1,307 @@
+//! The syntax of the A-normal form intermediate representation
+//!
+//! Every intermediate computation is bound to a variable of its own, and the
+//! operands of a computation can only be atoms: variables, literals, or other
+//! things that take no work to evaluate. This leaves the order in which a
+//! program does its work plain to see.
+
+use pretty::{BoxDoc, Doc};
+use std::fmt;
+
+use pikelet_core::syntax::{FreeVar, Label, Literal, NameHint, PRETTY_FALLBACK_WIDTH};
+
+/// The number of spaces to indent nested expressions by
+const PRETTY_INDENT_WIDTH: usize = 4;
+
+/// A local variable
+///
+/// Each variable that is bound in a program is given a unique number when it
+/// is lowered, so there is no need to worry about shadowing in later passes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Var {
+    pub id: usize,
+    pub name: NameHint,
+}
+
+impl Var {
+    pub fn new(id: usize, name: NameHint) -> Var {
+        Var { id, name }
+    }
+
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        match self.name.0 {
+            Some(ref name) => Doc::text(format!("{}#{}", name, self.id)),
+            None => Doc::text(format!("t#{}", self.id)),
+        }
+    }
+}
+
+impl fmt::Display for Var {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
+/// Values that take no work to compute
+#[derive(Debug, Clone, PartialEq)]
+pub enum Atom {
+    /// Local variables
+    Var(Var),
+    /// Global variables
+    Global(FreeVar<String>),
+    /// Primitive imports
+    Import(String),
+    /// Literals
+    Literal(Literal),
+    /// Types, and anything else that is not needed at runtime
+    Erased,
+}
+
+impl Atom {
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        match *self {
+            Atom::Import(ref name) => Doc::text(format!("import {:?}", name)),
+            ref atom => atom.to_doc_atomic(),
+        }
+    }
+
+    pub fn to_doc_atomic(&self) -> Doc<BoxDoc<()>> {
+        match *self {
+            Atom::Var(ref var) => var.to_doc(),
+            Atom::Global(ref free_var) => match free_var.pretty_name {
+                Some(ref name) => Doc::text(name.clone()),
+                None => Doc::text("<global>"),
+            },
+            Atom::Literal(ref literal) => literal.to_doc(),
+            Atom::Erased => Doc::text("erased"),
+            ref atom => Doc::text("(").append(atom.to_doc()).append(")"),
+        }
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
+/// Patterns, which can only look at the outermost constructor of the value
+/// that they are matched against, once they have been compiled to decision
+/// trees
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// Patterns that bind the matched value to a variable
+    Binder(Var),
+    /// Patterns that compare the matched value against an atom
+    Atom(Atom),
+    /// Variant patterns
+    VariantIntro(Label, Box<Pattern>),
+    /// Record patterns
+    RecordIntro(Vec<(Label, Pattern)>),
+    /// Array patterns
+    ArrayIntro(Vec<Pattern>),
+}
+
+impl Pattern {
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        match *self {
+            Pattern::Binder(ref var) => var.to_doc(),
+            Pattern::Atom(Atom::Literal(ref literal)) => literal.to_doc(),
+            Pattern::Atom(ref atom) => Doc::text("(== ").append(atom.to_doc()).append(")"),
+            Pattern::VariantIntro(ref label, ref pattern) => Doc::nil()
+                .append("variant ")
+                .append(Doc::as_string(label))
+                .append(" ")
+                .append(pattern.to_doc()),
+            Pattern::RecordIntro(ref fields) => fields_to_doc(
+                "record",
+                fields
+                    .iter()
+                    .map(|&(ref label, ref pattern)| (label, pattern.to_doc())),
+            ),
+            Pattern::ArrayIntro(ref patterns) => Doc::nil()
+                .append("[")
+                .append(Doc::intersperse(
+                    patterns.iter().map(Pattern::to_doc),
+                    Doc::text("; "),
+                ))
+                .append("]"),
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
+/// Computations, which only take atoms as operands
+#[derive(Debug, Clone, PartialEq)]
+pub enum Comp {
+    /// Atoms
+    Atom(Atom),
+    /// Functions
+    FunIntro(Var, Box<Expr>),
+    /// Function applications
+    FunApp(Atom, Atom),
+    /// Records
+    RecordIntro(Vec<(Label, Atom)>),
+    /// Record projections
+    RecordProj(Atom, Label),
+    /// Record extensions
+    RecordExtend(Atom, Vec<(Label, Atom)>),
+    /// Variants
+    VariantIntro(Label, Atom),
+    /// Arrays
+    ArrayIntro(Vec<Atom>),
+    /// Case expressions
+    Case(Atom, Vec<(Pattern, Expr)>),
+    /// Fixed points
+    Fix(Var, Box<Expr>),
+}
+
+impl Comp {
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        match *self {
+            Comp::Atom(ref atom) => atom.to_doc(),
+            Comp::FunIntro(ref param, ref body) => Doc::nil()
+                .append("\\")
+                .append(param.to_doc())
+                .append(" =>")
+                .append(
+                    Doc::newline()
+                        .append(body.to_doc())
+                        .nest(PRETTY_INDENT_WIDTH),
+                ),
+            Comp::FunApp(ref head, ref arg) => Doc::nil()
+                .append(head.to_doc_atomic())
+                .append(" ")
+                .append(arg.to_doc_atomic()),
+            Comp::RecordIntro(ref fields) => fields_to_doc(
+                "record",
+                fields
+                    .iter()
+                    .map(|&(ref label, ref atom)| (label, atom.to_doc())),
+            ),
+            Comp::RecordProj(ref atom, ref label) => Doc::nil()
+                .append(atom.to_doc_atomic())
+                .append(".")
+                .append(Doc::as_string(label)),
+            Comp::RecordExtend(ref atom, ref fields) => Doc::nil()
+                .append(atom.to_doc_atomic())
+                .append(" ")
+                .append(fields_to_doc(
+                    "with",
+                    fields
+                        .iter()
+                        .map(|&(ref label, ref atom)| (label, atom.to_doc())),
+                )),
+            Comp::VariantIntro(ref label, ref atom) => Doc::nil()
+                .append("variant ")
+                .append(Doc::as_string(label))
+                .append(" ")
+                .append(atom.to_doc_atomic()),
+            Comp::ArrayIntro(ref atoms) => Doc::nil()
+                .append("[")
+                .append(Doc::intersperse(
+                    atoms.iter().map(Atom::to_doc),
+                    Doc::text("; "),
+                ))
+                .append("]"),
+            Comp::Case(ref head, ref clauses) => Doc::nil()
+                .append("case ")
+                .append(head.to_doc_atomic())
+                .append(" {")
+                .append(
+                    Doc::concat(clauses.iter().map(|&(ref pattern, ref body)| {
+                        Doc::newline()
+                            .append(pattern.to_doc())
+                            .append(" =>")
+                            .append(
+                                Doc::newline()
+                                    .append(body.to_doc())
+                                    .nest(PRETTY_INDENT_WIDTH),
+                            )
+                            .append(",")
+                    }))
+                    .nest(PRETTY_INDENT_WIDTH),
+                )
+                .append(Doc::newline())
+                .append("}"),
+            Comp::Fix(ref var, ref body) => Doc::nil()
+                .append("fix ")
+                .append(var.to_doc())
+                .append(" =>")
+                .append(
+                    Doc::newline()
+                        .append(body.to_doc())
+                        .nest(PRETTY_INDENT_WIDTH),
+                ),
+        }
+    }
+}
+
+impl fmt::Display for Comp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
+/// A sequence of computations, each bound to a variable, followed by the
+/// computation that produces the result of the expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub bindings: Vec<(Var, Comp)>,
+    pub body: Comp,
+}
+
+impl Expr {
+    pub fn new(bindings: Vec<(Var, Comp)>, body: Comp) -> Expr {
+        Expr { bindings, body }
+    }
+
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        Doc::concat(self.bindings.iter().map(|&(ref var, ref comp)| {
+            Doc::nil()
+                .append("let ")
+                .append(var.to_doc())
+                .append(" = ")
+                .append(comp.to_doc())
+                .append(";")
+                .append(Doc::newline())
+        }))
+        .append(self.body.to_doc())
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
+fn fields_to_doc<'a>(
+    keyword: &'static str,
+    fields: impl Iterator<Item = (&'a Label, Doc<'a, BoxDoc<'a, ()>>)>,
+) -> Doc<'a, BoxDoc<'a, ()>> {
+    let fields = fields.collect::<Vec<_>>();
+
+    if fields.is_empty() {
+        return Doc::text(keyword).append(" {}");
+    }
+
+    Doc::nil()
+        .append(keyword)
+        .append(" { ")
+        .append(Doc::intersperse(
+            fields.into_iter().map(|(label, doc)| {
+                Doc::nil()
+                    .append(Doc::as_string(label))
+                    .append(" = ")
+                    .append(doc)
+            }),
+            Doc::text("; "),
+        ))
+        .append(" }")
+}
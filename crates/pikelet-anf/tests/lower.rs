@@ -0,0 +1,112 @@
+use pikelet_anf::lower::{self, LowerError};
+use pikelet_concrete::elaborate::Context;
+use pikelet_core::syntax::core::{RcTerm, Term};
+use pikelet_core::syntax::MetaVar;
+use pikelet_test_support::{parse_infer_term, write_golden};
+
+fn golden(filename: &str, literal: &str) {
+    let context = Context::default();
+
+    let term = parse_infer_term(&context, literal);
+    let expr = match lower::lower_term(&term) {
+        Ok(expr) => expr,
+        Err(error) => panic!("lower error: {}", error),
+    };
+
+    write_golden(filename, &expr);
+}
+
+#[test]
+fn id() {
+    golden("id", r"\(0 a : Type) (x : a) => x");
+}
+
+#[test]
+fn compose() {
+    golden(
+        "compose",
+        r"\(0 a b c : Type) (f : b -> c) (g : a -> b) (x : a) => f (g x)",
+    );
+}
+
+#[test]
+fn fun_app_erased() {
+    golden(
+        "fun_app_erased",
+        r#"(\(0 a : Type) (x : a) => x) String "hello""#,
+    );
+}
+
+#[test]
+fn prim_app() {
+    golden(
+        "prim_app",
+        r#"
+            let
+                add = import "prim/u64/add";
+            in
+                add 1 (add 2 3)
+        "#,
+    );
+}
+
+#[test]
+fn let_named() {
+    golden(
+        "let_named",
+        r#"
+            let
+                add = import "prim/u64/add";
+                x = add 1 2;
+            in
+                add x x
+        "#,
+    );
+}
+
+#[test]
+fn record() {
+    golden(
+        "record",
+        r#"
+            \(r : Record { x : U64; y : U64 }) =>
+                record { a = r.x; b = (import "prim/u64/add") r.x r.y }
+        "#,
+    );
+}
+
+#[test]
+fn case_literal() {
+    golden(
+        "case_literal",
+        r#"
+            \(x : U64) => case x {
+                0 => "zero";
+                _ => "other";
+            }
+        "#,
+    );
+}
+
+#[test]
+fn case_variant() {
+    golden(
+        "case_variant",
+        r#"
+            \(x : Variant { some : String; none : Record {} }) => case x {
+                variant some s => s;
+                variant none _ => "nothing";
+            }
+        "#,
+    );
+}
+
+#[test]
+fn meta_unsolved() {
+    let term = RcTerm::from(Term::Meta(MetaVar(0)));
+
+    assert_eq!(
+        lower::lower_term(&term),
+        Err(LowerError::new("unsolved metavariable `?0`")),
+    );
+}
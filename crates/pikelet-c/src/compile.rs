@@ -0,0 +1,395 @@
+//! Compilation of first-order programs to C source code
+//!
+//! Each function of the program is compiled to a static C function, and for
+//! each export a function is defined that calls the corresponding function.
+//! The exported functions are prefixed with the name of the module, so the
+//! export `is-even` of the module `parity` becomes `parity_is_even`. The
+//! local variables of the functions are named after their indices, like
+//! `v0`, with their parameters coming first.
+//!
+//! The generated code includes `pikelet.h`, which can be found in
+//! `RUNTIME_HEADER`.
+
+use std::fmt::Write;
+use std::mem;
+
+use pikelet_core::nbe::Env;
+use pikelet_core::syntax::core::RcTerm;
+use pikelet_core::syntax::{Label, Literal};
+use pikelet_mono::lower::LowerError;
+use pikelet_mono::syntax::{Expr, Function, FunctionId, Program, Repr};
+
+use crate::repr;
+
+/// The runtime header that is included by the generated code
+pub const RUNTIME_HEADER: &str = include_str!("pikelet.h");
+
+/// An error produced while compiling a module
+#[derive(Debug, Clone, PartialEq, failure::Fail)]
+#[fail(display = "{}", message)]
+pub struct CompileError {
+    pub message: String,
+}
+
+impl CompileError {
+    pub fn new(message: impl Into<String>) -> CompileError {
+        CompileError {
+            message: message.into(),
+        }
+    }
+}
+
+impl From<LowerError> for CompileError {
+    fn from(src: LowerError) -> CompileError {
+        CompileError::new(src.message)
+    }
+}
+
+/// Compile an elaborated file to C source code, going by way of the
+/// first-order representation, and using the given name as a prefix for the
+/// names in the generated code
+///
+/// The name should be a valid C identifier.
+pub fn compile_module(env: &dyn Env, term: &RcTerm, name: &str) -> Result<String, CompileError> {
+    let program = pikelet_mono::lower::lower_module(env, term)?;
+    compile_program(&program, name)
+}
+
+/// Compile a first-order program to C source code, using the given name as a
+/// prefix for the names in the generated code
+///
+/// The name should be a valid C identifier.
+pub fn compile_program(program: &Program, name: &str) -> Result<String, CompileError> {
+    let mut compiler = Compiler {
+        name,
+        structs: Vec::new(),
+    };
+
+    // Work out the prototypes first, so that all of the structs are known
+    // before the bodies refer to them
+    let mut prototypes = Vec::with_capacity(program.functions.len());
+    for (index, function) in program.functions.iter().enumerate() {
+        let name = compiler.function_name(FunctionId(index));
+        prototypes.push(compiler.prototype(&name, function)?);
+    }
+
+    let mut bodies = Vec::with_capacity(program.functions.len());
+    for function in &program.functions {
+        let mut state = FunctionState {
+            local_count: function.locals.len(),
+            statements: Vec::new(),
+        };
+        let expr = compiler.compile_expr(&mut state, &function.body)?;
+        state.emit(format!("return {};", expr));
+        bodies.push(state.statements);
+    }
+
+    let mut exports = Vec::with_capacity(program.exports.len());
+    let mut export_names = Vec::<String>::with_capacity(program.exports.len());
+    for &(ref label, id) in &program.exports {
+        let function = program.function(id);
+        let name = format!("{}_{}", compiler.name, repr::mangle(label));
+        if export_names.contains(&name) {
+            return Err(CompileError::new(format!(
+                "the name of the export `{}` clashes with another export in C",
+                label,
+            )));
+        }
+        let args = (0..function.param_count).map(|index| format!("v{}", index));
+        let call = format!(
+            "{}({})",
+            compiler.function_name(id),
+            args.collect::<Vec<_>>().join(", ")
+        );
+        exports.push((compiler.prototype(&name, function)?, call));
+        export_names.push(name);
+    }
+
+    let mut source = String::new();
+    writeln!(source, "/* Generated by Pikelet */").unwrap();
+    writeln!(source).unwrap();
+    writeln!(source, "#include \"pikelet.h\"").unwrap();
+
+    for &(_, ref definition) in &compiler.structs {
+        writeln!(source).unwrap();
+        writeln!(source, "{}", definition).unwrap();
+    }
+
+    writeln!(source).unwrap();
+    for prototype in &prototypes {
+        writeln!(source, "static {};", prototype).unwrap();
+    }
+
+    for (prototype, body) in prototypes.iter().zip(&bodies) {
+        writeln!(source).unwrap();
+        writeln!(source, "static {} {{", prototype).unwrap();
+        for statement in body {
+            writeln!(source, "    {}", statement).unwrap();
+        }
+        writeln!(source, "}}").unwrap();
+    }
+
+    for (prototype, call) in exports {
+        writeln!(source).unwrap();
+        writeln!(source, "{} {{", prototype).unwrap();
+        writeln!(source, "    return {};", call).unwrap();
+        writeln!(source, "}}").unwrap();
+    }
+
+    Ok(source)
+}
+
+/// The state of the function that is currently being compiled
+struct FunctionState {
+    /// The number of local variables, including the ones that were added for
+    /// the results of case expressions
+    local_count: usize,
+    statements: Vec<String>,
+}
+
+impl FunctionState {
+    fn emit(&mut self, statement: String) {
+        self.statements.push(statement);
+    }
+
+    fn new_local(&mut self) -> String {
+        let name = format!("v{}", self.local_count);
+        self.local_count += 1;
+        name
+    }
+
+    /// Run the compilation in a separate buffer of statements, returning
+    /// the statements that were emitted
+    fn buffered<T>(
+        &mut self,
+        f: impl FnOnce(&mut FunctionState) -> Result<T, CompileError>,
+    ) -> Result<(T, Vec<String>), CompileError> {
+        let outer = mem::replace(&mut self.statements, Vec::new());
+        let result = f(self);
+        let inner = mem::replace(&mut self.statements, outer);
+        Ok((result?, inner))
+    }
+
+    /// Emit a block of statements, indented by one level
+    fn emit_block(&mut self, statements: Vec<String>) {
+        self.statements.extend(
+            statements
+                .into_iter()
+                .map(|statement| format!("    {}", statement)),
+        );
+    }
+}
+
+struct Compiler<'name> {
+    name: &'name str,
+    /// The structs that have been used for records so far, along with their
+    /// definitions
+    structs: Vec<(Vec<(Label, Repr)>, String)>,
+}
+
+impl<'name> Compiler<'name> {
+    fn function_name(&self, id: FunctionId) -> String {
+        format!("{}_fn{}", self.name, id.0)
+    }
+
+    /// Compile an expression, returning a C expression for its value
+    fn compile_expr(
+        &mut self,
+        state: &mut FunctionState,
+        expr: &Expr,
+    ) -> Result<String, CompileError> {
+        match *expr {
+            Expr::Literal(ref literal) => compile_literal(literal),
+            Expr::Local(local, _) => Ok(local.to_string()),
+            Expr::Call(id, ref args, _) => {
+                let mut arg_exprs = Vec::with_capacity(args.len());
+                for arg in args {
+                    arg_exprs.push(self.compile_expr(state, arg)?);
+                }
+                Ok(format!(
+                    "{}({})",
+                    self.function_name(id),
+                    arg_exprs.join(", ")
+                ))
+            },
+            Expr::Prim(op, ref repr, ref args) => {
+                let ty = repr.prim_name().ok_or_else(|| {
+                    CompileError::new(format!("unsupported primitive on `{}`", repr))
+                })?;
+
+                let mut arg_exprs = Vec::with_capacity(args.len());
+                for arg in args {
+                    arg_exprs.push(self.compile_expr(state, arg)?);
+                }
+                Ok(format!("pk_{}_{}({})", ty, op.name(), arg_exprs.join(", ")))
+            },
+            Expr::RecordIntro(ref fields) => {
+                let mut field_exprs = Vec::with_capacity(fields.len());
+                for &(_, ref field) in fields {
+                    field_exprs.push(self.compile_expr(state, field)?);
+                }
+
+                let ty = self.c_type(&expr.repr())?;
+                if field_exprs.is_empty() {
+                    Ok(format!("(({}){{ 0 }})", ty))
+                } else {
+                    Ok(format!("(({}){{ {} }})", ty, field_exprs.join(", ")))
+                }
+            },
+            Expr::RecordProj(ref expr, ref label, _) => {
+                let expr = self.compile_expr(state, expr)?;
+                Ok(format!("{}.{}", expr, repr::mangle(label)))
+            },
+            Expr::Let(local, ref expr, ref body) => {
+                let ty = self.c_type(&expr.repr())?;
+                let expr = self.compile_expr(state, expr)?;
+                state.emit(format!("{} {} = {};", ty, local, expr));
+                self.compile_expr(state, body)
+            },
+            Expr::Case(local, ref clauses, ref default, ref repr) => {
+                self.compile_case(state, &local.to_string(), clauses, default, repr)
+            },
+        }
+    }
+
+    /// Compile a case expression on literals to a chain of `if`s that assign
+    /// to a local variable
+    fn compile_case(
+        &mut self,
+        state: &mut FunctionState,
+        head: &str,
+        clauses: &[(Literal, Expr)],
+        default: &Option<Box<Expr>>,
+        repr: &Repr,
+    ) -> Result<String, CompileError> {
+        let result_local = state.new_local();
+        let result_ty = self.c_type(repr)?;
+        state.emit(format!("{} {};", result_ty, result_local));
+
+        let mut branches = Vec::with_capacity(clauses.len() + 1);
+        for &(ref literal, ref body) in clauses {
+            let test = format!("{} == {}", head, compile_literal(literal)?);
+            let (expr, mut statements) = state.buffered(|state| self.compile_expr(state, body))?;
+            statements.push(format!("{} = {};", result_local, expr));
+            branches.push((Some(test), statements));
+        }
+        if let Some(ref body) = *default {
+            let (expr, mut statements) = state.buffered(|state| self.compile_expr(state, body))?;
+            statements.push(format!("{} = {};", result_local, expr));
+            branches.push((None, statements));
+        }
+
+        for (i, (test, statements)) in branches.into_iter().enumerate() {
+            match test {
+                Some(ref test) if i == 0 => state.emit(format!("if ({}) {{", test)),
+                Some(ref test) => state.emit(format!("}} else if ({}) {{", test)),
+                None if i == 0 => state.emit("{".to_owned()),
+                None => state.emit("} else {".to_owned()),
+            }
+            state.emit_block(statements);
+        }
+        if default.is_none() {
+            state.emit("} else {".to_owned());
+            state.emit_block(vec!["pk_unreachable();".to_owned()]);
+        }
+        state.emit("}".to_owned());
+
+        Ok(result_local)
+    }
+
+    /// Get the C type of a representation, defining a struct for it if it
+    /// is a record that hasn't been seen before
+    fn c_type(&mut self, repr: &Repr) -> Result<String, CompileError> {
+        let fields = match *repr {
+            Repr::Record(ref fields) => fields,
+            ref repr => return Ok(repr::scalar_type(repr).unwrap().to_owned()),
+        };
+
+        if let Some(index) = self
+            .structs
+            .iter()
+            .position(|&(ref other, _)| other == fields)
+        {
+            return Ok(self.struct_name(index));
+        }
+
+        let mut definition = "typedef struct {\n".to_owned();
+        let mut names = Vec::with_capacity(fields.len());
+        for &(ref label, ref repr) in fields {
+            let name = repr::mangle(label);
+            if names.contains(&name) {
+                return Err(CompileError::new(format!(
+                    "the name of the field `{}` clashes with another field in C",
+                    label,
+                )));
+            }
+            writeln!(definition, "    {} {};", self.c_type(repr)?, name).unwrap();
+            names.push(name);
+        }
+        if fields.is_empty() {
+            // Empty structs are not allowed in C
+            definition.push_str("    char empty;\n");
+        }
+
+        let index = self.structs.len();
+        write!(definition, "}} {};", self.struct_name(index)).unwrap();
+        self.structs.push((fields.clone(), definition));
+
+        Ok(self.struct_name(index))
+    }
+
+    fn struct_name(&self, index: usize) -> String {
+        format!("{}_record{}", self.name, index)
+    }
+
+    fn prototype(&mut self, name: &str, function: &Function) -> Result<String, CompileError> {
+        let mut param_decls = Vec::with_capacity(function.param_count);
+        for (index, repr) in function.params().iter().enumerate() {
+            param_decls.push(format!("{} v{}", self.c_type(repr)?, index));
+        }
+        if param_decls.is_empty() {
+            param_decls.push("void".to_owned());
+        }
+
+        Ok(format!(
+            "{} {}({})",
+            self.c_type(&function.result)?,
+            name,
+            param_decls.join(", ")
+        ))
+    }
+}
+
+fn compile_literal(literal: &Literal) -> Result<String, CompileError> {
+    Ok(match *literal {
+        Literal::Bool(value) => value.to_string(),
+        Literal::Char(value) => format!("((pk_char){})", value as u32),
+        Literal::U8(value) => format!("((uint8_t){})", value),
+        Literal::U16(value) => format!("((uint16_t){})", value),
+        Literal::U32(value) => format!("UINT32_C({})", value),
+        Literal::U64(value) => format!("UINT64_C({})", value),
+        Literal::S8(value) => format!("((int8_t){})", value),
+        Literal::S16(value) => format!("((int16_t){})", value),
+        Literal::S32(std::i32::MIN) => "INT32_MIN".to_owned(),
+        Literal::S32(value) => format!("INT32_C({})", value),
+        Literal::S64(std::i64::MIN) => "INT64_MIN".to_owned(),
+        Literal::S64(value) => format!("INT64_C({})", value),
+        Literal::F32(value) => compile_float(f64::from(value), "f"),
+        Literal::F64(value) => compile_float(value, ""),
+        Literal::String(_) => return Err(CompileError::new("strings are not yet supported")),
+    })
+}
+
+fn compile_float(value: f64, suffix: &str) -> String {
+    if value.is_nan() {
+        "NAN".to_owned()
+    } else if value.is_infinite() && value > 0.0 {
+        "INFINITY".to_owned()
+    } else if value.is_infinite() {
+        "(-INFINITY)".to_owned()
+    } else {
+        // The debug representation is the shortest one that round trips,
+        // always including a decimal point or an exponent
+        format!("({:?}{})", value, suffix)
+    }
+}
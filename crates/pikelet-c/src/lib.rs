@@ -0,0 +1,10 @@
+//! A C backend for Pikelet
+//!
+//! This compiles files to self-contained C source files by way of the
+//! first-order representation in `pikelet_mono`, allowing Pikelet code to be
+//! used in places where Rust is not available. The generated code only
+//! depends on the C99 standard library, and on a small runtime header that is
+//! provided by this crate.
+
+pub mod compile;
+pub mod repr;
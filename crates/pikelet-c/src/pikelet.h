@@ -0,0 +1,106 @@
+/*
+ * Runtime support for C code generated by Pikelet
+ *
+ * Primitive operations are defined as functions named after the primitive
+ * imports that they implement, so `prim/u8/add` becomes `pk_u8_add`. Integer
+ * arithmetic wraps on overflow, which is done using unsigned arithmetic to
+ * avoid undefined behaviour. Dividing the smallest signed integer by -1 wraps
+ * around to the smallest integer, and dividing by zero aborts the program.
+ */
+
+#ifndef PIKELET_H
+#define PIKELET_H
+
+#include <math.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef uint32_t pk_char;
+
+/* Called when none of the clauses of a case expression match */
+static inline void pk_unreachable(void) {
+    abort();
+}
+
+/* Called when an integer is divided by zero */
+static inline void pk_divide_by_zero(void) {
+    abort();
+}
+
+#define PK_COMPARISONS(name, type) \
+    static inline bool pk_##name##_eq(type x, type y) { return x == y; } \
+    static inline bool pk_##name##_ne(type x, type y) { return x != y; } \
+    static inline bool pk_##name##_lt(type x, type y) { return x < y; } \
+    static inline bool pk_##name##_le(type x, type y) { return x <= y; } \
+    static inline bool pk_##name##_gt(type x, type y) { return x > y; } \
+    static inline bool pk_##name##_ge(type x, type y) { return x >= y; }
+
+/* Arithmetic on integers, done in an unsigned type that is not promoted */
+#define PK_INT_ARITHMETIC(name, type, wide) \
+    static inline type pk_##name##_add(type x, type y) { return (type)((wide)x + (wide)y); } \
+    static inline type pk_##name##_sub(type x, type y) { return (type)((wide)x - (wide)y); } \
+    static inline type pk_##name##_mul(type x, type y) { return (type)((wide)x * (wide)y); }
+
+#define PK_UNSIGNED_DIV(name, type) \
+    static inline type pk_##name##_div(type x, type y) { \
+        if (y == 0) pk_divide_by_zero(); \
+        return (type)(x / y); \
+    }
+
+/* Division of the smallest integer by -1 is handled separately, because the
+ * result would overflow */
+#define PK_SIGNED_DIV(name, type, min) \
+    static inline type pk_##name##_div(type x, type y) { \
+        if (y == 0) pk_divide_by_zero(); \
+        if (x == min && y == -1) return min; \
+        return (type)(x / y); \
+    }
+
+#define PK_FLOAT_ARITHMETIC(name, type) \
+    static inline type pk_##name##_add(type x, type y) { return x + y; } \
+    static inline type pk_##name##_sub(type x, type y) { return x - y; } \
+    static inline type pk_##name##_mul(type x, type y) { return x * y; } \
+    static inline type pk_##name##_div(type x, type y) { return x / y; }
+
+PK_COMPARISONS(bool, bool)
+PK_COMPARISONS(char, pk_char)
+
+PK_COMPARISONS(u8, uint8_t)
+PK_COMPARISONS(u16, uint16_t)
+PK_COMPARISONS(u32, uint32_t)
+PK_COMPARISONS(u64, uint64_t)
+PK_COMPARISONS(i8, int8_t)
+PK_COMPARISONS(i16, int16_t)
+PK_COMPARISONS(i32, int32_t)
+PK_COMPARISONS(i64, int64_t)
+PK_COMPARISONS(f32, float)
+PK_COMPARISONS(f64, double)
+
+PK_INT_ARITHMETIC(u8, uint8_t, uint32_t)
+PK_INT_ARITHMETIC(u16, uint16_t, uint32_t)
+PK_INT_ARITHMETIC(u32, uint32_t, uint32_t)
+PK_INT_ARITHMETIC(u64, uint64_t, uint64_t)
+PK_INT_ARITHMETIC(i8, int8_t, uint32_t)
+PK_INT_ARITHMETIC(i16, int16_t, uint32_t)
+PK_INT_ARITHMETIC(i32, int32_t, uint32_t)
+PK_INT_ARITHMETIC(i64, int64_t, uint64_t)
+PK_FLOAT_ARITHMETIC(f32, float)
+PK_FLOAT_ARITHMETIC(f64, double)
+
+PK_UNSIGNED_DIV(u8, uint8_t)
+PK_UNSIGNED_DIV(u16, uint16_t)
+PK_UNSIGNED_DIV(u32, uint32_t)
+PK_UNSIGNED_DIV(u64, uint64_t)
+PK_SIGNED_DIV(i8, int8_t, INT8_MIN)
+PK_SIGNED_DIV(i16, int16_t, INT16_MIN)
+PK_SIGNED_DIV(i32, int32_t, INT32_MIN)
+PK_SIGNED_DIV(i64, int64_t, INT64_MIN)
+
+#undef PK_COMPARISONS
+#undef PK_INT_ARITHMETIC
+#undef PK_UNSIGNED_DIV
+#undef PK_SIGNED_DIV
+#undef PK_FLOAT_ARITHMETIC
+
+#endif /* PIKELET_H */
@@ -0,0 +1,56 @@
+//! The representations of runtime values in C
+//!
+//! Booleans, characters, integers and floating point numbers are represented
+//! using the fixed-width types from `stdint.h`, and records are represented
+//! as structs that are passed around by value. As records are only ever
+//! compatible with records that have the same fields, a struct can be
+//! picked for each record type.
+
+use pikelet_core::syntax::Label;
+use pikelet_mono::syntax::Repr;
+
+/// The C type that is used for scalar values, or `None` for records
+pub fn scalar_type(repr: &Repr) -> Option<&'static str> {
+    match *repr {
+        Repr::Bool => Some("bool"),
+        Repr::Char => Some("pk_char"),
+        Repr::U8 => Some("uint8_t"),
+        Repr::U16 => Some("uint16_t"),
+        Repr::U32 => Some("uint32_t"),
+        Repr::U64 => Some("uint64_t"),
+        Repr::S8 => Some("int8_t"),
+        Repr::S16 => Some("int16_t"),
+        Repr::S32 => Some("int32_t"),
+        Repr::S64 => Some("int64_t"),
+        Repr::F32 => Some("float"),
+        Repr::F64 => Some("double"),
+        Repr::Record(_) => None,
+    }
+}
+
+/// Convert a label to a C identifier, for use as the name of a field or a
+/// function
+///
+/// Characters that are not allowed in identifiers are replaced with
+/// underscores, so `is-even` becomes `is_even`, and keywords have an
+/// underscore appended to them.
+pub fn mangle(label: &Label) -> String {
+    let mut name = label
+        .0
+        .chars()
+        .map(|ch| if ch.is_ascii_alphanumeric() { ch } else { '_' })
+        .collect::<String>();
+
+    if KEYWORDS.contains(&name.as_str()) {
+        name.push('_');
+    }
+
+    name
+}
+
+const KEYWORDS: &[&str] = &[
+    "auto", "bool", "break", "case", "char", "const", "continue", "default", "do", "double",
+    "else", "enum", "extern", "false", "float", "for", "goto", "if", "inline", "int", "long",
+    "register", "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch",
+    "true", "typedef", "union", "unsigned", "void", "volatile", "while",
+];
@@ -0,0 +1,321 @@
+use pretty_assertions::assert_eq;
+use std::fmt::Write;
+use std::process::{Command, Output};
+use std::{env, fs, process};
+
+use pikelet_c::compile::{self, CompileError, RUNTIME_HEADER};
+use pikelet_c::repr;
+use pikelet_concrete::elaborate::Context;
+use pikelet_core::syntax::{Label, Literal};
+use pikelet_mono::lower;
+use pikelet_mono::syntax::Repr;
+use pikelet_test_support::{parse_infer_term, Observation};
+
+/// Check that the values of the fields of the file, when compiled to C and
+/// run, are the same as the ones found by the evaluator
+///
+/// Exported functions are only checked for being functions.
+fn assert_agrees(name: &str, src: &str) {
+    pikelet_test_support::assert_agrees(src, |context, term| {
+        let program = match lower::lower_module(context, term) {
+            Ok(program) => program,
+            Err(error) => panic!("lower error: {}", error),
+        };
+        let source = match compile::compile_program(&program, "test") {
+            Ok(source) => source,
+            Err(error) => panic!("compile error: {}", error),
+        };
+
+        let mut leaves = Vec::new();
+        for &(ref label, id) in &program.exports {
+            let function = program.function(id);
+            if function.param_count == 0 {
+                let expr = format!("test_{}()", repr::mangle(label));
+                collect_leaves(expr, &function.result, &mut leaves);
+            }
+        }
+
+        let run = run_c(name, &source, &leaves);
+        assert!(run.status.success(), "program failed: {}", run.status);
+        let output = String::from_utf8(run.stdout).unwrap();
+        let mut bits = output.lines().map(|line| line.parse::<u64>().unwrap());
+
+        Observation::record(program.exports.iter().map(|&(ref label, id)| {
+            let function = program.function(id);
+            let observation = match function.param_count {
+                0 => observe(&function.result, &mut bits),
+                _ => Observation::Function,
+            };
+            (label.clone(), observation)
+        }))
+    });
+}
+
+/// Collect the C expressions for the scalars in a value of the given
+/// representation
+fn collect_leaves(expr: String, repr: &Repr, leaves: &mut Vec<(String, Repr)>) {
+    match *repr {
+        Repr::Record(ref fields) => {
+            for &(ref label, ref repr) in fields {
+                let expr = format!("{}.{}", expr, repr::mangle(label));
+                collect_leaves(expr, repr, leaves);
+            }
+        },
+        _ => leaves.push((expr, repr.clone())),
+    }
+}
+
+/// Read back a value of the given representation from the bits that were
+/// printed for its scalars, in the order that `collect_leaves` found them
+fn observe(repr: &Repr, bits: &mut impl Iterator<Item = u64>) -> Observation {
+    let mut next = || bits.next().expect("missing output");
+
+    let literal = match *repr {
+        Repr::Bool => Literal::Bool(next() != 0),
+        Repr::Char => Literal::Char(std::char::from_u32(next() as u32).expect("invalid char")),
+        Repr::U8 => Literal::U8(next() as u8),
+        Repr::U16 => Literal::U16(next() as u16),
+        Repr::U32 => Literal::U32(next() as u32),
+        Repr::U64 => Literal::U64(next()),
+        Repr::S8 => Literal::S8(next() as i8),
+        Repr::S16 => Literal::S16(next() as i16),
+        Repr::S32 => Literal::S32(next() as i32),
+        Repr::S64 => Literal::S64(next() as i64),
+        Repr::F32 => Literal::F32(f32::from_bits(next() as u32)),
+        Repr::F64 => Literal::F64(f64::from_bits(next())),
+        Repr::Record(ref fields) => {
+            return Observation::record(
+                fields
+                    .iter()
+                    .map(|&(ref label, ref repr)| (label.clone(), observe(repr, bits))),
+            );
+        },
+    };
+
+    Observation::Literal(literal)
+}
+
+/// Compile the generated code along with a main function that prints the
+/// bits of the given expressions, returning the output of running it
+fn run_c(name: &str, source: &str, leaves: &[(String, Repr)]) -> Output {
+    let dir = env::temp_dir().join(format!("pikelet-c-{}-{}", name, process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let mut main = source.to_owned();
+    writeln!(main).unwrap();
+    writeln!(main, "#include <stdio.h>").unwrap();
+    writeln!(main, "#include <string.h>").unwrap();
+    writeln!(main).unwrap();
+    writeln!(main, "int main(void) {{").unwrap();
+    writeln!(main, "    uint32_t bits32;").unwrap();
+    writeln!(main, "    uint64_t bits64;").unwrap();
+    writeln!(main, "    float value32;").unwrap();
+    writeln!(main, "    double value64;").unwrap();
+    for &(ref expr, ref repr) in leaves {
+        match *repr {
+            Repr::F32 => {
+                writeln!(main, "    value32 = {};", expr).unwrap();
+                writeln!(main, "    memcpy(&bits32, &value32, sizeof bits32);").unwrap();
+                writeln!(main, "    printf(\"%llu\\n\", (unsigned long long)bits32);").unwrap();
+            },
+            Repr::F64 => {
+                writeln!(main, "    value64 = {};", expr).unwrap();
+                writeln!(main, "    memcpy(&bits64, &value64, sizeof bits64);").unwrap();
+                writeln!(main, "    printf(\"%llu\\n\", (unsigned long long)bits64);").unwrap();
+            },
+            Repr::S8 | Repr::S16 | Repr::S32 | Repr::S64 => {
+                let value = format!("(unsigned long long)(long long)({})", expr);
+                writeln!(main, "    printf(\"%llu\\n\", {});", value).unwrap();
+            },
+            _ => {
+                let value = format!("(unsigned long long)({})", expr);
+                writeln!(main, "    printf(\"%llu\\n\", {});", value).unwrap();
+            },
+        }
+    }
+    writeln!(main, "    return 0;").unwrap();
+    writeln!(main, "}}").unwrap();
+
+    fs::write(dir.join("pikelet.h"), RUNTIME_HEADER).unwrap();
+    fs::write(dir.join("main.c"), main).unwrap();
+
+    let cc = Command::new("cc")
+        .current_dir(&dir)
+        .args(&["-std=c99", "-pedantic", "-o", "main", "main.c"])
+        .output()
+        .expect("failed to run the C compiler");
+    if !cc.status.success() {
+        panic!("C compile error:\n{}", String::from_utf8_lossy(&cc.stderr));
+    }
+
+    let run = Command::new(dir.join("main")).output().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    run
+}
+
+#[test]
+fn mangle() {
+    assert_eq!(repr::mangle(&Label("is-even".to_owned())), "is_even");
+    assert_eq!(repr::mangle(&Label("int".to_owned())), "int_");
+}
+
+#[test]
+fn exported_prototypes() {
+    let context = Context::default();
+    let term = parse_infer_term(
+        &context,
+        r#"
+            let
+                Point = Record { x : F64; y : F64 };
+
+                scale : F64 -> Point -> Point;
+                scale k p = record {
+                    x = (import "prim/f64/mul") k p.x;
+                    y = (import "prim/f64/mul") k p.y;
+                };
+            in
+                record { Point; scale }
+        "#,
+    );
+    let source = compile::compile_module(&context, &term, "geometry").unwrap();
+
+    assert!(source.contains("typedef struct {\n    double x;\n    double y;\n} geometry_record0;"));
+    assert!(source.contains("geometry_record0 geometry_scale(double v0, geometry_record0 v1) {"));
+}
+
+#[test]
+fn export_string() {
+    let context = Context::default();
+    let term = parse_infer_term(&context, r#"record { greeting = "hello" }"#);
+
+    assert_eq!(
+        compile::compile_module(&context, &term, "test"),
+        Err(CompileError::new(
+            "could not export `greeting`: strings are not yet supported"
+        )),
+    );
+}
+
+#[test]
+fn agrees_arithmetic() {
+    assert_agrees(
+        "arithmetic",
+        r#"
+            record {
+                add-u8 = (import "prim/u8/add") 200 100 : U8;
+                sub-s8 = (import "prim/i8/sub") ((import "prim/i8/sub") 0 100) 100 : S8;
+                mul-u16 = (import "prim/u16/mul") 65535 65535 : U16;
+                div-s8 = (import "prim/i8/div") ((import "prim/i8/sub") ((import "prim/i8/sub") 0 127) 1) ((import "prim/i8/sub") 0 1) : S8;
+                div-s32 = (import "prim/i32/div") ((import "prim/i32/sub") 1 8) 2 : S32;
+                mul-u64 = (import "prim/u64/mul") 4294967296 3 : U64;
+                sub-s64 = (import "prim/i64/sub") 1 2 : S64;
+                div-f32 = (import "prim/f32/div") 1.0 3.0 : F32;
+                sub-f64 = (import "prim/f64/sub") 0.1 0.3 : F64;
+                lt-f64 = (import "prim/f64/lt") 0.1 0.3;
+                eq-char = (import "prim/char/eq") 'a' 'b';
+            }
+        "#,
+    );
+}
+
+#[test]
+fn divide_by_zero() {
+    let context = Context::default();
+    let term = parse_infer_term(
+        &context,
+        r#"record { quotient = (import "prim/u32/div") 1 0 : U32 }"#,
+    );
+    let source = compile::compile_module(&context, &term, "test").unwrap();
+    let leaves = [("test_quotient()".to_owned(), Repr::U32)];
+
+    assert!(!run_c("divide_by_zero", &source, &leaves).status.success());
+}
+
+#[test]
+fn agrees_case_literal() {
+    assert_agrees(
+        "case_literal",
+        r#"
+            let
+                describe : U32 -> Char;
+                describe n = case n {
+                    0 => 'z';
+                    1 => 'o';
+                    _ => 'm';
+                };
+
+                not : Bool -> Bool;
+                not b = if b then false else true;
+            in
+                record {
+                    describe;
+                    zero = describe 0;
+                    one = describe 1;
+                    many = describe 7;
+                    no = not true;
+                }
+        "#,
+    );
+}
+
+#[test]
+fn agrees_recursive() {
+    assert_agrees(
+        "recursive",
+        r#"
+            let
+                partial fact : U64 -> U64;
+                fact n =
+                    if (import "prim/u64/eq") n 0 then 1 else
+                        (import "prim/u64/mul") n (fact ((import "prim/u64/sub") n 1));
+
+                partial is-even : U64 -> Bool;
+                partial is-odd : U64 -> Bool;
+
+                is-even n =
+                    if (import "prim/u64/eq") n 0 then true else is-odd ((import "prim/u64/sub") n 1);
+                is-odd n =
+                    if (import "prim/u64/eq") n 0 then false else is-even ((import "prim/u64/sub") n 1);
+            in
+                record {
+                    fact-20 = fact 20;
+                    ten-is-even = is-even 10;
+                    seven-is-even = is-even 7;
+                }
+        "#,
+    );
+}
+
+#[test]
+fn agrees_records() {
+    assert_agrees(
+        "records",
+        r#"
+            let
+                Point = Record { x : S32; y : S32 };
+                Line = Record { start : Point; end : Point };
+
+                point : S32 -> S32 -> Point;
+                point x y = record { x = x; y = y };
+
+                swap : Line -> Line;
+                swap line = record { start = line.end; end = line.start };
+
+                length-x : Line -> S32;
+                length-x line =
+                    let diff = (import "prim/i32/sub") line.end.x line.start.x;
+                    in diff;
+            in
+                let
+                    start = point 1 2;
+                    end = point 5 ((import "prim/i32/sub") 0 3);
+                in record {
+                    Point;
+                    swapped = swap (record { start = start; end = end });
+                    length = length-x (record { start = start; end = end });
+                    empty = record {};
+                }
+        "#,
+    );
+}
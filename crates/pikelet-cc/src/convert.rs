@@ -0,0 +1,232 @@
+//! Closure conversion of programs in A-normal form
+//!
+//! Each function is converted into a closure, made up of a pointer to its
+//! code and an environment holding the values of its free variables. The code
+//! is lifted to the top level of the program, and at the start of its body it
+//! projects its free variables back out of the environment that it was called
+//! with.
+//!
+//! Variables are bound at most once in the A-normal form, so the variables
+//! that are projected from the environment can keep their original names, and
+//! the rest of the body can be converted without any renaming.
+//!
+//! Recursive functions refer to themselves through the closure that they are
+//! called with, so the variable bound by a fixed point is not part of the
+//! environment of the function. Fixed points are only supported if they bind
+//! functions.
+
+use pikelet_anf::syntax as anf;
+
+use crate::syntax::{Atom, Code, CodeId, Comp, Expr, Pattern, Program, Var};
+
+/// An error produced during closure conversion
+#[derive(Debug, Clone, PartialEq, failure::Fail)]
+#[fail(display = "{}", message)]
+pub struct ConvertError {
+    pub message: String,
+}
+
+impl ConvertError {
+    pub fn new(message: impl Into<String>) -> ConvertError {
+        ConvertError {
+            message: message.into(),
+        }
+    }
+}
+
+/// Convert a closed expression into a program
+pub fn convert_program(expr: &anf::Expr) -> Result<Program, ConvertError> {
+    let mut convert = Convert { codes: Vec::new() };
+    let main = convert.convert_expr(expr)?;
+
+    Ok(Program {
+        codes: convert.codes,
+        main,
+    })
+}
+
+/// Returns the free variables of a computation, in the order that they are
+/// first used
+pub fn free_vars(comp: &anf::Comp) -> Vec<Var> {
+    let mut free_vars = FreeVars::default();
+    free_vars.comp(comp);
+
+    let FreeVars { used, bound } = free_vars;
+    used.into_iter()
+        .filter(|var| !bound.contains(&var.id))
+        .collect()
+}
+
+/// Collects the variables that are used and bound in a term
+///
+/// As each variable is bound at most once, the free variables are the ones
+/// that are used without being bound.
+#[derive(Default)]
+struct FreeVars {
+    used: Vec<Var>,
+    bound: Vec<usize>,
+}
+
+impl FreeVars {
+    fn atom(&mut self, atom: &Atom) {
+        if let Atom::Var(ref var) = *atom {
+            if self.used.iter().all(|used| used.id != var.id) {
+                self.used.push(var.clone());
+            }
+        }
+    }
+
+    fn atoms<'a>(&mut self, atoms: impl IntoIterator<Item = &'a Atom>) {
+        for atom in atoms {
+            self.atom(atom);
+        }
+    }
+
+    fn bind(&mut self, var: &Var) {
+        self.bound.push(var.id);
+    }
+
+    fn expr(&mut self, expr: &anf::Expr) {
+        for &(ref var, ref comp) in &expr.bindings {
+            self.comp(comp);
+            self.bind(var);
+        }
+        self.comp(&expr.body);
+    }
+
+    fn comp(&mut self, comp: &anf::Comp) {
+        match *comp {
+            anf::Comp::Atom(ref atom) => self.atom(atom),
+            anf::Comp::FunIntro(ref param, ref body) | anf::Comp::Fix(ref param, ref body) => {
+                self.bind(param);
+                self.expr(body);
+            },
+            anf::Comp::FunApp(ref head, ref arg) => {
+                self.atom(head);
+                self.atom(arg);
+            },
+            anf::Comp::RecordIntro(ref fields) => {
+                self.atoms(fields.iter().map(|&(_, ref atom)| atom));
+            },
+            anf::Comp::RecordProj(ref atom, _) | anf::Comp::VariantIntro(_, ref atom) => {
+                self.atom(atom);
+            },
+            anf::Comp::RecordExtend(ref atom, ref fields) => {
+                self.atom(atom);
+                self.atoms(fields.iter().map(|&(_, ref atom)| atom));
+            },
+            anf::Comp::ArrayIntro(ref atoms) => self.atoms(atoms),
+            anf::Comp::Case(ref head, ref clauses) => {
+                self.atom(head);
+                for &(ref pattern, ref body) in clauses {
+                    self.pattern(pattern);
+                    self.expr(body);
+                }
+            },
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match *pattern {
+            Pattern::Binder(ref var) => self.bind(var),
+            Pattern::Atom(ref atom) => self.atom(atom),
+            Pattern::VariantIntro(_, ref pattern) => self.pattern(pattern),
+            Pattern::RecordIntro(ref fields) => {
+                for &(_, ref pattern) in fields {
+                    self.pattern(pattern);
+                }
+            },
+            Pattern::ArrayIntro(ref patterns) => {
+                for pattern in patterns {
+                    self.pattern(pattern);
+                }
+            },
+        }
+    }
+}
+
+/// Keeps track of the code that has been lifted so far
+struct Convert {
+    codes: Vec<Code>,
+}
+
+impl Convert {
+    fn convert_expr(&mut self, expr: &anf::Expr) -> Result<Expr, ConvertError> {
+        let bindings = expr
+            .bindings
+            .iter()
+            .map(|&(ref var, ref comp)| Ok((var.clone(), self.convert_comp(comp)?)))
+            .collect::<Result<_, ConvertError>>()?;
+        let body = self.convert_comp(&expr.body)?;
+
+        Ok(Expr::new(bindings, body))
+    }
+
+    fn convert_comp(&mut self, comp: &anf::Comp) -> Result<Comp, ConvertError> {
+        match *comp {
+            anf::Comp::Atom(ref atom) => Ok(Comp::Atom(atom.clone())),
+            anf::Comp::FunIntro(ref param, ref body) => self.lift(comp, None, param, body),
+            anf::Comp::Fix(ref this, ref body) => match **body {
+                anf::Expr {
+                    ref bindings,
+                    body: anf::Comp::FunIntro(ref param, ref body),
+                } if bindings.is_empty() => self.lift(comp, Some(this), param, body),
+                _ => Err(ConvertError::new(format!(
+                    "fixed point `{}` does not bind a function",
+                    this
+                ))),
+            },
+            anf::Comp::FunApp(ref head, ref arg) => Ok(Comp::FunApp(head.clone(), arg.clone())),
+            anf::Comp::RecordIntro(ref fields) => Ok(Comp::RecordIntro(fields.clone())),
+            anf::Comp::RecordProj(ref atom, ref label) => {
+                Ok(Comp::RecordProj(atom.clone(), label.clone()))
+            },
+            anf::Comp::RecordExtend(ref atom, ref fields) => {
+                Ok(Comp::RecordExtend(atom.clone(), fields.clone()))
+            },
+            anf::Comp::VariantIntro(ref label, ref atom) => {
+                Ok(Comp::VariantIntro(label.clone(), atom.clone()))
+            },
+            anf::Comp::ArrayIntro(ref atoms) => Ok(Comp::ArrayIntro(atoms.clone())),
+            anf::Comp::Case(ref head, ref clauses) => Ok(Comp::Case(
+                head.clone(),
+                clauses
+                    .iter()
+                    .map(|&(ref pattern, ref body)| Ok((pattern.clone(), self.convert_expr(body)?)))
+                    .collect::<Result<_, ConvertError>>()?,
+            )),
+        }
+    }
+
+    /// Lift the code of a function to the top level, returning a closure
+    /// that captures its free variables
+    fn lift(
+        &mut self,
+        comp: &anf::Comp,
+        this: Option<&Var>,
+        param: &Var,
+        body: &anf::Expr,
+    ) -> Result<Comp, ConvertError> {
+        let free_vars = free_vars(comp);
+        let body = self.convert_expr(body)?;
+
+        let mut bindings = free_vars
+            .iter()
+            .enumerate()
+            .map(|(index, var)| (var.clone(), Comp::EnvProj(index)))
+            .collect::<Vec<_>>();
+        bindings.extend(body.bindings);
+
+        let id = CodeId(self.codes.len());
+        self.codes.push(Code {
+            this: this.cloned(),
+            param: param.clone(),
+            body: Expr::new(bindings, body.body),
+        });
+
+        Ok(Comp::ClosureIntro(
+            id,
+            free_vars.into_iter().map(Atom::Var).collect(),
+        ))
+    }
+}
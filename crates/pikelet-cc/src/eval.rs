@@ -0,0 +1,403 @@
+//! An interpreter for closure converted programs
+//!
+//! This is mainly useful for checking that closure conversion preserves the
+//! meaning of programs. Functions are applied by looking up their code in the
+//! program and running it with the environment of the closure that they were
+//! applied through, so any mistakes in the environments that are built during
+//! closure conversion will show up as unbound variables.
+//!
+//! Primitive imports are looked up in a `pikelet_core::nbe::Env`. Globals and
+//! imports that are defined by terms are not supported, as the programs are
+//! expected to be closed.
+
+use pretty::{BoxDoc, Doc};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops;
+use std::rc::Rc;
+
+use pikelet_core::nbe::Env;
+use pikelet_core::syntax::{domain, Import, Label, Literal, PRETTY_FALLBACK_WIDTH};
+
+use crate::syntax::{Atom, CodeId, Comp, Expr, Pattern, Program};
+
+/// An error produced while running a program
+#[derive(Debug, Clone, PartialEq, failure::Fail)]
+#[fail(display = "{}", message)]
+pub struct RuntimeError {
+    pub message: String,
+}
+
+impl RuntimeError {
+    pub fn new(message: impl Into<String>) -> RuntimeError {
+        RuntimeError {
+            message: message.into(),
+        }
+    }
+}
+
+/// Runtime values
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Literals
+    Literal(Literal),
+    /// Closures, made up of a pointer to their code and the values of their
+    /// free variables
+    Closure(CodeId, Vec<RcValue>),
+    /// Primitive imports, along with the arguments that have been applied to
+    /// them so far
+    Prim(String, Vec<RcValue>),
+    /// Records
+    RecordIntro(Vec<(Label, RcValue)>),
+    /// Variants
+    VariantIntro(Label, RcValue),
+    /// Arrays
+    ArrayIntro(Vec<RcValue>),
+    /// Types, and anything else that is not needed at runtime
+    Erased,
+}
+
+impl Value {
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        match *self {
+            Value::VariantIntro(ref label, ref arg) => Doc::nil()
+                .append("variant")
+                .append(Doc::space())
+                .append(Doc::as_string(label))
+                .append(Doc::space())
+                .append(arg.to_doc_atomic()),
+            ref value => value.to_doc_atomic(),
+        }
+    }
+
+    fn to_doc_atomic(&self) -> Doc<BoxDoc<()>> {
+        match *self {
+            Value::Literal(ref literal) => literal.to_doc(),
+            Value::Closure(_, _) | Value::Prim(_, _) => Doc::text("<function>"),
+            Value::RecordIntro(ref fields) => Doc::nil()
+                .append("record {")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    fields.iter().map(|&(ref label, ref value)| {
+                        Doc::nil()
+                            .append(Doc::as_string(label))
+                            .append(Doc::space())
+                            .append("=")
+                            .append(Doc::space())
+                            .append(value.to_doc())
+                    }),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append(Doc::space())
+                .append("}"),
+            Value::ArrayIntro(ref elems) => Doc::nil()
+                .append("[")
+                .append(Doc::intersperse(
+                    elems.iter().map(|elem| elem.to_doc()),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append("]"),
+            Value::Erased => Doc::text("erased"),
+            ref value => Doc::text("(").append(value.to_doc()).append(")"),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
+/// Reference counted runtime values
+#[derive(Debug, Clone, PartialEq)]
+pub struct RcValue {
+    pub inner: Rc<Value>,
+}
+
+impl From<Value> for RcValue {
+    fn from(src: Value) -> RcValue {
+        RcValue {
+            inner: Rc::new(src),
+        }
+    }
+}
+
+impl ops::Deref for RcValue {
+    type Target = Value;
+
+    fn deref(&self) -> &Value {
+        &self.inner
+    }
+}
+
+impl fmt::Display for RcValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.inner, f)
+    }
+}
+
+/// The values of the variables that are in scope in the body of some code,
+/// indexed by their ids
+type Locals = HashMap<usize, RcValue>;
+
+/// Run a closure converted program
+pub fn run_program(env: &dyn Env, program: &Program) -> Result<RcValue, RuntimeError> {
+    Machine { env, program }.eval_expr(&mut Locals::new(), &[], &program.main)
+}
+
+struct Machine<'a> {
+    env: &'a dyn Env,
+    program: &'a Program,
+}
+
+impl<'a> Machine<'a> {
+    /// Evaluate an expression, where `closure_env` holds the values in the
+    /// environment of the closure that is currently being run
+    fn eval_expr(
+        &self,
+        locals: &mut Locals,
+        closure_env: &[RcValue],
+        expr: &Expr,
+    ) -> Result<RcValue, RuntimeError> {
+        for &(ref var, ref comp) in &expr.bindings {
+            let value = self.eval_comp(locals, closure_env, comp)?;
+            locals.insert(var.id, value);
+        }
+
+        self.eval_comp(locals, closure_env, &expr.body)
+    }
+
+    fn eval_comp(
+        &self,
+        locals: &mut Locals,
+        closure_env: &[RcValue],
+        comp: &Comp,
+    ) -> Result<RcValue, RuntimeError> {
+        match *comp {
+            Comp::Atom(ref atom) => self.eval_atom(locals, atom),
+            Comp::ClosureIntro(code, ref atoms) => Ok(RcValue::from(Value::Closure(
+                code,
+                self.eval_atoms(locals, atoms)?,
+            ))),
+            Comp::EnvProj(index) => match closure_env.get(index) {
+                Some(value) => Ok(value.clone()),
+                None => Err(RuntimeError::new(format!(
+                    "projected on non existent environment entry `{}`",
+                    index
+                ))),
+            },
+            Comp::FunApp(ref head, ref arg) => {
+                let head = self.eval_atom(locals, head)?;
+                let arg = self.eval_atom(locals, arg)?;
+
+                self.fun_app(&head, arg)
+            },
+
+            Comp::RecordIntro(ref fields) => Ok(RcValue::from(Value::RecordIntro(
+                self.eval_fields(locals, fields)?,
+            ))),
+            Comp::RecordProj(ref atom, ref label) => {
+                let expr = self.eval_atom(locals, atom)?;
+                record_proj(&expr, label)
+            },
+            Comp::RecordExtend(ref atom, ref fields) => {
+                let expr = self.eval_atom(locals, atom)?;
+                let fields = self.eval_fields(locals, fields)?;
+
+                match *expr {
+                    Value::RecordIntro(ref expr_fields) => {
+                        let mut expr_fields = expr_fields.clone();
+                        expr_fields.extend(fields);
+
+                        Ok(RcValue::from(Value::RecordIntro(expr_fields)))
+                    },
+                    _ => Err(RuntimeError::new("extended a value that is not a record")),
+                }
+            },
+
+            Comp::VariantIntro(ref label, ref atom) => Ok(RcValue::from(Value::VariantIntro(
+                label.clone(),
+                self.eval_atom(locals, atom)?,
+            ))),
+            Comp::ArrayIntro(ref atoms) => Ok(RcValue::from(Value::ArrayIntro(
+                self.eval_atoms(locals, atoms)?,
+            ))),
+
+            Comp::Case(ref head, ref clauses) => {
+                let head = self.eval_atom(locals, head)?;
+                for &(ref pattern, ref body) in clauses {
+                    if self.match_value(locals, pattern, &head)? {
+                        return self.eval_expr(locals, closure_env, body);
+                    }
+                }
+                Err(RuntimeError::new(
+                    "no patterns matched the given expression",
+                ))
+            },
+        }
+    }
+
+    fn eval_atom(&self, locals: &Locals, atom: &Atom) -> Result<RcValue, RuntimeError> {
+        match *atom {
+            Atom::Var(ref var) => match locals.get(&var.id) {
+                Some(value) => Ok(value.clone()),
+                None => Err(RuntimeError::new(format!("unbound variable `{}`", var))),
+            },
+            Atom::Global(ref free_var) => Err(RuntimeError::new(format!(
+                "unsupported global `{}`",
+                free_var
+                    .pretty_name
+                    .as_ref()
+                    .map_or("<global>", String::as_str)
+            ))),
+            Atom::Import(ref name) => match self.env.get_import(name) {
+                Some(&Import::Prim(_, _)) => self.prim_app(name, Vec::new()),
+                Some(&Import::Term(_)) => {
+                    Err(RuntimeError::new(format!("unsupported import `{}`", name)))
+                },
+                None => Err(RuntimeError::new(format!("unknown import `{}`", name))),
+            },
+            Atom::Literal(ref literal) => Ok(RcValue::from(Value::Literal(literal.clone()))),
+            Atom::Erased => Ok(RcValue::from(Value::Erased)),
+        }
+    }
+
+    fn eval_atoms(&self, locals: &Locals, atoms: &[Atom]) -> Result<Vec<RcValue>, RuntimeError> {
+        atoms
+            .iter()
+            .map(|atom| self.eval_atom(locals, atom))
+            .collect()
+    }
+
+    fn eval_fields(
+        &self,
+        locals: &Locals,
+        fields: &[(Label, Atom)],
+    ) -> Result<Vec<(Label, RcValue)>, RuntimeError> {
+        fields
+            .iter()
+            .map(|&(ref label, ref atom)| Ok((label.clone(), self.eval_atom(locals, atom)?)))
+            .collect()
+    }
+
+    /// Apply a function to an argument
+    fn fun_app(&self, head: &RcValue, arg: RcValue) -> Result<RcValue, RuntimeError> {
+        match **head {
+            Value::Closure(id, ref closure_env) => {
+                let code = self.program.code(id);
+                let mut locals = Locals::new();
+                if let Some(ref this) = code.this {
+                    locals.insert(this.id, head.clone());
+                }
+                locals.insert(code.param.id, arg);
+
+                self.eval_expr(&mut locals, closure_env, &code.body)
+            },
+            Value::Prim(ref name, ref args) => {
+                let mut args = args.clone();
+                args.push(arg);
+                self.prim_app(name, args)
+            },
+            _ => Err(RuntimeError::new("applied a value that is not a function")),
+        }
+    }
+
+    /// Apply a primitive to the arguments it has been given so far, returning
+    /// a partial application if it needs more of them
+    fn prim_app(&self, name: &str, args: Vec<RcValue>) -> Result<RcValue, RuntimeError> {
+        let (arity, interpretation) = match self.env.get_import(name) {
+            Some(&Import::Prim(arity, interpretation)) => (arity, interpretation),
+            Some(&Import::Term(_)) | None => {
+                return Err(RuntimeError::new(format!("unknown primitive `{}`", name)));
+            },
+        };
+
+        // Primitives are only ever defined over literals
+        let domain_args = args
+            .iter()
+            .map(|arg| match **arg {
+                Value::Literal(ref literal) => {
+                    domain::RcValue::from(domain::Value::Literal(literal.clone()))
+                },
+                _ => domain::RcValue::from(domain::Value::Erased),
+            })
+            .collect::<Vec<_>>();
+
+        match interpretation(&domain_args) {
+            None if args.len() < arity => Ok(RcValue::from(Value::Prim(name.to_owned(), args))),
+            None => Err(RuntimeError::new(format!(
+                "primitive `{}` is undefined for its arguments",
+                name
+            ))),
+            Some(result) => match *result {
+                domain::Value::Literal(ref literal) => {
+                    Ok(RcValue::from(Value::Literal(literal.clone())))
+                },
+                _ => Err(RuntimeError::new(format!(
+                    "primitive `{}` returned a value that is not a literal",
+                    name
+                ))),
+            },
+        }
+    }
+
+    /// Match a value against a pattern, binding the variables in the pattern
+    /// if it matches
+    fn match_value(
+        &self,
+        locals: &mut Locals,
+        pattern: &Pattern,
+        value: &RcValue,
+    ) -> Result<bool, RuntimeError> {
+        match (pattern, &**value) {
+            (&Pattern::Binder(ref var), _) => {
+                locals.insert(var.id, value.clone());
+                Ok(true)
+            },
+            (&Pattern::Atom(ref atom), _) => Ok(self.eval_atom(locals, atom)? == *value),
+            (
+                &Pattern::VariantIntro(ref pattern_label, ref pattern),
+                &Value::VariantIntro(ref value_label, ref value),
+            ) if pattern_label == value_label => self.match_value(locals, pattern, value),
+            (&Pattern::RecordIntro(ref pattern_fields), &Value::RecordIntro(ref value_fields)) => {
+                for &(ref label, ref pattern) in pattern_fields {
+                    let value = match value_fields.iter().find(|&&(ref l, _)| l == label) {
+                        Some(&(_, ref value)) => value,
+                        None => return Ok(false),
+                    };
+                    if !self.match_value(locals, pattern, value)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            },
+            (&Pattern::ArrayIntro(ref patterns), &Value::ArrayIntro(ref values))
+                if patterns.len() == values.len() =>
+            {
+                for (pattern, value) in Iterator::zip(patterns.iter(), values.iter()) {
+                    if !self.match_value(locals, pattern, value)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            },
+            (_, _) => Ok(false),
+        }
+    }
+}
+
+fn record_proj(expr: &RcValue, label: &Label) -> Result<RcValue, RuntimeError> {
+    if let Value::RecordIntro(ref fields) = **expr {
+        for &(ref current_label, ref current_expr) in fields {
+            if current_label == label {
+                return Ok(current_expr.clone());
+            }
+        }
+    }
+
+    Err(RuntimeError::new(format!(
+        "projected on non existent field `{}`",
+        label
+    )))
+}
@@ -0,0 +1,9 @@
+//! Closure conversion of the A-normal form intermediate representation
+//!
+//! Nested functions are replaced by closures that explicitly record the
+//! values of their free variables, which brings us a step closer to machine
+//! code, where functions can only live at the top level of a program.
+
+pub mod convert;
+pub mod eval;
+pub mod syntax;
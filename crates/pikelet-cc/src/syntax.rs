@@ -0,0 +1,252 @@
+//! The syntax of the closure converted intermediate representation
+//!
+//! This is much the same as the A-normal form that it is converted from,
+//! except that functions can no longer be nested inside one another. Instead,
+//! the code of every function is lifted to the top level of the program, and
+//! functions are represented at runtime as closures: a pointer to their code,
+//! paired with an environment record holding the values of their free
+//! variables.
+
+use pretty::{BoxDoc, Doc};
+use std::fmt;
+
+use pikelet_core::syntax::{Label, PRETTY_FALLBACK_WIDTH};
+
+pub use pikelet_anf::syntax::{Atom, Pattern, Var};
+
+/// The number of spaces to indent nested expressions by
+const PRETTY_INDENT_WIDTH: usize = 4;
+
+/// A pointer to the code of a function, referring to it by its position in
+/// the program
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CodeId(pub usize);
+
+impl fmt::Display for CodeId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "code{}", self.0)
+    }
+}
+
+/// Computations, which only take atoms as operands
+#[derive(Debug, Clone, PartialEq)]
+pub enum Comp {
+    /// Atoms
+    Atom(Atom),
+    /// Closures, made up of a pointer to the code of a function and the
+    /// values of its free variables
+    ClosureIntro(CodeId, Vec<Atom>),
+    /// Projections of free variables from the environment of the current
+    /// closure
+    EnvProj(usize),
+    /// Function applications
+    FunApp(Atom, Atom),
+    /// Records
+    RecordIntro(Vec<(Label, Atom)>),
+    /// Record projections
+    RecordProj(Atom, Label),
+    /// Record extensions
+    RecordExtend(Atom, Vec<(Label, Atom)>),
+    /// Variants
+    VariantIntro(Label, Atom),
+    /// Arrays
+    ArrayIntro(Vec<Atom>),
+    /// Case expressions
+    Case(Atom, Vec<(Pattern, Expr)>),
+}
+
+impl Comp {
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        match *self {
+            Comp::Atom(ref atom) => atom.to_doc(),
+            Comp::ClosureIntro(code, ref env) => Doc::nil()
+                .append("closure ")
+                .append(Doc::as_string(code))
+                .append(" ")
+                .append(atoms_to_doc(env)),
+            Comp::EnvProj(index) => Doc::text(format!("env.{}", index)),
+            Comp::FunApp(ref head, ref arg) => Doc::nil()
+                .append(head.to_doc_atomic())
+                .append(" ")
+                .append(arg.to_doc_atomic()),
+            Comp::RecordIntro(ref fields) => fields_to_doc("record", fields),
+            Comp::RecordProj(ref atom, ref label) => Doc::nil()
+                .append(atom.to_doc_atomic())
+                .append(".")
+                .append(Doc::as_string(label)),
+            Comp::RecordExtend(ref atom, ref fields) => Doc::nil()
+                .append(atom.to_doc_atomic())
+                .append(" ")
+                .append(fields_to_doc("with", fields)),
+            Comp::VariantIntro(ref label, ref atom) => Doc::nil()
+                .append("variant ")
+                .append(Doc::as_string(label))
+                .append(" ")
+                .append(atom.to_doc_atomic()),
+            Comp::ArrayIntro(ref atoms) => atoms_to_doc(atoms),
+            Comp::Case(ref head, ref clauses) => Doc::nil()
+                .append("case ")
+                .append(head.to_doc_atomic())
+                .append(" {")
+                .append(
+                    Doc::concat(clauses.iter().map(|&(ref pattern, ref body)| {
+                        Doc::newline()
+                            .append(pattern.to_doc())
+                            .append(" =>")
+                            .append(
+                                Doc::newline()
+                                    .append(body.to_doc())
+                                    .nest(PRETTY_INDENT_WIDTH),
+                            )
+                            .append(",")
+                    }))
+                    .nest(PRETTY_INDENT_WIDTH),
+                )
+                .append(Doc::newline())
+                .append("}"),
+        }
+    }
+}
+
+impl fmt::Display for Comp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
+/// A sequence of computations, each bound to a variable, followed by the
+/// computation that produces the result of the expression
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub bindings: Vec<(Var, Comp)>,
+    pub body: Comp,
+}
+
+impl Expr {
+    pub fn new(bindings: Vec<(Var, Comp)>, body: Comp) -> Expr {
+        Expr { bindings, body }
+    }
+
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        Doc::concat(self.bindings.iter().map(|&(ref var, ref comp)| {
+            Doc::nil()
+                .append("let ")
+                .append(var.to_doc())
+                .append(" = ")
+                .append(comp.to_doc())
+                .append(";")
+                .append(Doc::newline())
+        }))
+        .append(self.body.to_doc())
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
+/// The code of a function, lifted to the top level of a program
+///
+/// The body of the code can refer to the parameter, to the closure that it
+/// was called with if it is recursive, and to the values in the environment
+/// of that closure. Nothing else is in scope.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Code {
+    /// The variable that the closure itself is bound to, if the function is
+    /// recursive
+    pub this: Option<Var>,
+    pub param: Var,
+    pub body: Expr,
+}
+
+impl Code {
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        let header = match self.this {
+            None => Doc::nil(),
+            Some(ref this) => Doc::text("fix ").append(this.to_doc()).append(" => "),
+        };
+
+        header
+            .append("\\")
+            .append(self.param.to_doc())
+            .append(" =>")
+            .append(
+                Doc::newline()
+                    .append(self.body.to_doc())
+                    .nest(PRETTY_INDENT_WIDTH),
+            )
+    }
+}
+
+/// A closure converted program
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    /// The code of the functions in the program, indexed by `CodeId`
+    pub codes: Vec<Code>,
+    /// The expression that the program evaluates to
+    pub main: Expr,
+}
+
+impl Program {
+    pub fn code(&self, id: CodeId) -> &Code {
+        &self.codes[id.0]
+    }
+
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        Doc::concat(self.codes.iter().enumerate().map(|(index, code)| {
+            Doc::nil()
+                .append(Doc::as_string(CodeId(index)))
+                .append(" = ")
+                .append(code.to_doc())
+                .append(Doc::newline())
+                .append(Doc::newline())
+        }))
+        .append("main =")
+        .append(
+            Doc::newline()
+                .append(self.main.to_doc())
+                .nest(PRETTY_INDENT_WIDTH),
+        )
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
+fn atoms_to_doc(atoms: &[Atom]) -> Doc<BoxDoc<()>> {
+    Doc::nil()
+        .append("[")
+        .append(Doc::intersperse(
+            atoms.iter().map(Atom::to_doc),
+            Doc::text("; "),
+        ))
+        .append("]")
+}
+
+fn fields_to_doc<'a>(
+    keyword: &'static str,
+    fields: &'a [(Label, Atom)],
+) -> Doc<'a, BoxDoc<'a, ()>> {
+    if fields.is_empty() {
+        return Doc::text(keyword).append(" {}");
+    }
+
+    Doc::nil()
+        .append(keyword)
+        .append(" { ")
+        .append(Doc::intersperse(
+            fields.iter().map(|&(ref label, ref atom)| {
+                Doc::nil()
+                    .append(Doc::as_string(label))
+                    .append(" = ")
+                    .append(atom.to_doc())
+            }),
+            Doc::text("; "),
+        ))
+        .append(" }")
+}
@@ -0,0 +1,232 @@
+use pretty_assertions::assert_eq;
+
+use pikelet_anf::lower;
+use pikelet_cc::convert::{self, ConvertError};
+use pikelet_cc::eval::{self, RcValue, Value};
+use pikelet_cc::syntax::Program;
+use pikelet_concrete::elaborate::Context;
+use pikelet_core::syntax::core::RcTerm;
+use pikelet_test_support::{parse_infer_term, write_golden, Observation};
+
+fn golden(filename: &str, literal: &str) {
+    let context = Context::default();
+
+    let program = parse_convert_term(&context, literal);
+
+    write_golden(filename, &program);
+}
+
+/// Check that running the closure converted program gives the same result
+/// as the evaluator
+fn assert_agrees(src: &str) {
+    pikelet_test_support::assert_agrees(src, |context, term| {
+        let program = convert_term(term);
+        match eval::run_program(context, &program) {
+            Ok(value) => observe(&value),
+            Err(error) => panic!("runtime error: {}", error),
+        }
+    });
+}
+
+fn observe(value: &RcValue) -> Observation {
+    match **value {
+        Value::Literal(ref literal) => Observation::Literal(literal.clone()),
+        Value::Closure(_, _) | Value::Prim(_, _) => Observation::Function,
+        Value::RecordIntro(ref fields) => Observation::record(
+            fields
+                .iter()
+                .map(|&(ref label, ref value)| (label.clone(), observe(value))),
+        ),
+        Value::VariantIntro(ref label, ref value) => {
+            Observation::Variant(label.clone(), Box::new(observe(value)))
+        },
+        Value::ArrayIntro(ref elems) => Observation::Array(elems.iter().map(observe).collect()),
+        Value::Erased => Observation::Erased,
+    }
+}
+
+fn parse_convert_term(context: &Context, src: &str) -> Program {
+    convert_term(&parse_infer_term(context, src))
+}
+
+fn convert_term(term: &RcTerm) -> Program {
+    let expr = match lower::lower_term(term) {
+        Ok(expr) => expr,
+        Err(error) => panic!("lower error: {}", error),
+    };
+
+    match convert::convert_program(&expr) {
+        Ok(program) => program,
+        Err(error) => panic!("convert error: {}", error),
+    }
+}
+
+#[test]
+fn compose() {
+    golden(
+        "compose",
+        r"\(0 a b c : Type) (f : b -> c) (g : a -> b) (x : a) => f (g x)",
+    );
+}
+
+#[test]
+fn capture_let() {
+    golden(
+        "capture_let",
+        r#"
+            let
+                add = import "prim/u64/add";
+                x = add 1 2;
+            in
+                \(y : U64) => add x y
+        "#,
+    );
+}
+
+#[test]
+fn fix() {
+    golden(
+        "fix",
+        r#"
+            let
+                partial loop : U64 -> U64;
+                loop n = loop n;
+            in
+                loop
+        "#,
+    );
+}
+
+#[test]
+fn fix_not_function() {
+    use pikelet_anf::syntax::{Comp, Expr, Var};
+    use pikelet_core::syntax::NameHint;
+
+    let var = Var::new(0, NameHint::from("x"));
+    let expr = Expr::new(
+        vec![],
+        Comp::Fix(var, Box::new(Expr::new(vec![], Comp::RecordIntro(vec![])))),
+    );
+
+    assert_eq!(
+        convert::convert_program(&expr),
+        Err(ConvertError::new(
+            "fixed point `x#0` does not bind a function"
+        )),
+    );
+}
+
+#[test]
+fn agrees_compose() {
+    assert_agrees(
+        r#"
+            (\(0 a b c : Type) (f : b -> c) (g : a -> b) (x : a) => f (g x))
+                U64 U64 U64
+                ((import "prim/u64/add") 1)
+                ((import "prim/u64/mul") 2)
+                3
+        "#,
+    );
+}
+
+#[test]
+fn agrees_capture_let() {
+    assert_agrees(
+        r#"
+            let
+                add = import "prim/u64/add";
+                x = add 1 2;
+                f = \(y : U64) => add x y;
+            in
+                f 4
+        "#,
+    );
+}
+
+#[test]
+fn agrees_record() {
+    assert_agrees(
+        r#"
+            let
+                swap = \(r : Record { x : U64; y : String }) =>
+                    record { x = r.y; y = r.x };
+            in
+                swap (record { x = 1; y = "hello" })
+        "#,
+    );
+}
+
+#[test]
+fn agrees_case_variant() {
+    assert_agrees(
+        r#"
+            case (variant some "hello" : Variant { some : String; none : Record {} }) {
+                variant some x => x;
+                variant none _ => "nothing";
+            }
+        "#,
+    );
+}
+
+#[test]
+fn agrees_recursive() {
+    assert_agrees(
+        r#"
+            let
+                partial fact : U64 -> U64;
+                fact n =
+                    if (import "prim/u64/eq") n 0 then 1 else
+                        (import "prim/u64/mul") n (fact ((import "prim/u64/sub") n 1));
+            in
+                fact 5
+        "#,
+    );
+}
+
+#[test]
+fn agrees_mutually_recursive() {
+    assert_agrees(
+        r#"
+            let
+                partial is-even : U64 -> Bool;
+                partial is-odd : U64 -> Bool;
+
+                is-even n =
+                    if (import "prim/u64/eq") n 0 then true else is-odd ((import "prim/u64/sub") n 1);
+                is-odd n =
+                    if (import "prim/u64/eq") n 0 then false else is-even ((import "prim/u64/sub") n 1);
+            in
+                is-even 10
+        "#,
+    );
+}
+
+#[test]
+fn agrees_closures_in_array() {
+    assert_agrees(
+        r#"
+            let
+                add = import "prim/u64/add";
+                adder = \(x : U64) (y : U64) => add x y;
+                fs = [adder 1; adder 2] : Array 2 (U64 -> U64);
+            in
+                case fs {
+                    [f; g] => [f 10; g 10] : Array 2 U64;
+                }
+        "#,
+    );
+}
+
+#[test]
+fn divide_by_zero() {
+    let context = Context::default();
+
+    let program = parse_convert_term(&context, r#"(import "prim/u32/div") 1 0"#);
+
+    assert_eq!(
+        eval::run_program(&context, &program),
+        Err(eval::RuntimeError::new(
+            "primitive `prim/u32/div` is undefined for its arguments",
+        )),
+    );
+}
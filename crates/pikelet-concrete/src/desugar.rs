@@ -1,12 +1,13 @@
 use codespan::{ByteIndex, ByteOffset, ByteSpan};
 use codespan_reporting::{Diagnostic, Label as DiagnosticLabel};
 use im;
-use moniker::{Binder, Embed, FreeVar, Nest, Scope, Var};
+use moniker::{Binder, Embed, FreeVar, Nest, Rec, Scope, Var};
 
-use pikelet_core::syntax::{Label, Level, LevelShift};
+use pikelet_core::syntax::{Label, Level, LevelShift, Multiplicity, Plicity, Totality};
 
 use crate::syntax::concrete;
 use crate::syntax::raw;
+use crate::syntax::BindingKind;
 
 /// The environment used when desugaring from the concrete to raw syntax
 #[derive(Debug, Clone)]
@@ -40,13 +41,17 @@ impl DesugarEnv {
         free_var
     }
 
-    pub fn on_name(&self, span: ByteSpan, name: &str, shift: u32) -> raw::RcTerm {
+    pub fn on_name(&self, span: ByteSpan, name: &str, shift: Option<u32>) -> raw::RcTerm {
         let free_var = match self.locals.get(name) {
             None => FreeVar::fresh_named(name),
             Some(free_var) => free_var.clone(),
         };
 
-        raw::RcTerm::from(raw::Term::Var(span, Var::Free(free_var), LevelShift(shift)))
+        raw::RcTerm::from(raw::Term::Var(
+            span,
+            Var::Free(free_var),
+            shift.map(LevelShift),
+        ))
     }
 }
 
@@ -147,6 +152,8 @@ pub trait Desugar<T> {
 /// ```text
 /// (a : t1) -> (b : t1) -> (c : t2) -> t3
 /// ```
+///
+/// The plicity of each group is carried over to the nested pi types.
 fn desugar_fun_ty(
     env: &DesugarEnv,
     param_groups: &[concrete::FunTypeParamGroup],
@@ -155,23 +162,25 @@ fn desugar_fun_ty(
     let mut env = env.clone();
 
     let mut params = Vec::new();
-    for &(ref names, ref ann) in param_groups {
+    for &(plicity, multiplicity, ref names, ref ann) in param_groups {
         let ann = raw::RcTerm::from(ann.desugar(&env)?);
         params.extend(names.iter().map(|&(start, ref name)| {
             let free_var = env.on_binding(name);
-            (start, Binder(free_var), ann.clone())
+            (start, plicity, multiplicity, Binder(free_var), ann.clone())
         }));
     }
 
-    Ok(params
-        .into_iter()
-        .rev()
-        .fold(body.desugar(&env)?, |acc, (start, binder, ann)| {
+    Ok(params.into_iter().rev().fold(
+        body.desugar(&env)?,
+        |acc, (start, plicity, multiplicity, binder, ann)| {
             raw::RcTerm::from(raw::Term::FunType(
                 ByteSpan::new(start, acc.span().end()),
+                plicity,
+                multiplicity,
                 Scope::new((binder, Embed(ann.clone())), acc),
             ))
-        }))
+        },
+    ))
 }
 
 /// Convert a sugary lambda from something like:
@@ -194,15 +203,21 @@ fn desugar_fun_intro(
     let mut env = env.clone();
 
     let mut params = Vec::new();
-    for &(ref names, ref ann) in param_groups {
+    for &(plicity, multiplicity, ref names, ref ann) in param_groups {
         let ann = match *ann {
-            None => raw::RcTerm::from(raw::Term::Hole(ByteSpan::default())),
-            Some(ref ann) => ann.desugar(&env)?,
+            None => None,
+            Some(ref ann) => Some(ann.desugar(&env)?),
         };
 
         params.extend(names.iter().map(|&(start, ref name)| {
             let free_var = env.on_binding(name);
-            (start, Binder(free_var), ann.clone())
+            // Missing annotations are filled in with holes that point back
+            // at the parameter, in case they need to be reported as unsolved
+            let ann = ann.clone().unwrap_or_else(|| {
+                let span = ByteSpan::from_offset(start, ByteOffset::from_str(name));
+                raw::RcTerm::from(raw::Term::Hole(span))
+            });
+            (start, plicity, multiplicity, Binder(free_var), ann)
         }));
     }
 
@@ -214,9 +229,11 @@ fn desugar_fun_intro(
     Ok(params
         .into_iter()
         .rev()
-        .fold(body, |acc, (start, binder, ann)| {
+        .fold(body, |acc, (start, plicity, multiplicity, binder, ann)| {
             raw::RcTerm::from(raw::Term::FunIntro(
                 ByteSpan::new(start, acc.span().end()),
+                plicity,
+                multiplicity,
                 Scope::new((binder, Embed(ann.clone())), acc),
             ))
         }))
@@ -225,12 +242,12 @@ fn desugar_fun_intro(
 fn desugar_items(
     env: &mut DesugarEnv,
     concrete_items: &[concrete::Item],
-) -> Result<Nest<(Binder<String>, Embed<raw::RcTerm>)>, DesugarError> {
+) -> Result<Rec<Vec<raw::LetBinding>>, DesugarError> {
     use im::HashMap;
 
     #[derive(Clone)]
     pub enum ForwardDecl {
-        Pending(ByteSpan, raw::RcTerm),
+        Pending(ByteSpan, Totality, BindingKind, raw::RcTerm),
         Defined(ByteSpan),
     }
 
@@ -244,6 +261,8 @@ fn desugar_items(
     for concrete_item in concrete_items {
         match *concrete_item {
             concrete::Item::Declaration {
+                totality,
+                kind,
                 name: (start, ref name),
                 ref ann,
             } => {
@@ -263,7 +282,7 @@ fn desugar_items(
                     },
                     // There's a declaration  for this name already pending - we
                     // can't add a new one!
-                    Some(&ForwardDecl::Pending(original_span, _)) => {
+                    Some(&ForwardDecl::Pending(original_span, _, _, _)) => {
                         return Err(DesugarError::DuplicateDeclarations {
                             original_span,
                             duplicate_span: name_span,
@@ -277,11 +296,13 @@ fn desugar_items(
                 }
 
                 // Remember the declaration for when we get to a subsequent definition
-                let declaration = ForwardDecl::Pending(name_span, ann.desugar(&env)?);
+                let declaration =
+                    ForwardDecl::Pending(name_span, totality, kind, ann.desugar(&env)?);
                 forward_declarations.insert(binder.clone(), declaration);
             },
 
             concrete::Item::Definition {
+                kind,
                 name: (start, ref name),
                 ref params,
                 ref return_ann,
@@ -291,7 +312,7 @@ fn desugar_items(
                 let name_span = ByteSpan::from_offset(start, ByteOffset::from_str(name));
                 let term =
                     desugar_fun_intro(env, params, return_ann.as_ref().map(<_>::as_ref), body)?;
-                let ann = match forward_declarations.get(&binder).cloned() {
+                let (totality, decl_kind, ann) = match forward_declarations.get(&binder).cloned() {
                     // This declaration was already given a definition, so this
                     // is an error!
                     //
@@ -307,9 +328,17 @@ fn desugar_items(
                     },
                     // We found a prior declaration, so we'll use it as a basis
                     // for checking the definition
-                    Some(ForwardDecl::Pending(_, ann)) => ann.clone(),
+                    Some(ForwardDecl::Pending(_, totality, kind, ann)) => {
+                        (totality, kind, ann.clone())
+                    },
                     // No prior declaration was found, so use a hole instead
-                    None => hole.clone(),
+                    None => (Totality::Total, BindingKind::Ordinary, hole.clone()),
+                };
+                // The binding is an instance if either its declaration or its
+                // definition was marked as one
+                let kind = match (decl_kind, kind) {
+                    (BindingKind::Ordinary, BindingKind::Ordinary) => BindingKind::Ordinary,
+                    (_, _) => BindingKind::Instance,
                 };
 
                 // We must not remove this from the list of pending
@@ -317,13 +346,18 @@ fn desugar_items(
                 // definition of the same name later on!
                 forward_declarations.insert(binder.clone(), ForwardDecl::Defined(name_span));
                 // Add the definition to the elaborated items
-                items.push((binder, Embed(raw::RcTerm::from(raw::Term::Ann(term, ann)))));
+                items.push((
+                    binder,
+                    Embed(totality),
+                    Embed(kind),
+                    Embed(raw::RcTerm::from(raw::Term::Ann(term, ann))),
+                ));
             },
             concrete::Item::Error(_) => unimplemented!("error recovery"),
         }
     }
 
-    Ok(Nest::new(items))
+    Ok(Rec::new(items))
 }
 
 fn desugar_let(
@@ -374,7 +408,12 @@ fn desugar_record_ty(
                 None => env.on_binding(label),
             };
 
-            Ok((Label(label.clone()), Binder(free_var), Embed(ann)))
+            Ok((
+                Label(label.clone()),
+                field.multiplicity,
+                Binder(free_var),
+                Embed(ann),
+            ))
         })
         .collect::<Result<Vec<_>, _>>()?;
 
@@ -384,21 +423,21 @@ fn desugar_record_ty(
     )))
 }
 
-fn desugar_record_intro(
+fn desugar_record_intro_fields(
     env: &DesugarEnv,
     span: ByteSpan,
     fields: &[concrete::RecordIntroField],
-) -> Result<raw::RcTerm, DesugarError> {
+) -> Result<Vec<(Label, raw::RcTerm)>, DesugarError> {
     use crate::syntax::concrete::RecordIntroField;
 
-    let fields = fields
+    fields
         .iter()
         .map(|field| match field {
             RecordIntroField::Punned {
                 label: (_, ref name),
                 shift,
             } => {
-                let var = env.on_name(span, name, shift.unwrap_or(0));
+                let var = env.on_name(span, name, shift);
                 Ok((Label(name.clone()), var))
             },
             RecordIntroField::Explicit {
@@ -411,9 +450,7 @@ fn desugar_record_intro(
                 desugar_fun_intro(env, params, return_ann.as_ref().map(<_>::as_ref), term)?,
             )),
         })
-        .collect::<Result<Vec<_>, _>>()?;
-
-    Ok(raw::RcTerm::from(raw::Term::RecordIntro(span, fields)))
+        .collect()
 }
 
 impl Desugar<raw::Literal> for concrete::Literal {
@@ -467,6 +504,61 @@ impl Desugar<(raw::RcPattern, DesugarEnv)> for concrete::Pattern {
 
                 Ok((literal, env.clone()))
             },
+            concrete::Pattern::VariantIntro(_, (_, ref label), ref pattern) => {
+                let (pattern, env) = pattern.desugar(env)?;
+                let variant_pattern = raw::RcPattern::from(raw::Pattern::VariantIntro(
+                    self.span(),
+                    Label(label.clone()),
+                    pattern,
+                ));
+
+                Ok((variant_pattern, env))
+            },
+            concrete::Pattern::Wildcard(span) => Ok((
+                raw::RcPattern::from(raw::Pattern::Wildcard(span)),
+                env.clone(),
+            )),
+            concrete::Pattern::RecordIntro(span, ref fields) => {
+                let mut env = env.clone();
+                let mut pattern_fields = Vec::with_capacity(fields.len());
+
+                for field in fields {
+                    match *field {
+                        concrete::RecordPatternField::Punned {
+                            label: (start, ref name),
+                        } => {
+                            let span = ByteSpan::from_offset(start, ByteOffset::from_str(name));
+                            let binder = Binder(env.on_binding(name));
+                            let pattern = raw::RcPattern::from(raw::Pattern::Binder(span, binder));
+                            pattern_fields.push((Label(name.clone()), pattern));
+                        },
+                        concrete::RecordPatternField::Explicit {
+                            label: (_, ref name),
+                            ref pattern,
+                        } => {
+                            let (pattern, next_env) = pattern.desugar(&env)?;
+                            env = next_env;
+                            pattern_fields.push((Label(name.clone()), pattern));
+                        },
+                    }
+                }
+
+                let pattern = raw::RcPattern::from(raw::Pattern::RecordIntro(span, pattern_fields));
+                Ok((pattern, env))
+            },
+            concrete::Pattern::ArrayIntro(span, ref elems) => {
+                let mut env = env.clone();
+                let mut elem_patterns = Vec::with_capacity(elems.len());
+
+                for elem in elems {
+                    let (pattern, next_env) = elem.desugar(&env)?;
+                    env = next_env;
+                    elem_patterns.push(pattern);
+                }
+
+                let pattern = raw::RcPattern::from(raw::Pattern::ArrayIntro(span, elem_patterns));
+                Ok((pattern, env))
+            },
             concrete::Pattern::Error(_) => unimplemented!("error recovery"),
         }
     }
@@ -483,8 +575,11 @@ impl Desugar<raw::RcTerm> for concrete::Term {
             ))),
             concrete::Term::Universe(_, level) => Ok(raw::RcTerm::from(raw::Term::Universe(
                 span,
-                Level(level.unwrap_or(0)),
+                level.map(Level),
             ))),
+            concrete::Term::UniverseAt(_, ref level) => Ok(raw::RcTerm::from(
+                raw::Term::UniverseAt(span, level.desugar(env)?),
+            )),
             concrete::Term::Literal(ref literal) => {
                 Ok(raw::RcTerm::from(raw::Term::Literal(literal.desugar(env)?)))
             },
@@ -497,9 +592,7 @@ impl Desugar<raw::RcTerm> for concrete::Term {
                 Ok(raw::RcTerm::from(raw::Term::ArrayIntro(span, elems)))
             },
             concrete::Term::Hole(_) => Ok(raw::RcTerm::from(raw::Term::Hole(span))),
-            concrete::Term::Name(_, ref name, shift) => {
-                Ok(env.on_name(span, name, shift.unwrap_or(0)))
-            },
+            concrete::Term::Name(_, ref name, shift) => Ok(env.on_name(span, name, shift)),
             concrete::Term::Import(_, name_span, ref name) => Ok(raw::RcTerm::from(
                 raw::Term::Import(span, name_span, name.clone()),
             )),
@@ -510,6 +603,8 @@ impl Desugar<raw::RcTerm> for concrete::Term {
             concrete::Term::FunArrow(ref ann, ref body) => {
                 Ok(raw::RcTerm::from(raw::Term::FunType(
                     span,
+                    Plicity::Explicit,
+                    Multiplicity::Many,
                     Scope::new(
                         (Binder(FreeVar::fresh_unnamed()), Embed(ann.desugar(env)?)),
                         body.desugar(env)?,
@@ -561,9 +656,9 @@ impl Desugar<raw::RcTerm> for concrete::Term {
                 )))
             },
             concrete::Term::RecordType(span, ref fields) => desugar_record_ty(env, span, fields),
-            concrete::Term::RecordIntro(span, ref fields) => {
-                desugar_record_intro(env, span, fields)
-            },
+            concrete::Term::RecordIntro(span, ref fields) => Ok(raw::RcTerm::from(
+                raw::Term::RecordIntro(span, desugar_record_intro_fields(env, span, fields)?),
+            )),
             concrete::Term::RecordProj(_, ref tm, label_start, ref label, shift) => {
                 Ok(raw::RcTerm::from(raw::Term::RecordProj(
                     span,
@@ -573,6 +668,48 @@ impl Desugar<raw::RcTerm> for concrete::Term {
                     LevelShift(shift.unwrap_or(0)),
                 )))
             },
+            concrete::Term::RecordExtend(span, ref expr, ref fields) => {
+                Ok(raw::RcTerm::from(raw::Term::RecordExtend(
+                    span,
+                    expr.desugar(env)?,
+                    desugar_record_intro_fields(env, span, fields)?,
+                )))
+            },
+            concrete::Term::RecordUpdate(span, ref expr, ref fields) => {
+                Ok(raw::RcTerm::from(raw::Term::RecordUpdate(
+                    span,
+                    expr.desugar(env)?,
+                    desugar_record_intro_fields(env, span, fields)?,
+                )))
+            },
+            concrete::Term::RecordTypeMerge(ref lhs, ref rhs) => Ok(raw::RcTerm::from(
+                raw::Term::RecordTypeMerge(span, lhs.desugar(env)?, rhs.desugar(env)?),
+            )),
+            concrete::Term::VariantType(span, ref alts) => {
+                let alts = alts
+                    .iter()
+                    .map(|alt| Ok((Label(alt.label.1.clone()), alt.ann.desugar(env)?)))
+                    .collect::<Result<_, _>>()?;
+
+                Ok(raw::RcTerm::from(raw::Term::VariantType(span, alts)))
+            },
+            concrete::Term::VariantIntro(_, (_, ref label), ref arg) => Ok(raw::RcTerm::from(
+                raw::Term::VariantIntro(span, Label(label.clone()), arg.desugar(env)?),
+            )),
+            concrete::Term::EqType(ref lhs, ref rhs) => Ok(raw::RcTerm::from(raw::Term::EqType(
+                span,
+                lhs.desugar(env)?,
+                rhs.desugar(env)?,
+            ))),
+            concrete::Term::EqIntro(_) => Ok(raw::RcTerm::from(raw::Term::EqIntro(span))),
+            concrete::Term::EqElim(_, ref motive, ref proof, ref body) => {
+                Ok(raw::RcTerm::from(raw::Term::EqElim(
+                    span,
+                    motive.desugar(env)?,
+                    proof.desugar(env)?,
+                    body.desugar(env)?,
+                )))
+            },
             concrete::Term::Error(_) => unimplemented!("error recovery"),
         }
     }
@@ -1,13 +1,23 @@
+use codespan::ByteSpan;
 use im;
-use moniker::{Binder, FreeVar, Var};
+use moniker::FreeVar;
+use std::cell::RefCell;
+use std::fmt;
 use std::rc::Rc;
 
 use pikelet_core::nbe;
-use pikelet_core::syntax::core::RcTerm;
-use pikelet_core::syntax::domain::{RcType, RcValue, Value};
-use pikelet_core::syntax::{Import, Literal};
+use pikelet_core::syntax::core::{RcTerm, Term};
+use pikelet_core::syntax::domain::{
+    Folded, FoldedHead, LevelValue, Locals, RcType, RcValue, Value,
+};
+use pikelet_core::syntax::{
+    Import, LevelShift, Literal, MetaVar, NameHint, Totality, VarIndex, VarLevel,
+};
 
 use crate::resugar::{Resugar, ResugarEnv};
+use crate::syntax::{concrete, raw};
+
+use super::TypeWarning;
 
 // Some helper traits for marshalling between Rust and Pikelet values
 //
@@ -185,6 +195,118 @@ pub struct Globals {
     var_array: FreeVar<String>,
 }
 
+/// The reason that a metavariable was created
+///
+/// This is used to provide helpful error messages if the metavariable is left
+/// unsolved at the end of elaboration.
+#[derive(Clone, Debug)]
+pub enum MetaOrigin {
+    /// A hole that was written explicitly in the source code
+    Hole(ByteSpan),
+    /// The type of a function parameter that was missing an annotation
+    FunParamAnn(ByteSpan, FreeVar<String>),
+    /// An implicit argument that was inserted when applying a function
+    ImplicitArg(ByteSpan, NameHint),
+    /// An instance argument that was inserted when applying a function
+    InstanceArg(ByteSpan, NameHint),
+    /// A numeric literal that was checked against a type that had yet to be
+    /// solved
+    Literal(raw::Literal),
+    /// The level of a universe that was written without one
+    UniverseLevel(ByteSpan),
+}
+
+impl MetaOrigin {
+    pub fn span(&self) -> ByteSpan {
+        match *self {
+            MetaOrigin::Hole(span)
+            | MetaOrigin::FunParamAnn(span, _)
+            | MetaOrigin::ImplicitArg(span, _)
+            | MetaOrigin::InstanceArg(span, _)
+            | MetaOrigin::UniverseLevel(span) => span,
+            MetaOrigin::Literal(ref literal) => literal.span(),
+        }
+    }
+}
+
+/// A metavariable that was created during elaboration
+#[derive(Clone, Debug)]
+struct Meta {
+    /// Where the metavariable came from
+    origin: MetaOrigin,
+    /// The expected type of the metavariable, along with the resugar
+    /// environment and the values of the local variables it was created
+    /// with, if the type was known at the time
+    expected: Option<(ResugarEnv, Locals, RcType)>,
+    /// The solution to the metavariable, if one has been found
+    ///
+    /// This will be a function that takes the local variables that were in
+    /// scope when the metavariable was created as parameters.
+    solution: Option<RcTerm>,
+}
+
+/// The solutions to the metavariables at some point during elaboration, along
+/// with the number of level constraints that had been found
+///
+/// This can be used to undo the solutions found while trying out a candidate
+/// that turned out not to work.
+pub struct MetaSnapshot(Vec<Option<RcTerm>>, usize);
+
+/// A constraint that the universe level `lhs` is at most `rhs`
+///
+/// These are collected when comparing universes whose levels are not yet
+/// known, and are solved once elaboration is otherwise complete.
+#[derive(Clone, Debug)]
+pub struct LevelConstraint {
+    pub lhs: LevelValue,
+    pub rhs: LevelValue,
+}
+
+/// A metavariable that can't be solved until more is known about the
+/// metavariables that its expected type depends on
+///
+/// The way the problem is solved depends on the origin of the metavariable.
+#[derive(Clone)]
+pub struct Postponed {
+    /// The context that the metavariable was created in
+    pub context: Context,
+    /// The metavariable that was postponed
+    pub meta: MetaVar,
+    /// The metavariable, applied to the local variables that were in scope
+    pub term: RcTerm,
+    /// The expected type of the metavariable
+    pub expected_ty: RcType,
+}
+
+impl fmt::Debug for Postponed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The context contains the list of postponed problems, so we avoid
+        // printing it here to prevent infinite recursion
+        f.debug_struct("Postponed")
+            .field("meta", &self.meta)
+            .field("term", &self.term)
+            .field("expected_ty", &self.expected_ty)
+            .finish()
+    }
+}
+
+/// A local variable that we have passed over
+#[derive(Clone, Debug)]
+struct Local {
+    /// The type of the variable, if it has been declared yet
+    ///
+    /// The variables bound by recursive let bindings are introduced all at
+    /// once, and are only declared as their types become known.
+    ty: Option<RcType>,
+    /// Whether the variable is bound by a function parameter or a pattern,
+    /// rather than by a let binding
+    ///
+    /// Metavariables are applied to these variables when they are created,
+    /// allowing their solutions to refer to them even after the scopes they
+    /// were created in have been closed.
+    is_bound: bool,
+}
+
 /// The type checking context
 ///
 /// A default context with entries for built-in types is provided via the
@@ -204,17 +326,57 @@ pub struct Context {
     globals: Rc<Globals>,
     /// Imports
     imports: im::HashMap<String, (Import, RcType)>,
-    /// The type annotations of the binders we have passed over
+    /// The type annotations of the global variables we have passed over
     declarations: im::HashMap<FreeVar<String>, RcType>,
-    /// Any definitions we have passed over
+    /// The definitions of the global variables we have passed over
     definitions: im::HashMap<FreeVar<String>, RcTerm>,
+    /// The local variables we have passed over, in the order that they were
+    /// introduced
+    ///
+    /// The position of a local variable in this list is its de Bruijn level.
+    locals: im::Vector<Local>,
+    /// The values of the local variables, for evaluating terms in this
+    /// context
+    ///
+    /// Bound variables are given neutral values, referring to themselves.
+    local_values: Locals,
+    /// The levels of the local variables that can be referred to by name
+    local_levels: im::HashMap<FreeVar<String>, VarLevel>,
+    /// The instance definitions and instance parameters we have passed over,
+    /// which will be considered when searching for instance arguments
+    instances: im::Vector<FreeVar<String>>,
+    /// The pairs of variant types that we are part way through comparing
+    ///
+    /// Variant types can refer to themselves in their alternatives, so while
+    /// comparing the alternatives we assume that the variant types are
+    /// related, rather than comparing them forever.
+    assumed_variants: im::Vector<(RcType, RcType)>,
+    /// The metavariables created during elaboration
+    ///
+    /// Unlike the rest of the context this is shared between copies, ensuring
+    /// that the solutions found in one scope can be seen from every other.
+    metas: Rc<RefCell<Vec<Meta>>>,
+    /// The problems that have been postponed during elaboration, in the order
+    /// they were encountered
+    ///
+    /// Like the metavariables, this is shared between copies of the context.
+    postponed: Rc<RefCell<Vec<Postponed>>>,
+    /// The constraints between universe levels that have been found during
+    /// elaboration
+    ///
+    /// Like the metavariables, this is shared between copies of the context.
+    level_constraints: Rc<RefCell<Vec<LevelConstraint>>>,
+    /// The warnings that have been produced during elaboration, in the order
+    /// they were encountered
+    ///
+    /// Like the metavariables, this is shared between copies of the context.
+    warnings: Rc<RefCell<Vec<TypeWarning>>>,
 }
 
 impl Default for Context {
     fn default() -> Context {
-        use moniker::{Embed, Scope};
-
-        use pikelet_core::syntax::core::Term;
+        use pikelet_core::syntax::domain::Closure;
+        use pikelet_core::syntax::{Multiplicity, Plicity};
 
         let var_bool = FreeVar::fresh_named("Bool");
         let var_true = FreeVar::fresh_named("true");
@@ -232,43 +394,55 @@ impl Default for Context {
         let var_f32 = FreeVar::fresh_named("F32");
         let var_f64 = FreeVar::fresh_named("F64");
         let var_array = FreeVar::fresh_named("Array");
+        let var_level = FreeVar::fresh_named("Level");
+        let var_max = FreeVar::fresh_named("max");
 
         let mut context = Context {
             resugar_env: ResugarEnv::new(),
             globals: Rc::new(Globals {
-                ty_bool: RcValue::from(Value::var(Var::Free(var_bool.clone()), 0)),
-                ty_string: RcValue::from(Value::var(Var::Free(var_string.clone()), 0)),
-                ty_char: RcValue::from(Value::var(Var::Free(var_char.clone()), 0)),
-                ty_u8: RcValue::from(Value::var(Var::Free(var_u8.clone()), 0)),
-                ty_u16: RcValue::from(Value::var(Var::Free(var_u16.clone()), 0)),
-                ty_u32: RcValue::from(Value::var(Var::Free(var_u32.clone()), 0)),
-                ty_u64: RcValue::from(Value::var(Var::Free(var_u64.clone()), 0)),
-                ty_s8: RcValue::from(Value::var(Var::Free(var_s8.clone()), 0)),
-                ty_s16: RcValue::from(Value::var(Var::Free(var_s16.clone()), 0)),
-                ty_s32: RcValue::from(Value::var(Var::Free(var_s32.clone()), 0)),
-                ty_s64: RcValue::from(Value::var(Var::Free(var_s64.clone()), 0)),
-                ty_f32: RcValue::from(Value::var(Var::Free(var_f32.clone()), 0)),
-                ty_f64: RcValue::from(Value::var(Var::Free(var_f64.clone()), 0)),
+                ty_bool: RcValue::from(Value::global(var_bool.clone(), 0)),
+                ty_string: RcValue::from(Value::global(var_string.clone(), 0)),
+                ty_char: RcValue::from(Value::global(var_char.clone(), 0)),
+                ty_u8: RcValue::from(Value::global(var_u8.clone(), 0)),
+                ty_u16: RcValue::from(Value::global(var_u16.clone(), 0)),
+                ty_u32: RcValue::from(Value::global(var_u32.clone(), 0)),
+                ty_u64: RcValue::from(Value::global(var_u64.clone(), 0)),
+                ty_s8: RcValue::from(Value::global(var_s8.clone(), 0)),
+                ty_s16: RcValue::from(Value::global(var_s16.clone(), 0)),
+                ty_s32: RcValue::from(Value::global(var_s32.clone(), 0)),
+                ty_s64: RcValue::from(Value::global(var_s64.clone(), 0)),
+                ty_f32: RcValue::from(Value::global(var_f32.clone(), 0)),
+                ty_f64: RcValue::from(Value::global(var_f64.clone(), 0)),
                 var_array: var_array.clone(),
             }),
             imports: im::HashMap::new(),
             declarations: im::HashMap::new(),
             definitions: im::HashMap::new(),
+            locals: im::Vector::new(),
+            local_values: Locals::new(),
+            local_levels: im::HashMap::new(),
+            instances: im::Vector::new(),
+            assumed_variants: im::Vector::new(),
+            metas: Rc::new(RefCell::new(Vec::new())),
+            postponed: Rc::new(RefCell::new(Vec::new())),
+            level_constraints: Rc::new(RefCell::new(Vec::new())),
+            warnings: Rc::new(RefCell::new(Vec::new())),
         };
 
         let universe0 = RcValue::from(Value::universe(0));
         let bool_ty = context.globals.ty_bool.clone();
         let bool_lit = |value| RcTerm::from(Term::Literal(Literal::Bool(value)));
-        let array_ty = RcValue::from(Value::FunType(Scope::new(
-            (
-                Binder(FreeVar::fresh_unnamed()),
-                Embed(context.globals.ty_u64.clone()),
-            ),
-            RcValue::from(Value::FunType(Scope::new(
-                (Binder(FreeVar::fresh_unnamed()), Embed(universe0.clone())),
+        let array_ty = RcValue::from(Value::FunType(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            context.globals.ty_u64.clone(),
+            Closure::constant(RcValue::from(Value::FunType(
+                Plicity::Explicit,
+                Multiplicity::Many,
                 universe0.clone(),
+                Closure::constant(universe0.clone()),
             ))),
-        )));
+        ));
 
         context.insert_declaration(var_bool, universe0.clone());
         context.insert_declaration(var_string, universe0.clone());
@@ -285,32 +459,73 @@ impl Default for Context {
         context.insert_declaration(var_f64, universe0.clone());
         context.insert_declaration(var_array, array_ty);
 
+        let level_ty = RcValue::from(Value::LevelType);
+        let level_max_ty = RcValue::from(Value::FunType(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            level_ty.clone(),
+            Closure::constant(RcValue::from(Value::FunType(
+                Plicity::Explicit,
+                Multiplicity::Many,
+                level_ty.clone(),
+                Closure::constant(level_ty.clone()),
+            ))),
+        ));
+        let level_max = RcTerm::from(Term::FunIntro(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            NameHint::from("a"),
+            RcTerm::from(Term::LevelType),
+            RcTerm::from(Term::FunIntro(
+                Plicity::Explicit,
+                Multiplicity::Many,
+                NameHint::from("b"),
+                RcTerm::from(Term::LevelType),
+                RcTerm::from(Term::LevelMax(
+                    RcTerm::from(Term::var(1, 0)),
+                    RcTerm::from(Term::var(0, 0)),
+                )),
+            )),
+        ));
+
+        context.insert_declaration(var_level.clone(), universe0.clone());
+        context.insert_definition(var_level, RcTerm::from(Term::LevelType));
+        context.insert_declaration(var_max.clone(), level_max_ty);
+        context.insert_definition(var_max, level_max);
+
         context.insert_declaration(var_true.clone(), bool_ty.clone());
         context.insert_declaration(var_false.clone(), bool_ty.clone());
         context.insert_definition(var_true, bool_lit(true));
         context.insert_definition(var_false, bool_lit(false));
 
         /// Define a primitive import
+        ///
+        /// The body of a `partial` primitive returns an `Option`, and the
+        /// application of the primitive is left unevaluated if it is `None`.
         macro_rules! prim_import {
-            ($name:expr, fn($($param_name:ident : $PType:ty),*) -> $RType:ty $body:block) => {{
+            ($name:expr, fn($($param_name:ident : $PType:ty),*) -> $RType:ty $body:block) => {
+                prim_import!($name, partial fn($($param_name : $PType),*) -> $RType { Some($body) })
+            };
+            ($name:expr, partial fn($($param_name:ident : $PType:ty),*) -> $RType:ty $body:block) => {{
                 fn interpretation<'a>(params: &'a [RcValue]) -> Option<RcValue> {
                     match params {
                         [$(ref $param_name),*] if $($param_name.is_nf())&&* => {
                             $(let $param_name = <$PType>::try_from_value_ref($param_name)?;)*
-                            Some(<$RType>::into_value($body))
+                            Some(<$RType>::into_value($body?))
                         }
                         _ => None,
                     }
                 }
 
+                let arity = <[&str]>::len(&[$(stringify!($param_name)),*]);
                 let ty = <$RType>::ty(&context);
                 $(let ty = {
-                    let param_var = FreeVar::fresh_unnamed();
                     let param_ty = <$PType>::ty(&context);
-                    RcValue::from(Value::FunType(Scope::new((Binder(param_var), Embed(param_ty)), ty)))
+                    let body = Closure::constant(ty);
+                    RcValue::from(Value::FunType(Plicity::Explicit, Multiplicity::Many, param_ty, body))
                 };)*
 
-                context.insert_import($name.to_owned(), Import::Prim(interpretation), ty);
+                context.insert_import($name.to_owned(), Import::Prim(arity, interpretation), ty);
             }};
         }
 
@@ -398,47 +613,63 @@ impl Default for Context {
         prim_import!("prim/f32/ge", fn(x: f32, y: f32) -> bool { x >= y });
         prim_import!("prim/f64/ge", fn(x: f64, y: f64) -> bool { x >= y });
 
-        prim_import!("prim/u8/add", fn(x: u8, y: u8) -> u8 { x + y });
-        prim_import!("prim/u16/add", fn(x: u16, y: u16) -> u16 { x + y });
-        prim_import!("prim/u32/add", fn(x: u32, y: u32) -> u32 { x + y });
-        prim_import!("prim/u64/add", fn(x: u64, y: u64) -> u64 { x + y });
-        prim_import!("prim/i8/add", fn(x: i8, y: i8) -> i8 { x + y });
-        prim_import!("prim/i16/add", fn(x: i16, y: i16) -> i16 { x + y });
-        prim_import!("prim/i32/add", fn(x: i32, y: i32) -> i32 { x + y });
-        prim_import!("prim/i64/add", fn(x: i64, y: i64) -> i64 { x + y });
+        prim_import!("prim/u8/add", fn(x: u8, y: u8) -> u8 { x.wrapping_add(y) });
+        prim_import!("prim/u16/add", fn(x: u16, y: u16) -> u16 { x.wrapping_add(y) });
+        prim_import!("prim/u32/add", fn(x: u32, y: u32) -> u32 { x.wrapping_add(y) });
+        prim_import!("prim/u64/add", fn(x: u64, y: u64) -> u64 { x.wrapping_add(y) });
+        prim_import!("prim/i8/add", fn(x: i8, y: i8) -> i8 { x.wrapping_add(y) });
+        prim_import!("prim/i16/add", fn(x: i16, y: i16) -> i16 { x.wrapping_add(y) });
+        prim_import!("prim/i32/add", fn(x: i32, y: i32) -> i32 { x.wrapping_add(y) });
+        prim_import!("prim/i64/add", fn(x: i64, y: i64) -> i64 { x.wrapping_add(y) });
         prim_import!("prim/f32/add", fn(x: f32, y: f32) -> f32 { x + y });
         prim_import!("prim/f64/add", fn(x: f64, y: f64) -> f64 { x + y });
 
-        prim_import!("prim/u8/sub", fn(x: u8, y: u8) -> u8 { x - y });
-        prim_import!("prim/u16/sub", fn(x: u16, y: u16) -> u16 { x - y });
-        prim_import!("prim/u32/sub", fn(x: u32, y: u32) -> u32 { x - y });
-        prim_import!("prim/u64/sub", fn(x: u64, y: u64) -> u64 { x - y });
-        prim_import!("prim/i8/sub", fn(x: i8, y: i8) -> i8 { x - y });
-        prim_import!("prim/i16/sub", fn(x: i16, y: i16) -> i16 { x - y });
-        prim_import!("prim/i32/sub", fn(x: i32, y: i32) -> i32 { x - y });
-        prim_import!("prim/i64/sub", fn(x: i64, y: i64) -> i64 { x - y });
+        prim_import!("prim/u8/sub", fn(x: u8, y: u8) -> u8 { x.wrapping_sub(y) });
+        prim_import!("prim/u16/sub", fn(x: u16, y: u16) -> u16 { x.wrapping_sub(y) });
+        prim_import!("prim/u32/sub", fn(x: u32, y: u32) -> u32 { x.wrapping_sub(y) });
+        prim_import!("prim/u64/sub", fn(x: u64, y: u64) -> u64 { x.wrapping_sub(y) });
+        prim_import!("prim/i8/sub", fn(x: i8, y: i8) -> i8 { x.wrapping_sub(y) });
+        prim_import!("prim/i16/sub", fn(x: i16, y: i16) -> i16 { x.wrapping_sub(y) });
+        prim_import!("prim/i32/sub", fn(x: i32, y: i32) -> i32 { x.wrapping_sub(y) });
+        prim_import!("prim/i64/sub", fn(x: i64, y: i64) -> i64 { x.wrapping_sub(y) });
         prim_import!("prim/f32/sub", fn(x: f32, y: f32) -> f32 { x - y });
         prim_import!("prim/f64/sub", fn(x: f64, y: f64) -> f64 { x - y });
 
-        prim_import!("prim/u8/mul", fn(x: u8, y: u8) -> u8 { x * y });
-        prim_import!("prim/u16/mul", fn(x: u16, y: u16) -> u16 { x * y });
-        prim_import!("prim/u32/mul", fn(x: u32, y: u32) -> u32 { x * y });
-        prim_import!("prim/u64/mul", fn(x: u64, y: u64) -> u64 { x * y });
-        prim_import!("prim/i8/mul", fn(x: i8, y: i8) -> i8 { x * y });
-        prim_import!("prim/i16/mul", fn(x: i16, y: i16) -> i16 { x * y });
-        prim_import!("prim/i32/mul", fn(x: i32, y: i32) -> i32 { x * y });
-        prim_import!("prim/i64/mul", fn(x: i64, y: i64) -> i64 { x * y });
+        prim_import!("prim/u8/mul", fn(x: u8, y: u8) -> u8 { x.wrapping_mul(y) });
+        prim_import!("prim/u16/mul", fn(x: u16, y: u16) -> u16 { x.wrapping_mul(y) });
+        prim_import!("prim/u32/mul", fn(x: u32, y: u32) -> u32 { x.wrapping_mul(y) });
+        prim_import!("prim/u64/mul", fn(x: u64, y: u64) -> u64 { x.wrapping_mul(y) });
+        prim_import!("prim/i8/mul", fn(x: i8, y: i8) -> i8 { x.wrapping_mul(y) });
+        prim_import!("prim/i16/mul", fn(x: i16, y: i16) -> i16 { x.wrapping_mul(y) });
+        prim_import!("prim/i32/mul", fn(x: i32, y: i32) -> i32 { x.wrapping_mul(y) });
+        prim_import!("prim/i64/mul", fn(x: i64, y: i64) -> i64 { x.wrapping_mul(y) });
         prim_import!("prim/f32/mul", fn(x: f32, y: f32) -> f32 { x * y });
         prim_import!("prim/f64/mul", fn(x: f64, y: f64) -> f64 { x * y });
 
-        prim_import!("prim/u8/div", fn(x: u8, y: u8) -> u8 { x / y });
-        prim_import!("prim/u16/div", fn(x: u16, y: u16) -> u16 { x / y });
-        prim_import!("prim/u32/div", fn(x: u32, y: u32) -> u32 { x / y });
-        prim_import!("prim/u64/div", fn(x: u64, y: u64) -> u64 { x / y });
-        prim_import!("prim/i8/div", fn(x: i8, y: i8) -> i8 { x / y });
-        prim_import!("prim/i16/div", fn(x: i16, y: i16) -> i16 { x / y });
-        prim_import!("prim/i32/div", fn(x: i32, y: i32) -> i32 { x / y });
-        prim_import!("prim/i64/div", fn(x: i64, y: i64) -> i64 { x / y });
+        prim_import!("prim/u8/div", partial fn(x: u8, y: u8) -> u8 {
+            if y == 0 { None } else { Some(x.wrapping_div(y)) }
+        });
+        prim_import!("prim/u16/div", partial fn(x: u16, y: u16) -> u16 {
+            if y == 0 { None } else { Some(x.wrapping_div(y)) }
+        });
+        prim_import!("prim/u32/div", partial fn(x: u32, y: u32) -> u32 {
+            if y == 0 { None } else { Some(x.wrapping_div(y)) }
+        });
+        prim_import!("prim/u64/div", partial fn(x: u64, y: u64) -> u64 {
+            if y == 0 { None } else { Some(x.wrapping_div(y)) }
+        });
+        prim_import!("prim/i8/div", partial fn(x: i8, y: i8) -> i8 {
+            if y == 0 { None } else { Some(x.wrapping_div(y)) }
+        });
+        prim_import!("prim/i16/div", partial fn(x: i16, y: i16) -> i16 {
+            if y == 0 { None } else { Some(x.wrapping_div(y)) }
+        });
+        prim_import!("prim/i32/div", partial fn(x: i32, y: i32) -> i32 {
+            if y == 0 { None } else { Some(x.wrapping_div(y)) }
+        });
+        prim_import!("prim/i64/div", partial fn(x: i64, y: i64) -> i64 {
+            if y == 0 { None } else { Some(x.wrapping_div(y)) }
+        });
         prim_import!("prim/f32/div", fn(x: f32, y: f32) -> f32 { x / y });
         prim_import!("prim/f64/div", fn(x: f64, y: f64) -> f64 { x / y });
 
@@ -466,9 +697,8 @@ impl Context {
     }
 
     pub fn mappings(&self) -> im::HashMap<String, FreeVar<String>> {
-        self.declarations
-            .iter()
-            .filter_map(|(free_var, _)| {
+        Iterator::chain(self.declarations.keys(), self.local_levels.keys())
+            .filter_map(|free_var| {
                 let pretty_name = free_var.pretty_name.as_ref()?;
                 Some((pretty_name.clone(), free_var.clone()))
             })
@@ -528,9 +758,7 @@ impl Context {
     }
 
     pub fn array<'a>(&self, ty: &'a RcType) -> Option<(u64, &'a RcType)> {
-        use pikelet_core::syntax::LevelShift;
-
-        match ty.free_var_app() {
+        match ty.global_app() {
             // Conservatively forcing the shift to be zero for now. Perhaps this
             // could be relaxed in the future if it becomes a problem?
             Some((fv, LevelShift(0), &[ref len, ref elem_ty])) if *fv == self.globals.var_array => {
@@ -559,15 +787,280 @@ impl Context {
         self.imports.insert(name, (import, ty));
     }
 
+    /// Insert the declaration of a global variable
     pub fn insert_declaration(&mut self, free_var: FreeVar<String>, ty: RcType) {
-        self.resugar_env.on_binder(&Binder(free_var.clone()));
+        self.resugar_env.on_global(&free_var);
         self.declarations.insert(free_var, ty);
     }
 
+    /// Insert the definition of a global variable
+    ///
+    /// The definition must not refer to any local variables.
     pub fn insert_definition(&mut self, free_var: FreeVar<String>, term: RcTerm) {
-        self.resugar_env.on_binder(&Binder(free_var.clone()));
+        self.resugar_env.on_global(&free_var);
         self.definitions.insert(free_var, term);
     }
+
+    /// The number of local variables in scope
+    pub fn size(&self) -> usize {
+        self.locals.len()
+    }
+
+    /// The values of the local variables in scope
+    pub fn local_values(&self) -> &Locals {
+        &self.local_values
+    }
+
+    /// A neutral value for the next local variable to be inserted
+    pub fn fresh_var(&self) -> RcValue {
+        RcValue::from(Value::var(self.size(), 0))
+    }
+
+    /// Look up the index and type of a local variable that has been declared
+    pub fn get_local(&self, free_var: &FreeVar<String>) -> Option<(VarIndex, &RcType)> {
+        let level = *self.local_levels.get(free_var)?;
+        let ty = self.locals[level.0].ty.as_ref()?;
+        Some((level.to_index(self.size())?, ty))
+    }
+
+    /// Look up a variable that is in scope, returning a term that refers to it
+    /// along with its type
+    pub fn lookup(
+        &self,
+        free_var: &FreeVar<String>,
+        shift: LevelShift,
+    ) -> Option<(RcTerm, &RcType)> {
+        match self.get_local(free_var) {
+            Some((index, ty)) => Some((RcTerm::from(Term::Var(index, shift)), ty)),
+            None => {
+                let ty = self.get_declaration(free_var)?;
+                Some((RcTerm::from(Term::Global(free_var.clone(), shift)), ty))
+            },
+        }
+    }
+
+    /// Look up the type of a local variable using its index
+    pub fn get_local_ty(&self, index: VarIndex) -> Option<&RcType> {
+        let level = index.to_level(self.size())?;
+        self.locals[level.0].ty.as_ref()
+    }
+
+    /// Look up the value of a local variable using its index
+    pub fn get_local_value(&self, index: VarIndex) -> Option<&RcValue> {
+        let level = index.to_level(self.size())?;
+        Some(&self.local_values[level.0])
+    }
+
+    /// The indices of the local variables that are bound by function
+    /// parameters or patterns, in the order that they were introduced
+    pub fn bound_locals(&self) -> impl Iterator<Item = VarIndex> + '_ {
+        let size = self.size();
+        self.locals
+            .iter()
+            .enumerate()
+            .filter(|&(_, local)| local.is_bound)
+            .map(move |(level, _)| VarIndex(size - level - 1))
+    }
+
+    fn push_local(&mut self, name: &NameHint, ty: Option<RcType>, is_bound: bool) {
+        let value = self.fresh_var();
+        self.resugar_env.on_local(name);
+        self.locals.push_back(Local { ty, is_bound });
+        self.local_values.push_back(value);
+    }
+
+    /// Insert the declaration of a locally bound variable, like a function
+    /// parameter or a variable bound by a pattern
+    pub fn insert_local(&mut self, free_var: FreeVar<String>, ty: RcType) {
+        self.local_levels
+            .insert(free_var.clone(), VarLevel(self.size()));
+        self.push_local(&NameHint::from(&free_var), Some(ty), true);
+    }
+
+    /// Insert a locally bound variable that can't be referred to by name,
+    /// like the parameters introduced when comparing the bodies of functions
+    pub fn insert_fresh_local(&mut self, name: NameHint, ty: RcType) {
+        self.push_local(&name, Some(ty), true);
+    }
+
+    /// Insert the local variables of a group of let bindings, without
+    /// declaring them, returning the level of the first one
+    ///
+    /// The variables remain neutral until they are given definitions using
+    /// `define_local`. Metavariables are not applied to them, so solutions
+    /// can't refer to a binding before it has been defined.
+    pub fn insert_let_locals(&mut self, free_vars: &[FreeVar<String>]) -> VarLevel {
+        let first = VarLevel(self.size());
+        for free_var in free_vars {
+            self.local_levels
+                .insert(free_var.clone(), VarLevel(self.size()));
+            self.push_local(&NameHint::from(free_var), None, false);
+        }
+        first
+    }
+
+    /// Declare the type of a local variable that was inserted using
+    /// `insert_let_locals`
+    pub fn declare_local(&mut self, level: VarLevel, ty: RcType) {
+        self.locals[level.0].ty = Some(ty);
+    }
+
+    /// Give a definition to a local variable that was inserted using
+    /// `insert_let_locals`
+    ///
+    /// The value is glued to the variable, so that it can be displayed using
+    /// the name of the variable rather than its definition.
+    pub fn define_local(&mut self, level: VarLevel, value: RcValue) {
+        let folded = Folded::new(FoldedHead::Var(level, LevelShift(0)));
+        self.local_values[level.0] = value.with_folded(folded);
+    }
+
+    /// Evaluate a term in this context
+    pub fn eval(&self, term: &RcTerm) -> Result<RcValue, nbe::NbeError> {
+        nbe::eval(self, &self.local_values, term)
+    }
+
+    /// Read back a value in this context
+    pub fn quote(&self, value: &RcValue) -> Result<RcTerm, nbe::NbeError> {
+        nbe::quote(self, self.size(), value)
+    }
+
+    /// The candidates for instance arguments that are in scope, in the order
+    /// they were introduced
+    pub fn instances(&self) -> &im::Vector<FreeVar<String>> {
+        &self.instances
+    }
+
+    /// Make a declared variable available as a candidate when searching for
+    /// instance arguments
+    pub fn insert_instance(&mut self, free_var: FreeVar<String>) {
+        self.instances.push_back(free_var);
+    }
+
+    /// Assume that two variant types are related while comparing their
+    /// alternatives
+    pub fn assume_variants(&mut self, ty1: RcType, ty2: RcType) {
+        self.assumed_variants.push_back((ty1, ty2));
+    }
+
+    /// Returns `true` if two variant types are already being compared
+    pub fn is_assumed_variants(&self, ty1: &RcType, ty2: &RcType) -> bool {
+        self.assumed_variants
+            .iter()
+            .any(|&(ref assumed1, ref assumed2)| assumed1 == ty1 && assumed2 == ty2)
+    }
+
+    /// Create a fresh, unsolved metavariable
+    pub fn create_meta(&self, origin: MetaOrigin, expected_ty: Option<&RcType>) -> MetaVar {
+        let mut metas = self.metas.borrow_mut();
+        let meta = MetaVar(metas.len() as u32);
+
+        metas.push(Meta {
+            origin,
+            expected: expected_ty.map(|ty| {
+                let locals = self.local_values.clone();
+                (self.resugar_env.clone(), locals, ty.clone())
+            }),
+            solution: None,
+        });
+
+        meta
+    }
+
+    pub fn get_meta_origin(&self, meta: MetaVar) -> MetaOrigin {
+        self.metas.borrow()[meta.0 as usize].origin.clone()
+    }
+
+    /// Resugar the expected type of a metavariable, if it is known
+    pub fn get_meta_expected(&self, meta: MetaVar) -> Option<concrete::Term> {
+        let metas = self.metas.borrow();
+        let (ref resugar_env, ref locals, ref ty) = *metas[meta.0 as usize].expected.as_ref()?;
+        // Fill in the solutions we might have found since the metavariable was
+        // created, falling back to the original type if this fails
+        match nbe::quote(self, locals.len(), ty).and_then(|ty| nbe::nf(self, locals, &ty)) {
+            Ok(ty) => Some(ty.resugar(resugar_env)),
+            Err(_) => Some(ty.resugar(resugar_env)),
+        }
+    }
+
+    pub fn get_meta_solution(&self, meta: MetaVar) -> Option<RcTerm> {
+        self.metas.borrow()[meta.0 as usize].solution.clone()
+    }
+
+    pub fn insert_meta_solution(&self, meta: MetaVar, solution: RcTerm) {
+        self.metas.borrow_mut()[meta.0 as usize].solution = Some(solution);
+    }
+
+    /// The universe level metavariables that have yet to be solved
+    pub fn unsolved_level_metas(&self) -> Vec<MetaVar> {
+        let metas = self.metas.borrow();
+        (0..metas.len())
+            .filter(|&index| match metas[index] {
+                Meta {
+                    origin: MetaOrigin::UniverseLevel(_),
+                    solution: None,
+                    ..
+                } => true,
+                _ => false,
+            })
+            .map(|index| MetaVar(index as u32))
+            .collect()
+    }
+
+    /// Record the current solutions to the metavariables
+    pub fn snapshot_metas(&self) -> MetaSnapshot {
+        let metas = self.metas.borrow();
+        MetaSnapshot(
+            metas.iter().map(|meta| meta.solution.clone()).collect(),
+            self.level_constraints.borrow().len(),
+        )
+    }
+
+    /// Undo any solutions and level constraints that were found since the
+    /// snapshot was taken
+    pub fn rollback_metas(&self, snapshot: MetaSnapshot) {
+        let mut metas = self.metas.borrow_mut();
+        for (meta, solution) in Iterator::zip(metas.iter_mut(), snapshot.0) {
+            meta.solution = solution;
+        }
+        self.level_constraints.borrow_mut().truncate(snapshot.1);
+    }
+
+    /// Record that the universe level `lhs` must be at most `rhs`
+    pub fn add_level_constraint(&self, lhs: LevelValue, rhs: LevelValue) {
+        self.level_constraints
+            .borrow_mut()
+            .push(LevelConstraint { lhs, rhs });
+    }
+
+    /// Take the level constraints that have been found so far, leaving none
+    /// behind
+    pub fn take_level_constraints(&self) -> Vec<LevelConstraint> {
+        self.level_constraints.replace(Vec::new())
+    }
+
+    /// Postpone a problem until the end of elaboration
+    pub fn postpone(&self, problem: Postponed) {
+        self.postponed.borrow_mut().push(problem);
+    }
+
+    /// Take the problems that have been postponed so far, leaving none behind
+    pub fn take_postponed(&self) -> Vec<Postponed> {
+        self.postponed.replace(Vec::new())
+    }
+
+    /// Record a warning, ignoring it if it has already been recorded
+    pub fn warn(&self, warning: TypeWarning) {
+        let mut warnings = self.warnings.borrow_mut();
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    }
+
+    /// Take the warnings that have been produced so far, leaving none behind
+    pub fn take_warnings(&self) -> Vec<TypeWarning> {
+        self.warnings.replace(Vec::new())
+    }
 }
 
 impl nbe::Env for Context {
@@ -578,4 +1071,12 @@ impl nbe::Env for Context {
     fn get_definition(&self, free_var: &FreeVar<String>) -> Option<&RcTerm> {
         self.definitions.get(free_var)
     }
+
+    fn get_meta_solution(&self, meta: MetaVar) -> Option<RcTerm> {
+        Context::get_meta_solution(self, meta)
+    }
+
+    fn unfold_fix(&self, totality: Totality) -> bool {
+        totality == Totality::Total
+    }
 }
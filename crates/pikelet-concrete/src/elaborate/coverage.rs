@@ -0,0 +1,396 @@
+//! Exhaustiveness and redundancy checking for case expressions
+//!
+//! This is an implementation of the usefulness algorithm described in
+//! [Warnings for pattern matching](http://moscova.inria.fr/~maranget/papers/warn/index.html)
+//! by Luc Maranget. Each clause of a case expression is a row in a _pattern
+//! matrix_, and we ask whether a given row of patterns is _useful_ with
+//! respect to the rows before it - that is, whether there is a value that it
+//! matches, but that none of the previous rows match:
+//!
+//! - a clause that is not useful with respect to the clauses before it is
+//!   redundant, and will never be selected at runtime
+//! - a case expression is exhaustive if a wildcard pattern is not useful
+//!   with respect to all of its clauses
+//!
+//! When a wildcard is useful we also construct a _witness_ - an example of a
+//! pattern that is not covered by any of the clauses - to help explain the
+//! error to the programmer.
+
+use moniker::FreeVar;
+
+use pikelet_core::nbe;
+use pikelet_core::syntax::core::{Pattern, RcPattern};
+use pikelet_core::syntax::domain::{RcType, RcValue, Value};
+use pikelet_core::syntax::{Label, LevelShift, Literal, NameHint, VarIndex};
+
+use super::{unify, Context, TypeError};
+
+/// The head of a pattern that can be distinguished from other values at
+/// runtime
+#[derive(Debug, Clone, PartialEq)]
+enum Constructor {
+    /// A variant with the given label, containing a single sub-pattern
+    Variant(Label),
+    /// A literal value
+    Literal(Literal),
+    /// A local variable that is matched by equality with its value, but whose
+    /// value could not be reduced to a literal during elaboration
+    Var(VarIndex),
+    /// A global variable that is matched by equality with its definition, but
+    /// whose value could not be reduced to a literal during elaboration
+    Global(FreeVar<String>),
+    /// A record with the given fields, containing a sub-pattern for each field
+    Record(Vec<Label>),
+    /// An array of the given length, containing a sub-pattern for each element
+    Array(usize),
+}
+
+impl Constructor {
+    /// The number of sub-patterns that the constructor contains
+    fn arity(&self) -> usize {
+        match *self {
+            Constructor::Variant(_) => 1,
+            Constructor::Literal(_) | Constructor::Var(_) | Constructor::Global(_) => 0,
+            Constructor::Record(ref labels) => labels.len(),
+            Constructor::Array(len) => len,
+        }
+    }
+}
+
+/// A simplified pattern, with binders and annotations erased
+#[derive(Debug, Clone)]
+enum Pat {
+    /// A pattern that matches any value
+    Wildcard,
+    /// A pattern that matches a constructor applied to some sub-patterns
+    Constructor(Constructor, Vec<Pat>),
+}
+
+impl Pat {
+    /// Convert an elaborated pattern to a simplified pattern, resolving
+    /// variable patterns to the literals they are defined as, if possible
+    fn from_pattern(context: &Context, pattern: &RcPattern) -> Result<Pat, TypeError> {
+        match *pattern.inner {
+            Pattern::Ann(ref pattern, _) => Pat::from_pattern(context, pattern),
+            Pattern::Binder(_) => Ok(Pat::Wildcard),
+            Pattern::Var(index, _) => {
+                if let Some(value) = context.get_local_value(index) {
+                    if let Value::Literal(ref literal) = *value.inner {
+                        let constructor = Constructor::Literal(literal.clone());
+                        return Ok(Pat::Constructor(constructor, vec![]));
+                    }
+                }
+
+                Ok(Pat::Constructor(Constructor::Var(index), vec![]))
+            },
+            Pattern::Global(ref free_var, _) => {
+                if let Some(term) = context.get_definition(free_var) {
+                    if let Value::Literal(ref literal) = *nbe::eval_term(context, term)?.inner {
+                        let constructor = Constructor::Literal(literal.clone());
+                        return Ok(Pat::Constructor(constructor, vec![]));
+                    }
+                }
+
+                Ok(Pat::Constructor(
+                    Constructor::Global(free_var.clone()),
+                    vec![],
+                ))
+            },
+            Pattern::Literal(ref literal) => Ok(Pat::Constructor(
+                Constructor::Literal(literal.clone()),
+                vec![],
+            )),
+            Pattern::VariantIntro(ref label, ref pattern) => {
+                let pattern = Pat::from_pattern(context, pattern)?;
+                Ok(Pat::Constructor(
+                    Constructor::Variant(label.clone()),
+                    vec![pattern],
+                ))
+            },
+            Pattern::RecordIntro(ref fields) => {
+                let labels = fields.iter().map(|&(ref label, _)| label.clone()).collect();
+                let pats = fields
+                    .iter()
+                    .map(|&(_, ref pattern)| Pat::from_pattern(context, pattern))
+                    .collect::<Result<_, _>>()?;
+
+                Ok(Pat::Constructor(Constructor::Record(labels), pats))
+            },
+            Pattern::ArrayIntro(ref patterns) => {
+                let pats = patterns
+                    .iter()
+                    .map(|pattern| Pat::from_pattern(context, pattern))
+                    .collect::<Result<_, _>>()?;
+
+                Ok(Pat::Constructor(Constructor::Array(patterns.len()), pats))
+            },
+        }
+    }
+
+    /// Convert a simplified pattern back into an elaborated pattern, for use
+    /// in error messages
+    fn to_pattern(&self) -> RcPattern {
+        match *self {
+            Pat::Wildcard => RcPattern::from(Pattern::Binder(NameHint::from("_"))),
+            Pat::Constructor(Constructor::Variant(ref label), ref pats) => {
+                RcPattern::from(Pattern::VariantIntro(label.clone(), pats[0].to_pattern()))
+            },
+            Pat::Constructor(Constructor::Literal(ref literal), _) => {
+                RcPattern::from(Pattern::Literal(literal.clone()))
+            },
+            Pat::Constructor(Constructor::Var(index), _) => {
+                RcPattern::from(Pattern::Var(index, LevelShift(0)))
+            },
+            Pat::Constructor(Constructor::Global(ref free_var), _) => {
+                RcPattern::from(Pattern::Global(free_var.clone(), LevelShift(0)))
+            },
+            Pat::Constructor(Constructor::Record(ref labels), ref pats) => {
+                RcPattern::from(Pattern::RecordIntro(
+                    <_>::zip(labels.iter().cloned(), pats.iter().map(Pat::to_pattern)).collect(),
+                ))
+            },
+            Pat::Constructor(Constructor::Array(_), ref pats) => RcPattern::from(
+                Pattern::ArrayIntro(pats.iter().map(Pat::to_pattern).collect()),
+            ),
+        }
+    }
+}
+
+/// The types of the columns of a pattern matrix, if they are known
+type Types = [Option<RcType>];
+
+/// Returns the full list of constructors that inhabit the given type, along
+/// with the types of their sub-patterns, or `None` if the constructors can't
+/// be enumerated
+fn constructors(
+    context: &Context,
+    ty: &Option<RcType>,
+) -> Result<Option<Vec<(Constructor, Vec<Option<RcType>>)>>, TypeError> {
+    let ty = match *ty {
+        Some(ref ty) => unify::force(context, ty)?,
+        None => return Ok(None),
+    };
+
+    if let Value::VariantType(ref alts) = *ty.inner {
+        return Ok(Some(
+            alts.alts
+                .iter()
+                .map(|&(ref label, ref alt_ann)| {
+                    let alt_ty = nbe::eval(context, &alts.locals, alt_ann)?;
+                    Ok((Constructor::Variant(label.clone()), vec![Some(alt_ty)]))
+                })
+                .collect::<Result<_, TypeError>>()?,
+        ));
+    }
+
+    if let Value::RecordType(ref telescope) = *ty.inner {
+        // The fields that other fields depend on are left as variables, so we
+        // won't be able to enumerate the constructors of dependent fields
+        let mut locals = telescope.locals.clone();
+        let mut labels = Vec::new();
+        let mut tys = Vec::new();
+        for (i, &(ref label, _, _, ref ann)) in telescope.fields.iter().enumerate() {
+            labels.push(label.clone());
+            tys.push(Some(nbe::eval(context, &locals, ann)?));
+            locals.push_back(RcValue::from(Value::var(context.size() + i, 0)));
+        }
+
+        return Ok(Some(vec![(Constructor::Record(labels), tys)]));
+    }
+
+    if let Some((len, elem_ty)) = context.array(&ty) {
+        let len = len as usize;
+        return Ok(Some(vec![(
+            Constructor::Array(len),
+            vec![Some(elem_ty.clone()); len],
+        )]));
+    }
+
+    if context.bool() == &ty {
+        return Ok(Some(vec![
+            (Constructor::Literal(Literal::Bool(true)), vec![]),
+            (Constructor::Literal(Literal::Bool(false)), vec![]),
+        ]));
+    }
+
+    Ok(None)
+}
+
+/// Returns the types of the sub-patterns of a constructor
+fn sub_types(
+    context: &Context,
+    ty: &Option<RcType>,
+    constructor: &Constructor,
+) -> Result<Vec<Option<RcType>>, TypeError> {
+    if let Some(constructors) = constructors(context, ty)? {
+        for (candidate, sub_tys) in constructors {
+            if candidate == *constructor {
+                return Ok(sub_tys);
+            }
+        }
+    }
+
+    Ok(vec![None; constructor.arity()])
+}
+
+/// Keep the rows of the matrix whose first column could match the
+/// constructor, replacing the first column with the sub-patterns
+fn specialize(matrix: &[Vec<Pat>], constructor: &Constructor) -> Vec<Vec<Pat>> {
+    matrix
+        .iter()
+        .filter_map(|row| {
+            let pats = match row[0] {
+                Pat::Wildcard => vec![Pat::Wildcard; constructor.arity()],
+                Pat::Constructor(ref head, ref pats) if head == constructor => pats.clone(),
+                Pat::Constructor(_, _) => return None,
+            };
+
+            Some(pats.into_iter().chain(row[1..].iter().cloned()).collect())
+        })
+        .collect()
+}
+
+/// Keep the rows of the matrix whose first column is a wildcard, removing
+/// the first column
+fn default(matrix: &[Vec<Pat>]) -> Vec<Vec<Pat>> {
+    matrix
+        .iter()
+        .filter_map(|row| match row[0] {
+            Pat::Wildcard => Some(row[1..].to_vec()),
+            Pat::Constructor(_, _) => None,
+        })
+        .collect()
+}
+
+/// Rebuild a witness for a row, after it was found for the row specialized
+/// to the given constructor
+fn unspecialize(constructor: &Constructor, mut witness: Vec<Pat>) -> Vec<Pat> {
+    let rest = witness.split_off(constructor.arity());
+    let head = Pat::Constructor(constructor.clone(), witness);
+
+    std::iter::once(head).chain(rest).collect()
+}
+
+/// Check if `row` matches some value that none of the rows of `matrix`
+/// match, returning an example of such a value if it does
+fn is_useful(
+    context: &Context,
+    matrix: &[Vec<Pat>],
+    row: &[Pat],
+    tys: &Types,
+) -> Result<Option<Vec<Pat>>, TypeError> {
+    if row.is_empty() {
+        return Ok(if matrix.is_empty() {
+            Some(vec![])
+        } else {
+            None
+        });
+    }
+
+    match row[0] {
+        Pat::Constructor(ref constructor, ref pats) => {
+            let matrix = specialize(matrix, constructor);
+            let row = pats.iter().chain(&row[1..]).cloned().collect::<Vec<_>>();
+            let tys = sub_types(context, &tys[0], constructor)?
+                .into_iter()
+                .chain(tys[1..].iter().cloned())
+                .collect::<Vec<_>>();
+
+            Ok(is_useful(context, &matrix, &row, &tys)?
+                .map(|witness| unspecialize(constructor, witness)))
+        },
+        Pat::Wildcard => {
+            let mut used = Vec::new();
+            for matrix_row in matrix {
+                if let Pat::Constructor(ref constructor, _) = matrix_row[0] {
+                    if !used.contains(constructor) {
+                        used.push(constructor.clone());
+                    }
+                }
+            }
+
+            let all = constructors(context, &tys[0])?;
+
+            // If every constructor of the type is mentioned in the first
+            // column, the row is useful only if it is useful for one of them
+            if let Some(ref all) = all {
+                if all
+                    .iter()
+                    .all(|&(ref constructor, _)| used.contains(constructor))
+                {
+                    for &(ref constructor, ref sub_tys) in all {
+                        let matrix = specialize(matrix, constructor);
+                        let row = (vec![Pat::Wildcard; constructor.arity()].into_iter())
+                            .chain(row[1..].iter().cloned())
+                            .collect::<Vec<_>>();
+                        let tys = (sub_tys.iter().cloned())
+                            .chain(tys[1..].iter().cloned())
+                            .collect::<Vec<_>>();
+
+                        if let Some(witness) = is_useful(context, &matrix, &row, &tys)? {
+                            return Ok(Some(unspecialize(constructor, witness)));
+                        }
+                    }
+
+                    return Ok(None);
+                }
+            }
+
+            // Otherwise some values will fall through to the rows that
+            // begin with wildcards
+            let witness = match is_useful(context, &default(matrix), &row[1..], &tys[1..])? {
+                Some(witness) => witness,
+                None => return Ok(None),
+            };
+
+            let missing = match all {
+                Some(ref all) if !used.is_empty() => all
+                    .iter()
+                    .find(|&&(ref constructor, _)| !used.contains(constructor))
+                    .map(|&(ref constructor, _)| {
+                        Pat::Constructor(
+                            constructor.clone(),
+                            vec![Pat::Wildcard; constructor.arity()],
+                        )
+                    })
+                    .unwrap_or(Pat::Wildcard),
+                _ => Pat::Wildcard,
+            };
+
+            Ok(Some(std::iter::once(missing).chain(witness).collect()))
+        },
+    }
+}
+
+/// The result of checking the clauses of a case expression
+pub struct Coverage {
+    /// The indices of the clauses that will never be matched
+    pub redundant: Vec<usize>,
+    /// A pattern that is not matched by any of the clauses, if the clauses
+    /// are not exhaustive
+    pub missing: Option<RcPattern>,
+}
+
+/// Check the clauses of a case expression whose head has the type `head_ty`
+pub fn check_clauses(
+    context: &Context,
+    head_ty: &RcType,
+    patterns: &[RcPattern],
+) -> Result<Coverage, TypeError> {
+    let tys = [Some(head_ty.clone())];
+    let mut matrix = Vec::with_capacity(patterns.len());
+    let mut redundant = Vec::new();
+
+    for (index, pattern) in patterns.iter().enumerate() {
+        let row = vec![Pat::from_pattern(context, pattern)?];
+        if is_useful(context, &matrix, &row, &tys)?.is_none() {
+            redundant.push(index);
+        }
+        matrix.push(row);
+    }
+
+    let missing =
+        is_useful(context, &matrix, &[Pat::Wildcard], &tys)?.map(|witness| witness[0].to_pattern());
+
+    Ok(Coverage { redundant, missing })
+}
@@ -0,0 +1,449 @@
+//! Compilation of case expressions to decision trees
+//!
+//! The clauses of a case expression can contain nested patterns, which are
+//! tried against the head of the expression one clause at a time. Once
+//! elaboration is complete we compile each case expression into a tree of
+//! simpler case expressions, each of which only looks at the outermost
+//! constructor of a single value. This means that no part of the head is
+//! tested more than once, and that matching can make progress on the parts of
+//! the head that are known even when other parts are stuck.
+//!
+//! The compilation scheme follows [Compiling pattern matching to good decision trees](http://moscova.inria.fr/~maranget/papers/ml05e-maranget.pdf)
+//! by Luc Maranget, always testing the leftmost pattern of the first clause
+//! that has yet to be tested:
+//!
+//! - record patterns are irrefutable, so they are compiled to projections on
+//!   the value being matched, rather than to case expressions
+//! - binder patterns are compiled to let bindings around the body of the
+//!   clause they came from, or are replaced with the variable they match if
+//!   it is already in scope
+//! - any other pattern is compiled to a case expression with a clause for
+//!   each constructor that is tested at that point, followed by a fallback
+//!   clause for the remaining clauses that don't test it
+//!
+//! The bodies of clauses might end up being duplicated in more than one
+//! branch of the tree.
+//!
+//! The tree introduces variables of its own, so the variables in the bodies
+//! and patterns of the clauses are adjusted as they are moved into it. To make
+//! this easier, the parts of the head are referred to by the levels of the
+//! variables they are found in.
+
+use pikelet_core::nbe;
+use pikelet_core::syntax::core::{Pattern, RcPattern, RcTerm, Term};
+use pikelet_core::syntax::domain::Value;
+use pikelet_core::syntax::{
+    Label, LevelShift, Literal, Multiplicity, NameHint, VarIndex, VarLevel,
+};
+
+use super::{Context, TypeError};
+
+/// A part of the head of a case expression, found by projecting fields from a
+/// local variable
+#[derive(Debug, Clone, PartialEq)]
+struct Occurrence {
+    level: VarLevel,
+    path: Vec<Label>,
+}
+
+impl Occurrence {
+    fn new(level: VarLevel) -> Occurrence {
+        Occurrence {
+            level,
+            path: Vec::new(),
+        }
+    }
+
+    fn field(&self, label: &Label) -> Occurrence {
+        let mut path = self.path.clone();
+        path.push(label.clone());
+        Occurrence {
+            level: self.level,
+            path,
+        }
+    }
+
+    /// A term that refers to the occurrence, in a scope of `size` local
+    /// variables
+    ///
+    /// NOTE: Patterns don't remember the multiplicities of the fields that
+    /// they match, but fields with a multiplicity of zero can only be matched
+    /// with wildcards, so their projections are never used at runtime
+    fn to_term(&self, size: usize) -> RcTerm {
+        let index = self.level.to_index(size).expect("occurrence out of scope");
+        let var = RcTerm::from(Term::Var(index, LevelShift(0)));
+
+        self.path.iter().fold(var, |expr, label| {
+            RcTerm::from(Term::RecordProj(
+                expr,
+                label.clone(),
+                Multiplicity::Many,
+                LevelShift(0),
+            ))
+        })
+    }
+}
+
+/// A clause that has yet to be compiled
+#[derive(Debug, Clone)]
+struct Row {
+    /// The tests that remain to be performed before the body can be selected,
+    /// pairing a part of the head with the pattern that it needs to match,
+    /// and the position of the first variable that the pattern binds among
+    /// the variables bound by the clause
+    tests: Vec<(Occurrence, RcPattern, usize)>,
+    /// The parts of the head that the variables bound by the clause were
+    /// matched with by the tests performed so far
+    bindings: Vec<Option<Occurrence>>,
+    /// The names of the variables bound by the clause
+    names: Vec<NameHint>,
+    /// The body of the clause
+    body: RcTerm,
+}
+
+impl Row {
+    /// Remove the tests that can't fail, recording any variables they bind
+    fn simplify(self, context: &Context, size: usize) -> Result<Row, TypeError> {
+        let mut pending = self.tests;
+        pending.reverse();
+        let mut tests = Vec::with_capacity(pending.len());
+        let mut bindings = self.bindings;
+
+        while let Some((occurrence, pattern, binder)) = pending.pop() {
+            match *pattern.inner {
+                Pattern::Ann(ref pattern, _) => {
+                    pending.push((occurrence, pattern.clone(), binder));
+                },
+                Pattern::Binder(_) => bindings[binder] = Some(occurrence),
+                Pattern::RecordIntro(ref fields) => {
+                    let mut field_binder = binder;
+                    let mut field_tests = Vec::with_capacity(fields.len());
+                    for &(ref label, ref pattern) in fields {
+                        field_tests.push((occurrence.field(label), pattern.clone(), field_binder));
+                        field_binder += pattern.binders();
+                    }
+                    pending.extend(field_tests.into_iter().rev());
+                },
+                Pattern::Var(_, _) | Pattern::Global(_, _) => {
+                    // Variables that are defined as literals can be tested
+                    // alongside the other literals
+                    match constant_literal(context, size, &pattern)? {
+                        Some(literal) => {
+                            let pattern = RcPattern::from(Pattern::Literal(literal));
+                            tests.push((occurrence, pattern, binder));
+                        },
+                        None => tests.push((occurrence, pattern.clone(), binder)),
+                    }
+                },
+                Pattern::Literal(_) | Pattern::VariantIntro(_, _) | Pattern::ArrayIntro(_) => {
+                    tests.push((occurrence, pattern.clone(), binder))
+                },
+            }
+        }
+
+        Ok(Row {
+            tests,
+            bindings,
+            names: self.names,
+            body: self.body,
+        })
+    }
+
+    /// Returns the index of the test performed on the given occurrence, if
+    /// this row has one
+    fn test_index(&self, occurrence: &Occurrence) -> Option<usize> {
+        self.tests
+            .iter()
+            .position(|&(ref other, _, _)| other == occurrence)
+    }
+
+    /// Wrap the body of the row in the bindings it has collected, where the
+    /// case expression was found in a scope of `size` local variables, and
+    /// the body ends up in a scope of `depth` local variables
+    fn into_body(self, size: usize, depth: usize) -> RcTerm {
+        let num_binders = self.bindings.len();
+        let mut lets = Vec::new();
+        // The levels of the variables that the variables bound by the clause
+        // are replaced with
+        let mut levels = Vec::with_capacity(num_binders);
+
+        for (occurrence, name) in Iterator::zip(self.bindings.into_iter(), self.names) {
+            let occurrence = occurrence.expect("pattern variable was not bound");
+            if occurrence.path.is_empty() {
+                levels.push(occurrence.level);
+            } else {
+                let let_size = depth + lets.len();
+                levels.push(VarLevel(let_size));
+                lets.push((name, occurrence.to_term(let_size)));
+            }
+        }
+
+        let body_size = depth + lets.len();
+        let body = self.body.subst_vars(&mut |index, shift| {
+            let level = match index.0.checked_sub(num_binders) {
+                None => levels[num_binders - index.0 - 1],
+                Some(index) => VarIndex(index)
+                    .to_level(size)
+                    .expect("variable out of scope"),
+            };
+            let index = level.to_index(body_size).expect("variable out of scope");
+
+            RcTerm::from(Term::Var(index, shift))
+        });
+
+        if lets.is_empty() {
+            body
+        } else {
+            RcTerm::from(Term::Let(lets, body))
+        }
+    }
+}
+
+/// Returns the literal that a variable pattern refers to, if it is defined as
+/// one
+fn constant_literal(
+    context: &Context,
+    size: usize,
+    pattern: &RcPattern,
+) -> Result<Option<Literal>, TypeError> {
+    let value = match *pattern.inner {
+        Pattern::Var(index, _) => {
+            // Only the local variables from outside of the term being
+            // compiled have values in the context
+            let level = index.to_level(size);
+            match level.and_then(|level| context.local_values().get(level.0)) {
+                Some(value) => value.clone(),
+                None => return Ok(None),
+            }
+        },
+        Pattern::Global(ref free_var, _) => match context.get_definition(free_var) {
+            Some(term) => nbe::eval_term(context, term)?,
+            None => return Ok(None),
+        },
+        _ => return Ok(None),
+    };
+
+    match *value.inner {
+        Value::Literal(ref literal) => Ok(Some(literal.clone())),
+        _ => Ok(None),
+    }
+}
+
+/// Move a constant pattern underneath `count` more binders
+fn shift_constant(pattern: &RcPattern, count: usize) -> RcPattern {
+    match *pattern.inner {
+        Pattern::Var(index, shift) => {
+            RcPattern::from(Pattern::Var(VarIndex(index.0 + count), shift))
+        },
+        _ => pattern.clone(),
+    }
+}
+
+/// Returns the name to use when binding a sub-pattern, reusing the name of the
+/// variable that the pattern binds if it is a binder
+fn sub_name(pattern: &RcPattern) -> NameHint {
+    match *pattern.inner {
+        Pattern::Ann(ref pattern, _) => sub_name(pattern),
+        Pattern::Binder(ref name) => name.clone(),
+        _ => NameHint::default(),
+    }
+}
+
+/// Keep the rows that could match `pattern` at the given occurrence,
+/// replacing the test with the tests produced by `expand`
+fn specialize(
+    rows: &[Row],
+    occurrence: &Occurrence,
+    mut expand: impl FnMut(&RcPattern, usize) -> Option<Vec<(Occurrence, RcPattern, usize)>>,
+) -> Vec<Row> {
+    rows.iter()
+        .filter_map(|row| match row.test_index(occurrence) {
+            None => Some(row.clone()),
+            Some(index) => {
+                let (_, ref pattern, binder) = row.tests[index];
+                let sub_tests = expand(pattern, binder)?;
+                let mut row = row.clone();
+                let rest = row.tests.split_off(index + 1);
+                row.tests.pop();
+                row.tests.extend(sub_tests);
+                row.tests.extend(rest);
+                Some(row)
+            },
+        })
+        .collect()
+}
+
+/// Compile the rows into a decision tree, starting with the first test of the
+/// first row
+///
+/// The case expression was found in a scope of `size` local variables, and the
+/// tree is built in a scope of `depth` local variables.
+fn compile_rows(
+    context: &Context,
+    size: usize,
+    depth: usize,
+    rows: Vec<Row>,
+) -> Result<RcTerm, TypeError> {
+    let rows = rows
+        .into_iter()
+        .map(|row| row.simplify(context, size))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (occurrence, pattern) = match rows[0].tests.first() {
+        Some(&(ref occurrence, ref pattern, _)) => (occurrence.clone(), pattern.clone()),
+        None => return Ok(rows.into_iter().next().unwrap().into_body(size, depth)),
+    };
+
+    let mut clauses = Vec::new();
+
+    match *pattern.inner {
+        Pattern::VariantIntro(_, _) => {
+            let mut labels = Vec::new();
+            for row in &rows {
+                if let Some(index) = row.test_index(&occurrence) {
+                    if let Pattern::VariantIntro(ref label, ref pattern) = *row.tests[index].1 {
+                        if labels.iter().all(|&(ref other, _)| other != label) {
+                            labels.push((label.clone(), sub_name(pattern)));
+                        }
+                    }
+                }
+            }
+
+            for (label, name) in labels {
+                let sub_occurrence = Occurrence::new(VarLevel(depth));
+                let rows = specialize(&rows, &occurrence, |pattern, binder| match **pattern {
+                    Pattern::VariantIntro(ref other, ref pattern) if *other == label => {
+                        Some(vec![(sub_occurrence.clone(), pattern.clone(), binder)])
+                    },
+                    _ => None,
+                });
+                let pattern = Pattern::VariantIntro(label, RcPattern::from(Pattern::Binder(name)));
+
+                clauses.push((
+                    RcPattern::from(pattern),
+                    compile_rows(context, size, depth + 1, rows)?,
+                ));
+            }
+        },
+        Pattern::ArrayIntro(ref patterns) => {
+            let names = patterns.iter().map(sub_name).collect::<Vec<_>>();
+            let sub_occurrences = (0..patterns.len())
+                .map(|i| Occurrence::new(VarLevel(depth + i)))
+                .collect::<Vec<_>>();
+
+            let rows = specialize(&rows, &occurrence, |pattern, binder| match **pattern {
+                Pattern::ArrayIntro(ref patterns) if patterns.len() == sub_occurrences.len() => {
+                    let mut binder = binder;
+                    let sub_tests = <_>::zip(sub_occurrences.iter(), patterns.iter())
+                        .map(|(sub_occurrence, pattern)| {
+                            let test = (sub_occurrence.clone(), pattern.clone(), binder);
+                            binder += pattern.binders();
+                            test
+                        })
+                        .collect();
+
+                    Some(sub_tests)
+                },
+                _ => None,
+            });
+            let pattern = Pattern::ArrayIntro(
+                names
+                    .into_iter()
+                    .map(|name| RcPattern::from(Pattern::Binder(name)))
+                    .collect(),
+            );
+
+            clauses.push((
+                RcPattern::from(pattern),
+                compile_rows(context, size, depth + patterns.len(), rows)?,
+            ));
+        },
+        // Literals and variables that could not be resolved to literals
+        _ => {
+            let mut constants = Vec::<RcPattern>::new();
+            for row in &rows {
+                if let Some(index) = row.test_index(&occurrence) {
+                    let constant = &row.tests[index].1;
+                    if !constants.contains(constant) {
+                        constants.push(constant.clone());
+                    }
+                }
+            }
+
+            for constant in constants {
+                let rows = specialize(&rows, &occurrence, |pattern, _| {
+                    if *pattern == constant {
+                        Some(vec![])
+                    } else {
+                        None
+                    }
+                });
+
+                clauses.push((
+                    shift_constant(&constant, depth - size),
+                    compile_rows(context, size, depth, rows)?,
+                ));
+            }
+        },
+    }
+
+    // The rows that don't test this occurrence are tried if none of the
+    // constructors above match
+    let fallback = rows
+        .iter()
+        .filter(|row| row.test_index(&occurrence).is_none())
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if !fallback.is_empty() {
+        let pattern = RcPattern::from(Pattern::Binder(NameHint::from("_")));
+        clauses.push((pattern, compile_rows(context, size, depth + 1, fallback)?));
+    }
+
+    Ok(RcTerm::from(Term::Case(occurrence.to_term(depth), clauses)))
+}
+
+/// Compile a case expression, found in a scope of `size` local variables, to a
+/// decision tree
+pub fn compile_case(
+    context: &Context,
+    size: usize,
+    head: &RcTerm,
+    clauses: &[(RcPattern, RcTerm)],
+) -> Result<RcTerm, TypeError> {
+    if clauses.is_empty() {
+        return Ok(RcTerm::from(Term::Case(head.clone(), vec![])));
+    }
+
+    // Bind the head to a variable, so that it is only evaluated once
+    let head_level = match *head.inner {
+        Term::Var(index, LevelShift(0)) => index.to_level(size),
+        _ => None,
+    };
+    let (occurrence, depth) = match head_level {
+        Some(level) => (Occurrence::new(level), size),
+        None => (Occurrence::new(VarLevel(size)), size + 1),
+    };
+
+    let rows = clauses
+        .iter()
+        .map(|&(ref pattern, ref body)| {
+            let mut names = Vec::new();
+            pattern.binder_names(&mut names);
+
+            Row {
+                tests: vec![(occurrence.clone(), pattern.clone(), 0)],
+                bindings: vec![None; names.len()],
+                names,
+                body: body.clone(),
+            }
+        })
+        .collect();
+
+    let tree = compile_rows(context, size, depth, rows)?;
+
+    Ok(match head_level {
+        Some(_) => tree,
+        None => RcTerm::from(Term::Let(vec![(NameHint::default(), head.clone())], tree)),
+    })
+}
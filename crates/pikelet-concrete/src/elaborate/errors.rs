@@ -5,7 +5,7 @@ use codespan_reporting::{Diagnostic, Label};
 use moniker::{Binder, FreeVar, Var};
 
 use pikelet_core::nbe::NbeError;
-use pikelet_core::syntax;
+use pikelet_core::syntax::{self, NameHint};
 
 use crate::syntax::{concrete, raw};
 
@@ -118,6 +118,25 @@ pub enum TypeError {
         span: ByteSpan,
         expected: Option<Box<concrete::Term>>,
     },
+    #[fail(display = "Unable to infer the implicit argument `{}`", name)]
+    UnableToInferImplicitArg {
+        span: ByteSpan,
+        name: NameHint,
+        expected: Option<Box<concrete::Term>>,
+    },
+    #[fail(display = "No instance found for the argument `{}`", name)]
+    NoInstanceFound {
+        span: ByteSpan,
+        name: NameHint,
+        expected: Option<Box<concrete::Term>>,
+    },
+    #[fail(display = "Multiple instances found for the argument `{}`", name)]
+    AmbiguousInstance {
+        span: ByteSpan,
+        name: NameHint,
+        expected: Box<concrete::Term>,
+        candidates: Vec<FreeVar<String>>,
+    },
     #[fail(
         display = "Type mismatch: found `{}` but `{}` was expected",
         found, expected
@@ -132,11 +151,29 @@ pub enum TypeError {
         span: ByteSpan,
         expected: Box<concrete::Term>,
     },
+    #[fail(display = "Found a variant but expected `{}`", expected)]
+    UnexpectedVariant {
+        span: ByteSpan,
+        expected: Box<concrete::Term>,
+    },
     #[fail(display = "Found `{}` but a universe was expected", found)]
     ExpectedUniverse {
         span: ByteSpan,
         found: Box<concrete::Term>,
     },
+    #[fail(display = "Unable to determine the universe level")]
+    AmbiguousLevel { span: ByteSpan },
+    #[fail(display = "Level polymorphic types are too large to be given a type")]
+    NoUniverseAboveOmega { span: ByteSpan },
+    #[fail(
+        display = "Inconsistent universe levels: `{}` needs to fit in `{}`",
+        found, expected
+    )]
+    InconsistentLevels {
+        span: ByteSpan,
+        found: Box<concrete::Term>,
+        expected: Box<concrete::Term>,
+    },
     #[fail(display = "Not yet defined: `{}`", free_var)]
     UndefinedName {
         span: ByteSpan,
@@ -173,6 +210,57 @@ pub enum TypeError {
         expected_label: syntax::Label,
         found: Box<concrete::Term>,
     },
+    #[fail(display = "Ambiguous variant")]
+    AmbiguousVariantIntro { span: ByteSpan },
+    #[fail(
+        display = "The type `{}` does not contain a variant named `{}`.",
+        found, expected_label
+    )]
+    NoVariantInType {
+        span: ByteSpan,
+        expected_label: syntax::Label,
+        found: Box<concrete::Term>,
+    },
+    #[fail(display = "The variant `{}` was defined more than once", label)]
+    DuplicateVariant {
+        span: ByteSpan,
+        label: syntax::Label,
+    },
+    #[fail(display = "Ambiguous refl")]
+    AmbiguousEqIntro { span: ByteSpan },
+    #[fail(display = "Unable to prove `{} == {}` by reflexivity", lhs, rhs)]
+    UnequalTerms {
+        span: ByteSpan,
+        lhs: Box<concrete::Term>,
+        rhs: Box<concrete::Term>,
+    },
+    #[fail(display = "Found `{}` but an equality was expected", found)]
+    ExpectedEqType {
+        span: ByteSpan,
+        found: Box<concrete::Term>,
+    },
+    #[fail(
+        display = "`{}` was referred to before it was defined, but has no declaration",
+        free_var
+    )]
+    UndeclaredRecursiveItem {
+        span: ByteSpan,
+        free_var: FreeVar<String>,
+    },
+    #[fail(
+        display = "Unable to show that the recursive call to `{}` terminates",
+        free_var
+    )]
+    NonTerminatingCall {
+        span: ByteSpan,
+        definition_span: ByteSpan,
+        free_var: FreeVar<String>,
+    },
+    #[fail(display = "Patterns not covered: `{}`", missing)]
+    NonExhaustivePatterns {
+        span: ByteSpan,
+        missing: Box<concrete::Pattern>,
+    },
     #[fail(
         display = "Mismatched record size: expected {} fields but found {}",
         expected_size, found_size
@@ -182,6 +270,44 @@ pub enum TypeError {
         found_size: u64,
         expected_size: u64,
     },
+    #[fail(display = "Found `{}` but a record type was expected", found)]
+    ExpectedRecordType {
+        span: ByteSpan,
+        found: Box<concrete::Term>,
+    },
+    #[fail(display = "The field `{}` was defined more than once", label)]
+    DuplicateField {
+        span: ByteSpan,
+        label: syntax::Label,
+    },
+    #[fail(
+        display = "The field `{}` must also be updated: expected `{}` but found `{}`",
+        label, expected, found
+    )]
+    FieldNeedsUpdate {
+        span: ByteSpan,
+        label: syntax::Label,
+        found: Box<concrete::Term>,
+        expected: Box<concrete::Term>,
+    },
+    #[fail(
+        display = "The parameter `{}` was used {} times, but its multiplicity is {}",
+        binder, found, expected
+    )]
+    UsageMismatch {
+        span: ByteSpan,
+        binder: Binder<String>,
+        found: syntax::Multiplicity,
+        expected: syntax::Multiplicity,
+    },
+    #[fail(
+        display = "The field `{}` was used at runtime, but its multiplicity is 0",
+        label
+    )]
+    ErasedFieldUsed {
+        span: ByteSpan,
+        label: syntax::Label,
+    },
     #[fail(display = "Internal error - this is a bug! {}", _0)]
     Internal(#[cause] InternalError),
 }
@@ -301,6 +427,55 @@ impl TypeError {
                 expected,
             ))
             .with_label(Label::new_primary(span).with_message("the hole")),
+            TypeError::UnableToInferImplicitArg {
+                span,
+                ref name,
+                expected: None,
+            } => Diagnostic::new_error(format!("unable to infer the implicit argument `{}`", name))
+                .with_label(Label::new_primary(span).with_message("the function being applied")),
+            TypeError::UnableToInferImplicitArg {
+                span,
+                ref name,
+                expected: Some(ref expected),
+            } => Diagnostic::new_error(format!(
+                "unable to infer the implicit argument `{}` - expected: `{}`",
+                name, expected,
+            ))
+            .with_label(Label::new_primary(span).with_message("the function being applied")),
+            TypeError::NoInstanceFound {
+                span,
+                ref name,
+                expected: None,
+            } => Diagnostic::new_error(format!("no instance found for the argument `{}`", name))
+                .with_label(Label::new_primary(span).with_message("the function being applied")),
+            TypeError::NoInstanceFound {
+                span,
+                ref name,
+                expected: Some(ref expected),
+            } => Diagnostic::new_error(format!(
+                "no instance found for the argument `{}` - expected: `{}`",
+                name, expected,
+            ))
+            .with_label(Label::new_primary(span).with_message("the function being applied")),
+            TypeError::AmbiguousInstance {
+                span,
+                ref name,
+                ref expected,
+                ref candidates,
+            } => {
+                let candidates = candidates
+                    .iter()
+                    .map(|candidate| format!("`{}`", candidate))
+                    .collect::<Vec<_>>();
+
+                Diagnostic::new_error(format!(
+                    "multiple instances found for the argument `{}` - expected: `{}`, candidates: {}",
+                    name,
+                    expected,
+                    candidates.join(", "),
+                ))
+                .with_label(Label::new_primary(span).with_message("the function being applied"))
+            },
             TypeError::UnexpectedFunction {
                 span, ref expected, ..
             } => Diagnostic::new_error(format!(
@@ -308,6 +483,13 @@ impl TypeError {
                 expected,
             ))
             .with_label(Label::new_primary(span).with_message("the function")),
+            TypeError::UnexpectedVariant {
+                span, ref expected, ..
+            } => Diagnostic::new_error(format!(
+                "found a variant but expected a term of type `{}`",
+                expected,
+            ))
+            .with_label(Label::new_primary(span).with_message("the variant")),
             TypeError::Mismatch {
                 span,
                 ref found,
@@ -321,6 +503,23 @@ impl TypeError {
                 Diagnostic::new_error(format!("expected type, found a value of type `{}`", found))
                     .with_label(Label::new_primary(span).with_message("the value"))
             },
+            TypeError::AmbiguousLevel { span } => {
+                Diagnostic::new_error("unable to determine the universe level")
+                    .with_label(Label::new_primary(span).with_message("level needed here"))
+            },
+            TypeError::NoUniverseAboveOmega { span } => {
+                Diagnostic::new_error("level polymorphic types are too large to be given a type")
+                    .with_label(Label::new_primary(span).with_message("the type"))
+            },
+            TypeError::InconsistentLevels {
+                span,
+                ref found,
+                ref expected,
+            } => Diagnostic::new_error(format!(
+                "unable to find universe levels where `{}` fits in `{}`",
+                found, expected,
+            ))
+            .with_label(Label::new_primary(span).with_message("levels needed in this term")),
             TypeError::UndefinedName { ref free_var, span } => {
                 Diagnostic::new_bug(format!("cannot find `{}` in scope", free_var))
                     .with_label(Label::new_primary(span).with_message("not found in this scope"))
@@ -362,6 +561,66 @@ impl TypeError {
                 found, expected_label
             ))
             .with_label(Label::new_primary(label_span).with_message("the field lookup")),
+            TypeError::AmbiguousVariantIntro { span } => Diagnostic::new_error(
+                "ambiguous variant introduction",
+            )
+            .with_label(Label::new_primary(span).with_message("type annotations needed here")),
+            TypeError::NoVariantInType {
+                span,
+                ref expected_label,
+                ref found,
+            } => Diagnostic::new_error(format!(
+                "the type `{}` does not contain a variant called `{}`",
+                found, expected_label
+            ))
+            .with_label(Label::new_primary(span).with_message("the variant")),
+            TypeError::DuplicateVariant { span, ref label } => Diagnostic::new_error(format!(
+                "the variant `{}` was defined more than once",
+                label
+            ))
+            .with_label(Label::new_primary(span).with_message("the duplicated variant")),
+            TypeError::AmbiguousEqIntro { span } => Diagnostic::new_error("ambiguous refl")
+                .with_label(Label::new_primary(span).with_message("type annotations needed here")),
+            TypeError::UnequalTerms {
+                span,
+                ref lhs,
+                ref rhs,
+            } => Diagnostic::new_error(format!(
+                "unable to prove `{} == {}` by reflexivity",
+                lhs, rhs,
+            ))
+            .with_label(Label::new_primary(span).with_message("the terms are not equal")),
+            TypeError::ExpectedEqType { ref found, span } => Diagnostic::new_error(format!(
+                "expected a proof of equality, found a value of type `{}`",
+                found
+            ))
+            .with_label(Label::new_primary(span).with_message("the proof")),
+            TypeError::UndeclaredRecursiveItem { ref free_var, span } => {
+                Diagnostic::new_error(format!(
+                    "`{}` was referred to before it was defined, but has no declaration",
+                    free_var,
+                ))
+                .with_label(Label::new_primary(span).with_message("referred to in this definition"))
+            },
+            TypeError::NonTerminatingCall {
+                span,
+                definition_span,
+                ref free_var,
+            } => Diagnostic::new_error(format!(
+                "unable to show that the recursive call to `{}` terminates",
+                free_var,
+            ))
+            .with_label(Label::new_primary(span).with_message("the recursive call"))
+            .with_label(Label::new_secondary(definition_span).with_message(
+                "consider declaring this definition as `partial` if it is not meant to be total",
+            )),
+            TypeError::NonExhaustivePatterns { span, ref missing } => Diagnostic::new_error(
+                format!("patterns not covered: `{}`", missing),
+            )
+            .with_label(Label::new_primary(span).with_message(format!(
+                "the pattern `{}` is not matched by any of the clauses",
+                missing,
+            ))),
             TypeError::RecordSizeMismatch {
                 span,
                 found_size,
@@ -373,6 +632,68 @@ impl TypeError {
             .with_label(
                 Label::new_primary(span).with_message(format!("record with {} fields", found_size)),
             ),
+            TypeError::ExpectedRecordType { ref found, span } => Diagnostic::new_error(format!(
+                "expected a record, found a value of type `{}`",
+                found
+            ))
+            .with_label(Label::new_primary(span).with_message("the record")),
+            TypeError::FieldNeedsUpdate {
+                span,
+                ref label,
+                ref found,
+                ref expected,
+            } => Diagnostic::new_error(format!("the field `{}` must also be updated", label))
+                .with_label(Label::new_primary(span).with_message(format!(
+                    "expected a value of type `{}`, but the original has type `{}`",
+                    expected, found,
+                ))),
+            TypeError::DuplicateField { span, ref label } => {
+                Diagnostic::new_error(format!("the field `{}` was defined more than once", label))
+                    .with_label(Label::new_primary(span).with_message("the duplicated field"))
+            },
+            TypeError::UsageMismatch {
+                span,
+                ref binder,
+                found,
+                expected,
+            } => Diagnostic::new_error(format!(
+                "the parameter `{}` was used {} times, but its multiplicity is {}",
+                binder, found, expected
+            ))
+            .with_label(Label::new_primary(span).with_message(match expected {
+                syntax::Multiplicity::Zero => "the parameter can only be used in types",
+                syntax::Multiplicity::One => "the parameter must be used exactly once",
+                syntax::Multiplicity::Many => "the parameter can be used any number of times",
+            })),
+            TypeError::ErasedFieldUsed { span, ref label } => Diagnostic::new_error(format!(
+                "the field `{}` was used at runtime, but its multiplicity is 0",
+                label,
+            ))
+            .with_label(
+                Label::new_primary(span).with_message("the field can only be used in types"),
+            ),
+        }
+    }
+}
+
+/// A warning produced during type checking
+///
+/// Unlike type errors, warnings do not prevent a term from being elaborated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeWarning {
+    /// A clause of a case expression that is covered by the clauses before
+    /// it, and so will never be matched
+    UnreachableClause { span: ByteSpan },
+}
+
+impl TypeWarning {
+    /// Convert the warning into a diagnostic message
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        match *self {
+            TypeWarning::UnreachableClause { span } => Diagnostic::new_warning(
+                "unreachable pattern",
+            )
+            .with_label(Label::new_primary(span).with_message("this clause will never be matched")),
         }
     }
 }
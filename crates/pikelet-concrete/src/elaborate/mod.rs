@@ -5,84 +5,1320 @@
 //! For more information, check out the theory appendix of the Pikelet book.
 
 use codespan::ByteSpan;
-use moniker::{Binder, BoundPattern, BoundTerm, Embed, FreeVar, Nest, Scope, Var};
+use moniker::{Binder, BoundTerm, Embed, FreeVar, Scope, Var};
+use std::collections::HashMap;
+use std::iter;
 
 use pikelet_core::nbe;
 use pikelet_core::syntax::core::{Pattern, RcPattern, RcTerm, Term};
-use pikelet_core::syntax::domain::{RcType, RcValue, Value};
-use pikelet_core::syntax::{Level, Literal};
+use pikelet_core::syntax::domain::{Closure, Head, LevelValue, RcType, RcValue, Telescope, Value};
+use pikelet_core::syntax::{
+    Label, Level, LevelShift, Literal, MetaVar, Multiplicity, NameHint, Plicity, Totality,
+    VarIndex, VarLevel,
+};
 
 use crate::syntax::raw;
+use crate::syntax::BindingKind;
 
 mod context;
+mod coverage;
+mod decision_tree;
 mod errors;
+mod termination;
+mod unify;
+mod usage;
 
-pub use self::context::{Context, Globals};
-pub use self::errors::{InternalError, TypeError};
+pub use self::context::{Context, Globals, LevelConstraint, MetaOrigin, MetaSnapshot, Postponed};
+pub use self::errors::{InternalError, TypeError, TypeWarning};
 
-/// Returns true if `ty1` is a subtype of `ty2`
-fn is_subtype(context: &Context, ty1: &RcType, ty2: &RcType) -> bool {
-    match (&*ty1.inner, &*ty2.inner) {
-        // ST-TYPE
-        (&Value::Universe(level1), &Value::Universe(level2)) => level1 <= level2,
+/// Returns true if `ty1` is a subtype of `ty2`, solving any metavariables that
+/// are needed to make it so
+fn is_subtype(context: &Context, ty1: &RcType, ty2: &RcType) -> Result<bool, TypeError> {
+    let ty1 = unify::force(context, ty1)?;
+    let ty2 = unify::force(context, ty2)?;
+
+    if ty1.meta_app().is_some() || ty2.meta_app().is_some() {
+        return unify::unify(context, &ty1, &ty2);
+    }
 
+    // ST-TYPE
+    if let (Some(level1), Some(level2)) = (
+        UniverseLevel::from_universe(context, &ty1)?,
+        UniverseLevel::from_universe(context, &ty2)?,
+    ) {
+        return Ok(level1.check_le(context, &level2));
+    }
+
+    match (&*ty1.inner, &*ty2.inner) {
         // ST-PI
-        (&Value::FunType(ref scope1), &Value::FunType(ref scope2)) => {
-            let ((_, Embed(ann1)), body1, (Binder(free_var2), Embed(ann2)), body2) =
-                Scope::unbind2(scope1.clone(), scope2.clone());
+        (
+            &Value::FunType(plicity1, multiplicity1, ref ann1, ref body1),
+            &Value::FunType(plicity2, multiplicity2, ref ann2, ref body2),
+        ) if plicity1 == plicity2 && multiplicity1 == multiplicity2 => {
+            Ok(is_subtype(context, ann2, ann1)? && {
+                let var = context.fresh_var();
+                let body1 = nbe::apply_closure(context, body1, var.clone())?;
+                let body2_value = nbe::apply_closure(context, body2, var)?;
 
-            is_subtype(context, &ann2, &ann1) && {
                 let mut context = context.clone();
-                context.insert_declaration(free_var2, ann2);
-                is_subtype(&context, &body1, &body2)
-            }
+                context.insert_fresh_local(body2.name.clone(), ann2.clone());
+                is_subtype(&context, &body1, &body2_value)?
+            })
         },
 
         // ST-RECORD-TYPE, ST-EMPTY-RECORD-TYPE
-        (&Value::RecordType(ref scope1), &Value::RecordType(ref scope2)) => {
-            if scope1.unsafe_pattern.unsafe_patterns.len()
-                != scope2.unsafe_pattern.unsafe_patterns.len()
-            {
-                return false;
+        (&Value::RecordType(ref telescope1), &Value::RecordType(ref telescope2)) => {
+            if telescope1.fields.len() != telescope2.fields.len() {
+                return Ok(false);
             }
 
-            let (fields1, (), fields2, ()) = Scope::unbind2(scope1.clone(), scope2.clone());
-
             let mut context = context.clone();
+            let mut locals1 = telescope1.locals.clone();
+            let mut locals2 = telescope2.locals.clone();
             for (field1, field2) in
-                Iterator::zip(fields1.unnest().into_iter(), fields2.unnest().into_iter())
+                Iterator::zip(telescope1.fields.iter(), telescope2.fields.iter())
             {
-                let (label1, Binder(free_var1), Embed(ty1)) = field1;
-                let (label2, _, Embed(ty2)) = field2;
-
-                if label1 == label2 && is_subtype(&context, &ty1, &ty2) {
-                    context.insert_declaration(free_var1, ty1);
+                let (ref label1, ref name1, multiplicity1, ref ty1) = *field1;
+                let (ref label2, _, multiplicity2, ref ty2) = *field2;
+                let ty1 = nbe::eval(&context, &locals1, ty1)?;
+                let ty2 = nbe::eval(&context, &locals2, ty2)?;
+
+                if label1 == label2
+                    && multiplicity1 == multiplicity2
+                    && is_subtype(&context, &ty1, &ty2)?
+                {
+                    let var = context.fresh_var();
+                    locals1.push_back(var.clone());
+                    locals2.push_back(var);
+                    context.insert_fresh_local(name1.clone(), ty1);
                 } else {
-                    return false;
+                    return Ok(false);
                 }
             }
 
-            true
+            Ok(true)
+        },
+
+        // ST-VARIANT-TYPE, ST-EMPTY-VARIANT-TYPE
+        (&Value::VariantType(ref alts1), &Value::VariantType(ref alts2)) => {
+            if alts1.alts.len() != alts2.alts.len() {
+                return Ok(false);
+            }
+            // Recursive variant types contain themselves, so we might already
+            // be comparing them further up
+            if context.is_assumed_variants(&ty1, &ty2) {
+                return Ok(true);
+            }
+
+            let mut context = context.clone();
+            context.assume_variants(ty1.clone(), ty2.clone());
+            for (alt1, alt2) in Iterator::zip(alts1.alts.iter(), alts2.alts.iter()) {
+                let (ref label1, ref ann1) = *alt1;
+                let (ref label2, ref ann2) = *alt2;
+                let ann1 = nbe::eval(&context, &alts1.locals, ann1)?;
+                let ann2 = nbe::eval(&context, &alts2.locals, ann2)?;
+
+                if label1 != label2 || !is_subtype(&context, &ann1, &ann2)? {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
         },
 
         // ST-ALPHA-EQ
-        (_, _) => RcType::term_eq(ty1, ty2),
+        (_, _) => unify::unify(context, &ty1, &ty2),
+    }
+}
+
+/// Create a fresh metavariable, applied to the local variables that are
+/// currently in scope
+fn fresh_meta(context: &Context, origin: MetaOrigin, expected_ty: Option<&RcType>) -> RcTerm {
+    let meta = context.create_meta(origin, expected_ty);
+    apply_locals(context, meta)
+}
+
+/// Create a fresh metavariable like `fresh_meta`, postponing the problem of
+/// solving it until the end of elaboration, when more is known about its type
+fn fresh_postponed_meta(context: &Context, origin: MetaOrigin, expected_ty: &RcType) -> RcTerm {
+    let meta = context.create_meta(origin, Some(expected_ty));
+    let term = apply_locals(context, meta);
+
+    context.postpone(Postponed {
+        context: context.clone(),
+        meta,
+        term: term.clone(),
+        expected_ty: expected_ty.clone(),
+    });
+
+    term
+}
+
+/// Create a fresh metavariable for a universe level that is to be inferred
+///
+/// The level solver only ever solves these with constant levels, so unlike
+/// other metavariables they are not applied to the local variables in scope.
+fn fresh_level_meta(context: &Context, span: ByteSpan) -> MetaVar {
+    let level_ty = RcValue::from(Value::LevelType);
+    context.create_meta(MetaOrigin::UniverseLevel(span), Some(&level_ty))
+}
+
+/// Apply a metavariable to the local variables that are currently in scope
+///
+/// The arguments are given a multiplicity of zero, so that they are not
+/// counted as uses of the local variables by the usage checker.
+fn apply_locals(context: &Context, meta: MetaVar) -> RcTerm {
+    context
+        .bound_locals()
+        .fold(RcTerm::from(Term::Meta(meta)), |acc, index| {
+            let arg = RcTerm::from(Term::var(index, 0));
+            RcTerm::from(Term::FunApp(acc, Multiplicity::Zero, arg))
+        })
+}
+
+/// Returns the metavariable at the head of a term, if it has one
+fn meta_head(term: &Term) -> Option<MetaVar> {
+    match *term {
+        Term::Meta(meta) => Some(meta),
+        Term::FunApp(ref head, _, _) => meta_head(head),
+        _ => None,
+    }
+}
+
+/// Apply a term to fresh metavariables for each of the implicit and instance
+/// parameters at the start of its type, returning the applied term and its type
+fn insert_implicit_args(
+    context: &Context,
+    span: ByteSpan,
+    term: RcTerm,
+    ty: RcType,
+) -> Result<(RcTerm, RcType), TypeError> {
+    let mut term = term;
+    let mut ty = unify::force(context, &ty)?;
+
+    loop {
+        let (plicity, multiplicity, ann, body) = match *ty.inner {
+            Value::FunType(plicity, multiplicity, ref ann, ref body)
+                if plicity != Plicity::Explicit =>
+            {
+                (plicity, multiplicity, ann.clone(), body.clone())
+            },
+            _ => return Ok((term, ty)),
+        };
+        let arg = match plicity {
+            // Instance arguments are found by searching the context, which we
+            // put off until we know as much as possible about their types
+            Plicity::Instance => {
+                let origin = MetaOrigin::InstanceArg(span, body.name.clone());
+                fresh_postponed_meta(context, origin, &ann)
+            },
+            Plicity::Explicit | Plicity::Implicit => {
+                let origin = MetaOrigin::ImplicitArg(span, body.name.clone());
+                fresh_meta(context, origin, Some(&ann))
+            },
+        };
+        let body = nbe::apply_closure(context, &body, context.eval(&arg)?)?;
+
+        term = RcTerm::from(Term::FunApp(term, multiplicity, arg));
+        ty = unify::force(context, &body)?;
+    }
+}
+
+/// Returns `true` if a lambda should be inserted around the term when checking
+/// it against a function type with the given implicit or instance plicity
+fn needs_implicit_fun_intro(raw_term: &raw::Term, plicity: Plicity) -> bool {
+    match *raw_term {
+        raw::Term::FunIntro(_, fun_plicity, _, _) => fun_plicity != plicity,
+        raw::Term::Hole(_) => false,
+        _ => true,
+    }
+}
+
+/// Find the instances in scope that have types compatible with the expected
+/// type, without solving any metavariables along the way
+///
+/// Each candidate is returned along with a term that refers to it, and its type.
+fn instance_candidates(
+    context: &Context,
+    expected_ty: &RcType,
+) -> Result<Vec<(FreeVar<String>, RcTerm, RcType)>, TypeError> {
+    let mut candidates = Vec::new();
+
+    for free_var in context.instances() {
+        let (term, ty) = match context.lookup(free_var, LevelShift(0)) {
+            Some((term, ty)) => (term, ty.clone()),
+            None => continue,
+        };
+
+        let snapshot = context.snapshot_metas();
+        let is_candidate = unify::unify(context, &ty, expected_ty);
+        context.rollback_metas(snapshot);
+
+        if is_candidate? {
+            candidates.push((free_var.clone(), term, ty));
+        }
+    }
+
+    Ok(candidates)
+}
+
+/// Attempt to solve a postponed problem, returning `false` if we don't know
+/// enough to solve it yet
+fn solve_postponed_problem(problem: &Postponed) -> Result<bool, TypeError> {
+    let context = &problem.context;
+
+    // The metavariable might have already been solved by unification
+    if context.get_meta_solution(problem.meta).is_some() {
+        return Ok(true);
+    }
+
+    let expected_ty = context.eval(&context.quote(&problem.expected_ty)?)?;
+    let solution = match context.get_meta_origin(problem.meta) {
+        MetaOrigin::Literal(_) if expected_ty.meta_app().is_some() => return Ok(false),
+        MetaOrigin::Literal(ref raw_literal) => {
+            let literal = check_literal(context, raw_literal, &expected_ty)?;
+            RcValue::from(Value::Literal(literal))
+        },
+        MetaOrigin::InstanceArg(_, _) => {
+            // Only commit to an instance if it is the only one that fits
+            let mut candidates = instance_candidates(context, &expected_ty)?;
+            if candidates.len() != 1 {
+                return Ok(false);
+            }
+
+            let (_, term, ty) = candidates.remove(0);
+            if !unify::unify(context, &ty, &expected_ty)? {
+                return Ok(false);
+            }
+            context.eval(&term)?
+        },
+        MetaOrigin::Hole(_)
+        | MetaOrigin::FunParamAnn(_, _)
+        | MetaOrigin::ImplicitArg(_, _)
+        | MetaOrigin::UniverseLevel(_) => {
+            return Ok(false);
+        },
+    };
+
+    let term = context.eval(&problem.term)?;
+    unify::unify(context, &term, &solution)
+}
+
+/// Solve the problems that were postponed during elaboration, returning an
+/// error for the first one that could not be solved
+fn solve_postponed(mut problems: Vec<Postponed>) -> Result<(), TypeError> {
+    // Solving one problem might tell us enough to solve another, so we keep
+    // going until we stop making progress
+    loop {
+        let num_problems = problems.len();
+        let mut remaining = Vec::with_capacity(num_problems);
+        for problem in problems {
+            if !solve_postponed_problem(&problem)? {
+                remaining.push(problem);
+            }
+        }
+        problems = remaining;
+
+        match problems.first() {
+            None => return Ok(()),
+            Some(problem) if problems.len() == num_problems => {
+                return Err(unsolved_postponed_problem(problem));
+            },
+            Some(_) => {},
+        }
+    }
+}
+
+fn unsolved_postponed_problem(problem: &Postponed) -> TypeError {
+    let context = &problem.context;
+
+    match context.get_meta_origin(problem.meta) {
+        MetaOrigin::InstanceArg(span, name) => {
+            let expected_ty = context
+                .quote(&problem.expected_ty)
+                .and_then(|ty| context.eval(&ty))
+                .unwrap_or_else(|_| problem.expected_ty.clone());
+
+            match instance_candidates(context, &expected_ty) {
+                Ok(ref candidates) if candidates.len() > 1 => TypeError::AmbiguousInstance {
+                    span,
+                    name,
+                    expected: Box::new(context.resugar(&expected_ty)),
+                    candidates: candidates
+                        .iter()
+                        .map(|&(ref fv, _, _)| fv.clone())
+                        .collect(),
+                },
+                Ok(_) => unsolved_meta(context, problem.meta),
+                Err(err) => err,
+            }
+        },
+        _ => unsolved_meta(context, problem.meta),
+    }
+}
+
+fn unsolved_meta(context: &Context, meta: MetaVar) -> TypeError {
+    match context.get_meta_origin(meta) {
+        MetaOrigin::Hole(span) => TypeError::UnableToElaborateHole {
+            span,
+            expected: context.get_meta_expected(meta).map(Box::new),
+        },
+        MetaOrigin::FunParamAnn(param_span, name) => TypeError::FunctionParamNeedsAnnotation {
+            param_span,
+            var_span: None,
+            name,
+        },
+        MetaOrigin::ImplicitArg(span, name) => TypeError::UnableToInferImplicitArg {
+            span,
+            name,
+            expected: context.get_meta_expected(meta).map(Box::new),
+        },
+        MetaOrigin::InstanceArg(span, name) => TypeError::NoInstanceFound {
+            span,
+            name,
+            expected: context.get_meta_expected(meta).map(Box::new),
+        },
+        MetaOrigin::Literal(raw::Literal::Float(span, _, _)) => {
+            TypeError::AmbiguousFloatLiteral { span }
+        },
+        MetaOrigin::Literal(raw_literal) => TypeError::AmbiguousIntLiteral {
+            span: raw_literal.span(),
+        },
+        MetaOrigin::UniverseLevel(span) => TypeError::AmbiguousLevel { span },
+    }
+}
+
+/// Find the least universe levels that satisfy the level constraints found
+/// during elaboration, solving the level metavariables with them
+///
+/// Every unsolved level starts out at zero, and is raised until it is large
+/// enough to satisfy the constraints that it appears on the right hand side
+/// of. No least solution needs a level larger than the sum of the constants
+/// and shifts in the constraints, so if a level rises past that it must have
+/// been constrained to be larger than itself.
+fn solve_levels(context: &Context, span: ByteSpan) -> Result<(), TypeError> {
+    let constraints = context.take_level_constraints();
+    let mut levels = context
+        .unsolved_level_metas()
+        .into_iter()
+        .map(|meta| (meta, Level(0)))
+        .collect::<HashMap<_, _>>();
+    let max_level = constraints.iter().fold(0, |acc, constraint| {
+        acc + level_size(&constraint.lhs) + level_size(&constraint.rhs)
+    });
+
+    loop {
+        let mut changed = false;
+
+        for constraint in &constraints {
+            let lhs = assign_levels(context, &levels, &constraint.lhs)?;
+            let rhs = assign_levels(context, &levels, &constraint.rhs)?;
+            if lhs.le(&rhs) {
+                continue;
+            }
+
+            // The constant is the only part of the left hand side that can be
+            // covered by raising a level on the right hand side
+            let lhs_vars = LevelValue {
+                constant: Level(0),
+                vars: lhs.vars.clone(),
+                metas: Vec::new(),
+            };
+            let raised = constraint
+                .rhs
+                .metas
+                .iter()
+                .find(|&&(meta, _)| levels.contains_key(&meta));
+            let (meta, shift) = match raised {
+                Some(&(meta, shift)) if lhs_vars.le(&rhs) => (meta, shift),
+                Some(_) | None => return Err(inconsistent_levels(context, span, lhs, rhs)),
+            };
+
+            let level = Level(lhs.constant.0.saturating_sub(shift.0));
+            if level <= levels[&meta] || level.0 > max_level {
+                return Err(inconsistent_levels(context, span, lhs, rhs));
+            }
+            levels.insert(meta, level);
+            changed = true;
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    for (meta, level) in levels {
+        context.insert_meta_solution(meta, RcTerm::from(Term::LevelIntro(level)));
+    }
+
+    Ok(())
+}
+
+/// The sum of the constant and shifts in a level
+fn level_size(level: &LevelValue) -> u32 {
+    let shifts = Iterator::chain(
+        level.vars.iter().map(|&(_, shift)| shift.0),
+        level.metas.iter().map(|&(_, shift)| shift.0),
+    );
+
+    shifts.fold(level.constant.0, |acc, shift| acc + shift)
+}
+
+/// Replace the unsolved metavariables in a level with the levels that the
+/// level solver has currently assigned to them
+fn assign_levels(
+    context: &Context,
+    levels: &HashMap<MetaVar, Level>,
+    level: &LevelValue,
+) -> Result<LevelValue, TypeError> {
+    let level = unify::force_level(context, level)?;
+    let mut assigned = LevelValue {
+        constant: level.constant,
+        vars: level.vars.clone(),
+        metas: Vec::new(),
+    };
+    for &(meta, shift) in &level.metas {
+        let mut meta_level = match levels.get(&meta) {
+            Some(&meta_level) => LevelValue::constant(meta_level),
+            None => LevelValue::meta(meta, 0),
+        };
+        meta_level.shift_universes(shift);
+        assigned = assigned.max(&meta_level);
+    }
+
+    Ok(assigned)
+}
+
+fn inconsistent_levels(
+    context: &Context,
+    span: ByteSpan,
+    lhs: LevelValue,
+    rhs: LevelValue,
+) -> TypeError {
+    TypeError::InconsistentLevels {
+        span,
+        found: Box::new(context.resugar(&RcValue::from(Value::universe_at(lhs)))),
+        expected: Box::new(context.resugar(&RcValue::from(Value::universe_at(rhs)))),
+    }
+}
+
+/// Replace the solved metavariables in a term with their solutions, returning
+/// an error if any of them remain unsolved
+///
+/// `size` is the number of local variables that are in scope of the term.
+/// Case expressions are compiled to decision trees along the way, now that
+/// their patterns have been checked.
+fn zonk_term(context: &Context, size: usize, term: &RcTerm) -> Result<RcTerm, TypeError> {
+    if let Some(meta) = meta_head(term) {
+        return match context.get_meta_solution(meta) {
+            None => Err(unsolved_meta(context, meta)),
+            Some(_) => {
+                // Normalizing will apply the solution to the spine, filling
+                // in any other solutions that it depends on as well
+                let locals = (0..size)
+                    .map(|level| RcValue::from(Value::var(level, 0)))
+                    .collect();
+                zonk_term(context, size, &nbe::nf(context, &locals, term)?)
+            },
+        };
+    }
+
+    match *term.inner {
+        Term::Ann(ref expr, ref ty) => Ok(RcTerm::from(Term::Ann(
+            zonk_term(context, size, expr)?,
+            zonk_term(context, size, ty)?,
+        ))),
+        Term::Universe(_)
+        | Term::UniverseOmega
+        | Term::LevelType
+        | Term::LevelIntro(_)
+        | Term::Literal(_)
+        | Term::Var(_, _)
+        | Term::Global(_, _)
+        | Term::Import(_)
+        | Term::Meta(_)
+        | Term::EqIntro
+        | Term::Erased => Ok(term.clone()),
+        Term::UniverseAt(ref level) => {
+            let level = zonk_term(context, size, level)?;
+            // Inferred levels are constants once they have been solved
+            if let Term::LevelIntro(level) = *level.inner {
+                return Ok(RcTerm::from(Term::Universe(level)));
+            }
+            Ok(RcTerm::from(Term::UniverseAt(level)))
+        },
+        Term::LevelMax(ref level1, ref level2) => Ok(RcTerm::from(Term::LevelMax(
+            zonk_term(context, size, level1)?,
+            zonk_term(context, size, level2)?,
+        ))),
+        Term::LevelShift(ref level, shift) => {
+            let level = zonk_term(context, size, level)?;
+            if let Term::LevelIntro(level) = *level.inner {
+                return Ok(RcTerm::from(Term::LevelIntro(level + shift)));
+            }
+            Ok(RcTerm::from(Term::LevelShift(level, shift)))
+        },
+        Term::FunType(plicity, multiplicity, ref name, ref ann, ref body) => {
+            Ok(RcTerm::from(Term::FunType(
+                plicity,
+                multiplicity,
+                name.clone(),
+                zonk_term(context, size, ann)?,
+                zonk_term(context, size + 1, body)?,
+            )))
+        },
+        Term::FunIntro(plicity, multiplicity, ref name, ref ann, ref body) => {
+            Ok(RcTerm::from(Term::FunIntro(
+                plicity,
+                multiplicity,
+                name.clone(),
+                zonk_term(context, size, ann)?,
+                zonk_term(context, size + 1, body)?,
+            )))
+        },
+        Term::FunApp(ref head, multiplicity, ref arg) => Ok(RcTerm::from(Term::FunApp(
+            zonk_term(context, size, head)?,
+            multiplicity,
+            zonk_term(context, size, arg)?,
+        ))),
+        Term::RecordType(ref fields) => {
+            let fields = fields
+                .iter()
+                .enumerate()
+                .map(|(i, &(ref label, ref name, multiplicity, ref ann))| {
+                    Ok((
+                        label.clone(),
+                        name.clone(),
+                        multiplicity,
+                        zonk_term(context, size + i, ann)?,
+                    ))
+                })
+                .collect::<Result<_, TypeError>>()?;
+
+            Ok(RcTerm::from(Term::RecordType(fields)))
+        },
+        Term::RecordIntro(ref fields) => {
+            let fields = fields
+                .iter()
+                .map(|&(ref label, multiplicity, ref expr)| {
+                    Ok((label.clone(), multiplicity, zonk_term(context, size, expr)?))
+                })
+                .collect::<Result<_, TypeError>>()?;
+
+            Ok(RcTerm::from(Term::RecordIntro(fields)))
+        },
+        Term::RecordExtend(ref expr, ref fields) => {
+            let fields = fields
+                .iter()
+                .map(|&(ref label, ref expr)| Ok((label.clone(), zonk_term(context, size, expr)?)))
+                .collect::<Result<_, TypeError>>()?;
+
+            Ok(RcTerm::from(Term::RecordExtend(
+                zonk_term(context, size, expr)?,
+                fields,
+            )))
+        },
+        Term::RecordProj(ref expr, ref label, multiplicity, shift) => {
+            Ok(RcTerm::from(Term::RecordProj(
+                zonk_term(context, size, expr)?,
+                label.clone(),
+                multiplicity,
+                shift,
+            )))
+        },
+        Term::VariantType(ref alts) => {
+            let alts = alts
+                .iter()
+                .map(|&(ref label, ref ann)| Ok((label.clone(), zonk_term(context, size, ann)?)))
+                .collect::<Result<_, TypeError>>()?;
+
+            Ok(RcTerm::from(Term::VariantType(alts)))
+        },
+        Term::VariantIntro(ref label, ref arg) => Ok(RcTerm::from(Term::VariantIntro(
+            label.clone(),
+            zonk_term(context, size, arg)?,
+        ))),
+        Term::EqType(ref ty, ref lhs, ref rhs) => Ok(RcTerm::from(Term::EqType(
+            zonk_term(context, size, ty)?,
+            zonk_term(context, size, lhs)?,
+            zonk_term(context, size, rhs)?,
+        ))),
+        Term::EqElim(ref motive, ref proof, ref body) => Ok(RcTerm::from(Term::EqElim(
+            zonk_term(context, size, motive)?,
+            zonk_term(context, size, proof)?,
+            zonk_term(context, size, body)?,
+        ))),
+        Term::Case(ref head, ref clauses) => {
+            let clauses = clauses
+                .iter()
+                .map(|&(ref pattern, ref body)| {
+                    let body_size = size + pattern.binders();
+                    Ok((
+                        zonk_pattern(context, size, pattern)?,
+                        zonk_term(context, body_size, body)?,
+                    ))
+                })
+                .collect::<Result<Vec<_>, TypeError>>()?;
+
+            decision_tree::compile_case(context, size, &zonk_term(context, size, head)?, &clauses)
+        },
+        Term::ArrayIntro(ref elems) => {
+            let elems = elems
+                .iter()
+                .map(|elem| zonk_term(context, size, elem))
+                .collect::<Result<_, _>>()?;
+
+            Ok(RcTerm::from(Term::ArrayIntro(elems)))
+        },
+        Term::Let(ref bindings, ref body) => {
+            let bindings = bindings
+                .iter()
+                .enumerate()
+                .map(|(i, &(ref name, ref term))| {
+                    Ok((name.clone(), zonk_term(context, size + i, term)?))
+                })
+                .collect::<Result<_, TypeError>>()?;
+            let body = zonk_term(context, size + bindings.len(), body)?;
+
+            Ok(RcTerm::from(Term::Let(bindings, body)))
+        },
+        Term::Fix(totality, ref name, ref ann, ref body) => Ok(RcTerm::from(Term::Fix(
+            totality,
+            name.clone(),
+            zonk_term(context, size, ann)?,
+            zonk_term(context, size + 1, body)?,
+        ))),
+    }
+}
+
+fn zonk_pattern(
+    context: &Context,
+    size: usize,
+    pattern: &RcPattern,
+) -> Result<RcPattern, TypeError> {
+    match *pattern.inner {
+        Pattern::Ann(ref pattern, ref ty) => Ok(RcPattern::from(Pattern::Ann(
+            zonk_pattern(context, size, pattern)?,
+            zonk_term(context, size, ty)?,
+        ))),
+        Pattern::Binder(_) | Pattern::Var(_, _) | Pattern::Global(_, _) | Pattern::Literal(_) => {
+            Ok(pattern.clone())
+        },
+        Pattern::VariantIntro(ref label, ref pattern) => Ok(RcPattern::from(
+            Pattern::VariantIntro(label.clone(), zonk_pattern(context, size, pattern)?),
+        )),
+        Pattern::RecordIntro(ref fields) => {
+            let fields = fields
+                .iter()
+                .map(|&(ref label, ref pattern)| {
+                    Ok((label.clone(), zonk_pattern(context, size, pattern)?))
+                })
+                .collect::<Result<_, TypeError>>()?;
+
+            Ok(RcPattern::from(Pattern::RecordIntro(fields)))
+        },
+        Pattern::ArrayIntro(ref elems) => {
+            let elems = elems
+                .iter()
+                .map(|elem| zonk_pattern(context, size, elem))
+                .collect::<Result<_, _>>()?;
+
+            Ok(RcPattern::from(Pattern::ArrayIntro(elems)))
+        },
+    }
+}
+
+/// The level of a universe, as seen by the elaborator
+#[derive(Debug, Clone, PartialEq)]
+enum UniverseLevel {
+    /// Universes at a level that might depend on level variables
+    Finite(LevelValue),
+    /// The universe of types that are polymorphic over universe levels
+    Omega,
+}
+
+impl UniverseLevel {
+    fn constant(level: impl Into<Level>) -> UniverseLevel {
+        UniverseLevel::Finite(LevelValue::constant(level))
+    }
+
+    /// Returns the level of the universe, if the value is a universe whose
+    /// level is known, or is waiting on the level solver
+    fn from_universe(context: &Context, value: &Value) -> Result<Option<UniverseLevel>, TypeError> {
+        match *value {
+            Value::Universe(level) => Ok(Some(UniverseLevel::constant(level))),
+            Value::UniverseAt(ref level) => {
+                Ok(unify::level_value(context, level)?.map(UniverseLevel::Finite))
+            },
+            Value::UniverseOmega => Ok(Some(UniverseLevel::Omega)),
+            _ => Ok(None),
+        }
+    }
+
+    fn to_universe(&self) -> RcValue {
+        match *self {
+            UniverseLevel::Finite(ref level) => RcValue::from(Value::universe_at(level.clone())),
+            UniverseLevel::Omega => RcValue::from(Value::UniverseOmega),
+        }
+    }
+
+    /// The level of the universe that this universe lives in
+    fn succ(&self) -> Option<UniverseLevel> {
+        match *self {
+            UniverseLevel::Finite(ref level) => {
+                let mut level = level.clone();
+                level.shift_universes(LevelShift(1));
+                Some(UniverseLevel::Finite(level))
+            },
+            UniverseLevel::Omega => None,
+        }
+    }
+
+    fn max(&self, other: &UniverseLevel) -> UniverseLevel {
+        match (self, other) {
+            (&UniverseLevel::Finite(ref level1), &UniverseLevel::Finite(ref level2)) => {
+                UniverseLevel::Finite(level1.max(level2))
+            },
+            (_, _) => UniverseLevel::Omega,
+        }
+    }
+
+    fn le(&self, other: &UniverseLevel) -> bool {
+        match (self, other) {
+            (&UniverseLevel::Finite(ref level1), &UniverseLevel::Finite(ref level2)) => {
+                level1.le(level2)
+            },
+            (_, &UniverseLevel::Omega) => true,
+            (&UniverseLevel::Omega, &UniverseLevel::Finite(_)) => false,
+        }
+    }
+
+    /// Returns `true` if the level is less than or equal to `other`, leaving
+    /// it to the level solver to decide if this depends on levels that are
+    /// not yet known
+    fn check_le(&self, context: &Context, other: &UniverseLevel) -> bool {
+        match (self, other) {
+            (&UniverseLevel::Finite(ref level1), &UniverseLevel::Finite(ref level2))
+                if !level1.le(level2) && (level1.has_metas() || level2.has_metas()) =>
+            {
+                // Unknown levels are at least zero, so if nothing on the right
+                // can be raised then there is no point waiting for the solver
+                let lowest = level1.metas.iter().fold(
+                    LevelValue {
+                        constant: level1.constant,
+                        vars: level1.vars.clone(),
+                        metas: Vec::new(),
+                    },
+                    |acc, &(_, shift)| acc.max(&LevelValue::constant(Level(shift.0))),
+                );
+                if !level2.has_metas() && !lowest.le(level2) {
+                    return false;
+                }
+
+                context.add_level_constraint(level1.clone(), level2.clone());
+                true
+            },
+            (_, _) => self.le(other),
+        }
+    }
+
+    /// The level of a type that binds the level variable at `var`
+    ///
+    /// If the level depends on the variable, then the type is polymorphic
+    /// over universe levels, and so it can only live in the omega universe.
+    fn bind(self, var: VarLevel) -> UniverseLevel {
+        match self {
+            UniverseLevel::Finite(ref level) if level.has_var(var) => UniverseLevel::Omega,
+            level => level,
+        }
     }
 }
 
 /// Ensures that the given term is a universe, returning the level of that
 /// universe and its elaborated form.
-fn infer_universe(context: &Context, raw_term: &raw::RcTerm) -> Result<(RcTerm, Level), TypeError> {
-    let (term, ty) = infer_term(context, raw_term)?;
-    match *ty {
-        Value::Universe(level) => Ok((term, level)),
-        _ => Err(TypeError::ExpectedUniverse {
+fn infer_universe(
+    context: &Context,
+    raw_term: &raw::RcTerm,
+) -> Result<(RcTerm, UniverseLevel), TypeError> {
+    if let raw::Term::Hole(span) = *raw_term.inner {
+        // The level of the universe that the hole lives in is left for the
+        // level solver to find
+        let level = UniverseLevel::Finite(LevelValue::meta(fresh_level_meta(context, span), 0));
+        return Ok((
+            fresh_meta(context, MetaOrigin::Hole(span), Some(&level.to_universe())),
+            level,
+        ));
+    }
+
+    let (term, ty) = infer(context, raw_term)?;
+    let ty = unify::force(context, &ty)?;
+    match UniverseLevel::from_universe(context, &ty)? {
+        Some(level) => Ok((term, level)),
+        // Likewise for types that are yet to be solved
+        None if ty.meta_app().is_some() => {
+            let span = raw_term.span();
+            let level = UniverseLevel::Finite(LevelValue::meta(fresh_level_meta(context, span), 0));
+            if unify::unify(context, &ty, &level.to_universe())? {
+                Ok((term, level))
+            } else {
+                Err(TypeError::ExpectedUniverse {
+                    span,
+                    found: Box::new(context.resugar(&ty)),
+                })
+            }
+        },
+        None => match *ty.inner {
+            Value::UniverseAt(_) => Err(TypeError::AmbiguousLevel {
+                span: raw_term.span(),
+            }),
+            _ => Err(TypeError::ExpectedUniverse {
+                span: raw_term.span(),
+                found: Box::new(context.resugar(&ty)),
+            }),
+        },
+    }
+}
+
+/// Ensures that the given term is a record type, returning its fields, as
+/// seen from the current context, and the level of the universe it lives in
+fn infer_record_type(
+    context: &Context,
+    raw_term: &raw::RcTerm,
+) -> Result<(Vec<(Label, NameHint, Multiplicity, RcTerm)>, UniverseLevel), TypeError> {
+    let (term, level) = infer_universe(context, raw_term)?;
+    let value = unify::force(context, &context.eval(&term)?)?;
+
+    match *context.quote(&value)?.inner {
+        Term::RecordType(ref fields) => Ok((fields.clone(), level)),
+        _ => Err(TypeError::ExpectedRecordType {
             span: raw_term.span(),
-            found: Box::new(context.resugar(&ty)),
+            found: Box::new(context.resugar(&value)),
         }),
     }
 }
 
+/// Ensures that none of the `new_labels` are already among the `labels`, or
+/// are repeated among themselves
+fn check_distinct_labels<'a>(
+    span: ByteSpan,
+    labels: impl Iterator<Item = &'a Label>,
+    new_labels: impl Iterator<Item = &'a Label>,
+) -> Result<(), TypeError> {
+    let mut seen = labels.collect::<Vec<_>>();
+
+    for label in new_labels {
+        if seen.contains(&label) {
+            return Err(TypeError::DuplicateField {
+                span,
+                label: label.clone(),
+            });
+        }
+        seen.push(label);
+    }
+
+    Ok(())
+}
+
+/// Returns the level of the universe that an already elaborated type lives in
+///
+/// This is needed when a type is formed from terms, rather than from other
+/// types, like the equality type `x == y`.
+fn type_level(context: &Context, span: ByteSpan, ty: &RcType) -> Result<UniverseLevel, TypeError> {
+    let ty = unify::force(context, ty)?;
+
+    match *ty.inner {
+        Value::Universe(_) | Value::UniverseAt(_) | Value::UniverseOmega => {
+            match UniverseLevel::from_universe(context, &ty)? {
+                Some(level) => level.succ().ok_or(TypeError::NoUniverseAboveOmega { span }),
+                None => Err(TypeError::AmbiguousLevel { span }),
+            }
+        },
+        Value::FunType(_, _, ref ann, ref body) => {
+            let ann_level = type_level(context, span, ann)?;
+            let body_value = nbe::apply_closure(context, body, context.fresh_var())?;
+            let mut body_context = context.clone();
+            body_context.insert_fresh_local(body.name.clone(), ann.clone());
+            let body_level = type_level(&body_context, span, &body_value)?;
+
+            Ok(ann_level.max(&body_level.bind(VarLevel(context.size()))))
+        },
+        Value::RecordType(ref telescope) => {
+            let mut context = context.clone();
+            let mut locals = telescope.locals.clone();
+            let mut max_level = UniverseLevel::constant(0);
+            let first = VarLevel(context.size());
+
+            for &(_, ref name, _, ref ann) in &telescope.fields {
+                let ann = nbe::eval(&context, &locals, ann)?;
+                max_level = max_level.max(&type_level(&context, span, &ann)?);
+                locals.push_back(context.fresh_var());
+                context.insert_fresh_local(name.clone(), ann);
+            }
+
+            Ok((first.0..context.size()).fold(max_level, |level, var| level.bind(VarLevel(var))))
+        },
+        Value::VariantType(ref alts) => {
+            // Recursive variant types contain themselves, which can't make
+            // their level any larger than it already is
+            if context.is_assumed_variants(&ty, &ty) {
+                return Ok(UniverseLevel::constant(0));
+            }
+
+            let mut context = context.clone();
+            context.assume_variants(ty.clone(), ty.clone());
+            alts.alts
+                .iter()
+                .fold(Ok(UniverseLevel::constant(0)), |acc, &(_, ref ann)| {
+                    let ann = nbe::eval(&context, &alts.locals, ann)?;
+                    Ok(acc?.max(&type_level(&context, span, &ann)?))
+                })
+        },
+        Value::EqType(ref ty, _, _) => type_level(context, span, ty),
+        Value::Neutral(_, _) => {
+            // Find the universe of a neutral type by applying the type of its
+            // head to the arguments in the spine
+            let head_ty = ty.head_app().and_then(|(head, spine)| match *head {
+                Head::Var(level, shift) => {
+                    let index = level.to_index(context.size())?;
+                    Some((context.get_local_ty(index)?, shift, spine))
+                },
+                Head::Global(ref free_var, shift) => {
+                    Some((context.get_declaration(free_var)?, shift, spine))
+                },
+                Head::Import(_) | Head::Meta(_) | Head::Fix(_, _, _) => None,
+            });
+
+            if let Some((head_ty, shift, spine)) = head_ty {
+                let mut head_ty = head_ty.clone();
+                head_ty.shift_universes(shift);
+
+                for arg in spine {
+                    head_ty = match *head_ty.inner {
+                        Value::FunType(_, _, _, ref body) => {
+                            nbe::apply_closure(context, body, arg.clone())?
+                        },
+                        _ => break,
+                    };
+                }
+
+                if let Some(level) = UniverseLevel::from_universe(context, &head_ty)? {
+                    return Ok(level);
+                }
+            }
+
+            // NOTE: Like with holes, we assume that anything else lives in
+            // the lowest universe for now
+            Ok(UniverseLevel::constant(0))
+        },
+        Value::LevelType
+        | Value::Level(_)
+        | Value::Literal(_)
+        | Value::FunIntro(_, _, _, _)
+        | Value::RecordIntro(_)
+        | Value::VariantIntro(_, _)
+        | Value::EqIntro
+        | Value::ArrayIntro(_)
+        | Value::Erased => Ok(UniverseLevel::constant(0)),
+    }
+}
+
+/// Elaborate the bindings of a let expression, adding them to the context
+///
+/// Bindings may refer to themselves, or to bindings that come after them, as
+/// long as those bindings have been given a declaration. To allow for this,
+/// every binding is given a local variable up front. Once everything that a
+/// recursive binding depends on has been elaborated, it is checked for
+/// termination and tied up into a fixed point, so that the resulting bindings
+/// only ever refer to the bindings that came before them.
+fn infer_let_bindings(
+    context: &mut Context,
+    raw_bindings: &[raw::LetBinding],
+) -> Result<Vec<(NameHint, RcTerm)>, TypeError> {
+    let free_vars = raw_bindings
+        .iter()
+        .map(|&(Binder(ref free_var), _, _, _)| free_var.clone())
+        .collect::<Vec<_>>();
+    // The bindings that each binding refers to
+    let dependencies = raw_bindings
+        .iter()
+        .map(|&(_, _, _, Embed(ref raw_term))| {
+            let raw_free_vars = raw_term.free_vars();
+            (0..free_vars.len())
+                .filter(|&dep| raw_free_vars.contains(&free_vars[dep]))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    // Partiality is contagious - if one of the bindings in a recursive group
+    // is partial, then the fixed points of the others can't be unfolded
+    // safely either
+    let mut totalities = vec![Totality::Total; raw_bindings.len()];
+    for (index, &(_, Embed(totality), _, _)) in raw_bindings.iter().enumerate() {
+        if totality == Totality::Partial {
+            for dep in recursive_dependencies(&dependencies, index, 0) {
+                if recursive_dependencies(&dependencies, dep, 0).contains(&index) {
+                    totalities[dep] = Totality::Partial;
+                }
+            }
+        }
+    }
+
+    let first = context.insert_let_locals(&free_vars);
+    let level = |binding: usize| VarLevel(first.0 + binding);
+
+    let mut declared = vec![None; raw_bindings.len()];
+    let mut elaborated = Vec::with_capacity(raw_bindings.len());
+    let mut bindings = vec![None; raw_bindings.len()];
+
+    for (index, &(_, _, _, Embed(ref raw_term))) in raw_bindings.iter().enumerate() {
+        // Declare the bindings that we refer to before they are defined
+        for &dep in &dependencies[index] {
+            if dep < index || declared[dep].is_some() {
+                continue;
+            }
+
+            let (_, _, _, Embed(ref raw_dep_term)) = raw_bindings[dep];
+            let raw_ann = match *raw_dep_term.inner {
+                raw::Term::Ann(_, ref raw_ann) => match *raw_ann.inner {
+                    raw::Term::Hole(_) => None,
+                    _ => Some(raw_ann),
+                },
+                _ => None,
+            };
+
+            match raw_ann {
+                Some(raw_ann) => {
+                    let (ann, _) = infer_universe(context, raw_ann)?;
+                    let ann_value = context.eval(&ann)?;
+
+                    context.declare_local(level(dep), ann_value.clone());
+                    declared[dep] = Some((ann, ann_value));
+                },
+                None => {
+                    return Err(TypeError::UndeclaredRecursiveItem {
+                        span: raw_term.span(),
+                        free_var: free_vars[dep].clone(),
+                    });
+                },
+            }
+        }
+
+        let (term, ty) = match (&declared[index], &*raw_term.inner) {
+            // We've already elaborated the declaration, so check against it
+            (&Some((ref ann, ref ann_value)), &raw::Term::Ann(ref raw_expr, _)) => {
+                let expr = check(context, raw_expr, ann_value)?;
+                (
+                    RcTerm::from(Term::Ann(expr, ann.clone())),
+                    ann_value.clone(),
+                )
+            },
+            (_, _) => infer(context, raw_term)?,
+        };
+
+        context.declare_local(level(index), ty.clone());
+        elaborated.push((term, context.quote(&ty)?));
+
+        // Find the bindings that no longer depend on anything that is still
+        // waiting to be elaborated
+        let ready = (0..=index)
+            .filter(|&binding| {
+                bindings[binding].is_none()
+                    && recursive_dependencies(&dependencies, binding, binding)
+                        .into_iter()
+                        .all(|dep| dep <= index)
+            })
+            .collect::<Vec<_>>();
+
+        // Ensure that the total bindings terminate before we allow them to be
+        // unfolded during type checking
+        let mut group = Vec::new();
+        for &binding in &ready {
+            for dep in recursive_dependencies(&dependencies, binding, binding) {
+                if totalities[dep] == Totality::Total && !group.contains(&dep) {
+                    group.push(dep);
+                }
+            }
+        }
+        // Every recursive group must contain a reference to the same or a
+        // later binding, so we can skip the check for the others
+        let is_recursive = group
+            .iter()
+            .any(|&dep| dependencies[dep].iter().any(|&other| other >= dep));
+        if is_recursive {
+            let definitions = group
+                .iter()
+                .map(|&dep| (level(dep), elaborated[dep].0.clone()))
+                .collect::<Vec<_>>();
+
+            if let Err(call) = termination::check_definitions(context.size(), &definitions) {
+                let (caller, callee) = (group[call.caller], group[call.callee]);
+                let (_, _, _, Embed(ref raw_caller)) = raw_bindings[caller];
+                let spans = termination::occurrence_spans(raw_caller, &free_vars[callee]);
+
+                return Err(TypeError::NonTerminatingCall {
+                    span: spans
+                        .get(call.occurrence)
+                        .cloned()
+                        .unwrap_or_else(|| raw_caller.span()),
+                    definition_span: raw_caller.span(),
+                    free_var: free_vars[callee].clone(),
+                });
+            }
+        }
+
+        // Tie up the ready bindings
+        for binding in ready {
+            let (_, _, Embed(kind), Embed(ref raw_binding_term)) = raw_bindings[binding];
+            let ties = Ties {
+                size: context.size(),
+                first,
+                free_vars: &free_vars,
+                dependencies: &dependencies,
+                totalities: &totalities,
+                elaborated: &elaborated,
+                base: binding,
+            };
+            let term = match ties.tie_binding(binding, &[]) {
+                Some(term) => term,
+                None => {
+                    return Err(TypeError::from(InternalError::Unimplemented {
+                        span: Some(raw_binding_term.span()),
+                        message: "recursive bindings used as patterns".to_owned(),
+                    }));
+                },
+            };
+
+            let value = context.eval(&term)?;
+            context.define_local(level(binding), value);
+            if kind == BindingKind::Instance {
+                context.insert_instance(free_vars[binding].clone());
+            }
+            bindings[binding] = Some(term);
+        }
+    }
+
+    // The bindings were elaborated with the variables of every binding in
+    // scope, but they now only refer to the bindings that came before them,
+    // so we remove the variables of the bindings that come after
+    let num_bindings = free_vars.len();
+    free_vars
+        .iter()
+        .zip(bindings)
+        .enumerate()
+        .map(|(binding, (free_var, term))| {
+            let num_later = num_bindings - binding;
+            let term = term.unwrap().try_subst_vars(&mut |index, shift| {
+                let index = VarIndex(index.0.checked_sub(num_later)?);
+                Some(RcTerm::from(Term::Var(index, shift)))
+            });
+
+            match term {
+                Some(term) => Ok((NameHint::from(free_var), term)),
+                None => Err(TypeError::from(InternalError::Unimplemented {
+                    span: None,
+                    message: "let binding refers to a later binding".to_owned(),
+                })),
+            }
+        })
+        .collect()
+}
+
+/// Returns the bindings that the binding at `index` depends on, either
+/// directly or indirectly, including the binding itself, while only following
+/// the dependencies on bindings that are at or after `min`
+fn recursive_dependencies(dependencies: &[Vec<usize>], index: usize, min: usize) -> Vec<usize> {
+    let mut visited = vec![index];
+    let mut stack = vec![index];
+
+    while let Some(current) = stack.pop() {
+        for &dep in &dependencies[current] {
+            if dep >= min && !visited.contains(&dep) {
+                visited.push(dep);
+                stack.push(dep);
+            }
+        }
+    }
+
+    visited
+}
+
+/// The elaborated bindings of a let expression, ready to be tied up into
+/// their final definitions
+struct Ties<'a> {
+    /// The number of local variables in scope of the elaborated terms
+    size: usize,
+    /// The level of the variable of the first binding
+    first: VarLevel,
+    free_vars: &'a [FreeVar<String>],
+    dependencies: &'a [Vec<usize>],
+    totalities: &'a [Totality],
+    /// The elaborated terms and types of the bindings
+    elaborated: &'a [(RcTerm, RcTerm)],
+    /// The first binding that is not yet in scope
+    base: usize,
+}
+
+impl<'a> Ties<'a> {
+    /// Returns the binding that a variable refers to, if it refers to one
+    fn binding(&self, index: VarIndex) -> Option<usize> {
+        let level = index.to_level(self.size)?;
+        level.0.checked_sub(self.first.0)
+    }
+
+    /// Build the definition of the binding at `index`, substituting the
+    /// bindings that are not yet in scope with their own definitions, and
+    /// wrapping the result in a fixed point if it ends up referring to itself
+    ///
+    /// The bindings in `enclosing` are already bound by the fixed points that
+    /// we are currently building, so they are left alone. Returns `None` if a
+    /// binding that needs to be substituted appears in a pattern.
+    fn tie_binding(&self, index: usize, enclosing: &[usize]) -> Option<RcTerm> {
+        let mut inner_enclosing = enclosing.to_vec();
+        inner_enclosing.push(index);
+
+        let mappings = self.dependencies[index]
+            .iter()
+            .filter(|&&dep| dep >= self.base && dep != index && !enclosing.contains(&dep))
+            .map(|&dep| Some((dep, self.tie_binding(dep, &inner_enclosing)?)))
+            .collect::<Option<Vec<_>>>()?;
+        let (ref term, ref ty) = self.elaborated[index];
+
+        let term = term.try_subst_vars(&mut |var, shift| {
+            let binding = self.binding(var);
+            match mappings.iter().find(|&&(dep, _)| Some(dep) == binding) {
+                Some(&(_, ref term)) => {
+                    let mut term = term.clone();
+                    term.shift_universes(shift);
+                    Some(term)
+                },
+                None => Some(RcTerm::from(Term::Var(var, shift))),
+            }
+        })?;
+
+        let self_index = VarLevel(self.first.0 + index).to_index(self.size)?;
+        if term.has_free_var(self_index) {
+            // Bind the variable of the binding with the fixed point
+            let body = term.try_subst_vars(&mut |var, shift| {
+                let var = if var == self_index {
+                    VarIndex(0)
+                } else {
+                    VarIndex(var.0 + 1)
+                };
+                Some(RcTerm::from(Term::Var(var, shift)))
+            })?;
+            let name = NameHint::from(&self.free_vars[index]);
+
+            Some(RcTerm::from(Term::Fix(
+                self.totalities[index],
+                name,
+                ty.clone(),
+                body,
+            )))
+        } else {
+            Some(term)
+        }
+    }
+}
+
 /// Checks that a literal is compatible with the given type, returning the
 /// elaborated literal if successful
 fn check_literal(
@@ -90,6 +1326,8 @@ fn check_literal(
     raw_literal: &raw::Literal,
     expected_ty: &RcType,
 ) -> Result<Literal, TypeError> {
+    let expected_ty = &unify::force(context, expected_ty)?;
+
     match *raw_literal {
         raw::Literal::String(_, ref val) if context.string() == expected_ty => {
             Ok(Literal::String(val.clone()))
@@ -110,6 +1348,21 @@ fn check_literal(
         raw::Literal::Float(_, v, _) if context.f32() == expected_ty => Ok(Literal::F32(v as f32)),
         raw::Literal::Float(_, v, _) if context.f64() == expected_ty => Ok(Literal::F64(v)),
 
+        // The expected type has yet to be solved, so we'll try to solve it
+        // using the inferred type of the literal instead
+        _ if expected_ty.meta_app().is_some() => {
+            let (literal, ty) = infer_literal(context, raw_literal)?;
+            if unify::unify(context, &ty, expected_ty)? {
+                Ok(literal)
+            } else {
+                Err(TypeError::LiteralMismatch {
+                    literal_span: raw_literal.span(),
+                    found: raw_literal.clone(),
+                    expected: Box::new(context.resugar(expected_ty)),
+                })
+            }
+        },
+
         _ => Err(TypeError::LiteralMismatch {
             literal_span: raw_literal.span(),
             found: raw_literal.clone(),
@@ -135,29 +1388,141 @@ fn infer_literal(
 }
 
 /// Checks that a pattern is compatible with the given type, returning the
-/// elaborated pattern and a vector of the declarations it introduced if successful
+/// elaborated pattern if successful
+///
+/// The declarations of the variables bound by the pattern are appended to
+/// `declarations`, which also holds the declarations of any variables bound
+/// earlier on in the enclosing pattern.
 pub fn check_pattern(
     context: &Context,
     raw_pattern: &raw::RcPattern,
     expected_ty: &RcType,
-) -> Result<(RcPattern, Vec<(FreeVar<String>, RcType)>), TypeError> {
+    declarations: &mut Vec<(FreeVar<String>, RcType)>,
+) -> Result<RcPattern, TypeError> {
+    let expected_ty = &unify::force(context, expected_ty)?;
+
     match (&*raw_pattern.inner, &*expected_ty.inner) {
         (&raw::Pattern::Binder(_, Binder(ref free_var)), _) => {
-            return Ok((
-                RcPattern::from(Pattern::Binder(Binder(free_var.clone()))),
-                vec![(free_var.clone(), expected_ty.clone())],
-            ));
+            declarations.push((free_var.clone(), expected_ty.clone()));
+            return Ok(RcPattern::from(Pattern::Binder(NameHint::from(free_var))));
         },
         (&raw::Pattern::Literal(ref raw_literal), _) => {
             let literal = check_literal(context, raw_literal, expected_ty)?;
-            return Ok((RcPattern::from(Pattern::Literal(literal)), vec![]));
+            return Ok(RcPattern::from(Pattern::Literal(literal)));
+        },
+        (
+            &raw::Pattern::VariantIntro(span, ref label, ref raw_pattern),
+            &Value::VariantType(ref alts),
+        ) => {
+            let alt = alts
+                .alts
+                .iter()
+                .find(|&&(ref alt_label, _)| alt_label == label);
+            return match alt {
+                Some(&(_, ref alt_ann)) => {
+                    let alt_ty = nbe::eval(context, &alts.locals, alt_ann)?;
+                    let pattern = check_pattern(context, raw_pattern, &alt_ty, declarations)?;
+                    Ok(RcPattern::from(Pattern::VariantIntro(
+                        label.clone(),
+                        pattern,
+                    )))
+                },
+                None => Err(TypeError::NoVariantInType {
+                    span,
+                    expected_label: label.clone(),
+                    found: Box::new(context.resugar(expected_ty)),
+                }),
+            };
+        },
+        (&raw::Pattern::VariantIntro(span, _, _), _) if expected_ty.meta_app().is_none() => {
+            return Err(TypeError::UnexpectedVariant {
+                span,
+                expected: Box::new(context.resugar(expected_ty)),
+            });
+        },
+        (&raw::Pattern::Wildcard(_), _) => {
+            // Wildcards are elaborated as binders that can't be referred to,
+            // so that they have a value when substituted into the types of
+            // later record fields
+            declarations.push((FreeVar::fresh_named("_"), expected_ty.clone()));
+            return Ok(RcPattern::from(Pattern::Binder(NameHint::from("_"))));
+        },
+        (&raw::Pattern::RecordIntro(span, ref raw_fields), &Value::RecordType(ref telescope)) => {
+            for &(ref label, _) in raw_fields {
+                if telescope
+                    .fields
+                    .iter()
+                    .all(|&(ref ty_label, _, _, _)| ty_label != label)
+                {
+                    return Err(TypeError::NoFieldInType {
+                        label_span: span,
+                        expected_label: label.clone(),
+                        found: Box::new(context.resugar(expected_ty)),
+                    });
+                }
+            }
+
+            // Fields that are missing from the pattern are matched with
+            // wildcards
+            let mut fields = Vec::with_capacity(telescope.fields.len());
+            let mut locals = telescope.locals.clone();
+
+            for &(ref ty_label, _, multiplicity, ref ann) in &telescope.fields {
+                let raw_pattern = match raw_fields.iter().find(|&&(ref label, _)| label == ty_label)
+                {
+                    Some(&(_, ref raw_pattern)) => raw_pattern.clone(),
+                    None => raw::RcPattern::from(raw::Pattern::Wildcard(span)),
+                };
+
+                // Fields with a multiplicity of zero are erased, so there is
+                // nothing to match them against at runtime
+                let is_wildcard = match *raw_pattern.inner {
+                    raw::Pattern::Wildcard(_) => true,
+                    _ => false,
+                };
+                if multiplicity == Multiplicity::Zero && !is_wildcard {
+                    return Err(TypeError::ErasedFieldUsed {
+                        span: raw_pattern.span(),
+                        label: ty_label.clone(),
+                    });
+                }
+
+                // The variables bound by the pattern are given the levels
+                // that they will have in the body of the clause
+                let mut level = VarLevel(context.size() + declarations.len());
+                let ann = nbe::eval(context, &locals, ann)?;
+                let pattern = check_pattern(context, &raw_pattern, &ann, declarations)?;
+
+                locals.push_back(pattern_value(context, &pattern, &mut level)?);
+                fields.push((ty_label.clone(), pattern));
+            }
+
+            return Ok(RcPattern::from(Pattern::RecordIntro(fields)));
+        },
+        (&raw::Pattern::ArrayIntro(span, ref raw_elems), _) => {
+            if let Some((len, elem_ty)) = context.array(expected_ty) {
+                if len != raw_elems.len() as u64 {
+                    return Err(TypeError::ArrayLengthMismatch {
+                        span,
+                        found_len: raw_elems.len() as u64,
+                        expected_len: len,
+                    });
+                }
+
+                let elems = raw_elems
+                    .iter()
+                    .map(|raw_elem| check_pattern(context, raw_elem, elem_ty, declarations))
+                    .collect::<Result<_, _>>()?;
+
+                return Ok(RcPattern::from(Pattern::ArrayIntro(elems)));
+            }
         },
         _ => {},
     }
 
-    let (pattern, inferred_ty, declarations) = infer_pattern(context, raw_pattern)?;
-    if is_subtype(context, &inferred_ty, expected_ty) {
-        Ok((pattern, declarations))
+    let (pattern, inferred_ty) = infer_pattern(context, raw_pattern, declarations)?;
+    if is_subtype(context, &inferred_ty, expected_ty)? {
+        Ok(pattern)
     } else {
         Err(TypeError::Mismatch {
             span: raw_pattern.span(),
@@ -167,36 +1532,39 @@ pub fn check_pattern(
     }
 }
 
-/// Synthesize the type of a pattern, returning the elaborated pattern, the
-/// inferred type, and a vector of the declarations it introduced if successful
+/// Synthesize the type of a pattern, returning the elaborated pattern and the
+/// inferred type if successful
+///
+/// The declarations of the variables bound by the pattern are appended to
+/// `declarations`, like in `check_pattern`.
 pub fn infer_pattern(
     context: &Context,
     raw_pattern: &raw::RcPattern,
-) -> Result<(RcPattern, RcType, Vec<(FreeVar<String>, RcType)>), TypeError> {
+    declarations: &mut Vec<(FreeVar<String>, RcType)>,
+) -> Result<(RcPattern, RcType), TypeError> {
     match *raw_pattern.inner {
         raw::Pattern::Ann(ref raw_pattern, Embed(ref raw_ty)) => {
             let (ty, _) = infer_universe(context, raw_ty)?;
-            let value_ty = nbe::nf_term(context, &ty)?;
-            let (pattern, declarations) = check_pattern(context, raw_pattern, &value_ty)?;
+            let value_ty = context.eval(&ty)?;
+            let pattern = check_pattern(context, raw_pattern, &value_ty, declarations)?;
 
-            Ok((
-                RcPattern::from(Pattern::Ann(pattern, Embed(ty))),
-                value_ty,
-                declarations,
-            ))
+            Ok((RcPattern::from(Pattern::Ann(pattern, ty)), value_ty))
         },
         raw::Pattern::Binder(span, ref binder) => Err(TypeError::BinderNeedsAnnotation {
             span,
             binder: binder.clone(),
         }),
         raw::Pattern::Var(span, Embed(ref var), shift) => match *var {
-            Var::Free(ref free_var) => match context.get_declaration(free_var) {
-                Some(ty) => {
+            Var::Free(ref free_var) => match context.lookup(free_var, shift) {
+                Some((term, ty)) => {
                     let mut ty = ty.clone();
                     ty.shift_universes(shift);
-                    let pattern = RcPattern::from(Pattern::Var(Embed(var.clone()), shift));
+                    let pattern = match *term.inner {
+                        Term::Var(index, shift) => Pattern::Var(index, shift),
+                        _ => Pattern::Global(free_var.clone(), shift),
+                    };
 
-                    Ok((pattern, ty, vec![]))
+                    Ok((RcPattern::from(pattern), ty))
                 },
                 None => Err(TypeError::UndefinedName {
                     span,
@@ -204,61 +1572,308 @@ pub fn infer_pattern(
                 }),
             },
 
-            // We should always be substituting bound variables with fresh
-            // variables when entering scopes using `unbind`, so if we've
-            // encountered one here this is definitely a bug!
-            Var::Bound(_) => Err(InternalError::UnexpectedBoundVar {
-                span: raw_pattern.span(),
-                var: var.clone(),
-            }
-            .into()),
+            // We should always be substituting bound variables with fresh
+            // variables when entering scopes using `unbind`, so if we've
+            // encountered one here this is definitely a bug!
+            Var::Bound(_) => Err(InternalError::UnexpectedBoundVar {
+                span: raw_pattern.span(),
+                var: var.clone(),
+            }
+            .into()),
+        },
+        raw::Pattern::Literal(ref literal) => {
+            let (literal, ty) = infer_literal(context, literal)?;
+            Ok((RcPattern::from(Pattern::Literal(literal)), ty))
+        },
+        raw::Pattern::Wildcard(span) => Err(TypeError::BinderNeedsAnnotation {
+            span,
+            binder: Binder(FreeVar::fresh_named("_")),
+        }),
+        raw::Pattern::VariantIntro(span, _, _) => Err(TypeError::AmbiguousVariantIntro { span }),
+        raw::Pattern::RecordIntro(_, ref raw_fields) => {
+            let mut fields = Vec::with_capacity(raw_fields.len());
+            let mut ty_fields = Vec::with_capacity(raw_fields.len());
+
+            for (i, &(ref label, ref raw_pattern)) in raw_fields.iter().enumerate() {
+                let (pattern, ty) = infer_pattern(context, raw_pattern, declarations)?;
+                // The types of the fields don't depend on each other, but
+                // they are still found underneath the earlier fields
+                let ty = nbe::quote(context, context.size() + i, &ty)?;
+
+                fields.push((label.clone(), pattern));
+                let name = NameHint::from(label.0.as_str());
+                ty_fields.push((label.clone(), name, Multiplicity::Many, ty));
+            }
+
+            Ok((
+                RcPattern::from(Pattern::RecordIntro(fields)),
+                RcValue::from(Value::RecordType(Telescope {
+                    locals: context.local_values().clone(),
+                    fields: ty_fields,
+                })),
+            ))
+        },
+        raw::Pattern::ArrayIntro(span, _) => Err(TypeError::AmbiguousArrayLiteral { span }),
+    }
+}
+
+/// Returns the value that is matched by a pattern, in terms of the variables
+/// bound by the pattern, which are given levels starting from `level`
+fn pattern_value(
+    context: &Context,
+    pattern: &RcPattern,
+    level: &mut VarLevel,
+) -> Result<RcValue, TypeError> {
+    match *pattern.inner {
+        Pattern::Ann(ref pattern, _) => pattern_value(context, pattern, level),
+        Pattern::Binder(_) => {
+            let value = RcValue::from(Value::var(*level, 0));
+            level.0 += 1;
+            Ok(value)
         },
-        raw::Pattern::Literal(ref literal) => {
-            let (literal, ty) = infer_literal(context, literal)?;
-            Ok((RcPattern::from(Pattern::Literal(literal)), ty, vec![]))
+        Pattern::Var(index, shift) => Ok(context.eval(&RcTerm::from(Term::Var(index, shift)))?),
+        Pattern::Global(ref free_var, shift) => {
+            Ok(context.eval(&RcTerm::from(Term::Global(free_var.clone(), shift)))?)
+        },
+        Pattern::Literal(ref literal) => Ok(RcValue::from(Value::Literal(literal.clone()))),
+        Pattern::VariantIntro(ref label, ref pattern) => Ok(RcValue::from(Value::VariantIntro(
+            label.clone(),
+            pattern_value(context, pattern, level)?,
+        ))),
+        Pattern::RecordIntro(ref fields) => {
+            let fields = fields
+                .iter()
+                .map(|&(ref label, ref pattern)| {
+                    Ok((label.clone(), pattern_value(context, pattern, level)?))
+                })
+                .collect::<Result<_, TypeError>>()?;
+
+            Ok(RcValue::from(Value::RecordIntro(fields)))
         },
+        Pattern::ArrayIntro(ref elems) => {
+            let elems = elems
+                .iter()
+                .map(|elem| pattern_value(context, elem, level))
+                .collect::<Result<_, _>>()?;
+
+            Ok(RcValue::from(Value::ArrayIntro(elems)))
+        },
+    }
+}
+
+/// Ensure that the clauses of a case expression cover every value of the
+/// head's type, warning about any clauses that can never be matched
+fn check_coverage(
+    context: &Context,
+    span: ByteSpan,
+    head_ty: &RcType,
+    raw_clauses: &[Scope<raw::RcPattern, raw::RcTerm>],
+    clauses: &[(RcPattern, RcTerm)],
+) -> Result<(), TypeError> {
+    let patterns = clauses
+        .iter()
+        .map(|&(ref pattern, _)| pattern.clone())
+        .collect::<Vec<_>>();
+    let coverage = coverage::check_clauses(context, head_ty, &patterns)?;
+
+    for index in coverage.redundant {
+        let span = raw_clauses[index].unsafe_pattern.span();
+        context.warn(TypeWarning::UnreachableClause { span });
+    }
+
+    match coverage.missing {
+        None => Ok(()),
+        Some(missing) => Err(TypeError::NonExhaustivePatterns {
+            span,
+            missing: Box::new(context.resugar(&missing)),
+        }),
     }
 }
 
 /// Checks that a term is compatible with the given type, returning the
 /// elaborated term if successful
+///
+/// Any metavariables that were created during elaboration must be solved by
+/// the end of checking, and will be replaced with their solutions. Postponed
+/// problems, like instance arguments, are solved before this happens, followed
+/// by the universe levels that were left to be inferred.
 pub fn check_term(
     context: &Context,
     raw_term: &raw::RcTerm,
     expected_ty: &RcType,
 ) -> Result<RcTerm, TypeError> {
+    let term = check(context, raw_term, expected_ty);
+    // Take the postponed problems even if checking failed, so that they don't
+    // linger in the context
+    let postponed = context.take_postponed();
+    let result = term.and_then(|term| {
+        solve_postponed(postponed)?;
+        solve_levels(context, raw_term.span())?;
+        let term = zonk_term(context, context.size(), &term)?;
+        usage::check_erased_fields(raw_term.span(), &term)?;
+
+        Ok(term)
+    });
+    // Likewise for the level constraints, which are left behind on failure
+    context.take_level_constraints();
+
+    result
+}
+
+/// Synthesize the type of a term, returning the elaborated term and the
+/// inferred type if successful
+///
+/// Any metavariables that were created during elaboration must be solved by
+/// the end of inference, and will be replaced with their solutions. Postponed
+/// problems, like instance arguments, are solved before this happens, followed
+/// by the universe levels that were left to be inferred.
+pub fn infer_term(
+    context: &Context,
+    raw_term: &raw::RcTerm,
+) -> Result<(RcTerm, RcType), TypeError> {
+    let result = infer(context, raw_term);
+    // Take the postponed problems even if inference failed, so that they don't
+    // linger in the context
+    let postponed = context.take_postponed();
+    let result = result.and_then(|(term, ty)| {
+        solve_postponed(postponed)?;
+        solve_levels(context, raw_term.span())?;
+        let term = zonk_term(context, context.size(), &term)?;
+        usage::check_erased_fields(raw_term.span(), &term)?;
+        let ty = context.eval(&zonk_term(context, context.size(), &context.quote(&ty)?)?)?;
+
+        Ok((term, ty))
+    });
+    // Likewise for the level constraints, which are left behind on failure
+    context.take_level_constraints();
+
+    result
+}
+
+/// Checks that a term is compatible with the given type, returning the
+/// elaborated term, which may still contain metavariables
+fn check(
+    context: &Context,
+    raw_term: &raw::RcTerm,
+    expected_ty: &RcType,
+) -> Result<RcTerm, TypeError> {
+    let expected_ty = &unify::force(context, expected_ty)?;
+
     match (&*raw_term.inner, &*expected_ty.inner) {
+        // C-LEVEL
+        (&raw::Term::Literal(raw::Literal::Int(_, value, _)), &Value::LevelType) => {
+            // FIXME: overflow?
+            return Ok(RcTerm::from(Term::LevelIntro(Level(value as u32))));
+        },
+
         (&raw::Term::Literal(ref raw_literal), _) => {
+            // We can't tell what sort of number a numeric literal is until we
+            // know its type, so we postpone it if the type is yet to be solved
+            let is_numeric = match *raw_literal {
+                raw::Literal::Int(_, _, _) | raw::Literal::Float(_, _, _) => true,
+                raw::Literal::String(_, _) | raw::Literal::Char(_, _) => false,
+            };
+            if is_numeric && expected_ty.meta_app().is_some() {
+                let origin = MetaOrigin::Literal(raw_literal.clone());
+                return Ok(fresh_postponed_meta(context, origin, expected_ty));
+            }
+
             let literal = check_literal(context, raw_literal, expected_ty)?;
             return Ok(RcTerm::from(Term::Literal(literal)));
         },
 
         // C-LAM
-        (&raw::Term::FunIntro(_, ref fun_scope), &Value::FunType(ref fun_ty_scope)) => {
-            let (
-                (fun_name, Embed(fun_ann)),
-                fun_body,
-                (Binder(fun_ty_name), Embed(fun_ty_ann)),
-                fun_ty_body,
-            ) = Scope::unbind2(fun_scope.clone(), fun_ty_scope.clone());
+        (
+            &raw::Term::FunIntro(span, fun_plicity, fun_multiplicity, ref fun_scope),
+            &Value::FunType(fun_ty_plicity, fun_ty_multiplicity, ref fun_ty_ann, ref fun_ty_body),
+        ) if fun_plicity == fun_ty_plicity => {
+            // Lambdas without a multiplicity take the one from the expected type
+            if fun_multiplicity.is_none() {
+                let raw_term = raw::RcTerm::from(raw::Term::FunIntro(
+                    span,
+                    fun_plicity,
+                    Some(fun_ty_multiplicity),
+                    fun_scope.clone(),
+                ));
+                return check(context, &raw_term, expected_ty);
+            }
+
+            let ((Binder(free_var), Embed(fun_ann)), fun_body) = fun_scope.clone().unbind();
+
+            // Elaborate the hole, if it exists. Lambdas with a different
+            // multiplicity are left to be reported as mismatches by C-CONV
+            let is_hole = match *fun_ann.inner {
+                raw::Term::Hole(_) => true,
+                _ => false,
+            };
+            if is_hole && fun_multiplicity == Some(fun_ty_multiplicity) {
+                let fun_ty_body = nbe::apply_closure(context, fun_ty_body, context.fresh_var())?;
 
-            // Elaborate the hole, if it exists
-            if let raw::Term::Hole(_) = *fun_ann.inner {
-                let fun_ann = RcTerm::from(Term::from(&*fun_ty_ann));
+                let fun_ann = context.quote(fun_ty_ann)?;
                 let fun_body = {
                     let mut body_context = context.clone();
-                    body_context.insert_declaration(fun_ty_name, fun_ty_ann);
-                    check_term(&body_context, &fun_body, &fun_ty_body)?
+                    body_context.insert_local(free_var.clone(), fun_ty_ann.clone());
+                    if fun_plicity == Plicity::Instance {
+                        body_context.insert_instance(free_var.clone());
+                    }
+                    check(&body_context, &fun_body, &fun_ty_body)?
                 };
-                let fun_scope = Scope::new((fun_name, Embed(fun_ann)), fun_body);
-
-                return Ok(RcTerm::from(Term::FunIntro(fun_scope)));
+                usage::check_param(
+                    span,
+                    &Binder(free_var.clone()),
+                    fun_ty_multiplicity,
+                    &fun_body,
+                )?;
+                let name = NameHint::from(&free_var);
+
+                return Ok(RcTerm::from(Term::FunIntro(
+                    fun_plicity,
+                    fun_ty_multiplicity,
+                    name,
+                    fun_ann,
+                    fun_body,
+                )));
             }
 
             // TODO: We might want to optimise for this case, rather than
             // falling through to `infer` and unbinding again at I-LAM
         },
-        (&raw::Term::FunIntro(_, _), _) => {
+
+        // C-LAM-IMPLICIT
+        (
+            _,
+            &Value::FunType(fun_ty_plicity, fun_ty_multiplicity, ref fun_ty_ann, ref fun_ty_body),
+        ) if fun_ty_plicity != Plicity::Explicit
+            && needs_implicit_fun_intro(raw_term, fun_ty_plicity) =>
+        {
+            // The parameter can't be referred to in the raw term, so we give
+            // it a fresh variable in case it is an instance parameter
+            let name = fun_ty_body.name.clone();
+            let free_var = match name.0 {
+                Some(ref name) => FreeVar::fresh_named(name.clone()),
+                None => FreeVar::fresh_unnamed(),
+            };
+            let fun_ty_body = nbe::apply_closure(context, fun_ty_body, context.fresh_var())?;
+
+            let fun_ann = context.quote(fun_ty_ann)?;
+            let fun_body = {
+                let mut body_context = context.clone();
+                body_context.insert_local(free_var.clone(), fun_ty_ann.clone());
+                if fun_ty_plicity == Plicity::Instance {
+                    body_context.insert_instance(free_var);
+                }
+                check(&body_context, raw_term, &fun_ty_body)?
+            };
+
+            return Ok(RcTerm::from(Term::FunIntro(
+                fun_ty_plicity,
+                fun_ty_multiplicity,
+                name,
+                fun_ann,
+                fun_body,
+            )));
+        },
+        (&raw::Term::FunIntro(_, _, _, _), _) if expected_ty.meta_app().is_none() => {
             return Err(TypeError::UnexpectedFunction {
                 span: raw_term.span(),
                 expected: Box::new(context.resugar(expected_ty)),
@@ -266,40 +1881,33 @@ pub fn check_term(
         },
 
         // C-RECORD
-        (&raw::Term::RecordIntro(span, ref raw_fields), &Value::RecordType(ref raw_ty_scope)) => {
-            let (raw_ty_fields, ()) = {
-                let expected_size = raw_ty_scope.unsafe_pattern.binders().len();
-                if raw_fields.len() == raw_ty_scope.unsafe_pattern.binders().len() {
-                    raw_ty_scope.clone().unbind()
-                } else {
-                    return Err(TypeError::RecordSizeMismatch {
-                        span,
-                        found_size: raw_fields.len() as u64,
-                        expected_size: expected_size as u64,
-                    });
-                }
-            };
-
-            let raw_ty_fields = raw_ty_fields.unnest();
+        (&raw::Term::RecordIntro(span, ref raw_fields), &Value::RecordType(ref telescope)) => {
+            if raw_fields.len() != telescope.fields.len() {
+                return Err(TypeError::RecordSizeMismatch {
+                    span,
+                    found_size: raw_fields.len() as u64,
+                    expected_size: telescope.fields.len() as u64,
+                });
+            }
 
             // FIXME: Check that record is well-formed?
-            let fields = {
-                let mut mappings = Vec::with_capacity(raw_fields.len());
-                <_>::zip(raw_fields.iter(), raw_ty_fields.into_iter())
+            let fields: Vec<_> = {
+                let mut locals = telescope.locals.clone();
+                <_>::zip(raw_fields.iter(), telescope.fields.iter())
                     .map(|(field, ty_field)| {
                         let &(ref label, ref raw_expr) = field;
-                        let (ty_label, Binder(ty_free_var), Embed(ann)) = ty_field;
+                        let &(ref ty_label, _, multiplicity, ref ann) = ty_field;
 
-                        if *label == ty_label {
-                            let ann = nbe::nf_term(context, &ann.substs(&mappings))?;
-                            let expr = check_term(context, &raw_expr, &ann)?;
-                            mappings.push((ty_free_var, expr.clone()));
-                            Ok((label.clone(), expr))
+                        if label == ty_label {
+                            let ann = nbe::eval(context, &locals, ann)?;
+                            let expr = check(context, &raw_expr, &ann)?;
+                            locals.push_back(context.eval(&expr)?);
+                            Ok((label.clone(), multiplicity, expr))
                         } else {
                             Err(TypeError::LabelMismatch {
                                 span,
                                 found: label.clone(),
-                                expected: ty_label,
+                                expected: ty_label.clone(),
                             })
                         }
                     })
@@ -309,28 +1917,70 @@ pub fn check_term(
             return Ok(RcTerm::from(Term::RecordIntro(fields)));
         },
 
-        (&raw::Term::Case(_, ref raw_head, ref raw_clauses), _) => {
-            let (head, head_ty) = infer_term(context, raw_head)?;
+        // C-REFL
+        (&raw::Term::EqIntro(span), &Value::EqType(_, ref lhs, ref rhs)) => {
+            return if unify::unify(context, lhs, rhs)? {
+                Ok(RcTerm::from(Term::EqIntro))
+            } else {
+                Err(TypeError::UnequalTerms {
+                    span,
+                    lhs: Box::new(context.resugar(lhs)),
+                    rhs: Box::new(context.resugar(rhs)),
+                })
+            };
+        },
+
+        // C-VARIANT
+        (&raw::Term::VariantIntro(span, ref label, ref raw_arg), &Value::VariantType(ref alts)) => {
+            let alt = alts
+                .alts
+                .iter()
+                .find(|&&(ref alt_label, _)| alt_label == label);
+            return match alt {
+                Some(&(_, ref alt_ann)) => {
+                    let alt_ty = nbe::eval(context, &alts.locals, alt_ann)?;
+                    let arg = check(context, raw_arg, &alt_ty)?;
+                    Ok(RcTerm::from(Term::VariantIntro(label.clone(), arg)))
+                },
+                None => Err(TypeError::NoVariantInType {
+                    span,
+                    expected_label: label.clone(),
+                    found: Box::new(context.resugar(expected_ty)),
+                }),
+            };
+        },
+        (&raw::Term::VariantIntro(span, _, _), _) if expected_ty.meta_app().is_none() => {
+            return Err(TypeError::UnexpectedVariant {
+                span,
+                expected: Box::new(context.resugar(expected_ty)),
+            });
+        },
+
+        (&raw::Term::Case(span, ref raw_head, ref raw_clauses), _) => {
+            let (head, head_ty) = infer(context, raw_head)?;
 
-            // TODO: ensure that patterns are exhaustive
-            let clauses = raw_clauses
+            let clauses: Vec<_> = raw_clauses
                 .iter()
                 .map(|raw_clause| {
                     let (raw_pattern, raw_body) = raw_clause.clone().unbind();
-                    let (pattern, declarations) = check_pattern(context, &raw_pattern, &head_ty)?;
+                    let mut declarations = Vec::new();
+                    let pattern =
+                        check_pattern(context, &raw_pattern, &head_ty, &mut declarations)?;
 
                     let body = {
                         let mut body_context = context.clone();
                         for (free_var, ty) in declarations {
-                            body_context.insert_declaration(free_var, ty);
+                            body_context.insert_local(free_var, ty);
                         }
-                        check_term(&body_context, &raw_body, expected_ty)?
+                        check(&body_context, &raw_body, expected_ty)?
                     };
 
-                    Ok(Scope::new(pattern, body))
+                    Ok((pattern, body))
                 })
                 .collect::<Result<_, TypeError>>()?;
 
+            check_coverage(context, span, &head_ty, raw_clauses, &clauses)?;
+
             return Ok(RcTerm::from(Term::Case(head, clauses)));
         },
 
@@ -339,7 +1989,7 @@ pub fn check_term(
                 Some((len, elem_ty)) if len == elems.len() as u64 => {
                     let elems = elems
                         .iter()
-                        .map(|elem| check_term(context, elem, elem_ty))
+                        .map(|elem| check(context, elem, elem_ty))
                         .collect::<Result<_, _>>()?;
 
                     Ok(RcTerm::from(Term::ArrayIntro(elems)))
@@ -357,60 +2007,250 @@ pub fn check_term(
         },
 
         (&raw::Term::Hole(span), _) => {
-            let expected = Some(Box::new(context.resugar(expected_ty)));
-            return Err(TypeError::UnableToElaborateHole { span, expected });
+            return Ok(fresh_meta(
+                context,
+                MetaOrigin::Hole(span),
+                Some(expected_ty),
+            ));
         },
 
         _ => {},
     }
 
     // C-CONV
-    let (term, inferred_ty) = infer_term(context, raw_term)?;
-    if is_subtype(context, &inferred_ty, expected_ty) {
-        Ok(term)
-    } else {
-        Err(TypeError::Mismatch {
-            span: raw_term.span(),
-            found: Box::new(context.resugar(&inferred_ty)),
-            expected: Box::new(context.resugar(expected_ty)),
+    let (term, inferred_ty) = infer(context, raw_term)?;
+    let (term, inferred_ty) = match *expected_ty.inner {
+        Value::FunType(plicity, _, _, _) if plicity != Plicity::Explicit => (term, inferred_ty),
+        _ => insert_implicit_args(context, raw_term.span(), term, inferred_ty)?,
+    };
+    // Variables that were written without a shift might fit once they have
+    // been shifted, so we keep a snapshot around to try again from
+    let snapshot = match *raw_term.inner {
+        raw::Term::Var(_, Var::Free(_), None) => Some(context.snapshot_metas()),
+        _ => None,
+    };
+    if is_subtype(context, &inferred_ty, expected_ty)? {
+        return Ok(term);
+    }
+
+    // C-RECORD-WIDTH
+    let inferred_ty = unify::force(context, &inferred_ty)?;
+    if let (&Value::RecordType(ref telescope1), &Value::RecordType(ref telescope2)) = (
+        &*inferred_ty.inner,
+        &*unify::force(context, expected_ty)?.inner,
+    ) {
+        if let Some(term) = coerce_record(context, &term, telescope1, telescope2)? {
+            return Ok(term);
+        }
+    }
+
+    // C-VAR-SHIFT
+    if let (Some(snapshot), &raw::Term::Var(span, Var::Free(ref free_var), None)) =
+        (snapshot, &*raw_term.inner)
+    {
+        context.rollback_metas(snapshot);
+        if let Some(term) = check_var_shift(context, span, free_var, expected_ty)? {
+            return Ok(term);
+        }
+    }
+
+    Err(TypeError::Mismatch {
+        span: raw_term.span(),
+        found: Box::new(context.resugar(&inferred_ty)),
+        expected: Box::new(context.resugar(expected_ty)),
+    })
+}
+
+/// Coerce a record to a record type that has fewer fields, by projecting out
+/// the fields that the expected type asks for
+///
+/// The fields of `telescope2` must appear in the same order in `telescope1`,
+/// but are allowed to be interspersed with fields that will be dropped.
+/// Returns `None` if there is no such coercion.
+///
+/// NOTE: Only the outermost record is coerced - the types of the fields that
+/// are kept must be subtypes without any further coercions
+fn coerce_record(
+    context: &Context,
+    term: &RcTerm,
+    telescope1: &Telescope,
+    telescope2: &Telescope,
+) -> Result<Option<RcTerm>, TypeError> {
+    if telescope2.fields.len() >= telescope1.fields.len() {
+        return Ok(None);
+    }
+
+    let snapshot = context.snapshot_metas();
+    let mut labels = Vec::with_capacity(telescope2.fields.len());
+    {
+        let mut context = context.clone();
+        let mut locals1 = telescope1.locals.clone();
+        let mut locals2 = telescope2.locals.clone();
+        let mut fields2 = telescope2.fields.iter().peekable();
+
+        for &(ref label1, ref name1, multiplicity1, ref ty1) in &telescope1.fields {
+            let ty1 = nbe::eval(&context, &locals1, ty1)?;
+            let var = context.fresh_var();
+
+            match fields2.peek() {
+                Some(&&(ref label2, _, multiplicity2, ref ty2)) if label1 == label2 => {
+                    let ty2 = nbe::eval(&context, &locals2, ty2)?;
+                    if multiplicity1 != multiplicity2 || !is_subtype(&context, &ty1, &ty2)? {
+                        context.rollback_metas(snapshot);
+                        return Ok(None);
+                    }
+
+                    labels.push((label1.clone(), multiplicity1));
+                    locals2.push_back(var.clone());
+                    fields2.next();
+                },
+                Some(_) | None => {},
+            }
+
+            locals1.push_back(var);
+            context.insert_fresh_local(name1.clone(), ty1);
+        }
+
+        if fields2.next().is_some() {
+            context.rollback_metas(snapshot);
+            return Ok(None);
+        }
+    }
+
+    let fields = labels
+        .into_iter()
+        .map(|(label, multiplicity)| (label, multiplicity, None))
+        .collect();
+    Ok(Some(rebuild_record(term, fields)))
+}
+
+/// Build a new record from the fields of an existing record
+///
+/// Fields paired with `None` are projected from the existing record, which is
+/// bound first if needed, to avoid duplicating it in each projection.
+fn rebuild_record(record: &RcTerm, fields: Vec<(Label, Multiplicity, Option<RcTerm>)>) -> RcTerm {
+    let (binding, record, count) = match *record.inner {
+        Term::Var(_, _) | Term::Global(_, _) => (None, record.clone(), 0),
+        _ => (
+            Some((NameHint::from("record"), record.clone())),
+            RcTerm::from(Term::Var(VarIndex(0), LevelShift(0))),
+            1,
+        ),
+    };
+
+    let fields = fields
+        .into_iter()
+        .map(|(label, multiplicity, term)| match term {
+            Some(term) => (label, multiplicity, term.shift_vars(count)),
+            None => {
+                let term = RcTerm::from(Term::RecordProj(
+                    record.clone(),
+                    label.clone(),
+                    multiplicity,
+                    LevelShift(0),
+                ));
+                (label, multiplicity, term)
+            },
         })
+        .collect();
+    let body = RcTerm::from(Term::RecordIntro(fields));
+
+    match binding {
+        None => body,
+        Some(binding) => RcTerm::from(Term::Let(vec![binding], body)),
     }
 }
 
-/// Synthesize the type of a term, returning the elaborated term and the
-/// inferred type if successful
-pub fn infer_term(
+/// Find the least shift that allows a variable to be used at the expected
+/// type, returning `None` if there is no such shift
+///
+/// Shifting a definition past the level of the expected type would only make
+/// it too large to fit, so we don't search any further than that.
+fn check_var_shift(
     context: &Context,
-    raw_term: &raw::RcTerm,
-) -> Result<(RcTerm, RcType), TypeError> {
-    use std::cmp;
+    span: ByteSpan,
+    free_var: &FreeVar<String>,
+    expected_ty: &RcType,
+) -> Result<Option<RcTerm>, TypeError> {
+    let max_shift = match type_level(context, span, expected_ty) {
+        Ok(UniverseLevel::Finite(ref level)) => match level.as_constant() {
+            Some(level) => level.0,
+            None => return Ok(None),
+        },
+        Ok(UniverseLevel::Omega) | Err(_) => return Ok(None),
+    };
+
+    for shift in 1..=max_shift {
+        let shift = LevelShift(shift);
+        let (term, mut ty) = match context.lookup(free_var, shift) {
+            Some((term, ty)) => (term, ty.clone()),
+            None => return Ok(None),
+        };
+        ty.shift_universes(shift);
+
+        // NOTE: Implicit arguments are not inserted here, so variables with
+        // implicit parameters must be shifted explicitly
+        let snapshot = context.snapshot_metas();
+        if is_subtype(context, &ty, expected_ty)? {
+            return Ok(Some(term));
+        }
+        context.rollback_metas(snapshot);
+    }
+
+    Ok(None)
+}
 
+/// Synthesize the type of a term, returning the elaborated term, which may
+/// still contain metavariables, and the inferred type
+fn infer(context: &Context, raw_term: &raw::RcTerm) -> Result<(RcTerm, RcType), TypeError> {
     match *raw_term.inner {
         //  I-ANN
         raw::Term::Ann(ref raw_term, ref raw_ty) => {
             if let raw::Term::Hole(_) = *raw_ty.inner {
-                let (term, value_ty) = infer_term(context, &raw_term)?;
-                let ty = RcTerm::from(&*value_ty);
+                let (term, value_ty) = infer(context, &raw_term)?;
+                let ty = context.quote(&value_ty)?;
 
                 Ok((RcTerm::from(Term::Ann(term, ty)), value_ty))
             } else {
                 let (ty, _) = infer_universe(context, &raw_ty)?;
-                let value_ty = nbe::nf_term(context, &ty)?;
-                let term = check_term(context, raw_term, &value_ty)?;
+                let value_ty = context.eval(&ty)?;
+                let term = check(context, raw_term, &value_ty)?;
 
                 Ok((RcTerm::from(Term::Ann(term, ty)), value_ty))
             }
         },
 
         // I-TYPE
-        raw::Term::Universe(_, level) => Ok((
+        raw::Term::Universe(_, Some(level)) => Ok((
             RcTerm::from(Term::Universe(level)),
             RcValue::from(Value::Universe(level.succ())),
         )),
+        raw::Term::Universe(span, None) => {
+            // The level is left for the level solver to find, once we know
+            // what the universe needs to contain
+            let meta = fresh_level_meta(context, span);
+            let level = RcTerm::from(Term::Meta(meta));
+            let ty = Value::universe_at(LevelValue::meta(meta, 1));
+
+            Ok((RcTerm::from(Term::UniverseAt(level)), RcValue::from(ty)))
+        },
+        raw::Term::UniverseAt(span, ref raw_level) => {
+            let level = check(context, raw_level, &RcValue::from(Value::LevelType))?;
+            let level_value = match unify::level_value(context, &context.eval(&level)?)? {
+                Some(level_value) => UniverseLevel::Finite(level_value),
+                None => return Err(TypeError::AmbiguousLevel { span }),
+            };
+            let ty = level_value.succ().expect("finite levels have successors");
+
+            Ok((RcTerm::from(Term::UniverseAt(level)), ty.to_universe()))
+        },
 
         raw::Term::Hole(span) => {
-            let expected = None;
-            Err(TypeError::UnableToElaborateHole { span, expected })
+            let ty = fresh_meta(context, MetaOrigin::Hole(span), None);
+            let ty = context.eval(&ty)?;
+            let term = fresh_meta(context, MetaOrigin::Hole(span), Some(&ty));
+
+            Ok((term, ty))
         },
 
         raw::Term::Literal(ref raw_literal) => {
@@ -420,17 +2260,20 @@ pub fn infer_term(
 
         // I-VAR
         raw::Term::Var(span, ref var, shift) => match *var {
-            Var::Free(ref free_var) => match context.get_declaration(free_var) {
-                Some(ty) => {
-                    let mut ty = ty.clone();
-                    ty.shift_universes(shift);
+            Var::Free(ref free_var) => {
+                let shift = shift.unwrap_or(LevelShift(0));
+                match context.lookup(free_var, shift) {
+                    Some((term, ty)) => {
+                        let mut ty = ty.clone();
+                        ty.shift_universes(shift);
 
-                    Ok((RcTerm::from(Term::Var(var.clone(), shift)), ty))
-                },
-                None => Err(TypeError::UndefinedName {
-                    span,
-                    free_var: free_var.clone(),
-                }),
+                        Ok((term, ty))
+                    },
+                    None => Err(TypeError::UndefinedName {
+                        span,
+                        free_var: free_var.clone(),
+                    }),
+                }
             },
 
             // We should always be substituting bound variables with fresh
@@ -452,95 +2295,113 @@ pub fn infer_term(
         },
 
         // I-PI
-        raw::Term::FunType(_, ref raw_scope) => {
+        raw::Term::FunType(_, plicity, multiplicity, ref raw_scope) => {
             let ((Binder(free_var), Embed(raw_ann)), raw_body) = raw_scope.clone().unbind();
 
             let (ann, ann_level) = infer_universe(context, &raw_ann)?;
             let (body, body_level) = {
-                let ann = nbe::nf_term(context, &ann)?;
+                let ann = context.eval(&ann)?;
                 let mut body_context = context.clone();
-                body_context.insert_declaration(free_var.clone(), ann);
+                body_context.insert_local(free_var.clone(), ann);
                 infer_universe(&body_context, &raw_body)?
             };
-
-            let param = (Binder(free_var), Embed(ann));
+            let name = NameHint::from(&free_var);
+            // Level polymorphic function types live in the omega universe
+            let level = ann_level.max(&body_level.bind(VarLevel(context.size())));
 
             Ok((
-                RcTerm::from(Term::FunType(Scope::new(param, body))),
-                RcValue::from(Value::Universe(cmp::max(ann_level, body_level))),
+                RcTerm::from(Term::FunType(plicity, multiplicity, name, ann, body)),
+                level.to_universe(),
             ))
         },
 
         // I-LAM
-        raw::Term::FunIntro(_, ref raw_scope) => {
+        raw::Term::FunIntro(span, plicity, multiplicity, ref raw_scope) => {
             let ((Binder(free_var), Embed(raw_ann)), raw_body) = raw_scope.clone().unbind();
-
-            // Check for holes before entering to ensure we get a nice error
-            if let raw::Term::Hole(_) = *raw_ann {
-                return Err(TypeError::FunctionParamNeedsAnnotation {
-                    param_span: ByteSpan::default(), // TODO: param.span(),
-                    var_span: None,
-                    name: free_var.clone(),
-                });
-            }
-
-            let (fun_ann, _) = infer_universe(context, &raw_ann)?;
-            let fun_ty_ann = nbe::nf_term(context, &fun_ann)?;
+            let multiplicity = multiplicity.unwrap_or(Multiplicity::Many);
+
+            let fun_ann = match *raw_ann {
+                // Fill in missing annotations with metavariables, in the hope
+                // that they will be solved when elaborating the body
+                raw::Term::Hole(span) => {
+                    let origin = MetaOrigin::FunParamAnn(span, free_var.clone());
+                    let ty = RcValue::from(Value::universe(0));
+                    fresh_meta(context, origin, Some(&ty))
+                },
+                _ => infer_universe(context, &raw_ann)?.0,
+            };
+            let fun_ty_ann = context.eval(&fun_ann)?;
             let (fun_body, fun_ty_body) = {
                 let mut body_context = context.clone();
-                body_context.insert_declaration(free_var.clone(), fun_ty_ann.clone());
-                infer_term(&body_context, &raw_body)?
+                body_context.insert_local(free_var.clone(), fun_ty_ann.clone());
+                if plicity == Plicity::Instance {
+                    body_context.insert_instance(free_var.clone());
+                }
+                let (fun_body, fun_ty_body) = infer(&body_context, &raw_body)?;
+                (fun_body, body_context.quote(&fun_ty_body)?)
             };
-
-            let fun_param = (Binder(free_var.clone()), Embed(fun_ann));
-            let fun_ty_param = (Binder(free_var.clone()), Embed(fun_ty_ann));
+            usage::check_param(span, &Binder(free_var.clone()), multiplicity, &fun_body)?;
+            let name = NameHint::from(&free_var);
+            let fun_ty_body =
+                Closure::new(context.local_values().clone(), name.clone(), fun_ty_body);
 
             Ok((
-                RcTerm::from(Term::FunIntro(Scope::new(fun_param, fun_body))),
-                RcValue::from(Value::FunType(Scope::new(fun_ty_param, fun_ty_body))),
+                RcTerm::from(Term::FunIntro(
+                    plicity,
+                    multiplicity,
+                    name,
+                    fun_ann,
+                    fun_body,
+                )),
+                RcValue::from(Value::FunType(
+                    plicity,
+                    multiplicity,
+                    fun_ty_ann,
+                    fun_ty_body,
+                )),
             ))
         },
 
         // I-LET
         raw::Term::Let(_, ref raw_scope) => {
-            let (raw_fields, raw_body) = raw_scope.clone().unbind();
-
-            let (term, ty) = {
-                let mut context = context.clone();
-                let bindings = raw_fields
-                    .unnest()
-                    .into_iter()
-                    .map(|(Binder(free_var), Embed(raw_term))| {
-                        let (term, term_ty) = infer_term(&context, &raw_term)?;
-
-                        context.insert_definition(free_var.clone(), term.clone());
-                        context.insert_declaration(free_var.clone(), term_ty);
-
-                        Ok((Binder(free_var), Embed(term)))
-                    })
-                    .collect::<Result<_, TypeError>>()?;
-
-                let (body, ty) = infer_term(&context, &raw_body)?;
-                let term = RcTerm::from(Term::Let(Scope::new(Nest::new(bindings), body)));
-
-                (term, ty)
-            };
+            let (raw_bindings, raw_body) = raw_scope.clone().unbind();
+
+            let mut body_context = context.clone();
+            let bindings = infer_let_bindings(&mut body_context, &raw_bindings.unrec())?;
+            let num_bindings = bindings.len();
+
+            let (body, ty) = infer(&body_context, &raw_body)?;
+            let term = RcTerm::from(Term::Let(bindings, body));
+
+            // The type of the body might be glued to the let bindings, which
+            // are about to go out of scope, so we read it back without them
+            let ty = body_context
+                .quote(&ty)?
+                .try_subst_vars(&mut |index, shift| {
+                    let index = VarIndex(index.0.checked_sub(num_bindings)?);
+                    Some(RcTerm::from(Term::Var(index, shift)))
+                });
 
-            Ok((term, ty))
+            match ty {
+                Some(ty) => Ok((term, context.eval(&ty)?)),
+                None => Err(TypeError::from(InternalError::Unimplemented {
+                    span: Some(raw_term.span()),
+                    message: "the type of a let body refers to its bindings".to_owned(),
+                })),
+            }
         },
 
         // I-APP
         raw::Term::FunApp(ref raw_head, ref raw_arg) => {
-            let (head, head_ty) = infer_term(context, raw_head)?;
+            let (head, head_ty) = infer(context, raw_head)?;
+            let (head, head_ty) = insert_implicit_args(context, raw_head.span(), head, head_ty)?;
 
             match *head_ty {
-                Value::FunType(ref scope) => {
-                    let ((Binder(free_var), Embed(ann)), body) = scope.clone().unbind();
-
-                    let arg = check_term(context, raw_arg, &ann)?;
-                    let body = nbe::nf_term(context, &body.substs(&[(free_var, arg.clone())]))?;
+                Value::FunType(_, multiplicity, ref ann, ref body) => {
+                    let arg = check(context, raw_arg, ann)?;
+                    let body = nbe::apply_closure(context, body, context.eval(&arg)?)?;
 
-                    Ok((RcTerm::from(Term::FunApp(head, arg)), body))
+                    Ok((RcTerm::from(Term::FunApp(head, multiplicity, arg)), body))
                 },
                 _ => Err(TypeError::ArgAppliedToNonFunction {
                     fn_span: raw_head.span(),
@@ -553,7 +2414,7 @@ pub fn infer_term(
         // I-RECORD-TYPE, I-EMPTY-RECORD-TYPE
         raw::Term::RecordType(_, ref raw_scope) => {
             let (raw_fields, ()) = raw_scope.clone().unbind();
-            let mut max_level = Level(0);
+            let mut max_level = UniverseLevel::constant(0);
 
             // FIXME: Check that record is well-formed?
             let fields = {
@@ -561,21 +2422,27 @@ pub fn infer_term(
                 raw_fields
                     .unnest()
                     .into_iter()
-                    .map(|(label, Binder(free_var), Embed(raw_ann))| {
+                    .map(|(label, multiplicity, Binder(free_var), Embed(raw_ann))| {
                         let (ann, ann_level) = infer_universe(&context, &raw_ann)?;
-                        let nf_ann = nbe::nf_term(&context, &ann)?;
+                        let ann_value = context.eval(&ann)?;
+                        let name = NameHint::from(&free_var);
 
-                        max_level = cmp::max(max_level, ann_level);
-                        context.insert_declaration(free_var.clone(), nf_ann);
+                        max_level = max_level.max(&ann_level);
+                        context.insert_local(free_var, ann_value);
 
-                        Ok((label, Binder(free_var), Embed(ann)))
+                        Ok((label, name, multiplicity, ann))
                     })
                     .collect::<Result<_, TypeError>>()?
             };
 
+            // Records that contain universe levels live in the omega universe
+            let max_level = (0..fields.len()).fold(max_level, |level, i| {
+                level.bind(VarLevel(context.size() + i))
+            });
+
             Ok((
-                RcTerm::from(Term::RecordType(Scope::new(Nest::new(fields), ()))),
-                RcValue::from(Value::Universe(max_level)),
+                RcTerm::from(Term::RecordType(fields)),
+                max_level.to_universe(),
             ))
         },
 
@@ -585,46 +2452,195 @@ pub fn infer_term(
             let mut ty_fields = Vec::with_capacity(raw_fields.len());
 
             // FIXME: error on duplicate field names
-            {
-                let mut ty_mappings = Vec::with_capacity(raw_fields.len());
-                for &(ref label, ref raw_term) in raw_fields {
-                    let free_var = FreeVar::fresh_named(label.0.clone());
-                    let (term, term_ty) = infer_term(context, &raw_term)?;
-                    let term_ty = nbe::nf_term(context, &term_ty.substs(&ty_mappings))?;
-
-                    fields.push((label.clone(), term.clone()));
-                    ty_fields.push((label.clone(), Binder(free_var.clone()), Embed(term_ty)));
-                    ty_mappings.push((free_var, term));
-                }
+            for (i, &(ref label, ref raw_term)) in raw_fields.iter().enumerate() {
+                let (term, term_ty) = infer(context, &raw_term)?;
+                // The types of the fields don't depend on each other, but
+                // they are still found underneath the earlier fields
+                let term_ty = nbe::quote(context, context.size() + i, &term_ty)?;
+
+                let name = NameHint::from(label.0.as_str());
+                fields.push((label.clone(), Multiplicity::Many, term));
+                ty_fields.push((label.clone(), name, Multiplicity::Many, term_ty));
             }
 
             Ok((
                 RcTerm::from(Term::RecordIntro(fields)),
-                RcValue::from(Value::RecordType(Scope::new(Nest::new(ty_fields), ()))),
+                RcValue::from(Value::RecordType(Telescope {
+                    locals: context.local_values().clone(),
+                    fields: ty_fields,
+                })),
             ))
         },
 
-        // I-PROJ
-        raw::Term::RecordProj(_, ref expr, label_span, ref label, shift) => {
-            let (expr, ty) = infer_term(context, expr)?;
+        // I-RECORD-EXTEND
+        raw::Term::RecordExtend(span, ref raw_expr, ref raw_fields) => {
+            let (expr, expr_ty) = infer(context, raw_expr)?;
+            let expr_ty = unify::force(context, &expr_ty)?;
+            let mut ty_fields = match *context.quote(&expr_ty)?.inner {
+                Term::RecordType(ref ty_fields) => ty_fields.clone(),
+                _ => {
+                    return Err(TypeError::ExpectedRecordType {
+                        span: raw_expr.span(),
+                        found: Box::new(context.resugar(&expr_ty)),
+                    });
+                },
+            };
+            check_distinct_labels(
+                span,
+                ty_fields.iter().map(|&(ref label, _, _, _)| label),
+                raw_fields.iter().map(|&(ref label, _)| label),
+            )?;
+
+            let size = context.size() + ty_fields.len();
+            let mut fields = Vec::with_capacity(raw_fields.len());
+
+            for (i, &(ref label, ref raw_term)) in raw_fields.iter().enumerate() {
+                let (term, term_ty) = infer(context, &raw_term)?;
+                // Like with record introductions, the new fields are found
+                // underneath all of the fields that came before them
+                let term_ty = nbe::quote(context, size + i, &term_ty)?;
+
+                let name = NameHint::from(label.0.as_str());
+                fields.push((label.clone(), term));
+                ty_fields.push((label.clone(), name, Multiplicity::Many, term_ty));
+            }
+
+            Ok((
+                RcTerm::from(Term::RecordExtend(expr, fields)),
+                RcValue::from(Value::RecordType(Telescope {
+                    locals: context.local_values().clone(),
+                    fields: ty_fields,
+                })),
+            ))
+        },
+
+        // I-RECORD-UPDATE
+        raw::Term::RecordUpdate(span, ref raw_expr, ref raw_fields) => {
+            let (expr, expr_ty) = infer(context, raw_expr)?;
+            let expr_ty = unify::force(context, &expr_ty)?;
+            let telescope = match *expr_ty.inner {
+                Value::RecordType(ref telescope) => telescope,
+                _ => {
+                    return Err(TypeError::ExpectedRecordType {
+                        span: raw_expr.span(),
+                        found: Box::new(context.resugar(&expr_ty)),
+                    });
+                },
+            };
+            check_distinct_labels(
+                span,
+                iter::empty(),
+                raw_fields.iter().map(|&(ref label, _)| label),
+            )?;
+            for &(ref label, _) in raw_fields {
+                if !telescope.fields.iter().any(|&(ref l, _, _, _)| l == label) {
+                    return Err(TypeError::NoFieldInType {
+                        label_span: span,
+                        expected_label: label.clone(),
+                        found: Box::new(context.resugar(&expr_ty)),
+                    });
+                }
+            }
+
+            let expr_value = context.eval(&expr)?;
+            let mut old_locals = telescope.locals.clone();
+            let mut new_locals = telescope.locals.clone();
+            let mut fields = Vec::with_capacity(telescope.fields.len());
+
+            for &(ref label, _, multiplicity, ref ann) in &telescope.fields {
+                let old_value = nbe::record_proj(&expr_value, label, LevelShift(0))?;
+                let new_ty = nbe::eval(context, &new_locals, ann)?;
+
+                match raw_fields.iter().find(|&&(ref l, _)| l == label) {
+                    Some(&(_, ref raw_term)) => {
+                        let term = check(context, raw_term, &new_ty)?;
+                        new_locals.push_back(context.eval(&term)?);
+                        fields.push((label.clone(), multiplicity, Some(term)));
+                    },
+                    None => {
+                        // Fields that are left alone still need to fit, in
+                        // case their types depend on a field that was changed
+                        let old_ty = nbe::eval(context, &old_locals, ann)?;
+                        if !is_subtype(context, &old_ty, &new_ty)? {
+                            return Err(TypeError::FieldNeedsUpdate {
+                                span,
+                                label: label.clone(),
+                                found: Box::new(context.resugar(&old_ty)),
+                                expected: Box::new(context.resugar(&new_ty)),
+                            });
+                        }
+                        new_locals.push_back(old_value.clone());
+                        fields.push((label.clone(), multiplicity, None));
+                    },
+                }
+
+                old_locals.push_back(old_value);
+            }
+
+            Ok((rebuild_record(&expr, fields), expr_ty.clone()))
+        },
+
+        // I-RECORD-TYPE-MERGE
+        raw::Term::RecordTypeMerge(span, ref raw_lhs, ref raw_rhs) => {
+            let (lhs_fields, lhs_level) = infer_record_type(context, raw_lhs)?;
+            let (rhs_fields, rhs_level) = infer_record_type(context, raw_rhs)?;
+            check_distinct_labels(
+                span,
+                lhs_fields.iter().map(|&(ref label, _, _, _)| label),
+                rhs_fields.iter().map(|&(ref label, _, _, _)| label),
+            )?;
+
+            // The fields on the right are moved underneath the fields on the
+            // left, so the variables that they refer to from outside of the
+            // record need to be adjusted
+            let count = lhs_fields.len();
+            let mut fields = lhs_fields;
+            fields.extend(rhs_fields.into_iter().enumerate().map(
+                |(i, (label, name, multiplicity, ann))| {
+                    let ann = ann.subst_vars(&mut |index, shift| {
+                        let index = if index.0 < i {
+                            index
+                        } else {
+                            VarIndex(index.0 + count)
+                        };
+                        RcTerm::from(Term::Var(index, shift))
+                    });
+
+                    (label, name, multiplicity, ann)
+                },
+            ));
 
-            if let Value::RecordType(ref scope) = *ty.inner {
-                let (fields, ()) = scope.clone().unbind();
-                let mut mappings = vec![];
+            Ok((
+                RcTerm::from(Term::RecordType(fields)),
+                lhs_level.max(&rhs_level).to_universe(),
+            ))
+        },
 
-                for (current_label, Binder(free_var), Embed(current_ann)) in fields.unnest() {
-                    if current_label == *label {
-                        let expr = RcTerm::from(Term::RecordProj(expr, current_label, shift));
-                        let mut ty = nbe::nf_term(context, &current_ann.substs(&mappings))?;
+        // I-PROJ
+        raw::Term::RecordProj(_, ref expr, label_span, ref label, shift) => {
+            let (expr, ty) = infer(context, expr)?;
+            let ty = unify::force(context, &ty)?;
+
+            if let Value::RecordType(ref telescope) = *ty.inner {
+                let expr_value = context.eval(&expr)?;
+                let mut locals = telescope.locals.clone();
+
+                for &(ref current_label, _, multiplicity, ref current_ann) in &telescope.fields {
+                    if current_label == label {
+                        let expr = RcTerm::from(Term::RecordProj(
+                            expr,
+                            label.clone(),
+                            multiplicity,
+                            shift,
+                        ));
+                        let mut ty = nbe::eval(context, &locals, current_ann)?;
                         ty.shift_universes(shift);
 
                         return Ok((expr, ty));
                     } else {
-                        mappings.push((
-                            free_var,
-                            // NOTE: Not sure if we should be shifting here...
-                            RcTerm::from(Term::RecordProj(expr.clone(), current_label, shift)),
-                        ));
+                        // NOTE: Not sure if we should be shifting here...
+                        let value = nbe::record_proj(&expr_value, current_label, shift)?;
+                        locals.push_back(value);
                     }
                 }
             }
@@ -636,30 +2652,108 @@ pub fn infer_term(
             })
         },
 
+        // I-VARIANT-TYPE, I-EMPTY-VARIANT-TYPE
+        raw::Term::VariantType(span, ref raw_alts) => {
+            let mut max_level = UniverseLevel::constant(0);
+            let mut seen = Vec::with_capacity(raw_alts.len());
+
+            let alts = raw_alts
+                .iter()
+                .map(|&(ref label, ref raw_ann)| {
+                    if seen.contains(&label) {
+                        return Err(TypeError::DuplicateVariant {
+                            span,
+                            label: label.clone(),
+                        });
+                    }
+                    seen.push(label);
+
+                    let (ann, ann_level) = infer_universe(context, raw_ann)?;
+                    max_level = max_level.max(&ann_level);
+
+                    Ok((label.clone(), ann))
+                })
+                .collect::<Result<_, TypeError>>()?;
+
+            Ok((
+                RcTerm::from(Term::VariantType(alts)),
+                max_level.to_universe(),
+            ))
+        },
+
+        raw::Term::VariantIntro(span, _, _) => Err(TypeError::AmbiguousVariantIntro { span }),
+
+        // I-EQ-TYPE
+        raw::Term::EqType(_, ref raw_lhs, ref raw_rhs) => {
+            let (lhs, ty) = infer(context, raw_lhs)?;
+            let rhs = check(context, raw_rhs, &ty)?;
+            let level = type_level(context, raw_term.span(), &ty)?;
+
+            Ok((
+                RcTerm::from(Term::EqType(context.quote(&ty)?, lhs, rhs)),
+                level.to_universe(),
+            ))
+        },
+
+        raw::Term::EqIntro(span) => Err(TypeError::AmbiguousEqIntro { span }),
+
+        // I-REWRITE
+        raw::Term::EqElim(_, ref raw_motive, ref raw_proof, ref raw_body) => {
+            let (proof, proof_ty) = infer(context, raw_proof)?;
+            let proof_ty = unify::force(context, &proof_ty)?;
+            let (ty, lhs, rhs) = match *proof_ty.inner {
+                Value::EqType(ref ty, ref lhs, ref rhs) => (ty.clone(), lhs.clone(), rhs.clone()),
+                _ => {
+                    return Err(TypeError::ExpectedEqType {
+                        span: raw_proof.span(),
+                        found: Box::new(context.resugar(&proof_ty)),
+                    });
+                },
+            };
+
+            // The motive is a family of types, indexed by the terms being
+            // compared in the equality
+            let motive_ty = RcValue::from(Value::FunType(
+                Plicity::Explicit,
+                Multiplicity::Many,
+                ty,
+                Closure::constant(RcValue::from(Value::universe(0))),
+            ));
+            let motive = check(context, raw_motive, &motive_ty)?;
+            let motive_value = context.eval(&motive)?;
+            let motive_app = |arg: &RcValue| nbe::fun_app(context, &motive_value, arg.clone());
+
+            let body = check(context, raw_body, &motive_app(&lhs)?)?;
+            let ty = motive_app(&rhs)?;
+
+            Ok((RcTerm::from(Term::EqElim(motive, proof, body)), ty))
+        },
+
         // I-CASE
         raw::Term::Case(span, ref raw_head, ref raw_clauses) => {
-            let (head, head_ty) = infer_term(context, raw_head)?;
+            let (head, head_ty) = infer(context, raw_head)?;
             let mut ty = None;
 
-            // TODO: ensure that patterns are exhaustive
-            let clauses = raw_clauses
+            let clauses: Vec<_> = raw_clauses
                 .iter()
                 .map(|raw_clause| {
                     let (raw_pattern, raw_body) = raw_clause.clone().unbind();
-                    let (pattern, declarations) = check_pattern(context, &raw_pattern, &head_ty)?;
+                    let mut declarations = Vec::new();
+                    let pattern =
+                        check_pattern(context, &raw_pattern, &head_ty, &mut declarations)?;
 
                     let (body, body_ty) = {
                         let mut body_context = context.clone();
                         for (free_var, ty) in declarations {
-                            body_context.insert_declaration(free_var, ty);
+                            body_context.insert_local(free_var, ty);
                         }
-                        infer_term(&body_context, &raw_body)?
+                        infer(&body_context, &raw_body)?
                     };
 
                     match ty {
                         None => ty = Some(body_ty),
                         // FIXME: use common subtype?
-                        Some(ref ty) if RcValue::term_eq(&body_ty, ty) => {},
+                        Some(ref ty) if unify::unify(context, &body_ty, ty)? => {},
                         Some(ref ty) => {
                             return Err(TypeError::Mismatch {
                                 span: raw_body.span(),
@@ -669,12 +2763,15 @@ pub fn infer_term(
                         },
                     }
 
-                    Ok(Scope::new(pattern, body))
+                    Ok((pattern, body))
                 })
                 .collect::<Result<_, TypeError>>()?;
 
             match ty {
-                Some(ty) => Ok((RcTerm::from(Term::Case(head, clauses)), ty)),
+                Some(ty) => {
+                    check_coverage(context, span, &head_ty, raw_clauses, &clauses)?;
+                    Ok((RcTerm::from(Term::Case(head, clauses)), ty))
+                },
                 None => Err(TypeError::AmbiguousEmptyCase { span }),
             }
         },
@@ -0,0 +1,721 @@
+//! Termination checking of recursive definitions
+//!
+//! We use a simple form of size-change termination. Each call between the
+//! definitions of a recursive binding group is described by a matrix that
+//! records how the size of each argument relates to the size of each of the
+//! parameters of the caller. Arguments are considered to be smaller than a
+//! parameter if they were bound by matching a variant pattern on it, or were
+//! projected from it. We then compose these calls until no new ones are
+//! found, and require that every call from a definition back to itself
+//! strictly decreases the size of one of its parameters.
+//!
+//! References found in the alternatives of variant types are not counted as
+//! calls. Alternatives are only evaluated when they are needed, so recursive
+//! variant types like `List a` never unfold forever.
+//!
+//! This happens after elaboration, so the checker works on the core syntax.
+//! Core terms don't have spans, so offending calls are reported by their
+//! position among the references to the called definition, and are then
+//! matched up with the corresponding variables in the raw syntax.
+//!
+//! Local variables are tracked using their de Bruijn levels, which stay the
+//! same as we descend into the bodies of binders.
+
+use codespan::ByteSpan;
+use im;
+use moniker::{Embed, FreeVar, Var};
+use std::cmp;
+
+use pikelet_core::syntax::core::{Pattern, RcPattern, RcTerm, Term};
+use pikelet_core::syntax::{VarIndex, VarLevel};
+
+use crate::syntax::raw;
+
+/// How the size of an argument relates to the size of a parameter
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Relation {
+    /// The argument is no larger than the parameter
+    Le,
+    /// The argument is strictly smaller than the parameter
+    Lt,
+}
+
+/// The parameters that the local variables are known to be related to
+type Sizes = im::HashMap<VarLevel, (usize, Relation)>;
+
+/// A call from one definition to another
+#[derive(Debug, Clone, PartialEq)]
+struct Call {
+    caller: usize,
+    callee: usize,
+    /// `matrix[j][i]` describes how the `j`th argument of the call relates to
+    /// the `i`th parameter of the caller
+    matrix: Vec<Vec<Option<Relation>>>,
+}
+
+impl Call {
+    /// Combine this call with a call that is made from its callee
+    fn compose(&self, next: &Call, arities: &[usize]) -> Call {
+        let matrix = next
+            .matrix
+            .iter()
+            .map(|next_row| {
+                (0..arities[self.caller])
+                    .map(|i| {
+                        Iterator::zip(next_row.iter(), self.matrix.iter())
+                            .filter_map(|(&next_rel, row)| match (next_rel, row[i]) {
+                                (Some(rel1), Some(rel2)) => Some(cmp::max(rel1, rel2)),
+                                (_, _) => None,
+                            })
+                            .max()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Call {
+            caller: self.caller,
+            callee: next.callee,
+            matrix,
+        }
+    }
+
+    /// Returns `true` if a call from a definition back to itself strictly
+    /// decreases the size of one of its parameters
+    fn decreases(&self) -> bool {
+        self.matrix
+            .iter()
+            .enumerate()
+            .any(|(i, row)| row.get(i) == Some(&Some(Relation::Lt)))
+    }
+}
+
+/// A recursive call that could not be shown to terminate
+#[derive(Debug, Clone, PartialEq)]
+pub struct NonTerminatingCall {
+    /// The index of the definition that makes the call
+    pub caller: usize,
+    /// The index of the definition that is called
+    pub callee: usize,
+    /// The position of the call among the references to the callee in the
+    /// definition of the caller
+    pub occurrence: usize,
+}
+
+/// Check that the calls between a group of definitions terminate
+///
+/// The definitions are found in a scope of `size` local variables, and are
+/// paired with the levels of the variables that refer to them.
+pub fn check_definitions(
+    size: usize,
+    definitions: &[(VarLevel, RcTerm)],
+) -> Result<(), NonTerminatingCall> {
+    let levels = definitions
+        .iter()
+        .map(|&(level, _)| level)
+        .collect::<Vec<_>>();
+    let arities = definitions
+        .iter()
+        .map(|&(_, ref term)| arity(term))
+        .collect::<Vec<_>>();
+
+    let mut calls = Vec::new();
+    for (caller, &(_, ref term)) in definitions.iter().enumerate() {
+        let mut collector = CallCollector {
+            levels: &levels,
+            arities: &arities,
+            caller,
+            occurrences: vec![0; definitions.len()],
+            calls: Vec::new(),
+        };
+        collector.collect_definition(size, term);
+        calls.extend(collector.calls);
+    }
+
+    // Compose the calls until no new ones can be found, remembering the call
+    // that each sequence of calls started with
+    let mut closure = calls.clone();
+    let mut index = 0;
+    while index < closure.len() {
+        let call = closure[index].0.clone();
+        for &(ref next, _) in &calls {
+            if next.caller != call.callee {
+                continue;
+            }
+
+            let composed = call.compose(next, &arities);
+            if closure
+                .iter()
+                .all(|&(ref existing, _)| *existing != composed)
+            {
+                let origin = closure[index].1.clone();
+                closure.push((composed, origin));
+            }
+        }
+        index += 1;
+    }
+
+    for (call, origin) in closure {
+        if call.caller == call.callee && call.compose(&call, &arities) == call && !call.decreases()
+        {
+            return Err(origin);
+        }
+    }
+
+    Ok(())
+}
+
+/// The number of parameters that a definition takes
+fn arity(term: &RcTerm) -> usize {
+    match *term.inner {
+        Term::Ann(ref expr, _) => arity(expr),
+        Term::FunIntro(_, _, _, _, ref body) => 1 + arity(body),
+        _ => 0,
+    }
+}
+
+/// Returns the parameter that a term is known to be related to
+fn relation(sizes: &Sizes, size: usize, term: &RcTerm) -> Option<(usize, Relation)> {
+    match *term.inner {
+        Term::Var(index, _) => sizes.get(&index.to_level(size)?).cloned(),
+        Term::Ann(ref expr, _) => relation(sizes, size, expr),
+        Term::RecordProj(ref expr, _, _, _) => {
+            relation(sizes, size, expr).map(|(param, _)| (param, Relation::Lt))
+        },
+        _ => None,
+    }
+}
+
+/// Collects the calls made from the definition of the `caller`
+struct CallCollector<'a> {
+    /// The levels of the variables that refer to the definitions
+    levels: &'a [VarLevel],
+    arities: &'a [usize],
+    caller: usize,
+    /// The number of references to each definition seen so far
+    occurrences: Vec<usize>,
+    calls: Vec<(Call, NonTerminatingCall)>,
+}
+
+impl<'a> CallCollector<'a> {
+    fn collect_definition(&mut self, size: usize, term: &RcTerm) {
+        let mut sizes = Sizes::new();
+        let mut size = size;
+        let mut term = term.clone();
+        let mut param = 0;
+
+        loop {
+            term = match *term.inner {
+                Term::Ann(ref expr, _) => expr.clone(),
+                Term::FunIntro(_, _, _, ref ann, ref body) => {
+                    self.collect_term(&sizes, size, ann);
+                    sizes.insert(VarLevel(size), (param, Relation::Le));
+                    param += 1;
+                    size += 1;
+                    body.clone()
+                },
+                _ => break,
+            };
+        }
+
+        self.collect_term(&sizes, size, &term);
+    }
+
+    fn collect_call(&mut self, sizes: &Sizes, size: usize, index: VarIndex, args: &[&RcTerm]) {
+        let level = index.to_level(size);
+        let callee = match self.levels.iter().position(|&other| Some(other) == level) {
+            Some(callee) => callee,
+            None => return,
+        };
+
+        let matrix = (0..self.arities[callee])
+            .map(|arg| {
+                let mut row = vec![None; self.arities[self.caller]];
+                let arg_relation = args.get(arg).and_then(|arg| relation(sizes, size, arg));
+                if let Some((param, rel)) = arg_relation {
+                    row[param] = Some(rel);
+                }
+                row
+            })
+            .collect();
+
+        let occurrence = self.occurrences[callee];
+        self.occurrences[callee] += 1;
+        self.calls.push((
+            Call {
+                caller: self.caller,
+                callee,
+                matrix,
+            },
+            NonTerminatingCall {
+                caller: self.caller,
+                callee,
+                occurrence,
+            },
+        ));
+    }
+
+    /// Record the relations of the variables bound by a pattern, which are
+    /// given levels starting from `level`
+    fn collect_pattern(
+        &mut self,
+        sizes: &mut Sizes,
+        size: usize,
+        level: &mut usize,
+        pattern: &RcPattern,
+        rel: Option<(usize, Relation)>,
+    ) {
+        match *pattern.inner {
+            Pattern::Ann(ref pattern, ref ty) => {
+                self.collect_pattern(sizes, size, level, pattern, rel);
+                self.collect_term(sizes, size, ty);
+            },
+            Pattern::Binder(_) => {
+                if let Some(rel) = rel {
+                    sizes.insert(VarLevel(*level), rel);
+                }
+                *level += 1;
+            },
+            Pattern::Var(_, _) | Pattern::Global(_, _) | Pattern::Literal(_) => {},
+            Pattern::VariantIntro(_, ref pattern) => {
+                let rel = rel.map(|(param, _)| (param, Relation::Lt));
+                self.collect_pattern(sizes, size, level, pattern, rel);
+            },
+            Pattern::RecordIntro(ref fields) => {
+                let rel = rel.map(|(param, _)| (param, Relation::Lt));
+                for &(_, ref pattern) in fields {
+                    self.collect_pattern(sizes, size, level, pattern, rel);
+                }
+            },
+            Pattern::ArrayIntro(ref patterns) => {
+                let rel = rel.map(|(param, _)| (param, Relation::Lt));
+                for pattern in patterns {
+                    self.collect_pattern(sizes, size, level, pattern, rel);
+                }
+            },
+        }
+    }
+
+    fn collect_term(&mut self, sizes: &Sizes, size: usize, term: &RcTerm) {
+        match *term.inner {
+            Term::Ann(ref expr, ref ty) => {
+                self.collect_term(sizes, size, expr);
+                self.collect_term(sizes, size, ty);
+            },
+            Term::Universe(_)
+            | Term::UniverseOmega
+            | Term::LevelType
+            | Term::LevelIntro(_)
+            | Term::Literal(_)
+            | Term::Global(_, _)
+            | Term::Import(_)
+            | Term::Meta(_)
+            | Term::EqIntro
+            | Term::Erased => {},
+            Term::UniverseAt(ref level) | Term::LevelShift(ref level, _) => {
+                self.collect_term(sizes, size, level)
+            },
+            Term::LevelMax(ref level1, ref level2) => {
+                self.collect_term(sizes, size, level1);
+                self.collect_term(sizes, size, level2);
+            },
+            Term::Var(index, _) => self.collect_call(sizes, size, index, &[]),
+            Term::FunType(_, _, _, ref ann, ref body)
+            | Term::FunIntro(_, _, _, ref ann, ref body)
+            | Term::Fix(_, _, ref ann, ref body) => {
+                self.collect_term(sizes, size, ann);
+                self.collect_term(sizes, size + 1, body);
+            },
+            Term::FunApp(..) => {
+                let mut head = term;
+                let mut args = Vec::new();
+                while let Term::FunApp(ref next_head, _, ref arg) = *head.inner {
+                    head = next_head;
+                    args.push(arg);
+                }
+                args.reverse();
+
+                match *head.inner {
+                    Term::Var(index, _) => self.collect_call(sizes, size, index, &args),
+                    _ => self.collect_term(sizes, size, head),
+                }
+                for arg in args {
+                    self.collect_term(sizes, size, arg);
+                }
+            },
+            Term::RecordType(ref fields) => {
+                for (i, &(_, _, _, ref ann)) in fields.iter().enumerate() {
+                    self.collect_term(sizes, size + i, ann);
+                }
+            },
+            Term::RecordIntro(ref fields) => {
+                for &(_, _, ref expr) in fields {
+                    self.collect_term(sizes, size, expr);
+                }
+            },
+            Term::RecordExtend(ref expr, ref fields) => {
+                self.collect_term(sizes, size, expr);
+                for &(_, ref expr) in fields {
+                    self.collect_term(sizes, size, expr);
+                }
+            },
+            Term::RecordProj(ref expr, _, _, _) | Term::VariantIntro(_, ref expr) => {
+                self.collect_term(sizes, size, expr)
+            },
+            // The alternatives of variant types are evaluated lazily, so the
+            // references to recursive variant types that are found in them
+            // are not calls
+            Term::VariantType(_) => {},
+            // The type of the equality is inferred from the terms, so it is
+            // not visited - it doesn't appear in the raw syntax
+            Term::EqType(_, ref lhs, ref rhs) => {
+                self.collect_term(sizes, size, lhs);
+                self.collect_term(sizes, size, rhs);
+            },
+            Term::EqElim(ref motive, ref proof, ref body) => {
+                self.collect_term(sizes, size, motive);
+                self.collect_term(sizes, size, proof);
+                self.collect_term(sizes, size, body);
+            },
+            Term::Case(ref head, ref clauses) => {
+                self.collect_term(sizes, size, head);
+                let rel = relation(sizes, size, head);
+                for &(ref pattern, ref body) in clauses {
+                    let mut sizes = sizes.clone();
+                    let mut level = size;
+                    self.collect_pattern(&mut sizes, size, &mut level, pattern, rel);
+                    self.collect_term(&sizes, level, body);
+                }
+            },
+            Term::ArrayIntro(ref elems) => {
+                for elem in elems {
+                    self.collect_term(sizes, size, elem);
+                }
+            },
+            Term::Let(ref bindings, ref body) => {
+                let mut sizes = sizes.clone();
+                for (i, &(_, ref term)) in bindings.iter().enumerate() {
+                    self.collect_term(&sizes, size + i, term);
+                    if let Some(rel) = relation(&sizes, size + i, term) {
+                        sizes.insert(VarLevel(size + i), rel);
+                    }
+                }
+                self.collect_term(&sizes, size + bindings.len(), body);
+            },
+        }
+    }
+}
+
+/// Returns the spans of the references to `free_var` in a raw term, in the
+/// same order that they are visited when collecting calls
+pub fn occurrence_spans(term: &raw::RcTerm, free_var: &FreeVar<String>) -> Vec<ByteSpan> {
+    fn go_pattern(pattern: &raw::RcPattern, free_var: &FreeVar<String>, spans: &mut Vec<ByteSpan>) {
+        match *pattern.inner {
+            raw::Pattern::Ann(ref pattern, Embed(ref ty)) => {
+                go_pattern(pattern, free_var, spans);
+                go(ty, free_var, spans);
+            },
+            raw::Pattern::VariantIntro(_, _, ref pattern) => go_pattern(pattern, free_var, spans),
+            raw::Pattern::RecordIntro(_, ref fields) => {
+                for &(_, ref pattern) in fields {
+                    go_pattern(pattern, free_var, spans);
+                }
+            },
+            raw::Pattern::ArrayIntro(_, ref patterns) => {
+                for pattern in patterns {
+                    go_pattern(pattern, free_var, spans);
+                }
+            },
+            raw::Pattern::Binder(..)
+            | raw::Pattern::Var(..)
+            | raw::Pattern::Literal(..)
+            | raw::Pattern::Wildcard(..) => {},
+        }
+    }
+
+    fn go(term: &raw::RcTerm, free_var: &FreeVar<String>, spans: &mut Vec<ByteSpan>) {
+        match *term.inner {
+            raw::Term::Var(span, Var::Free(ref other), _) if other == free_var => spans.push(span),
+            raw::Term::Universe(..)
+            | raw::Term::Literal(..)
+            | raw::Term::Hole(..)
+            | raw::Term::Var(..)
+            | raw::Term::Import(..)
+            | raw::Term::EqIntro(..) => {},
+            raw::Term::UniverseAt(_, ref level) => go(level, free_var, spans),
+            raw::Term::Ann(ref expr, ref ty) => {
+                go(expr, free_var, spans);
+                go(ty, free_var, spans);
+            },
+            raw::Term::FunType(_, _, _, ref scope) | raw::Term::FunIntro(_, _, _, ref scope) => {
+                go(&(scope.unsafe_pattern.1).0, free_var, spans);
+                go(&scope.unsafe_body, free_var, spans);
+            },
+            raw::Term::FunApp(ref head, ref arg) => {
+                go(head, free_var, spans);
+                go(arg, free_var, spans);
+            },
+            raw::Term::RecordType(_, ref scope) => {
+                for &(_, _, _, Embed(ref ann)) in &scope.unsafe_pattern.unsafe_patterns {
+                    go(ann, free_var, spans);
+                }
+            },
+            raw::Term::RecordIntro(_, ref fields) => {
+                for &(_, ref expr) in fields {
+                    go(expr, free_var, spans);
+                }
+            },
+            raw::Term::RecordExtend(_, ref expr, ref fields)
+            | raw::Term::RecordUpdate(_, ref expr, ref fields) => {
+                go(expr, free_var, spans);
+                for &(_, ref expr) in fields {
+                    go(expr, free_var, spans);
+                }
+            },
+            raw::Term::RecordTypeMerge(_, ref lhs, ref rhs) => {
+                go(lhs, free_var, spans);
+                go(rhs, free_var, spans);
+            },
+            raw::Term::RecordProj(_, ref expr, _, _, _)
+            | raw::Term::VariantIntro(_, _, ref expr) => go(expr, free_var, spans),
+            // Skipped when collecting calls, so we skip them here too
+            raw::Term::VariantType(..) => {},
+            raw::Term::EqType(_, ref lhs, ref rhs) => {
+                go(lhs, free_var, spans);
+                go(rhs, free_var, spans);
+            },
+            raw::Term::EqElim(_, ref motive, ref proof, ref body) => {
+                go(motive, free_var, spans);
+                go(proof, free_var, spans);
+                go(body, free_var, spans);
+            },
+            raw::Term::Case(_, ref head, ref clauses) => {
+                go(head, free_var, spans);
+                for clause in clauses {
+                    go_pattern(&clause.unsafe_pattern, free_var, spans);
+                    go(&clause.unsafe_body, free_var, spans);
+                }
+            },
+            raw::Term::ArrayIntro(_, ref elems) => {
+                for elem in elems {
+                    go(elem, free_var, spans);
+                }
+            },
+            raw::Term::Let(_, ref scope) => {
+                for &(_, _, _, Embed(ref term)) in &scope.unsafe_pattern.unsafe_pattern {
+                    go(term, free_var, spans);
+                }
+                go(&scope.unsafe_body, free_var, spans);
+            },
+        }
+    }
+
+    let mut spans = Vec::new();
+    go(term, free_var, &mut spans);
+    spans
+}
+
+#[cfg(test)]
+mod tests {
+    use pikelet_core::syntax::{Label, Multiplicity, NameHint, Plicity};
+
+    use super::*;
+
+    fn var(index: usize) -> RcTerm {
+        RcTerm::from(Term::var(index, 0))
+    }
+
+    fn fun_app(head: RcTerm, args: &[RcTerm]) -> RcTerm {
+        args.iter().fold(head, |head, arg| {
+            RcTerm::from(Term::FunApp(head, Multiplicity::Many, arg.clone()))
+        })
+    }
+
+    fn fun_intro(param: &str, body: RcTerm) -> RcTerm {
+        let ann = RcTerm::from(Term::universe(0));
+        RcTerm::from(Term::FunIntro(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            NameHint::from(param),
+            ann,
+            body,
+        ))
+    }
+
+    /// `case head { variant more binder => body; _ => default }`
+    fn case_more(head: RcTerm, binder: &str, body: RcTerm, default: RcTerm) -> RcTerm {
+        let more = RcPattern::from(Pattern::VariantIntro(
+            Label("more".to_owned()),
+            RcPattern::from(Pattern::Binder(NameHint::from(binder))),
+        ));
+        let wildcard = RcPattern::from(Pattern::Binder(NameHint::from("_")));
+
+        RcTerm::from(Term::Case(head, vec![(more, body), (wildcard, default)]))
+    }
+
+    /// `case head { variant cons pattern => body; _ => default }`
+    fn case_cons(head: RcTerm, pattern: RcPattern, body: RcTerm, default: RcTerm) -> RcTerm {
+        let cons = RcPattern::from(Pattern::VariantIntro(Label("cons".to_owned()), pattern));
+        let wildcard = RcPattern::from(Pattern::Binder(NameHint::from("_")));
+
+        RcTerm::from(Term::Case(head, vec![(cons, body), (wildcard, default)]))
+    }
+
+    fn binder(name: &str) -> RcPattern {
+        RcPattern::from(Pattern::Binder(NameHint::from(name)))
+    }
+
+    fn tail(expr: RcTerm) -> RcTerm {
+        let label = Label("tail".to_owned());
+        RcTerm::from(Term::RecordProj(expr, label, Multiplicity::Many, 0.into()))
+    }
+
+    #[test]
+    fn structural() {
+        // f x = case x { variant more y => f y; _ => x }
+        let body = case_more(var(0), "y", fun_app(var(2), &[var(0)]), var(1));
+        let definitions = vec![(VarLevel(0), fun_intro("x", body))];
+
+        assert_eq!(check_definitions(1, &definitions), Ok(()));
+    }
+
+    #[test]
+    fn record_proj() {
+        // f x = f x.next
+        let label = Label("next".to_owned());
+        let proj = RcTerm::from(Term::RecordProj(
+            var(0),
+            label,
+            Multiplicity::Many,
+            0.into(),
+        ));
+        let definitions = vec![(VarLevel(0), fun_intro("x", fun_app(var(1), &[proj])))];
+
+        assert_eq!(check_definitions(1, &definitions), Ok(()));
+    }
+
+    #[test]
+    fn variant_type() {
+        // f x = Variant { more : f x }
+        let alts = vec![(Label("more".to_owned()), fun_app(var(1), &[var(0)]))];
+        let body = RcTerm::from(Term::VariantType(alts));
+        let definitions = vec![(VarLevel(0), fun_intro("x", body))];
+
+        assert_eq!(check_definitions(1, &definitions), Ok(()));
+    }
+
+    #[test]
+    fn list_tail() {
+        // f a xs = case xs { variant cons node => f a node.tail; _ => a }
+        let call = fun_app(var(3), &[var(2), tail(var(0))]);
+        let body = case_cons(var(0), binder("node"), call, var(2));
+        let definitions = vec![(VarLevel(0), fun_intro("a", fun_intro("xs", body)))];
+
+        assert_eq!(check_definitions(1, &definitions), Ok(()));
+    }
+
+    #[test]
+    fn list_record_pattern() {
+        // f xs = case xs { variant cons (record { head; tail }) => f tail; _ => xs }
+        let fields = vec![
+            (Label("head".to_owned()), binder("head")),
+            (Label("tail".to_owned()), binder("tail")),
+        ];
+        let pattern = RcPattern::from(Pattern::RecordIntro(fields));
+        let body = case_cons(var(0), pattern, fun_app(var(3), &[var(0)]), var(1));
+        let definitions = vec![(VarLevel(0), fun_intro("xs", body))];
+
+        assert_eq!(check_definitions(1, &definitions), Ok(()));
+    }
+
+    #[test]
+    fn list_mutual() {
+        // f xs = case xs { variant cons node => g node.tail; _ => xs }
+        // g ys = case ys { variant cons node => f node.tail; _ => ys }
+        let g_call = fun_app(var(2), &[tail(var(0))]);
+        let f_call = fun_app(var(3), &[tail(var(0))]);
+        let f_body = case_cons(var(0), binder("node"), g_call, var(1));
+        let g_body = case_cons(var(0), binder("node"), f_call, var(1));
+        let definitions = vec![
+            (VarLevel(0), fun_intro("xs", f_body)),
+            (VarLevel(1), fun_intro("ys", g_body)),
+        ];
+
+        assert_eq!(check_definitions(2, &definitions), Ok(()));
+    }
+
+    #[test]
+    fn list_unchanged() {
+        // f xs = case xs { variant cons node => f xs; _ => xs }
+        let body = case_cons(var(0), binder("node"), fun_app(var(2), &[var(1)]), var(1));
+        let definitions = vec![(VarLevel(0), fun_intro("xs", body))];
+
+        assert_eq!(
+            check_definitions(1, &definitions),
+            Err(NonTerminatingCall {
+                caller: 0,
+                callee: 0,
+                occurrence: 0,
+            }),
+        );
+    }
+
+    #[test]
+    fn non_decreasing() {
+        // f x = f x
+        let definitions = vec![(VarLevel(0), fun_intro("x", fun_app(var(1), &[var(0)])))];
+
+        assert_eq!(
+            check_definitions(1, &definitions),
+            Err(NonTerminatingCall {
+                caller: 0,
+                callee: 0,
+                occurrence: 0,
+            }),
+        );
+    }
+
+    #[test]
+    fn permuted_params() {
+        // f x y = case x { variant more z => f y z; _ => x }
+        let body = case_more(var(1), "z", fun_app(var(3), &[var(1), var(0)]), var(2));
+        let definitions = vec![(VarLevel(0), fun_intro("x", fun_intro("y", body)))];
+
+        assert_eq!(check_definitions(1, &definitions), Ok(()));
+    }
+
+    #[test]
+    fn mutual() {
+        // f x = case x { variant more y => g y; _ => x }
+        // g z = f z
+        let f_body = case_more(var(0), "y", fun_app(var(2), &[var(0)]), var(1));
+        let g_body = fun_app(var(2), &[var(0)]);
+        let definitions = vec![
+            (VarLevel(0), fun_intro("x", f_body)),
+            (VarLevel(1), fun_intro("z", g_body)),
+        ];
+
+        assert_eq!(check_definitions(2, &definitions), Ok(()));
+    }
+
+    #[test]
+    fn mutual_non_decreasing() {
+        // f x = g x
+        // g z = f z
+        let f_body = fun_app(var(1), &[var(0)]);
+        let g_body = fun_app(var(2), &[var(0)]);
+        let definitions = vec![
+            (VarLevel(0), fun_intro("x", f_body)),
+            (VarLevel(1), fun_intro("z", g_body)),
+        ];
+
+        assert_eq!(
+            check_definitions(2, &definitions),
+            Err(NonTerminatingCall {
+                caller: 0,
+                callee: 1,
+                occurrence: 0,
+            }),
+        );
+    }
+}
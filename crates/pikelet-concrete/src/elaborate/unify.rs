@@ -0,0 +1,471 @@
+//! Unification of values, solving metavariables along the way
+//!
+//! We use a simple form of pattern unification, where a metavariable can only
+//! be solved if it has been applied to a spine of distinct local variables.
+//! This is enough to fill in most holes and missing annotations, but we don't
+//! yet postpone problems that fall outside of this fragment, so these will
+//! simply fail to unify.
+
+use pikelet_core::nbe;
+use pikelet_core::syntax::core::{RcTerm, Term};
+use pikelet_core::syntax::domain::{Head, LevelValue, Neutral, RcNeutral, RcValue, Value};
+use pikelet_core::syntax::{MetaVar, Multiplicity, NameHint, Plicity, VarIndex, VarLevel};
+
+use super::{Context, TypeError};
+
+/// Returns the metavariable at the head of a neutral value, if it has one
+fn neutral_meta(neutral: &Neutral) -> Option<MetaVar> {
+    match *neutral {
+        Neutral::Head(Head::Meta(meta)) => Some(meta),
+        Neutral::Head(Head::Var(_, _))
+        | Neutral::Head(Head::Global(_, _))
+        | Neutral::Head(Head::Import(_))
+        | Neutral::Head(Head::Fix(_, _, _)) => None,
+        Neutral::RecordProj(ref expr, _, _)
+        | Neutral::RecordExtend(ref expr, _)
+        | Neutral::EqElim(_, ref expr, _)
+        | Neutral::Case(ref expr, _) => neutral_meta(expr),
+    }
+}
+
+/// Returns `true` if the metavariable occurs somewhere in the term
+fn contains_meta(term: &RcTerm, meta: MetaVar) -> bool {
+    match *term.inner {
+        Term::Meta(other_meta) => meta == other_meta,
+        Term::Ann(ref expr, ref ty) => contains_meta(expr, meta) || contains_meta(ty, meta),
+        Term::Universe(_)
+        | Term::UniverseOmega
+        | Term::LevelType
+        | Term::LevelIntro(_)
+        | Term::Literal(_)
+        | Term::Var(_, _)
+        | Term::Global(_, _)
+        | Term::Import(_)
+        | Term::EqIntro
+        | Term::Erased => false,
+        Term::FunType(_, _, _, ref ann, ref body)
+        | Term::FunIntro(_, _, _, ref ann, ref body)
+        | Term::Fix(_, _, ref ann, ref body) => {
+            contains_meta(ann, meta) || contains_meta(body, meta)
+        },
+        Term::UniverseAt(ref level) | Term::LevelShift(ref level, _) => contains_meta(level, meta),
+        Term::FunApp(ref head, _, ref arg) | Term::LevelMax(ref head, ref arg) => {
+            contains_meta(head, meta) || contains_meta(arg, meta)
+        },
+        Term::RecordType(ref fields) => fields
+            .iter()
+            .any(|&(_, _, _, ref ann)| contains_meta(ann, meta)),
+        Term::RecordIntro(ref fields) => fields
+            .iter()
+            .any(|&(_, _, ref expr)| contains_meta(expr, meta)),
+        Term::RecordExtend(ref expr, ref fields) => {
+            contains_meta(expr, meta)
+                || fields
+                    .iter()
+                    .any(|&(_, ref expr)| contains_meta(expr, meta))
+        },
+        Term::RecordProj(ref expr, _, _, _) => contains_meta(expr, meta),
+        Term::VariantType(ref alts) => alts.iter().any(|&(_, ref ann)| contains_meta(ann, meta)),
+        Term::VariantIntro(_, ref arg) => contains_meta(arg, meta),
+        Term::EqType(ref term1, ref term2, ref term3)
+        | Term::EqElim(ref term1, ref term2, ref term3) => {
+            contains_meta(term1, meta) || contains_meta(term2, meta) || contains_meta(term3, meta)
+        },
+        Term::Case(ref head, ref clauses) => {
+            contains_meta(head, meta)
+                || clauses
+                    .iter()
+                    .any(|&(_, ref body)| contains_meta(body, meta))
+        },
+        Term::ArrayIntro(ref elems) => elems.iter().any(|elem| contains_meta(elem, meta)),
+        Term::Let(ref bindings, ref body) => {
+            bindings
+                .iter()
+                .any(|&(_, ref term)| contains_meta(term, meta))
+                || contains_meta(body, meta)
+        },
+    }
+}
+
+/// Replace a value headed by a solved metavariable with the result of
+/// applying the solution
+pub fn force(context: &Context, value: &RcValue) -> Result<RcValue, TypeError> {
+    if let Value::Neutral(ref neutral, _) = *value.inner {
+        if let Some(meta) = neutral_meta(neutral) {
+            if context.get_meta_solution(meta).is_some() {
+                // Evaluating again will fill in any solutions we've found
+                return Ok(context.eval(&context.quote(value)?)?);
+            }
+        }
+    }
+
+    Ok(value.clone())
+}
+
+/// Fill in the solutions to the metavariables that a universe level depends on
+pub fn force_level(context: &Context, level: &LevelValue) -> Result<LevelValue, TypeError> {
+    let is_solved = |&(meta, _): &(MetaVar, _)| context.get_meta_solution(meta).is_some();
+    if !level.metas.iter().any(is_solved) {
+        return Ok(level.clone());
+    }
+
+    let mut forced = LevelValue {
+        constant: level.constant,
+        vars: level.vars.clone(),
+        metas: Vec::new(),
+    };
+    for &(meta, shift) in &level.metas {
+        let solution = match context.get_meta_solution(meta) {
+            Some(solution) => LevelValue::from_value(&context.eval(&solution)?),
+            None => None,
+        };
+        let mut meta_level = match solution {
+            Some(solution) => force_level(context, &solution)?,
+            None => LevelValue::meta(meta, 0),
+        };
+        meta_level.shift_universes(shift);
+        forced = forced.max(&meta_level);
+    }
+
+    Ok(forced)
+}
+
+/// Read a universe level from a value, filling in the solutions to any
+/// metavariables that it depends on
+pub fn level_value(context: &Context, value: &RcValue) -> Result<Option<LevelValue>, TypeError> {
+    match LevelValue::from_value(&force(context, value)?) {
+        Some(level) => Ok(Some(force_level(context, &level)?)),
+        None => Ok(None),
+    }
+}
+
+/// Returns `true` if the two values are equal, solving any metavariables
+/// needed to make them so
+pub fn unify(context: &Context, value1: &RcValue, value2: &RcValue) -> Result<bool, TypeError> {
+    let value1 = force(context, value1)?;
+    let value2 = force(context, value2)?;
+
+    match (value1.meta_app(), value2.meta_app()) {
+        (Some((meta1, spine1)), Some((meta2, spine2))) if meta1 == meta2 => {
+            return unify_spines(context, spine1, spine2);
+        },
+        (Some((meta, spine)), _) => return solve(context, meta, spine, &value2),
+        (_, Some((meta, spine))) => return solve(context, meta, spine, &value1),
+        (None, None) => {},
+    }
+
+    match (&*value1.inner, &*value2.inner) {
+        (&Value::Universe(level1), &Value::Universe(level2)) => Ok(level1 == level2),
+        (&Value::UniverseAt(ref level1), &Value::UniverseAt(ref level2)) => {
+            unify_levels(context, level1, level2)
+        },
+        (&Value::UniverseAt(ref level1), &Value::Universe(level2)) => {
+            let level2 = RcValue::from(Value::Level(LevelValue::constant(level2)));
+            unify_levels(context, level1, &level2)
+        },
+        (&Value::Universe(level1), &Value::UniverseAt(ref level2)) => {
+            let level1 = RcValue::from(Value::Level(LevelValue::constant(level1)));
+            unify_levels(context, &level1, level2)
+        },
+        (&Value::UniverseOmega, &Value::UniverseOmega) => Ok(true),
+        (&Value::LevelType, &Value::LevelType) => Ok(true),
+        (&Value::Level(_), &Value::Level(_)) => unify_levels(context, &value1, &value2),
+        (&Value::Literal(ref literal1), &Value::Literal(ref literal2)) => Ok(literal1 == literal2),
+
+        (
+            &Value::FunType(plicity1, multiplicity1, ref ann1, ref body1),
+            &Value::FunType(plicity2, multiplicity2, ref ann2, ref body2),
+        )
+        | (
+            &Value::FunIntro(plicity1, multiplicity1, ref ann1, ref body1),
+            &Value::FunIntro(plicity2, multiplicity2, ref ann2, ref body2),
+        ) if plicity1 == plicity2 && multiplicity1 == multiplicity2 => {
+            Ok(unify(context, ann1, ann2)? && {
+                // Apply both bodies to the same fresh variable
+                let var = context.fresh_var();
+                let body1_value = nbe::apply_closure(context, body1, var.clone())?;
+                let body2_value = nbe::apply_closure(context, body2, var)?;
+
+                let mut context = context.clone();
+                context.insert_fresh_local(body1.name.clone(), ann1.clone());
+                unify(&context, &body1_value, &body2_value)?
+            })
+        },
+
+        (&Value::RecordType(ref telescope1), &Value::RecordType(ref telescope2)) => {
+            if telescope1.fields.len() != telescope2.fields.len() {
+                return Ok(false);
+            }
+
+            let mut context = context.clone();
+            let mut locals1 = telescope1.locals.clone();
+            let mut locals2 = telescope2.locals.clone();
+            for (field1, field2) in
+                Iterator::zip(telescope1.fields.iter(), telescope2.fields.iter())
+            {
+                let (ref label1, ref name1, multiplicity1, ref ann1) = *field1;
+                let (ref label2, _, multiplicity2, ref ann2) = *field2;
+                let ann1 = nbe::eval(&context, &locals1, ann1)?;
+                let ann2 = nbe::eval(&context, &locals2, ann2)?;
+
+                if label1 == label2
+                    && multiplicity1 == multiplicity2
+                    && unify(&context, &ann1, &ann2)?
+                {
+                    let var = context.fresh_var();
+                    locals1.push_back(var.clone());
+                    locals2.push_back(var);
+                    context.insert_fresh_local(name1.clone(), ann1);
+                } else {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        },
+
+        (&Value::RecordIntro(ref fields1), &Value::RecordIntro(ref fields2)) => {
+            if fields1.len() != fields2.len() {
+                return Ok(false);
+            }
+
+            for (field1, field2) in Iterator::zip(fields1.iter(), fields2.iter()) {
+                let (ref label1, ref expr1) = *field1;
+                let (ref label2, ref expr2) = *field2;
+
+                if label1 != label2 || !unify(context, expr1, expr2)? {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        },
+
+        (&Value::VariantType(ref alts1), &Value::VariantType(ref alts2)) => {
+            if alts1.alts.len() != alts2.alts.len() {
+                return Ok(false);
+            }
+            // Recursive variant types contain themselves, so we might already
+            // be unifying them further up
+            if context.is_assumed_variants(&value1, &value2) {
+                return Ok(true);
+            }
+
+            let mut context = context.clone();
+            context.assume_variants(value1.clone(), value2.clone());
+            for (alt1, alt2) in Iterator::zip(alts1.alts.iter(), alts2.alts.iter()) {
+                let (ref label1, ref ann1) = *alt1;
+                let (ref label2, ref ann2) = *alt2;
+                let ann1 = nbe::eval(&context, &alts1.locals, ann1)?;
+                let ann2 = nbe::eval(&context, &alts2.locals, ann2)?;
+
+                if label1 != label2 || !unify(&context, &ann1, &ann2)? {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        },
+
+        (
+            &Value::VariantIntro(ref label1, ref arg1),
+            &Value::VariantIntro(ref label2, ref arg2),
+        ) => Ok(label1 == label2 && unify(context, arg1, arg2)?),
+
+        (
+            &Value::EqType(ref ty1, ref lhs1, ref rhs1),
+            &Value::EqType(ref ty2, ref lhs2, ref rhs2),
+        ) => Ok(unify(context, ty1, ty2)?
+            && unify(context, lhs1, lhs2)?
+            && unify(context, rhs1, rhs2)?),
+        (&Value::EqIntro, &Value::EqIntro) => Ok(true),
+        (&Value::Erased, &Value::Erased) => Ok(true),
+
+        (&Value::ArrayIntro(ref elems1), &Value::ArrayIntro(ref elems2)) => {
+            unify_spines(context, elems1, elems2)
+        },
+
+        (&Value::Neutral(ref neutral1, ref spine1), &Value::Neutral(ref neutral2, ref spine2)) => {
+            Ok(unify_neutrals(context, neutral1, neutral2)?
+                && unify_spines(context, spine1, spine2)?)
+        },
+
+        (_, _) => Ok(false),
+    }
+}
+
+/// Returns `true` if the two universe levels are equal
+///
+/// Lone metavariables are solved directly, but levels that depend on
+/// metavariables in other ways might still be made equal by the level solver,
+/// so we record the constraints needed for this instead.
+fn unify_levels(context: &Context, level1: &RcValue, level2: &RcValue) -> Result<bool, TypeError> {
+    let level1 = force(context, level1)?;
+    let level2 = force(context, level2)?;
+
+    if level1.meta_app().is_some() || level2.meta_app().is_some() {
+        return unify(context, &level1, &level2);
+    }
+
+    match (
+        level_value(context, &level1)?,
+        level_value(context, &level2)?,
+    ) {
+        (Some(level1), Some(level2)) => {
+            if level1 == level2 {
+                Ok(true)
+            } else if level1.has_metas() || level2.has_metas() {
+                context.add_level_constraint(level1.clone(), level2.clone());
+                context.add_level_constraint(level2, level1);
+                Ok(true)
+            } else {
+                Ok(false)
+            }
+        },
+        (_, _) => unify(context, &level1, &level2),
+    }
+}
+
+fn unify_spines(
+    context: &Context,
+    spine1: &[RcValue],
+    spine2: &[RcValue],
+) -> Result<bool, TypeError> {
+    if spine1.len() != spine2.len() {
+        return Ok(false);
+    }
+
+    for (arg1, arg2) in Iterator::zip(spine1.iter(), spine2.iter()) {
+        if !unify(context, arg1, arg2)? {
+            return Ok(false);
+        }
+    }
+
+    Ok(true)
+}
+
+fn unify_neutrals(
+    context: &Context,
+    neutral1: &RcNeutral,
+    neutral2: &RcNeutral,
+) -> Result<bool, TypeError> {
+    match (&*neutral1.inner, &*neutral2.inner) {
+        // Fixed points are compared up to alpha-equivalence
+        (&Neutral::Head(_), &Neutral::Head(_)) => {
+            let term1 = nbe::quote_neutral(context, context.size(), neutral1)?;
+            let term2 = nbe::quote_neutral(context, context.size(), neutral2)?;
+            Ok(term1 == term2)
+        },
+        (
+            &Neutral::RecordProj(ref expr1, ref label1, shift1),
+            &Neutral::RecordProj(ref expr2, ref label2, shift2),
+        ) => Ok(label1 == label2 && shift1 == shift2 && unify_neutrals(context, expr1, expr2)?),
+        (
+            &Neutral::RecordExtend(ref expr1, ref fields1),
+            &Neutral::RecordExtend(ref expr2, ref fields2),
+        ) => {
+            if fields1.len() != fields2.len() || !unify_neutrals(context, expr1, expr2)? {
+                return Ok(false);
+            }
+
+            for (&(ref label1, ref expr1), &(ref label2, ref expr2)) in
+                Iterator::zip(fields1.iter(), fields2.iter())
+            {
+                if label1 != label2 || !unify(context, expr1, expr2)? {
+                    return Ok(false);
+                }
+            }
+
+            Ok(true)
+        },
+        (
+            &Neutral::EqElim(ref motive1, ref proof1, ref body1),
+            &Neutral::EqElim(ref motive2, ref proof2, ref body2),
+        ) => Ok(unify(context, motive1, motive2)?
+            && unify_neutrals(context, proof1, proof2)?
+            && unify(context, body1, body2)?),
+        // TODO: Unify the heads and clauses of case expressions
+        (&Neutral::Case(_, _), &Neutral::Case(_, _)) => {
+            let term1 = nbe::quote_neutral(context, context.size(), neutral1)?;
+            let term2 = nbe::quote_neutral(context, context.size(), neutral2)?;
+            Ok(term1 == term2)
+        },
+        (_, _) => Ok(false),
+    }
+}
+
+/// Rename the local variables of a term, found in a scope of `size` local
+/// variables, to refer to the parameters of a solution
+///
+/// Returns `None` if the term refers to a local variable that is not one of
+/// the parameters.
+fn rename_params(params: &[VarLevel], size: usize, term: &RcTerm) -> Option<RcTerm> {
+    term.try_subst_vars(&mut |index, shift| {
+        let level = index.to_level(size)?;
+        let param = params.iter().position(|&other| other == level)?;
+        let index = VarIndex(params.len() - param - 1);
+
+        Some(RcTerm::from(Term::Var(index, shift)))
+    })
+}
+
+/// Attempt to solve the metavariable `meta`, applied to `spine`, with `value`
+fn solve(
+    context: &Context,
+    meta: MetaVar,
+    spine: &[RcValue],
+    value: &RcValue,
+) -> Result<bool, TypeError> {
+    // The spine must be made up of distinct variables, which will become the
+    // parameters of the solution
+    let mut params = Vec::with_capacity(spine.len());
+    for arg in spine {
+        match arg.var_level() {
+            Some(level) if !params.contains(&level) => params.push(level),
+            Some(_) | None => return Ok(false),
+        }
+    }
+
+    // Fill in any other solutions we might have found in the mean time
+    let term = nbe::nf(context, context.local_values(), &context.quote(value)?)?;
+
+    // Occurs check, to avoid creating cyclic solutions
+    if contains_meta(&term, meta) {
+        return Ok(false);
+    }
+
+    // Scope check, to ensure that the solution only refers to the local
+    // variables that were passed to the metavariable
+    let size = context.size();
+    let mut solution = match rename_params(&params, size, &term) {
+        Some(solution) => solution,
+        None => return Ok(false),
+    };
+
+    for (param, &level) in params.iter().enumerate().rev() {
+        let ann = match level
+            .to_index(size)
+            .and_then(|index| context.get_local_ty(index))
+        {
+            Some(ann) => context.quote(ann)?,
+            None => return Ok(false),
+        };
+        let ann = match rename_params(&params[..param], size, &ann) {
+            Some(ann) => ann,
+            None => return Ok(false),
+        };
+
+        // The parameters match up with the arguments from `apply_locals`
+        solution = RcTerm::from(Term::FunIntro(
+            Plicity::Explicit,
+            Multiplicity::Zero,
+            NameHint::default(),
+            ann,
+            solution,
+        ));
+    }
+
+    context.insert_meta_solution(meta, solution);
+
+    Ok(true)
+}
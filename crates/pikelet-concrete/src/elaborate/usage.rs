@@ -0,0 +1,250 @@
+//! Usage checking of function parameters and record fields
+//!
+//! Parameters can be annotated with a multiplicity that restricts how many
+//! times they may be used at runtime. Once the body of a lambda has been
+//! elaborated, we count the uses of its parameter and check that they agree
+//! with the multiplicity it was given.
+//!
+//! Uses in types don't count, as types are erased before runtime. This
+//! includes the annotations on terms and binders, and the arguments passed to
+//! parameters that have a multiplicity of zero. Arguments passed to
+//! metavariables are also given a multiplicity of zero, so uses that only
+//! appear in solutions to metavariables will not be noticed.
+//!
+//! Record fields can be given multiplicities too, which scale the uses found
+//! in their values in the same way as the multiplicities of parameters scale
+//! the uses found in arguments. The values of fields with a multiplicity of
+//! zero are erased, so once a term has been elaborated we also check that
+//! they are never projected from records at runtime.
+
+use codespan::ByteSpan;
+use moniker::Binder;
+
+use pikelet_core::syntax::core::{Pattern, RcPattern, RcTerm, Term};
+use pikelet_core::syntax::{Label, Multiplicity, NameHint, VarIndex};
+
+use crate::elaborate::TypeError;
+
+/// Check that the parameter bound by a lambda is used in a way that is
+/// compatible with its multiplicity
+pub fn check_param(
+    span: ByteSpan,
+    binder: &Binder<String>,
+    expected: Multiplicity,
+    body: &RcTerm,
+) -> Result<(), TypeError> {
+    let found = count(body, VarIndex(0));
+
+    if found.is_within(expected) {
+        Ok(())
+    } else {
+        Err(TypeError::UsageMismatch {
+            span,
+            binder: binder.clone(),
+            found,
+            expected,
+        })
+    }
+}
+
+/// Check that none of the fields with a multiplicity of zero are projected
+/// from records at runtime
+pub fn check_erased_fields(span: ByteSpan, term: &RcTerm) -> Result<(), TypeError> {
+    match erased_field(term) {
+        None => Ok(()),
+        Some(label) => Err(TypeError::ErasedFieldUsed {
+            span,
+            label: label.clone(),
+        }),
+    }
+}
+
+/// Count the number of times that the variable with the given index, as seen
+/// from outside of the term, is used at runtime
+fn count(term: &RcTerm, index: VarIndex) -> Multiplicity {
+    match *term.inner {
+        Term::Var(other, _) if other == index => Multiplicity::One,
+        Term::Var(_, _)
+        | Term::Universe(_)
+        | Term::UniverseOmega
+        | Term::LevelType
+        | Term::LevelIntro(_)
+        | Term::Literal(_)
+        | Term::Global(_, _)
+        | Term::Import(_)
+        | Term::Meta(_)
+        | Term::EqIntro
+        | Term::Erased => Multiplicity::Zero,
+
+        Term::Ann(ref expr, _) => count(expr, index),
+        Term::UniverseAt(ref level) | Term::LevelShift(ref level, _) => count(level, index),
+        Term::LevelMax(ref level1, ref level2) => count(level1, index) + count(level2, index),
+
+        Term::FunType(_, _, _, ref ann, ref body) => {
+            count(ann, index) + count(body, VarIndex(index.0 + 1))
+        },
+        Term::FunIntro(_, _, _, _, ref body) => count(body, VarIndex(index.0 + 1)),
+        Term::FunApp(ref head, multiplicity, ref arg) => {
+            count(head, index) + multiplicity * count(arg, index)
+        },
+
+        Term::RecordType(ref fields) => fields
+            .iter()
+            .enumerate()
+            .map(|(i, &(_, _, _, ref ann))| count(ann, VarIndex(index.0 + i)))
+            .fold(Multiplicity::Zero, |acc, found| acc + found),
+        Term::RecordIntro(ref fields) => fields
+            .iter()
+            .fold(Multiplicity::Zero, |acc, &(_, multiplicity, ref term)| {
+                acc + multiplicity * count(term, index)
+            }),
+        Term::RecordExtend(ref expr, ref fields) => {
+            count(expr, index) + count_fields(fields, index)
+        },
+        Term::RecordProj(ref expr, _, _, _) => count(expr, index),
+
+        Term::VariantType(ref alts) => count_fields(alts, index),
+        Term::VariantIntro(_, ref arg) => count(arg, index),
+
+        Term::EqType(ref ty, ref lhs, ref rhs) => {
+            count(ty, index) + count(lhs, index) + count(rhs, index)
+        },
+        // The proof is only needed to rewrite the type of the body
+        Term::EqElim(_, _, ref body) => count(body, index),
+
+        // Only one of the clauses will be taken at runtime
+        Term::Case(ref head, ref clauses) => {
+            let clauses = clauses
+                .iter()
+                .map(|&(ref pattern, ref body)| {
+                    count_pattern(pattern, index)
+                        + count(body, VarIndex(index.0 + pattern.binders()))
+                })
+                .fold(None, |acc: Option<Multiplicity>, found| {
+                    Some(acc.map_or(found, |acc| acc.join(found)))
+                });
+
+            count(head, index) + clauses.unwrap_or(Multiplicity::Zero)
+        },
+        Term::ArrayIntro(ref elems) => elems
+            .iter()
+            .fold(Multiplicity::Zero, |acc, elem| acc + count(elem, index)),
+
+        Term::Let(ref bindings, ref body) => count_let(bindings, body, index),
+        // The body of a fixed point may be unfolded any number of times
+        Term::Fix(_, _, _, ref body) => Multiplicity::Many * count(body, VarIndex(index.0 + 1)),
+    }
+}
+
+fn count_fields<T>(fields: &[(T, RcTerm)], index: VarIndex) -> Multiplicity {
+    fields
+        .iter()
+        .fold(Multiplicity::Zero, |acc, &(_, ref term)| {
+            acc + count(term, index)
+        })
+}
+
+/// Count the uses of a variable in a group of let bindings, where each
+/// binding is used as many times as the bindings that follow it use it
+fn count_let(bindings: &[(NameHint, RcTerm)], body: &RcTerm, index: VarIndex) -> Multiplicity {
+    match bindings.split_first() {
+        None => count(body, index),
+        Some((&(_, ref term), rest)) => {
+            count(term, index) * count_let(rest, body, VarIndex(0))
+                + count_let(rest, body, VarIndex(index.0 + 1))
+        },
+    }
+}
+
+/// Find a field with a multiplicity of zero that is projected from a record
+/// at runtime
+///
+/// This looks at the same parts of the term as `count`, except for types,
+/// which have no runtime representation.
+fn erased_field(term: &RcTerm) -> Option<&Label> {
+    match *term.inner {
+        Term::RecordProj(_, ref label, Multiplicity::Zero, _) => Some(label),
+        Term::RecordProj(ref expr, _, _, _) => erased_field(expr),
+
+        Term::Var(_, _)
+        | Term::Universe(_)
+        | Term::UniverseAt(_)
+        | Term::UniverseOmega
+        | Term::LevelType
+        | Term::LevelIntro(_)
+        | Term::LevelMax(_, _)
+        | Term::LevelShift(_, _)
+        | Term::Literal(_)
+        | Term::Global(_, _)
+        | Term::Import(_)
+        | Term::Meta(_)
+        | Term::FunType(_, _, _, _, _)
+        | Term::RecordType(_)
+        | Term::VariantType(_)
+        | Term::EqType(_, _, _)
+        | Term::EqIntro
+        | Term::Erased => None,
+
+        Term::Ann(ref expr, _) => erased_field(expr),
+        Term::FunIntro(_, _, _, _, ref body) | Term::Fix(_, _, _, ref body) => erased_field(body),
+        Term::FunApp(ref head, Multiplicity::Zero, _) => erased_field(head),
+        Term::FunApp(ref head, _, ref arg) => erased_field(head).or_else(|| erased_field(arg)),
+
+        Term::RecordIntro(ref fields) => fields
+            .iter()
+            .filter(|&&(_, multiplicity, _)| multiplicity != Multiplicity::Zero)
+            .filter_map(|&(_, _, ref term)| erased_field(term))
+            .next(),
+        Term::RecordExtend(ref expr, ref fields) => erased_field(expr).or_else(|| {
+            fields
+                .iter()
+                .filter_map(|&(_, ref term)| erased_field(term))
+                .next()
+        }),
+        Term::VariantIntro(_, ref arg) => erased_field(arg),
+        Term::EqElim(_, _, ref body) => erased_field(body),
+
+        Term::Case(ref head, ref clauses) => erased_field(head).or_else(|| {
+            clauses
+                .iter()
+                .filter_map(|&(_, ref body)| erased_field(body))
+                .next()
+        }),
+        Term::ArrayIntro(ref elems) => elems.iter().filter_map(erased_field).next(),
+
+        // Bindings that are never used at runtime can be ignored, like in
+        // `count_let`
+        Term::Let(ref bindings, ref body) => bindings
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| {
+                count_let(&bindings[i + 1..], body, VarIndex(0)) != Multiplicity::Zero
+            })
+            .filter_map(|(_, &(_, ref term))| erased_field(term))
+            .next()
+            .or_else(|| erased_field(body)),
+    }
+}
+
+/// Comparing against a variable in a pattern counts as a use
+fn count_pattern(pattern: &RcPattern, index: VarIndex) -> Multiplicity {
+    match *pattern.inner {
+        Pattern::Var(other, _) if other == index => Multiplicity::One,
+        Pattern::Var(_, _) | Pattern::Binder(_) | Pattern::Global(_, _) | Pattern::Literal(_) => {
+            Multiplicity::Zero
+        },
+        Pattern::Ann(ref pattern, _) | Pattern::VariantIntro(_, ref pattern) => {
+            count_pattern(pattern, index)
+        },
+        Pattern::RecordIntro(ref fields) => fields
+            .iter()
+            .fold(Multiplicity::Zero, |acc, &(_, ref pattern)| {
+                acc + count_pattern(pattern, index)
+            }),
+        Pattern::ArrayIntro(ref patterns) => {
+            patterns.iter().fold(Multiplicity::Zero, |acc, pattern| {
+                acc + count_pattern(pattern, index)
+            })
+        },
+    }
+}
@@ -12,6 +12,8 @@ pub enum ParseError {
     Lexer(#[cause] LexerError),
     #[fail(display = "An identifier was expected when parsing a pi type.")]
     IdentifierExpectedInPiType { span: ByteSpan },
+    #[fail(display = "Invalid multiplicity `{}`, expected `0` or `1`.", found)]
+    InvalidMultiplicity { span: ByteSpan, found: u64 },
     #[fail(display = "Unknown repl command `:{}` found.", command)]
     UnknownReplCommand { span: ByteSpan, command: String },
     #[fail(display = "Unexpected EOF, expected one of: {}.", expected)]
@@ -73,6 +75,7 @@ impl ParseError {
         match *self {
             ParseError::Lexer(ref err) => err.span(),
             ParseError::IdentifierExpectedInPiType { span }
+            | ParseError::InvalidMultiplicity { span, .. }
             | ParseError::UnknownReplCommand { span, .. }
             | ParseError::UnexpectedToken { span, .. }
             | ParseError::ExtraToken { span, .. } => span,
@@ -90,6 +93,10 @@ impl ParseError {
                         Label::new_primary(span).with_message("ill-formed dependent function type"),
                     )
             },
+            ParseError::InvalidMultiplicity { span, found } => {
+                Diagnostic::new_error(format!("invalid multiplicity `{}`", found))
+                    .with_label(Label::new_primary(span).with_message("expected `0` or `1` here"))
+            },
             ParseError::UnknownReplCommand { span, ref command } => {
                 Diagnostic::new_error(format!("unknown repl command `:{}`", command))
                     .with_label(Label::new_primary(span).with_message("unexpected command"))
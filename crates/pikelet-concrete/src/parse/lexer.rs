@@ -147,31 +147,40 @@ pub enum Token<S> {
     DecFloatLiteral(f64),
 
     // Keywords
-    As,         // as
-    Case,       // case
-    Else,       // else
-    If,         // if
-    Import,     // import
-    In,         // in
-    Let,        // let
-    Record,     // record
-    RecordType, // Record
-    Then,       // then
-    Type,       // Type
-    Where,      // where
+    As,          // as
+    Case,        // case
+    Else,        // else
+    If,          // if
+    Import,      // import
+    In,          // in
+    Instance,    // instance
+    Let,         // let
+    Partial,     // partial
+    Record,      // record
+    RecordType,  // Record
+    Refl,        // refl
+    Rewrite,     // rewrite
+    Then,        // then
+    Type,        // Type
+    Variant,     // variant
+    VariantType, // Variant
+    Where,       // where
+    With,        // with
 
     // Symbols
-    BSlash,    // \
-    Caret,     // ^
-    Colon,     // :
-    Comma,     // ,
-    Dot,       // .
-    DotDot,    // ..
-    Equal,     // =
-    LArrow,    // ->
-    LFatArrow, // =>
-    Question,  // ?
-    Semi,      // ;
+    BSlash,     // \
+    Caret,      // ^
+    Colon,      // :
+    Comma,      // ,
+    Dot,        // .
+    DotDot,     // ..
+    Equal,      // =
+    EqualEqual, // ==
+    LArrow,     // ->
+    LFatArrow,  // =>
+    Question,   // ?
+    Semi,       // ;
+    SlashSlash, // //
 
     // Delimiters
     LParen,   // (
@@ -200,12 +209,19 @@ impl<S: fmt::Display> fmt::Display for Token<S> {
             Token::If => write!(f, "if"),
             Token::Import => write!(f, "import"),
             Token::In => write!(f, "in"),
+            Token::Instance => write!(f, "instance"),
             Token::Let => write!(f, "let"),
+            Token::Partial => write!(f, "partial"),
             Token::Record => write!(f, "record"),
             Token::RecordType => write!(f, "Record"),
+            Token::Refl => write!(f, "refl"),
+            Token::Rewrite => write!(f, "rewrite"),
             Token::Then => write!(f, "then"),
             Token::Type => write!(f, "Type"),
+            Token::Variant => write!(f, "variant"),
+            Token::VariantType => write!(f, "Variant"),
             Token::Where => write!(f, "where"),
+            Token::With => write!(f, "with"),
             Token::BSlash => write!(f, "\\"),
             Token::Caret => write!(f, "^"),
             Token::Colon => write!(f, ":"),
@@ -213,10 +229,12 @@ impl<S: fmt::Display> fmt::Display for Token<S> {
             Token::Dot => write!(f, "."),
             Token::DotDot => write!(f, ".."),
             Token::Equal => write!(f, "="),
+            Token::EqualEqual => write!(f, "=="),
             Token::LFatArrow => write!(f, "=>"),
             Token::LArrow => write!(f, "->"),
             Token::Question => write!(f, "?"),
             Token::Semi => write!(f, ";"),
+            Token::SlashSlash => write!(f, "//"),
             Token::LParen => write!(f, "("),
             Token::RParen => write!(f, ")"),
             Token::LBrace => write!(f, "{{"),
@@ -245,12 +263,19 @@ impl<'input> From<Token<&'input str>> for Token<String> {
             Token::If => Token::If,
             Token::Import => Token::Import,
             Token::In => Token::In,
+            Token::Instance => Token::Instance,
             Token::Let => Token::Let,
+            Token::Partial => Token::Partial,
             Token::Record => Token::Record,
             Token::RecordType => Token::RecordType,
+            Token::Refl => Token::Refl,
+            Token::Rewrite => Token::Rewrite,
             Token::Then => Token::Then,
             Token::Type => Token::Type,
+            Token::Variant => Token::Variant,
+            Token::VariantType => Token::VariantType,
             Token::Where => Token::Where,
+            Token::With => Token::With,
             Token::BSlash => Token::BSlash,
             Token::Caret => Token::Caret,
             Token::Colon => Token::Colon,
@@ -258,10 +283,12 @@ impl<'input> From<Token<&'input str>> for Token<String> {
             Token::Dot => Token::Dot,
             Token::DotDot => Token::DotDot,
             Token::Equal => Token::Equal,
+            Token::EqualEqual => Token::EqualEqual,
             Token::LFatArrow => Token::LFatArrow,
             Token::LArrow => Token::LArrow,
             Token::Question => Token::Question,
             Token::Semi => Token::Semi,
+            Token::SlashSlash => Token::SlashSlash,
             Token::LParen => Token::LParen,
             Token::RParen => Token::RParen,
             Token::LBrace => Token::LBrace,
@@ -380,12 +407,19 @@ impl<'input> Lexer<'input> {
             "if" => Token::If,
             "import" => Token::Import,
             "in" => Token::In,
+            "instance" => Token::Instance,
             "let" => Token::Let,
+            "partial" => Token::Partial,
             "record" => Token::Record,
             "Record" => Token::RecordType,
+            "refl" => Token::Refl,
+            "rewrite" => Token::Rewrite,
             "then" => Token::Then,
             "Type" => Token::Type,
+            "variant" => Token::Variant,
+            "Variant" => Token::VariantType,
             "where" => Token::Where,
+            "with" => Token::With,
             ident => Token::Ident(ident),
         };
 
@@ -549,10 +583,12 @@ impl<'input> Iterator for Lexer<'input> {
                         "." => Ok((start, Token::Dot, end)),
                         ".." => Ok((start, Token::DotDot, end)),
                         "=" => Ok((start, Token::Equal, end)),
+                        "==" => Ok((start, Token::EqualEqual, end)),
                         "->" => Ok((start, Token::LArrow, end)),
                         "=>" => Ok((start, Token::LFatArrow, end)),
                         "?" => Ok((start, Token::Question, end)),
                         ";" => Ok((start, Token::Semi, end)),
+                        "//" => Ok((start, Token::SlashSlash, end)),
                         symbol if symbol.starts_with("|||") => Ok(self.doc_comment(start)),
                         symbol if symbol.starts_with("--") => {
                             self.take_until(start, |ch| ch == '\n');
@@ -694,36 +730,45 @@ mod tests {
     #[test]
     fn keywords() {
         test! {
-            "  as case else if import in let record Record then Type where  ",
-            "  ~~                                                              " => Token::As,
-            "     ~~~~                                                         " => Token::Case,
-            "          ~~~~                                                    " => Token::Else,
-            "               ~~                                                 " => Token::If,
-            "                  ~~~~~~                                          " => Token::Import,
-            "                         ~~                                       " => Token::In,
-            "                            ~~~                                   " => Token::Let,
-            "                                ~~~~~~                            " => Token::Record,
-            "                                       ~~~~~~                     " => Token::RecordType,
-            "                                              ~~~~                " => Token::Then,
-            "                                                   ~~~~           " => Token::Type,
-            "                                                        ~~~~~     " => Token::Where,
+            "  as case else if import in instance let partial record Record refl rewrite then Type variant Variant where with  ",
+            "  ~~                                                                                                              " => Token::As,
+            "     ~~~~                                                                                                         " => Token::Case,
+            "          ~~~~                                                                                                    " => Token::Else,
+            "               ~~                                                                                                 " => Token::If,
+            "                  ~~~~~~                                                                                          " => Token::Import,
+            "                         ~~                                                                                       " => Token::In,
+            "                            ~~~~~~~~                                                                              " => Token::Instance,
+            "                                     ~~~                                                                          " => Token::Let,
+            "                                         ~~~~~~~                                                                  " => Token::Partial,
+            "                                                 ~~~~~~                                                           " => Token::Record,
+            "                                                        ~~~~~~                                                    " => Token::RecordType,
+            "                                                               ~~~~                                               " => Token::Refl,
+            "                                                                    ~~~~~~~                                       " => Token::Rewrite,
+            "                                                                            ~~~~                                  " => Token::Then,
+            "                                                                                 ~~~~                             " => Token::Type,
+            "                                                                                      ~~~~~~~                     " => Token::Variant,
+            "                                                                                              ~~~~~~~             " => Token::VariantType,
+            "                                                                                                      ~~~~~       " => Token::Where,
+            "                                                                                                            ~~~~  " => Token::With,
         };
     }
 
     #[test]
     fn symbols() {
         test! {
-            r" \ ^ : , .. = -> => ? ; ",
-            r" ~                      " => Token::BSlash,
-            r"   ~                    " => Token::Caret,
-            r"     ~                  " => Token::Colon,
-            r"       ~                " => Token::Comma,
-            r"         ~~             " => Token::DotDot,
-            r"            ~           " => Token::Equal,
-            r"              ~~        " => Token::LArrow,
-            r"                 ~~     " => Token::LFatArrow,
-            r"                    ~   " => Token::Question,
-            r"                      ~ " => Token::Semi,
+            r" \ ^ : , .. = == -> => ? ; // ",
+            r" ~                            " => Token::BSlash,
+            r"   ~                          " => Token::Caret,
+            r"     ~                        " => Token::Colon,
+            r"       ~                      " => Token::Comma,
+            r"         ~~                   " => Token::DotDot,
+            r"            ~                 " => Token::Equal,
+            r"              ~~              " => Token::EqualEqual,
+            r"                 ~~           " => Token::LArrow,
+            r"                    ~~        " => Token::LFatArrow,
+            r"                       ~      " => Token::Question,
+            r"                         ~    " => Token::Semi,
+            r"                           ~~ " => Token::SlashSlash,
         }
     }
 
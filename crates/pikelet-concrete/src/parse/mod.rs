@@ -2,6 +2,7 @@
 
 use codespan::{ByteIndex, ByteSpan, FileMap};
 use lalrpop_util::ParseError as LalrpopError;
+use pikelet_core::syntax::{Multiplicity, Plicity};
 
 use crate::parse::lexer::Lexer;
 use crate::syntax::concrete;
@@ -41,6 +42,20 @@ mod grammar {
     include!(concat!(env!("OUT_DIR"), "/parse/grammar.rs"));
 }
 
+/// Convert a decimal literal into a multiplicity
+fn multiplicity<L, T>(
+    span: ByteSpan,
+    value: u64,
+) -> Result<Multiplicity, LalrpopError<L, T, ParseError>> {
+    match value {
+        0 => Ok(Multiplicity::Zero),
+        1 => Ok(Multiplicity::One),
+        found => Err(LalrpopError::User {
+            error: ParseError::InvalidMultiplicity { span, found },
+        }),
+    }
+}
+
 /// This is an ugly hack that cobbles together a pi type from a binder term and
 /// a body. See the comments on the `PiTerm` rule in the `grammar.lalrpop` for
 /// more information.
@@ -49,7 +64,9 @@ fn reparse_fun_ty_hack<L, T>(
     binder: concrete::Term,
     body: concrete::Term,
 ) -> Result<concrete::Term, LalrpopError<L, T, ParseError>> {
-    use crate::syntax::concrete::Term;
+    use crate::syntax::concrete::{Literal, Term};
+    use crate::syntax::IntFormat;
+    use std::slice;
 
     fn fun_ty_binder<L, T>(
         binder: &Term,
@@ -57,9 +74,27 @@ fn reparse_fun_ty_hack<L, T>(
         match *binder {
             Term::Parens(_, ref term) => match **term {
                 Term::Ann(ref params, ref ann) => {
+                    // Binders can start with a multiplicity, ie. `(0 x : t1)`
+                    let (multiplicity, params) = match **params {
+                        Term::FunApp(ref head, ref args) => match **head {
+                            Term::Literal(Literal::Int(span, value, IntFormat::Dec)) => {
+                                (multiplicity(span, value)?, &args[..])
+                            },
+                            _ => (Multiplicity::Many, slice::from_ref(&**params)),
+                        },
+                        _ => (Multiplicity::Many, slice::from_ref(&**params)),
+                    };
+
                     let mut names = Vec::new();
-                    param_names(&**params, &mut names)?;
-                    Ok(Some((names, (**ann).clone())))
+                    for param in params {
+                        param_names(param, &mut names)?;
+                    }
+                    Ok(Some((
+                        Plicity::Explicit,
+                        multiplicity,
+                        names,
+                        (**ann).clone(),
+                    )))
                 },
                 _ => Ok(None),
             },
@@ -96,7 +131,7 @@ fn reparse_fun_ty_hack<L, T>(
 
             for next in iter::once(&**head).chain(args).map(fun_ty_binder) {
                 match next? {
-                    Some((names, ann)) => binders.push((names, ann)),
+                    Some(binder) => binders.push(binder),
                     None => return Ok(Term::FunArrow(Box::new(binder.clone()), Box::new(body))),
                 }
             }
@@ -109,3 +144,31 @@ fn reparse_fun_ty_hack<L, T>(
         },
     }
 }
+
+/// Cobbles together a pi type that starts with some implicit parameters, for
+/// example `{a : Type} (x : a) -> a`. The explicit parameters that follow the
+/// implicit ones are reparsed using `reparse_fun_ty_hack`.
+fn reparse_implicit_fun_ty_hack<L, T>(
+    span: ByteSpan,
+    implicit_params: concrete::FunTypeParams,
+    binder: concrete::Term,
+    body: concrete::Term,
+) -> Result<concrete::Term, LalrpopError<L, T, ParseError>> {
+    use crate::syntax::concrete::Term;
+
+    match reparse_fun_ty_hack(span, binder, body)? {
+        Term::FunType(_, explicit_params, body) => {
+            let mut params = implicit_params;
+            params.extend(explicit_params);
+            Ok(Term::FunType(span.start(), params, body))
+        },
+        // Non-dependent arrows can't follow implicit parameters without an
+        // arrow of their own, ie. `{a : Type} -> a -> a`
+        Term::FunArrow(binder, _) => Err(LalrpopError::User {
+            error: ParseError::IdentifierExpectedInPiType {
+                span: binder.span(),
+            },
+        }),
+        _ => unreachable!(),
+    }
+}
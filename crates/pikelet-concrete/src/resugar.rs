@@ -1,90 +1,112 @@
 use codespan::{ByteIndex, ByteSpan};
 use im;
-use moniker::{Binder, BoundTerm, Embed, FreeVar, Nest, Scope, Var};
+use moniker::FreeVar;
 
+use pikelet_core::nbe::{self, Env};
 use pikelet_core::syntax::{core, domain};
-use pikelet_core::syntax::{Label, Level, LevelShift};
+use pikelet_core::syntax::{
+    Import, Label, Level, LevelShift, MetaVar, Multiplicity, NameHint, Plicity, Totality, VarIndex,
+};
 
-use crate::syntax::{concrete, FloatFormat, IntFormat};
+use crate::syntax::{concrete, BindingKind, FloatFormat, IntFormat};
 
 /// The environment used when resugaring from the core to the concrete syntax
 #[derive(Debug, Clone)]
 pub struct ResugarEnv {
     usages: im::HashMap<String, u32>,
-    renames: im::HashMap<FreeVar<String>, String>,
+    globals: im::HashMap<FreeVar<String>, String>,
+    locals: im::Vector<String>,
 }
 
 const KEYWORDS: &[&str] = &[
-    "as", "case", "else", "if", "import", "in", "let", "record", "Record", "then", "Type", "where",
+    "as", "case", "else", "if", "import", "in", "let", "record", "Record", "refl", "rewrite",
+    "then", "Type", "variant", "Variant", "where", "with",
 ];
 
 impl ResugarEnv {
     pub fn new() -> ResugarEnv {
         ResugarEnv {
             usages: KEYWORDS.iter().map(|&kw| (kw.to_owned(), 0)).collect(),
-            renames: im::HashMap::new(),
+            globals: im::HashMap::new(),
+            locals: im::Vector::new(),
         }
     }
 
-    pub fn on_item(&mut self, label: &Label, binder: &Binder<String>) -> String {
-        let Label(ref name) = *label;
-        let Binder(ref free_var) = *binder;
+    /// The number of local variables in scope
+    pub fn size(&self) -> usize {
+        self.locals.len()
+    }
 
-        self.renames.get(free_var).cloned().unwrap_or_else(|| {
-            match self.usages.get(name).cloned() {
-                Some(count) => {
-                    let count = count + 1;
-                    let mapped_name = format!("{}{}", name, count);
+    /// Find a name that does not clash with any of the names used so far
+    fn fresh_name(&mut self, name: &str) -> String {
+        match self.usages.get(name).cloned() {
+            Some(count) => {
+                let count = count + 1;
+                let mapped_name = format!("{}{}", name, count);
 
-                    self.usages.insert(name.clone(), count);
-                    self.usages.insert(mapped_name.clone(), count);
-                    self.renames.insert(free_var.clone(), mapped_name.clone());
+                self.usages.insert(name.to_owned(), count);
+                self.usages.insert(mapped_name.clone(), count);
 
-                    mapped_name
-                },
-                None => {
-                    self.usages.insert(name.clone(), 0);
-                    self.renames.insert(free_var.clone(), name.clone());
+                mapped_name
+            },
+            None => {
+                self.usages.insert(name.to_owned(), 0);
 
-                    name.clone()
-                },
-            }
-        })
+                name.to_owned()
+            },
+        }
     }
 
-    // pub fn on_binder(&mut self, binder: &Binder<String>, free_vars: &HashSet<String>) -> String {
-    pub fn on_binder(&mut self, binder: &Binder<String>) -> String {
-        let Binder(ref free_var) = *binder;
+    /// Bring a record field into scope as a local variable, naming it after
+    /// its label
+    pub fn on_item(&mut self, label: &Label) -> String {
+        let Label(ref name) = *label;
+        let name = self.fresh_name(name);
+        self.locals.push_back(name.clone());
+        name
+    }
 
-        self.renames.get(free_var).cloned().unwrap_or_else(|| {
-            let pretty_name = match free_var.pretty_name {
-                Some(ref name) => name.clone(),
-                None => "a".to_owned(),
-            };
+    /// Bring a local variable into scope
+    pub fn on_local(&mut self, name: &NameHint) -> String {
+        let name = self.fresh_name(match name.0 {
+            Some(ref name) => name,
+            None => "a",
+        });
+        self.locals.push_back(name.clone());
+        name
+    }
 
-            match self.usages.get(&pretty_name).cloned() {
-                Some(count) => {
-                    let count = count + 1;
-                    let mapped_name = format!("{}{}", pretty_name, count);
+    /// Bring a local variable into scope that can't be referred to by name
+    fn on_wildcard(&mut self) {
+        self.locals.push_back("_".to_owned());
+    }
 
-                    self.usages.insert(pretty_name, count);
-                    self.usages.insert(mapped_name.clone(), count);
-                    self.renames.insert(free_var.clone(), mapped_name.clone());
+    /// Bring a global variable into scope
+    pub fn on_global(&mut self, free_var: &FreeVar<String>) -> String {
+        if let Some(name) = self.globals.get(free_var) {
+            return name.clone();
+        }
 
-                    mapped_name
-                },
-                None => {
-                    self.usages.insert(pretty_name.clone(), 0);
-                    self.renames.insert(free_var.clone(), pretty_name.clone());
+        let name = self.fresh_name(match free_var.pretty_name {
+            Some(ref name) => name,
+            None => "a",
+        });
+        self.globals.insert(free_var.clone(), name.clone());
+        name
+    }
 
-                    pretty_name
-                },
-            }
-        })
+    pub fn on_var(&self, index: VarIndex) -> String {
+        match index.to_level(self.size()) {
+            Some(level) => self.locals[level.0].clone(),
+            None => panic!(
+                "on_var: expected {} to be bound in resugar environment",
+                index,
+            ),
+        }
     }
 
     pub fn on_free_var(&self, free_var: &FreeVar<String>) -> String {
-        self.renames.get(free_var).cloned().unwrap_or_else(|| {
+        self.globals.get(free_var).cloned().unwrap_or_else(|| {
             panic!(
                 "on_free_var: expected {} to be bound in resugar environment",
                 free_var,
@@ -128,32 +150,47 @@ fn parens_if(should_wrap: bool, inner: concrete::Term) -> concrete::Term {
     }
 }
 
+/// Resugar a pattern, where `env` is the scope outside of the pattern and
+/// `body_env` is extended with the variables that the pattern binds
 fn resugar_pattern(
-    env: &mut ResugarEnv,
+    env: &ResugarEnv,
+    body_env: &mut ResugarEnv,
     pattern: &core::Pattern,
     _prec: Prec,
 ) -> concrete::Pattern {
     match *pattern {
-        core::Pattern::Ann(ref pattern, Embed(ref ty)) => concrete::Pattern::Ann(
-            Box::new(resugar_pattern(env, pattern, Prec::NO_WRAP)),
+        core::Pattern::Ann(ref pattern, ref ty) => concrete::Pattern::Ann(
+            Box::new(resugar_pattern(env, body_env, pattern, Prec::NO_WRAP)),
             Box::new(resugar_term(env, ty, Prec::LAM)),
         ),
-        core::Pattern::Binder(ref binder) => {
-            let name = env.on_binder(binder);
-            concrete::Pattern::Name(ByteSpan::default(), name, None)
+        core::Pattern::Binder(ref name) => match name.0 {
+            // Wildcards are elaborated as binders that can't be referred to
+            Some(ref name) if name == "_" => {
+                body_env.on_wildcard();
+                concrete::Pattern::Wildcard(ByteSpan::default())
+            },
+            _ => {
+                let name = body_env.on_local(name);
+                concrete::Pattern::Name(ByteSpan::default(), name, None)
+            },
         },
-        core::Pattern::Var(Embed(Var::Free(ref free_var)), shift) => {
+        core::Pattern::Var(index, shift) => {
             let shift = match shift {
                 LevelShift(0) => None,
                 LevelShift(shift) => Some(shift),
             };
 
-            let name = env.on_free_var(free_var);
+            let name = env.on_var(index);
             concrete::Pattern::Name(ByteSpan::default(), name, shift)
         },
-        core::Pattern::Var(Embed(Var::Bound(_)), _) => {
-            // TODO: Better message
-            panic!("Tried to convert a term that was not locally closed");
+        core::Pattern::Global(ref free_var, shift) => {
+            let shift = match shift {
+                LevelShift(0) => None,
+                LevelShift(shift) => Some(shift),
+            };
+
+            let name = env.on_free_var(free_var);
+            concrete::Pattern::Name(ByteSpan::default(), name, shift)
         },
         core::Pattern::Literal(ref literal) => {
             use pikelet_core::syntax::Literal;
@@ -186,29 +223,86 @@ fn resugar_pattern(
                 Literal::F64(v) => Pattern::Literal(Float(span, v, FloatFormat::Dec)),
             }
         },
+        core::Pattern::VariantIntro(Label(ref label), ref pattern) => {
+            use crate::syntax::concrete::Pattern;
+
+            let pattern = match resugar_pattern(env, body_env, pattern, Prec::ATOMIC) {
+                pattern @ Pattern::Ann(..) | pattern @ Pattern::VariantIntro(..) => {
+                    Pattern::Parens(ByteSpan::default(), Box::new(pattern))
+                },
+                pattern => pattern,
+            };
+
+            Pattern::VariantIntro(
+                ByteIndex::default(),
+                (ByteIndex::default(), label.clone()),
+                Box::new(pattern),
+            )
+        },
+        core::Pattern::RecordIntro(ref fields) => {
+            use crate::syntax::concrete::{Pattern, RecordPatternField};
+
+            let fields = fields
+                .iter()
+                .map(|&(Label(ref label), ref pattern)| {
+                    match resugar_pattern(env, body_env, pattern, Prec::NO_WRAP) {
+                        Pattern::Name(_, ref name, None) if name == label => {
+                            RecordPatternField::Punned {
+                                label: (ByteIndex::default(), label.clone()),
+                            }
+                        },
+                        pattern => RecordPatternField::Explicit {
+                            label: (ByteIndex::default(), label.clone()),
+                            pattern,
+                        },
+                    }
+                })
+                .collect();
+
+            Pattern::RecordIntro(ByteSpan::default(), fields)
+        },
+        core::Pattern::ArrayIntro(ref elems) => concrete::Pattern::ArrayIntro(
+            ByteSpan::default(),
+            elems
+                .iter()
+                .map(|elem| resugar_pattern(env, body_env, elem, Prec::NO_WRAP))
+                .collect(),
+        ),
     }
 }
 
 fn resugar_fun_ty(
     env: &ResugarEnv,
-    scope: &Scope<(Binder<String>, Embed<core::RcTerm>), core::RcTerm>,
+    mut plicity: Plicity,
+    mut multiplicity: Multiplicity,
+    name: &NameHint,
+    ann: &core::RcTerm,
+    body: &core::RcTerm,
     prec: Prec,
 ) -> concrete::Term {
     let mut env = env.clone();
-
-    let ((binder, Embed(mut ann)), mut body) = scope.clone().unbind();
-    let body_fvs = body.free_vars();
+    let mut ann = ann.clone();
+    let mut body = body.clone();
 
     // Only use explicit parameter names if the body is dependent on
-    // the parameter or there is a human-readable name given.
+    // the parameter or there is a human-readable name given. Implicit and
+    // instance parameters always need names, because there is no arrow syntax
+    // for them, and neither do parameters with multiplicities.
     //
     // We'll be checking for readable names as we go, because if they've
     // survived until now they're probably desirable to retain!
-    if body_fvs.contains(&binder.0) || binder.0.pretty_name.is_some() {
-        let name = env.on_binder(&binder);
+    if plicity != Plicity::Explicit
+        || multiplicity != Multiplicity::Many
+        || body.has_free_var(VarIndex(0))
+        || name.0.is_some()
+    {
+        let ann_term = resugar_term(&env, &ann, Prec::APP);
+        let name = env.on_local(name);
         let mut params = vec![(
+            plicity,
+            multiplicity,
             vec![(ByteIndex::default(), name)],
-            resugar_term(&env, &ann, Prec::APP),
+            ann_term,
         )];
 
         // Argument resugaring
@@ -221,12 +315,32 @@ fn resugar_fun_ty(
             // (a : Type) -> (b : Type -> Type) -> ...
             // (a : Type) (b : Type -> Type) -> ...
             // ```
-            let ((next_binder, Embed(next_ann)), next_body) = match *body {
-                core::Term::FunType(ref scope) => scope.clone().unbind(),
+            //
+            // Implicit and instance parameters can only appear at the start of
+            // a parameter list, so we stop collapsing if they follow explicit
+            // ones.
+            let (next_plicity, next_multiplicity, next_name, next_ann, next_body) = match *body {
+                core::Term::FunType(
+                    next_plicity,
+                    next_multiplicity,
+                    ref next_name,
+                    ref next_ann,
+                    ref next_body,
+                ) if plicity != Plicity::Explicit || next_plicity == Plicity::Explicit => (
+                    next_plicity,
+                    next_multiplicity,
+                    next_name.clone(),
+                    next_ann.clone(),
+                    next_body.clone(),
+                ),
                 _ => break,
             };
 
-            if core::Term::term_eq(&ann, &next_ann) && next_binder.0.pretty_name.is_some() {
+            if next_plicity == plicity
+                && next_multiplicity == multiplicity
+                && ann.shift_vars(1) == next_ann
+                && next_name.0.is_some()
+            {
                 // Combine the parameters if the type annotations are
                 // alpha-equivalent. For example:
                 //
@@ -234,34 +348,44 @@ fn resugar_fun_ty(
                 // (a : Type) (b : Type) -> ...
                 // (a b : Type) -> ...
                 // ```
-                let next_name = env.on_binder(&next_binder);
+                let next_name = env.on_local(&next_name);
                 let next_param = (ByteIndex::default(), next_name);
-                params.last_mut().unwrap().0.push(next_param);
-            } else if next_body.free_vars().contains(&next_binder.0)
-                || next_binder.0.pretty_name.is_some()
+                params.last_mut().unwrap().1.push(next_param);
+            } else if next_plicity != Plicity::Explicit
+                || next_multiplicity != Multiplicity::Many
+                || next_body.has_free_var(VarIndex(0))
+                || next_name.0.is_some()
             {
                 // Add a new parameter if the body is dependent on the parameter
                 // or there is a human-readable name given
-                let next_name = env.on_binder(&next_binder);
+                let next_ann_term = resugar_term(&env, &next_ann, Prec::APP);
+                let next_name = env.on_local(&next_name);
                 params.push((
+                    next_plicity,
+                    next_multiplicity,
                     vec![(ByteIndex::default(), next_name)],
-                    resugar_term(&env, &next_ann, Prec::APP),
+                    next_ann_term,
                 ));
             } else {
                 // Stop collapsing parameters if we encounter a non-dependent pi type.
+                let next_ann_term = resugar_term(&env, &next_ann, Prec::APP);
+                env.on_local(&next_name);
+
                 return parens_if(
                     Prec::PI < prec,
                     concrete::Term::FunType(
                         ByteIndex::default(),
                         params,
                         Box::new(concrete::Term::FunArrow(
-                            Box::new(resugar_term(&env, &next_ann, Prec::APP)),
+                            Box::new(next_ann_term),
                             Box::new(resugar_term(&env, &next_body, Prec::LAM)),
                         )),
                     ),
                 );
             }
 
+            plicity = next_plicity;
+            multiplicity = next_multiplicity;
             ann = next_ann;
             body = next_body;
         }
@@ -282,10 +406,13 @@ fn resugar_fun_ty(
         // (a : Type) -> Type
         // Type -> Type
         // ```
+        let ann_term = resugar_term(&env, &ann, Prec::APP);
+        env.on_local(name);
+
         parens_if(
             Prec::PI < prec,
             concrete::Term::FunArrow(
-                Box::new(resugar_term(&env, &ann, Prec::APP)),
+                Box::new(ann_term),
                 Box::new(resugar_term(&env, &body, Prec::LAM)),
             ),
         )
@@ -294,18 +421,27 @@ fn resugar_fun_ty(
 
 fn resugar_fun_intro(
     env: &ResugarEnv,
-    scope: &Scope<(Binder<String>, Embed<core::RcTerm>), core::RcTerm>,
+    plicity: Plicity,
+    multiplicity: Multiplicity,
+    name: &NameHint,
+    ann: &core::RcTerm,
+    body: &core::RcTerm,
     prec: Prec,
 ) -> concrete::Term {
     let mut env = env.clone();
+    let mut ann = ann.clone();
+    let mut body = body.clone();
 
-    let ((binder, Embed(mut ann)), mut body) = scope.clone().unbind();
-
-    let name = env.on_binder(&binder);
+    let ann_term = resugar_term(&env, &ann, Prec::LAM);
+    let name = env.on_local(name);
     let mut params = vec![(
+        plicity,
+        resugar_multiplicity(multiplicity),
         vec![(ByteIndex::default(), name)],
-        Some(Box::new(resugar_term(&env, &ann, Prec::LAM))),
+        Some(Box::new(ann_term)),
     )];
+    let mut plicity = plicity;
+    let mut multiplicity = multiplicity;
 
     // Argument resugaring
     #[cfg_attr(feature = "cargo-clippy", allow(while_let_loop))]
@@ -317,8 +453,20 @@ fn resugar_fun_intro(
         // \(a : Type) => \(b : Type -> Type) => ...
         // \(a : Type) (b : Type -> Type) => ...
         // ```
-        let ((next_binder, Embed(next_ann)), next_body) = match *body {
-            core::Term::FunIntro(ref scope) => scope.clone().unbind(),
+        let (next_plicity, next_multiplicity, next_name, next_ann, next_body) = match *body {
+            core::Term::FunIntro(
+                next_plicity,
+                next_multiplicity,
+                ref next_name,
+                ref next_ann,
+                ref next_body,
+            ) => (
+                next_plicity,
+                next_multiplicity,
+                next_name.clone(),
+                next_ann.clone(),
+                next_body.clone(),
+            ),
             _ => break,
         };
 
@@ -329,17 +477,26 @@ fn resugar_fun_intro(
         // \(a : Type) (b : Type) => ...
         // \(a b : Type) => ...
         // ```
-        let next_name = env.on_binder(&next_binder);
-        if core::Term::term_eq(&ann, &next_ann) {
+        if plicity == next_plicity
+            && multiplicity == next_multiplicity
+            && ann.shift_vars(1) == next_ann
+        {
+            let next_name = env.on_local(&next_name);
             let next_param = (ByteIndex::default(), next_name);
-            params.last_mut().unwrap().0.push(next_param);
+            params.last_mut().unwrap().1.push(next_param);
         } else {
+            let next_ann_term = resugar_term(&env, &next_ann, Prec::LAM);
+            let next_name = env.on_local(&next_name);
             params.push((
+                next_plicity,
+                resugar_multiplicity(next_multiplicity),
                 vec![(ByteIndex::default(), next_name)],
-                Some(Box::new(resugar_term(&env, &next_ann, Prec::LAM))),
+                Some(Box::new(next_ann_term)),
             ));
         }
 
+        plicity = next_plicity;
+        multiplicity = next_multiplicity;
         ann = next_ann;
         body = next_body;
     }
@@ -354,102 +511,161 @@ fn resugar_fun_intro(
     )
 }
 
-fn resugar_let(
+/// Lambda parameters are assumed to be unrestricted unless a multiplicity is
+/// given, so we only need to show the others
+fn resugar_multiplicity(multiplicity: Multiplicity) -> Option<Multiplicity> {
+    match multiplicity {
+        Multiplicity::Many => None,
+        multiplicity => Some(multiplicity),
+    }
+}
+
+/// Resugar the fields of a record introduction or extension
+fn resugar_record_fields<'a>(
     env: &ResugarEnv,
-    scope: &Scope<Nest<(Binder<String>, Embed<core::RcTerm>)>, core::RcTerm>,
+    fields: impl Iterator<Item = (&'a Label, &'a core::RcTerm)>,
+) -> Vec<concrete::RecordIntroField> {
+    fields
+        .map(|(label, term)| {
+            let (term_params, term_body) = resugar_definition_body(env, term);
+
+            // TODO: use a punned label if possible?
+            concrete::RecordIntroField::Explicit {
+                label: (ByteIndex::default(), label.0.clone()),
+                params: term_params,
+                return_ann: None,
+                term: term_body,
+            }
+        })
+        .collect()
+}
+
+/// Pull the lambda arguments from the body of a definition into the
+/// definition itself
+fn resugar_definition_body(
+    env: &ResugarEnv,
+    term: &core::RcTerm,
+) -> (concrete::FunIntroParams, concrete::Term) {
+    match resugar_term(env, term, Prec::NO_WRAP) {
+        concrete::Term::FunIntro(_, params, term_body) => (params, *term_body),
+        term_body => (vec![], term_body),
+    }
+}
+
+fn resugar_fix(
+    env: &ResugarEnv,
+    totality: Totality,
+    name: &NameHint,
+    ann: &core::RcTerm,
+    body: &core::RcTerm,
     prec: Prec,
 ) -> concrete::Term {
     let mut env = env.clone();
 
-    let (bindings, mut body) = scope.clone().unbind();
-    let bindings = bindings.unnest();
+    let ann = resugar_term(&env, ann, Prec::ANN);
+    let name = env.on_local(name);
+    let (term_params, term_body) = resugar_definition_body(&env, body);
+
+    let items = vec![
+        concrete::Item::Declaration {
+            totality,
+            kind: BindingKind::Ordinary,
+            name: (ByteIndex::default(), name.clone()),
+            ann,
+        },
+        concrete::Item::Definition {
+            kind: BindingKind::Ordinary,
+            name: (ByteIndex::default(), name.clone()),
+            params: term_params,
+            return_ann: None,
+            body: term_body,
+        },
+    ];
+
+    parens_if(
+        Prec::LAM < prec,
+        concrete::Term::Let(
+            ByteIndex::default(),
+            items,
+            Box::new(concrete::Term::Name(ByteSpan::default(), name, None)),
+        ),
+    )
+}
+
+/// Resugar a let binding into items, bringing it into scope in `env`
+///
+/// Recursive bindings are wrapped in fixed points, which are replaced with
+/// references to the binding itself.
+fn resugar_let_binding(
+    env: &mut ResugarEnv,
+    items: &mut Vec<concrete::Item>,
+    name: &NameHint,
+    term: &core::RcTerm,
+) {
+    let (declaration, (term_params, term_body)) = match *term.inner {
+        core::Term::Fix(totality, _, ref ann, ref body) => {
+            let ann = resugar_term(env, ann, Prec::ANN);
+            env.on_local(name);
+            (Some((totality, ann)), resugar_definition_body(env, body))
+        },
+        core::Term::Ann(ref term, ref ann) => {
+            let ann = resugar_term(env, ann, Prec::ANN);
+            let definition_body = resugar_definition_body(env, term);
+            env.on_local(name);
+            (Some((Totality::Total, ann)), definition_body)
+        },
+        _ => {
+            let definition_body = resugar_definition_body(env, term);
+            env.on_local(name);
+            (None, definition_body)
+        },
+    };
+
+    let name = env.on_var(VarIndex(0));
+    if let Some((totality, ann)) = declaration {
+        items.push(concrete::Item::Declaration {
+            totality,
+            kind: BindingKind::Ordinary,
+            name: (ByteIndex::default(), name.clone()),
+            ann,
+        });
+    }
+    items.push(concrete::Item::Definition {
+        kind: BindingKind::Ordinary,
+        name: (ByteIndex::default(), name),
+        params: term_params,
+        return_ann: None,
+        body: term_body,
+    });
+}
+
+fn resugar_let(
+    env: &ResugarEnv,
+    bindings: &[(NameHint, core::RcTerm)],
+    body: &core::RcTerm,
+    prec: Prec,
+) -> concrete::Term {
+    let mut env = env.clone();
+    let mut body = body.clone();
 
     let mut items = Vec::with_capacity(bindings.len() * 2);
 
-    for (binder, Embed(term)) in bindings {
-        let name = env.on_binder(&binder);
-
-        match *term.inner {
-            core::Term::Ann(ref term, ref ann) => {
-                // pull lambda arguments from the body into the definition
-                let (term_params, term_body) = match resugar_term(&env, term, Prec::NO_WRAP) {
-                    concrete::Term::FunIntro(_, params, term_body) => (params, *term_body),
-                    term_body => (vec![], term_body),
-                };
-
-                items.push(concrete::Item::Declaration {
-                    name: (ByteIndex::default(), name.clone()),
-                    ann: resugar_term(&env, &ann, Prec::ANN),
-                });
-                items.push(concrete::Item::Definition {
-                    name: (ByteIndex::default(), name),
-                    params: term_params,
-                    return_ann: None,
-                    body: term_body,
-                });
-            },
-            _ => {
-                // pull lambda arguments from the body into the definition
-                let (term_params, term_body) = match resugar_term(&env, &term, Prec::NO_WRAP) {
-                    concrete::Term::FunIntro(_, params, term_body) => (params, *term_body),
-                    term_body => (vec![], term_body),
-                };
-
-                items.push(concrete::Item::Definition {
-                    name: (ByteIndex::default(), name),
-                    params: term_params,
-                    return_ann: None,
-                    body: term_body,
-                });
-            },
-        }
+    for &(ref name, ref term) in bindings {
+        resugar_let_binding(&mut env, &mut items, name, term);
     }
 
     #[cfg_attr(feature = "cargo-clippy", allow(while_let_loop))]
     loop {
-        let (bindings, next_body) = match *body {
-            core::Term::Let(ref scope) => scope.clone().unbind(),
+        let next_body = match *body {
+            core::Term::Let(ref bindings, ref next_body) => {
+                for &(ref name, ref term) in bindings {
+                    resugar_let_binding(&mut env, &mut items, name, term);
+                }
+                next_body.clone()
+            },
             _ => break,
         };
 
-        for (binder, Embed(term)) in bindings.unnest() {
-            let next_name = env.on_binder(&binder);
-            match *term.inner {
-                core::Term::Ann(ref term, ref ann) => {
-                    // pull lambda arguments from the body into the definition
-                    let (term_params, term_body) = match resugar_term(&env, term, Prec::NO_WRAP) {
-                        concrete::Term::FunIntro(_, params, term_body) => (params, *term_body),
-                        term_body => (vec![], term_body),
-                    };
-
-                    items.push(concrete::Item::Declaration {
-                        name: (ByteIndex::default(), next_name.clone()),
-                        ann: resugar_term(&env, &ann, Prec::ANN),
-                    });
-                    items.push(concrete::Item::Definition {
-                        name: (ByteIndex::default(), next_name),
-                        params: term_params,
-                        return_ann: None,
-                        body: term_body,
-                    });
-                },
-                _ => {
-                    // pull lambda arguments from the body into the definition
-                    let (term_params, term_body) = match resugar_term(&env, &term, Prec::NO_WRAP) {
-                        concrete::Term::FunIntro(_, params, term_body) => (params, *term_body),
-                        term_body => (vec![], term_body),
-                    };
-
-                    items.push(concrete::Item::Definition {
-                        name: (ByteIndex::default(), next_name),
-                        params: term_params,
-                        return_ann: None,
-                        body: term_body,
-                    });
-                },
-            }
-        }
-
         body = next_body;
     }
 
@@ -483,6 +699,68 @@ fn resugar_term(env: &ResugarEnv, term: &core::Term, prec: Prec) -> concrete::Te
                 concrete::Term::Universe(ByteSpan::default(), level),
             )
         },
+        core::Term::UniverseAt(ref level) => {
+            // Levels that are not yet known are shown as if they were written
+            // without one, keeping any shift that was applied to them
+            let unknown_shift = match *level.inner {
+                core::Term::Meta(_) => Some(0),
+                core::Term::LevelShift(ref level, LevelShift(shift)) => match *level.inner {
+                    core::Term::Meta(_) => Some(shift),
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some(shift) = unknown_shift {
+                let level = if shift == 0 { None } else { Some(shift) };
+
+                return parens_if(
+                    Prec::APP < prec && level.is_some(),
+                    concrete::Term::Universe(ByteSpan::default(), level),
+                );
+            }
+
+            let level = match resugar_term(env, level, Prec::NO_WRAP) {
+                level @ concrete::Term::Name(_, _, None) => level,
+                level => concrete::Term::Parens(ByteSpan::default(), Box::new(level)),
+            };
+
+            parens_if(
+                Prec::APP < prec,
+                concrete::Term::UniverseAt(ByteSpan::default(), Box::new(level)),
+            )
+        },
+        core::Term::UniverseOmega => {
+            let omega = concrete::Term::Name(ByteSpan::default(), "ω".to_owned(), None);
+
+            parens_if(
+                Prec::APP < prec,
+                concrete::Term::UniverseAt(ByteSpan::default(), Box::new(omega)),
+            )
+        },
+        // FIXME: Draw these names from some environment?
+        core::Term::LevelType => {
+            concrete::Term::Name(ByteSpan::default(), "Level".to_owned(), None)
+        },
+        core::Term::LevelIntro(Level(level)) => concrete::Term::Literal(concrete::Literal::Int(
+            ByteSpan::default(),
+            u64::from(level),
+            IntFormat::Dec,
+        )),
+        core::Term::LevelMax(ref level1, ref level2) => parens_if(
+            Prec::APP < prec,
+            concrete::Term::FunApp(
+                Box::new(concrete::Term::Name(
+                    ByteSpan::default(),
+                    "max".to_owned(),
+                    None,
+                )),
+                vec![
+                    resugar_term(env, level1, Prec::ATOMIC),
+                    resugar_term(env, level2, Prec::ATOMIC),
+                ],
+            ),
+        ),
+        core::Term::LevelShift(..) => concrete::Term::Hole(ByteSpan::default()),
         core::Term::Literal(ref literal) => {
             use pikelet_core::syntax::Literal;
 
@@ -514,47 +792,61 @@ fn resugar_term(env: &ResugarEnv, term: &core::Term, prec: Prec) -> concrete::Te
                 Literal::F64(val) => Term::Literal(Float(span, val, FloatFormat::Dec)),
             }
         },
-        core::Term::Var(Var::Free(ref free_var), shift) => {
+        core::Term::Var(index, shift) => {
             let shift = match shift {
                 LevelShift(0) => None,
                 LevelShift(shift) => Some(shift),
             };
 
-            let name = env.on_free_var(free_var);
+            let name = env.on_var(index);
             concrete::Term::Name(ByteSpan::default(), name, shift)
         },
-        core::Term::Var(Var::Bound(_), _) => {
-            // TODO: Better message
-            panic!("Tried to convert a term that was not locally closed");
+        core::Term::Global(ref free_var, shift) => {
+            let shift = match shift {
+                LevelShift(0) => None,
+                LevelShift(shift) => Some(shift),
+            };
+
+            let name = env.on_free_var(free_var);
+            concrete::Term::Name(ByteSpan::default(), name, shift)
         },
         core::Term::Import(ref name) => parens_if(
             Prec::LAM < prec,
             concrete::Term::Import(ByteSpan::default(), ByteSpan::default(), name.clone()),
         ),
-        core::Term::FunType(ref scope) => resugar_fun_ty(env, scope, prec),
-        core::Term::FunIntro(ref scope) => resugar_fun_intro(env, scope, prec),
-        core::Term::FunApp(ref head, ref arg) => parens_if(
+        core::Term::Meta(_) => concrete::Term::Hole(ByteSpan::default()),
+        // There is no surface syntax for erased terms, so we show them as holes
+        core::Term::Erased => concrete::Term::Hole(ByteSpan::default()),
+        core::Term::FunType(plicity, multiplicity, ref name, ref ann, ref body) => {
+            resugar_fun_ty(env, plicity, multiplicity, name, ann, body, prec)
+        },
+        core::Term::FunIntro(plicity, multiplicity, ref name, ref ann, ref body) => {
+            resugar_fun_intro(env, plicity, multiplicity, name, ann, body, prec)
+        },
+        core::Term::FunApp(ref head, _, ref arg) => parens_if(
             Prec::APP < prec,
             concrete::Term::FunApp(
                 Box::new(resugar_term(env, head, Prec::NO_WRAP)),
                 vec![resugar_term(env, arg, Prec::NO_WRAP)], // TODO
             ),
         ),
-        core::Term::Let(ref scope) => resugar_let(env, scope, prec),
-        core::Term::RecordType(ref scope) => {
+        core::Term::Let(ref bindings, ref body) => resugar_let(env, bindings, body, prec),
+        core::Term::Fix(totality, ref name, ref ann, ref body) => {
+            resugar_fix(env, totality, name, ann, body, prec)
+        },
+        core::Term::RecordType(ref fields) => {
             let mut env = env.clone();
-            let (scope, ()) = scope.clone().unbind();
 
-            let fields = scope
-                .unnest()
-                .into_iter()
-                .map(|(label, binder, Embed(ann))| {
-                    let ann = resugar_term(&env, &ann, Prec::NO_WRAP);
-                    let name = env.on_item(&label, &binder);
+            let fields = fields
+                .iter()
+                .map(|&(ref label, ref name_hint, multiplicity, ref ann)| {
+                    let ann = resugar_term(&env, ann, Prec::NO_WRAP);
+                    let name = env.on_item(label);
 
                     concrete::RecordTypeField {
+                        multiplicity,
                         label: (ByteIndex::default(), label.0.clone()),
-                        binder: match binder.0.pretty_name {
+                        binder: match name_hint.0 {
                             Some(ref pretty_name) if *pretty_name == name => None,
                             None | Some(_) => Some((ByteIndex::default(), name)),
                         },
@@ -566,28 +858,22 @@ fn resugar_term(env: &ResugarEnv, term: &core::Term, prec: Prec) -> concrete::Te
             concrete::Term::RecordType(ByteSpan::default(), fields)
         },
         core::Term::RecordIntro(ref fields) => {
-            let fields = fields
-                .iter()
-                .map(|&(ref label, ref term)| {
-                    let (term_params, term_body) = match resugar_term(env, &term, Prec::NO_WRAP) {
-                        concrete::Term::FunIntro(_, params, term_body) => (params, *term_body),
-                        term_body => (vec![], term_body),
-                    };
-
-                    // TODO: use a punned label if possible?
-                    concrete::RecordIntroField::Explicit {
-                        label: (ByteIndex::default(), label.0.clone()),
-                        params: term_params,
-                        return_ann: None,
-                        term: term_body,
-                    }
-                })
-                .collect();
-
             // TODO: Add let to rename shadowed globals?
-            concrete::Term::RecordIntro(ByteSpan::default(), fields)
+            let fields = fields.iter().map(|&(ref label, _, ref term)| (label, term));
+            concrete::Term::RecordIntro(ByteSpan::default(), resugar_record_fields(env, fields))
         },
-        core::Term::RecordProj(ref expr, Label(ref label), shift) => {
+        core::Term::RecordExtend(ref expr, ref fields) => {
+            let fields = fields.iter().map(|&(ref label, ref term)| (label, term));
+            parens_if(
+                Prec::APP < prec,
+                concrete::Term::RecordExtend(
+                    ByteSpan::default(),
+                    Box::new(resugar_term(env, expr, Prec::APP)),
+                    resugar_record_fields(env, fields),
+                ),
+            )
+        },
+        core::Term::RecordProj(ref expr, Label(ref label), _, shift) => {
             let shift = match shift {
                 LevelShift(0) => None,
                 LevelShift(shift) => Some(shift),
@@ -601,18 +887,53 @@ fn resugar_term(env: &ResugarEnv, term: &core::Term, prec: Prec) -> concrete::Te
                 shift,
             )
         },
+        core::Term::VariantType(ref alts) => {
+            let alts = alts
+                .iter()
+                .map(|&(Label(ref label), ref ann)| concrete::VariantTypeAlt {
+                    label: (ByteIndex::default(), label.clone()),
+                    ann: resugar_term(env, ann, Prec::NO_WRAP),
+                })
+                .collect();
+
+            concrete::Term::VariantType(ByteSpan::default(), alts)
+        },
+        core::Term::VariantIntro(Label(ref label), ref arg) => parens_if(
+            Prec::APP < prec,
+            concrete::Term::VariantIntro(
+                ByteIndex::default(),
+                (ByteIndex::default(), label.clone()),
+                Box::new(resugar_term(env, arg, Prec::ATOMIC)),
+            ),
+        ),
+        core::Term::EqType(_, ref lhs, ref rhs) => parens_if(
+            Prec::PI < prec,
+            concrete::Term::EqType(
+                Box::new(resugar_term(env, lhs, Prec::APP)),
+                Box::new(resugar_term(env, rhs, Prec::APP)),
+            ),
+        ),
+        core::Term::EqIntro => concrete::Term::EqIntro(ByteSpan::default()),
+        core::Term::EqElim(ref motive, ref proof, ref body) => parens_if(
+            Prec::APP < prec,
+            concrete::Term::EqElim(
+                ByteIndex::default(),
+                Box::new(resugar_term(env, motive, Prec::ATOMIC)),
+                Box::new(resugar_term(env, proof, Prec::ATOMIC)),
+                Box::new(resugar_term(env, body, Prec::ATOMIC)),
+            ),
+        ),
         // TODO: Resugar boolean patterns into if-then-else expressions?
         core::Term::Case(ref head, ref clauses) => concrete::Term::Case(
             ByteSpan::default(),
             Box::new(resugar_term(env, head, Prec::NO_WRAP)),
             clauses
                 .iter()
-                .map(|scope| {
-                    let (pattern, term) = scope.clone().unbind();
-                    let mut env = env.clone();
+                .map(|&(ref pattern, ref body)| {
+                    let mut body_env = env.clone();
                     (
-                        resugar_pattern(&mut env, &pattern, Prec::NO_WRAP),
-                        resugar_term(&env, &term, Prec::NO_WRAP),
+                        resugar_pattern(env, &mut body_env, pattern, Prec::NO_WRAP),
+                        resugar_term(&body_env, body, Prec::NO_WRAP),
                     )
                 })
                 .collect(),
@@ -633,33 +954,66 @@ impl Resugar<concrete::Term> for core::Term {
     }
 }
 
-impl Resugar<concrete::Term> for domain::Value {
-    fn resugar(&self, env: &ResugarEnv) -> concrete::Term {
-        // FIXME: Make this more efficient?
-        resugar_term(env, &core::Term::from(self), Prec::NO_WRAP)
+/// The environment used when reading values back into terms for display
+///
+/// No definitions are unfolded, so the bodies of closures are only evaluated
+/// as far as their local environments allow. Values that were unfolded from
+/// definitions are displayed in their folded forms.
+struct DisplayEnv;
+
+impl Env for DisplayEnv {
+    fn get_import(&self, _: &str) -> Option<&Import> {
+        None
+    }
+
+    fn get_definition(&self, _: &FreeVar<String>) -> Option<&core::RcTerm> {
+        None
+    }
+
+    fn get_meta_solution(&self, _: MetaVar) -> Option<core::RcTerm> {
+        None
+    }
+
+    fn unfold_fix(&self, _: Totality) -> bool {
+        false
+    }
+
+    fn quote_folded(&self) -> bool {
+        true
     }
 }
 
-impl Resugar<concrete::Term> for domain::Neutral {
+impl Resugar<concrete::Term> for domain::RcValue {
     fn resugar(&self, env: &ResugarEnv) -> concrete::Term {
-        // FIXME: Make this more efficient?
-        resugar_term(env, &core::Term::from(self), Prec::NO_WRAP)
+        match nbe::quote(&DisplayEnv, env.size(), self) {
+            Ok(term) => resugar_term(env, &term, Prec::NO_WRAP),
+            Err(_) => concrete::Term::Error(ByteSpan::default()),
+        }
     }
 }
 
-impl Resugar<concrete::Term> for core::RcTerm {
+impl Resugar<concrete::Term> for domain::RcNeutral {
     fn resugar(&self, env: &ResugarEnv) -> concrete::Term {
-        self.inner.resugar(env)
+        match nbe::quote_neutral(&DisplayEnv, env.size(), self) {
+            Ok(term) => resugar_term(env, &term, Prec::NO_WRAP),
+            Err(_) => concrete::Term::Error(ByteSpan::default()),
+        }
     }
 }
 
-impl Resugar<concrete::Term> for domain::RcValue {
-    fn resugar(&self, env: &ResugarEnv) -> concrete::Term {
+impl Resugar<concrete::Pattern> for core::Pattern {
+    fn resugar(&self, env: &ResugarEnv) -> concrete::Pattern {
+        resugar_pattern(env, &mut env.clone(), self, Prec::NO_WRAP)
+    }
+}
+
+impl Resugar<concrete::Pattern> for core::RcPattern {
+    fn resugar(&self, env: &ResugarEnv) -> concrete::Pattern {
         self.inner.resugar(env)
     }
 }
 
-impl Resugar<concrete::Term> for domain::RcNeutral {
+impl Resugar<concrete::Term> for core::RcTerm {
     fn resugar(&self, env: &ResugarEnv) -> concrete::Term {
         self.inner.resugar(env)
     }
@@ -4,22 +4,32 @@ use codespan::{ByteIndex, ByteSpan};
 use pretty::{BoxDoc, Doc};
 use std::fmt;
 
-use crate::syntax::{FloatFormat, IntFormat, PRETTY_FALLBACK_WIDTH, PRETTY_INDENT_WIDTH};
+use pikelet_core::syntax::{Multiplicity, Plicity, Totality};
+
+use crate::syntax::{
+    BindingKind, FloatFormat, IntFormat, PRETTY_FALLBACK_WIDTH, PRETTY_INDENT_WIDTH,
+};
 
 /// A group of lambda parameters that share an annotation
-pub type FunIntroParamGroup = (Vec<(ByteIndex, String)>, Option<Box<Term>>);
+pub type FunIntroParamGroup = (
+    Plicity,
+    Option<Multiplicity>,
+    Vec<(ByteIndex, String)>,
+    Option<Box<Term>>,
+);
 
 /// The parameters to a lambda abstraction
 pub type FunIntroParams = Vec<FunIntroParamGroup>;
 
 /// A group of parameters to a dependent function that share an annotation
-pub type FunTypeParamGroup = (Vec<(ByteIndex, String)>, Term);
+pub type FunTypeParamGroup = (Plicity, Multiplicity, Vec<(ByteIndex, String)>, Term);
 
 /// The parameters to a dependent function type
 pub type FunTypeParams = Vec<FunTypeParamGroup>;
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct RecordTypeField {
+    pub multiplicity: Multiplicity,
     pub label: (ByteIndex, String),
     pub binder: Option<(ByteIndex, String)>,
     pub ann: Term,
@@ -39,6 +49,23 @@ pub enum RecordIntroField {
     },
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordPatternField {
+    Punned {
+        label: (ByteIndex, String),
+    },
+    Explicit {
+        label: (ByteIndex, String),
+        pattern: Pattern,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariantTypeAlt {
+    pub label: (ByteIndex, String),
+    pub ann: Term,
+}
+
 /// Top-level items within a module
 #[derive(Debug, Clone, PartialEq)]
 pub enum Item {
@@ -46,8 +73,12 @@ pub enum Item {
     ///
     /// ```text
     /// foo : some-type
+    /// partial foo : some-type
+    /// instance foo : some-type
     /// ```
     Declaration {
+        totality: Totality,
+        kind: BindingKind,
         name: (ByteIndex, String),
         ann: Term,
     },
@@ -56,8 +87,10 @@ pub enum Item {
     /// ```text
     /// foo = some-body
     /// foo x (y : some-type) = some-body
+    /// instance foo : some-type = some-body
     /// ```
     Definition {
+        kind: BindingKind,
         name: (ByteIndex, String),
         params: FunIntroParams,
         return_ann: Option<Box<Term>>,
@@ -81,6 +114,7 @@ impl Item {
             | Item::Declaration {
                 name: (start, _),
                 ann: ref term,
+                ..
             } => ByteSpan::new(start, term.span().end()),
             Item::Error(span) => span,
         }
@@ -89,20 +123,29 @@ impl Item {
     pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
         match *self {
             Item::Declaration {
+                totality,
+                kind,
                 name: (_, ref name),
                 ref ann,
-                ..
-            } => Doc::as_string(name)
+            } => Doc::nil()
+                .append(match totality {
+                    Totality::Total => Doc::nil(),
+                    Totality::Partial => Doc::text("partial").append(Doc::space()),
+                })
+                .append(pretty_binding_kind(kind))
+                .append(Doc::as_string(name))
                 .append(Doc::space())
                 .append(":")
                 .append(Doc::space())
                 .append(ann.to_doc()),
             Item::Definition {
+                kind,
                 name: (_, ref name),
                 ref params,
                 ref return_ann,
                 ref body,
-            } => Doc::as_string(name)
+            } => pretty_binding_kind(kind)
+                .append(Doc::as_string(name))
                 .append(Doc::space())
                 .append(match params[..] {
                     [] => Doc::nil(),
@@ -195,6 +238,30 @@ pub enum Pattern {
     /// false
     /// ```
     Name(ByteSpan, String, Option<u32>),
+    /// Patterns that match any value, without binding it
+    ///
+    /// ```text
+    /// _
+    /// ```
+    Wildcard(ByteSpan),
+    /// Variant patterns
+    ///
+    /// ```text
+    /// variant l p
+    /// ```
+    VariantIntro(ByteIndex, (ByteIndex, String), Box<Pattern>),
+    /// Record patterns
+    ///
+    /// ```text
+    /// record { x; y = p }
+    /// ```
+    RecordIntro(ByteSpan, Vec<RecordPatternField>),
+    /// Array patterns
+    ///
+    /// ```text
+    /// [p1; p2; p3]
+    /// ```
+    ArrayIntro(ByteSpan, Vec<Pattern>),
     /// Terms that could not be correctly parsed
     ///
     /// This is used for error recovery
@@ -205,9 +272,17 @@ impl Pattern {
     /// Return the span of source code that this pattern originated from
     pub fn span(&self) -> ByteSpan {
         match *self {
-            Pattern::Parens(span, _) | Pattern::Name(span, _, _) | Pattern::Error(span) => span,
+            Pattern::Parens(span, _)
+            | Pattern::Name(span, _, _)
+            | Pattern::Wildcard(span)
+            | Pattern::RecordIntro(span, _)
+            | Pattern::ArrayIntro(span, _)
+            | Pattern::Error(span) => span,
             Pattern::Ann(ref pattern, ref ty) => pattern.span().to(ty.span()),
             Pattern::Literal(ref literal) => literal.span(),
+            Pattern::VariantIntro(start, _, ref pattern) => {
+                ByteSpan::new(start, pattern.span().end())
+            },
         }
     }
 
@@ -223,11 +298,53 @@ impl Pattern {
             Pattern::Name(_, ref name, None) => Doc::text(format!("{}", name)),
             Pattern::Name(_, ref name, Some(shift)) => Doc::text(format!("{}^{}", name, shift)),
             Pattern::Literal(ref literal) => literal.to_doc(),
+            Pattern::VariantIntro(_, (_, ref label), ref pattern) => Doc::nil()
+                .append("variant")
+                .append(Doc::space())
+                .append(Doc::as_string(label))
+                .append(Doc::space())
+                .append(pattern.to_doc()),
+            Pattern::Wildcard(_) => Doc::text("_"),
+            Pattern::RecordIntro(_, ref fields) => Doc::nil()
+                .append("record {")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    fields.iter().map(|field| match field {
+                        RecordPatternField::Punned {
+                            label: (_, ref label),
+                        } => Doc::text(format!("{}", label)),
+                        RecordPatternField::Explicit {
+                            label: (_, ref label),
+                            ref pattern,
+                        } => Doc::nil()
+                            .append(Doc::as_string(label))
+                            .append(Doc::space())
+                            .append("=")
+                            .append(Doc::space())
+                            .append(pattern.to_doc()),
+                    }),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append(Doc::space())
+                .append("}"),
+            Pattern::ArrayIntro(_, ref elems) => Doc::nil()
+                .append("[")
+                .append(Doc::intersperse(
+                    elems.iter().map(Pattern::to_doc),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append("]"),
             Pattern::Error(_) => Doc::text("<error>"),
         }
     }
 }
 
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
 /// Terms
 #[derive(Debug, Clone, PartialEq)]
 pub enum Term {
@@ -249,6 +366,13 @@ pub enum Term {
     /// Type
     /// ```
     Universe(ByteSpan, Option<u32>),
+    /// Universes at a level given by a term
+    ///
+    /// ```text
+    /// Type^l
+    /// Type^(max l1 l2)
+    /// ```
+    UniverseAt(ByteSpan, Box<Term>),
     /// Literals
     Literal(Literal),
     /// Array literals
@@ -277,6 +401,9 @@ pub enum Term {
     /// ```text
     /// (x : t1) -> t2
     /// (x y : t1) -> t2
+    /// (0 x : t1) -> t2
+    /// {x : t1} -> t2
+    /// {{x : t1}} -> t2
     /// ```
     FunType(ByteIndex, FunTypeParams, Box<Term>),
     /// Non-Dependent function type
@@ -293,6 +420,9 @@ pub enum Term {
     /// \x : t1 => t2
     /// \(x : t1) y (z : t2) => t3
     /// \(x y : t1) => t3
+    /// \(0 x : t1) => t2
+    /// \{x} y => t2
+    /// \{{x}} y => t2
     /// ```
     FunIntro(ByteIndex, FunIntroParams, Box<Term>),
     /// Function application
@@ -352,6 +482,54 @@ pub enum Term {
     /// e.l^1
     /// ```
     RecordProj(ByteSpan, Box<Term>, ByteIndex, String, Option<u32>),
+    /// Record extension
+    ///
+    /// ```text
+    /// e with { x = t1, .. }
+    /// ```
+    RecordExtend(ByteSpan, Box<Term>, Vec<RecordIntroField>),
+    /// Record type merging
+    ///
+    /// ```text
+    /// t1 // t2
+    /// ```
+    RecordTypeMerge(Box<Term>, Box<Term>),
+    /// Record update
+    ///
+    /// ```text
+    /// record e { x = t1, .. }
+    /// ```
+    RecordUpdate(ByteSpan, Box<Term>, Vec<RecordIntroField>),
+    /// Variant type
+    ///
+    /// ```text
+    /// Variant { l1 : t1, .. }
+    /// ```
+    VariantType(ByteSpan, Vec<VariantTypeAlt>),
+    /// Variant introduction
+    ///
+    /// ```text
+    /// variant l e
+    /// ```
+    VariantIntro(ByteIndex, (ByteIndex, String), Box<Term>),
+    /// Equality type
+    ///
+    /// ```text
+    /// e1 == e2
+    /// ```
+    EqType(Box<Term>, Box<Term>),
+    /// Equality introduction
+    ///
+    /// ```text
+    /// refl
+    /// ```
+    EqIntro(ByteSpan),
+    /// Equality elimination
+    ///
+    /// ```text
+    /// rewrite e1 e2 e3
+    /// ```
+    EqElim(ByteIndex, Box<Term>, Box<Term>, Box<Term>),
     /// Terms that could not be correctly parsed
     ///
     /// This is used for error recovery
@@ -364,6 +542,7 @@ impl Term {
         match *self {
             Term::Parens(span, ..)
             | Term::Universe(span, ..)
+            | Term::UniverseAt(span, ..)
             | Term::Hole(span)
             | Term::Name(span, ..)
             | Term::Import(span, ..)
@@ -371,16 +550,25 @@ impl Term {
             | Term::RecordType(span, ..)
             | Term::RecordIntro(span, ..)
             | Term::RecordProj(span, ..)
+            | Term::RecordExtend(span, ..)
+            | Term::RecordUpdate(span, ..)
+            | Term::VariantType(span, ..)
+            | Term::EqIntro(span)
             | Term::ArrayIntro(span, ..)
             | Term::Error(span) => span,
             Term::Literal(ref literal) => literal.span(),
             Term::FunType(start, _, ref body)
             | Term::FunIntro(start, _, ref body)
             | Term::Let(start, _, ref body)
+            | Term::VariantIntro(start, _, ref body)
+            | Term::EqElim(start, _, _, ref body)
             | Term::If(start, _, _, ref body) => ByteSpan::new(start, body.span().end()),
             Term::Where(ref expr, _, end) => ByteSpan::new(expr.span().start(), end),
             Term::Ann(ref term, ref ty) => term.span().to(ty.span()),
             Term::FunArrow(ref ann, ref body) => ann.span().to(body.span()),
+            Term::EqType(ref lhs, ref rhs) | Term::RecordTypeMerge(ref lhs, ref rhs) => {
+                lhs.span().to(rhs.span())
+            },
             Term::FunApp(ref head, ref arg) => head.span().to(arg.last().unwrap().span()),
         }
     }
@@ -396,6 +584,7 @@ impl Term {
                 .append(ty.to_doc()),
             Term::Universe(_, None) => Doc::text("Type"),
             Term::Universe(_, Some(level)) => Doc::text(format!("Type^{}", level)),
+            Term::UniverseAt(_, ref level) => Doc::text("Type^").append(level.to_doc()),
             Term::Literal(ref literal) => literal.to_doc(),
             Term::ArrayIntro(_, ref elems) => Doc::nil()
                 .append("[")
@@ -502,6 +691,7 @@ impl Term {
                     fields.iter().map(|field| {
                         Doc::group(
                             Doc::nil()
+                                .append(pretty_multiplicity(Some(field.multiplicity)))
                                 .append(Doc::as_string(&field.label.1))
                                 .append(match field.binder {
                                     Some((_, ref binder)) => Doc::space()
@@ -525,37 +715,7 @@ impl Term {
                 .append("record {")
                 .append(Doc::space())
                 .append(Doc::intersperse(
-                    fields.iter().map(|field| match field {
-                        RecordIntroField::Punned {
-                            label: (_, ref label),
-                            shift,
-                        } => match shift {
-                            None => Doc::text(format!("{}", label)),
-                            Some(shift) => Doc::text(format!("{}^{}", label, shift)),
-                        },
-                        RecordIntroField::Explicit {
-                            label: (_, ref label),
-                            ref params,
-                            ref return_ann,
-                            ref term,
-                        } => Doc::group(
-                            Doc::nil()
-                                .append(Doc::as_string(label))
-                                .append(Doc::space())
-                                .append(match params[..] {
-                                    [] => Doc::nil(),
-                                    _ => pretty_fun_intro_params(params).append(Doc::space()),
-                                })
-                                .append(return_ann.as_ref().map_or(Doc::nil(), |return_ann| {
-                                    Doc::text(":")
-                                        .append(return_ann.to_doc())
-                                        .append(Doc::space())
-                                }))
-                                .append("=")
-                                .append(Doc::space())
-                                .append(term.to_doc()),
-                        ),
-                    }),
+                    fields.iter().map(pretty_record_intro_field),
                     Doc::text(";").append(Doc::space()),
                 ))
                 .nest(PRETTY_INDENT_WIDTH)
@@ -568,6 +728,79 @@ impl Term {
                 .append(expr.to_doc())
                 .append(".")
                 .append(format!("{}^{}", label, shift)),
+            Term::RecordUpdate(_, ref expr, ref fields) => Doc::nil()
+                .append("record")
+                .append(Doc::space())
+                .append(expr.to_doc())
+                .append(Doc::space())
+                .append("{")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    fields.iter().map(pretty_record_intro_field),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .nest(PRETTY_INDENT_WIDTH)
+                .append(Doc::space())
+                .append("}"),
+            Term::RecordExtend(_, ref expr, ref fields) => Doc::nil()
+                .append(expr.to_doc())
+                .append(Doc::space())
+                .append("with {")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    fields.iter().map(pretty_record_intro_field),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .nest(PRETTY_INDENT_WIDTH)
+                .append(Doc::space())
+                .append("}"),
+            Term::RecordTypeMerge(ref lhs, ref rhs) => Doc::nil()
+                .append(lhs.to_doc())
+                .append(Doc::space())
+                .append("//")
+                .append(Doc::space())
+                .append(rhs.to_doc()),
+            Term::VariantType(_, ref alts) if alts.is_empty() => Doc::text("Variant {}"),
+            Term::VariantType(_, ref alts) => Doc::nil()
+                .append("Variant {")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    alts.iter().map(|alt| {
+                        Doc::group(
+                            Doc::nil()
+                                .append(Doc::as_string(&alt.label.1))
+                                .append(Doc::space())
+                                .append(":")
+                                .append(Doc::space())
+                                .append(alt.ann.to_doc()),
+                        )
+                    }),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .nest(PRETTY_INDENT_WIDTH)
+                .append(Doc::space())
+                .append("}"),
+            Term::VariantIntro(_, (_, ref label), ref arg) => Doc::nil()
+                .append("variant")
+                .append(Doc::space())
+                .append(Doc::as_string(label))
+                .append(Doc::space())
+                .append(arg.to_doc()),
+            Term::EqType(ref lhs, ref rhs) => Doc::nil()
+                .append(lhs.to_doc())
+                .append(Doc::space())
+                .append("==")
+                .append(Doc::space())
+                .append(rhs.to_doc()),
+            Term::EqIntro(_) => Doc::text("refl"),
+            Term::EqElim(_, ref motive, ref proof, ref body) => Doc::nil()
+                .append("rewrite")
+                .append(Doc::space())
+                .append(motive.to_doc())
+                .append(Doc::space())
+                .append(proof.to_doc())
+                .append(Doc::space())
+                .append(body.to_doc()),
             Term::Error(_) => Doc::text("<error>"),
         }
     }
@@ -579,42 +812,105 @@ impl fmt::Display for Term {
     }
 }
 
-fn pretty_fun_intro_params(params: &[FunIntroParamGroup]) -> Doc<BoxDoc<()>> {
-    Doc::intersperse(
-        params.iter().map(|&(ref names, ref ann)| match *ann {
-            None if names.len() == 1 => Doc::as_string(&names[0].1),
-            None => unreachable!(), // FIXME - shouldn't be possible in AST
-            Some(ref ann) => Doc::nil()
-                .append("(")
-                .append(Doc::intersperse(
-                    names.iter().map(|name| Doc::as_string(&name.1)),
-                    Doc::space(),
-                ))
+fn pretty_record_intro_field(field: &RecordIntroField) -> Doc<BoxDoc<()>> {
+    match field {
+        RecordIntroField::Punned {
+            label: (_, ref label),
+            shift,
+        } => match shift {
+            None => Doc::text(format!("{}", label)),
+            Some(shift) => Doc::text(format!("{}^{}", label, shift)),
+        },
+        RecordIntroField::Explicit {
+            label: (_, ref label),
+            ref params,
+            ref return_ann,
+            ref term,
+        } => Doc::group(
+            Doc::nil()
+                .append(Doc::as_string(label))
                 .append(Doc::space())
-                .append(":")
+                .append(match params[..] {
+                    [] => Doc::nil(),
+                    _ => pretty_fun_intro_params(params).append(Doc::space()),
+                })
+                .append(return_ann.as_ref().map_or(Doc::nil(), |return_ann| {
+                    Doc::text(":")
+                        .append(return_ann.to_doc())
+                        .append(Doc::space())
+                }))
+                .append("=")
                 .append(Doc::space())
-                .append(ann.to_doc())
-                .append(")"),
-        }),
+                .append(term.to_doc()),
+        ),
+    }
+}
+
+fn pretty_fun_intro_params(params: &[FunIntroParamGroup]) -> Doc<BoxDoc<()>> {
+    Doc::intersperse(
+        params.iter().map(
+            |&(plicity, multiplicity, ref names, ref ann)| match (plicity, ann) {
+                (Plicity::Explicit, &None) if multiplicity.is_none() && names.len() == 1 => {
+                    Doc::as_string(&names[0].1)
+                },
+                (_, &None) => Doc::nil()
+                    .append(plicity.open_delim())
+                    .append(pretty_multiplicity(multiplicity))
+                    .append(Doc::intersperse(
+                        names.iter().map(|name| Doc::as_string(&name.1)),
+                        Doc::space(),
+                    ))
+                    .append(plicity.close_delim()),
+                (_, &Some(ref ann)) => Doc::nil()
+                    .append(plicity.open_delim())
+                    .append(pretty_multiplicity(multiplicity))
+                    .append(Doc::intersperse(
+                        names.iter().map(|name| Doc::as_string(&name.1)),
+                        Doc::space(),
+                    ))
+                    .append(Doc::space())
+                    .append(":")
+                    .append(Doc::space())
+                    .append(ann.to_doc())
+                    .append(plicity.close_delim()),
+            },
+        ),
         Doc::space(),
     )
 }
 
 fn pretty_fun_ty_params(params: &[FunTypeParamGroup]) -> Doc<BoxDoc<()>> {
     Doc::intersperse(
-        params.iter().map(|&(ref names, ref ann)| {
-            Doc::nil()
-                .append("(")
-                .append(Doc::intersperse(
-                    names.iter().map(|name| Doc::as_string(&name.1)),
-                    Doc::space(),
-                ))
-                .append(Doc::space())
-                .append(":")
-                .append(Doc::space())
-                .append(ann.to_doc())
-                .append(")")
-        }),
+        params
+            .iter()
+            .map(|&(plicity, multiplicity, ref names, ref ann)| {
+                Doc::nil()
+                    .append(plicity.open_delim())
+                    .append(pretty_multiplicity(Some(multiplicity)))
+                    .append(Doc::intersperse(
+                        names.iter().map(|name| Doc::as_string(&name.1)),
+                        Doc::space(),
+                    ))
+                    .append(Doc::space())
+                    .append(":")
+                    .append(Doc::space())
+                    .append(ann.to_doc())
+                    .append(plicity.close_delim())
+            }),
         Doc::space(),
     )
 }
+
+fn pretty_binding_kind(kind: BindingKind) -> Doc<'static, BoxDoc<'static, ()>> {
+    match kind {
+        BindingKind::Ordinary => Doc::nil(),
+        BindingKind::Instance => Doc::text("instance").append(Doc::space()),
+    }
+}
+
+fn pretty_multiplicity(multiplicity: Option<Multiplicity>) -> Doc<'static, BoxDoc<'static, ()>> {
+    match multiplicity {
+        None | Some(Multiplicity::Many) => Doc::nil(),
+        Some(multiplicity) => Doc::as_string(multiplicity).append(Doc::space()),
+    }
+}
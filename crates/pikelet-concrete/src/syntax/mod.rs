@@ -73,3 +73,27 @@ impl<N: Clone + PartialEq> BoundPattern<N> for FloatFormat {
     fn visit_binders(&self, _: &mut impl FnMut(&Binder<N>)) {}
     fn visit_mut_binders(&mut self, _: &mut impl FnMut(&mut Binder<N>)) {}
 }
+
+/// Whether a binding can be used to fill in instance arguments
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BindingKind {
+    /// Bindings that are only used when they are referred to by name
+    Ordinary,
+    /// Bindings that are candidates for instance search
+    ///
+    /// ```text
+    /// instance x : t;
+    /// ```
+    Instance,
+}
+
+impl<N: Clone + PartialEq> BoundTerm<N> for BindingKind {
+    fn term_eq(&self, other: &BindingKind) -> bool {
+        self == other
+    }
+
+    fn close_term(&mut self, _: ScopeState, _: &impl OnFreeFn<N>) {}
+    fn open_term(&mut self, _: ScopeState, _: &impl OnBoundFn<N>) {}
+    fn visit_vars(&self, _: &mut impl FnMut(&Var<N>)) {}
+    fn visit_mut_vars(&mut self, _: &mut impl FnMut(&mut Var<N>)) {}
+}
@@ -2,15 +2,15 @@
 //! be elaborated in a type-directed way during type checking and inference
 
 use codespan::ByteSpan;
-use moniker::{Binder, Embed, Nest, Scope, Var};
+use moniker::{Binder, Embed, Nest, Rec, Scope, Var};
 use pretty::{BoxDoc, Doc};
 use std::fmt;
 use std::ops;
 use std::rc::Rc;
 
-use pikelet_core::syntax::{Label, Level, LevelShift};
+use pikelet_core::syntax::{Label, Level, LevelShift, Multiplicity, Plicity, Totality};
 
-use crate::syntax::{FloatFormat, IntFormat, PRETTY_FALLBACK_WIDTH};
+use crate::syntax::{BindingKind, FloatFormat, IntFormat, PRETTY_FALLBACK_WIDTH};
 
 /// Literals
 #[derive(Debug, Clone, PartialEq, PartialOrd, moniker::BoundTerm, moniker::BoundPattern)]
@@ -61,6 +61,14 @@ pub enum Pattern {
     Var(ByteSpan, Embed<Var<String>>, LevelShift),
     /// Literal patterns
     Literal(Literal),
+    /// Patterns that match any value, without binding it
+    Wildcard(ByteSpan),
+    /// Variant patterns
+    VariantIntro(ByteSpan, Label, RcPattern),
+    /// Record patterns
+    RecordIntro(ByteSpan, Vec<(Label, RcPattern)>),
+    /// Array patterns
+    ArrayIntro(ByteSpan, Vec<RcPattern>),
 }
 
 impl Pattern {
@@ -68,7 +76,12 @@ impl Pattern {
     pub fn span(&self) -> ByteSpan {
         match *self {
             Pattern::Ann(ref pattern, Embed(ref ty)) => pattern.span().to(ty.span()),
-            Pattern::Var(span, _, _) | Pattern::Binder(span, _) => span,
+            Pattern::Var(span, _, _)
+            | Pattern::Binder(span, _)
+            | Pattern::Wildcard(span)
+            | Pattern::VariantIntro(span, _, _)
+            | Pattern::RecordIntro(span, _)
+            | Pattern::ArrayIntro(span, _) => span,
             Pattern::Literal(ref literal) => literal.span(),
         }
     }
@@ -81,6 +94,12 @@ impl Pattern {
                 .append(":")
                 .append(Doc::space())
                 .append(ty.to_doc_expr()),
+            Pattern::VariantIntro(_, ref label, ref pattern) => Doc::nil()
+                .append("variant")
+                .append(Doc::space())
+                .append(Doc::as_string(label))
+                .append(Doc::space())
+                .append(pattern.to_doc_atomic()),
             ref pattern => pattern.to_doc_atomic(),
         }
     }
@@ -90,6 +109,30 @@ impl Pattern {
             Pattern::Binder(_, ref binder) => Doc::as_string(binder),
             Pattern::Var(_, Embed(ref var), shift) => Doc::as_string(format!("{}^{}", var, shift)),
             Pattern::Literal(ref literal) => literal.to_doc(),
+            Pattern::Wildcard(_) => Doc::text("_"),
+            Pattern::RecordIntro(_, ref fields) => Doc::nil()
+                .append("record {")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    fields.iter().map(|&(ref label, ref pattern)| {
+                        Doc::nil()
+                            .append(Doc::as_string(label))
+                            .append(Doc::space())
+                            .append("=")
+                            .append(Doc::space())
+                            .append(pattern.to_doc())
+                    }),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append(Doc::space())
+                .append("}"),
+            Pattern::ArrayIntro(_, ref elems) => Doc::nil()
+                .append("[")
+                .append(Doc::intersperse(
+                    elems.iter().map(|elem| elem.to_doc()),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append("]"),
             ref pattern => Doc::text("(").append(pattern.to_doc()).append(")"),
         }
     }
@@ -137,46 +180,78 @@ impl fmt::Display for RcPattern {
 pub enum Term {
     /// A term annotated with a type
     Ann(RcTerm, RcTerm),
-    /// Universes
-    Universe(ByteSpan, Level),
+    /// Universes, with a level that is inferred if it is not given
+    Universe(ByteSpan, Option<Level>),
+    /// Universes at a level given by a term
+    UniverseAt(ByteSpan, RcTerm),
     /// Literals
     Literal(Literal),
     /// A hole
     Hole(ByteSpan),
-    /// A variable
-    Var(ByteSpan, Var<String>, LevelShift),
+    /// A variable, with a shift that is inferred if it is not given
+    Var(ByteSpan, Var<String>, Option<LevelShift>),
     /// An imported definition
     Import(ByteSpan, ByteSpan, String),
     /// Dependent function types
-    FunType(ByteSpan, Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
-    /// Function introductions
-    FunIntro(ByteSpan, Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
+    FunType(
+        ByteSpan,
+        Plicity,
+        Multiplicity,
+        Scope<(Binder<String>, Embed<RcTerm>), RcTerm>,
+    ),
+    /// Function introductions, with a multiplicity that is inferred if it is
+    /// not given
+    FunIntro(
+        ByteSpan,
+        Plicity,
+        Option<Multiplicity>,
+        Scope<(Binder<String>, Embed<RcTerm>), RcTerm>,
+    ),
     /// Function application
     FunApp(RcTerm, RcTerm),
     /// Dependent record types
     RecordType(
         ByteSpan,
-        Scope<Nest<(Label, Binder<String>, Embed<RcTerm>)>, ()>,
+        Scope<Nest<(Label, Multiplicity, Binder<String>, Embed<RcTerm>)>, ()>,
     ),
     /// Record introductions
     RecordIntro(ByteSpan, Vec<(Label, RcTerm)>),
     /// Record field projection
     RecordProj(ByteSpan, RcTerm, ByteSpan, Label, LevelShift),
+    /// Record extension
+    RecordExtend(ByteSpan, RcTerm, Vec<(Label, RcTerm)>),
+    /// Record type merging
+    RecordTypeMerge(ByteSpan, RcTerm, RcTerm),
+    /// Record update
+    RecordUpdate(ByteSpan, RcTerm, Vec<(Label, RcTerm)>),
+    /// Variant types
+    VariantType(ByteSpan, Vec<(Label, RcTerm)>),
+    /// Variant introductions
+    VariantIntro(ByteSpan, Label, RcTerm),
+    /// Equality types
+    EqType(ByteSpan, RcTerm, RcTerm),
+    /// Equality introductions
+    EqIntro(ByteSpan),
+    /// Equality eliminations
+    EqElim(ByteSpan, RcTerm, RcTerm, RcTerm),
     /// Case expressions
     Case(ByteSpan, RcTerm, Vec<Scope<RcPattern, RcTerm>>),
     /// Array literals
     ArrayIntro(ByteSpan, Vec<RcTerm>),
     /// Let bindings
-    Let(
-        ByteSpan,
-        Scope<Nest<(Binder<String>, Embed<RcTerm>)>, RcTerm>,
-    ),
+    ///
+    /// The bindings are in scope in each other, allowing for recursive and
+    /// mutually recursive definitions. Bindings that are marked as partial are
+    /// exempt from termination checking, and bindings that are marked as
+    /// instances are candidates for instance arguments.
+    Let(ByteSpan, Scope<Rec<Vec<LetBinding>>, RcTerm>),
 }
 
 impl Term {
     pub fn span(&self) -> ByteSpan {
         match *self {
             Term::Universe(span, ..)
+            | Term::UniverseAt(span, ..)
             | Term::Hole(span)
             | Term::Var(span, ..)
             | Term::Import(span, ..)
@@ -185,6 +260,14 @@ impl Term {
             | Term::RecordType(span, ..)
             | Term::RecordIntro(span, ..)
             | Term::RecordProj(span, ..)
+            | Term::RecordExtend(span, ..)
+            | Term::RecordUpdate(span, ..)
+            | Term::RecordTypeMerge(span, ..)
+            | Term::VariantType(span, ..)
+            | Term::VariantIntro(span, ..)
+            | Term::EqType(span, ..)
+            | Term::EqIntro(span)
+            | Term::EqElim(span, ..)
             | Term::Case(span, ..)
             | Term::ArrayIntro(span, ..)
             | Term::Let(span, ..) => span,
@@ -212,7 +295,7 @@ impl Term {
                 .append("import")
                 .append(Doc::space())
                 .append(format!("{:?}", name)),
-            Term::FunIntro(_, ref scope) => Doc::nil()
+            Term::FunIntro(_, Plicity::Explicit, None, ref scope) => Doc::nil()
                 .append("\\")
                 .append(Doc::as_string(&scope.unsafe_pattern.0))
                 .append(Doc::space())
@@ -223,6 +306,20 @@ impl Term {
                 .append("=>")
                 .append(Doc::space())
                 .append(scope.unsafe_body.to_doc_expr()),
+            Term::FunIntro(_, plicity, multiplicity, ref scope) => Doc::nil()
+                .append("\\")
+                .append(plicity.open_delim())
+                .append(multiplicity.map_or(Doc::nil(), pretty_multiplicity))
+                .append(Doc::as_string(&scope.unsafe_pattern.0))
+                .append(Doc::space())
+                .append(":")
+                .append(Doc::space())
+                .append((scope.unsafe_pattern.1).0.to_doc())
+                .append(plicity.close_delim())
+                .append(Doc::space())
+                .append("=>")
+                .append(Doc::space())
+                .append(scope.unsafe_body.to_doc_expr()),
             Term::Case(_, ref head, ref clauses) => Doc::nil()
                 .append("case")
                 .append(Doc::space())
@@ -248,9 +345,19 @@ impl Term {
                 .append("let")
                 .append(Doc::space())
                 .append(Doc::intersperse(
-                    scope.unsafe_pattern.unsafe_patterns.iter().map(
-                        |&(ref binder, Embed(ref term))| {
+                    scope.unsafe_pattern.unsafe_pattern.iter().map(
+                        |&(ref binder, Embed(totality), Embed(kind), Embed(ref term))| {
                             Doc::nil()
+                                .append(match totality {
+                                    Totality::Total => Doc::nil(),
+                                    Totality::Partial => Doc::text("partial").append(Doc::space()),
+                                })
+                                .append(match kind {
+                                    BindingKind::Ordinary => Doc::nil(),
+                                    BindingKind::Instance => {
+                                        Doc::text("instance").append(Doc::space())
+                                    },
+                                })
                                 .append(Doc::as_string(binder))
                                 .append(Doc::space())
                                 .append("=")
@@ -270,18 +377,25 @@ impl Term {
 
     fn to_doc_arrow(&self) -> Doc<BoxDoc<()>> {
         match *self {
-            Term::FunType(_, ref scope) => Doc::nil()
-                .append("(")
+            Term::FunType(_, plicity, multiplicity, ref scope) => Doc::nil()
+                .append(plicity.open_delim())
+                .append(pretty_multiplicity(multiplicity))
                 .append(Doc::as_string(&scope.unsafe_pattern.0))
                 .append(Doc::space())
                 .append(":")
                 .append(Doc::space())
                 .append((scope.unsafe_pattern.1).0.to_doc_arrow())
-                .append(")")
+                .append(plicity.close_delim())
                 .append(Doc::space())
                 .append("->")
                 .append(Doc::space())
                 .append(scope.unsafe_body.to_doc_expr()),
+            Term::EqType(_, ref lhs, ref rhs) => Doc::nil()
+                .append(lhs.to_doc_app())
+                .append(Doc::space())
+                .append("==")
+                .append(Doc::space())
+                .append(rhs.to_doc_app()),
             ref term => term.to_doc_app(),
         }
     }
@@ -292,13 +406,29 @@ impl Term {
                 .append(fun.to_doc_atomic())
                 .append(Doc::space())
                 .append(arg.to_doc_atomic()),
+            Term::VariantIntro(_, ref label, ref arg) => Doc::nil()
+                .append("variant")
+                .append(Doc::space())
+                .append(Doc::as_string(label))
+                .append(Doc::space())
+                .append(arg.to_doc_atomic()),
+            Term::EqElim(_, ref motive, ref proof, ref body) => Doc::nil()
+                .append("rewrite")
+                .append(Doc::space())
+                .append(motive.to_doc_atomic())
+                .append(Doc::space())
+                .append(proof.to_doc_atomic())
+                .append(Doc::space())
+                .append(body.to_doc_atomic()),
             ref term => term.to_doc_atomic(),
         }
     }
 
     fn to_doc_atomic(&self) -> Doc<BoxDoc<()>> {
         match *self {
-            Term::Universe(_, level) => Doc::text(format!("Type^{}", level)),
+            Term::Universe(_, None) => Doc::text("Type"),
+            Term::Universe(_, Some(level)) => Doc::text(format!("Type^{}", level)),
+            Term::UniverseAt(_, ref level) => Doc::text("Type^").append(level.to_doc_atomic()),
             Term::ArrayIntro(_, ref elems) => Doc::nil()
                 .append("[")
                 .append(Doc::intersperse(
@@ -306,15 +436,18 @@ impl Term {
                     Doc::text(";").append(Doc::space()),
                 ))
                 .append("]"),
-            Term::Var(_, ref var, ref level) => Doc::text(format!("{}^{}", var, level)),
+            Term::Var(_, ref var, None) => Doc::as_string(var),
+            Term::Var(_, ref var, Some(shift)) => Doc::text(format!("{}^{}", var, shift)),
             Term::Hole(_) => Doc::text("_"),
+            Term::EqIntro(_) => Doc::text("refl"),
             Term::RecordType(_, ref scope) => Doc::nil()
                 .append("Record {")
                 .append(Doc::space())
                 .append(Doc::intersperse(
                     scope.unsafe_pattern.unsafe_patterns.iter().map(
-                        |&(ref label, ref binder, Embed(ref ann))| {
+                        |&(ref label, multiplicity, ref binder, Embed(ref ann))| {
                             Doc::nil()
+                                .append(pretty_multiplicity(multiplicity))
                                 .append(Doc::as_string(label))
                                 .append(Doc::space())
                                 .append("as")
@@ -350,6 +483,66 @@ impl Term {
                 .append(expr.to_doc_atomic())
                 .append(".")
                 .append(format!("{}^{}", label, shift)),
+            Term::RecordExtend(_, ref expr, ref fields) => Doc::nil()
+                .append(expr.to_doc_atomic())
+                .append(Doc::space())
+                .append("with {")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    fields.iter().map(|&(ref label, ref value)| {
+                        Doc::nil()
+                            .append(Doc::as_string(label))
+                            .append(Doc::space())
+                            .append("=")
+                            .append(Doc::space())
+                            .append(value.to_doc())
+                    }),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append(Doc::space())
+                .append("}"),
+            Term::RecordTypeMerge(_, ref lhs, ref rhs) => Doc::nil()
+                .append(lhs.to_doc_atomic())
+                .append(Doc::space())
+                .append("//")
+                .append(Doc::space())
+                .append(rhs.to_doc_atomic()),
+            Term::RecordUpdate(_, ref expr, ref fields) => Doc::nil()
+                .append("record")
+                .append(Doc::space())
+                .append(expr.to_doc_atomic())
+                .append(Doc::space())
+                .append("{")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    fields.iter().map(|&(ref label, ref value)| {
+                        Doc::nil()
+                            .append(Doc::as_string(label))
+                            .append(Doc::space())
+                            .append("=")
+                            .append(Doc::space())
+                            .append(value.to_doc())
+                    }),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append(Doc::space())
+                .append("}"),
+            Term::VariantType(_, ref alts) => Doc::nil()
+                .append("Variant {")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    alts.iter().map(|&(ref label, ref ann)| {
+                        Doc::nil()
+                            .append(Doc::as_string(label))
+                            .append(Doc::space())
+                            .append(":")
+                            .append(Doc::space())
+                            .append(ann.to_doc())
+                    }),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append(Doc::space())
+                .append("}"),
             ref term => Doc::text("(").append(term.to_doc()).append(")"),
         }
     }
@@ -361,6 +554,14 @@ impl fmt::Display for Term {
     }
 }
 
+/// A binding in a let expression
+pub type LetBinding = (
+    Binder<String>,
+    Embed<Totality>,
+    Embed<BindingKind>,
+    Embed<RcTerm>,
+);
+
 /// Reference counted terms
 #[derive(Debug, Clone, PartialEq, moniker::BoundTerm)]
 pub struct RcTerm {
@@ -388,3 +589,10 @@ impl fmt::Display for RcTerm {
         fmt::Display::fmt(&self.inner, f)
     }
 }
+
+fn pretty_multiplicity(multiplicity: Multiplicity) -> Doc<'static, BoxDoc<'static, ()>> {
+    match multiplicity {
+        Multiplicity::Many => Doc::nil(),
+        multiplicity => Doc::as_string(multiplicity).append(Doc::space()),
+    }
+}
@@ -13,7 +13,7 @@ fn record_intro() {
     let expected_ty = r"Record { t : Type; x : String }";
     let given_expr = r#"record { t = String; x = "hello" }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
 }
 
@@ -26,7 +26,7 @@ fn record_intro_field_mismatch_lt() {
     let expected_ty = r"Record { x : String; y : String }";
     let given_expr = r#"record { x = "hello" }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
@@ -38,6 +38,39 @@ fn record_intro_field_mismatch_lt() {
     }
 }
 
+#[test]
+fn record_width() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Record { x : String; z : String }";
+    let given_expr = r#"(record { x = "hello"; y = "hi"; z = "bye" } : Record { x : String; y : String; z : String })"#;
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+}
+
+#[test]
+fn record_width_out_of_order() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let expected_ty = r"Record { y : String; x : String }";
+    let given_expr = r#"(record { x = "hello"; y = "hi"; z = "bye" } : Record { x : String; y : String; z : String })"#;
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::check_term(&context, &raw_term, &expected_ty) {
+        Err(TypeError::Mismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
+}
+
 #[test]
 fn record_intro_field_mismatch_gt() {
     let mut codemap = CodeMap::new();
@@ -47,7 +80,7 @@ fn record_intro_field_mismatch_gt() {
     let expected_ty = r"Record { x : String }";
     let given_expr = r#"record { x = "hello"; y = "hello" }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
@@ -67,7 +100,7 @@ fn record_intro_dependent_record_ty() {
     let expected_ty = r"Record { t : Type; x : t }";
     let given_expr = r#"record { t = String; x = "hello" }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
 }
 
@@ -79,10 +112,55 @@ fn record_intro_dependent_record_ty_propagate_types() {
     let expected_ty = r"Record { t : Type; x : t }";
     let given_expr = r#"record { t = S32; x = 1 }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
 }
 
+#[test]
+fn record_intro_erased_field() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"(0 a : Type) -> a -> Record { 0 t : Type; x : t }";
+    let given_expr = r"\a x => record { t = a; x = x }";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+}
+
+#[test]
+fn record_intro_linear_field() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"(1 x : String) -> Record { 1 a : String; 0 b : String }";
+    let given_expr = r"\x => record { a = x; b = x }";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+}
+
+#[test]
+fn record_intro_linear_param_in_unrestricted_field() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let expected_ty = r"(1 x : String) -> Record { a : String }";
+    let given_expr = r"\x => record { a = x }";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::check_term(&context, &raw_term, &expected_ty) {
+        Err(TypeError::UsageMismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
+}
+
 #[test]
 fn case_expr() {
     let mut codemap = CodeMap::new();
@@ -95,7 +173,7 @@ fn case_expr() {
         greeting => (import "prim/string/append") greeting "!!";
     }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
 }
 
@@ -111,7 +189,7 @@ fn case_expr_bad_literal() {
         1 => "byee";
     }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
@@ -133,7 +211,7 @@ fn case_expr_wildcard() {
         _ => 123;
     }"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
 }
 
@@ -142,11 +220,58 @@ fn case_expr_empty() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
+    let expected_ty = r"Variant {} -> String";
+    let given_expr = r#"\x => case x {}"#;
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+}
+
+#[test]
+fn case_expr_empty_non_exhaustive() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
     let expected_ty = r"String";
     let given_expr = r#"case "helloo" {}"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
-    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::check_term(&context, &raw_term, &expected_ty) {
+        Err(TypeError::NonExhaustivePatterns { ref missing, .. }) => {
+            assert_eq!(missing.to_string(), "_");
+        },
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
+}
+
+#[test]
+fn case_expr_non_exhaustive_literal() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let expected_ty = r"String";
+    let given_expr = r#"case "helloo" {
+        "hi" => "haha";
+        "hello" => "byee";
+    }"#;
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::check_term(&context, &raw_term, &expected_ty) {
+        Err(TypeError::NonExhaustivePatterns { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
 }
 
 #[test]
@@ -157,7 +282,7 @@ fn array_intro_0_string() {
     let expected_ty = r"Array 0 String";
     let given_expr = r#"[]"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
 }
 
@@ -169,7 +294,7 @@ fn array_intro_3_string() {
     let expected_ty = r"Array 3 String";
     let given_expr = r#"["hello"; "hi"; "byee"]"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
 }
 
@@ -182,7 +307,7 @@ fn array_intro_len_mismatch() {
     let expected_ty = r"Array 3 String";
     let given_expr = r#"["hello"; "hi"]"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
@@ -203,7 +328,7 @@ fn array_intro_elem_ty_mismatch() {
     let expected_ty = r"Array 3 String";
     let given_expr = r#"["hello"; "hi"; 4]"#;
 
-    let expected_ty = support::parse_nf_term(&mut codemap, &context, expected_ty);
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
@@ -213,3 +338,149 @@ fn array_intro_elem_ty_mismatch() {
         Ok(term) => panic!("expected error but found: {}", term),
     }
 }
+
+#[test]
+fn hole_solved_from_usage() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"(\(a : Type) (x : a) => x) ? "hello""#;
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+}
+
+#[test]
+fn hole_unsolved() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let expected_ty = r"String";
+    let given_expr = r"?";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::check_term(&context, &raw_term, &expected_ty) {
+        Err(TypeError::UnableToElaborateHole {
+            expected: Some(_), ..
+        }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
+}
+
+#[test]
+fn implicit_fun_intro_inserted() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"{a : Type} -> a -> a";
+    let given_expr = r"\x => x";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+}
+
+#[test]
+fn fun_intro_multiplicity_inherited() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"(0 a : Type) -> a -> a";
+    let given_expr = r"\a x => x";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+}
+
+#[test]
+fn fun_intro_multiplicity_inherited_used() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let expected_ty = r"(0 a : Type) -> Type";
+    let given_expr = r"\a => a";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::check_term(&context, &raw_term, &expected_ty) {
+        Err(TypeError::UsageMismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
+}
+
+#[test]
+fn variant_intro() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Variant { some : String; none : Record {} }";
+    let given_expr = r#"variant some "hello""#;
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+}
+
+#[test]
+fn eq_intro() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"(x : String) -> x == x";
+    let given_expr = r"\x => refl";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    support::parse_check_term(&mut codemap, &context, given_expr, &expected_ty);
+}
+
+#[test]
+fn eq_intro_unequal_terms() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let expected_ty = r#""hello" == "goodbye""#;
+    let given_expr = r"refl";
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::check_term(&context, &raw_term, &expected_ty) {
+        Err(TypeError::UnequalTerms { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
+}
+
+#[test]
+fn variant_intro_no_variant_in_type() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let expected_ty = r"Variant { some : String; none : Record {} }";
+    let given_expr = r#"variant ok "hello""#;
+
+    let expected_ty = support::parse_eval_term(&mut codemap, &context, expected_ty);
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::check_term(&context, &raw_term, &expected_ty) {
+        Err(TypeError::NoVariantInType { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok(term) => panic!("expected error but found: {}", term),
+    }
+}
@@ -2,14 +2,14 @@ use codespan::{ByteSpan, CodeMap, FileName};
 use codespan_reporting::termcolor::{ColorChoice, StandardStream};
 use goldenfile::Mint;
 use moniker::{assert_term_eq, Binder, Embed, FreeVar, Scope, Var};
-use std::io::Write;
 use pretty_assertions::assert_eq;
+use std::io::Write;
 
 use pikelet_concrete::desugar::{Desugar, DesugarEnv, DesugarError};
 use pikelet_concrete::parse;
 use pikelet_concrete::syntax::raw::{RcTerm, Term};
 use pikelet_concrete::syntax::{concrete, raw};
-use pikelet_core::syntax::{Level, LevelShift};
+use pikelet_core::syntax::{Label, Multiplicity, Plicity};
 
 fn golden(filename: &str, literal: &str) {
     let path = "tests/goldenfiles";
@@ -52,15 +52,11 @@ fn parse_desugar_term(env: &DesugarEnv, src: &str) -> raw::RcTerm {
 }
 
 fn var(x: &FreeVar<String>) -> RcTerm {
-    RcTerm::from(Term::Var(
-        ByteSpan::default(),
-        Var::Free(x.clone()),
-        LevelShift(0),
-    ))
+    RcTerm::from(Term::Var(ByteSpan::default(), Var::Free(x.clone()), None))
 }
 
 fn u0() -> RcTerm {
-    RcTerm::from(Term::Universe(ByteSpan::default(), Level(0)))
+    RcTerm::from(Term::Universe(ByteSpan::default(), None))
 }
 
 #[test]
@@ -68,7 +64,7 @@ fn free_var() {
     let env = DesugarEnv::new(im::HashMap::new());
 
     match *parse_desugar_term(&env, r"or-elim").inner {
-        raw::Term::Var(_, Var::Free(ref free_var), LevelShift(0)) => {
+        raw::Term::Var(_, Var::Free(ref free_var), None) => {
             assert_eq!(free_var.pretty_name, Some("or-elim".to_owned()));
         },
         ref term => panic!("unexpected term: {}", term),
@@ -115,11 +111,15 @@ fn fun_intro_ann() {
         parse_desugar_term(&env, r"\x : Type -> Type => x"),
         RcTerm::from(Term::FunIntro(
             ByteSpan::default(),
+            Plicity::Explicit,
+            None,
             Scope::new(
                 (
                     Binder(x.clone()),
                     Embed(RcTerm::from(Term::FunType(
                         ByteSpan::default(),
+                        Plicity::Explicit,
+                        Multiplicity::Many,
                         Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(u0())), u0()),
                     ))),
                 ),
@@ -141,11 +141,15 @@ fn fun_intro() {
         parse_desugar_term(&env, r"\x : (\y => y) => x"),
         RcTerm::from(Term::FunIntro(
             ByteSpan::default(),
+            Plicity::Explicit,
+            None,
             Scope::new(
                 (
                     Binder(x.clone()),
                     Embed(RcTerm::from(Term::FunIntro(
                         ByteSpan::default(),
+                        Plicity::Explicit,
+                        None,
                         Scope::new((Binder(y.clone()), Embed(hole())), var(&y)),
                     )))
                 ),
@@ -166,10 +170,14 @@ fn fun_intro2_ann() {
         parse_desugar_term(&env, r"\(x y : Type) => x"),
         RcTerm::from(Term::FunIntro(
             ByteSpan::default(),
+            Plicity::Explicit,
+            None,
             Scope::new(
                 (Binder(x.clone()), Embed(u0())),
                 RcTerm::from(Term::FunIntro(
                     ByteSpan::default(),
+                    Plicity::Explicit,
+                    None,
                     Scope::new((Binder(y.clone()), Embed(u0())), var(&x)),
                 )),
             ),
@@ -185,6 +193,8 @@ fn arrow() {
         parse_desugar_term(&env, r"Type -> Type"),
         RcTerm::from(Term::FunType(
             ByteSpan::default(),
+            Plicity::Explicit,
+            Multiplicity::Many,
             Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(u0())), u0()),
         )),
     );
@@ -200,11 +210,15 @@ fn fun_ty() {
         parse_desugar_term(&env, r"(x : Type -> Type) -> x"),
         RcTerm::from(Term::FunType(
             ByteSpan::default(),
+            Plicity::Explicit,
+            Multiplicity::Many,
             Scope::new(
                 (
                     Binder(x.clone()),
                     Embed(RcTerm::from(Term::FunType(
                         ByteSpan::default(),
+                        Plicity::Explicit,
+                        Multiplicity::Many,
                         Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(u0())), u0()),
                     ))),
                 ),
@@ -225,10 +239,14 @@ fn fun_ty2() {
         parse_desugar_term(&env, r"(x y : Type) -> x"),
         RcTerm::from(Term::FunType(
             ByteSpan::default(),
+            Plicity::Explicit,
+            Multiplicity::Many,
             Scope::new(
                 (Binder(x.clone()), Embed(u0())),
                 RcTerm::from(Term::FunType(
                     ByteSpan::default(),
+                    Plicity::Explicit,
+                    Multiplicity::Many,
                     Scope::new((Binder(y.clone()), Embed(u0())), var(&x)),
                 )),
             ),
@@ -246,10 +264,14 @@ fn fun_ty_arrow() {
         parse_desugar_term(&env, r"(x : Type) -> x -> x"),
         RcTerm::from(Term::FunType(
             ByteSpan::default(),
+            Plicity::Explicit,
+            Multiplicity::Many,
             Scope::new(
                 (Binder(x.clone()), Embed(u0())),
                 RcTerm::from(Term::FunType(
                     ByteSpan::default(),
+                    Plicity::Explicit,
+                    Multiplicity::Many,
                     Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(var(&x))), var(&x)),
                 )),
             ),
@@ -257,6 +279,168 @@ fn fun_ty_arrow() {
     );
 }
 
+#[test]
+fn fun_ty_implicit() {
+    let env = DesugarEnv::new(im::HashMap::new());
+
+    let a = FreeVar::fresh_named("a");
+    let x = FreeVar::fresh_named("x");
+
+    assert_term_eq!(
+        parse_desugar_term(&env, r"{a : Type} (x : a) -> a"),
+        RcTerm::from(Term::FunType(
+            ByteSpan::default(),
+            Plicity::Implicit,
+            Multiplicity::Many,
+            Scope::new(
+                (Binder(a.clone()), Embed(u0())),
+                RcTerm::from(Term::FunType(
+                    ByteSpan::default(),
+                    Plicity::Explicit,
+                    Multiplicity::Many,
+                    Scope::new((Binder(x.clone()), Embed(var(&a))), var(&a)),
+                )),
+            ),
+        )),
+    );
+}
+
+#[test]
+fn fun_intro_implicit() {
+    let env = DesugarEnv::new(im::HashMap::new());
+
+    let a = FreeVar::fresh_named("a");
+    let x = FreeVar::fresh_named("x");
+
+    assert_term_eq!(
+        parse_desugar_term(&env, r"\{a : Type} (x : a) => x"),
+        RcTerm::from(Term::FunIntro(
+            ByteSpan::default(),
+            Plicity::Implicit,
+            None,
+            Scope::new(
+                (Binder(a.clone()), Embed(u0())),
+                RcTerm::from(Term::FunIntro(
+                    ByteSpan::default(),
+                    Plicity::Explicit,
+                    None,
+                    Scope::new((Binder(x.clone()), Embed(var(&a))), var(&x)),
+                )),
+            ),
+        )),
+    );
+}
+
+#[test]
+fn fun_ty_instance() {
+    let env = DesugarEnv::new(im::HashMap::new());
+
+    let a = FreeVar::fresh_named("a");
+    let x = FreeVar::fresh_named("x");
+
+    assert_term_eq!(
+        parse_desugar_term(&env, r"{a : Type} {{x : a}} -> a"),
+        RcTerm::from(Term::FunType(
+            ByteSpan::default(),
+            Plicity::Implicit,
+            Multiplicity::Many,
+            Scope::new(
+                (Binder(a.clone()), Embed(u0())),
+                RcTerm::from(Term::FunType(
+                    ByteSpan::default(),
+                    Plicity::Instance,
+                    Multiplicity::Many,
+                    Scope::new((Binder(x.clone()), Embed(var(&a))), var(&a)),
+                )),
+            ),
+        )),
+    );
+}
+
+#[test]
+fn variant_case() {
+    let x = FreeVar::fresh_named("x");
+    let y = FreeVar::fresh_named("y");
+    let env = DesugarEnv::new(im::hashmap! {
+        "x".to_owned() => x.clone(),
+    });
+
+    assert_term_eq!(
+        parse_desugar_term(&env, r"case x { variant some y => variant some y }"),
+        RcTerm::from(Term::Case(
+            ByteSpan::default(),
+            var(&x),
+            vec![Scope::new(
+                raw::RcPattern::from(raw::Pattern::VariantIntro(
+                    ByteSpan::default(),
+                    Label("some".to_owned()),
+                    raw::RcPattern::from(raw::Pattern::Binder(
+                        ByteSpan::default(),
+                        Binder(y.clone()),
+                    )),
+                )),
+                RcTerm::from(Term::VariantIntro(
+                    ByteSpan::default(),
+                    Label("some".to_owned()),
+                    var(&y),
+                )),
+            )],
+        )),
+    );
+}
+
+#[test]
+fn record_case() {
+    let x = FreeVar::fresh_named("x");
+    let a = FreeVar::fresh_named("a");
+    let c = FreeVar::fresh_named("c");
+    let env = DesugarEnv::new(im::hashmap! {
+        "x".to_owned() => x.clone(),
+    });
+
+    assert_term_eq!(
+        parse_desugar_term(&env, r"case x { record { a; b = _; c = [c; _] } => a }"),
+        RcTerm::from(Term::Case(
+            ByteSpan::default(),
+            var(&x),
+            vec![Scope::new(
+                raw::RcPattern::from(raw::Pattern::RecordIntro(
+                    ByteSpan::default(),
+                    vec![
+                        (
+                            Label("a".to_owned()),
+                            raw::RcPattern::from(raw::Pattern::Binder(
+                                ByteSpan::default(),
+                                Binder(a.clone()),
+                            )),
+                        ),
+                        (
+                            Label("b".to_owned()),
+                            raw::RcPattern::from(raw::Pattern::Wildcard(ByteSpan::default())),
+                        ),
+                        (
+                            Label("c".to_owned()),
+                            raw::RcPattern::from(raw::Pattern::ArrayIntro(
+                                ByteSpan::default(),
+                                vec![
+                                    raw::RcPattern::from(raw::Pattern::Binder(
+                                        ByteSpan::default(),
+                                        Binder(c.clone()),
+                                    )),
+                                    raw::RcPattern::from(raw::Pattern::Wildcard(
+                                        ByteSpan::default(),
+                                    )),
+                                ],
+                            )),
+                        ),
+                    ],
+                )),
+                var(&a),
+            )],
+        )),
+    );
+}
+
 #[test]
 fn fun_intro_fun_app() {
     let env = DesugarEnv::new(im::HashMap::new());
@@ -268,16 +452,22 @@ fn fun_intro_fun_app() {
         parse_desugar_term(&env, r"\(x : Type -> Type) (y : Type) => x y"),
         RcTerm::from(Term::FunIntro(
             ByteSpan::default(),
+            Plicity::Explicit,
+            None,
             Scope::new(
                 (
                     Binder(x.clone()),
                     Embed(RcTerm::from(Term::FunType(
                         ByteSpan::default(),
+                        Plicity::Explicit,
+                        Multiplicity::Many,
                         Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(u0())), u0()),
                     ))),
                 ),
                 RcTerm::from(Term::FunIntro(
                     ByteSpan::default(),
+                    Plicity::Explicit,
+                    None,
                     Scope::new(
                         (Binder(y.clone()), Embed(u0())),
                         RcTerm::from(Term::FunApp(var(&x), var(&y))),
@@ -299,10 +489,14 @@ fn id() {
         parse_desugar_term(&env, r"\(a : Type) (x : a) => x"),
         RcTerm::from(Term::FunIntro(
             ByteSpan::default(),
+            Plicity::Explicit,
+            None,
             Scope::new(
                 (Binder(a.clone()), Embed(u0())),
                 RcTerm::from(Term::FunIntro(
                     ByteSpan::default(),
+                    Plicity::Explicit,
+                    None,
                     Scope::new((Binder(x.clone()), Embed(var(&a))), var(&x)),
                 )),
             ),
@@ -320,10 +514,14 @@ fn id_ty() {
         parse_desugar_term(&env, r"(a : Type) -> a -> a"),
         RcTerm::from(Term::FunType(
             ByteSpan::default(),
+            Plicity::Explicit,
+            Multiplicity::Many,
             Scope::new(
                 (Binder(a.clone()), Embed(u0())),
                 RcTerm::from(Term::FunType(
                     ByteSpan::default(),
+                    Plicity::Explicit,
+                    Multiplicity::Many,
                     Scope::new((Binder(FreeVar::fresh_unnamed()), Embed(var(&a))), var(&a)),
                 )),
             ),
@@ -0,0 +1,143 @@
+use codespan::CodeMap;
+use pretty_assertions::assert_eq;
+
+use pikelet_concrete::elaborate::Context;
+use pikelet_core::erase::erase;
+use pikelet_core::syntax::core::{RcTerm, Term};
+use pikelet_core::syntax::{Label, Literal, Multiplicity, NameHint, Plicity};
+
+mod support;
+
+#[test]
+fn id() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r"\(0 a : Type) (x : a) => x";
+
+    assert_eq!(
+        erase(&support::parse_infer_term(&mut codemap, &context, given_expr).0),
+        RcTerm::from(Term::FunIntro(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            NameHint::from("x"),
+            RcTerm::from(Term::Erased),
+            RcTerm::from(Term::var(0, 0)),
+        )),
+    );
+}
+
+#[test]
+fn id_unrestricted() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r"\(a : Type) (x : a) => x";
+
+    assert_eq!(
+        erase(&support::parse_infer_term(&mut codemap, &context, given_expr).0),
+        RcTerm::from(Term::FunIntro(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            NameHint::from("a"),
+            RcTerm::from(Term::Erased),
+            RcTerm::from(Term::FunIntro(
+                Plicity::Explicit,
+                Multiplicity::Many,
+                NameHint::from("x"),
+                RcTerm::from(Term::Erased),
+                RcTerm::from(Term::var(0, 0)),
+            )),
+        )),
+    );
+}
+
+#[test]
+fn id_fun_app() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"(\(0 a : Type) (x : a) => x) String "hello""#;
+
+    assert_eq!(
+        erase(&support::parse_infer_term(&mut codemap, &context, given_expr).0),
+        RcTerm::from(Term::FunApp(
+            RcTerm::from(Term::FunIntro(
+                Plicity::Explicit,
+                Multiplicity::Many,
+                NameHint::from("x"),
+                RcTerm::from(Term::Erased),
+                RcTerm::from(Term::var(0, 0)),
+            )),
+            Multiplicity::Many,
+            RcTerm::from(Term::Literal(Literal::String("hello".to_owned()))),
+        )),
+    );
+}
+
+#[test]
+fn record_erased_field() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr =
+        r"\(0 a : Type) (x : a) => (record { t = a; x = x } : Record { 0 t : Type; x : t })";
+
+    assert_eq!(
+        erase(&support::parse_infer_term(&mut codemap, &context, given_expr).0),
+        RcTerm::from(Term::FunIntro(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            NameHint::from("x"),
+            RcTerm::from(Term::Erased),
+            RcTerm::from(Term::RecordIntro(vec![
+                (
+                    Label("t".to_owned()),
+                    Multiplicity::Zero,
+                    RcTerm::from(Term::Erased),
+                ),
+                (
+                    Label("x".to_owned()),
+                    Multiplicity::Many,
+                    RcTerm::from(Term::var(0, 0)),
+                ),
+            ])),
+        )),
+    );
+}
+
+#[test]
+fn compose() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r"\(0 a b c : Type) (f : b -> c) (g : a -> b) (x : a) => f (g x)";
+
+    let fun_intro = |name: &str, body| {
+        RcTerm::from(Term::FunIntro(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            NameHint::from(name),
+            RcTerm::from(Term::Erased),
+            body,
+        ))
+    };
+    let fun_app = |head, arg| RcTerm::from(Term::FunApp(head, Multiplicity::Many, arg));
+
+    assert_eq!(
+        erase(&support::parse_infer_term(&mut codemap, &context, given_expr).0),
+        fun_intro(
+            "f",
+            fun_intro(
+                "g",
+                fun_intro(
+                    "x",
+                    fun_app(
+                        RcTerm::from(Term::var(2, 0)),
+                        fun_app(RcTerm::from(Term::var(1, 0)), RcTerm::from(Term::var(0, 0))),
+                    ),
+                ),
+            ),
+        ),
+    );
+}
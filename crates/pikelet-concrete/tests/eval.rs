@@ -0,0 +1,298 @@
+use codespan::CodeMap;
+use pretty_assertions::assert_eq;
+
+use pikelet_concrete::elaborate::Context;
+use pikelet_core::eval::{self, Machine, RcValue, RuntimeError, Value};
+use pikelet_core::syntax::{Label, Literal};
+
+mod support;
+
+fn string(value: &str) -> RcValue {
+    RcValue::from(Value::Literal(Literal::String(value.to_owned())))
+}
+
+fn u64(value: u64) -> RcValue {
+    RcValue::from(Value::Literal(Literal::U64(value)))
+}
+
+#[test]
+fn literal() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#""hello""#;
+
+    assert_eq!(
+        support::parse_run_term(&mut codemap, &context, given_expr).0,
+        string("hello"),
+    );
+}
+
+#[test]
+fn id_fun_app() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"(\(0 a : Type) (x : a) => x) String "hello""#;
+
+    assert_eq!(
+        support::parse_run_term(&mut codemap, &context, given_expr).0,
+        string("hello"),
+    );
+}
+
+#[test]
+fn id_fun_app_unrestricted() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"(\(a : Type) (x : a) => x) String "hello""#;
+
+    assert_eq!(
+        support::parse_run_term(&mut codemap, &context, given_expr).0,
+        string("hello"),
+    );
+}
+
+// The body of a function should not be evaluated until it is applied
+#[test]
+fn fun_intro() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"\x : U64 => (import "prim/u64/add") x 1"#;
+
+    match *support::parse_run_term(&mut codemap, &context, given_expr).0 {
+        Value::FunIntro(_) => {},
+        ref value => panic!("unexpected value: {:?}", value),
+    }
+}
+
+#[test]
+fn prim_partial_app() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            add1 = (import "prim/u64/add") 1;
+        in
+            add1 41
+    "#;
+
+    assert_eq!(
+        support::parse_run_term(&mut codemap, &context, given_expr).0,
+        u64(42),
+    );
+}
+
+#[test]
+fn prim_partial_app_undefined() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"(import "prim/u32/div") 1"#;
+
+    match *support::parse_run_term(&mut codemap, &context, given_expr).0 {
+        Value::Prim(_, _) => {},
+        ref value => panic!("unexpected value: {:?}", value),
+    }
+}
+
+#[test]
+fn divide_by_zero() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"(import "prim/u32/div") 1 0"#;
+
+    let term = support::parse_infer_term(&mut codemap, &context, given_expr).0;
+
+    assert_eq!(
+        eval::run_term(&context, &term),
+        Err(RuntimeError::new(
+            "primitive `prim/u32/div` is undefined for its arguments",
+        )),
+    );
+}
+
+#[test]
+fn record_proj() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        (record { t = String; x = "hello" } : Record { t : Type; x : t }).x
+    "#;
+
+    assert_eq!(
+        support::parse_run_term(&mut codemap, &context, given_expr).0,
+        string("hello"),
+    );
+}
+
+#[test]
+fn record_intro() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        record { t = String; x = "hello" } : Record { t : Type; x : t }
+    "#;
+
+    assert_eq!(
+        support::parse_run_term(&mut codemap, &context, given_expr).0,
+        RcValue::from(Value::RecordIntro(vec![
+            (Label("t".to_owned()), RcValue::from(Value::Erased)),
+            (Label("x".to_owned()), string("hello")),
+        ])),
+    );
+}
+
+#[test]
+fn case_expr_variant() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        case (variant some "hello" : Variant { some : String; none : Record {} }) {
+            variant some x => x;
+            variant none _ => "nothing";
+        }
+    "#;
+
+    assert_eq!(
+        support::parse_run_term(&mut codemap, &context, given_expr).0,
+        string("hello"),
+    );
+}
+
+#[test]
+fn let_expr_recursive() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            partial fact : U64 -> U64;
+            fact n =
+                if (import "prim/u64/eq") n 0 then 1 else
+                    (import "prim/u64/mul") n (fact ((import "prim/u64/sub") n 1));
+        in
+            fact 5
+    "#;
+
+    let (value, stats) = support::parse_run_term(&mut codemap, &context, given_expr);
+
+    assert_eq!(value, u64(120));
+    assert!(stats.steps > 0);
+    assert!(stats.max_depth > 5);
+}
+
+#[test]
+fn case_expr_variant_recursive() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            List : Type -> Type;
+            List a = Variant {
+                nil : Record {};
+                cons : Record { head : a; tail : List a };
+            };
+
+            sum : List U64 -> U64;
+            sum xs = case xs {
+                variant nil _ => 0;
+                variant cons node => (import "prim/u64/add") node.head (sum node.tail);
+            };
+
+            xs : List U64;
+            xs = variant cons (record {
+                head = 1;
+                tail = variant cons (record { head = 2; tail = variant nil (record {}) })
+            });
+        in
+            sum xs
+    "#;
+
+    assert_eq!(
+        support::parse_run_term(&mut codemap, &context, given_expr).0,
+        u64(3),
+    );
+}
+
+#[test]
+fn let_expr_mutually_recursive() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            partial is-even : U64 -> Bool;
+            partial is-odd : U64 -> Bool;
+
+            is-even n =
+                if (import "prim/u64/eq") n 0 then true else is-odd ((import "prim/u64/sub") n 1);
+            is-odd n =
+                if (import "prim/u64/eq") n 0 then false else is-even ((import "prim/u64/sub") n 1);
+        in
+            is-even 10
+    "#;
+
+    assert_eq!(
+        support::parse_run_term(&mut codemap, &context, given_expr).0,
+        RcValue::from(Value::Literal(Literal::Bool(true))),
+    );
+}
+
+#[test]
+fn depth_limit() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            partial fact : U64 -> U64;
+            fact n =
+                if (import "prim/u64/eq") n 0 then 1 else
+                    (import "prim/u64/mul") n (fact ((import "prim/u64/sub") n 1));
+        in
+            fact 20
+    "#;
+
+    let term = support::parse_infer_term(&mut codemap, &context, given_expr).0;
+    let mut machine = Machine::new(&context).with_depth_limit(10);
+
+    match machine.eval_term(&term) {
+        Err(RuntimeError { .. }) => assert_eq!(machine.stats().max_depth, 11),
+        Ok(value) => panic!("expected error, found {}", value),
+    }
+}
+
+#[test]
+fn default_depth_limit() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            partial sum : U64 -> U64;
+            sum n =
+                if (import "prim/u64/eq") n 0 then 0 else
+                    (import "prim/u64/add") n (sum ((import "prim/u64/sub") n 1));
+        in
+            sum 100000
+    "#;
+
+    let term = support::parse_infer_term(&mut codemap, &context, given_expr).0;
+
+    assert_eq!(
+        eval::run_term(&context, &term),
+        Err(RuntimeError::new(format!(
+            "stack overflow: exceeded the depth limit of {}",
+            eval::DEFAULT_DEPTH_LIMIT,
+        ))),
+    );
+}
@@ -1,24 +1,22 @@
 use codespan::{ByteIndex, ByteSpan, CodeMap};
-use moniker::{assert_term_eq, FreeVar, Var};
+use moniker::{FreeVar, Var};
 use pretty_assertions::assert_eq;
 
 use pikelet_concrete::desugar::{Desugar, DesugarEnv};
-use pikelet_concrete::elaborate::{self, Context, TypeError};
+use pikelet_concrete::elaborate::{self, Context, TypeError, TypeWarning};
 use pikelet_concrete::syntax::{concrete, raw};
 
 mod support;
 
 #[test]
 fn undefined_name() {
-    use pikelet_core::syntax::LevelShift;
-
     let context = Context::default();
 
     let x = FreeVar::fresh_named("x");
     let given_expr = raw::RcTerm::from(raw::Term::Var(
         ByteSpan::default(),
         Var::Free(x.clone()),
-        LevelShift(0),
+        None,
     ));
 
     assert_eq!(
@@ -57,7 +55,7 @@ fn ty() {
     let expected_ty = r"Type^1";
     let given_expr = r"Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -71,7 +69,7 @@ fn ty_levels() {
     let expected_ty = r"Type^1";
     let given_expr = r"Type^0 : Type^1 : Type^2 : Type^3"; //... Type^∞       ...+:｡(ﾉ･ω･)ﾉﾞ
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -85,7 +83,7 @@ fn ann_ty_id() {
     let expected_ty = r"Type -> Type";
     let given_expr = r"(\a => a) : Type -> Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -99,7 +97,7 @@ fn ann_arrow_ty_id() {
     let expected_ty = r"(Type -> Type) -> (Type -> Type)";
     let given_expr = r"(\a => a) : (Type -> Type) -> (Type -> Type)";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -131,7 +129,7 @@ fn fun_app() {
     let expected_ty = r"Type^1";
     let given_expr = r"(\a : Type^1 => a) Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -167,7 +165,7 @@ fn fun_intro() {
     let expected_ty = r"(a : Type) -> Type";
     let given_expr = r"\a : Type => a";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -181,7 +179,7 @@ fn fun_ty() {
     let expected_ty = r"Type^1";
     let given_expr = r"(a : Type) -> a";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -195,7 +193,7 @@ fn id() {
     let expected_ty = r"(a : Type) -> a -> a";
     let given_expr = r"\(a : Type) (x : a) => x";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -209,7 +207,7 @@ fn id_ann() {
     let expected_ty = r"(a : Type) -> a -> a";
     let given_expr = r"(\a (x : a) => x) : (A : Type) -> A -> A";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -225,7 +223,7 @@ fn id_fun_app_ty() {
     let expected_ty = r"Type -> Type";
     let given_expr = r"(\(a : Type^1) (x : a) => x) Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -240,7 +238,7 @@ fn id_fun_app_ty_ty() {
     let expected_ty = r"Type^1";
     let given_expr = r"(\(a : Type^2) (x : a) => x) (Type^1) Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -254,7 +252,7 @@ fn id_fun_app_ty_arr_ty() {
     let expected_ty = r"Type^1";
     let given_expr = r"(\(a : Type^2) (x : a) => x) (Type^1) (Type -> Type)";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -268,7 +266,7 @@ fn id_fun_app_arr_fun_ty() {
     let expected_ty = r"Type -> Type";
     let given_expr = r"(\(a : Type^1) (x : a) => x) (Type -> Type) (\x => x)";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -282,7 +280,7 @@ fn apply() {
     let expected_ty = r"(a b : Type) -> (a -> b) -> a -> b";
     let given_expr = r"\(a b : Type) (f : a -> b) (x : a) => f x";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -296,7 +294,7 @@ fn const_() {
     let expected_ty = r"(a b : Type) -> a -> b -> a";
     let given_expr = r"\(a b : Type) (x : a) (y : b) => x";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -310,7 +308,7 @@ fn const_flipped() {
     let expected_ty = r"(a b : Type) -> a -> b -> b";
     let given_expr = r"\(a b : Type) (x : a) (y : b) => y";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -324,7 +322,7 @@ fn flip() {
     let expected_ty = r"(a b c : Type) -> (a -> b -> c) -> (b -> a -> c)";
     let given_expr = r"\(a b c : Type) (f : a -> b -> c) (y : b) (x : a) => f x y";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
@@ -338,561 +336,2022 @@ fn compose() {
     let expected_ty = r"(a b c : Type) -> (b -> c) -> (a -> b) -> (a -> c)";
     let given_expr = r"\(a b c : Type) (f : b -> c) (g : a -> b) (x : a) => f (g x)";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn let_expr_1() {
+fn compose_erased() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
-    let expected_ty = r"String";
-    let given_expr = r#"
-        let x = "helloo";
-        in
-            x
-    "#;
+    let expected_ty = r"(0 a b c : Type) -> (b -> c) -> (a -> b) -> (a -> c)";
+    let given_expr = r"\(0 a b c : Type) (f : b -> c) (g : a -> b) (x : a) => f (g x)";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn let_expr_2() {
+fn fun_intro_linear() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
-    let expected_ty = r"String";
-    let given_expr = r#"
-        let x = "helloo";
-            y = x;
-        in
-            x
-    "#;
+    let expected_ty = r"(1 x : String) -> String";
+    let given_expr = r"\(1 x : String) => x";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn let_shift_universes() {
+fn fun_intro_erased_param_used() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
 
-    let given_expr = r#"
-        let
-            id : (a : Type) -> a -> a;
-            id a x = x;
-
-            test1 = id String "hello";
-            test2 = id S32 1;
-            test3 = id^1 Type String;
-            test4 = id^2 Type String;
-            test5 = id^2 Type^1 String;
-            test6 = id^2 Type^1 Type;
+    let given_expr = r"\(0 a : Type) => a";
 
-            id1 : (a : Type^1) -> a -> a = id^1;
-            id2 : (a : Type^2) -> a -> a = id^2;
-            id11 : (a : Type^2) -> a -> a = id1^1;
-            id22 : (a : Type^4) -> a -> a = id2^2;
-        in
-            record {}
-    "#;
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
 
-    support::parse_infer_term(&mut codemap, &context, given_expr);
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::UsageMismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
 }
 
 #[test]
-fn let_shift_universes_id_self_application() {
+fn fun_intro_linear_param_used_twice() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
 
-    // Here is a curious example from the Idris docs:
-    // http://docs.idris-lang.org/en/v1.3.0/tutorial/miscellany.html#cumulativity
-    //
-    // ```idris
-    // myid : (a : Type) -> a -> a
-    // myid _ x = x
-    //
-    // idid : (a : Type) -> a -> a
-    // idid = myid _ myid
-    // ```
-    //
-    // This would cause a cycle in the universe hierarchy in Idris, but is
-    // perfectly ok when implemented using explicit universe shifting.
-
-    let given_expr = r#"
-        let
-            id : (a : Type) -> a -> a;
-            id a x = x;
+    let given_expr = r"\(1 x : String) => record { a = x; b = x }";
 
-            id-id : (a : Type) -> a -> a;
-            id-id = id^1 ((a : Type) -> a -> a) id;
-        in
-            record {}
-    "#;
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
 
-    support::parse_infer_term(&mut codemap, &context, given_expr);
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::UsageMismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
 }
 
 #[test]
-fn let_shift_universes_literals() {
+fn fun_intro_param_solved() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
-    let given_expr = r#"
-        let
-            id : (a : Type) -> a -> a;
-            id a x = x;
-
-            test2 = "hello" : id^1 Type String;
-        in
-            record {}
-    "#;
+    let expected_ty = r"(a : Type) -> (a -> a) -> a -> a";
+    let given_expr = r"\(a : Type) (f : a -> a) x => f x";
 
-    support::parse_infer_term(&mut codemap, &context, given_expr);
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
 }
 
 #[test]
-fn let_shift_universes_literals_bad() {
+fn fun_intro_param_unsolved() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
     let desugar_env = DesugarEnv::new(context.mappings());
 
-    let given_expr = r#"
-        let
-            id : (a : Type) -> a -> a;
-            id a x = x;
-
-            test2 = "hello" : id^2 Type^1 String^1;
-        in
-            record {}
-    "#;
+    let given_expr = r"\x => x";
 
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
 
     match elaborate::infer_term(&context, &raw_term) {
-        Ok(_) => panic!("expected error"),
-        Err(TypeError::LiteralMismatch { .. }) => {},
-        Err(err) => panic!("unexpected error: {}", err),
+        Err(TypeError::FunctionParamNeedsAnnotation { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
     }
 }
 
 #[test]
-fn let_shift_universes_too_little() {
+fn id_fun_app_hole() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
-    let desugar_env = DesugarEnv::new(context.mappings());
 
-    let given_expr = r#"
-        let
-            id : (a : Type) -> a -> a;
-            id a x = x;
+    let expected_ty = r"String";
+    let given_expr = r#"(\(a : Type) (x : a) => x) ? "hello""#;
 
-            test1 = id^1 Type^1 Type;
-        in
-            record {}
-    "#;
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn hole_unsolved() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r"?";
 
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
 
     match elaborate::infer_term(&context, &raw_term) {
-        Ok(_) => panic!("expected error"),
-        Err(TypeError::Mismatch { .. }) => {},
-        Err(err) => panic!("unexpected error: {}", err),
+        Err(TypeError::UnableToElaborateHole { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
     }
 }
 
 #[test]
-fn let_shift_universes_too_much() {
+fn implicit_id_fun_app() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
-    let given_expr = r#"
-        let
-            id : (a : Type) -> a -> a;
-            id a x = x;
+    let expected_ty = r"String";
+    let given_expr = r#"(\{a : Type} (x : a) => x) "hello""#;
 
-            test1 = id^2 Type String;
-        in
-            record {}
-    "#;
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
 
-    support::parse_infer_term(&mut codemap, &context, given_expr);
+#[test]
+fn implicit_arg_unsolved() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r"(\{a : Type} (x : S32) => x) 1";
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::UnableToInferImplicitArg { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
 }
 
 #[test]
-fn case_expr() {
+fn let_expr_1() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
     let expected_ty = r"String";
-    let given_expr = r#"case "helloo" {
-        "hi" => "haha";
-        "hello" => "byee";
-        greeting => (import "prim/string/append") greeting "!!";
-    }"#;
+    let given_expr = r#"
+        let x = "helloo";
+        in
+            x
+    "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn case_expr_bool() {
+fn let_expr_2() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
     let expected_ty = r"String";
-    let given_expr = r#"case true {
-        true => "hello";
-        false => "hi";
-    }"#;
+    let given_expr = r#"
+        let x = "helloo";
+            y = x;
+        in
+            x
+    "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn case_expr_bool_bad() {
+fn let_mismatch_folded() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
     let desugar_env = DesugarEnv::new(context.mappings());
 
-    let given_expr = r#"case "hello" {
-        true => "hello";
-        false => "hi";
-    }"#;
+    let given_expr = r#"
+        let Greeting = Record { message : String };
+        in
+            Type : Greeting
+    "#;
 
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
 
     match elaborate::infer_term(&context, &raw_term) {
-        Err(TypeError::Mismatch { .. }) => {},
+        Err(TypeError::Mismatch { ref expected, .. }) => assert_eq!(
+            **expected,
+            concrete::Term::Name(ByteSpan::default(), "Greeting".to_owned(), None),
+        ),
         Err(err) => panic!("unexpected error: {:?}", err),
         Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
     }
 }
 
 #[test]
-fn case_expr_wildcard() {
+fn let_mismatch_folded_app() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
 
-    let expected_ty = r"String";
-    let given_expr = r#"case "helloo" {
-        test => test;
-    }"#;
+    let given_expr = r#"
+        let Pair (a : Type) = Record { fst : a; snd : a };
+        in
+            Type : Pair String
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::Mismatch { ref expected, .. }) => assert_eq!(
+            **expected,
+            concrete::Term::FunApp(
+                Box::new(concrete::Term::Name(
+                    ByteSpan::default(),
+                    "Pair".to_owned(),
+                    None,
+                )),
+                vec![concrete::Term::Name(
+                    ByteSpan::default(),
+                    "String".to_owned(),
+                    None,
+                )],
+            ),
+        ),
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn let_recursive() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"U64";
+    let given_expr = r#"
+        let
+            partial sum : U64 -> U64;
+            sum n = if (import "prim/u64/eq") n 0 then 0 else
+                (import "prim/u64/add") n (sum ((import "prim/u64/sub") n 1));
+        in
+            sum 10
+    "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn case_expr_empty() {
+fn let_recursive_undeclared() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
     let desugar_env = DesugarEnv::new(context.mappings());
 
-    let given_expr = r#"case "helloo" {}"#;
+    let given_expr = r#"
+        let
+            loop (n : U64) : U64 = loop n;
+        in
+            record {}
+    "#;
 
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
 
     match elaborate::infer_term(&context, &raw_term) {
-        Err(TypeError::AmbiguousEmptyCase { .. }) => {},
-        other => panic!("unexpected result: {:#?}", other),
+        Ok(_) => panic!("expected error"),
+        Err(TypeError::UndeclaredRecursiveItem { .. }) => {},
+        Err(err) => panic!("unexpected error: {}", err),
     }
 }
 
-mod church_encodings {
-    use super::*;
+#[test]
+fn let_recursive_non_terminating() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
 
-    #[test]
-    fn void() {
-        let mut codemap = CodeMap::new();
-        let context = Context::default();
+    let given_expr = r#"
+        let
+            loop : U64 -> U64;
+            loop n = loop n;
+        in
+            record {}
+    "#;
 
-        let given_expr = r"
-        record {
-            void; not;
-        } where {
-            ||| Logical absurdity
-            void : Type^1;
-            void = (a : Type) -> a;
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Ok(_) => panic!("expected error"),
+        Err(TypeError::NonTerminatingCall { .. }) => {},
+        Err(err) => panic!("unexpected error: {}", err),
+    }
+}
 
+#[test]
+fn let_recursive_mutual_non_terminating() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
 
-            ||| Logical negation
-            not : Type -> Type^1;
-            not a = a -> void;
-        }
-    ";
+    let given_expr = r#"
+        let
+            ping : U64 -> U64;
+            pong : U64 -> U64;
 
-        support::parse_infer_term(&mut codemap, &context, given_expr);
+            ping n = pong n;
+            pong n = ping n;
+        in
+            record {}
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Ok(_) => panic!("expected error"),
+        Err(TypeError::NonTerminatingCall { .. }) => {},
+        Err(err) => panic!("unexpected error: {}", err),
     }
+}
 
-    #[test]
-    fn unit() {
-        let mut codemap = CodeMap::new();
-        let context = Context::default();
+#[test]
+fn let_recursive_partial_in_type() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
 
-        let given_expr = r"
-        record {
-            unit; unit-intro; unit-elim;
-        } where {
-            unit : Type^1;
-            unit = (a : Type) -> a -> a;
+    let given_expr = r#"
+        let
+            partial loop : U64 -> Type;
+            loop n = loop n;
 
-            unit-intro : unit;
-            unit-intro a x = x;
+            test : loop 0 -> loop 0;
+            test x = x;
+        in
+            record {}
+    "#;
 
-            unit-elim : (a : Type) -> unit -> a -> a;
-            unit-elim a f x = f a x;
-        }
-    ";
+    support::parse_infer_term(&mut codemap, &context, given_expr);
+}
 
-        support::parse_infer_term(&mut codemap, &context, given_expr);
+#[test]
+fn let_recursive_structural() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            List : Type -> Type;
+            List a = Variant {
+                nil : Record {};
+                cons : Record { head : a; tail : List a };
+            };
+
+            sum : List U64 -> U64;
+            sum xs = case xs {
+                variant nil _ => 0;
+                variant cons node => (import "prim/u64/add") node.head (sum node.tail);
+            };
+
+            xs : List U64;
+            xs = variant cons (record {
+                head = 1;
+                tail = variant cons (record { head = 2; tail = variant nil (record {}) })
+            });
+
+            test : sum xs == 3;
+            test = refl;
+        in
+            record {}
+    "#;
+
+    support::parse_infer_term(&mut codemap, &context, given_expr);
+}
+
+#[test]
+fn let_recursive_structural_record_pattern() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"U64";
+    let given_expr = r#"
+        let
+            List : Type -> Type;
+            List a = Variant {
+                nil : Record {};
+                cons : Record { head : a; tail : List a };
+            };
+
+            sum : List U64 -> U64;
+            sum xs = case xs {
+                variant nil _ => 0;
+                variant cons (record { head; tail }) => (import "prim/u64/add") head (sum tail);
+            };
+        in
+            sum (variant cons (record { head = 1; tail = variant nil (record {}) }) : List U64)
+    "#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn let_recursive_structural_mutual() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Bool";
+    let given_expr = r#"
+        let
+            List : Type -> Type;
+            List a = Variant {
+                nil : Record {};
+                cons : Record { head : a; tail : List a };
+            };
+
+            is-even-length : List U64 -> Bool;
+            is-odd-length : List U64 -> Bool;
+
+            is-even-length xs = case xs {
+                variant nil _ => true;
+                variant cons node => is-odd-length node.tail;
+            };
+            is-odd-length xs = case xs {
+                variant nil _ => false;
+                variant cons node => is-even-length node.tail;
+            };
+
+            xs : List U64;
+            xs = variant cons (record { head = 1; tail = variant nil (record {}) });
+        in
+            is-odd-length xs
+    "#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn let_recursive_structural_non_decreasing() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"
+        let
+            List : Type -> Type;
+            List a = Variant {
+                nil : Record {};
+                cons : Record { head : a; tail : List a };
+            };
+
+            sum : List U64 -> U64;
+            sum xs = case xs {
+                variant nil _ => 0;
+                variant cons node => sum xs;
+            };
+        in
+            record {}
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Ok(_) => panic!("expected error"),
+        Err(TypeError::NonTerminatingCall { .. }) => {},
+        Err(err) => panic!("unexpected error: {}", err),
     }
+}
 
-    #[test]
-    fn and() {
-        let mut codemap = CodeMap::new();
-        let context = Context::default();
+#[test]
+fn let_shift_universes() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
 
-        let given_expr = r"
+    let given_expr = r#"
+        let
+            id : (a : Type) -> a -> a;
+            id a x = x;
+
+            test1 = id String "hello";
+            test2 = id S32 1;
+            test3 = id^1 Type String;
+            test4 = id^2 Type String;
+            test5 = id^2 Type^1 String;
+            test6 = id^2 Type^1 Type;
+
+            id1 : (a : Type^1) -> a -> a = id^1;
+            id2 : (a : Type^2) -> a -> a = id^2;
+            id11 : (a : Type^2) -> a -> a = id1^1;
+            id22 : (a : Type^4) -> a -> a = id2^2;
+        in
+            record {}
+    "#;
+
+    support::parse_infer_term(&mut codemap, &context, given_expr);
+}
+
+#[test]
+fn let_implicit_id() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Record { test1 : String; test2 : String }";
+    let given_expr = r#"
+        let
+            id : {a : Type} -> a -> a;
+            id x = x;
+
+            const : {a b : Type} -> a -> b -> a;
+            const x y = x;
+        in
             record {
-                and; and-elim-left; and-elim-right;
-            } where {
-                ||| Logical conjunction (Church encoded)
-                |||
-                ||| You could also interpret this as a product type
-                and : Type -> Type -> Type^1;
-                and p q = (c : Type) -> (p -> q -> c) -> c;
+                test1 = id "hello";
+                test2 = const (id "hello") 'a';
+            }
+    "#;
 
-                ||| Introduce a logical conjunction between two types
-                and-intro : (p q : Type) -> p -> q -> and p q;
-                and-intro p q x y c f = f x y;
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn let_instance_arg() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Record { test1 : Bool; test2 : Bool }";
+    let given_expr = r#"
+        let
+            Eq (a : Type) = Record { eq : a -> a -> Bool };
+
+            eq : {a : Type} {{EQ : Eq a}} -> a -> a -> Bool;
+            eq {{EQ}} = EQ.eq;
+
+            instance Eq-String : Eq String = record { eq = \x y => true };
+            instance Eq-Char : Eq Char;
+            Eq-Char = record { eq = \x y => false };
+
+            self-eq : {a : Type} {{EQ : Eq a}} -> a -> Bool;
+            self-eq {{EQ}} x = eq x x;
+        in
+            record {
+                test1 = eq "hello" "world";
+                test2 = self-eq 'a';
+            }
+    "#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn let_instance_arg_literals() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"U32";
+    let given_expr = r#"
+        let
+            Num (a : Type) = Record { add : a -> a -> a };
+
+            add : {a : Type} {{N : Num a}} -> a -> a -> a;
+            add {{N}} = N.add;
+
+            instance Num-U32 : Num U32 = record { add = \x y => x };
+            instance Num-S32 : Num S32 = record { add = \x y => y };
+        in
+            add 1 2 : U32
+    "#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn let_instance_arg_ambiguous() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"
+        let
+            Num (a : Type) = Record { add : a -> a -> a };
+
+            add : {a : Type} {{N : Num a}} -> a -> a -> a;
+            add {{N}} = N.add;
+
+            instance Num-U32 : Num U32 = record { add = \x y => x };
+            instance Num-S32 : Num S32 = record { add = \x y => y };
+        in
+            add 1 2
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::AmbiguousInstance { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn let_instance_arg_not_found() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"
+        let
+            Eq (a : Type) = Record { eq : a -> a -> Bool };
+
+            eq : {a : Type} {{EQ : Eq a}} -> a -> a -> Bool;
+            eq {{EQ}} = EQ.eq;
+
+            instance Eq-String : Eq String = record { eq = \x y => true };
+        in
+            eq 'a' 'b'
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::NoInstanceFound { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn let_instance_arg_ignores_ordinary_bindings() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Bool";
+    let given_expr = r#"
+        let
+            Eq (a : Type) = Record { eq : a -> a -> Bool };
+
+            eq : {a : Type} {{EQ : Eq a}} -> a -> a -> Bool;
+            eq {{EQ}} = EQ.eq;
+
+            instance Eq-String : Eq String = record { eq = \x y => true };
+            never-eq : Eq String = record { eq = \x y => false };
+        in
+            eq "hello" "world"
+    "#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn let_instance_arg_not_found_ordinary_binding() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"
+        let
+            offset : {{n : U32}} -> U32 -> U32;
+            offset {{n}} x = x;
+
+            ten : U32 = 10;
+        in
+            offset 1
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::NoInstanceFound { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn let_shift_universes_id_self_application() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    // Here is a curious example from the Idris docs:
+    // http://docs.idris-lang.org/en/v1.3.0/tutorial/miscellany.html#cumulativity
+    //
+    // ```idris
+    // myid : (a : Type) -> a -> a
+    // myid _ x = x
+    //
+    // idid : (a : Type) -> a -> a
+    // idid = myid _ myid
+    // ```
+    //
+    // This would cause a cycle in the universe hierarchy in Idris, but is
+    // perfectly ok when implemented using explicit universe shifting.
+
+    let given_expr = r#"
+        let
+            id : (a : Type) -> a -> a;
+            id a x = x;
+
+            id-id : (a : Type) -> a -> a;
+            id-id = id^1 ((a : Type) -> a -> a) id;
+        in
+            record {}
+    "#;
+
+    support::parse_infer_term(&mut codemap, &context, given_expr);
+}
+
+#[test]
+fn let_shift_universes_literals() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            id : (a : Type) -> a -> a;
+            id a x = x;
+
+            test2 = "hello" : id^1 Type String;
+        in
+            record {}
+    "#;
+
+    support::parse_infer_term(&mut codemap, &context, given_expr);
+}
+
+#[test]
+fn let_shift_universes_literals_bad() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"
+        let
+            id : (a : Type) -> a -> a;
+            id a x = x;
+
+            test2 = "hello" : id^2 Type^1 String^1;
+        in
+            record {}
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Ok(_) => panic!("expected error"),
+        Err(TypeError::LiteralMismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn let_shift_universes_too_little() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"
+        let
+            id : (a : Type^0) -> a -> a;
+            id a x = x;
+
+            test1 = id^1 Type^1 Type;
+        in
+            record {}
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Ok(_) => panic!("expected error"),
+        Err(TypeError::Mismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn let_shift_universes_too_much() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            id : (a : Type) -> a -> a;
+            id a x = x;
+
+            test1 = id^2 Type String;
+        in
+            record {}
+    "#;
+
+    support::parse_infer_term(&mut codemap, &context, given_expr);
+}
+
+#[test]
+fn let_shift_universes_inferred() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            id : (a : Type^0) -> a -> a;
+            id a x = x;
+
+            id1 : (a : Type^1) -> a -> a;
+            id1 = id;
+
+            test1 = id1 Type String;
+        in
+            record {}
+    "#;
+
+    support::parse_infer_term(&mut codemap, &context, given_expr);
+}
+
+#[test]
+fn let_universe_level_inferred() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Type^2";
+    let given_expr = r#"
+        let
+            Box = Record { t : Type };
+            box : Box = record { t = Type };
+        in
+            Box
+    "#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn let_universe_level_inconsistent() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"
+        let
+            Box = Record { t : Type };
+            box : Box = record { t = Box };
+        in
+            record {}
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Ok(_) => panic!("expected error"),
+        Err(TypeError::InconsistentLevels { .. }) => {},
+        Err(err) => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn level_ty() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Type";
+    let given_expr = r"Level";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn universe_level_var() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"(l : Level) -> Type^(l^1)";
+    let given_expr = r"\(l : Level) => Type^l";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn let_level_polymorphic_id() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            id : (l : Level) -> (a : Type^l) -> a -> a;
+            id l a x = x;
+
+            test1 = id 0 String "hello";
+            test2 = id 1 Type String;
+            test3 = id 2 Type^1 Type;
+        in
+            record {}
+    "#;
+
+    support::parse_infer_term(&mut codemap, &context, given_expr);
+}
+
+#[test]
+fn let_level_polymorphic_records() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            Category : (l : Level) -> Type^(l^1);
+            Category l = Record {
+                Object : Type^l;
+                Arrow : Object -> Object -> Type^l;
+            };
+
+            Pair : (l1 l2 : Level) -> Type^l1 -> Type^l2 -> Type^(max l1 l2);
+            Pair l1 l2 A B = Record { fst : A; snd : B };
+
+            test1 : Category 1 = record {
+                Object = Type;
+                Arrow a b = a -> b;
+            };
+            test2 : Pair 0 1 String Type = record { fst = "hello"; snd = String };
+        in
+            record {}
+    "#;
+
+    support::parse_infer_term(&mut codemap, &context, given_expr);
+}
+
+#[test]
+fn let_level_polymorphic_too_little() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"
+        let
+            id : (l : Level) -> (a : Type^l) -> a -> a;
+            id l a x = x;
+
+            test1 = id 0 Type String;
+        in
+            record {}
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Ok(_) => panic!("expected error"),
+        Err(TypeError::Mismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn case_expr() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"case "helloo" {
+        "hi" => "haha";
+        "hello" => "byee";
+        greeting => (import "prim/string/append") greeting "!!";
+    }"#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn case_expr_bool() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"case true {
+        true => "hello";
+        false => "hi";
+    }"#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn case_expr_bool_non_exhaustive() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"case true {
+        true => "hello";
+    }"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::NonExhaustivePatterns { ref missing, .. }) => {
+            assert_eq!(missing.to_string(), "false");
+        },
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn case_expr_bool_bad() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"case "hello" {
+        true => "hello";
+        false => "hi";
+    }"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::Mismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn case_expr_wildcard() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"case "helloo" {
+        test => test;
+    }"#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn case_expr_empty() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"case "helloo" {}"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::AmbiguousEmptyCase { .. }) => {},
+        other => panic!("unexpected result: {:#?}", other),
+    }
+}
+
+mod church_encodings {
+    use super::*;
+
+    #[test]
+    fn void() {
+        let mut codemap = CodeMap::new();
+        let context = Context::default();
+
+        let given_expr = r"
+        record {
+            void; not;
+        } where {
+            ||| Logical absurdity
+            void : Type^1;
+            void = (a : Type) -> a;
+
+
+            ||| Logical negation
+            not : Type -> Type^1;
+            not a = a -> void;
+        }
+    ";
+
+        support::parse_infer_term(&mut codemap, &context, given_expr);
+    }
+
+    #[test]
+    fn unit() {
+        let mut codemap = CodeMap::new();
+        let context = Context::default();
+
+        let given_expr = r"
+        record {
+            unit; unit-intro; unit-elim;
+        } where {
+            unit : Type^1;
+            unit = (a : Type) -> a -> a;
+
+            unit-intro : unit;
+            unit-intro a x = x;
+
+            unit-elim : (a : Type) -> unit -> a -> a;
+            unit-elim a f x = f a x;
+        }
+    ";
+
+        support::parse_infer_term(&mut codemap, &context, given_expr);
+    }
+
+    #[test]
+    fn and() {
+        let mut codemap = CodeMap::new();
+        let context = Context::default();
+
+        let given_expr = r"
+            record {
+                and; and-elim-left; and-elim-right;
+            } where {
+                ||| Logical conjunction (Church encoded)
+                |||
+                ||| You could also interpret this as a product type
+                and : Type -> Type -> Type^1;
+                and p q = (c : Type) -> (p -> q -> c) -> c;
+
+                ||| Introduce a logical conjunction between two types
+                and-intro : (p q : Type) -> p -> q -> and p q;
+                and-intro p q x y c f = f x y;
+
+                and-elim-left : (p q : Type) -> and p q -> p;
+                and-elim-left p q (pq : and p q) = pq p (\x y => x);
+
+                and-elim-right : (p q : Type) -> and p q -> q;
+                and-elim-right p q (pq : and p q) = pq q (\x y => y);
+            }
+        ";
+
+        support::parse_infer_term(&mut codemap, &context, given_expr);
+    }
+
+    #[test]
+    fn or() {
+        let mut codemap = CodeMap::new();
+        let context = Context::default();
+
+        let given_expr = r"
+            record {
+                or; or-intro-left; or-intro-right;
+            } where {
+                ||| Logical disjunction (Church encoded)
+                |||
+                ||| You could also interpret this as a sum type
+                or : Type -> Type -> Type^1;
+                or p q = (c : Type) -> (p -> c) -> (q -> c) -> c;
+
+                or-intro-left : (p q : Type) -> p -> or p q;
+                or-intro-left p q x =
+                    \(c : Type) (on-p : p -> c) (on-q : q -> c) => on-p x;
+
+                or-intro-right : (p q : Type) -> q -> or p q;
+                or-intro-right p q y =
+                    \(c : Type) (on-p : p -> c) (on-q : q -> c) => on-q y;
+            }
+        ";
+
+        support::parse_infer_term(&mut codemap, &context, given_expr);
+    }
+}
+
+#[test]
+fn empty_record_ty() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Type";
+    let given_expr = r"Record {}";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn empty_record_intro() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Record {}";
+    let given_expr = r"record {}";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn dependent_record_ty() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Type^2";
+    let given_expr = r"Record { t : Type^1; x : t }";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn record_intro() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Record { t : Type; x : String }";
+    let given_expr = r#"record { t = String; x = "Hello" }"#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn record_proj() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"(record { t = String; x = "hello" } : Record { t : Type; x : String }).x"#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn record_extend() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Record { t : Type; x : t; y : String }";
+    let given_expr =
+        r#"(record { t = String; x = "hello" } : Record { t : Type; x : t }) with { y = "hi" }"#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn record_extend_duplicate_field() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"record { x = "hello" } with { x = "goodbye" }"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::DuplicateField { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn record_update() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Record { t : Type; x : t }";
+    let given_expr = r#"
+        record (record { t = String; x = "hello" } : Record { t : Type; x : t }) {
+            t = S32;
+            x = 1;
+        }
+    "#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn record_update_dependent_field() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"
+        record (record { t = String; x = "hello" } : Record { t : Type; x : t }) { t = S32 }
+    "#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::FieldNeedsUpdate { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn record_update_missing_field() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"record (record { x = "hello" } : Record { x : String }) { y = "hi" }"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::NoFieldInType { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn record_type_merge() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Type^1";
+    let given_expr = r"Record { t : Type } // Record { x : String }";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn record_type_merge_not_a_record() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r"Record { t : Type } // String";
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::ExpectedRecordType { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn record_proj_missing() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"(record { x = "hello" } : Record { x : String }).bloop"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::NoFieldInType { .. }) => {},
+        x => panic!("expected a field lookup error, found {:?}", x),
+    }
+}
+
+#[test]
+fn record_proj_erased_field() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r"\(r : Record { 0 n : U32 }) => r.n";
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::ErasedFieldUsed { ref label, .. }) => assert_eq!(label.0, "n"),
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn record_proj_erased_field_in_type() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"(r : Record { 0 t : Type; x : t }) -> r.t";
+    let given_expr = r"\(r : Record { 0 t : Type; x : t }) => r.x : r.t";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn record_proj_weird1() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Type^1";
+    let given_expr = r"Record {
+        data : Record {
+            t : Type;
+            x : t;
+        };
+
+        f : data.t -> Type;
+        test : f data.x;
+    }";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn record_proj_weird2() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Type^1";
+    let given_expr = r"Record {
+        Array : U16 -> Type -> Type;
+        t : Record { n : U16; x : Array n S8; y : Array n S8 };
+        inner-prod : (len : U16) -> Array len S8 -> Array len S8 -> S32;
+
+        test1 : S32 -> Type;
+        test2 : test1 (inner-prod t.n t.x t.y);
+    }";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn record_proj_shift() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"(a : Type^1) -> a -> a";
+    let given_expr = r"record {
+        id = \(a : Type) (x : a) => x;
+    }.id^1";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn array_intro_ambiguous() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"[1; 2 : S32]"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::AmbiguousArrayLiteral { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn variant_ty() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"Type^1";
+    let given_expr = r"Variant { some : Type; none : Record {} }";
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
 
-                and-elim-left : (p q : Type) -> and p q -> p;
-                and-elim-left p q (pq : and p q) = pq p (\x y => x);
+#[test]
+fn variant_ty_duplicate() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
 
-                and-elim-right : (p q : Type) -> and p q -> q;
-                and-elim-right p q (pq : and p q) = pq q (\x y => y);
-            }
-        ";
+    let given_expr = r"Variant { some : String; some : Record {} }";
 
-        support::parse_infer_term(&mut codemap, &context, given_expr);
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::DuplicateVariant { ref label, .. }) => assert_eq!(label.0, "some"),
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
     }
+}
 
-    #[test]
-    fn or() {
-        let mut codemap = CodeMap::new();
-        let context = Context::default();
+#[test]
+fn variant_intro_ann() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
 
-        let given_expr = r"
-            record {
-                or; or-intro-left; or-intro-right;
-            } where {
-                ||| Logical disjunction (Church encoded)
-                |||
-                ||| You could also interpret this as a sum type
-                or : Type -> Type -> Type^1;
-                or p q = (c : Type) -> (p -> c) -> (q -> c) -> c;
+    let expected_ty = r"Variant { some : String; none : Record {} }";
+    let given_expr = r#"variant some "hello" : Variant { some : String; none : Record {} }"#;
 
-                or-intro-left : (p q : Type) -> p -> or p q;
-                or-intro-left p q x =
-                    \(c : Type) (on-p : p -> c) (on-q : q -> c) => on-p x;
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
 
-                or-intro-right : (p q : Type) -> q -> or p q;
-                or-intro-right p q y =
-                    \(c : Type) (on-p : p -> c) (on-q : q -> c) => on-q y;
-            }
-        ";
+#[test]
+fn variant_intro_ambiguous() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
 
-        support::parse_infer_term(&mut codemap, &context, given_expr);
+    let given_expr = r#"variant some "hello""#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::AmbiguousVariantIntro { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
     }
 }
 
 #[test]
-fn empty_record_ty() {
+fn eq_ty() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
     let expected_ty = r"Type";
-    let given_expr = r"Record {}";
+    let given_expr = r#""hello" == "hello""#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn empty_record_intro() {
+fn eq_ty_level() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
-    let expected_ty = r"Record {}";
-    let given_expr = r"record {}";
+    let expected_ty = r"Type^2";
+    let given_expr = r"Type == Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn dependent_record_ty() {
+fn eq_intro_ann() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
-    let expected_ty = r"Type^2";
-    let given_expr = r"Record { t : Type^1; x : t }";
+    let expected_ty = r#""hello" == "hello""#;
+    let given_expr = r#"refl : "hello" == "hello""#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn record_intro() {
+fn eq_intro_ambiguous() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
 
-    let expected_ty = r"Record { t : Type; x : String }";
-    let given_expr = r#"record { t = String; x = "Hello" }"#;
+    let given_expr = r"refl";
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::AmbiguousEqIntro { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn eq_elim_sym() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"(a : Type) (x y : a) -> x == y -> y == x";
+    let given_expr = r"
+        (\(a : Type) (x y : a) (p : x == y) => rewrite (\z => z == x) p refl)
+            : (a : Type) (x y : a) -> x == y -> y == x
+    ";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn record_proj() {
+fn eq_elim_expected_eq_ty() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"rewrite (\z => z == "hello") "hello" refl"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::ExpectedEqType { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn case_expr_variant() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
     let expected_ty = r"String";
-    let given_expr = r#"(record { t = String; x = "hello" } : Record { t : Type; x : String }).x"#;
+    let given_expr = r#"case (variant some "hello" : Variant { some : String; none : Record {} }) {
+        variant some greeting => greeting;
+        variant none _ => "goodbye";
+    }"#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn record_proj_missing() {
+fn case_expr_variant_missing() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
     let desugar_env = DesugarEnv::new(context.mappings());
 
-    let given_expr = r#"(record { x = "hello" } : Record { x : String }).bloop"#;
+    let given_expr = r#"case (variant some "hello" : Variant { some : String; none : Record {} }) {
+        variant ok greeting => greeting;
+    }"#;
 
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
 
     match elaborate::infer_term(&context, &raw_term) {
-        Err(TypeError::NoFieldInType { .. }) => {},
-        x => panic!("expected a field lookup error, found {:?}", x),
+        Err(TypeError::NoVariantInType { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
     }
 }
 
 #[test]
-fn record_proj_weird1() {
+fn case_expr_variant_non_exhaustive() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
 
-    let expected_ty = r"Type^1";
-    let given_expr = r"Record {
-        data : Record {
-            t : Type;
-            x : t;
-        };
+    let given_expr = r#"case (variant some "hello" : Variant { some : String; none : Record {} }) {
+        variant some greeting => greeting;
+    }"#;
 
-        f : data.t -> Type;
-        test : f data.x;
-    }";
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::NonExhaustivePatterns { ref missing, .. }) => {
+            assert_eq!(missing.to_string(), "variant none _");
+        },
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn case_expr_variant_recursive() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"U64";
+    let given_expr = r#"
+        let
+            List : Type -> Type;
+            List a = Variant {
+                nil : Record {};
+                cons : Record { head : a; tail : List a };
+            };
+
+            length : (a : Type) -> List a -> U64;
+            length a xs = case xs {
+                variant nil _ => 0;
+                variant cons node => (import "prim/u64/add") 1 (length a node.tail);
+            };
+
+            xs : List U64;
+            xs = variant cons (record {
+                head = 1;
+                tail = variant cons (record { head = 2; tail = variant nil (record {}) })
+            });
+        in
+            length U64 xs
+    "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn record_proj_weird2() {
+fn case_expr_variant_recursive_without_params() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
-    let expected_ty = r"Type^1";
-    let given_expr = r"Record {
-        Array : U16 -> Type -> Type;
-        t : Record { n : U16; x : Array n S8; y : Array n S8 };
-        inner-prod : (len : U16) -> Array len S8 -> Array len S8 -> S32;
+    let expected_ty = r"Bool";
+    let given_expr = r#"
+        let
+            Nat : Type;
+            Nat = Variant { zero : Record {}; succ : Nat };
+
+            is-even : Nat -> Bool;
+            is-odd : Nat -> Bool;
+
+            is-even n = case n {
+                variant zero _ => true;
+                variant succ m => is-odd m;
+            };
+            is-odd n = case n {
+                variant zero _ => false;
+                variant succ m => is-even m;
+            };
+        in
+            is-even (variant succ (variant succ (variant zero (record {}))) : Nat)
+    "#;
 
-        test1 : S32 -> Type;
-        test2 : test1 (inner-prod t.n t.x t.y);
-    }";
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+}
+
+#[test]
+fn case_expr_record_punned() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"case (record { x = "hello"; y = 1 } : Record { x : String; y : S32 }) {
+        record { x } => x;
+    }"#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn record_proj_shift() {
+fn case_expr_record_missing_field() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
 
-    let expected_ty = r"(a : Type^1) -> a -> a";
-    let given_expr = r"record {
-        id = \(a : Type) (x : a) => x;
-    }.id^1";
+    let given_expr = r#"case (record { x = "hello" } : Record { x : String }) {
+        record { z } => z;
+    }"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::NoFieldInType { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn case_expr_record_erased_field() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
 
-    assert_term_eq!(
+    let expected_ty = r"(r : Record { 0 t : Type; x : String }) -> String";
+    let given_expr = r#"\(r : Record { 0 t : Type; x : String }) => case r {
+        record { t = _; x } => x;
+    }"#;
+
+    assert_eq!(
         support::parse_infer_term(&mut codemap, &context, given_expr).1,
         support::parse_nf_term(&mut codemap, &context, expected_ty),
     );
 }
 
 #[test]
-fn array_intro_ambiguous() {
+fn case_expr_record_erased_field_bound() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
     let desugar_env = DesugarEnv::new(context.mappings());
 
-    let given_expr = r#"[1; 2 : S32]"#;
+    let given_expr = r#"\(r : Record { 0 t : Type; x : String }) => case r {
+        record { t; x } => x;
+    }"#;
 
     let raw_term = support::parse_term(&mut codemap, given_expr)
         .desugar(&desugar_env)
         .unwrap();
 
     match elaborate::infer_term(&context, &raw_term) {
-        Err(TypeError::AmbiguousArrayLiteral { .. }) => {},
+        Err(TypeError::ErasedFieldUsed { ref label, .. }) => assert_eq!(label.0, "t"),
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn case_expr_nested_non_exhaustive() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"case (record { flag = true; value = variant none record {} } : Record { flag : Bool; value : Variant { some : String; none : Record {} } }) {
+        record { flag = true; value = _ } => "on";
+        record { flag = false; value = variant some x } => x;
+    }"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::NonExhaustivePatterns { ref missing, .. }) => {
+            assert_eq!(
+                missing.to_string(),
+                "record { flag = false; value = variant none _ }",
+            );
+        },
         Err(err) => panic!("unexpected error: {:?}", err),
         Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
     }
 }
+
+#[test]
+fn case_expr_array_length_mismatch() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+    let desugar_env = DesugarEnv::new(context.mappings());
+
+    let given_expr = r#"case (["a"; "b"] : Array 2 String) {
+        [x] => x;
+    }"#;
+
+    let raw_term = support::parse_term(&mut codemap, given_expr)
+        .desugar(&desugar_env)
+        .unwrap();
+
+    match elaborate::infer_term(&context, &raw_term) {
+        Err(TypeError::ArrayLengthMismatch { .. }) => {},
+        Err(err) => panic!("unexpected error: {:?}", err),
+        Ok((term, ty)) => panic!("expected error, found {} : {:?}", term, ty),
+    }
+}
+
+#[test]
+fn case_expr_variant_unreachable_clause() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let expected_ty = r"String";
+    let given_expr = r#"case (variant some "hello" : Variant { some : String; none : Record {} }) {
+        variant some greeting => greeting;
+        variant none _ => "goodbye";
+        _ => "unreachable";
+    }"#;
+
+    assert_eq!(
+        support::parse_infer_term(&mut codemap, &context, given_expr).1,
+        support::parse_nf_term(&mut codemap, &context, expected_ty),
+    );
+
+    match context.take_warnings().as_slice() {
+        [TypeWarning::UnreachableClause { .. }] => {},
+        warnings => panic!("unexpected warnings: {:?}", warnings),
+    }
+}
@@ -1,23 +1,24 @@
 use codespan::CodeMap;
-use moniker::{assert_term_eq, Binder, Embed, FreeVar, Scope, Var};
+use moniker::FreeVar;
 use pretty_assertions::assert_eq;
 
 use pikelet_concrete::elaborate::Context;
 use pikelet_core::syntax::core::{RcTerm, Term};
-use pikelet_core::syntax::domain::{Neutral, RcNeutral, RcValue, Value};
+use pikelet_core::syntax::domain::{RcValue, Value};
+use pikelet_core::syntax::{Multiplicity, NameHint, Plicity};
 
 mod support;
 
 #[test]
-fn var() {
+fn global() {
     let context = Context::default();
 
     let x = FreeVar::fresh_named("x");
-    let var = RcTerm::from(Term::var(Var::Free(x.clone()), 0));
+    let var = RcTerm::from(Term::global(x.clone(), 0));
 
     assert_eq!(
-        pikelet_core::nbe::nf_term(&context, &var).unwrap(),
-        RcValue::from(Value::var(Var::Free(x), 0)),
+        pikelet_core::nbe::eval_term(&context, &var).unwrap(),
+        RcValue::from(Value::global(x, 0)),
     );
 }
 
@@ -28,7 +29,7 @@ fn ty() {
 
     assert_eq!(
         support::parse_nf_term(&mut codemap, &context, r"Type"),
-        RcValue::from(Value::universe(0)),
+        RcTerm::from(Term::universe(0)),
     );
 }
 
@@ -37,14 +38,15 @@ fn fun_intro() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
-    let x = FreeVar::fresh_named("x");
-
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, r"\x : Type => x"),
-        RcValue::from(Value::FunIntro(Scope::new(
-            (Binder(x.clone()), Embed(RcValue::from(Value::universe(0)))),
-            RcValue::from(Value::var(Var::Free(x), 0)),
-        ))),
+        RcTerm::from(Term::FunIntro(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            NameHint::from("x"),
+            RcTerm::from(Term::universe(0)),
+            RcTerm::from(Term::var(0, 0)),
+        )),
     );
 }
 
@@ -53,14 +55,15 @@ fn fun_ty() {
     let mut codemap = CodeMap::new();
     let context = Context::default();
 
-    let x = FreeVar::fresh_named("x");
-
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, r"(x : Type) -> x"),
-        RcValue::from(Value::FunType(Scope::new(
-            (Binder(x.clone()), Embed(RcValue::from(Value::universe(0)))),
-            RcValue::from(Value::var(Var::Free(x), 0)),
-        ))),
+        RcTerm::from(Term::FunType(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            NameHint::from("x"),
+            RcTerm::from(Term::universe(0)),
+            RcTerm::from(Term::var(0, 0)),
+        )),
     );
 }
 
@@ -71,28 +74,33 @@ fn fun_intro_fun_app() {
 
     let given_expr = r"\(x : Type -> Type) (y : Type) => x y";
 
-    let x = FreeVar::fresh_named("x");
-    let y = FreeVar::fresh_named("y");
-    let ty_arr = RcValue::from(Value::FunType(Scope::new(
-        (
-            Binder(FreeVar::fresh_unnamed()),
-            Embed(RcValue::from(Value::universe(0))),
-        ),
-        RcValue::from(Value::universe(0)),
-    )));
-
-    assert_term_eq!(
+    let ty_arr = RcTerm::from(Term::FunType(
+        Plicity::Explicit,
+        Multiplicity::Many,
+        NameHint::default(),
+        RcTerm::from(Term::universe(0)),
+        RcTerm::from(Term::universe(0)),
+    ));
+
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr,),
-        RcValue::from(Value::FunIntro(Scope::new(
-            (Binder(x.clone()), Embed(ty_arr)),
-            RcValue::from(Value::FunIntro(Scope::new(
-                (Binder(y.clone()), Embed(RcValue::from(Value::universe(0)))),
-                RcValue::from(Value::Neutral(
-                    RcNeutral::from(Neutral::var(Var::Free(x), 0)),
-                    vec![RcValue::from(Value::var(Var::Free(y), 0))],
+        RcTerm::from(Term::FunIntro(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            NameHint::from("x"),
+            ty_arr,
+            RcTerm::from(Term::FunIntro(
+                Plicity::Explicit,
+                Multiplicity::Many,
+                NameHint::from("y"),
+                RcTerm::from(Term::universe(0)),
+                RcTerm::from(Term::FunApp(
+                    RcTerm::from(Term::var(1, 0)),
+                    Multiplicity::Many,
+                    RcTerm::from(Term::var(0, 0)),
                 )),
-            ))),
-        ))),
+            )),
+        )),
     );
 }
 
@@ -103,28 +111,33 @@ fn fun_ty_fun_app() {
 
     let given_expr = r"(x : Type -> Type) -> (y : Type) -> x y";
 
-    let x = FreeVar::fresh_named("x");
-    let y = FreeVar::fresh_named("y");
-    let ty_arr = RcValue::from(Value::FunType(Scope::new(
-        (
-            Binder(FreeVar::fresh_unnamed()),
-            Embed(RcValue::from(Value::universe(0))),
-        ),
-        RcValue::from(Value::universe(0)),
-    )));
-
-    assert_term_eq!(
+    let ty_arr = RcTerm::from(Term::FunType(
+        Plicity::Explicit,
+        Multiplicity::Many,
+        NameHint::default(),
+        RcTerm::from(Term::universe(0)),
+        RcTerm::from(Term::universe(0)),
+    ));
+
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
-        RcValue::from(Value::FunType(Scope::new(
-            (Binder(x.clone()), Embed(ty_arr)),
-            RcValue::from(Value::FunType(Scope::new(
-                (Binder(y.clone()), Embed(RcValue::from(Value::universe(0)))),
-                RcValue::from(Value::Neutral(
-                    RcNeutral::from(Neutral::var(Var::Free(x), 0)),
-                    vec![RcValue::from(Value::var(Var::Free(y), 0))],
+        RcTerm::from(Term::FunType(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            NameHint::from("x"),
+            ty_arr,
+            RcTerm::from(Term::FunType(
+                Plicity::Explicit,
+                Multiplicity::Many,
+                NameHint::from("y"),
+                RcTerm::from(Term::universe(0)),
+                RcTerm::from(Term::FunApp(
+                    RcTerm::from(Term::var(1, 0)),
+                    Multiplicity::Many,
+                    RcTerm::from(Term::var(0, 0)),
                 )),
-            ))),
-        ))),
+            )),
+        )),
     );
 }
 
@@ -138,7 +151,23 @@ fn id_fun_app_ty() {
     let given_expr = r"(\(a : Type^1) (x : a) => x) Type";
     let expected_expr = r"\x : Type => x";
 
-    assert_term_eq!(
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        support::parse_nf_term(&mut codemap, &context, expected_expr),
+    );
+}
+
+// The argument should still be available to the inner function once the
+// outer function has returned
+#[test]
+fn const_fun_app_partial() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"(\(a : Type) (b : Type) (x : a) (y : b) => x) String U8 "hello""#;
+    let expected_expr = r#"\y : U8 => "hello""#;
+
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -153,7 +182,7 @@ fn id_fun_app_ty_ty() {
     let given_expr = r"(\(a : Type^2) (x : a) => x) (Type^1) Type";
     let expected_expr = r"Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -169,7 +198,7 @@ fn id_fun_app_ty_arr_ty() {
     let given_expr = r"(\(a : Type^2) (x : a) => x) (Type^1) (Type -> Type)";
     let expected_expr = r"Type -> Type";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -188,7 +217,7 @@ fn id_fun_app_id() {
         ";
     let expected_expr = r"\(a : Type) (x : a) => x";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -210,7 +239,7 @@ fn const_fun_app_id_ty() {
     ";
     let expected_expr = r"\(a : Type) (x : a) => x";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -224,7 +253,7 @@ fn horrifying_fun_app_1() {
     let given_expr = r"(\(t : Type) (f : (a : Type) -> Type) => f t) String (\(a : Type) => a)";
     let expected_expr = r"String";
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -238,7 +267,7 @@ fn horrifying_fun_app_2() {
     let given_expr = r#"(\(t: String) (f: String -> String) => f t) "hello""#;
     let expected_expr = r#"\(f : String -> String) => f "hello""#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -258,7 +287,7 @@ fn let_expr_1() {
         "helloo"
     "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -279,7 +308,86 @@ fn let_expr_2() {
         "helloo"
     "#;
 
-    assert_term_eq!(
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        support::parse_nf_term(&mut codemap, &context, expected_expr),
+    );
+}
+
+#[test]
+fn let_expr_recursive() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            partial fact : U64 -> U64;
+            fact n =
+                if (import "prim/u64/eq") n 0 then 1 else
+                    (import "prim/u64/mul") n (fact ((import "prim/u64/sub") n 1));
+        in
+            fact 5
+    "#;
+    let expected_expr = r#"
+        120 : U64
+    "#;
+
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        support::parse_nf_term(&mut codemap, &context, expected_expr),
+    );
+}
+
+#[test]
+fn let_expr_recursive_neutral() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            partial fact : U64 -> U64;
+            fact n =
+                if (import "prim/u64/eq") n 0 then 1 else
+                    (import "prim/u64/mul") n (fact ((import "prim/u64/sub") n 1));
+        in
+            \n : U64 => fact n
+    "#;
+
+    // Should terminate, leaving the recursive call stuck on `n`
+    match *support::parse_nf_term(&mut codemap, &context, given_expr) {
+        Term::FunIntro(Plicity::Explicit, ..) => {},
+        ref value => panic!("unexpected value: {:?}", value),
+    }
+}
+
+#[test]
+fn let_expr_mutually_recursive() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            partial is-even : U64 -> Bool;
+            partial is-odd : U64 -> Bool;
+
+            is-even n =
+                if (import "prim/u64/eq") n 0 then true else is-odd ((import "prim/u64/sub") n 1);
+            is-odd n =
+                if (import "prim/u64/eq") n 0 then false else is-even ((import "prim/u64/sub") n 1);
+        in
+            record {
+                test-even = is-even 10;
+                test-odd = is-odd 10;
+            }
+    "#;
+    let expected_expr = r#"
+        record {
+            test-even = true;
+            test-odd = false;
+        }
+    "#;
+
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -297,7 +405,7 @@ fn if_true() {
         "true"
     "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -315,7 +423,7 @@ fn if_false() {
         "false"
     "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -344,7 +452,7 @@ fn if_eval_cond() {
         }
     "#;
 
-    assert_term_eq!(
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -374,7 +482,118 @@ fn case_expr_bool() {
         }
     "#;
 
-    assert_term_eq!(
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        support::parse_nf_term(&mut codemap, &context, expected_expr),
+    );
+}
+
+#[test]
+fn case_expr_variant() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            Option (a : Type) : Type = Variant { some : a; none : Record {} };
+            unwrap-or (a : Type) (default : a) (opt : Option a) : a = case opt {
+                variant some x => x;
+                variant none _ => default;
+            };
+        in
+            record {
+                test-some = unwrap-or String "hi" (variant some "hello");
+                test-none = unwrap-or String "hi" (variant none record {});
+            }
+    "#;
+    let expected_expr = r#"
+        record {
+            test-some = "hello";
+            test-none = "hi";
+        }
+    "#;
+
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        support::parse_nf_term(&mut codemap, &context, expected_expr),
+    );
+}
+
+#[test]
+fn case_expr_nested() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            Option (a : Type) : Type = Variant { some : a; none : Record {} };
+            describe (r : Record { flag : Bool; value : Option String }) : String = case r {
+                record { flag = true; value = variant some x } => x;
+                record { value = variant none _ } => "none";
+                _ => "off";
+            };
+        in
+            record {
+                test-some = describe (record { flag = true; value = variant some "hello" });
+                test-none = describe (record { flag = true; value = variant none record {} });
+                test-off = describe (record { flag = false; value = variant some "hello" });
+            }
+    "#;
+    let expected_expr = r#"
+        record {
+            test-some = "hello";
+            test-none = "none";
+            test-off = "off";
+        }
+    "#;
+
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        support::parse_nf_term(&mut codemap, &context, expected_expr),
+    );
+}
+
+#[test]
+fn case_expr_array() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        let
+            second-or-last (xs : Array 3 String) : String = case xs {
+                ["a"; y; _] => y;
+                [_; _; z] => z;
+            };
+        in
+            record {
+                test-second = second-or-last ["a"; "b"; "c"];
+                test-last = second-or-last ["x"; "b"; "c"];
+            }
+    "#;
+    let expected_expr = r#"
+        record {
+            test-second = "b";
+            test-last = "c";
+        }
+    "#;
+
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        support::parse_nf_term(&mut codemap, &context, expected_expr),
+    );
+}
+
+#[test]
+fn eq_elim_refl() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        rewrite (\x => String) (refl : "hello" == "hello") "goodbye"
+    "#;
+    let expected_expr = r#""goodbye""#;
+
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -388,7 +607,69 @@ fn record_ty_shadow() {
     let given_expr = r"(\t : Type => Record { String : Type; x : t; y : String }) String";
     let expected_expr = r#"Record { String as String1 : Type; x : String; y : String1 }"#;
 
-    assert_term_eq!(
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        support::parse_nf_term(&mut codemap, &context, expected_expr),
+    );
+}
+
+#[test]
+fn record_extend_proj() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr =
+        r#"(\r : Record { x : String } => (r with { y = "hi" }).x) (record { x = "hello" })"#;
+    let expected_expr = r#""hello""#;
+
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        support::parse_nf_term(&mut codemap, &context, expected_expr),
+    );
+}
+
+#[test]
+fn record_width_coercion() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        (\r : Record { x : String } => r) (record { x = "hello"; y = "hi" } : Record { x : String; y : String })
+    "#;
+    let expected_expr = r#"record { x = "hello" }"#;
+
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        support::parse_nf_term(&mut codemap, &context, expected_expr),
+    );
+}
+
+#[test]
+fn record_type_merge_weakened() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r"(\t : Type => Record { x : t } // Record { y : t; z : t -> t }) String";
+    let expected_expr = r"Record { x : String; y : String; z : String -> String }";
+
+    assert_eq!(
+        support::parse_nf_term(&mut codemap, &context, given_expr),
+        support::parse_nf_term(&mut codemap, &context, expected_expr),
+    );
+}
+
+#[test]
+fn record_update() {
+    let mut codemap = CodeMap::new();
+    let context = Context::default();
+
+    let given_expr = r#"
+        (\r : Record { x : String; y : String } => record r { y = "bye" })
+            (record { x = "hello"; y = "hi" })
+    "#;
+    let expected_expr = r#"record { x = "hello"; y = "bye" }"#;
+
+    assert_eq!(
         support::parse_nf_term(&mut codemap, &context, given_expr),
         support::parse_nf_term(&mut codemap, &context, expected_expr),
     );
@@ -84,3 +84,24 @@ fn integer_overflow() {
         )
     );
 }
+
+#[test]
+fn fun_ty_invalid_multiplicity() {
+    let src = "(2 x : Type) -> Type";
+    let mut codemap = CodeMap::new();
+    let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+
+    let parse_result = parse::term(&filemap);
+
+    assert_eq!(
+        parse_result,
+        (
+            concrete::Term::Error(ByteSpan::new(ByteIndex(1), ByteIndex(21))),
+            vec![],
+            vec![ParseError::InvalidMultiplicity {
+                span: ByteSpan::new(ByteIndex(2), ByteIndex(3)),
+                found: 2,
+            }],
+        )
+    );
+}
@@ -1,10 +1,10 @@
 use codespan::{ByteIndex, ByteSpan};
-use moniker::{Binder, Embed, FreeVar, Nest, Scope, Var};
+use moniker::FreeVar;
 use pretty_assertions::assert_eq;
 
 use pikelet_concrete::resugar::{Resugar, ResugarEnv};
-use pikelet_concrete::syntax::concrete;
-use pikelet_core::syntax::{core, Label, LevelShift, Literal};
+use pikelet_concrete::syntax::{concrete, BindingKind};
+use pikelet_core::syntax::{core, Label, LevelShift, Literal, Multiplicity, NameHint, Plicity};
 
 fn span() -> ByteSpan {
     ByteSpan::default()
@@ -74,11 +74,10 @@ fn lit_string() {
 
 #[test]
 fn var() {
-    let free_var = FreeVar::fresh_named("x");
     let mut env = ResugarEnv::new();
-    env.on_item(&Label("x".to_owned()), &Binder(free_var.clone()));
+    env.on_local(&NameHint::from("x"));
 
-    let core_term = core::Term::var(Var::Free(free_var), 0);
+    let core_term = core::Term::var(0, 0);
     let concrete_term = concrete::Term::Name(span(), "x".to_owned(), None);
 
     assert_eq!(core_term.resugar(&env), concrete_term);
@@ -86,16 +85,27 @@ fn var() {
 
 #[test]
 fn var_shadow_keyword() {
-    let free_var = FreeVar::fresh_named("if");
     let mut env = ResugarEnv::new();
-    env.on_item(&Label("if".to_owned()), &Binder(free_var.clone()));
+    env.on_local(&NameHint::from("if"));
 
-    let core_term = core::Term::var(Var::Free(free_var), 0);
+    let core_term = core::Term::var(0, 0);
     let concrete_term = concrete::Term::Name(span(), "if1".to_owned(), None);
 
     assert_eq!(core_term.resugar(&env), concrete_term);
 }
 
+#[test]
+fn global() {
+    let free_var = FreeVar::fresh_named("x");
+    let mut env = ResugarEnv::new();
+    env.on_global(&free_var);
+
+    let core_term = core::Term::global(free_var, 0);
+    let concrete_term = concrete::Term::Name(span(), "x".to_owned(), None);
+
+    assert_eq!(core_term.resugar(&env), concrete_term);
+}
+
 #[test]
 fn import() {
     let core_term = core::Term::Import("type".to_owned());
@@ -108,15 +118,13 @@ fn import() {
 
 #[test]
 fn arrow() {
-    let core_term = core::RcTerm::from(core::Term::FunType(Scope::new(
-        (
-            Binder(FreeVar::fresh_unnamed()),
-            Embed(core::RcTerm::from(core::RcTerm::from(
-                core::Term::universe(0),
-            ))),
-        ),
-        core::RcTerm::from(core::RcTerm::from(core::Term::universe(0))),
-    )));
+    let core_term = core::RcTerm::from(core::Term::FunType(
+        Plicity::Explicit,
+        Multiplicity::Many,
+        NameHint::default(),
+        core::RcTerm::from(core::Term::universe(0)),
+        core::RcTerm::from(core::Term::universe(0)),
+    ));
 
     let concrete_term = concrete::Term::FunArrow(
         Box::new(concrete::Term::Universe(span(), None)),
@@ -128,21 +136,19 @@ fn arrow() {
 
 #[test]
 fn arrow_parens() {
-    let core_term = core::Term::FunType(Scope::new(
-        (
-            Binder(FreeVar::fresh_unnamed()),
-            Embed(core::RcTerm::from(core::Term::FunType(Scope::new(
-                (
-                    Binder(FreeVar::fresh_unnamed()),
-                    Embed(core::RcTerm::from(core::RcTerm::from(
-                        core::Term::universe(0),
-                    ))),
-                ),
-                core::RcTerm::from(core::RcTerm::from(core::Term::universe(0))),
-            )))),
-        ),
-        core::RcTerm::from(core::RcTerm::from(core::Term::universe(1))),
-    ));
+    let core_term = core::Term::FunType(
+        Plicity::Explicit,
+        Multiplicity::Many,
+        NameHint::default(),
+        core::RcTerm::from(core::Term::FunType(
+            Plicity::Explicit,
+            Multiplicity::Many,
+            NameHint::default(),
+            core::RcTerm::from(core::Term::universe(0)),
+            core::RcTerm::from(core::Term::universe(0)),
+        )),
+        core::RcTerm::from(core::Term::universe(1)),
+    );
 
     let concrete_term = concrete::Term::FunArrow(
         Box::new(concrete::Term::Parens(
@@ -163,33 +169,32 @@ fn arrow_parens() {
 
 #[test]
 fn let_shadow_keyword() {
-    let var_else1 = FreeVar::fresh_named("else");
-    let var_else2 = FreeVar::fresh_named("else");
-
-    let core_module = core::Term::Let(Scope::new(
-        Nest::new(vec![
+    let core_module = core::Term::Let(
+        vec![
             (
-                Binder(var_else1.clone()),
-                Embed(core::RcTerm::from(core::Term::universe(0))),
+                NameHint::from("else"),
+                core::RcTerm::from(core::Term::universe(0)),
             ),
             (
-                Binder(var_else2.clone()),
-                Embed(core::RcTerm::from(core::Term::universe(0))),
+                NameHint::from("else"),
+                core::RcTerm::from(core::Term::universe(0)),
             ),
-        ]),
+        ],
         core::RcTerm::from(core::Term::RecordIntro(vec![])),
-    ));
+    );
 
     let concrete_module = concrete::Term::Let(
         index(),
         vec![
             concrete::Item::Definition {
+                kind: BindingKind::Ordinary,
                 name: (index(), "else1".to_owned()),
                 params: vec![],
                 return_ann: None,
                 body: concrete::Term::Universe(span(), None),
             },
             concrete::Item::Definition {
+                kind: BindingKind::Ordinary,
                 name: (index(), "else2".to_owned()),
                 params: vec![],
                 return_ann: None,
@@ -204,7 +209,7 @@ fn let_shadow_keyword() {
 
 #[test]
 fn record_ty_empty() {
-    let core_term = core::Term::RecordType(Scope::new(Nest::new(vec![]), ()));
+    let core_term = core::Term::RecordType(vec![]);
     let concrete_term = concrete::Term::RecordType(span(), vec![]);
 
     assert_eq!(core_term.resugar(&ResugarEnv::new()), concrete_term);
@@ -213,43 +218,33 @@ fn record_ty_empty() {
 #[test]
 fn record_ty() {
     let mut env = ResugarEnv::new();
-    env.on_item(
-        &Label("String".to_owned()),
-        &Binder(FreeVar::fresh_named("String")),
-    );
-
-    let var_string = FreeVar::fresh_named("String");
-    let var_x = FreeVar::fresh_named("x");
+    env.on_global(&FreeVar::fresh_named("String"));
 
-    let core_term = core::Term::RecordType(Scope::new(
-        Nest::new(vec![
-            (
-                Label("String".to_owned()),
-                Binder(var_string.clone()),
-                Embed(core::RcTerm::from(core::RcTerm::from(
-                    core::Term::universe(0),
-                ))),
-            ),
-            (
-                Label("x".to_owned()),
-                Binder(var_x.clone()),
-                Embed(core::RcTerm::from(core::RcTerm::from(core::Term::var(
-                    Var::Free(var_string),
-                    0,
-                )))),
-            ),
-        ]),
-        (),
-    ));
+    let core_term = core::Term::RecordType(vec![
+        (
+            Label("String".to_owned()),
+            NameHint::from("String"),
+            Multiplicity::Many,
+            core::RcTerm::from(core::Term::universe(0)),
+        ),
+        (
+            Label("x".to_owned()),
+            NameHint::from("x"),
+            Multiplicity::Many,
+            core::RcTerm::from(core::Term::var(0, 0)),
+        ),
+    ]);
     let concrete_term = concrete::Term::RecordType(
         span(),
         vec![
             concrete::RecordTypeField {
+                multiplicity: Multiplicity::Many,
                 label: (index(), "String".to_owned()),
                 binder: Some((index(), "String1".to_owned())),
                 ann: concrete::Term::Universe(span(), None),
             },
             concrete::RecordTypeField {
+                multiplicity: Multiplicity::Many,
                 label: (index(), "x".to_owned()),
                 binder: None,
                 ann: concrete::Term::Name(span(), "String1".to_owned(), None),
@@ -275,6 +270,7 @@ fn record_proj_atomic() {
     let core_term = core::Term::RecordProj(
         core::RcTerm::from(core::RcTerm::from(core::Term::universe(0))),
         Label("hello".to_owned()),
+        Multiplicity::Many,
         LevelShift(0),
     );
 
@@ -294,6 +290,7 @@ fn record_proj_fun_app() {
     let core_term = core::Term::RecordProj(
         core::RcTerm::from(core::RcTerm::from(core::Term::universe(1))),
         Label("hello".to_owned()),
+        Multiplicity::Many,
         LevelShift(0),
     );
 
@@ -311,6 +308,71 @@ fn record_proj_fun_app() {
     assert_eq!(core_term.resugar(&ResugarEnv::new()), concrete_term);
 }
 
+#[test]
+fn variant_ty() {
+    let core_term = core::Term::VariantType(vec![
+        (
+            Label("some".to_owned()),
+            core::RcTerm::from(core::Term::universe(0)),
+        ),
+        (
+            Label("none".to_owned()),
+            core::RcTerm::from(core::Term::RecordType(vec![])),
+        ),
+    ]);
+
+    let concrete_term = concrete::Term::VariantType(
+        span(),
+        vec![
+            concrete::VariantTypeAlt {
+                label: (index(), "some".to_owned()),
+                ann: concrete::Term::Universe(span(), None),
+            },
+            concrete::VariantTypeAlt {
+                label: (index(), "none".to_owned()),
+                ann: concrete::Term::RecordType(span(), vec![]),
+            },
+        ],
+    );
+
+    assert_eq!(core_term.resugar(&ResugarEnv::new()), concrete_term);
+}
+
+#[test]
+fn variant_intro_fun_app() {
+    let core_term = core::Term::VariantIntro(
+        Label("some".to_owned()),
+        core::RcTerm::from(core::Term::universe(1)),
+    );
+
+    let concrete_term = concrete::Term::VariantIntro(
+        index(),
+        (index(), "some".to_owned()),
+        Box::new(concrete::Term::Parens(
+            span(),
+            Box::new(concrete::Term::Universe(span(), Some(1))),
+        )),
+    );
+
+    assert_eq!(core_term.resugar(&ResugarEnv::new()), concrete_term);
+}
+
+#[test]
+fn eq_ty() {
+    let core_term = core::Term::EqType(
+        core::RcTerm::from(core::Term::universe(1)),
+        core::RcTerm::from(core::Term::universe(0)),
+        core::RcTerm::from(core::Term::universe(0)),
+    );
+
+    let concrete_term = concrete::Term::EqType(
+        Box::new(concrete::Term::Universe(span(), None)),
+        Box::new(concrete::Term::Universe(span(), None)),
+    );
+
+    assert_eq!(core_term.resugar(&ResugarEnv::new()), concrete_term);
+}
+
 // TODO: core::Term::Case
 
 #[test]
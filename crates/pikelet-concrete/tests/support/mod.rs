@@ -8,9 +8,9 @@ use pikelet_concrete::desugar::{Desugar, DesugarEnv};
 use pikelet_concrete::elaborate::{self, Context};
 use pikelet_concrete::parse;
 use pikelet_concrete::syntax::concrete;
-use pikelet_core::nbe;
 use pikelet_core::syntax::core::RcTerm;
 use pikelet_core::syntax::domain::{RcType, RcValue};
+use pikelet_core::{eval, nbe};
 
 pub fn parse_term(codemap: &mut CodeMap, src: &str) -> concrete::Term {
     let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
@@ -27,12 +27,15 @@ pub fn parse_term(codemap: &mut CodeMap, src: &str) -> concrete::Term {
     concrete_term
 }
 
-pub fn parse_infer_term(codemap: &mut CodeMap, context: &Context, src: &str) -> (RcTerm, RcType) {
+pub fn parse_infer_term(codemap: &mut CodeMap, context: &Context, src: &str) -> (RcTerm, RcTerm) {
     let raw_term = parse_term(codemap, src)
         .desugar(&DesugarEnv::new(context.mappings()))
         .unwrap();
     match elaborate::infer_term(context, &raw_term) {
-        Ok((term, ty)) => (term, ty),
+        Ok((term, ty)) => match context.quote(&ty) {
+            Ok(ty) => (term, ty),
+            Err(error) => panic!("read back error: {}", error),
+        },
         Err(error) => {
             let writer = StandardStream::stdout(ColorChoice::Always);
             codespan_reporting::emit(&mut writer.lock(), &codemap, &error.to_diagnostic()).unwrap();
@@ -41,10 +44,30 @@ pub fn parse_infer_term(codemap: &mut CodeMap, context: &Context, src: &str) ->
     }
 }
 
-pub fn parse_nf_term(codemap: &mut CodeMap, context: &Context, src: &str) -> RcValue {
+pub fn parse_eval_term(codemap: &mut CodeMap, context: &Context, src: &str) -> RcValue {
     let term = parse_infer_term(codemap, context, src).0;
-    match nbe::nf_term(context, &term) {
+    match nbe::eval_term(&nbe::EvalEnv(context), &term) {
         Ok(value) => value,
+        Err(error) => panic!("eval error: {}", error),
+    }
+}
+
+pub fn parse_run_term(
+    codemap: &mut CodeMap,
+    context: &Context,
+    src: &str,
+) -> (eval::RcValue, eval::Stats) {
+    let term = parse_infer_term(codemap, context, src).0;
+    match eval::run_term(context, &term) {
+        Ok(result) => result,
+        Err(error) => panic!("runtime error: {}", error),
+    }
+}
+
+pub fn parse_nf_term(codemap: &mut CodeMap, context: &Context, src: &str) -> RcTerm {
+    let term = parse_infer_term(codemap, context, src).0;
+    match nbe::nf_term(&nbe::EvalEnv(context), &term) {
+        Ok(term) => term,
         Err(error) => panic!("normalize error: {}", error),
     }
 }
@@ -0,0 +1,121 @@
+//! Erasure of the parts of a term that are not needed at runtime
+//!
+//! Parameters with a multiplicity of zero can only be used in types, so once a
+//! term has passed the usage checker they can be removed, along with the
+//! arguments that are passed to them. Record fields with a multiplicity of
+//! zero are kept, so that records have the same fields at runtime, but their
+//! values are erased. Type annotations are dropped as well.
+
+use crate::syntax::core::{RcTerm, Term};
+use crate::syntax::{Multiplicity, VarIndex};
+
+/// Erase a term, removing the parameters that have a multiplicity of zero,
+/// along with the arguments that are passed to them, and the values of the
+/// record fields that have a multiplicity of zero
+///
+/// The remaining references to erased variables are replaced with
+/// `Term::Erased`, and will only be found in types.
+///
+/// # Panics
+///
+/// The term is expected to have passed the usage checker, so erased variables
+/// should never be compared against in patterns.
+pub fn erase(term: &RcTerm) -> RcTerm {
+    match *term.inner {
+        Term::Ann(ref expr, _) => erase(expr),
+
+        Term::FunIntro(_, Multiplicity::Zero, _, _, ref body) => remove_param(&erase(body)),
+        Term::FunIntro(plicity, multiplicity, ref name, _, ref body) => {
+            RcTerm::from(Term::FunIntro(
+                plicity,
+                multiplicity,
+                name.clone(),
+                RcTerm::from(Term::Erased),
+                erase(body),
+            ))
+        },
+        Term::FunApp(ref head, Multiplicity::Zero, _) => erase(head),
+        Term::FunApp(ref head, multiplicity, ref arg) => {
+            RcTerm::from(Term::FunApp(erase(head), multiplicity, erase(arg)))
+        },
+        Term::Fix(totality, ref name, _, ref body) => RcTerm::from(Term::Fix(
+            totality,
+            name.clone(),
+            RcTerm::from(Term::Erased),
+            erase(body),
+        )),
+
+        Term::RecordIntro(ref fields) => RcTerm::from(Term::RecordIntro(
+            fields
+                .iter()
+                .map(|&(ref label, multiplicity, ref expr)| match multiplicity {
+                    Multiplicity::Zero => (label.clone(), multiplicity, RcTerm::from(Term::Erased)),
+                    _ => (label.clone(), multiplicity, erase(expr)),
+                })
+                .collect(),
+        )),
+        Term::RecordProj(ref expr, ref label, multiplicity, shift) => RcTerm::from(
+            Term::RecordProj(erase(expr), label.clone(), multiplicity, shift),
+        ),
+        Term::RecordExtend(ref expr, ref fields) => RcTerm::from(Term::RecordExtend(
+            erase(expr),
+            fields
+                .iter()
+                .map(|&(ref label, ref expr)| (label.clone(), erase(expr)))
+                .collect(),
+        )),
+        Term::VariantIntro(ref label, ref arg) => {
+            RcTerm::from(Term::VariantIntro(label.clone(), erase(arg)))
+        },
+        // Rewriting doesn't change the value of the body, so the proof is
+        // not needed at runtime
+        Term::EqElim(_, _, ref body) => erase(body),
+        Term::Case(ref head, ref clauses) => RcTerm::from(Term::Case(
+            erase(head),
+            clauses
+                .iter()
+                .map(|&(ref pattern, ref body)| (pattern.clone(), erase(body)))
+                .collect(),
+        )),
+        Term::ArrayIntro(ref elems) => {
+            RcTerm::from(Term::ArrayIntro(elems.iter().map(erase).collect()))
+        },
+        Term::Let(ref bindings, ref body) => RcTerm::from(Term::Let(
+            bindings
+                .iter()
+                .map(|&(ref name, ref term)| (name.clone(), erase(term)))
+                .collect(),
+            erase(body),
+        )),
+
+        // Types are left alone, as they can still be passed to parameters
+        // that are used at runtime
+        Term::Universe(_)
+        | Term::UniverseAt(_)
+        | Term::UniverseOmega
+        | Term::LevelType
+        | Term::LevelIntro(_)
+        | Term::LevelMax(_, _)
+        | Term::LevelShift(_, _)
+        | Term::FunType(_, _, _, _, _)
+        | Term::RecordType(_)
+        | Term::VariantType(_)
+        | Term::EqType(_, _, _) => term.clone(),
+
+        Term::Literal(_)
+        | Term::Var(_, _)
+        | Term::Global(_, _)
+        | Term::Import(_)
+        | Term::Meta(_)
+        | Term::EqIntro
+        | Term::Erased => term.clone(),
+    }
+}
+
+/// Remove the innermost parameter from the body of a function
+fn remove_param(body: &RcTerm) -> RcTerm {
+    body.subst_vars(&mut |index, shift| match index {
+        VarIndex(0) => RcTerm::from(Term::Erased),
+        VarIndex(index) => RcTerm::from(Term::Var(VarIndex(index - 1), shift)),
+    })
+}
@@ -0,0 +1,593 @@
+//! A call-by-value evaluator for running programs
+//!
+//! Normalization by evaluation has to cope with open terms, and reduces
+//! underneath binders in order to compare terms during type checking. When
+//! we just want to run a closed program we can do a lot less work: arguments
+//! are evaluated before they are passed to functions, as befits a strict
+//! language, and the bodies of functions are only evaluated once they are
+//! applied. Types have no runtime representation, and evaluate to
+//! `Value::Erased`.
+//!
+//! Terms are erased before they are run, along with the definitions that they
+//! refer to, so that arguments passed to parameters with a multiplicity of
+//! zero are never evaluated.
+//!
+//! The evaluator keeps count of the number of steps that it takes, and of the
+//! depth that its stack grows to, which is handy when comparing the running
+//! times of different programs.
+
+use moniker::FreeVar;
+use pretty::{BoxDoc, Doc};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops;
+use std::rc::Rc;
+
+use crate::erase::erase;
+use crate::nbe::Env;
+use crate::syntax::core::{Pattern, RcPattern, RcTerm, Term};
+use crate::syntax::{domain, Import, Label, Literal, PRETTY_FALLBACK_WIDTH};
+
+/// An error produced while running a program
+///
+/// Running a program that has been successfully type checked can still fail,
+/// for example if the evaluator runs out of stack.
+#[derive(Debug, Clone, PartialEq, failure::Fail)]
+#[fail(display = "{}", message)]
+pub struct RuntimeError {
+    pub message: String,
+}
+
+impl RuntimeError {
+    pub fn new(message: impl Into<String>) -> RuntimeError {
+        RuntimeError {
+            message: message.into(),
+        }
+    }
+}
+
+/// The values of the local variables in scope, in the order that they were
+/// bound
+pub type Locals = im::Vector<RcValue>;
+
+/// The body of a function, along with the values of the local variables that
+/// were in scope when it was created
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure {
+    pub locals: Locals,
+    pub body: RcTerm,
+}
+
+impl Closure {
+    pub fn new(locals: Locals, body: RcTerm) -> Closure {
+        Closure { locals, body }
+    }
+}
+
+/// Runtime values
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// Literals
+    Literal(Literal),
+    /// Functions
+    FunIntro(Closure),
+    /// Fixed points, which are unfolded each time they are looked up
+    Fix(Closure),
+    /// Primitive imports, along with the arguments that have been applied to
+    /// them so far
+    Prim(String, Vec<RcValue>),
+    /// Records
+    RecordIntro(Vec<(Label, RcValue)>),
+    /// Variants
+    VariantIntro(Label, RcValue),
+    /// Arrays
+    ArrayIntro(Vec<RcValue>),
+    /// Equality proofs
+    EqIntro,
+    /// Types, and anything else that is not needed at runtime
+    Erased,
+}
+
+impl Value {
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        match *self {
+            Value::VariantIntro(ref label, ref arg) => Doc::nil()
+                .append("variant")
+                .append(Doc::space())
+                .append(Doc::as_string(label))
+                .append(Doc::space())
+                .append(arg.to_doc_atomic()),
+            ref value => value.to_doc_atomic(),
+        }
+    }
+
+    fn to_doc_atomic(&self) -> Doc<BoxDoc<()>> {
+        match *self {
+            Value::Literal(ref literal) => literal.to_doc(),
+            Value::FunIntro(_) | Value::Fix(_) | Value::Prim(_, _) => Doc::text("<function>"),
+            Value::RecordIntro(ref fields) => Doc::nil()
+                .append("record {")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    fields.iter().map(|&(ref label, ref value)| {
+                        Doc::nil()
+                            .append(Doc::as_string(label))
+                            .append(Doc::space())
+                            .append("=")
+                            .append(Doc::space())
+                            .append(value.to_doc())
+                    }),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append(Doc::space())
+                .append("}"),
+            Value::ArrayIntro(ref elems) => Doc::nil()
+                .append("[")
+                .append(Doc::intersperse(
+                    elems.iter().map(|elem| elem.to_doc()),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append("]"),
+            Value::EqIntro => Doc::text("refl"),
+            Value::Erased => Doc::text("erased"),
+            ref value => Doc::text("(").append(value.to_doc()).append(")"),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
+/// Reference counted runtime values
+#[derive(Debug, Clone, PartialEq)]
+pub struct RcValue {
+    pub inner: Rc<Value>,
+}
+
+impl From<Value> for RcValue {
+    fn from(src: Value) -> RcValue {
+        RcValue {
+            inner: Rc::new(src),
+        }
+    }
+}
+
+impl ops::Deref for RcValue {
+    type Target = Value;
+
+    fn deref(&self) -> &Value {
+        &self.inner
+    }
+}
+
+impl fmt::Display for RcValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.inner, f)
+    }
+}
+
+/// Statistics collected while running a program
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of terms that were evaluated
+    pub steps: u64,
+    /// The greatest number of terms that were being evaluated at once
+    pub max_depth: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} steps, max depth {}", self.steps, self.max_depth)
+    }
+}
+
+/// The depth limit used by `run_term`
+///
+/// This is kept low enough that the evaluator reports an error before it
+/// overflows the native stack, even in a debug build running on a thread with
+/// a small stack.
+pub const DEFAULT_DEPTH_LIMIT: usize = 1000;
+
+/// Run a program, returning its value along with the statistics that were
+/// collected along the way
+///
+/// Evaluation is abandoned with an error if the stack grows deeper than
+/// `DEFAULT_DEPTH_LIMIT`. Use a `Machine` directly to pick a different limit.
+pub fn run_term(env: &dyn Env, term: &RcTerm) -> Result<(RcValue, Stats), RuntimeError> {
+    let mut machine = Machine::new(env).with_depth_limit(DEFAULT_DEPTH_LIMIT);
+    let value = machine.eval_term(term)?;
+
+    Ok((value, machine.stats()))
+}
+
+/// The state of a running program
+pub struct Machine<'env> {
+    env: &'env dyn Env,
+    /// Global definitions that have already been evaluated
+    globals: HashMap<FreeVar<String>, RcValue>,
+    /// Imports that have already been evaluated
+    imports: HashMap<String, RcValue>,
+    /// The number of terms that are currently being evaluated
+    depth: usize,
+    /// The depth at which evaluation should be abandoned
+    depth_limit: Option<usize>,
+    stats: Stats,
+}
+
+impl<'env> Machine<'env> {
+    pub fn new(env: &'env dyn Env) -> Machine<'env> {
+        Machine {
+            env,
+            globals: HashMap::new(),
+            imports: HashMap::new(),
+            depth: 0,
+            depth_limit: None,
+            stats: Stats::default(),
+        }
+    }
+
+    /// Report an error if the evaluation stack grows past `limit`, rather
+    /// than risking overflowing the native stack
+    pub fn with_depth_limit(mut self, limit: usize) -> Machine<'env> {
+        self.depth_limit = Some(limit);
+        self
+    }
+
+    /// The statistics that have been collected so far
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Erase and evaluate a term that does not refer to any local variables
+    pub fn eval_term(&mut self, term: &RcTerm) -> Result<RcValue, RuntimeError> {
+        self.eval(&Locals::new(), &erase(term))
+    }
+
+    /// Evaluate an erased term, looking up the values of local variables in
+    /// `locals`
+    fn eval(&mut self, locals: &Locals, term: &RcTerm) -> Result<RcValue, RuntimeError> {
+        self.stats.steps += 1;
+        self.depth += 1;
+        self.stats.max_depth = self.stats.max_depth.max(self.depth);
+
+        let value = match self.depth_limit {
+            Some(limit) if self.depth > limit => Err(RuntimeError::new(format!(
+                "stack overflow: exceeded the depth limit of {}",
+                limit
+            ))),
+            Some(_) | None => self.eval_step(locals, term),
+        };
+
+        self.depth -= 1;
+        value
+    }
+
+    fn eval_step(&mut self, locals: &Locals, term: &RcTerm) -> Result<RcValue, RuntimeError> {
+        match *term.inner {
+            Term::Ann(ref expr, _) => self.eval(locals, expr),
+
+            Term::Universe(_)
+            | Term::UniverseAt(_)
+            | Term::UniverseOmega
+            | Term::LevelType
+            | Term::LevelIntro(_)
+            | Term::LevelMax(_, _)
+            | Term::LevelShift(_, _)
+            | Term::FunType(_, _, _, _, _)
+            | Term::RecordType(_)
+            | Term::VariantType(_)
+            | Term::EqType(_, _, _)
+            | Term::Erased => Ok(RcValue::from(Value::Erased)),
+
+            Term::Literal(ref literal) => Ok(RcValue::from(Value::Literal(literal.clone()))),
+
+            // Universe shifts only matter to the type checker
+            Term::Var(index, _) => match index.to_level(locals.len()) {
+                Some(level) => self.unfold_fix(&locals[level.0]),
+                None => Err(RuntimeError::new(format!(
+                    "unbound local variable `{}`",
+                    index
+                ))),
+            },
+            Term::Global(ref free_var, _) => self.global(free_var),
+            Term::Import(ref name) => self.import(name),
+            Term::Meta(meta) => match self.env.get_meta_solution(meta) {
+                Some(term) => self.eval_term(&term),
+                None => Err(RuntimeError::new(format!(
+                    "unsolved metavariable `{}`",
+                    meta
+                ))),
+            },
+
+            Term::FunIntro(_, _, _, _, ref body) => Ok(RcValue::from(Value::FunIntro(
+                Closure::new(locals.clone(), body.clone()),
+            ))),
+            Term::FunApp(ref head, _, ref arg) => {
+                let head = self.eval(locals, head)?;
+                let arg = self.eval(locals, arg)?;
+
+                self.fun_app(&head, arg)
+            },
+
+            Term::Let(ref bindings, ref body) => {
+                let mut locals = locals.clone();
+
+                for &(_, ref term) in bindings {
+                    let value = self.eval(&locals, term)?;
+                    locals.push_back(value);
+                }
+
+                self.eval(&locals, body)
+            },
+            Term::Fix(_, _, _, ref body) => {
+                let fix = Value::Fix(Closure::new(locals.clone(), body.clone()));
+                self.unfold_fix(&RcValue::from(fix))
+            },
+
+            Term::RecordIntro(ref fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|&(ref label, _, ref term)| Ok((label.clone(), self.eval(locals, term)?)))
+                    .collect::<Result<_, RuntimeError>>()?;
+
+                Ok(RcValue::from(Value::RecordIntro(fields)))
+            },
+            Term::RecordProj(ref expr, ref label, _, _) => {
+                let expr = self.eval(locals, expr)?;
+                record_proj(&expr, label)
+            },
+            Term::RecordExtend(ref expr, ref fields) => {
+                let expr = self.eval(locals, expr)?;
+                let fields = self.eval_fields(locals, fields)?;
+
+                match *expr {
+                    Value::RecordIntro(ref expr_fields) => {
+                        let mut expr_fields = expr_fields.clone();
+                        expr_fields.extend(fields);
+
+                        Ok(RcValue::from(Value::RecordIntro(expr_fields)))
+                    },
+                    _ => Err(RuntimeError::new("extended a value that is not a record")),
+                }
+            },
+
+            Term::VariantIntro(ref label, ref arg) => Ok(RcValue::from(Value::VariantIntro(
+                label.clone(),
+                self.eval(locals, arg)?,
+            ))),
+
+            Term::EqIntro => Ok(RcValue::from(Value::EqIntro)),
+            // The only proof of an equality is `refl`, so we don't need to
+            // evaluate it in order to know that the body can be evaluated
+            Term::EqElim(_, _, ref body) => self.eval(locals, body),
+
+            Term::Case(ref head, ref clauses) => {
+                let head = self.eval(locals, head)?;
+
+                for &(ref pattern, ref body) in clauses {
+                    if let Some(bindings) = self.match_value(locals, pattern, &head)? {
+                        let mut locals = locals.clone();
+                        locals.extend(bindings);
+                        return self.eval(&locals, body);
+                    }
+                }
+
+                Err(RuntimeError::new("no patterns applicable"))
+            },
+
+            Term::ArrayIntro(ref elems) => Ok(RcValue::from(Value::ArrayIntro(
+                elems
+                    .iter()
+                    .map(|elem| self.eval(locals, elem))
+                    .collect::<Result<_, _>>()?,
+            ))),
+        }
+    }
+
+    fn eval_fields(
+        &mut self,
+        locals: &Locals,
+        fields: &[(Label, RcTerm)],
+    ) -> Result<Vec<(Label, RcValue)>, RuntimeError> {
+        fields
+            .iter()
+            .map(|&(ref label, ref term)| Ok((label.clone(), self.eval(locals, term)?)))
+            .collect()
+    }
+
+    /// Evaluate the definition of a global variable, reusing the value from
+    /// a previous lookup if there is one
+    ///
+    /// Globals without definitions are the built-in types, and so are erased.
+    fn global(&mut self, free_var: &FreeVar<String>) -> Result<RcValue, RuntimeError> {
+        if let Some(value) = self.globals.get(free_var) {
+            return Ok(value.clone());
+        }
+
+        let env = self.env;
+        let value = match env.get_definition(free_var) {
+            Some(term) => self.eval_term(term)?,
+            None => RcValue::from(Value::Erased),
+        };
+        self.globals.insert(free_var.clone(), value.clone());
+
+        Ok(value)
+    }
+
+    /// Evaluate an import, reusing the value from a previous lookup if there
+    /// is one
+    fn import(&mut self, name: &str) -> Result<RcValue, RuntimeError> {
+        if let Some(value) = self.imports.get(name) {
+            return Ok(value.clone());
+        }
+
+        let env = self.env;
+        let value = match env.get_import(name) {
+            Some(&Import::Term(ref term)) => self.eval_term(term)?,
+            Some(&Import::Prim(_, _)) => self.prim_app(name, Vec::new())?,
+            None => return Err(RuntimeError::new(format!("unknown import `{}`", name))),
+        };
+        self.imports.insert(name.to_owned(), value.clone());
+
+        Ok(value)
+    }
+
+    /// Unfold a fixed point, evaluating its body with the fixed point itself
+    /// as the value of its binder
+    fn unfold_fix(&mut self, value: &RcValue) -> Result<RcValue, RuntimeError> {
+        match **value {
+            Value::Fix(ref body) => self.apply_closure(body, value.clone()),
+            _ => Ok(value.clone()),
+        }
+    }
+
+    /// Apply a closure to an argument
+    fn apply_closure(&mut self, closure: &Closure, arg: RcValue) -> Result<RcValue, RuntimeError> {
+        let mut locals = closure.locals.clone();
+        locals.push_back(arg);
+
+        self.eval(&locals, &closure.body)
+    }
+
+    /// Apply a function to an argument
+    fn fun_app(&mut self, head: &RcValue, arg: RcValue) -> Result<RcValue, RuntimeError> {
+        match **head {
+            Value::FunIntro(ref body) => self.apply_closure(body, arg),
+            Value::Prim(ref name, ref args) => {
+                let mut args = args.clone();
+                args.push(arg);
+
+                self.prim_app(name, args)
+            },
+            // Type constructors can still be applied in terms that have not
+            // been erased
+            Value::Erased => Ok(head.clone()),
+            _ => Err(RuntimeError::new("argument applied to non function")),
+        }
+    }
+
+    /// Call a primitive if enough arguments have been supplied, otherwise
+    /// wait for more arguments
+    fn prim_app(&mut self, name: &str, args: Vec<RcValue>) -> Result<RcValue, RuntimeError> {
+        let (arity, interpretation) = match self.env.get_import(name) {
+            Some(&Import::Prim(arity, interpretation)) => (arity, interpretation),
+            Some(&Import::Term(_)) | None => {
+                return Err(RuntimeError::new(format!("unknown primitive `{}`", name)));
+            },
+        };
+
+        // Primitives are only ever defined over literals
+        let domain_args = args
+            .iter()
+            .map(|arg| match **arg {
+                Value::Literal(ref literal) => {
+                    domain::RcValue::from(domain::Value::Literal(literal.clone()))
+                },
+                _ => domain::RcValue::from(domain::Value::Erased),
+            })
+            .collect::<Vec<_>>();
+
+        match interpretation(&domain_args) {
+            None if args.len() < arity => Ok(RcValue::from(Value::Prim(name.to_owned(), args))),
+            // The primitive has all of its arguments, so it must be undefined
+            // for them, as it is when dividing by zero
+            None => Err(RuntimeError::new(format!(
+                "primitive `{}` is undefined for its arguments",
+                name
+            ))),
+            Some(result) => match *result {
+                domain::Value::Literal(ref literal) => {
+                    Ok(RcValue::from(Value::Literal(literal.clone())))
+                },
+                _ => Err(RuntimeError::new(format!(
+                    "primitive `{}` returned a value that is not a literal",
+                    name
+                ))),
+            },
+        }
+    }
+
+    /// If the pattern matches the value, this function returns the values of
+    /// the variables that the pattern binds
+    fn match_value(
+        &mut self,
+        locals: &Locals,
+        pattern: &RcPattern,
+        value: &RcValue,
+    ) -> Result<Option<Vec<RcValue>>, RuntimeError> {
+        match (&*pattern.inner, &*value.inner) {
+            (&Pattern::Ann(ref pattern, _), _) => self.match_value(locals, pattern, value),
+            (&Pattern::Binder(_), _) => Ok(Some(vec![value.clone()])),
+            (&Pattern::Var(index, _), _) => match index.to_level(locals.len()) {
+                Some(level) if locals[level.0] == *value => Ok(Some(vec![])),
+                Some(_) => Ok(None),
+                None => Err(RuntimeError::new(format!(
+                    "unbound local variable `{}`",
+                    index
+                ))),
+            },
+            (&Pattern::Global(ref free_var, _), _) => {
+                if self.global(free_var)? == *value {
+                    Ok(Some(vec![]))
+                } else {
+                    Ok(None)
+                }
+            },
+            (&Pattern::Literal(ref pattern_lit), &Value::Literal(ref value_lit))
+                if pattern_lit == value_lit =>
+            {
+                Ok(Some(vec![]))
+            },
+            (
+                &Pattern::VariantIntro(ref pattern_label, ref pattern),
+                &Value::VariantIntro(ref value_label, ref value),
+            ) if pattern_label == value_label => self.match_value(locals, pattern, value),
+            (&Pattern::RecordIntro(ref pattern_fields), &Value::RecordIntro(ref value_fields)) => {
+                let mut bindings = Vec::new();
+                for &(ref label, ref pattern) in pattern_fields {
+                    let value = match value_fields.iter().find(|&&(ref l, _)| l == label) {
+                        Some(&(_, ref value)) => value,
+                        None => return Ok(None),
+                    };
+                    match self.match_value(locals, pattern, value)? {
+                        Some(field_bindings) => bindings.extend(field_bindings),
+                        None => return Ok(None),
+                    }
+                }
+                Ok(Some(bindings))
+            },
+            (&Pattern::ArrayIntro(ref patterns), &Value::ArrayIntro(ref values))
+                if patterns.len() == values.len() =>
+            {
+                let mut bindings = Vec::new();
+                for (pattern, value) in Iterator::zip(patterns.iter(), values.iter()) {
+                    match self.match_value(locals, pattern, value)? {
+                        Some(elem_bindings) => bindings.extend(elem_bindings),
+                        None => return Ok(None),
+                    }
+                }
+                Ok(Some(bindings))
+            },
+            (_, _) => Ok(None),
+        }
+    }
+}
+
+/// Project on a field of a record
+fn record_proj(expr: &RcValue, label: &Label) -> Result<RcValue, RuntimeError> {
+    if let Value::RecordIntro(ref fields) = **expr {
+        for &(ref current_label, ref current_expr) in fields {
+            if current_label == label {
+                return Ok(current_expr.clone());
+            }
+        }
+    }
+
+    Err(RuntimeError::new(format!(
+        "projected on non existent field `{}`",
+        label
+    )))
+}
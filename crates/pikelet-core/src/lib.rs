@@ -1,4 +1,6 @@
 //! The syntax of the language
 
+pub mod erase;
+pub mod eval;
 pub mod nbe;
 pub mod syntax;
@@ -1,8 +1,32 @@
-use moniker::{Binder, Embed, FreeVar, Nest, Scope, Var};
+//! Normalization by evaluation
+//!
+//! Terms are _evaluated_ into values, where the bodies of binders are stored
+//! as closures, along with the values of the local variables that were in
+//! scope at the time. Applying a function extends the local environment of
+//! its closure with the argument and continues evaluating the body, rather
+//! than substituting the argument into the body. Values can then be _quoted_
+//! (or _read back_) into terms in normal form, evaluating the bodies of the
+//! closures along the way.
+//!
+//! Terms refer to local variables using de Bruijn indices, while values refer
+//! to them using de Bruijn levels. Levels stay the same no matter how many
+//! binders a value is moved underneath, so closures never need to be
+//! traversed in order to rename their variables. When quoting we keep track of
+//! the number of variables in scope, converting levels back into indices.
+//!
+//! Values that were produced by unfolding definitions remember the variables
+//! that they were unfolded from. These _glued_ values are compared using their
+//! unfolded forms, but can be read back in their folded forms when they are
+//! displayed to the user, keeping type synonyms intact in error messages.
+
+use moniker::FreeVar;
 
 use crate::syntax::core::{Pattern, RcPattern, RcTerm, Term};
-use crate::syntax::domain::{Head, Neutral, RcNeutral, RcValue, Value};
-use crate::syntax::Import;
+use crate::syntax::domain::{
+    Alternatives, Clauses, Closure, Folded, FoldedHead, Head, LevelValue, Locals, Neutral,
+    RcNeutral, RcValue, Telescope, Value,
+};
+use crate::syntax::{Import, Label, Level, LevelShift, Literal, MetaVar, Multiplicity, Totality};
 
 /// An error produced during normalization
 ///
@@ -26,39 +50,177 @@ impl NbeError {
 pub trait Env {
     fn get_import(&self, name: &str) -> Option<&Import>;
     fn get_definition(&self, free_var: &FreeVar<String>) -> Option<&RcTerm>;
+    fn get_meta_solution(&self, meta: MetaVar) -> Option<RcTerm>;
+
+    /// Look up the literal that a global is defined as, like the `true` and
+    /// `false` that `if` expressions are desugared to match on
+    ///
+    /// Returns `None` if the global is not defined as a literal.
+    fn get_literal(&self, free_var: &FreeVar<String>) -> Option<&Literal> {
+        match *self.get_definition(free_var)?.strip_ann().inner {
+            Term::Literal(ref literal) => Some(literal),
+            _ => None,
+        }
+    }
+
+    /// Returns `true` if fixed points with the given totality should be
+    /// unfolded during normalization
+    fn unfold_fix(&self, _totality: Totality) -> bool {
+        true
+    }
+
+    /// Returns `true` if values that were produced by unfolding definitions
+    /// should be read back in their folded forms
+    fn quote_folded(&self) -> bool {
+        false
+    }
+}
+
+/// An environment where every fixed point is unfolded, including partial ones
+///
+/// This should be used when evaluating terms, as opposed to normalizing them
+/// during type checking, where unfolding a partial definition might never
+/// terminate.
+pub struct EvalEnv<'a>(pub &'a dyn Env);
+
+impl<'a> Env for EvalEnv<'a> {
+    fn get_import(&self, name: &str) -> Option<&Import> {
+        self.0.get_import(name)
+    }
+
+    fn get_definition(&self, free_var: &FreeVar<String>) -> Option<&RcTerm> {
+        self.0.get_definition(free_var)
+    }
+
+    fn get_meta_solution(&self, meta: MetaVar) -> Option<RcTerm> {
+        self.0.get_meta_solution(meta)
+    }
+
+    fn unfold_fix(&self, _: Totality) -> bool {
+        true
+    }
+
+    fn quote_folded(&self) -> bool {
+        self.0.quote_folded()
+    }
 }
 
-/// Reduce a term to its normal form
-pub fn nf_term(env: &dyn Env, term: &RcTerm) -> Result<RcValue, NbeError> {
+/// An environment where fixed points are no longer unfolded
+///
+/// We read back the clauses of stuck case expressions and the alternatives of
+/// variant types in this environment - otherwise recursive calls in those
+/// clauses and recursive variant types would be unfolded forever!
+struct StuckEnv<'a>(&'a dyn Env);
+
+impl<'a> Env for StuckEnv<'a> {
+    fn get_import(&self, name: &str) -> Option<&Import> {
+        self.0.get_import(name)
+    }
+
+    fn get_definition(&self, free_var: &FreeVar<String>) -> Option<&RcTerm> {
+        self.0.get_definition(free_var)
+    }
+
+    fn get_meta_solution(&self, meta: MetaVar) -> Option<RcTerm> {
+        self.0.get_meta_solution(meta)
+    }
+
+    fn unfold_fix(&self, _: Totality) -> bool {
+        false
+    }
+
+    fn quote_folded(&self) -> bool {
+        self.0.quote_folded()
+    }
+}
+
+/// Evaluate a term that does not refer to any local variables
+pub fn eval_term(env: &dyn Env, term: &RcTerm) -> Result<RcValue, NbeError> {
+    eval(env, &Locals::new(), term)
+}
+
+/// Evaluate a term, looking up the values of local variables in `locals`
+pub fn eval(env: &dyn Env, locals: &Locals, term: &RcTerm) -> Result<RcValue, NbeError> {
     match *term.inner {
         // E-ANN
-        Term::Ann(ref expr, _) => nf_term(env, expr),
+        Term::Ann(ref expr, _) => eval(env, locals, expr),
 
         // E-TYPE
         Term::Universe(level) => Ok(RcValue::from(Value::Universe(level))),
+        Term::UniverseAt(ref level) => {
+            let level = eval(env, locals, level)?;
+            match LevelValue::from_value(&level) {
+                Some(level) => Ok(RcValue::from(Value::universe_at(level))),
+                None => Ok(RcValue::from(Value::UniverseAt(level))),
+            }
+        },
+        Term::UniverseOmega => Ok(RcValue::from(Value::UniverseOmega)),
+
+        // E-LEVEL-TYPE
+        Term::LevelType => Ok(RcValue::from(Value::LevelType)),
+
+        // E-LEVEL
+        Term::LevelIntro(level) => Ok(RcValue::from(Value::Level(LevelValue::constant(level)))),
+
+        // E-LEVEL-MAX
+        Term::LevelMax(ref level1, ref level2) => {
+            let level1 = eval(env, locals, level1)?;
+            let level2 = eval(env, locals, level2)?;
+            match (
+                LevelValue::from_value(&level1),
+                LevelValue::from_value(&level2),
+            ) {
+                (Some(level1), Some(level2)) => Ok(RcValue::from(level1.max(&level2).into_value())),
+                (_, _) => Err(NbeError::new("maximum of levels that are not yet known")),
+            }
+        },
+
+        // E-LEVEL-SHIFT
+        Term::LevelShift(ref level, shift) => {
+            let level = eval(env, locals, level)?;
+            match LevelValue::from_value(&level) {
+                Some(mut level) => {
+                    level.shift_universes(shift);
+                    Ok(RcValue::from(level.into_value()))
+                },
+                None => Err(NbeError::new("shifted a level that is not yet known")),
+            }
+        },
 
         Term::Literal(ref lit) => Ok(RcValue::from(Value::Literal(lit.clone()))),
 
-        // E-VAR, E-VAR-DEF
-        Term::Var(ref var, shift) => match *var {
-            Var::Free(ref name) => match env.get_definition(name) {
-                Some(term) => {
-                    let mut value = nf_term(env, term)?;
-                    value.shift_universes(shift);
-                    Ok(value)
+        // E-VAR
+        Term::Var(index, shift) => match index.to_level(locals.len()) {
+            // Local variables are left unshifted, unless they have yet to be
+            // given a value or refer to universe levels
+            Some(level) => match *locals[level.0].inner {
+                Value::Level(ref level) => {
+                    let mut level = level.clone();
+                    level.shift_universes(shift);
+                    Ok(RcValue::from(Value::Level(level)))
+                },
+                _ => match locals[level.0].var_level() {
+                    Some(level) if shift.0 != 0 => Ok(RcValue::from(Value::var(level, shift))),
+                    Some(_) | None => unfold_fix(env, &locals[level.0]),
                 },
-                None => Ok(RcValue::from(Value::var(var.clone(), shift))),
             },
+            None => Err(NbeError::new(format!("unbound local variable `{}`", index))),
+        },
 
-            // We should always be substituting bound variables with fresh
-            // variables when entering scopes using `unbind`, so if we've
-            // encountered one here this is definitely a bug!
-            Var::Bound(_) => Err(NbeError::new(format!("unexpected bound var `{}`", var))),
+        // E-VAR-DEF
+        Term::Global(ref free_var, shift) => match env.get_definition(free_var) {
+            Some(term) => {
+                let mut value = eval_term(env, term)?;
+                value.shift_universes(shift);
+                let head = FoldedHead::Global(free_var.clone(), shift);
+                Ok(value.with_folded(Folded::new(head)))
+            },
+            None => Ok(RcValue::from(Value::global(free_var.clone(), shift))),
         },
 
         Term::Import(ref name) => match env.get_import(name) {
-            Some(&Import::Term(ref term)) => nf_term(env, term),
-            Some(&Import::Prim(ref interpretation)) => match interpretation(&[]) {
+            Some(&Import::Term(ref term)) => eval_term(env, term),
+            Some(&Import::Prim(_, ref interpretation)) => match interpretation(&[]) {
                 Some(value) => Ok(value),
                 None => Ok(RcValue::from(Value::from(Neutral::Head(Head::Import(
                     name.clone(),
@@ -69,158 +231,150 @@ pub fn nf_term(env: &dyn Env, term: &RcTerm) -> Result<RcValue, NbeError> {
             ))))),
         },
 
-        // E-PI
-        Term::FunType(ref scope) => {
-            let ((name, Embed(ann)), body) = scope.clone().unbind();
+        Term::Meta(meta) => match env.get_meta_solution(meta) {
+            Some(term) => eval_term(env, &term),
+            None => Ok(RcValue::from(Value::from(Neutral::Head(Head::Meta(meta))))),
+        },
 
-            Ok(RcValue::from(Value::FunType(Scope::new(
-                (name, Embed(nf_term(env, &ann)?)),
-                nf_term(env, &body)?,
-            ))))
+        // E-PI
+        Term::FunType(plicity, multiplicity, ref name, ref ann, ref body) => {
+            Ok(RcValue::from(Value::FunType(
+                plicity,
+                multiplicity,
+                eval(env, locals, ann)?,
+                Closure::new(locals.clone(), name.clone(), body.clone()),
+            )))
         },
 
         // E-LAM
-        Term::FunIntro(ref scope) => {
-            let ((name, Embed(ann)), body) = scope.clone().unbind();
-
-            Ok(RcValue::from(Value::FunIntro(Scope::new(
-                (name, Embed(nf_term(env, &ann)?)),
-                nf_term(env, &body)?,
-            ))))
+        Term::FunIntro(plicity, multiplicity, ref name, ref ann, ref body) => {
+            Ok(RcValue::from(Value::FunIntro(
+                plicity,
+                multiplicity,
+                eval(env, locals, ann)?,
+                Closure::new(locals.clone(), name.clone(), body.clone()),
+            )))
         },
 
         // E-APP
-        Term::FunApp(ref head, ref arg) => {
-            match *nf_term(env, head)?.inner {
-                Value::FunIntro(ref scope) => {
-                    // FIXME: do a local unbind here
-                    let ((Binder(free_var), Embed(_)), body) = scope.clone().unbind();
-                    nf_term(env, &body.substs(&[(free_var, arg.clone())]))
-                },
-                Value::Neutral(ref neutral, ref spine) => {
-                    let arg = nf_term(env, arg)?;
-                    let mut spine = spine.clone();
-
-                    match *neutral.inner {
-                        Neutral::Head(Head::Import(ref name)) => {
-                            spine.push(arg);
-
-                            match env.get_import(name) {
-                                Some(&Import::Term(ref _term)) => {
-                                    // nf_term(env, term)
-                                    unimplemented!("import applications")
-                                },
-                                Some(&Import::Prim(ref interpretation)) => {
-                                    match interpretation(&spine) {
-                                        Some(value) => return Ok(value),
-                                        None => {},
-                                    }
-                                },
-                                None => {},
-                            }
-                        },
-                        Neutral::Head(Head::Var(..))
-                        | Neutral::RecordProj(..)
-                        | Neutral::Case(..) => spine.push(arg),
-                    }
+        Term::FunApp(ref head, _, ref arg) => {
+            let head = eval(env, locals, head)?;
+            let arg = eval(env, locals, arg)?;
 
-                    Ok(RcValue::from(Value::Neutral(neutral.clone(), spine)))
-                },
-                _ => Err(NbeError::new("argument applied to non function")),
-            }
+            fun_app(env, &head, arg)
         },
 
         // E-LET
-        Term::Let(ref scope) => {
-            let (bindings, body) = scope.clone().unbind();
-            let mut mappings = Vec::with_capacity(bindings.unsafe_patterns.len());
+        Term::Let(ref bindings, ref body) => {
+            let mut locals = locals.clone();
 
-            for (Binder(free_var), Embed(term)) in bindings.unnest() {
-                let value = nf_term(env, &term.substs(&mappings))?;
-                mappings.push((free_var, RcTerm::from(&*value.inner)));
+            for &(_, ref term) in bindings {
+                let value = eval(env, &locals, term)?;
+                locals.push_back(value);
             }
 
-            nf_term(env, &body.substs(&mappings))
+            eval(env, &locals, body)
         },
 
-        // E-RECORD-TYPE, E-EMPTY-RECORD-TYPE
-        Term::RecordType(ref scope) => {
-            let (fields, ()) = scope.clone().unbind();
-            let fields = Nest::new(
-                fields
-                    .unnest()
-                    .into_iter()
-                    .map(|(label, binder, Embed(ann))| {
-                        Ok((label, binder, Embed(nf_term(env, &ann)?)))
-                    })
-                    .collect::<Result<_, _>>()?,
+        // E-FIX
+        Term::Fix(totality, ref name, ref ann, ref body) => {
+            let head = Head::Fix(
+                totality,
+                eval(env, locals, ann)?,
+                Closure::new(locals.clone(), name.clone(), body.clone()),
             );
 
-            Ok(RcValue::from(Value::RecordType(Scope::new(fields, ()))))
+            unfold_fix(env, &RcValue::from(Value::from(Neutral::Head(head))))
         },
 
+        // E-RECORD-TYPE, E-EMPTY-RECORD-TYPE
+        Term::RecordType(ref fields) => Ok(RcValue::from(Value::RecordType(Telescope {
+            locals: locals.clone(),
+            fields: fields.clone(),
+        }))),
+
         // E-RECORD, E-EMPTY-RECORD
         Term::RecordIntro(ref fields) => {
             let fields = fields
                 .iter()
-                .map(|&(ref label, ref term)| Ok((label.clone(), nf_term(env, &term)?)))
+                .map(|&(ref label, _, ref term)| Ok((label.clone(), eval(env, locals, &term)?)))
                 .collect::<Result<_, _>>()?;
 
             Ok(RcValue::from(Value::RecordIntro(fields)))
         },
 
         // E-PROJ
-        Term::RecordProj(ref expr, ref label, shift) => {
-            match *nf_term(env, expr)? {
-                Value::Neutral(ref neutral, ref spine) => {
-                    return Ok(RcValue::from(Value::Neutral(
-                        RcNeutral::from(Neutral::RecordProj(neutral.clone(), label.clone(), shift)),
-                        spine.clone(),
-                    )));
-                },
-                Value::RecordIntro(ref fields) => {
-                    for &(ref current_label, ref current_expr) in fields {
-                        if current_label == label {
-                            return Ok(current_expr.clone());
-                        }
-                    }
-                },
-                _ => {},
-            }
+        Term::RecordProj(ref expr, ref label, _, shift) => {
+            record_proj(&eval(env, locals, expr)?, label, shift)
+        },
 
-            Err(NbeError::new(format!(
-                "projected on non existent field `{}`",
-                label
-            )))
+        // E-RECORD-EXTEND
+        Term::RecordExtend(ref expr, ref fields) => {
+            let fields = fields
+                .iter()
+                .map(|&(ref label, ref term)| Ok((label.clone(), eval(env, locals, &term)?)))
+                .collect::<Result<_, _>>()?;
+
+            record_extend(&eval(env, locals, expr)?, fields)
+        },
+
+        // E-VARIANT-TYPE, E-EMPTY-VARIANT-TYPE
+        Term::VariantType(ref alts) => Ok(RcValue::from(Value::VariantType(Alternatives {
+            locals: locals.clone(),
+            alts: alts.clone(),
+        }))),
+
+        // E-VARIANT
+        Term::VariantIntro(ref label, ref arg) => Ok(RcValue::from(Value::VariantIntro(
+            label.clone(),
+            eval(env, locals, arg)?,
+        ))),
+
+        // E-EQ-TYPE
+        Term::EqType(ref ty, ref lhs, ref rhs) => Ok(RcValue::from(Value::EqType(
+            eval(env, locals, ty)?,
+            eval(env, locals, lhs)?,
+            eval(env, locals, rhs)?,
+        ))),
+
+        // E-REFL
+        Term::EqIntro => Ok(RcValue::from(Value::EqIntro)),
+
+        // E-REWRITE
+        Term::EqElim(ref motive, ref proof, ref body) => match *eval(env, locals, proof)? {
+            Value::EqIntro => eval(env, locals, body),
+            Value::Neutral(ref neutral, ref spine) => Ok(RcValue::from(Value::Neutral(
+                RcNeutral::from(Neutral::EqElim(
+                    eval(&StuckEnv(env), locals, motive)?,
+                    neutral.clone(),
+                    eval(&StuckEnv(env), locals, body)?,
+                )),
+                spine.clone(),
+            ))),
+            _ => Err(NbeError::new("rewrite with a non-equality proof")),
         },
 
         // E-CASE
         Term::Case(ref head, ref clauses) => {
-            let head = nf_term(env, head)?;
+            let head = eval(env, locals, head)?;
 
             if let Value::Neutral(ref neutral, ref spine) = *head {
                 Ok(RcValue::from(Value::Neutral(
                     RcNeutral::from(Neutral::Case(
                         neutral.clone(),
-                        clauses
-                            .iter()
-                            .map(|clause| {
-                                let (pattern, body) = clause.clone().unbind();
-                                Ok(Scope::new(pattern, nf_term(env, &body)?))
-                            })
-                            .collect::<Result<_, _>>()?,
+                        Clauses {
+                            locals: locals.clone(),
+                            clauses: clauses.clone(),
+                        },
                     )),
                     spine.clone(),
                 )))
             } else {
-                for clause in clauses {
-                    let (pattern, body) = clause.clone().unbind();
-                    if let Some(mappings) = match_value(env, &pattern, &head)? {
-                        let mappings = mappings
-                            .into_iter()
-                            .map(|(free_var, value)| (free_var, RcTerm::from(&*value.inner)))
-                            .collect::<Vec<_>>();
-                        return nf_term(env, &body.substs(&mappings));
+                for &(ref pattern, ref body) in clauses {
+                    if let Some(bindings) = match_value(env, locals, pattern, &head)? {
+                        let mut locals = locals.clone();
+                        locals.extend(bindings);
+                        return eval(env, &locals, body);
                     }
                 }
                 Err(NbeError::new("no patterns applicable"))
@@ -231,35 +385,492 @@ pub fn nf_term(env: &dyn Env, term: &RcTerm) -> Result<RcValue, NbeError> {
         Term::ArrayIntro(ref elems) => Ok(RcValue::from(Value::ArrayIntro(
             elems
                 .iter()
-                .map(|elem| nf_term(env, elem))
+                .map(|elem| eval(env, locals, elem))
+                .collect::<Result<_, _>>()?,
+        ))),
+
+        Term::Erased => Ok(RcValue::from(Value::Erased)),
+    }
+}
+
+/// Unfold a fixed point that has not been applied to any arguments, if the
+/// environment allows it
+///
+/// The body of a fixed point is evaluated with the fixed point itself as the
+/// value of its binder, so each reference to it is only unfolded once it is
+/// needed.
+fn unfold_fix(env: &dyn Env, value: &RcValue) -> Result<RcValue, NbeError> {
+    if let Value::Neutral(ref neutral, ref spine) = *value.inner {
+        if let Neutral::Head(Head::Fix(totality, _, ref body)) = *neutral.inner {
+            if spine.is_empty() && env.unfold_fix(totality) {
+                return apply_closure(env, body, value.clone());
+            }
+        }
+    }
+
+    Ok(value.clone())
+}
+
+/// Apply a closure to an argument
+pub fn apply_closure(env: &dyn Env, closure: &Closure, arg: RcValue) -> Result<RcValue, NbeError> {
+    let mut locals = closure.locals.clone();
+    locals.push_back(arg);
+
+    eval(env, &locals, &closure.body)
+}
+
+/// Apply a function to an argument
+///
+/// If the function was unfolded from a definition, then so is the result.
+pub fn fun_app(env: &dyn Env, head: &RcValue, arg: RcValue) -> Result<RcValue, NbeError> {
+    let value = fun_app_unfolded(env, head, arg.clone())?;
+
+    match head.folded {
+        Some(ref folded) => Ok(value.with_folded(folded.app(arg))),
+        None => Ok(value),
+    }
+}
+
+fn fun_app_unfolded(env: &dyn Env, head: &RcValue, arg: RcValue) -> Result<RcValue, NbeError> {
+    match *head.inner {
+        Value::FunIntro(_, _, _, ref body) => apply_closure(env, body, arg),
+        Value::Neutral(ref neutral, ref spine) => {
+            let mut spine = spine.clone();
+            spine.push(arg);
+
+            match *neutral.inner {
+                Neutral::Head(Head::Import(ref name)) => match env.get_import(name) {
+                    Some(&Import::Term(ref _term)) => {
+                        // eval_term(env, term)
+                        unimplemented!("import applications")
+                    },
+                    Some(&Import::Prim(_, ref interpretation)) => {
+                        if let Some(value) = interpretation(&spine) {
+                            return Ok(value);
+                        }
+                    },
+                    None => {},
+                },
+                Neutral::Head(Head::Var(..))
+                | Neutral::Head(Head::Global(..))
+                | Neutral::Head(Head::Meta(..))
+                | Neutral::Head(Head::Fix(..))
+                | Neutral::RecordProj(..)
+                | Neutral::RecordExtend(..)
+                | Neutral::EqElim(..)
+                | Neutral::Case(..) => {},
+            }
+
+            Ok(RcValue::from(Value::Neutral(neutral.clone(), spine)))
+        },
+        _ => Err(NbeError::new("argument applied to non function")),
+    }
+}
+
+/// Project on a field of a record
+pub fn record_proj(expr: &RcValue, label: &Label, shift: LevelShift) -> Result<RcValue, NbeError> {
+    match *expr.inner {
+        Value::Neutral(ref neutral, ref spine) => {
+            // The fields added by an extension are known, even if the record
+            // being extended is not
+            if let Neutral::RecordExtend(ref expr, ref fields) = *neutral.inner {
+                for &(ref current_label, ref current_expr) in fields {
+                    if current_label == label {
+                        return Ok(current_expr.clone());
+                    }
+                }
+
+                let expr = RcValue::from(Value::Neutral(expr.clone(), spine.clone()));
+                return record_proj(&expr, label, shift);
+            }
+
+            return Ok(RcValue::from(Value::Neutral(
+                RcNeutral::from(Neutral::RecordProj(neutral.clone(), label.clone(), shift)),
+                spine.clone(),
+            )));
+        },
+        Value::RecordIntro(ref fields) => {
+            for &(ref current_label, ref current_expr) in fields {
+                if current_label == label {
+                    return Ok(current_expr.clone());
+                }
+            }
+        },
+        _ => {},
+    }
+
+    Err(NbeError::new(format!(
+        "projected on non existent field `{}`",
+        label
+    )))
+}
+
+/// Add fields to the end of a record
+pub fn record_extend(expr: &RcValue, fields: Vec<(Label, RcValue)>) -> Result<RcValue, NbeError> {
+    match *expr.inner {
+        Value::Neutral(ref neutral, ref spine) => Ok(RcValue::from(Value::Neutral(
+            RcNeutral::from(Neutral::RecordExtend(neutral.clone(), fields)),
+            spine.clone(),
+        ))),
+        Value::RecordIntro(ref expr_fields) => {
+            let mut expr_fields = expr_fields.clone();
+            expr_fields.extend(fields);
+
+            Ok(RcValue::from(Value::RecordIntro(expr_fields)))
+        },
+        _ => Err(NbeError::new("extended a value that is not a record")),
+    }
+}
+
+/// Reduce a term that does not refer to any local variables to its normal
+/// form
+pub fn nf_term(env: &dyn Env, term: &RcTerm) -> Result<RcTerm, NbeError> {
+    quote(env, 0, &eval_term(env, term)?)
+}
+
+/// Reduce a term to its normal form, looking up the values of local variables
+/// in `locals`
+pub fn nf(env: &dyn Env, locals: &Locals, term: &RcTerm) -> Result<RcTerm, NbeError> {
+    quote(env, locals.len(), &eval(env, locals, term)?)
+}
+
+/// Apply a closure to a fresh variable, bound at the given level
+fn open_closure(env: &dyn Env, size: usize, closure: &Closure) -> Result<RcValue, NbeError> {
+    apply_closure(env, closure, RcValue::from(Value::var(size, 0)))
+}
+
+/// Read a value back into a term in normal form, where `size` is the number of
+/// local variables that are in scope
+pub fn quote(env: &dyn Env, size: usize, value: &RcValue) -> Result<RcTerm, NbeError> {
+    if env.quote_folded() {
+        if let Some(ref folded) = value.folded {
+            if let Some(term) = quote_folded(env, size, folded)? {
+                return Ok(term);
+            }
+        }
+    }
+
+    match *value.inner {
+        Value::Universe(level) => Ok(RcTerm::from(Term::Universe(level))),
+        Value::UniverseAt(ref level) => {
+            Ok(RcTerm::from(Term::UniverseAt(quote(env, size, level)?)))
+        },
+        Value::UniverseOmega => Ok(RcTerm::from(Term::UniverseOmega)),
+        Value::LevelType => Ok(RcTerm::from(Term::LevelType)),
+        Value::Level(ref level) => quote_level(env, size, level),
+        Value::Literal(ref lit) => Ok(RcTerm::from(Term::Literal(lit.clone()))),
+        Value::FunType(plicity, multiplicity, ref ann, ref body) => {
+            Ok(RcTerm::from(Term::FunType(
+                plicity,
+                multiplicity,
+                body.name.clone(),
+                quote(env, size, ann)?,
+                quote(env, size + 1, &open_closure(env, size, body)?)?,
+            )))
+        },
+        Value::FunIntro(plicity, multiplicity, ref ann, ref body) => {
+            Ok(RcTerm::from(Term::FunIntro(
+                plicity,
+                multiplicity,
+                body.name.clone(),
+                quote(env, size, ann)?,
+                quote(env, size + 1, &open_closure(env, size, body)?)?,
+            )))
+        },
+        Value::RecordType(ref telescope) => {
+            let mut locals = telescope.locals.clone();
+            let fields = telescope
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, &(ref label, ref name, multiplicity, ref ann))| {
+                    let ann = eval(env, &locals, ann)?;
+                    locals.push_back(RcValue::from(Value::var(size + i, 0)));
+                    Ok((
+                        label.clone(),
+                        name.clone(),
+                        multiplicity,
+                        quote(env, size + i, &ann)?,
+                    ))
+                })
+                .collect::<Result<_, _>>()?;
+
+            Ok(RcTerm::from(Term::RecordType(fields)))
+        },
+        // NOTE: Record values don't remember the multiplicities of their
+        // fields, so like with spines we assume that they are used many times
+        Value::RecordIntro(ref fields) => {
+            let fields = fields
+                .iter()
+                .map(|&(ref label, ref expr)| {
+                    Ok((label.clone(), Multiplicity::Many, quote(env, size, expr)?))
+                })
+                .collect::<Result<_, _>>()?;
+
+            Ok(RcTerm::from(Term::RecordIntro(fields)))
+        },
+        Value::VariantType(ref alts) => {
+            let env = &StuckEnv(env);
+            let alts = alts
+                .alts
+                .iter()
+                .map(|&(ref label, ref ann)| {
+                    let ann = eval(env, &alts.locals, ann)?;
+                    Ok((label.clone(), quote(env, size, &ann)?))
+                })
+                .collect::<Result<_, _>>()?;
+
+            Ok(RcTerm::from(Term::VariantType(alts)))
+        },
+        Value::VariantIntro(ref label, ref arg) => Ok(RcTerm::from(Term::VariantIntro(
+            label.clone(),
+            quote(env, size, arg)?,
+        ))),
+        Value::EqType(ref ty, ref lhs, ref rhs) => Ok(RcTerm::from(Term::EqType(
+            quote(env, size, ty)?,
+            quote(env, size, lhs)?,
+            quote(env, size, rhs)?,
+        ))),
+        Value::EqIntro => Ok(RcTerm::from(Term::EqIntro)),
+        Value::Erased => Ok(RcTerm::from(Term::Erased)),
+        Value::ArrayIntro(ref elems) => Ok(RcTerm::from(Term::ArrayIntro(
+            elems
+                .iter()
+                .map(|elem| quote(env, size, elem))
                 .collect::<Result<_, _>>()?,
         ))),
+        // NOTE: Spines don't remember the multiplicities of the parameters
+        // that their arguments were passed to, so we conservatively assume
+        // that they are used many times
+        Value::Neutral(ref neutral, ref spine) => {
+            spine
+                .iter()
+                .fold(quote_neutral(env, size, neutral), |acc, arg| {
+                    Ok(RcTerm::from(Term::FunApp(
+                        acc?,
+                        Multiplicity::Many,
+                        quote(env, size, arg)?,
+                    )))
+                })
+        },
+    }
+}
+
+/// Read a universe level back into a term, where `size` is the number of
+/// local variables that are in scope
+fn quote_level(env: &dyn Env, size: usize, level: &LevelValue) -> Result<RcTerm, NbeError> {
+    let mut terms = Vec::with_capacity(level.vars.len() + level.metas.len() + 1);
+    if level.as_constant().is_some() || level.constant != Level(0) {
+        terms.push(RcTerm::from(Term::LevelIntro(level.constant)));
+    }
+    for &(var, shift) in &level.vars {
+        let neutral = RcNeutral::from(Neutral::var(var, shift));
+        terms.push(quote_neutral(env, size, &neutral)?);
+    }
+    for &(meta, shift) in &level.metas {
+        terms.push(match shift {
+            LevelShift(0) => RcTerm::from(Term::Meta(meta)),
+            shift => RcTerm::from(Term::LevelShift(RcTerm::from(Term::Meta(meta)), shift)),
+        });
     }
+
+    let mut terms = terms.into_iter();
+    let first = terms.next().expect("levels should not be empty");
+    Ok(terms.fold(first, |acc, term| RcTerm::from(Term::LevelMax(acc, term))))
+}
+
+/// Read back the folded form of a value, returning `None` if it refers to a
+/// local variable that is no longer in scope
+fn quote_folded(env: &dyn Env, size: usize, folded: &Folded) -> Result<Option<RcTerm>, NbeError> {
+    let head = match folded.head {
+        FoldedHead::Var(level, shift) => match level.to_index(size) {
+            Some(index) => RcTerm::from(Term::Var(index, shift)),
+            None => return Ok(None),
+        },
+        FoldedHead::Global(ref free_var, shift) => {
+            RcTerm::from(Term::Global(free_var.clone(), shift))
+        },
+    };
+
+    let term = folded.spine.iter().fold(Ok(head), |acc, arg| {
+        Ok(RcTerm::from(Term::FunApp(
+            acc?,
+            Multiplicity::Many,
+            quote(env, size, arg)?,
+        )))
+    })?;
+
+    Ok(Some(term))
 }
 
-/// If the pattern matches the value, this function returns the substitutions
-/// needed to apply the pattern to some body expression
+/// Read a neutral value back into a term in normal form, where `size` is the
+/// number of local variables that are in scope
+pub fn quote_neutral(env: &dyn Env, size: usize, neutral: &RcNeutral) -> Result<RcTerm, NbeError> {
+    match *neutral.inner {
+        Neutral::Head(Head::Var(level, shift)) => match level.to_index(size) {
+            Some(index) => Ok(RcTerm::from(Term::Var(index, shift))),
+            None => Err(NbeError::new(format!(
+                "local variable at level `{}` escaped its scope",
+                level,
+            ))),
+        },
+        Neutral::Head(Head::Global(ref free_var, shift)) => {
+            Ok(RcTerm::from(Term::Global(free_var.clone(), shift)))
+        },
+        Neutral::Head(Head::Import(ref name)) => Ok(RcTerm::from(Term::Import(name.clone()))),
+        Neutral::Head(Head::Meta(meta)) => Ok(RcTerm::from(Term::Meta(meta))),
+        Neutral::Head(Head::Fix(totality, ref ann, ref body)) => Ok(RcTerm::from(Term::Fix(
+            totality,
+            body.name.clone(),
+            quote(env, size, ann)?,
+            quote(env, size + 1, &open_closure(env, size, body)?)?,
+        ))),
+        Neutral::RecordProj(ref expr, ref label, shift) => Ok(RcTerm::from(Term::RecordProj(
+            quote_neutral(env, size, expr)?,
+            label.clone(),
+            Multiplicity::Many,
+            shift,
+        ))),
+        Neutral::RecordExtend(ref expr, ref fields) => {
+            let fields = fields
+                .iter()
+                .map(|&(ref label, ref expr)| Ok((label.clone(), quote(env, size, expr)?)))
+                .collect::<Result<_, _>>()?;
+
+            Ok(RcTerm::from(Term::RecordExtend(
+                quote_neutral(env, size, expr)?,
+                fields,
+            )))
+        },
+        Neutral::EqElim(ref motive, ref proof, ref body) => Ok(RcTerm::from(Term::EqElim(
+            quote(env, size, motive)?,
+            quote_neutral(env, size, proof)?,
+            quote(env, size, body)?,
+        ))),
+        Neutral::Case(ref head, ref clauses) => {
+            let env = &StuckEnv(env);
+            let head = quote_neutral(env, size, head)?;
+            let clauses = clauses
+                .clauses
+                .iter()
+                .map(|&(ref pattern, ref body)| {
+                    let binders = pattern.binders();
+                    let mut locals = clauses.locals.clone();
+                    locals.extend((0..binders).map(|i| RcValue::from(Value::var(size + i, 0))));
+                    let pattern = quote_pattern(env, &clauses.locals, size, pattern)?;
+                    let body = eval(env, &locals, body)?;
+
+                    Ok((pattern, quote(env, size + binders, &body)?))
+                })
+                .collect::<Result<_, _>>()?;
+
+            Ok(RcTerm::from(Term::Case(head, clauses)))
+        },
+    }
+}
+
+/// Read back the pattern of a clause whose variables were in scope of
+/// `locals`, so that its variables are in scope of `size` local variables
+fn quote_pattern(
+    env: &dyn Env,
+    locals: &Locals,
+    size: usize,
+    pattern: &RcPattern,
+) -> Result<RcPattern, NbeError> {
+    let pattern = match *pattern.inner {
+        Pattern::Ann(ref pattern, ref ty) => Pattern::Ann(
+            quote_pattern(env, locals, size, pattern)?,
+            quote(env, size, &eval(env, locals, ty)?)?,
+        ),
+        Pattern::Binder(_) | Pattern::Global(_, _) | Pattern::Literal(_) => {
+            return Ok(pattern.clone());
+        },
+        Pattern::Var(index, shift) => {
+            let var = RcTerm::from(Term::Var(index, shift));
+            match *quote(env, size, &eval(env, locals, &var)?)?.inner {
+                Term::Var(index, shift) => Pattern::Var(index, shift),
+                Term::Global(ref free_var, shift) => Pattern::Global(free_var.clone(), shift),
+                Term::Literal(ref literal) => Pattern::Literal(literal.clone()),
+                _ => return Err(NbeError::new("unable to read back pattern variable")),
+            }
+        },
+        Pattern::VariantIntro(ref label, ref pattern) => {
+            Pattern::VariantIntro(label.clone(), quote_pattern(env, locals, size, pattern)?)
+        },
+        Pattern::RecordIntro(ref fields) => Pattern::RecordIntro(
+            fields
+                .iter()
+                .map(|&(ref label, ref pattern)| {
+                    Ok((label.clone(), quote_pattern(env, locals, size, pattern)?))
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        Pattern::ArrayIntro(ref patterns) => Pattern::ArrayIntro(
+            patterns
+                .iter()
+                .map(|pattern| quote_pattern(env, locals, size, pattern))
+                .collect::<Result<_, _>>()?,
+        ),
+    };
+
+    Ok(RcPattern::from(pattern))
+}
+
+/// If the pattern matches the value, this function returns the values of
+/// the variables bound by the pattern, in order
 pub fn match_value(
     env: &dyn Env,
+    locals: &Locals,
     pattern: &RcPattern,
     value: &RcValue,
-) -> Result<Option<Vec<(FreeVar<String>, RcValue)>>, NbeError> {
+) -> Result<Option<Vec<RcValue>>, NbeError> {
     match (&*pattern.inner, &*value.inner) {
-        (&Pattern::Binder(Binder(ref free_var)), _) => {
-            Ok(Some(vec![(free_var.clone(), value.clone())]))
-        },
-        (&Pattern::Var(Embed(Var::Free(ref free_var)), _), _) => {
-            match env.get_definition(free_var).map(|term| nf_term(env, term)) {
-                Some(Ok(ref term)) if term == value => Ok(Some(vec![])),
-                Some(Ok(_)) | None => Ok(None),
-                Some(Err(err)) => Err(err),
-            }
+        (&Pattern::Ann(ref pattern, _), _) => match_value(env, locals, pattern, value),
+        (&Pattern::Binder(_), _) => Ok(Some(vec![value.clone()])),
+        (&Pattern::Var(index, _), _) => match index.to_level(locals.len()) {
+            Some(level) if locals[level.0] == *value => Ok(Some(vec![])),
+            Some(_) => Ok(None),
+            None => Err(NbeError::new(format!("unbound local variable `{}`", index))),
+        },
+        (&Pattern::Global(ref free_var, _), _) => match env.get_definition(free_var) {
+            Some(term) if eval_term(env, term)? == *value => Ok(Some(vec![])),
+            Some(_) | None => Ok(None),
         },
         (&Pattern::Literal(ref pattern_lit), &Value::Literal(ref value_lit))
             if pattern_lit == value_lit =>
         {
             Ok(Some(vec![]))
         },
+        (
+            &Pattern::VariantIntro(ref pattern_label, ref pattern),
+            &Value::VariantIntro(ref value_label, ref value),
+        ) if pattern_label == value_label => match_value(env, locals, pattern, value),
+        (&Pattern::RecordIntro(ref pattern_fields), &Value::RecordIntro(ref value_fields)) => {
+            let mut bindings = Vec::new();
+            for &(ref label, ref pattern) in pattern_fields {
+                let value = match value_fields.iter().find(|&&(ref l, _)| l == label) {
+                    Some(&(_, ref value)) => value,
+                    None => return Ok(None),
+                };
+                match match_value(env, locals, pattern, value)? {
+                    Some(field_bindings) => bindings.extend(field_bindings),
+                    None => return Ok(None),
+                }
+            }
+            Ok(Some(bindings))
+        },
+        (&Pattern::ArrayIntro(ref patterns), &Value::ArrayIntro(ref values))
+            if patterns.len() == values.len() =>
+        {
+            let mut bindings = Vec::new();
+            for (pattern, value) in Iterator::zip(patterns.iter(), values.iter()) {
+                match match_value(env, locals, pattern, value)? {
+                    Some(elem_bindings) => bindings.extend(elem_bindings),
+                    None => return Ok(None),
+                }
+            }
+            Ok(Some(bindings))
+        },
         (_, _) => Ok(None),
     }
 }
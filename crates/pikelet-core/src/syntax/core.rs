@@ -1,45 +1,169 @@
 //! The core syntax of the language
-
-use moniker::{Binder, Embed, FreeVar, Nest, Scope, Var};
+//!
+//! Local variables are referred to by their de Bruijn index, and binders only
+//! keep the names they were given in the source code as hints for display.
+//! This means that alpha-equivalent terms are equal, without needing to rename
+//! any variables. Top-level definitions, like those that are added to the
+//! driver or the built-in types, are referred to by name as global variables.
+
+use im;
+use moniker::FreeVar;
 use pretty::{BoxDoc, Doc};
 use std::fmt;
 use std::ops;
 use std::rc::Rc;
 
-use crate::syntax::domain::{Head, Neutral, Value};
-use crate::syntax::{Label, Level, LevelShift, Literal, PRETTY_FALLBACK_WIDTH};
+use crate::syntax::{
+    Label, Level, LevelShift, Literal, MetaVar, Multiplicity, NameHint, Plicity, Totality,
+    VarIndex, PRETTY_FALLBACK_WIDTH,
+};
+
+/// The names of the local variables in scope when displaying a term, with the
+/// most recently bound variable at the end
+type Names = im::Vector<NameHint>;
+
+fn var_name(names: &Names, index: VarIndex, shift: LevelShift) -> String {
+    match index.to_level(names.len()) {
+        Some(level) => format!("{}^{}", names[level.0], shift),
+        None => format!("#{}^{}", index, shift),
+    }
+}
+
+fn push_name(names: &Names, name: &NameHint) -> Names {
+    let mut names = names.clone();
+    names.push_back(name.clone());
+    names
+}
+
+/// The multiplicity that is written before a binder, if it is not the default
+fn multiplicity_doc(multiplicity: Multiplicity) -> Doc<'static, BoxDoc<'static, ()>> {
+    match multiplicity {
+        Multiplicity::Many => Doc::nil(),
+        multiplicity => Doc::as_string(multiplicity).append(Doc::space()),
+    }
+}
 
-#[derive(Debug, Clone, PartialEq, moniker::BoundPattern)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Pattern {
     /// Patterns annotated with types
-    Ann(RcPattern, Embed<RcTerm>),
+    Ann(RcPattern, RcTerm),
     /// Patterns that bind variables
-    Binder(Binder<String>),
-    /// Patterns to be compared structurally with a variable in scope
-    Var(Embed<Var<String>>, LevelShift),
+    Binder(NameHint),
+    /// Patterns to be compared structurally with a local variable in scope
+    Var(VarIndex, LevelShift),
+    /// Patterns to be compared structurally with a global variable
+    Global(FreeVar<String>, LevelShift),
     /// Literal patterns
     Literal(Literal),
+    /// Variant patterns
+    VariantIntro(Label, RcPattern),
+    /// Record patterns
+    RecordIntro(Vec<(Label, RcPattern)>),
+    /// Array patterns
+    ArrayIntro(Vec<RcPattern>),
 }
 
 impl Pattern {
+    /// The number of variables bound by the pattern
+    ///
+    /// The body of a clause is checked in a scope that is extended with these
+    /// variables, in the order that they appear in the pattern from left to
+    /// right. Any annotations or variables within the pattern itself refer to
+    /// the scope outside of the clause.
+    pub fn binders(&self) -> usize {
+        match *self {
+            Pattern::Ann(ref pattern, _) | Pattern::VariantIntro(_, ref pattern) => {
+                pattern.binders()
+            },
+            Pattern::Binder(_) => 1,
+            Pattern::Var(_, _) | Pattern::Global(_, _) | Pattern::Literal(_) => 0,
+            Pattern::RecordIntro(ref fields) => fields
+                .iter()
+                .map(|&(_, ref pattern)| pattern.binders())
+                .sum(),
+            Pattern::ArrayIntro(ref patterns) => {
+                patterns.iter().map(|pattern| pattern.binders()).sum()
+            },
+        }
+    }
+
+    /// The names of the variables bound by the pattern, in order
+    pub fn binder_names(&self, names: &mut Vec<NameHint>) {
+        match *self {
+            Pattern::Ann(ref pattern, _) | Pattern::VariantIntro(_, ref pattern) => {
+                pattern.binder_names(names)
+            },
+            Pattern::Binder(ref name) => names.push(name.clone()),
+            Pattern::Var(_, _) | Pattern::Global(_, _) | Pattern::Literal(_) => {},
+            Pattern::RecordIntro(ref fields) => {
+                for &(_, ref pattern) in fields {
+                    pattern.binder_names(names);
+                }
+            },
+            Pattern::ArrayIntro(ref patterns) => {
+                for pattern in patterns {
+                    pattern.binder_names(names);
+                }
+            },
+        }
+    }
+
     pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        self.to_doc_names(&Names::new())
+    }
+
+    fn to_doc_names(&self, names: &Names) -> Doc<BoxDoc<()>> {
         match *self {
-            Pattern::Ann(ref pattern, Embed(ref ty)) => Doc::nil()
-                .append(pattern.to_doc())
+            Pattern::Ann(ref pattern, ref ty) => Doc::nil()
+                .append(pattern.to_doc_names(names))
                 .append(Doc::space())
                 .append(":")
                 .append(Doc::space())
-                .append(ty.to_doc()), // fun-intro?
-            ref pattern => pattern.to_doc_atomic(),
+                .append(ty.to_doc_names(names)), // fun-intro?
+            Pattern::VariantIntro(ref label, ref pattern) => Doc::nil()
+                .append("variant")
+                .append(Doc::space())
+                .append(Doc::as_string(label))
+                .append(Doc::space())
+                .append(pattern.to_doc_atomic(names)),
+            ref pattern => pattern.to_doc_atomic(names),
         }
     }
 
-    fn to_doc_atomic(&self) -> Doc<BoxDoc<()>> {
+    fn to_doc_atomic(&self, names: &Names) -> Doc<BoxDoc<()>> {
         match *self {
-            Pattern::Binder(ref binder) => Doc::as_string(binder),
-            Pattern::Var(Embed(ref var), shift) => Doc::as_string(format!("{}^{}", var, shift)),
+            Pattern::Binder(ref name) => Doc::as_string(name),
+            Pattern::Var(index, shift) => Doc::text(var_name(names, index, shift)),
+            Pattern::Global(ref free_var, shift) => {
+                Doc::as_string(format!("{}^{}", free_var, shift))
+            },
             Pattern::Literal(ref literal) => literal.to_doc(),
-            ref pattern => Doc::text("(").append(pattern.to_doc()).append(")"),
+            Pattern::RecordIntro(ref fields) => Doc::nil()
+                .append("record {")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    fields.iter().map(|&(ref label, ref pattern)| {
+                        Doc::nil()
+                            .append(Doc::as_string(label))
+                            .append(Doc::space())
+                            .append("=")
+                            .append(Doc::space())
+                            .append(pattern.to_doc_names(names))
+                    }),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append(Doc::space())
+                .append("}"),
+            Pattern::ArrayIntro(ref elems) => Doc::nil()
+                .append("[")
+                .append(Doc::intersperse(
+                    elems.iter().map(|elem| elem.to_doc_names(names)),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append("]"),
+            ref pattern => Doc::text("(")
+                .append(pattern.to_doc_names(names))
+                .append(")"),
         }
     }
 }
@@ -51,7 +175,7 @@ impl fmt::Display for Pattern {
 }
 
 /// Reference counted patterns
-#[derive(Debug, Clone, PartialEq, moniker::BoundPattern)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RcPattern {
     pub inner: Rc<Pattern>,
 }
@@ -79,36 +203,75 @@ impl fmt::Display for RcPattern {
 }
 
 /// The core term syntax
-#[derive(Debug, Clone, PartialEq, moniker::BoundTerm)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Term {
     /// A term annotated with a type
     Ann(RcTerm, RcTerm),
     /// Universes
     Universe(Level),
+    /// Universes at a level that depends on level variables
+    UniverseAt(RcTerm),
+    /// The universe of types that are polymorphic over universe levels
+    UniverseOmega,
+    /// The type of universe levels
+    LevelType,
+    /// Universe level literals
+    LevelIntro(Level),
+    /// The maximum of two universe levels
+    LevelMax(RcTerm, RcTerm),
+    /// A universe level that is not yet known, shifted up by some number of
+    /// universes
+    LevelShift(RcTerm, LevelShift),
     /// Literals
     Literal(Literal),
-    /// A variable
-    Var(Var<String>, LevelShift),
+    /// A local variable
+    Var(VarIndex, LevelShift),
+    /// A global variable
+    Global(FreeVar<String>, LevelShift),
     /// An imported definition
     Import(String),
-    /// Dependent function types
-    FunType(Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
-    /// Function introductions
-    FunIntro(Scope<(Binder<String>, Embed<RcTerm>), RcTerm>),
-    /// Function applications
-    FunApp(RcTerm, RcTerm),
-    /// Dependent record types
-    RecordType(Scope<Nest<(Label, Binder<String>, Embed<RcTerm>)>, ()>),
-    /// Record introductions
-    RecordIntro(Vec<(Label, RcTerm)>),
-    /// Record field projection
-    RecordProj(RcTerm, Label, LevelShift),
-    /// Case expressions
-    Case(RcTerm, Vec<Scope<RcPattern, RcTerm>>),
+    /// Metavariables
+    Meta(MetaVar),
+    /// Dependent function types, with the body bound over the parameter
+    FunType(Plicity, Multiplicity, NameHint, RcTerm, RcTerm),
+    /// Function introductions, with the body bound over the parameter
+    FunIntro(Plicity, Multiplicity, NameHint, RcTerm, RcTerm),
+    /// Function applications, along with the multiplicity of the parameter
+    /// that the argument is passed to
+    FunApp(RcTerm, Multiplicity, RcTerm),
+    /// Dependent record types, with the type of each field bound over the
+    /// fields that come before it
+    RecordType(Vec<(Label, NameHint, Multiplicity, RcTerm)>),
+    /// Record introductions, along with the multiplicities of the fields
+    RecordIntro(Vec<(Label, Multiplicity, RcTerm)>),
+    /// Record field projection, along with the multiplicity of the field
+    RecordProj(RcTerm, Label, Multiplicity, LevelShift),
+    /// Record extension, adding fields to the end of an existing record
+    RecordExtend(RcTerm, Vec<(Label, RcTerm)>),
+    /// Variant types
+    VariantType(Vec<(Label, RcTerm)>),
+    /// Variant introductions
+    VariantIntro(Label, RcTerm),
+    /// Equality types
+    EqType(RcTerm, RcTerm, RcTerm),
+    /// Equality introductions
+    EqIntro,
+    /// Equality eliminations
+    EqElim(RcTerm, RcTerm, RcTerm),
+    /// Case expressions, with the body of each clause bound over the
+    /// variables in its pattern
+    Case(RcTerm, Vec<(RcPattern, RcTerm)>),
     /// Array literals
     ArrayIntro(Vec<RcTerm>),
-    /// Let bindings
-    Let(Scope<Nest<(Binder<String>, Embed<RcTerm>)>, RcTerm>),
+    /// Let bindings, with each definition bound over the ones that come
+    /// before it
+    Let(Vec<(NameHint, RcTerm)>, RcTerm),
+    /// Fixed points, for recursive definitions, with the body bound over the
+    /// definition itself
+    Fix(Totality, NameHint, RcTerm, RcTerm),
+    /// Terms that were removed during erasure, as they are not needed at
+    /// runtime
+    Erased,
 }
 
 impl Term {
@@ -116,147 +279,267 @@ impl Term {
         Term::Universe(level.into())
     }
 
-    pub fn var(var: impl Into<Var<String>>, shift: impl Into<LevelShift>) -> Term {
-        Term::Var(var.into(), shift.into())
+    pub fn var(index: impl Into<VarIndex>, shift: impl Into<LevelShift>) -> Term {
+        Term::Var(index.into(), shift.into())
+    }
+
+    pub fn global(free_var: FreeVar<String>, shift: impl Into<LevelShift>) -> Term {
+        Term::Global(free_var, shift.into())
     }
 
     pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        self.to_doc_names(&Names::new())
+    }
+
+    fn to_doc_names(&self, names: &Names) -> Doc<BoxDoc<()>> {
         match *self {
             Term::Ann(ref term, ref ty) => Doc::nil()
-                .append(term.to_doc_expr())
+                .append(term.to_doc_expr(names))
                 .append(Doc::space())
                 .append(":")
                 .append(Doc::space())
-                .append(ty.to_doc_expr()),
-            ref term => term.to_doc_expr(),
+                .append(ty.to_doc_expr(names)),
+            ref term => term.to_doc_expr(names),
         }
     }
 
-    fn to_doc_expr(&self) -> Doc<BoxDoc<()>> {
+    fn to_doc_expr(&self, names: &Names) -> Doc<BoxDoc<()>> {
         match *self {
             Term::Import(ref name) => Doc::nil()
                 .append("import")
                 .append(Doc::space())
                 .append(format!("{:?}", name)),
-            Term::FunIntro(ref scope) => Doc::nil()
+            Term::FunIntro(Plicity::Explicit, Multiplicity::Many, ref name, ref ann, ref body) => {
+                Doc::nil()
+                    .append("\\")
+                    .append(Doc::as_string(name))
+                    .append(Doc::space())
+                    .append(":")
+                    .append(Doc::space())
+                    .append(ann.to_doc_arrow(names))
+                    .append(Doc::space())
+                    .append("=>")
+                    .append(Doc::space())
+                    .append(body.to_doc_expr(&push_name(names, name)))
+            },
+            Term::FunIntro(plicity, multiplicity, ref name, ref ann, ref body) => Doc::nil()
                 .append("\\")
-                .append(Doc::as_string(&scope.unsafe_pattern.0))
+                .append(plicity.open_delim())
+                .append(multiplicity_doc(multiplicity))
+                .append(Doc::as_string(name))
                 .append(Doc::space())
                 .append(":")
                 .append(Doc::space())
-                .append((scope.unsafe_pattern.1).0.to_doc_arrow())
+                .append(ann.to_doc_names(names))
+                .append(plicity.close_delim())
                 .append(Doc::space())
                 .append("=>")
                 .append(Doc::space())
-                .append(scope.unsafe_body.to_doc_expr()),
+                .append(body.to_doc_expr(&push_name(names, name))),
             Term::Case(ref head, ref clauses) => Doc::nil()
                 .append("case")
                 .append(Doc::space())
-                .append(head.to_doc_app())
+                .append(head.to_doc_app(names))
                 .append(Doc::space())
                 .append("{")
                 .append(Doc::space())
                 .append(Doc::intersperse(
-                    clauses.iter().map(|scope| {
+                    clauses.iter().map(|&(ref pattern, ref body)| {
+                        let mut body_names = names.clone();
+                        let mut binder_names = Vec::new();
+                        pattern.binder_names(&mut binder_names);
+                        body_names.extend(binder_names);
+
                         Doc::nil()
-                            .append(scope.unsafe_pattern.to_doc())
+                            .append(pattern.to_doc_names(names))
                             .append(Doc::space())
                             .append("=>")
                             .append(Doc::space())
-                            .append(scope.unsafe_body.to_doc())
+                            .append(body.to_doc_names(&body_names))
                             .append(";")
                     }),
                     Doc::newline(),
                 ))
                 .append(Doc::space())
                 .append("}"),
-            Term::Let(ref scope) => Doc::nil()
-                .append("let")
+            Term::Let(ref bindings, ref body) => {
+                let mut names = names.clone();
+                let bindings = bindings
+                    .iter()
+                    .map(|&(ref name, ref term)| {
+                        let doc = Doc::nil()
+                            .append(Doc::as_string(name))
+                            .append(Doc::space())
+                            .append("=")
+                            .append(Doc::space())
+                            .append(term.to_doc_names(&names));
+                        names.push_back(name.clone());
+                        doc
+                    })
+                    .collect::<Vec<_>>();
+
+                Doc::nil()
+                    .append("let")
+                    .append(Doc::space())
+                    .append(Doc::intersperse(bindings, Doc::newline()))
+                    .append(Doc::space())
+                    .append("in")
+                    .append(Doc::space())
+                    .append(body.to_doc_expr(&names))
+            },
+            Term::Fix(totality, ref name, ref ann, ref body) => Doc::nil()
+                .append(match totality {
+                    Totality::Total => Doc::nil(),
+                    Totality::Partial => Doc::text("partial").append(Doc::space()),
+                })
+                .append("fix")
                 .append(Doc::space())
-                .append(Doc::intersperse(
-                    scope.unsafe_pattern.unsafe_patterns.iter().map(
-                        |&(ref binder, Embed(ref term))| {
-                            Doc::nil()
-                                .append(Doc::as_string(binder))
-                                .append(Doc::space())
-                                .append("=")
-                                .append(Doc::space())
-                                .append(term.to_doc())
-                        },
-                    ),
-                    Doc::newline(),
-                ))
+                .append(Doc::as_string(name))
+                .append(Doc::space())
+                .append(":")
                 .append(Doc::space())
-                .append("in")
+                .append(ann.to_doc_arrow(names))
                 .append(Doc::space())
-                .append(scope.unsafe_body.to_doc_expr()),
-            ref term => term.to_doc_arrow(),
+                .append("=>")
+                .append(Doc::space())
+                .append(body.to_doc_expr(&push_name(names, name))),
+            ref term => term.to_doc_arrow(names),
         }
     }
 
-    fn to_doc_arrow(&self) -> Doc<BoxDoc<()>> {
+    fn to_doc_arrow(&self, names: &Names) -> Doc<BoxDoc<()>> {
         match *self {
-            Term::FunType(ref scope) => Doc::nil()
-                .append("(")
-                .append(Doc::as_string(&scope.unsafe_pattern.0))
+            Term::FunType(plicity, multiplicity, ref name, ref ann, ref body) => Doc::nil()
+                .append(plicity.open_delim())
+                .append(multiplicity_doc(multiplicity))
+                .append(Doc::as_string(name))
                 .append(Doc::space())
                 .append(":")
                 .append(Doc::space())
-                .append((scope.unsafe_pattern.1).0.to_doc_arrow())
-                .append(")")
+                .append(ann.to_doc_arrow(names))
+                .append(plicity.close_delim())
                 .append(Doc::space())
                 .append("->")
                 .append(Doc::space())
-                .append(scope.unsafe_body.to_doc_expr()),
-            ref term => term.to_doc_app(),
+                .append(body.to_doc_expr(&push_name(names, name))),
+            Term::EqType(_, ref lhs, ref rhs) => Doc::nil()
+                .append(lhs.to_doc_app(names))
+                .append(Doc::space())
+                .append("==")
+                .append(Doc::space())
+                .append(rhs.to_doc_app(names)),
+            ref term => term.to_doc_app(names),
         }
     }
 
-    fn to_doc_app(&self) -> Doc<BoxDoc<()>> {
+    fn to_doc_app(&self, names: &Names) -> Doc<BoxDoc<()>> {
         match *self {
-            Term::FunApp(ref fun, ref arg) => Doc::nil()
-                .append(fun.to_doc_atomic())
+            Term::FunApp(ref fun, _, ref arg) => Doc::nil()
+                .append(fun.to_doc_atomic(names))
+                .append(Doc::space())
+                .append(arg.to_doc_atomic(names)),
+            Term::VariantIntro(ref label, ref arg) => Doc::nil()
+                .append("variant")
+                .append(Doc::space())
+                .append(Doc::as_string(label))
+                .append(Doc::space())
+                .append(arg.to_doc_atomic(names)),
+            Term::LevelMax(ref level1, ref level2) => Doc::nil()
+                .append("max")
+                .append(Doc::space())
+                .append(level1.to_doc_atomic(names))
+                .append(Doc::space())
+                .append(level2.to_doc_atomic(names)),
+            Term::EqElim(ref motive, ref proof, ref body) => Doc::nil()
+                .append("rewrite")
+                .append(Doc::space())
+                .append(motive.to_doc_atomic(names))
                 .append(Doc::space())
-                .append(arg.to_doc_atomic()),
-            ref term => term.to_doc_atomic(),
+                .append(proof.to_doc_atomic(names))
+                .append(Doc::space())
+                .append(body.to_doc_atomic(names)),
+            ref term => term.to_doc_atomic(names),
         }
     }
 
-    fn to_doc_atomic(&self) -> Doc<BoxDoc<()>> {
+    fn to_doc_atomic(&self, names: &Names) -> Doc<BoxDoc<()>> {
         match *self {
             Term::Universe(level) => Doc::text(format!("Type^{}", level)),
+            Term::UniverseAt(ref level) => Doc::text("Type^").append(level.to_doc_atomic(names)),
+            Term::UniverseOmega => Doc::text("Type^ω"),
+            Term::LevelType => Doc::text("Level"),
+            Term::LevelIntro(level) => Doc::as_string(level),
+            Term::LevelShift(ref level, shift) => Doc::nil()
+                .append(level.to_doc_atomic(names))
+                .append(format!("^{}", shift)),
             Term::ArrayIntro(ref elems) => Doc::nil()
                 .append("[")
                 .append(Doc::intersperse(
-                    elems.iter().map(|elem| elem.to_doc()),
+                    elems.iter().map(|elem| elem.to_doc_names(names)),
                     Doc::text(";").append(Doc::space()),
                 ))
                 .append("]"),
-            Term::Var(ref var, ref level) => Doc::text(format!("{}^{}", var, level)),
-            Term::RecordType(ref scope) => Doc::nil()
-                .append("Record {")
+            Term::Var(index, shift) => Doc::text(var_name(names, index, shift)),
+            Term::Global(ref free_var, shift) => Doc::text(format!("{}^{}", free_var, shift)),
+            Term::Meta(meta) => Doc::as_string(meta),
+            Term::EqIntro => Doc::text("refl"),
+            Term::Erased => Doc::text("erased"),
+            Term::RecordType(ref fields) => {
+                let mut names = names.clone();
+                let fields = fields
+                    .iter()
+                    .map(|&(ref label, ref name, multiplicity, ref ann)| {
+                        let doc = Doc::nil()
+                            .append(multiplicity_doc(multiplicity))
+                            .append(Doc::as_string(label))
+                            .append(Doc::space())
+                            .append("as")
+                            .append(Doc::space())
+                            .append(Doc::as_string(name))
+                            .append(Doc::space())
+                            .append(":")
+                            .append(Doc::space())
+                            .append(ann.to_doc_names(&names));
+                        names.push_back(name.clone());
+                        doc
+                    })
+                    .collect::<Vec<_>>();
+
+                Doc::nil()
+                    .append("Record {")
+                    .append(Doc::space())
+                    .append(Doc::intersperse(
+                        fields,
+                        Doc::text(";").append(Doc::space()),
+                    ))
+                    .append(Doc::space())
+                    .append("}")
+            },
+            Term::RecordIntro(ref fields) => Doc::nil()
+                .append("record {")
                 .append(Doc::space())
                 .append(Doc::intersperse(
-                    scope.unsafe_pattern.unsafe_patterns.iter().map(
-                        |&(ref label, ref binder, Embed(ref ann))| {
-                            Doc::nil()
-                                .append(Doc::as_string(label))
-                                .append(Doc::space())
-                                .append("as")
-                                .append(Doc::space())
-                                .append(Doc::as_string(binder))
-                                .append(Doc::space())
-                                .append(":")
-                                .append(Doc::space())
-                                .append(ann.to_doc())
-                        },
-                    ),
+                    fields.iter().map(|&(ref label, multiplicity, ref value)| {
+                        Doc::nil()
+                            .append(multiplicity_doc(multiplicity))
+                            .append(Doc::as_string(label))
+                            .append(Doc::space())
+                            .append("=")
+                            .append(Doc::space())
+                            .append(value.to_doc_names(names))
+                    }),
                     Doc::text(";").append(Doc::space()),
                 ))
                 .append(Doc::space())
                 .append("}"),
-            Term::RecordIntro(ref fields) => Doc::nil()
-                .append("record {")
+            Term::RecordProj(ref expr, ref label, _, ref shift) => Doc::nil()
+                .append(expr.to_doc_atomic(names))
+                .append(".")
+                .append(format!("{}^{}", label, shift)),
+            Term::RecordExtend(ref expr, ref fields) => Doc::nil()
+                .append(expr.to_doc_atomic(names))
+                .append(Doc::space())
+                .append("with {")
                 .append(Doc::space())
                 .append(Doc::intersperse(
                     fields.iter().map(|&(ref label, ref value)| {
@@ -265,17 +548,29 @@ impl Term {
                             .append(Doc::space())
                             .append("=")
                             .append(Doc::space())
-                            .append(value.to_doc())
+                            .append(value.to_doc_names(names))
                     }),
                     Doc::text(";").append(Doc::space()),
                 ))
                 .append(Doc::space())
                 .append("}"),
-            Term::RecordProj(ref expr, ref label, ref shift) => Doc::nil()
-                .append(expr.to_doc_atomic())
-                .append(".")
-                .append(format!("{}^{}", label, shift)),
-            ref term => Doc::text("(").append(term.to_doc()).append(")"),
+            Term::VariantType(ref alts) => Doc::nil()
+                .append("Variant {")
+                .append(Doc::space())
+                .append(Doc::intersperse(
+                    alts.iter().map(|&(ref label, ref ann)| {
+                        Doc::nil()
+                            .append(Doc::as_string(label))
+                            .append(Doc::space())
+                            .append(":")
+                            .append(Doc::space())
+                            .append(ann.to_doc_names(names))
+                    }),
+                    Doc::text(";").append(Doc::space()),
+                ))
+                .append(Doc::space())
+                .append("}"),
+            ref term => Doc::text("(").append(term.to_doc_names(names)).append(")"),
         }
     }
 }
@@ -287,223 +582,381 @@ impl fmt::Display for Term {
 }
 
 /// Reference counted terms
-#[derive(Debug, Clone, PartialEq, moniker::BoundTerm)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RcTerm {
     pub inner: Rc<Term>,
 }
 
 impl RcTerm {
-    pub fn substs(&self, mappings: &[(FreeVar<String>, RcTerm)]) -> RcTerm {
+    /// Returns the term underneath any type annotations
+    pub fn strip_ann(&self) -> &RcTerm {
         match *self.inner {
-            Term::Ann(ref term, ref ty) => {
-                RcTerm::from(Term::Ann(term.substs(mappings), ty.substs(mappings)))
+            Term::Ann(ref expr, _) => expr.strip_ann(),
+            _ => self,
+        }
+    }
+
+    pub fn shift_universes(&mut self, shift: LevelShift) {
+        match *Rc::make_mut(&mut self.inner) {
+            Term::Ann(ref mut expr, ref mut ty) => {
+                expr.shift_universes(shift);
+                ty.shift_universes(shift);
             },
-            Term::Universe(_) | Term::Literal(_) => self.clone(),
-            Term::Var(ref var, _) => match mappings.iter().find(|&(ref name, _)| var == name) {
-                Some(&(_, ref term)) => term.clone(),
-                None => self.clone(),
+            Term::Universe(ref mut level) | Term::LevelIntro(ref mut level) => *level += shift,
+            Term::UniverseAt(ref mut level) => level.shift_level(shift),
+            Term::LevelMax(ref mut level1, ref mut level2) => {
+                level1.shift_level(shift);
+                level2.shift_level(shift);
             },
-            Term::Import(ref name) => RcTerm::from(Term::Import(name.clone())),
-            Term::FunType(ref scope) => {
-                let (ref name, Embed(ref ann)) = scope.unsafe_pattern;
-                RcTerm::from(Term::FunType(Scope {
-                    unsafe_pattern: (name.clone(), Embed(ann.substs(mappings))),
-                    unsafe_body: scope.unsafe_body.substs(mappings),
-                }))
+            Term::LevelShift(_, ref mut level_shift) => *level_shift += shift,
+            Term::UniverseOmega | Term::LevelType => {},
+            // Shifting a variable shifts the definition it refers to, once
+            // it has been evaluated
+            Term::Var(_, ref mut var_shift) | Term::Global(_, ref mut var_shift) => {
+                *var_shift += shift
             },
-            Term::FunIntro(ref scope) => {
-                let (ref name, Embed(ref ann)) = scope.unsafe_pattern;
-                RcTerm::from(Term::FunIntro(Scope {
-                    unsafe_pattern: (name.clone(), Embed(ann.substs(mappings))),
-                    unsafe_body: scope.unsafe_body.substs(mappings),
-                }))
+            Term::Literal(_) | Term::Import(_) | Term::Meta(_) | Term::EqIntro | Term::Erased => {},
+            Term::FunType(_, _, _, ref mut ann, ref mut body)
+            | Term::FunIntro(_, _, _, ref mut ann, ref mut body)
+            | Term::Fix(_, _, ref mut ann, ref mut body) => {
+                ann.shift_universes(shift);
+                body.shift_universes(shift);
             },
-            Term::Let(ref scope) => {
-                let unsafe_patterns = scope
-                    .unsafe_pattern
-                    .unsafe_patterns
-                    .iter()
-                    .map(|&(ref binder, Embed(ref term))| {
-                        (binder.clone(), Embed(term.substs(mappings)))
-                    })
-                    .collect();
-
-                RcTerm::from(Term::Let(Scope {
-                    unsafe_pattern: Nest { unsafe_patterns },
-                    unsafe_body: scope.unsafe_body.substs(mappings),
-                }))
+            Term::FunApp(ref mut head, _, ref mut arg) => {
+                head.shift_universes(shift);
+                arg.shift_universes(shift);
             },
-            Term::FunApp(ref head, ref arg) => {
-                RcTerm::from(Term::FunApp(head.substs(mappings), arg.substs(mappings)))
+            Term::RecordType(ref mut fields) => {
+                for &mut (_, _, _, ref mut ann) in fields {
+                    ann.shift_universes(shift);
+                }
             },
-            Term::RecordType(ref scope) if scope.unsafe_pattern.unsafe_patterns.is_empty() => {
-                self.clone()
+            Term::RecordIntro(ref mut fields) => {
+                for &mut (_, _, ref mut term) in fields {
+                    term.shift_universes(shift);
+                }
             },
-            Term::RecordType(ref scope) => {
-                let unsafe_patterns = scope
-                    .unsafe_pattern
-                    .unsafe_patterns
-                    .iter()
-                    .map(|&(ref label, ref binder, Embed(ref ann))| {
-                        (label.clone(), binder.clone(), Embed(ann.substs(mappings)))
-                    })
-                    .collect();
-
-                RcTerm::from(Term::RecordType(Scope {
-                    unsafe_pattern: Nest { unsafe_patterns },
-                    unsafe_body: (),
-                }))
+            Term::RecordProj(ref mut expr, _, _, _) => expr.shift_universes(shift),
+            Term::RecordExtend(ref mut expr, ref mut fields) => {
+                expr.shift_universes(shift);
+                for &mut (_, ref mut term) in fields {
+                    term.shift_universes(shift);
+                }
             },
-            Term::RecordIntro(ref fields) if fields.is_empty() => self.clone(),
-            Term::RecordIntro(ref fields) => {
-                let fields = fields
-                    .iter()
-                    .map(|&(ref label, ref expr)| (label.clone(), expr.substs(mappings)))
-                    .collect();
-
-                RcTerm::from(Term::RecordIntro(fields))
+            Term::VariantType(ref mut alts) => {
+                for &mut (_, ref mut ann) in alts {
+                    ann.shift_universes(shift);
+                }
+            },
+            Term::VariantIntro(_, ref mut arg) => arg.shift_universes(shift),
+            Term::EqType(ref mut term1, ref mut term2, ref mut term3)
+            | Term::EqElim(ref mut term1, ref mut term2, ref mut term3) => {
+                term1.shift_universes(shift);
+                term2.shift_universes(shift);
+                term3.shift_universes(shift);
+            },
+            Term::Case(ref mut head, ref mut clauses) => {
+                head.shift_universes(shift);
+                for &mut (_, ref mut body) in clauses {
+                    // FIXME: implement shifting for patterns as well!
+                    body.shift_universes(shift);
+                }
+            },
+            Term::ArrayIntro(ref mut elems) => {
+                for elem in elems {
+                    elem.shift_universes(shift);
+                }
+            },
+            Term::Let(ref mut bindings, ref mut body) => {
+                for &mut (_, ref mut term) in bindings {
+                    term.shift_universes(shift);
+                }
+                body.shift_universes(shift);
             },
-            Term::RecordProj(ref expr, ref label, shift) => RcTerm::from(Term::RecordProj(
-                expr.substs(mappings),
-                label.clone(),
-                shift,
-            )),
-            Term::Case(ref head, ref clauses) => RcTerm::from(Term::Case(
-                head.substs(mappings),
-                clauses
-                    .iter()
-                    .map(|scope| {
-                        Scope {
-                            unsafe_pattern: scope.unsafe_pattern.clone(), // subst?
-                            unsafe_body: scope.unsafe_body.substs(mappings),
-                        }
-                    })
-                    .collect(),
-            )),
-            Term::ArrayIntro(ref elems) => RcTerm::from(Term::ArrayIntro(
-                elems.iter().map(|elem| elem.substs(mappings)).collect(),
-            )),
         }
     }
-}
 
-impl From<Term> for RcTerm {
-    fn from(src: Term) -> RcTerm {
-        RcTerm {
-            inner: Rc::new(src),
+    /// Shift a term that is used as a universe level
+    ///
+    /// Metavariables don't know how to shift their eventual solutions, so we
+    /// wrap them in an explicit shift instead.
+    fn shift_level(&mut self, shift: LevelShift) {
+        match *self.inner {
+            Term::Meta(_) => *self = RcTerm::from(Term::LevelShift(self.clone(), shift)),
+            _ => self.shift_universes(shift),
         }
     }
-}
 
-impl ops::Deref for RcTerm {
-    type Target = Term;
+    /// Adjust the free variables of the term so that it can be placed
+    /// underneath `count` more binders than it was originally found under
+    pub fn shift_vars(&self, count: usize) -> RcTerm {
+        if count == 0 {
+            return self.clone();
+        }
 
-    fn deref(&self) -> &Term {
-        &self.inner
+        self.subst_vars(&mut |index, shift| {
+            RcTerm::from(Term::Var(VarIndex(index.0 + count), shift))
+        })
     }
-}
 
-impl fmt::Display for RcTerm {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        fmt::Display::fmt(&self.inner, f)
+    /// Replace the free variables of the term
+    ///
+    /// `f` is called with the index of each free variable, as seen from
+    /// outside of the term, and returns the term to replace it with, again as
+    /// seen from outside of the term. Replacements are shifted to account for
+    /// the binders they end up underneath.
+    ///
+    /// # Panics
+    ///
+    /// Variables in patterns can only be replaced with variables or literals.
+    pub fn subst_vars(&self, f: &mut impl FnMut(VarIndex, LevelShift) -> RcTerm) -> RcTerm {
+        self.try_subst_vars(&mut |index, shift| Some(f(index, shift)))
+            .expect("pattern variable replaced with a term that is not a pattern")
     }
-}
 
-impl<'a> From<&'a Value> for Term {
-    fn from(src: &'a Value) -> Term {
-        // Bypassing `Scope::new` and `Scope::unbind` here should be fine
-        // because we aren't altering the structure of the scopes during this
-        // transformation. This should save on some traversals of the AST!
-        match *src {
-            Value::Universe(level) => Term::Universe(level),
-            Value::Literal(ref lit) => Term::Literal(lit.clone()),
-            Value::FunType(ref scope) => {
-                let (ref name, Embed(ref ann)) = scope.unsafe_pattern;
-                Term::FunType(Scope {
-                    unsafe_pattern: (name.clone(), Embed(RcTerm::from(&**ann))),
-                    unsafe_body: RcTerm::from(&*scope.unsafe_body),
-                })
+    /// Replace the free variables of the term, returning `None` if `f` does,
+    /// or if a variable in a pattern would be replaced with something other
+    /// than a variable or a literal
+    pub fn try_subst_vars(
+        &self,
+        f: &mut impl FnMut(VarIndex, LevelShift) -> Option<RcTerm>,
+    ) -> Option<RcTerm> {
+        self.try_subst_vars_at(0, f)
+    }
+
+    fn try_subst_vars_at(
+        &self,
+        depth: usize,
+        f: &mut impl FnMut(VarIndex, LevelShift) -> Option<RcTerm>,
+    ) -> Option<RcTerm> {
+        let term = match *self.inner {
+            Term::Var(index, shift) => {
+                return match index.0.checked_sub(depth) {
+                    None => Some(self.clone()),
+                    Some(index) => Some(f(VarIndex(index), shift)?.shift_vars(depth)),
+                };
             },
-            Value::FunIntro(ref scope) => {
-                let (ref name, Embed(ref ann)) = scope.unsafe_pattern;
-                Term::FunIntro(Scope {
-                    unsafe_pattern: (name.clone(), Embed(RcTerm::from(&**ann))),
-                    unsafe_body: RcTerm::from(&*scope.unsafe_body),
-                })
+            Term::Universe(_)
+            | Term::UniverseOmega
+            | Term::LevelType
+            | Term::LevelIntro(_)
+            | Term::Literal(_)
+            | Term::Global(_, _)
+            | Term::Import(_)
+            | Term::Meta(_)
+            | Term::EqIntro
+            | Term::Erased => return Some(self.clone()),
+            Term::Ann(ref term, ref ty) => Term::Ann(
+                term.try_subst_vars_at(depth, f)?,
+                ty.try_subst_vars_at(depth, f)?,
+            ),
+            Term::UniverseAt(ref level) => Term::UniverseAt(level.try_subst_vars_at(depth, f)?),
+            Term::LevelMax(ref level1, ref level2) => Term::LevelMax(
+                level1.try_subst_vars_at(depth, f)?,
+                level2.try_subst_vars_at(depth, f)?,
+            ),
+            Term::LevelShift(ref level, shift) => {
+                Term::LevelShift(level.try_subst_vars_at(depth, f)?, shift)
             },
-            Value::RecordType(ref scope) => {
-                let unsafe_patterns = scope
-                    .unsafe_pattern
-                    .unsafe_patterns
+            Term::FunType(plicity, multiplicity, ref name, ref ann, ref body) => Term::FunType(
+                plicity,
+                multiplicity,
+                name.clone(),
+                ann.try_subst_vars_at(depth, f)?,
+                body.try_subst_vars_at(depth + 1, f)?,
+            ),
+            Term::FunIntro(plicity, multiplicity, ref name, ref ann, ref body) => Term::FunIntro(
+                plicity,
+                multiplicity,
+                name.clone(),
+                ann.try_subst_vars_at(depth, f)?,
+                body.try_subst_vars_at(depth + 1, f)?,
+            ),
+            Term::FunApp(ref head, multiplicity, ref arg) => Term::FunApp(
+                head.try_subst_vars_at(depth, f)?,
+                multiplicity,
+                arg.try_subst_vars_at(depth, f)?,
+            ),
+            Term::RecordType(ref fields) => Term::RecordType(
+                fields
                     .iter()
-                    .map(|&(ref label, ref binder, Embed(ref ann))| {
-                        (label.clone(), binder.clone(), Embed(RcTerm::from(&**ann)))
+                    .enumerate()
+                    .map(|(i, &(ref label, ref name, multiplicity, ref ann))| {
+                        Some((
+                            label.clone(),
+                            name.clone(),
+                            multiplicity,
+                            ann.try_subst_vars_at(depth + i, f)?,
+                        ))
                     })
-                    .collect();
-
-                Term::RecordType(Scope {
-                    unsafe_pattern: Nest { unsafe_patterns },
-                    unsafe_body: (),
-                })
+                    .collect::<Option<_>>()?,
+            ),
+            Term::RecordIntro(ref fields) => Term::RecordIntro(
+                fields
+                    .iter()
+                    .map(|&(ref label, multiplicity, ref expr)| {
+                        Some((
+                            label.clone(),
+                            multiplicity,
+                            expr.try_subst_vars_at(depth, f)?,
+                        ))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            Term::RecordProj(ref expr, ref label, multiplicity, shift) => Term::RecordProj(
+                expr.try_subst_vars_at(depth, f)?,
+                label.clone(),
+                multiplicity,
+                shift,
+            ),
+            Term::RecordExtend(ref expr, ref fields) => Term::RecordExtend(
+                expr.try_subst_vars_at(depth, f)?,
+                fields
+                    .iter()
+                    .map(|&(ref label, ref expr)| {
+                        Some((label.clone(), expr.try_subst_vars_at(depth, f)?))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            Term::VariantType(ref alts) => Term::VariantType(
+                alts.iter()
+                    .map(|&(ref label, ref ann)| {
+                        Some((label.clone(), ann.try_subst_vars_at(depth, f)?))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            Term::VariantIntro(ref label, ref arg) => {
+                Term::VariantIntro(label.clone(), arg.try_subst_vars_at(depth, f)?)
             },
-            Value::RecordIntro(ref fields) => {
-                let fields = fields
+            Term::EqType(ref ty, ref lhs, ref rhs) => Term::EqType(
+                ty.try_subst_vars_at(depth, f)?,
+                lhs.try_subst_vars_at(depth, f)?,
+                rhs.try_subst_vars_at(depth, f)?,
+            ),
+            Term::EqElim(ref motive, ref proof, ref body) => Term::EqElim(
+                motive.try_subst_vars_at(depth, f)?,
+                proof.try_subst_vars_at(depth, f)?,
+                body.try_subst_vars_at(depth, f)?,
+            ),
+            Term::Case(ref head, ref clauses) => Term::Case(
+                head.try_subst_vars_at(depth, f)?,
+                clauses
+                    .iter()
+                    .map(|&(ref pattern, ref body)| {
+                        let body_depth = depth + pattern.binders();
+                        Some((
+                            pattern.try_subst_vars_at(depth, f)?,
+                            body.try_subst_vars_at(body_depth, f)?,
+                        ))
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            Term::ArrayIntro(ref elems) => Term::ArrayIntro(
+                elems
+                    .iter()
+                    .map(|elem| elem.try_subst_vars_at(depth, f))
+                    .collect::<Option<_>>()?,
+            ),
+            Term::Let(ref bindings, ref body) => Term::Let(
+                bindings
                     .iter()
-                    .map(|&(ref label, ref expr)| (label.clone(), RcTerm::from(&**expr)))
-                    .collect();
+                    .enumerate()
+                    .map(|(i, &(ref name, ref term))| {
+                        Some((name.clone(), term.try_subst_vars_at(depth + i, f)?))
+                    })
+                    .collect::<Option<_>>()?,
+                body.try_subst_vars_at(depth + bindings.len(), f)?,
+            ),
+            Term::Fix(totality, ref name, ref ann, ref body) => Term::Fix(
+                totality,
+                name.clone(),
+                ann.try_subst_vars_at(depth, f)?,
+                body.try_subst_vars_at(depth + 1, f)?,
+            ),
+        };
 
-                Term::RecordIntro(fields)
-            },
-            Value::ArrayIntro(ref elems) => {
-                Term::ArrayIntro(elems.iter().map(|elem| RcTerm::from(&**elem)).collect())
-            },
-            Value::Neutral(ref neutral, ref spine) => {
-                spine.iter().fold(Term::from(&*neutral.inner), |acc, arg| {
-                    Term::FunApp(RcTerm::from(acc), RcTerm::from(&**arg))
-                })
-            },
-        }
+        Some(RcTerm::from(term))
     }
-}
 
-impl<'a> From<&'a Value> for RcTerm {
-    fn from(src: &'a Value) -> RcTerm {
-        RcTerm::from(Term::from(src))
+    /// Returns `true` if the variable with the given index, as seen from
+    /// outside of the term, occurs in the term
+    pub fn has_free_var(&self, index: VarIndex) -> bool {
+        // Substitution bails out as soon as the variable is found
+        self.try_subst_vars(&mut |other, shift| {
+            if other == index {
+                None
+            } else {
+                Some(RcTerm::from(Term::Var(other, shift)))
+            }
+        })
+        .is_none()
     }
 }
 
-impl<'a> From<&'a Neutral> for Term {
-    fn from(src: &'a Neutral) -> Term {
-        match *src {
-            Neutral::Head(ref head) => Term::from(head),
-            Neutral::RecordProj(ref expr, ref name, shift) => {
-                Term::RecordProj(RcTerm::from(&**expr), name.clone(), shift)
+impl RcPattern {
+    fn try_subst_vars_at(
+        &self,
+        depth: usize,
+        f: &mut impl FnMut(VarIndex, LevelShift) -> Option<RcTerm>,
+    ) -> Option<RcPattern> {
+        let pattern = match *self.inner {
+            Pattern::Var(index, shift) => match index.0.checked_sub(depth) {
+                None => return Some(self.clone()),
+                Some(index) => match *f(VarIndex(index), shift)?.shift_vars(depth).inner {
+                    Term::Var(index, shift) => Pattern::Var(index, shift),
+                    Term::Global(ref free_var, shift) => Pattern::Global(free_var.clone(), shift),
+                    Term::Literal(ref literal) => Pattern::Literal(literal.clone()),
+                    _ => return None,
+                },
             },
-            Neutral::Case(ref head, ref clauses) => Term::Case(
-                RcTerm::from(&**head),
-                clauses
+            Pattern::Binder(_) | Pattern::Global(_, _) | Pattern::Literal(_) => {
+                return Some(self.clone());
+            },
+            Pattern::Ann(ref pattern, ref ty) => Pattern::Ann(
+                pattern.try_subst_vars_at(depth, f)?,
+                ty.try_subst_vars_at(depth, f)?,
+            ),
+            Pattern::VariantIntro(ref label, ref pattern) => {
+                Pattern::VariantIntro(label.clone(), pattern.try_subst_vars_at(depth, f)?)
+            },
+            Pattern::RecordIntro(ref fields) => Pattern::RecordIntro(
+                fields
                     .iter()
-                    .map(|clause| Scope {
-                        unsafe_pattern: clause.unsafe_pattern.clone(),
-                        unsafe_body: RcTerm::from(&*clause.unsafe_body),
+                    .map(|&(ref label, ref pattern)| {
+                        Some((label.clone(), pattern.try_subst_vars_at(depth, f)?))
                     })
-                    .collect(),
+                    .collect::<Option<_>>()?,
             ),
+            Pattern::ArrayIntro(ref patterns) => Pattern::ArrayIntro(
+                patterns
+                    .iter()
+                    .map(|pattern| pattern.try_subst_vars_at(depth, f))
+                    .collect::<Option<_>>()?,
+            ),
+        };
+
+        Some(RcPattern::from(pattern))
+    }
+}
+
+impl From<Term> for RcTerm {
+    fn from(src: Term) -> RcTerm {
+        RcTerm {
+            inner: Rc::new(src),
         }
     }
 }
 
-impl<'a> From<&'a Neutral> for RcTerm {
-    fn from(src: &'a Neutral) -> RcTerm {
-        RcTerm::from(Term::from(src))
+impl ops::Deref for RcTerm {
+    type Target = Term;
+
+    fn deref(&self) -> &Term {
+        &self.inner
     }
 }
 
-impl<'a> From<&'a Head> for Term {
-    fn from(src: &'a Head) -> Term {
-        match *src {
-            Head::Var(ref var, shift) => Term::Var(var.clone(), shift),
-            Head::Import(ref name) => Term::Import(name.clone()),
-        }
+impl fmt::Display for RcTerm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&self.inner, f)
     }
 }
@@ -1,33 +1,333 @@
 //! The semantic domain of the language
 
-use moniker::{Binder, Embed, FreeVar, Nest, Scope, Var};
+use moniker::FreeVar;
+use std::cmp;
 use std::ops;
 use std::rc::Rc;
 
 use crate::syntax::core::{RcPattern, RcTerm, Term};
-use crate::syntax::{Label, Level, LevelShift, Literal};
+use crate::syntax::{
+    Label, Level, LevelShift, Literal, MetaVar, Multiplicity, NameHint, Plicity, Totality,
+    VarIndex, VarLevel,
+};
+
+/// The values of the variables that were in scope when a closure was created,
+/// in the order that they were bound
+///
+/// A variable with the de Bruijn level `l` is found at position `l`, and so a
+/// variable with the de Bruijn index `i` is found at position
+/// `locals.len() - i - 1`.
+pub type Locals = im::Vector<RcValue>;
+
+/// The body of a binder, waiting for the value of its argument
+///
+/// Applying a closure evaluates the body in its local environment, extended
+/// with the argument, rather than substituting the argument into the body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Closure {
+    pub locals: Locals,
+    pub name: NameHint,
+    pub body: RcTerm,
+}
+
+impl Closure {
+    pub fn new(locals: Locals, name: NameHint, body: RcTerm) -> Closure {
+        Closure { locals, name, body }
+    }
+
+    /// Create a closure that ignores its argument, returning `value`
+    pub fn constant(value: RcValue) -> Closure {
+        // The argument is bound at index 0, with the value just outside of it
+        let body = RcTerm::from(Term::Var(VarIndex(1), LevelShift(0)));
+
+        Closure {
+            locals: Locals::unit(value),
+            name: NameHint::default(),
+            body,
+        }
+    }
+
+    pub fn shift_universes(&mut self, shift: LevelShift) {
+        shift_locals(&mut self.locals, shift);
+        self.body.shift_universes(shift);
+    }
+}
+
+/// The fields of a dependent record type, where the type of each field can
+/// depend on the values of the fields before it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Telescope {
+    pub locals: Locals,
+    pub fields: Vec<(Label, NameHint, Multiplicity, RcTerm)>,
+}
+
+impl Telescope {
+    pub fn shift_universes(&mut self, shift: LevelShift) {
+        shift_locals(&mut self.locals, shift);
+        for &mut (_, _, _, ref mut ann) in &mut self.fields {
+            ann.shift_universes(shift);
+        }
+    }
+}
+
+/// The alternatives of a variant type
+///
+/// These are only evaluated when they are needed, so that variant types can
+/// refer to themselves in their alternatives without being unfolded forever.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alternatives {
+    pub locals: Locals,
+    pub alts: Vec<(Label, RcTerm)>,
+}
+
+impl Alternatives {
+    pub fn shift_universes(&mut self, shift: LevelShift) {
+        shift_locals(&mut self.locals, shift);
+        for &mut (_, ref mut ann) in &mut self.alts {
+            ann.shift_universes(shift);
+        }
+    }
+}
+
+/// The clauses of a case expression whose head could not be evaluated
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clauses {
+    pub locals: Locals,
+    pub clauses: Vec<(RcPattern, RcTerm)>,
+}
+
+impl Clauses {
+    pub fn shift_universes(&mut self, shift: LevelShift) {
+        shift_locals(&mut self.locals, shift);
+        for &mut (_, ref mut body) in &mut self.clauses {
+            // FIXME: implement shifting for patterns as well!
+            body.shift_universes(shift);
+        }
+    }
+}
+
+fn shift_locals(locals: &mut Locals, shift: LevelShift) {
+    for value in locals.iter_mut() {
+        // Levels are shifted by the variables that refer to them
+        if let Value::Level(_) = **value {
+            continue;
+        }
+        value.shift_universes(shift);
+    }
+}
+
+/// Universe levels, in the form `max(constant, var_1^shift_1, .., var_n^shift_n)`
+///
+/// Levels that are not yet known are represented with metavariables, which
+/// are shifted in the same way as the level variables.
+///
+/// The level variables and metavariables are kept sorted and free of
+/// duplicates, and the constant is reset to zero if one of them is already
+/// known to be at least as large, so that equal levels are represented in the
+/// same way.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LevelValue {
+    pub constant: Level,
+    pub vars: Vec<(VarLevel, LevelShift)>,
+    pub metas: Vec<(MetaVar, LevelShift)>,
+}
+
+impl LevelValue {
+    pub fn constant(level: impl Into<Level>) -> LevelValue {
+        LevelValue {
+            constant: level.into(),
+            vars: Vec::new(),
+            metas: Vec::new(),
+        }
+    }
+
+    pub fn var(level: impl Into<VarLevel>, shift: impl Into<LevelShift>) -> LevelValue {
+        LevelValue {
+            constant: Level(0),
+            vars: vec![(level.into(), shift.into())],
+            metas: Vec::new(),
+        }
+    }
+
+    pub fn meta(meta: MetaVar, shift: impl Into<LevelShift>) -> LevelValue {
+        LevelValue {
+            constant: Level(0),
+            vars: Vec::new(),
+            metas: vec![(meta, shift.into())],
+        }
+    }
+
+    /// Read a level back from a value, returning `None` if the value is
+    /// stuck on something other than a level variable or an unsolved
+    /// metavariable
+    pub fn from_value(value: &Value) -> Option<LevelValue> {
+        if let Value::Level(ref level) = *value {
+            return Some(level.clone());
+        }
+
+        match value.head_app()? {
+            (&Head::Var(level, shift), spine) if spine.is_empty() => {
+                Some(LevelValue::var(level, shift))
+            },
+            (&Head::Meta(meta), spine) if spine.is_empty() => Some(LevelValue::meta(meta, 0)),
+            (_, _) => None,
+        }
+    }
+
+    /// Convert the level into a value, leaving lone level variables and
+    /// metavariables as neutral values
+    pub fn into_value(self) -> Value {
+        if self.constant == Level(0) && self.vars.len() + self.metas.len() == 1 {
+            if let Some(&(level, shift)) = self.vars.first() {
+                return Value::var(level, shift);
+            }
+            if let Some(&(meta, LevelShift(0))) = self.metas.first() {
+                return Value::from(Neutral::Head(Head::Meta(meta)));
+            }
+        }
+
+        Value::Level(self)
+    }
+
+    /// Returns the level if it does not depend on any level variables or
+    /// metavariables
+    pub fn as_constant(&self) -> Option<Level> {
+        if self.vars.is_empty() && self.metas.is_empty() {
+            Some(self.constant)
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the level depends on the given level variable
+    pub fn has_var(&self, level: VarLevel) -> bool {
+        self.vars.iter().any(|&(other, _)| other == level)
+    }
+
+    /// Returns `true` if the level depends on any metavariables
+    pub fn has_metas(&self) -> bool {
+        !self.metas.is_empty()
+    }
+
+    pub fn max(&self, other: &LevelValue) -> LevelValue {
+        let mut level = LevelValue {
+            constant: cmp::max(self.constant, other.constant),
+            vars: self.vars.clone(),
+            metas: self.metas.clone(),
+        };
+        for &(var, shift) in &other.vars {
+            insert_max(&mut level.vars, var, shift);
+        }
+        for &(meta, shift) in &other.metas {
+            insert_max(&mut level.metas, meta, shift);
+        }
+        level.normalize_constant();
+        level
+    }
+
+    /// Returns `true` if the level is less than or equal to `other`, no matter
+    /// what the level variables and metavariables are assigned to
+    pub fn le(&self, other: &LevelValue) -> bool {
+        let constant_le = self.constant <= other.constant
+            || Iterator::chain(
+                other.vars.iter().map(|&(_, shift)| shift),
+                other.metas.iter().map(|&(_, shift)| shift),
+            )
+            .any(|shift| self.constant <= Level(0) + shift);
+        let vars_le = self.vars.iter().all(|&(level, shift)| {
+            other
+                .vars
+                .iter()
+                .any(|&(other_level, other_shift)| level == other_level && shift <= other_shift)
+        });
+        let metas_le = self.metas.iter().all(|&(meta, shift)| {
+            other
+                .metas
+                .iter()
+                .any(|&(other_meta, other_shift)| meta == other_meta && shift <= other_shift)
+        });
+
+        constant_le && vars_le && metas_le
+    }
+
+    pub fn shift_universes(&mut self, shift: LevelShift) {
+        // A constant of zero is redundant when there are level variables or
+        // metavariables, so it should stay that way
+        if (self.vars.is_empty() && self.metas.is_empty()) || self.constant != Level(0) {
+            self.constant += shift;
+        }
+        for &mut (_, ref mut var_shift) in &mut self.vars {
+            *var_shift += shift;
+        }
+        for &mut (_, ref mut meta_shift) in &mut self.metas {
+            *meta_shift += shift;
+        }
+    }
+
+    fn normalize_constant(&mut self) {
+        let constant = self.constant;
+        let is_redundant = Iterator::chain(
+            self.vars.iter().map(|&(_, shift)| shift),
+            self.metas.iter().map(|&(_, shift)| shift),
+        )
+        .any(|shift| constant <= Level(0) + shift);
+
+        if is_redundant {
+            self.constant = Level(0);
+        }
+    }
+}
+
+/// Insert a shifted variable into a sorted list of variables, keeping the
+/// largest shift if it is already present
+fn insert_max<V: Copy + Ord>(entries: &mut Vec<(V, LevelShift)>, var: V, shift: LevelShift) {
+    match entries.binary_search_by_key(&var, |&(other, _)| other) {
+        Ok(i) => entries[i].1 = cmp::max(entries[i].1, shift),
+        Err(i) => entries.insert(i, (var, shift)),
+    }
+}
 
 /// Values
 ///
-/// These are either in _normal form_ (they cannot be reduced further) or are
-/// _neutral terms_ (there is a possibility of reducing further depending
-/// on the bindings given in the context)
-#[derive(Debug, Clone, PartialEq, moniker::BoundTerm)]
+/// These are either in _weak head normal form_ (they cannot be reduced
+/// further until they are applied or projected on) or are _neutral terms_
+/// (there is a possibility of reducing further depending on the bindings
+/// given in the context). The bodies of binders are stored as closures, and
+/// are only evaluated when they are needed.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     /// Universes
     Universe(Level),
+    /// Universes at a level that depends on level variables
+    UniverseAt(RcValue),
+    /// The universe of types that are polymorphic over universe levels
+    UniverseOmega,
+    /// The type of universe levels
+    LevelType,
+    /// Universe levels that depend on level variables, or are constants
+    Level(LevelValue),
     /// Literals
     Literal(Literal),
     /// Dependent function types
-    FunType(Scope<(Binder<String>, Embed<RcValue>), RcValue>),
+    FunType(Plicity, Multiplicity, RcValue, Closure),
     /// Function introductions
-    FunIntro(Scope<(Binder<String>, Embed<RcValue>), RcValue>),
+    FunIntro(Plicity, Multiplicity, RcValue, Closure),
     /// Dependent record types
-    RecordType(Scope<Nest<(Label, Binder<String>, Embed<RcValue>)>, ()>),
+    RecordType(Telescope),
     /// Dependent record introductions
     RecordIntro(Vec<(Label, RcValue)>),
+    /// Variant types
+    VariantType(Alternatives),
+    /// Variant introductions
+    VariantIntro(Label, RcValue),
+    /// Equality types
+    EqType(RcValue, RcValue, RcValue),
+    /// Equality introductions
+    EqIntro,
     /// Array literals
     ArrayIntro(Vec<RcValue>),
+    /// Terms that were removed during erasure
+    Erased,
     /// Neutral terms
     ///
     /// A term whose computation has stopped because of an attempt to compute an
@@ -40,42 +340,69 @@ impl Value {
         Value::Universe(level.into())
     }
 
-    pub fn var(var: impl Into<Var<String>>, shift: impl Into<LevelShift>) -> Value {
-        Value::Neutral(RcNeutral::from(Neutral::var(var, shift)), Spine::new())
+    /// The universe at the given level
+    pub fn universe_at(level: LevelValue) -> Value {
+        match level.as_constant() {
+            Some(level) => Value::Universe(level),
+            None => Value::UniverseAt(RcValue::from(level.into_value())),
+        }
     }
 
-    pub fn substs(&self, mappings: &[(FreeVar<String>, RcTerm)]) -> RcTerm {
-        // FIXME: This seems quite wasteful!
-        RcTerm::from(Term::from(self)).substs(mappings)
+    pub fn var(level: impl Into<VarLevel>, shift: impl Into<LevelShift>) -> Value {
+        Value::Neutral(RcNeutral::from(Neutral::var(level, shift)), Spine::new())
+    }
+
+    pub fn global(free_var: FreeVar<String>, shift: impl Into<LevelShift>) -> Value {
+        Value::Neutral(
+            RcNeutral::from(Neutral::global(free_var, shift)),
+            Spine::new(),
+        )
     }
 
     /// Returns `true` if the value is in weak head normal form
     pub fn is_whnf(&self) -> bool {
         match *self {
             Value::Universe(_)
+            | Value::UniverseAt(_)
+            | Value::UniverseOmega
+            | Value::LevelType
+            | Value::Level(_)
             | Value::Literal(_)
-            | Value::FunType(_)
-            | Value::FunIntro(_)
+            | Value::FunType(_, _, _, _)
+            | Value::FunIntro(_, _, _, _)
             | Value::RecordType(_)
             | Value::RecordIntro(_)
-            | Value::ArrayIntro(_) => true,
+            | Value::VariantType(_)
+            | Value::VariantIntro(_, _)
+            | Value::EqType(_, _, _)
+            | Value::EqIntro
+            | Value::ArrayIntro(_)
+            | Value::Erased => true,
             Value::Neutral(_, _) => false,
         }
     }
 
     /// Returns `true` if the value is in normal form (ie. it contains no neutral terms within it)
+    ///
+    /// Values containing closures are never considered to be in normal form,
+    /// because the bodies of the closures have yet to be evaluated.
     pub fn is_nf(&self) -> bool {
         match *self {
-            Value::Universe(_) | Value::Literal(_) => true,
-            Value::FunType(ref scope) | Value::FunIntro(ref scope) => {
-                (scope.unsafe_pattern.1).0.is_nf() && scope.unsafe_body.is_nf()
-            },
-            Value::RecordType(ref scope) => scope
-                .unsafe_pattern
-                .unsafe_patterns
-                .iter()
-                .all(|(_, _, Embed(ref term))| term.is_nf()),
+            Value::Universe(_)
+            | Value::UniverseOmega
+            | Value::LevelType
+            | Value::Literal(_)
+            | Value::EqIntro
+            | Value::Erased => true,
+            Value::UniverseAt(ref level) => level.is_nf(),
+            Value::Level(ref level) => level.as_constant().is_some(),
+            Value::FunType(_, _, _, _)
+            | Value::FunIntro(_, _, _, _)
+            | Value::RecordType(_)
+            | Value::VariantType(_) => false,
             Value::RecordIntro(ref fields) => fields.iter().all(|&(_, ref term)| term.is_nf()),
+            Value::VariantIntro(_, ref arg) => arg.is_nf(),
+            Value::EqType(ref ty, ref lhs, ref rhs) => ty.is_nf() && lhs.is_nf() && rhs.is_nf(),
             Value::ArrayIntro(ref elems) => elems.iter().all(|elem| elem.is_nf()),
             Value::Neutral(_, _) => false,
         }
@@ -90,39 +417,90 @@ impl Value {
         None
     }
 
-    pub fn free_var_app(&self) -> Option<(&FreeVar<String>, LevelShift, &[RcValue])> {
+    /// Returns the metavariable and spine if the value is an application of an
+    /// unsolved metavariable
+    pub fn meta_app(&self) -> Option<(MetaVar, &[RcValue])> {
         self.head_app().and_then(|(head, spine)| match *head {
-            Head::Var(Var::Free(ref free_var), shift) => Some((free_var, shift, &spine[..])),
-            Head::Import(_) | Head::Var(Var::Bound(_), _) => None,
+            Head::Meta(meta) => Some((meta, &spine[..])),
+            Head::Import(_) | Head::Var(_, _) | Head::Global(_, _) | Head::Fix(_, _, _) => None,
+        })
+    }
+
+    /// Returns the level of the variable if the value is a local variable
+    /// that has not been shifted or applied to any arguments
+    pub fn var_level(&self) -> Option<VarLevel> {
+        self.head_app().and_then(|(head, spine)| match *head {
+            Head::Var(level, LevelShift(0)) if spine.is_empty() => Some(level),
+            _ => None,
+        })
+    }
+
+    pub fn global_app(&self) -> Option<(&FreeVar<String>, LevelShift, &[RcValue])> {
+        self.head_app().and_then(|(head, spine)| match *head {
+            Head::Global(ref free_var, shift) => Some((free_var, shift, &spine[..])),
+            Head::Import(_) | Head::Meta(_) | Head::Fix(_, _, _) | Head::Var(_, _) => None,
         })
     }
 }
 
 /// Reference counted values
-#[derive(Debug, Clone, PartialEq, moniker::BoundTerm)]
+///
+/// Values that were produced by unfolding a definition are _glued_ to the
+/// application of the variable that they were unfolded from. The unfolded
+/// value is used when comparing values, while the folded form can be used
+/// when displaying them to the user.
+#[derive(Debug, Clone)]
 pub struct RcValue {
     pub inner: Rc<Value>,
+    pub folded: Option<Rc<Folded>>,
 }
 
 impl RcValue {
+    /// Remember that the value was unfolded from `folded`
+    pub fn with_folded(self, folded: Folded) -> RcValue {
+        RcValue {
+            inner: self.inner,
+            folded: Some(Rc::new(folded)),
+        }
+    }
+
     pub fn shift_universes(&mut self, shift: LevelShift) {
+        if let Some(ref mut folded) = self.folded {
+            Rc::make_mut(folded).shift_universes(shift);
+        }
+
         match *Rc::make_mut(&mut self.inner) {
             Value::Universe(ref mut level) => *level += shift,
-            Value::Literal(_) => {},
-            Value::FunType(ref mut scope) | Value::FunIntro(ref mut scope) => {
-                (scope.unsafe_pattern.1).0.shift_universes(shift);
-                scope.unsafe_body.shift_universes(shift);
-            },
-            Value::RecordType(ref mut scope) => {
-                for &mut (_, _, Embed(ref mut term)) in &mut scope.unsafe_pattern.unsafe_patterns {
-                    term.shift_universes(shift);
+            Value::UniverseAt(ref mut level) => {
+                if let Some(mut level_value) = LevelValue::from_value(level) {
+                    level_value.shift_universes(shift);
+                    *level = RcValue::from(level_value.into_value());
                 }
             },
+            Value::Level(ref mut level) => level.shift_universes(shift),
+            Value::UniverseOmega
+            | Value::LevelType
+            | Value::Literal(_)
+            | Value::EqIntro
+            | Value::Erased => {},
+            Value::FunType(_, _, ref mut ann, ref mut body)
+            | Value::FunIntro(_, _, ref mut ann, ref mut body) => {
+                ann.shift_universes(shift);
+                body.shift_universes(shift);
+            },
+            Value::RecordType(ref mut fields) => fields.shift_universes(shift),
+            Value::VariantType(ref mut alts) => alts.shift_universes(shift),
             Value::RecordIntro(ref mut fields) => {
                 for &mut (_, ref mut term) in fields {
                     term.shift_universes(shift);
                 }
             },
+            Value::VariantIntro(_, ref mut arg) => arg.shift_universes(shift),
+            Value::EqType(ref mut ty, ref mut lhs, ref mut rhs) => {
+                ty.shift_universes(shift);
+                lhs.shift_universes(shift);
+                rhs.shift_universes(shift);
+            },
             Value::ArrayIntro(ref mut elems) => {
                 for elem in elems {
                     elem.shift_universes(shift);
@@ -138,10 +516,18 @@ impl RcValue {
     }
 }
 
+impl PartialEq for RcValue {
+    fn eq(&self, other: &RcValue) -> bool {
+        // Glued values are equal if their unfolded values are equal
+        self.inner == other.inner
+    }
+}
+
 impl From<Value> for RcValue {
     fn from(src: Value) -> RcValue {
         RcValue {
             inner: Rc::new(src),
+            folded: None,
         }
     }
 }
@@ -154,14 +540,66 @@ impl ops::Deref for RcValue {
     }
 }
 
+/// The application of a variable that a value was unfolded from
+#[derive(Debug, Clone, PartialEq)]
+pub struct Folded {
+    pub head: FoldedHead,
+    pub spine: Spine,
+}
+
+impl Folded {
+    pub fn new(head: FoldedHead) -> Folded {
+        Folded {
+            head,
+            spine: Spine::new(),
+        }
+    }
+
+    /// Apply the folded form to another argument
+    pub fn app(&self, arg: RcValue) -> Folded {
+        let mut spine = self.spine.clone();
+        spine.push(arg);
+
+        Folded {
+            head: self.head.clone(),
+            spine,
+        }
+    }
+
+    pub fn shift_universes(&mut self, shift: LevelShift) {
+        match self.head {
+            FoldedHead::Var(_, ref mut head_shift) | FoldedHead::Global(_, ref mut head_shift) => {
+                *head_shift += shift
+            },
+        }
+        for arg in &mut self.spine {
+            arg.shift_universes(shift);
+        }
+    }
+}
+
+/// The variable that a value was unfolded from
+#[derive(Debug, Clone, PartialEq)]
+pub enum FoldedHead {
+    /// Local variables that were given a definition
+    Var(VarLevel, LevelShift),
+    /// Global variables that were given a definition
+    Global(FreeVar<String>, LevelShift),
+}
+
 /// The head of an application
-#[derive(Debug, Clone, PartialEq, moniker::BoundTerm)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Head {
-    /// Variables that have not yet been replaced with a definition
-    Var(Var<String>, LevelShift),
+    /// Local variables that have not yet been given a value
+    Var(VarLevel, LevelShift),
+    /// Global variables that have not yet been replaced with a definition
+    Global(FreeVar<String>, LevelShift),
     /// Imported definitions
     Import(String),
-    // TODO: Metavariables
+    /// Metavariables that have not yet been solved
+    Meta(MetaVar),
+    /// Fixed points that have not yet been unfolded
+    Fix(Totality, RcValue, Closure),
 }
 
 /// The spine of a neutral term
@@ -173,24 +611,32 @@ pub type Spine = Vec<RcValue>;
 ///
 /// These might be able to be reduced further depending on the bindings in the
 /// context
-#[derive(Debug, Clone, PartialEq, moniker::BoundTerm)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Neutral {
     /// Head of an application
     Head(Head),
     /// Field projection
     RecordProj(RcNeutral, Label, LevelShift),
+    /// Record extension
+    RecordExtend(RcNeutral, Vec<(Label, RcValue)>),
+    /// Equality elimination
+    EqElim(RcValue, RcNeutral, RcValue),
     /// Case expressions
-    Case(RcNeutral, Vec<Scope<RcPattern, RcValue>>),
+    Case(RcNeutral, Clauses),
 }
 
 impl Neutral {
-    pub fn var(var: impl Into<Var<String>>, shift: impl Into<LevelShift>) -> Neutral {
-        Neutral::Head(Head::Var(var.into(), shift.into()))
+    pub fn var(level: impl Into<VarLevel>, shift: impl Into<LevelShift>) -> Neutral {
+        Neutral::Head(Head::Var(level.into(), shift.into()))
+    }
+
+    pub fn global(free_var: FreeVar<String>, shift: impl Into<LevelShift>) -> Neutral {
+        Neutral::Head(Head::Global(free_var, shift.into()))
     }
 }
 
 /// Reference counted neutral values
-#[derive(Debug, Clone, PartialEq, moniker::BoundTerm)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct RcNeutral {
     pub inner: Rc<Neutral>,
 }
@@ -201,16 +647,30 @@ impl RcNeutral {
             // Neutral::Head(Head::Var(_, ref mut head_shift)) => {
             //     *head_shift += shift; // NOTE: Not sure if this is correct!
             // },
-            Neutral::Head(Head::Var(_, _)) | Neutral::Head(Head::Import(_)) => {},
+            Neutral::Head(Head::Var(_, _))
+            | Neutral::Head(Head::Global(_, _))
+            | Neutral::Head(Head::Import(_))
+            | Neutral::Head(Head::Meta(_)) => {},
+            Neutral::Head(Head::Fix(_, ref mut ann, ref mut body)) => {
+                ann.shift_universes(shift);
+                body.shift_universes(shift);
+            },
             Neutral::RecordProj(ref mut expr, _, _) => expr.shift_universes(shift),
-            Neutral::Case(ref mut expr, ref mut clauses) => {
+            Neutral::RecordExtend(ref mut expr, ref mut fields) => {
                 expr.shift_universes(shift);
-                for clause in clauses {
-                    // FIXME: implement shifting for patterns as well!
-                    // clause.unsafe_pattern.shift_universes(shift);
-                    clause.unsafe_body.shift_universes(shift);
+                for &mut (_, ref mut value) in fields {
+                    value.shift_universes(shift);
                 }
             },
+            Neutral::EqElim(ref mut motive, ref mut proof, ref mut body) => {
+                motive.shift_universes(shift);
+                proof.shift_universes(shift);
+                body.shift_universes(shift);
+            },
+            Neutral::Case(ref mut expr, ref mut clauses) => {
+                expr.shift_universes(shift);
+                clauses.shift_universes(shift);
+            },
         }
     }
 }
@@ -1,10 +1,14 @@
+use moniker::{BoundTerm, OnBoundFn, OnFreeFn, ScopeState, Var};
 use pretty::{BoxDoc, Doc};
 use std::fmt;
-use std::ops::{Add, AddAssign};
+use std::ops::{Add, AddAssign, Mul};
 
 pub mod core;
 pub mod domain;
 
+/// The names of global definitions, which are still bound using `moniker`
+pub use moniker::FreeVar;
+
 /// An effectively 'infinite' line length for when we don't have an explicit
 /// width provided for pretty printing.
 ///
@@ -16,14 +20,24 @@ pub const PRETTY_FALLBACK_WIDTH: usize = 1_000_000;
 #[derive(Clone)]
 pub enum Import {
     Term(core::RcTerm),
-    Prim(for<'a> fn(&'a [domain::RcValue]) -> Option<domain::RcValue>),
+    /// Primitives, along with the number of arguments that they expect. The
+    /// interpretation returns `None` if the arguments are not yet known, or
+    /// if the primitive is undefined for them, as when dividing by zero.
+    Prim(
+        usize,
+        for<'a> fn(&'a [domain::RcValue]) -> Option<domain::RcValue>,
+    ),
 }
 
 impl fmt::Debug for Import {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Import::Term(ref term) => f.debug_tuple("Term").field(term).finish(),
-            Import::Prim(_) => f.debug_tuple("Prim").field(&"|params| { .. }").finish(),
+            Import::Prim(arity, _) => f
+                .debug_tuple("Prim")
+                .field(&arity)
+                .field(&"|params| { .. }")
+                .finish(),
         }
     }
 }
@@ -98,7 +112,9 @@ impl fmt::Display for Level {
 }
 
 /// A shift in universe level
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, moniker::BoundTerm, moniker::BoundPattern)]
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, moniker::BoundTerm, moniker::BoundPattern,
+)]
 pub struct LevelShift(pub u32);
 
 impl From<u32> for LevelShift {
@@ -141,10 +157,278 @@ impl AddAssign<LevelShift> for Level {
     }
 }
 
+/// A metavariable, standing in for a term that is yet to be solved during
+/// elaboration
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, moniker::BoundTerm)]
+pub struct MetaVar(pub u32);
+
+impl fmt::Display for MetaVar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "?{}", self.0)
+    }
+}
+
+/// A de Bruijn index, referring to a local variable by the number of binders
+/// between its occurrence and the binder that introduced it
+///
+/// Indices are used in terms, where they don't need to be adjusted when a term
+/// is placed inside more binders than the ones it mentions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VarIndex(pub usize);
+
+impl VarIndex {
+    /// Convert the index to a level, in a scope of the given size
+    pub fn to_level(self, size: usize) -> Option<VarLevel> {
+        Some(VarLevel(size.checked_sub(self.0 + 1)?))
+    }
+}
+
+impl From<usize> for VarIndex {
+    fn from(src: usize) -> VarIndex {
+        VarIndex(src)
+    }
+}
+
+impl fmt::Display for VarIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A de Bruijn level, referring to a local variable by the number of binders
+/// between the outside of the term and the binder that introduced it
+///
+/// Levels are used in values, where they don't need to be adjusted when a
+/// value is moved underneath more binders.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VarLevel(pub usize);
+
+impl VarLevel {
+    /// Convert the level to an index, in a scope of the given size
+    pub fn to_index(self, size: usize) -> Option<VarIndex> {
+        Some(VarIndex(size.checked_sub(self.0 + 1)?))
+    }
+}
+
+impl From<usize> for VarLevel {
+    fn from(src: usize) -> VarLevel {
+        VarLevel(src)
+    }
+}
+
+impl fmt::Display for VarLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The name that a variable was given in the source code, if any
+///
+/// Names are only kept around as hints for when terms are displayed to the
+/// user, so they are ignored when comparing terms for equality.
+#[derive(Debug, Clone, Default)]
+pub struct NameHint(pub Option<String>);
+
+impl PartialEq for NameHint {
+    fn eq(&self, _: &NameHint) -> bool {
+        true
+    }
+}
+
+impl From<String> for NameHint {
+    fn from(src: String) -> NameHint {
+        NameHint(Some(src))
+    }
+}
+
+impl<'a> From<&'a str> for NameHint {
+    fn from(src: &'a str) -> NameHint {
+        NameHint(Some(src.to_owned()))
+    }
+}
+
+impl<'a> From<&'a FreeVar<String>> for NameHint {
+    fn from(src: &'a FreeVar<String>) -> NameHint {
+        NameHint(src.pretty_name.clone())
+    }
+}
+
+impl fmt::Display for NameHint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(ref name) => write!(f, "{}", name),
+            None => write!(f, "_"),
+        }
+    }
+}
+
+/// Whether a function parameter is supplied explicitly by the caller, or is
+/// to be inferred during elaboration
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Plicity {
+    /// Explicit parameters
+    ///
+    /// ```text
+    /// (x : t1) -> t2
+    /// ```
+    Explicit,
+    /// Implicit parameters, which are filled in with metavariables at
+    /// application sites
+    ///
+    /// ```text
+    /// {x : t1} -> t2
+    /// ```
+    Implicit,
+    /// Instance parameters, which are filled in by searching for a definition
+    /// of the expected type that is in scope at the application site
+    ///
+    /// ```text
+    /// {{x : t1}} -> t2
+    /// ```
+    Instance,
+}
+
+impl Plicity {
+    /// The delimiter that opens a parameter group with this plicity
+    pub fn open_delim(self) -> &'static str {
+        match self {
+            Plicity::Explicit => "(",
+            Plicity::Implicit => "{",
+            Plicity::Instance => "{{",
+        }
+    }
+
+    /// The delimiter that closes a parameter group with this plicity
+    pub fn close_delim(self) -> &'static str {
+        match self {
+            Plicity::Explicit => ")",
+            Plicity::Implicit => "}",
+            Plicity::Instance => "}}",
+        }
+    }
+}
+
+impl<N: Clone + PartialEq> BoundTerm<N> for Plicity {
+    fn term_eq(&self, other: &Plicity) -> bool {
+        self == other
+    }
+
+    fn close_term(&mut self, _: ScopeState, _: &impl OnFreeFn<N>) {}
+    fn open_term(&mut self, _: ScopeState, _: &impl OnBoundFn<N>) {}
+    fn visit_vars(&self, _: &mut impl FnMut(&Var<N>)) {}
+    fn visit_mut_vars(&mut self, _: &mut impl FnMut(&mut Var<N>)) {}
+}
+
+/// The number of times that a variable may be used at runtime
+///
+/// These form a semiring, which lets us count the uses of a variable by
+/// adding the uses that are found in each part of a term, and scaling the
+/// uses in an argument by the multiplicity of the parameter it is passed to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, moniker::BoundTerm, moniker::BoundPattern)]
+pub enum Multiplicity {
+    /// Variables that are erased at runtime, and so may only be used in types
+    ///
+    /// ```text
+    /// (0 x : t1) -> t2
+    /// ```
+    Zero,
+    /// Variables that must be used exactly once at runtime
+    ///
+    /// ```text
+    /// (1 x : t1) -> t2
+    /// ```
+    One,
+    /// Variables that can be used any number of times
+    ///
+    /// ```text
+    /// (x : t1) -> t2
+    /// ```
+    Many,
+}
+
+impl Multiplicity {
+    /// Returns `true` if a variable that was used `self` times is allowed by
+    /// a binder of multiplicity `bound`
+    pub fn is_within(self, bound: Multiplicity) -> bool {
+        self == bound || bound == Multiplicity::Many
+    }
+
+    /// Combine the uses found in alternative branches, only one of which
+    /// will be taken at runtime
+    pub fn join(self, other: Multiplicity) -> Multiplicity {
+        if self == other {
+            self
+        } else {
+            Multiplicity::Many
+        }
+    }
+}
+
+impl fmt::Display for Multiplicity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Multiplicity::Zero => write!(f, "0"),
+            Multiplicity::One => write!(f, "1"),
+            Multiplicity::Many => write!(f, "ω"),
+        }
+    }
+}
+
+impl Add for Multiplicity {
+    type Output = Multiplicity;
+
+    fn add(self, other: Multiplicity) -> Multiplicity {
+        match (self, other) {
+            (Multiplicity::Zero, mult) | (mult, Multiplicity::Zero) => mult,
+            (_, _) => Multiplicity::Many,
+        }
+    }
+}
+
+impl Mul for Multiplicity {
+    type Output = Multiplicity;
+
+    fn mul(self, other: Multiplicity) -> Multiplicity {
+        match (self, other) {
+            (Multiplicity::Zero, _) | (_, Multiplicity::Zero) => Multiplicity::Zero,
+            (Multiplicity::One, mult) | (mult, Multiplicity::One) => mult,
+            (Multiplicity::Many, Multiplicity::Many) => Multiplicity::Many,
+        }
+    }
+}
+
+/// Whether a recursive definition is known to terminate
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Totality {
+    /// Definitions that have passed the termination checker, and so can be
+    /// safely unfolded during type checking
+    Total,
+    /// Definitions that may not terminate, which are never unfolded during
+    /// type checking
+    ///
+    /// ```text
+    /// partial x : t;
+    /// ```
+    Partial,
+}
+
+impl<N: Clone + PartialEq> BoundTerm<N> for Totality {
+    fn term_eq(&self, other: &Totality) -> bool {
+        self == other
+    }
+
+    fn close_term(&mut self, _: ScopeState, _: &impl OnFreeFn<N>) {}
+    fn open_term(&mut self, _: ScopeState, _: &impl OnBoundFn<N>) {}
+    fn visit_vars(&self, _: &mut impl FnMut(&Var<N>)) {}
+    fn visit_mut_vars(&mut self, _: &mut impl FnMut(&mut Var<N>)) {}
+}
+
 /// A label that describes the name of a field in a record
 ///
 /// Labels are significant when comparing for alpha-equality
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, moniker::BoundPattern, moniker::BoundTerm)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, moniker::BoundPattern, moniker::BoundTerm,
+)]
 pub struct Label(pub String);
 
 impl From<String> for Label {
@@ -0,0 +1,35 @@
+//! Benchmarks the time taken to elaborate the prelude
+//!
+//! Run with `cargo bench --package pikelet-driver`, or with
+//! `tools/bench-prelude <revision>` to compare against an earlier revision.
+
+use std::time::{Duration, Instant};
+
+use pikelet_driver::Driver;
+
+const WARMUP_ITERATIONS: u32 = 3;
+const ITERATIONS: u32 = 20;
+
+fn main() {
+    for _ in 0..WARMUP_ITERATIONS {
+        Driver::with_prelude();
+    }
+
+    let mut total = Duration::from_secs(0);
+    let mut fastest = Duration::from_secs(u64::max_value());
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        Driver::with_prelude();
+        let elapsed = start.elapsed();
+
+        total += elapsed;
+        fastest = fastest.min(elapsed);
+    }
+
+    println!(
+        "prelude: {:?} mean, {:?} fastest ({} iterations)",
+        total / ITERATIONS,
+        fastest,
+        ITERATIONS,
+    );
+}
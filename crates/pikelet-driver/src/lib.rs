@@ -42,21 +42,33 @@
 //!                       |                                  '-------------------------------------'
 //!                       v                                                    ^
 //!     .----------------------------------.                                   |
-//!     | pikelet_core::syntax::core::Term | -- pikelet_core::normalize -------'
+//!     | pikelet_core::syntax::core::Term | -- pikelet_core::nbe::eval -------'
 //!     '----------------------------------'
 //!                       |
 //!                       |
 //! - - - - - - - - - - - | - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
-//! Middle (TODO)         |
+//! Middle                |
+//!                       |
+//!              pikelet_anf::lower
 //!                       |
 //!                       v
-//!               A-Normal Form (ANF)
+//!         .---------------------------.
+//!         | pikelet_anf::syntax::Expr |
+//!         '---------------------------'
+//!                       |
+//!              pikelet_cc::convert
 //!                       |
 //!                       v
-//!             Closure Conversion (CC)
+//!        .-----------------------------.
+//!        | pikelet_cc::syntax::Program |
+//!        '-----------------------------'
+//!                       |
+//!              pikelet_ssa::lower
 //!                       |
 //!                       v
-//!          Static Single Assignment (SSA)
+//!        .------------------------------.
+//!        | pikelet_ssa::syntax::Program |
+//!        '------------------------------'
 //!                       |
 //!                       |
 //! - - - - - - - - - - - | - - - - - - - - - - - - - - - - - - - - - - - - - - - - -
@@ -65,25 +77,50 @@
 //!                       v
 //!                    Codegen
 //!                       |
-//!                       *-------> Bytecode?
+//!                       *-------> Bytecode VM (pikelet_vm::compile, from the erased core syntax)
+//!                       |
+//!                       *-------> WASM (pikelet_wasm::compile, by way of pikelet_mono::lower)
 //!                       |
-//!                       *-------> WASM?
+//!                       *-------> C (pikelet_c::compile, by way of pikelet_mono::lower)
 //!                       |
-//!                       *-------> Cranelift IR?
+//!                       *-------> Cranelift JIT (pikelet_jit::compile)
 //!                       |
 //!                       '-------> LLVM IR?
 //! ```
 //!
-//! As you can see we have only built the front-end as of the time of writing. When
-//! we begin to build out a [compiler back end](https://github.com/pikelet-lang/pikelet/issues/9),
-//! more stages will be added after type checking and elaboration.
+//! As you can see the [compiler back end](https://github.com/pikelet-lang/pikelet/issues/9)
+//! is still in its early stages. Programs can be compiled to machine code at
+//! runtime with the Cranelift JIT in `pikelet_jit`, or run by erasing and
+//! evaluating the core syntax directly with the call-by-value evaluator in
+//! `pikelet_core::eval`. The erased core syntax can also be compiled to
+//! bytecode for the stack-based virtual machine in `pikelet_vm`, which
+//! supports closures and records without needing a native code generator.
+//! First-order files can be compiled to WebAssembly modules with
+//! `pikelet_wasm`, or to C source code with `pikelet_c`. These share the
+//! first-order representation in `pikelet_mono`, which is lowered from the
+//! typed core syntax so that the representations of values can be picked from
+//! their types.
 //!
 //! ## Name binding
 //!
 //! Name binding is a surprisingly challenging thing to implement in type checkers
-//! and compilers. We use the [`moniker` crate](https://github.com/brendanzab/moniker)
-//! for this. Unfortunately this uses a quite slow method of name binding, and could
-//! result in performance blowouts in the future. This is something to keep an eye on!
+//! and compilers. The concrete and raw syntaxes use the
+//! [`moniker` crate](https://github.com/brendanzab/moniker) for this, which is
+//! convenient but quite slow. The core syntax instead uses de Bruijn indices for
+//! the local variables in terms, and de Bruijn levels for those in values, keeping
+//! the original names around only as hints for resugaring. This avoids having to
+//! generate fresh variables and walk terms when entering binders during
+//! elaboration and normalization. The `prelude` benchmark can be used to keep an
+//! eye on the performance of checking the prelude, and `tools/bench-prelude`
+//! runs it against an earlier revision too, so that the timings can be compared
+//! on the same machine. By default it compares against the last revision that
+//! used `moniker` in the core syntax, showing the effect of the switch to
+//! de Bruijn indices, but any other revision can be passed instead:
+//!
+//! ```text
+//! $ tools/bench-prelude
+//! $ tools/bench-prelude master
+//! ```
 //!
 //! ## Performance considerations
 //!
@@ -134,6 +171,7 @@ use pikelet_concrete::desugar::{Desugar, DesugarEnv};
 use pikelet_concrete::elaborate::Context;
 use pikelet_concrete::resugar::Resugar;
 use pikelet_concrete::syntax::raw;
+use pikelet_core::eval;
 use pikelet_core::syntax::{core, domain, Import};
 
 /// An environment that keeps track of the state of a Pikelet program during
@@ -226,11 +264,12 @@ impl Driver {
         &mut self,
         name: FileName,
         src: String,
-    ) -> Result<domain::RcValue, Vec<Diagnostic>> {
+    ) -> Result<core::RcTerm, Vec<Diagnostic>> {
         use pikelet_concrete::elaborate::InternalError;
+        use pikelet_core::nbe::EvalEnv;
 
         let (term, _) = self.infer_file(name, src)?;
-        pikelet_core::nbe::nf_term(&self.context, &term)
+        pikelet_core::nbe::nf_term(&EvalEnv(&self.context), &term)
             .map_err(|err| vec![InternalError::from(err).to_diagnostic()])
     }
 
@@ -244,10 +283,34 @@ impl Driver {
     }
 
     /// Normalize a term
-    pub fn normalize_term(&self, term: &core::RcTerm) -> Result<domain::RcValue, Vec<Diagnostic>> {
+    pub fn normalize_term(&self, term: &core::RcTerm) -> Result<core::RcTerm, Vec<Diagnostic>> {
+        use pikelet_concrete::elaborate::InternalError;
+        use pikelet_core::nbe::EvalEnv;
+
+        pikelet_core::nbe::nf_term(&EvalEnv(&self.context), term)
+            .map_err(|err| vec![InternalError::from(err).to_diagnostic()])
+    }
+
+    /// Run a term using the call-by-value evaluator, returning its value
+    /// along with some statistics about the run
+    ///
+    /// Unlike `normalize_term`, this does not evaluate the bodies of functions
+    /// until they are applied. Programs that recurse too deeply are reported
+    /// as errors, rather than overflowing the stack.
+    pub fn run_term(
+        &self,
+        term: &core::RcTerm,
+    ) -> Result<(eval::RcValue, eval::Stats), Vec<Diagnostic>> {
+        eval::run_term(&self.context, term)
+            .map_err(|err| vec![Diagnostic::new_error(format!("runtime error: {}", err))])
+    }
+
+    /// Read a value back into a term in normal form
+    pub fn read_back(&self, value: &domain::RcValue) -> Result<core::RcTerm, Vec<Diagnostic>> {
         use pikelet_concrete::elaborate::InternalError;
 
-        pikelet_core::nbe::nf_term(&self.context, term)
+        self.context
+            .quote(value)
             .map_err(|err| vec![InternalError::from(err).to_diagnostic()])
     }
 
@@ -262,6 +325,13 @@ impl Driver {
         self.context.resugar(src)
     }
 
+    /// Take the warnings that have been produced since they were last taken
+    pub fn take_warnings(&self) -> Vec<Diagnostic> {
+        (self.context.take_warnings().iter())
+            .map(|warning| warning.to_diagnostic())
+            .collect()
+    }
+
     /// Emit the diagnostics using the given writer
     pub fn emit<'a>(
         &self,
@@ -0,0 +1,30 @@
+use pikelet_driver::{Driver, FileName};
+
+#[test]
+fn depth_limit() {
+    let mut driver = Driver::new();
+
+    let (term, _) = driver
+        .infer_file(
+            FileName::virtual_("test"),
+            r#"
+                let
+                    partial sum : U64 -> U64;
+                    sum n =
+                        if (import "prim/u64/eq") n 0 then 0 else
+                            (import "prim/u64/add") n (sum ((import "prim/u64/sub") n 1));
+                in
+                    sum 100000
+            "#
+            .to_owned(),
+        )
+        .unwrap_or_else(|_| panic!("type error!"));
+
+    match driver.run_term(&term) {
+        Ok((value, _)) => panic!("expected error, found {}", value),
+        Err(diagnostics) => assert_eq!(
+            diagnostics[0].message,
+            "runtime error: stack overflow: exceeded the depth limit of 1000",
+        ),
+    }
+}
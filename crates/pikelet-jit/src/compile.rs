@@ -0,0 +1,699 @@
+//! Compilation of programs in static single assignment form to machine code
+//!
+//! Each function in the program is translated to a Cranelift function that
+//! takes the closure that it was called through and its argument, and the
+//! main function is translated to a function that takes no arguments. The
+//! blocks of the functions map directly to Cranelift's extended basic blocks,
+//! with the parameters of the blocks becoming the parameters of the EBBs.
+//!
+//! Primitive imports are only supported when they are applied directly, in
+//! which case they are compiled to the corresponding machine instructions, or
+//! to calls into the runtime. They can't be passed around as values yet.
+//!
+//! This assumes that the target has 64-bit pointers.
+
+use cranelift::codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift::codegen::ir::{self, types, AbiParam, Ebb, InstBuilder, MemFlags, TrapCode};
+use cranelift::frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_module::{FuncId, Linkage, Module, ModuleError};
+use cranelift_simplejit::{SimpleJITBackend, SimpleJITBuilder};
+use std::collections::HashMap;
+use std::mem;
+
+use pikelet_core::syntax::core::RcTerm;
+use pikelet_core::syntax::{Label, Literal};
+use pikelet_ssa::lower::LowerError;
+use pikelet_ssa::syntax::{Function, Inst, Program, Terminator, ValueId};
+
+use crate::runtime::{self, Labels, Repr, Scalar, Value, Word};
+
+/// The type of words in compiled code
+const WORD: ir::Type = types::I64;
+
+/// The size of a word, in bytes
+const WORD_SIZE: i32 = 8;
+
+/// An error produced while compiling a program
+#[derive(Debug, Clone, PartialEq, failure::Fail)]
+#[fail(display = "{}", message)]
+pub struct JitError {
+    pub message: String,
+}
+
+impl JitError {
+    pub fn new(message: impl Into<String>) -> JitError {
+        JitError {
+            message: message.into(),
+        }
+    }
+}
+
+impl From<LowerError> for JitError {
+    fn from(src: LowerError) -> JitError {
+        JitError::new(src.message)
+    }
+}
+
+impl From<ModuleError> for JitError {
+    fn from(src: ModuleError) -> JitError {
+        JitError::new(src.to_string())
+    }
+}
+
+/// A compiled program
+#[derive(Copy, Clone)]
+pub struct Compiled {
+    main: extern "C" fn() -> Word,
+}
+
+impl Compiled {
+    /// Run the main function of the program
+    pub fn run(&self) -> Word {
+        (self.main)()
+    }
+}
+
+/// The functions in the runtime that compiled code calls into
+struct RuntimeFunctions {
+    alloc: FuncId,
+    record_proj: FuncId,
+    record_extend: FuncId,
+    string_cmp: FuncId,
+    string_append: FuncId,
+    to_string: FuncId,
+}
+
+/// A just-in-time compiler, which holds on to the code of the programs that
+/// it has compiled
+pub struct Jit {
+    module: Module<SimpleJITBackend>,
+    runtime: RuntimeFunctions,
+    labels: Labels,
+    /// The number of programs compiled so far, used to give the functions of
+    /// each program unique names
+    program_count: usize,
+}
+
+impl Jit {
+    pub fn new() -> Jit {
+        let mut builder = SimpleJITBuilder::new(cranelift_module::default_libcall_names());
+        for (name, address) in runtime::symbols() {
+            builder.symbol(name, address);
+        }
+        let mut module = Module::new(builder);
+
+        let mut declare = |name: &str, param_count: usize| {
+            let signature = word_signature(&module, param_count);
+            module
+                .declare_function(name, Linkage::Import, &signature)
+                .expect("failed to declare runtime function")
+        };
+
+        let runtime = RuntimeFunctions {
+            alloc: declare("pikelet_alloc", 1),
+            record_proj: declare("pikelet_record_proj", 2),
+            record_extend: declare("pikelet_record_extend", 2),
+            string_cmp: declare("pikelet_string_cmp", 2),
+            string_append: declare("pikelet_string_append", 2),
+            to_string: declare("pikelet_to_string", 2),
+        };
+
+        Jit {
+            module,
+            runtime,
+            labels: Labels::default(),
+            program_count: 0,
+        }
+    }
+
+    /// Compile a closed term, going by way of static single assignment form
+    pub fn compile_term(&mut self, term: &RcTerm) -> Result<Compiled, JitError> {
+        let program = pikelet_ssa::lower::lower_term(term)?;
+        self.compile(&program)
+    }
+
+    /// Compile a program in static single assignment form
+    pub fn compile(&mut self, program: &Program) -> Result<Compiled, JitError> {
+        let prefix = format!("program{}", self.program_count);
+        self.program_count += 1;
+
+        // Declare the functions up front, so that closures can refer to
+        // functions that have not been defined yet
+        let closure_signature = word_signature(&self.module, 2);
+        let function_ids = (0..program.functions.len())
+            .map(|index| {
+                let name = format!("{}_function{}", prefix, index);
+                self.module
+                    .declare_function(&name, Linkage::Local, &closure_signature)
+            })
+            .collect::<Result<Vec<_>, ModuleError>>()?;
+        let main_signature = word_signature(&self.module, 0);
+        let main_id = self.module.declare_function(
+            &format!("{}_main", prefix),
+            Linkage::Export,
+            &main_signature,
+        )?;
+
+        for (&id, function) in function_ids.iter().zip(&program.functions) {
+            self.define(id, closure_signature.clone(), function, &function_ids)?;
+        }
+        self.define(main_id, main_signature, &program.main, &function_ids)?;
+
+        self.module.finalize_definitions();
+        let main = self.module.get_finalized_function(main_id);
+
+        Ok(Compiled {
+            main: unsafe { mem::transmute::<*const u8, extern "C" fn() -> Word>(main) },
+        })
+    }
+
+    /// Apply a compiled function to an argument
+    ///
+    /// # Safety
+    ///
+    /// The function must be a closure that was produced by compiled code, and
+    /// the argument must be of the type that the function expects.
+    pub unsafe fn apply(&self, function: Word, arg: Word) -> Word {
+        let code = *(function as *const *const u8);
+        let code = mem::transmute::<*const u8, extern "C" fn(Word, Word) -> Word>(code);
+        code(function, arg)
+    }
+
+    /// Encode a value so that it can be passed to compiled code
+    pub fn encode(&mut self, value: &Value) -> Word {
+        runtime::encode(&mut self.labels, value)
+    }
+
+    /// Decode a value that was returned by compiled code
+    ///
+    /// # Safety
+    ///
+    /// The word must be a value of a type with the given representation.
+    pub unsafe fn decode(&self, word: Word, repr: &Repr) -> Result<Value, JitError> {
+        runtime::decode(&self.labels, word, repr).ok_or_else(|| {
+            JitError::new("the value returned by compiled code does not fit the representation")
+        })
+    }
+
+    fn define(
+        &mut self,
+        id: FuncId,
+        signature: ir::Signature,
+        function: &Function,
+        function_ids: &[FuncId],
+    ) -> Result<(), JitError> {
+        let mut context = self.module.make_context();
+        context.func.signature = signature;
+        let mut builder_context = FunctionBuilderContext::new();
+
+        Translator {
+            builder: FunctionBuilder::new(&mut context.func, &mut builder_context),
+            module: &mut self.module,
+            runtime: &self.runtime,
+            labels: &mut self.labels,
+            function_ids,
+            ebbs: Vec::new(),
+            values: HashMap::new(),
+            prims: HashMap::new(),
+            func_refs: HashMap::new(),
+            closure_sig: None,
+        }
+        .translate(function)?;
+
+        self.module.define_function(id, &mut context)?;
+        self.module.clear_context(&mut context);
+
+        Ok(())
+    }
+}
+
+impl Default for Jit {
+    fn default() -> Jit {
+        Jit::new()
+    }
+}
+
+/// A signature that takes some words and returns a word
+fn word_signature(module: &Module<SimpleJITBackend>, param_count: usize) -> ir::Signature {
+    let mut signature = module.make_signature();
+    for _ in 0..param_count {
+        signature.params.push(AbiParam::new(WORD));
+    }
+    signature.returns.push(AbiParam::new(WORD));
+    signature
+}
+
+/// Translates a function to Cranelift IR
+struct Translator<'a> {
+    builder: FunctionBuilder<'a>,
+    module: &'a mut Module<SimpleJITBackend>,
+    runtime: &'a RuntimeFunctions,
+    labels: &'a mut Labels,
+    /// The functions of the program that is being compiled
+    function_ids: &'a [FuncId],
+    /// The EBBs that the blocks of the function are translated to
+    ebbs: Vec<Ebb>,
+    values: HashMap<ValueId, ir::Value>,
+    /// Primitives that have been applied to fewer arguments than they need
+    prims: HashMap<ValueId, (String, Vec<ir::Value>)>,
+    func_refs: HashMap<FuncId, ir::FuncRef>,
+    closure_sig: Option<ir::SigRef>,
+}
+
+impl<'a> Translator<'a> {
+    fn translate(mut self, function: &Function) -> Result<(), JitError> {
+        // Create the EBBs up front, so that they can be jumped to before they
+        // have been filled in
+        for block in &function.blocks {
+            let ebb = self.builder.create_ebb();
+            for &param in &block.params {
+                let value = self.builder.append_ebb_param(ebb, WORD);
+                self.values.insert(param, value);
+            }
+            self.ebbs.push(ebb);
+        }
+
+        for (ebb, block) in Iterator::zip(self.ebbs.clone().into_iter(), &function.blocks) {
+            self.builder.switch_to_block(ebb);
+            for &(value, ref inst) in &block.insts {
+                self.translate_inst(value, inst)?;
+            }
+            self.translate_terminator(&block.terminator)?;
+        }
+
+        self.builder.seal_all_blocks();
+        self.builder.finalize();
+
+        Ok(())
+    }
+
+    fn translate_inst(&mut self, value: ValueId, inst: &Inst) -> Result<(), JitError> {
+        let result = match *inst {
+            Inst::Literal(ref literal) => self.word(runtime::encode_literal(literal)),
+            Inst::Erased => self.word(0),
+            Inst::Import(ref name) => {
+                parse_prim(name)?;
+                self.prims.insert(value, (name.clone(), Vec::new()));
+                return Ok(());
+            },
+            Inst::ClosureIntro(function, ref env) => {
+                let closure = self.alloc(1 + env.len());
+                let func_ref = self.func_ref(self.function_ids[function.0]);
+                let code = self.builder.ins().func_addr(WORD, func_ref);
+                self.store(closure, 0, code);
+                for (index, &env_value) in env.iter().enumerate() {
+                    let env_value = self.value(env_value)?;
+                    self.store(closure, 1 + index, env_value);
+                }
+                closure
+            },
+            Inst::ClosureEnv(closure, index) => {
+                let closure = self.value(closure)?;
+                self.load(closure, 1 + index)
+            },
+            Inst::Call(function, arg) => {
+                let arg = self.value(arg)?;
+                match self.prims.get(&function).cloned() {
+                    Some((name, mut args)) => {
+                        args.push(arg);
+                        let (_, op) = parse_prim(&name)?;
+                        if args.len() < prim_arity(op) {
+                            self.prims.insert(value, (name, args));
+                            return Ok(());
+                        }
+                        self.translate_prim(&name, &args)?
+                    },
+                    None => {
+                        let closure = self.value(function)?;
+                        let code = self.load(closure, 0);
+                        let sig = self.closure_sig();
+                        let call = self.builder.ins().call_indirect(sig, code, &[closure, arg]);
+                        self.builder.inst_results(call)[0]
+                    },
+                }
+            },
+            Inst::RecordIntro(ref fields) => self.record(fields)?,
+            Inst::RecordProj(record, ref label) => {
+                let record = self.value(record)?;
+                let label = self.labels.intern(label);
+                let label = self.word(label);
+                self.call_runtime(self.runtime.record_proj, &[record, label])
+            },
+            Inst::RecordExtend(record, ref fields) => {
+                let record = self.value(record)?;
+                let extension = self.record(fields)?;
+                self.call_runtime(self.runtime.record_extend, &[record, extension])
+            },
+            Inst::VariantIntro(ref label, arg) => {
+                let label = self.labels.intern(label);
+                let label = self.word(label);
+                let arg = self.value(arg)?;
+                let variant = self.alloc(2);
+                self.store(variant, 0, label);
+                self.store(variant, 1, arg);
+                variant
+            },
+            Inst::ArrayIntro(ref elems) => {
+                let array = self.alloc(1 + elems.len());
+                let length = self.word(elems.len() as Word);
+                self.store(array, 0, length);
+                for (index, &elem) in elems.iter().enumerate() {
+                    let elem = self.value(elem)?;
+                    self.store(array, 1 + index, elem);
+                }
+                array
+            },
+            Inst::ArrayIndex(array, index) => {
+                let array = self.value(array)?;
+                self.load(array, 1 + index)
+            },
+            Inst::Eq(_, _) => {
+                return Err(JitError::new(
+                    "comparing against variables in patterns is not yet supported",
+                ));
+            },
+        };
+
+        self.values.insert(value, result);
+        Ok(())
+    }
+
+    fn translate_terminator(&mut self, terminator: &Terminator) -> Result<(), JitError> {
+        match *terminator {
+            Terminator::Return(value) => {
+                let value = self.value(value)?;
+                self.builder.ins().return_(&[value]);
+            },
+            Terminator::Jump(block, ref args) => {
+                let args = self.values(args)?;
+                self.builder.ins().jump(self.ebbs[block.0], &args);
+            },
+            Terminator::Switch(value, ref cases, default) => {
+                let value = self.value(value)?;
+                for &(ref literal, block) in cases {
+                    let matches = self.literal_eq(value, literal);
+                    self.builder.ins().brnz(matches, self.ebbs[block.0], &[]);
+                }
+                self.builder.ins().jump(self.ebbs[default.0], &[]);
+            },
+            Terminator::SwitchVariant(value, ref cases, default) => {
+                let variant = self.value(value)?;
+                let label = self.load(variant, 0);
+                let value = self.load(variant, 1);
+                for &(ref case_label, block) in cases {
+                    let case_label = self.labels.intern(case_label);
+                    let matches =
+                        self.builder
+                            .ins()
+                            .icmp_imm(IntCC::Equal, label, case_label as i64);
+                    self.builder
+                        .ins()
+                        .brnz(matches, self.ebbs[block.0], &[value]);
+                }
+                self.builder.ins().jump(self.ebbs[default.0], &[]);
+            },
+            Terminator::Unreachable => {
+                self.builder.ins().trap(TrapCode::UnreachableCodeReached);
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Translate a fully applied primitive
+    fn translate_prim(&mut self, name: &str, args: &[ir::Value]) -> Result<ir::Value, JitError> {
+        let (scalar, op) = parse_prim(name)?;
+        let unsupported = || JitError::new(format!("unsupported primitive `{}`", name));
+
+        match op {
+            "eq" | "ne" | "lt" | "le" | "gt" | "ge" => {
+                let matches = match scalar {
+                    Scalar::String => {
+                        let ordering =
+                            self.call_runtime(self.runtime.string_cmp, &[args[0], args[1]]);
+                        let cond = int_cc(op, true).ok_or_else(unsupported)?;
+                        self.builder.ins().icmp_imm(cond, ordering, 0)
+                    },
+                    Scalar::F32 | Scalar::F64 => {
+                        let lhs = self.word_to_float(scalar, args[0]);
+                        let rhs = self.word_to_float(scalar, args[1]);
+                        let cond = float_cc(op).ok_or_else(unsupported)?;
+                        self.builder.ins().fcmp(cond, lhs, rhs)
+                    },
+                    _ => {
+                        let cond = int_cc(op, is_signed(scalar)).ok_or_else(unsupported)?;
+                        self.builder.ins().icmp(cond, args[0], args[1])
+                    },
+                };
+
+                Ok(self.builder.ins().bint(WORD, matches))
+            },
+            "add" | "sub" | "mul" | "div" => match scalar {
+                Scalar::F32 | Scalar::F64 => {
+                    let lhs = self.word_to_float(scalar, args[0]);
+                    let rhs = self.word_to_float(scalar, args[1]);
+                    let result = match op {
+                        "add" => self.builder.ins().fadd(lhs, rhs),
+                        "sub" => self.builder.ins().fsub(lhs, rhs),
+                        "mul" => self.builder.ins().fmul(lhs, rhs),
+                        _ => self.builder.ins().fdiv(lhs, rhs),
+                    };
+
+                    Ok(self.float_to_word(scalar, result))
+                },
+                Scalar::Bool | Scalar::String | Scalar::Char => Err(unsupported()),
+                _ => {
+                    let (lhs, rhs) = (args[0], args[1]);
+                    let result = match op {
+                        "add" => self.builder.ins().iadd(lhs, rhs),
+                        "sub" => self.builder.ins().isub(lhs, rhs),
+                        "mul" => self.builder.ins().imul(lhs, rhs),
+                        _ if is_signed(scalar) => self.signed_div(lhs, rhs),
+                        _ => self.builder.ins().udiv(lhs, rhs),
+                    };
+
+                    Ok(self.wrap_int(scalar, result))
+                },
+            },
+            "append" if scalar == Scalar::String => {
+                Ok(self.call_runtime(self.runtime.string_append, &[args[0], args[1]]))
+            },
+            "to-string" => {
+                let scalar = self.word(scalar as Word);
+                Ok(self.call_runtime(self.runtime.to_string, &[args[0], scalar]))
+            },
+            _ => Err(unsupported()),
+        }
+    }
+
+    /// Compare a value with a literal
+    fn literal_eq(&mut self, value: ir::Value, literal: &Literal) -> ir::Value {
+        match *literal {
+            Literal::String(_) => {
+                let literal = self.word(runtime::encode_literal(literal));
+                let ordering = self.call_runtime(self.runtime.string_cmp, &[value, literal]);
+                self.builder.ins().icmp_imm(IntCC::Equal, ordering, 0)
+            },
+            Literal::F32(literal) => {
+                let value = self.word_to_float(Scalar::F32, value);
+                let literal = self.builder.ins().f32const(literal);
+                self.builder.ins().fcmp(FloatCC::Equal, value, literal)
+            },
+            Literal::F64(literal) => {
+                let value = self.word_to_float(Scalar::F64, value);
+                let literal = self.builder.ins().f64const(literal);
+                self.builder.ins().fcmp(FloatCC::Equal, value, literal)
+            },
+            _ => {
+                let literal = runtime::encode_literal(literal) as i64;
+                self.builder.ins().icmp_imm(IntCC::Equal, value, literal)
+            },
+        }
+    }
+
+    /// Divide two signed integers, wrapping around when the smallest integer is
+    /// divided by `-1` rather than trapping like `sdiv` does
+    ///
+    /// Division by zero still traps.
+    fn signed_div(&mut self, lhs: ir::Value, rhs: ir::Value) -> ir::Value {
+        let is_minus_one = self.builder.ins().icmp_imm(IntCC::Equal, rhs, -1);
+        let one = self.builder.ins().iconst(WORD, 1);
+        let divisor = self.builder.ins().select(is_minus_one, one, rhs);
+        let quotient = self.builder.ins().sdiv(lhs, divisor);
+        let negated = self.builder.ins().ineg(lhs);
+        self.builder.ins().select(is_minus_one, negated, quotient)
+    }
+
+    /// Wrap the result of an integer operation to the width of its type
+    fn wrap_int(&mut self, scalar: Scalar, value: ir::Value) -> ir::Value {
+        match scalar {
+            Scalar::U8 => self.builder.ins().band_imm(value, 0xff),
+            Scalar::U16 => self.builder.ins().band_imm(value, 0xffff),
+            Scalar::U32 => self.builder.ins().band_imm(value, 0xffff_ffff),
+            Scalar::S8 | Scalar::S16 | Scalar::S32 => {
+                let ty = match scalar {
+                    Scalar::S8 => types::I8,
+                    Scalar::S16 => types::I16,
+                    _ => types::I32,
+                };
+                let value = self.builder.ins().ireduce(ty, value);
+                self.builder.ins().sextend(WORD, value)
+            },
+            _ => value,
+        }
+    }
+
+    fn word_to_float(&mut self, scalar: Scalar, word: ir::Value) -> ir::Value {
+        if scalar == Scalar::F32 {
+            let bits = self.builder.ins().ireduce(types::I32, word);
+            self.builder.ins().bitcast(types::F32, bits)
+        } else {
+            self.builder.ins().bitcast(types::F64, word)
+        }
+    }
+
+    fn float_to_word(&mut self, scalar: Scalar, value: ir::Value) -> ir::Value {
+        if scalar == Scalar::F32 {
+            let bits = self.builder.ins().bitcast(types::I32, value);
+            self.builder.ins().uextend(WORD, bits)
+        } else {
+            self.builder.ins().bitcast(WORD, value)
+        }
+    }
+
+    fn record(&mut self, fields: &[(Label, ValueId)]) -> Result<ir::Value, JitError> {
+        let record = self.alloc(1 + fields.len() * 2);
+        let length = self.word(fields.len() as Word);
+        self.store(record, 0, length);
+        for (index, &(ref label, value)) in fields.iter().enumerate() {
+            let label = self.labels.intern(label);
+            let label = self.word(label);
+            let value = self.value(value)?;
+            self.store(record, 1 + index * 2, label);
+            self.store(record, 2 + index * 2, value);
+        }
+
+        Ok(record)
+    }
+
+    fn value(&self, value: ValueId) -> Result<ir::Value, JitError> {
+        if let Some(&(ref name, _)) = self.prims.get(&value) {
+            return Err(JitError::new(format!(
+                "using the primitive `{}` as a value is not yet supported",
+                name,
+            )));
+        }
+
+        match self.values.get(&value) {
+            Some(&value) => Ok(value),
+            None => Err(JitError::new(format!("undefined value `{}`", value))),
+        }
+    }
+
+    fn values(&self, values: &[ValueId]) -> Result<Vec<ir::Value>, JitError> {
+        values.iter().map(|&value| self.value(value)).collect()
+    }
+
+    fn word(&mut self, word: Word) -> ir::Value {
+        self.builder.ins().iconst(WORD, word as i64)
+    }
+
+    fn alloc(&mut self, length: usize) -> ir::Value {
+        let length = self.word(length as Word);
+        self.call_runtime(self.runtime.alloc, &[length])
+    }
+
+    fn load(&mut self, ptr: ir::Value, index: usize) -> ir::Value {
+        let offset = index as i32 * WORD_SIZE;
+        self.builder.ins().load(WORD, MemFlags::new(), ptr, offset)
+    }
+
+    fn store(&mut self, ptr: ir::Value, index: usize, value: ir::Value) {
+        let offset = index as i32 * WORD_SIZE;
+        self.builder
+            .ins()
+            .store(MemFlags::new(), value, ptr, offset);
+    }
+
+    fn func_ref(&mut self, id: FuncId) -> ir::FuncRef {
+        let (module, builder) = (&mut self.module, &mut self.builder);
+        *self
+            .func_refs
+            .entry(id)
+            .or_insert_with(|| module.declare_func_in_func(id, &mut builder.func))
+    }
+
+    fn closure_sig(&mut self) -> ir::SigRef {
+        match self.closure_sig {
+            Some(sig) => sig,
+            None => {
+                let signature = word_signature(self.module, 2);
+                let sig = self.builder.import_signature(signature);
+                self.closure_sig = Some(sig);
+                sig
+            },
+        }
+    }
+
+    fn call_runtime(&mut self, id: FuncId, args: &[ir::Value]) -> ir::Value {
+        let func_ref = self.func_ref(id);
+        let call = self.builder.ins().call(func_ref, args);
+        self.builder.inst_results(call)[0]
+    }
+}
+
+/// Split the name of a primitive import into the scalar and the operation
+/// that it is defined over
+fn parse_prim(name: &str) -> Result<(Scalar, &str), JitError> {
+    let mut parts = name.splitn(3, '/');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some("prim"), Some(scalar), Some(op)) => match Scalar::from_prim_name(scalar) {
+            Some(scalar) => Ok((scalar, op)),
+            None => Err(JitError::new(format!("unknown primitive `{}`", name))),
+        },
+        _ => Err(JitError::new(format!("unknown primitive `{}`", name))),
+    }
+}
+
+/// The number of arguments that a primitive operation takes
+fn prim_arity(op: &str) -> usize {
+    match op {
+        "to-string" => 1,
+        _ => 2,
+    }
+}
+
+fn is_signed(scalar: Scalar) -> bool {
+    match scalar {
+        Scalar::S8 | Scalar::S16 | Scalar::S32 | Scalar::S64 => true,
+        _ => false,
+    }
+}
+
+fn int_cc(op: &str, signed: bool) -> Option<IntCC> {
+    match (op, signed) {
+        ("eq", _) => Some(IntCC::Equal),
+        ("ne", _) => Some(IntCC::NotEqual),
+        ("lt", true) => Some(IntCC::SignedLessThan),
+        ("le", true) => Some(IntCC::SignedLessThanOrEqual),
+        ("gt", true) => Some(IntCC::SignedGreaterThan),
+        ("ge", true) => Some(IntCC::SignedGreaterThanOrEqual),
+        ("lt", false) => Some(IntCC::UnsignedLessThan),
+        ("le", false) => Some(IntCC::UnsignedLessThanOrEqual),
+        ("gt", false) => Some(IntCC::UnsignedGreaterThan),
+        ("ge", false) => Some(IntCC::UnsignedGreaterThanOrEqual),
+        _ => None,
+    }
+}
+
+fn float_cc(op: &str) -> Option<FloatCC> {
+    match op {
+        "eq" => Some(FloatCC::Equal),
+        "ne" => Some(FloatCC::NotEqual),
+        "lt" => Some(FloatCC::LessThan),
+        "le" => Some(FloatCC::LessThanOrEqual),
+        "gt" => Some(FloatCC::GreaterThan),
+        "ge" => Some(FloatCC::GreaterThanOrEqual),
+        _ => None,
+    }
+}
@@ -0,0 +1,9 @@
+//! A just-in-time compiler for Pikelet programs
+//!
+//! This allows Pikelet code to be embedded in Rust programs and run natively,
+//! without having to go through an interpreter. Programs are compiled from
+//! the static single assignment form, and the values that they produce can
+//! be read back by the host by describing how they are represented.
+
+pub mod compile;
+pub mod runtime;
@@ -0,0 +1,367 @@
+//! The representation of values in compiled code, and the functions that
+//! compiled code calls into for the operations that are not worth inlining
+//!
+//! Every value is represented by a single 64-bit word:
+//!
+//! - booleans are `0` or `1`
+//! - characters are their code points
+//! - integers are zero or sign extended to 64 bits, depending on their type
+//! - floating point numbers are stored as their bit patterns
+//! - strings are pointers to a Rust `String`
+//! - everything else is a pointer to a block of words on the heap
+//!
+//! The blocks on the heap are laid out as follows:
+//!
+//! | Value   | Layout                                          |
+//! |---------|-------------------------------------------------|
+//! | closure | `[code, env0, env1, ...]`                       |
+//! | record  | `[length, label0, value0, label1, value1, ...]` |
+//! | variant | `[label, value]`                                |
+//! | array   | `[length, elem0, elem1, ...]`                   |
+//!
+//! Labels are identified by numbers that are handed out by `Labels`. There is
+//! no garbage collector yet, so memory that is allocated by compiled code is
+//! never freed.
+
+use std::cmp::Ordering;
+use std::process;
+
+use pikelet_core::syntax::{Label, Literal};
+
+/// A value, as it is represented in compiled code
+pub type Word = u64;
+
+/// The types that primitives are defined over
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Scalar {
+    Bool,
+    String,
+    Char,
+    U8,
+    U16,
+    U32,
+    U64,
+    S8,
+    S16,
+    S32,
+    S64,
+    F32,
+    F64,
+}
+
+/// The scalars, in the order of their discriminants
+const SCALARS: [Scalar; 13] = [
+    Scalar::Bool,
+    Scalar::String,
+    Scalar::Char,
+    Scalar::U8,
+    Scalar::U16,
+    Scalar::U32,
+    Scalar::U64,
+    Scalar::S8,
+    Scalar::S16,
+    Scalar::S32,
+    Scalar::S64,
+    Scalar::F32,
+    Scalar::F64,
+];
+
+impl Scalar {
+    /// Look up a scalar by the name that is used for it in the names of
+    /// primitive imports, for example the `u8` in `prim/u8/add`
+    pub fn from_prim_name(name: &str) -> Option<Scalar> {
+        match name {
+            "bool" => Some(Scalar::Bool),
+            "string" => Some(Scalar::String),
+            "char" => Some(Scalar::Char),
+            "u8" => Some(Scalar::U8),
+            "u16" => Some(Scalar::U16),
+            "u32" => Some(Scalar::U32),
+            "u64" => Some(Scalar::U64),
+            "i8" => Some(Scalar::S8),
+            "i16" => Some(Scalar::S16),
+            "i32" => Some(Scalar::S32),
+            "i64" => Some(Scalar::S64),
+            "f32" => Some(Scalar::F32),
+            "f64" => Some(Scalar::F64),
+            _ => None,
+        }
+    }
+
+    fn from_word(word: Word) -> Scalar {
+        SCALARS[word as usize]
+    }
+}
+
+/// Describes how the values of a type are represented, so that they can be
+/// passed between compiled code and the host
+#[derive(Debug, Clone, PartialEq)]
+pub enum Repr {
+    Scalar(Scalar),
+    Record(Vec<(Label, Repr)>),
+    Variant(Vec<(Label, Repr)>),
+    Array(Box<Repr>),
+    Function,
+    Erased,
+}
+
+/// Values that have been passed out of compiled code
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Literal(Literal),
+    RecordIntro(Vec<(Label, Value)>),
+    VariantIntro(Label, Box<Value>),
+    ArrayIntro(Vec<Value>),
+    /// Functions, which can be applied with `Jit::apply`
+    Function(Word),
+    Erased,
+}
+
+/// Hands out the numbers that identify labels in compiled code
+#[derive(Debug, Clone, Default)]
+pub struct Labels {
+    labels: Vec<Label>,
+}
+
+impl Labels {
+    /// Returns the number for a label, handing out a new one if the label
+    /// has not been seen before
+    pub fn intern(&mut self, label: &Label) -> Word {
+        match self.get(label) {
+            Some(id) => id,
+            None => {
+                self.labels.push(label.clone());
+                (self.labels.len() - 1) as Word
+            },
+        }
+    }
+
+    pub fn get(&self, label: &Label) -> Option<Word> {
+        self.labels
+            .iter()
+            .position(|other| other == label)
+            .map(|id| id as Word)
+    }
+}
+
+/// Encode a literal as a word
+pub fn encode_literal(literal: &Literal) -> Word {
+    match *literal {
+        Literal::Bool(value) => value as Word,
+        Literal::String(ref value) => alloc_string(value.clone()),
+        Literal::Char(value) => value as Word,
+        Literal::U8(value) => Word::from(value),
+        Literal::U16(value) => Word::from(value),
+        Literal::U32(value) => Word::from(value),
+        Literal::U64(value) => value,
+        Literal::S8(value) => i64::from(value) as Word,
+        Literal::S16(value) => i64::from(value) as Word,
+        Literal::S32(value) => i64::from(value) as Word,
+        Literal::S64(value) => value as Word,
+        Literal::F32(value) => Word::from(value.to_bits()),
+        Literal::F64(value) => value.to_bits(),
+    }
+}
+
+/// Decode a word as a literal
+///
+/// # Safety
+///
+/// If the scalar is a string, the word must be a pointer to a string that
+/// was allocated by compiled code, or by `encode_literal`.
+pub unsafe fn decode_literal(word: Word, scalar: Scalar) -> Option<Literal> {
+    Some(match scalar {
+        Scalar::Bool => Literal::Bool(word != 0),
+        Scalar::String => Literal::String(string(word).clone()),
+        Scalar::Char => Literal::Char(std::char::from_u32(word as u32)?),
+        Scalar::U8 => Literal::U8(word as u8),
+        Scalar::U16 => Literal::U16(word as u16),
+        Scalar::U32 => Literal::U32(word as u32),
+        Scalar::U64 => Literal::U64(word),
+        Scalar::S8 => Literal::S8(word as i8),
+        Scalar::S16 => Literal::S16(word as i16),
+        Scalar::S32 => Literal::S32(word as i32),
+        Scalar::S64 => Literal::S64(word as i64),
+        Scalar::F32 => Literal::F32(f32::from_bits(word as u32)),
+        Scalar::F64 => Literal::F64(f64::from_bits(word)),
+    })
+}
+
+/// Encode a value as a word, allocating it on the heap if needed
+pub fn encode(labels: &mut Labels, value: &Value) -> Word {
+    match *value {
+        Value::Literal(ref literal) => encode_literal(literal),
+        Value::RecordIntro(ref fields) => {
+            let mut words = vec![fields.len() as Word];
+            for &(ref label, ref value) in fields {
+                words.push(labels.intern(label));
+                words.push(encode(labels, value));
+            }
+            alloc_words(words)
+        },
+        Value::VariantIntro(ref label, ref value) => {
+            let label = labels.intern(label);
+            alloc_words(vec![label, encode(labels, value)])
+        },
+        Value::ArrayIntro(ref elems) => {
+            let mut words = vec![elems.len() as Word];
+            words.extend(elems.iter().map(|elem| encode(labels, elem)));
+            alloc_words(words)
+        },
+        Value::Function(word) => word,
+        Value::Erased => 0,
+    }
+}
+
+/// Decode a word as a value, returning `None` if it does not fit the
+/// representation
+///
+/// # Safety
+///
+/// The word must have been produced by compiled code, or by `encode`, for
+/// a value of a type that has the given representation.
+pub unsafe fn decode(labels: &Labels, word: Word, repr: &Repr) -> Option<Value> {
+    match *repr {
+        Repr::Scalar(scalar) => decode_literal(word, scalar).map(Value::Literal),
+        Repr::Record(ref field_reprs) => {
+            let fields = field_reprs
+                .iter()
+                .map(|&(ref label, ref repr)| {
+                    let value = record_lookup(word, labels.get(label)?)?;
+                    Some((label.clone(), decode(labels, value, repr)?))
+                })
+                .collect::<Option<_>>()?;
+
+            Some(Value::RecordIntro(fields))
+        },
+        Repr::Variant(ref variant_reprs) => {
+            let id = load(word, 0);
+            let &(ref label, ref repr) = variant_reprs
+                .iter()
+                .find(|&&(ref label, _)| labels.get(label) == Some(id))?;
+            let value = decode(labels, load(word, 1), repr)?;
+
+            Some(Value::VariantIntro(label.clone(), Box::new(value)))
+        },
+        Repr::Array(ref elem_repr) => {
+            let elems = (0..load(word, 0) as usize)
+                .map(|index| decode(labels, load(word, index + 1), elem_repr))
+                .collect::<Option<_>>()?;
+
+            Some(Value::ArrayIntro(elems))
+        },
+        Repr::Function => Some(Value::Function(word)),
+        Repr::Erased => Some(Value::Erased),
+    }
+}
+
+unsafe fn load(ptr: Word, index: usize) -> Word {
+    *(ptr as *const Word).add(index)
+}
+
+unsafe fn string<'a>(word: Word) -> &'a String {
+    &*(word as *const String)
+}
+
+fn alloc_string(value: String) -> Word {
+    Box::into_raw(Box::new(value)) as Word
+}
+
+fn alloc_words(words: Vec<Word>) -> Word {
+    Box::into_raw(words.into_boxed_slice()) as *mut Word as Word
+}
+
+/// Look up the value of a field in a record
+unsafe fn record_lookup(record: Word, label: Word) -> Option<Word> {
+    (0..load(record, 0) as usize)
+        .find(|index| load(record, 1 + index * 2) == label)
+        .map(|index| load(record, 2 + index * 2))
+}
+
+/// Print a message and abort, for situations that can't happen in well-typed
+/// programs
+///
+/// Unwinding out of the functions that are called by compiled code is not
+/// allowed, so we can't panic here.
+fn abort(message: &str) -> ! {
+    eprintln!("pikelet runtime error: {}", message);
+    process::abort()
+}
+
+/// The names and addresses of the functions that compiled code can call
+pub(crate) fn symbols() -> Vec<(&'static str, *const u8)> {
+    vec![
+        ("pikelet_alloc", pikelet_alloc as *const u8),
+        ("pikelet_record_proj", pikelet_record_proj as *const u8),
+        ("pikelet_record_extend", pikelet_record_extend as *const u8),
+        ("pikelet_string_cmp", pikelet_string_cmp as *const u8),
+        ("pikelet_string_append", pikelet_string_append as *const u8),
+        ("pikelet_to_string", pikelet_to_string as *const u8),
+    ]
+}
+
+/// Allocate a block of words on the heap
+extern "C" fn pikelet_alloc(length: Word) -> Word {
+    alloc_words(vec![0; length as usize])
+}
+
+/// Project a field out of a record
+extern "C" fn pikelet_record_proj(record: Word, label: Word) -> Word {
+    match unsafe { record_lookup(record, label) } {
+        Some(value) => value,
+        None => abort("projected on non existent field"),
+    }
+}
+
+/// Allocate a copy of a record with the fields of another record added to
+/// the end of it
+extern "C" fn pikelet_record_extend(record: Word, extension: Word) -> Word {
+    unsafe {
+        let record_length = load(record, 0) as usize;
+        let extension_length = load(extension, 0) as usize;
+
+        let mut words = vec![(record_length + extension_length) as Word];
+        words.extend((0..record_length * 2).map(|index| load(record, index + 1)));
+        words.extend((0..extension_length * 2).map(|index| load(extension, index + 1)));
+
+        alloc_words(words)
+    }
+}
+
+/// Compare two strings, returning `-1`, `0`, or `1`
+extern "C" fn pikelet_string_cmp(lhs: Word, rhs: Word) -> Word {
+    let ordering = unsafe { string(lhs).cmp(string(rhs)) };
+    match ordering {
+        Ordering::Less => -1i64 as Word,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    }
+}
+
+extern "C" fn pikelet_string_append(lhs: Word, rhs: Word) -> Word {
+    let (lhs, rhs) = unsafe { (string(lhs), string(rhs)) };
+    alloc_string(lhs.clone() + rhs)
+}
+
+/// Convert a scalar to a string, where `scalar` is the discriminant of its
+/// `Scalar`
+extern "C" fn pikelet_to_string(word: Word, scalar: Word) -> Word {
+    let string = match unsafe { decode_literal(word, Scalar::from_word(scalar)) } {
+        Some(Literal::Bool(value)) => value.to_string(),
+        Some(Literal::String(value)) => value,
+        Some(Literal::Char(value)) => value.to_string(),
+        Some(Literal::U8(value)) => value.to_string(),
+        Some(Literal::U16(value)) => value.to_string(),
+        Some(Literal::U32(value)) => value.to_string(),
+        Some(Literal::U64(value)) => value.to_string(),
+        Some(Literal::S8(value)) => value.to_string(),
+        Some(Literal::S16(value)) => value.to_string(),
+        Some(Literal::S32(value)) => value.to_string(),
+        Some(Literal::S64(value)) => value.to_string(),
+        Some(Literal::F32(value)) => value.to_string(),
+        Some(Literal::F64(value)) => value.to_string(),
+        None => abort("converted an invalid scalar to a string"),
+    };
+
+    alloc_string(string)
+}
@@ -0,0 +1,294 @@
+use pretty_assertions::assert_eq;
+
+use pikelet_concrete::elaborate::Context;
+use pikelet_core::syntax::{Label, Literal};
+use pikelet_jit::compile::{Jit, JitError};
+use pikelet_jit::runtime::{Repr, Scalar, Value};
+use pikelet_test_support::{parse_infer_term, Observation};
+
+/// Check that running the compiled term gives the same result as the
+/// evaluator, reading the result back with the given representation
+fn assert_agrees(repr: Repr, src: &str) {
+    pikelet_test_support::assert_agrees(src, |_, term| {
+        let mut jit = Jit::new();
+        let compiled = match jit.compile_term(term) {
+            Ok(compiled) => compiled,
+            Err(error) => panic!("compile error: {}", error),
+        };
+        match unsafe { jit.decode(compiled.run(), &repr) } {
+            Ok(value) => observe(&value),
+            Err(error) => panic!("decode error: {}", error),
+        }
+    });
+}
+
+fn observe(value: &Value) -> Observation {
+    match *value {
+        Value::Literal(ref literal) => Observation::Literal(literal.clone()),
+        Value::RecordIntro(ref fields) => Observation::record(
+            fields
+                .iter()
+                .map(|&(ref label, ref value)| (label.clone(), observe(value))),
+        ),
+        Value::VariantIntro(ref label, ref value) => {
+            Observation::Variant(label.clone(), Box::new(observe(value)))
+        },
+        Value::ArrayIntro(ref elems) => Observation::Array(elems.iter().map(observe).collect()),
+        Value::Function(_) => Observation::Function,
+        Value::Erased => Observation::Erased,
+    }
+}
+
+fn u64_value(value: u64) -> Value {
+    Value::Literal(Literal::U64(value))
+}
+
+#[test]
+fn apply_from_host() {
+    let context = Context::default();
+    let term = parse_infer_term(
+        &context,
+        r#"
+            \(x : U64) (y : U64) =>
+                (import "prim/u64/add") x ((import "prim/u64/mul") y 2)
+        "#,
+    );
+
+    let mut jit = Jit::new();
+    let compiled = jit.compile_term(&term).unwrap();
+    let function = compiled.run();
+
+    let x = jit.encode(&u64_value(3));
+    let y = jit.encode(&u64_value(4));
+    let result = unsafe {
+        let function = jit.apply(function, x);
+        jit.apply(function, y)
+    };
+
+    assert_eq!(
+        unsafe { jit.decode(result, &Repr::Scalar(Scalar::U64)) },
+        Ok(u64_value(11)),
+    );
+}
+
+#[test]
+fn apply_from_host_record() {
+    let context = Context::default();
+    let term = parse_infer_term(
+        &context,
+        r#"
+            \(p : Record { x : U64; y : U64 }) =>
+                record { sum = (import "prim/u64/add") p.x p.y; x = p.x }
+        "#,
+    );
+
+    let mut jit = Jit::new();
+    let compiled = jit.compile_term(&term).unwrap();
+
+    let arg = jit.encode(&Value::RecordIntro(vec![
+        (Label("x".to_owned()), u64_value(1)),
+        (Label("y".to_owned()), u64_value(2)),
+    ]));
+    let result = unsafe { jit.apply(compiled.run(), arg) };
+    let repr = Repr::Record(vec![
+        (Label("sum".to_owned()), Repr::Scalar(Scalar::U64)),
+        (Label("x".to_owned()), Repr::Scalar(Scalar::U64)),
+    ]);
+
+    assert_eq!(
+        unsafe { jit.decode(result, &repr) },
+        Ok(Value::RecordIntro(vec![
+            (Label("sum".to_owned()), u64_value(3)),
+            (Label("x".to_owned()), u64_value(1)),
+        ])),
+    );
+}
+
+#[test]
+fn prim_as_value() {
+    let context = Context::default();
+    let term = parse_infer_term(&context, r#"import "prim/u64/add""#);
+
+    assert_eq!(
+        Jit::new().compile_term(&term).map(|_| ()),
+        Err(JitError::new(
+            "using the primitive `prim/u64/add` as a value is not yet supported"
+        )),
+    );
+}
+
+#[test]
+fn agrees_u64_arithmetic() {
+    assert_agrees(
+        Repr::Scalar(Scalar::U64),
+        r#"(import "prim/u64/add") 1 ((import "prim/u64/mul") 2 3)"#,
+    );
+}
+
+#[test]
+fn agrees_s32_arithmetic() {
+    assert_agrees(
+        Repr::Scalar(Scalar::S32),
+        r#"(import "prim/i32/div") ((import "prim/i32/sub") 1 7) 2"#,
+    );
+}
+
+#[test]
+fn agrees_u8_arithmetic() {
+    assert_agrees(
+        Repr::Scalar(Scalar::U8),
+        r#"(import "prim/u8/sub") ((import "prim/u8/add") 200 50) 10"#,
+    );
+}
+
+#[test]
+fn agrees_u8_overflow() {
+    assert_agrees(
+        Repr::Scalar(Scalar::U8),
+        r#"(import "prim/u8/add") 200 100"#,
+    );
+}
+
+#[test]
+fn agrees_s64_division_overflow() {
+    assert_agrees(
+        Repr::Scalar(Scalar::S64),
+        r#"
+            (import "prim/i64/div")
+                ((import "prim/i64/sub") ((import "prim/i64/sub") 0 9223372036854775807) 1)
+                ((import "prim/i64/sub") 0 1)
+        "#,
+    );
+}
+
+#[test]
+fn agrees_f64_arithmetic() {
+    assert_agrees(
+        Repr::Scalar(Scalar::F64),
+        r#"(import "prim/f64/mul") 1.5 ((import "prim/f64/sub") 4.0 1.5)"#,
+    );
+}
+
+#[test]
+fn agrees_f32_comparison() {
+    assert_agrees(
+        Repr::Scalar(Scalar::Bool),
+        r#"(import "prim/f32/lt") ((import "prim/f32/div") 1.0 3.0) 0.5"#,
+    );
+}
+
+#[test]
+fn agrees_if() {
+    assert_agrees(
+        Repr::Scalar(Scalar::String),
+        r#"if (import "prim/u64/gt") 1 2 then "yes" else "no""#,
+    );
+}
+
+#[test]
+fn agrees_strings() {
+    assert_agrees(
+        Repr::Scalar(Scalar::String),
+        r#"
+            (import "prim/string/append")
+                "answer: "
+                ((import "prim/u8/to-string") 42)
+        "#,
+    );
+}
+
+#[test]
+fn agrees_case_string() {
+    assert_agrees(
+        Repr::Scalar(Scalar::String),
+        r#"
+            case "b" {
+                "a" => "first";
+                "b" => "second";
+                _ => "other";
+            }
+        "#,
+    );
+}
+
+#[test]
+fn agrees_record() {
+    assert_agrees(
+        Repr::Record(vec![
+            (Label("x".to_owned()), Repr::Scalar(Scalar::String)),
+            (Label("y".to_owned()), Repr::Scalar(Scalar::U64)),
+        ]),
+        r#"
+            let
+                swap = \(r : Record { x : U64; y : String }) =>
+                    record { x = r.y; y = r.x };
+            in
+                swap (record { x = 1; y = "hello" })
+        "#,
+    );
+}
+
+#[test]
+fn agrees_case_variant() {
+    assert_agrees(
+        Repr::Scalar(Scalar::String),
+        r#"
+            case (variant some "hello" : Variant { some : String; none : Record {} }) {
+                variant some x => x;
+                variant none _ => "nothing";
+            }
+        "#,
+    );
+}
+
+#[test]
+fn agrees_recursive() {
+    assert_agrees(
+        Repr::Scalar(Scalar::U64),
+        r#"
+            let
+                partial fact : U64 -> U64;
+                fact n =
+                    if (import "prim/u64/eq") n 0 then 1 else
+                        (import "prim/u64/mul") n (fact ((import "prim/u64/sub") n 1));
+            in
+                fact 5
+        "#,
+    );
+}
+
+#[test]
+fn agrees_mutually_recursive() {
+    assert_agrees(
+        Repr::Scalar(Scalar::Bool),
+        r#"
+            let
+                partial is-even : U64 -> Bool;
+                partial is-odd : U64 -> Bool;
+
+                is-even n =
+                    if (import "prim/u64/eq") n 0 then true else is-odd ((import "prim/u64/sub") n 1);
+                is-odd n =
+                    if (import "prim/u64/eq") n 0 then false else is-even ((import "prim/u64/sub") n 1);
+            in
+                is-even 10
+        "#,
+    );
+}
+
+#[test]
+fn agrees_closures_in_array() {
+    assert_agrees(
+        Repr::Array(Box::new(Repr::Scalar(Scalar::U64))),
+        r#"
+            let
+                add = import "prim/u64/add";
+                adder = \(x : U64) (y : U64) => add x y;
+                fs = [adder 1; adder 2] : Array 2 (U64 -> U64);
+            in
+                case fs {
+                    [f; g] => [f 10; g 10] : Array 2 U64;
+                }
+        "#,
+    );
+}
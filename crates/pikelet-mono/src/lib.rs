@@ -0,0 +1,11 @@
+//! A first-order intermediate representation, where every value has a known
+//! representation
+//!
+//! This is lowered from the typed core syntax, rather than from the
+//! intermediate representations that the just-in-time compiler is built on,
+//! because picking machine types for values requires their types. It is
+//! shared by the backends that need to know these ahead of time, like the
+//! WebAssembly and C backends.
+
+pub mod lower;
+pub mod syntax;
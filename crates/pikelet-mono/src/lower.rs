@@ -0,0 +1,628 @@
+//! Lowering of elaborated files to first-order programs
+//!
+//! Files are expected to be made up of a series of let bindings that end in
+//! a record. Each of the top-level definitions is lowered to a function,
+//! taking the parameters of the lambdas that it starts with, and the fields
+//! of the record are exported from the program by name. Erased parameters and
+//! parameters that take types are skipped, along with record fields that have
+//! a multiplicity of zero, and definitions that are not functions are lowered
+//! to functions that take no parameters.
+//!
+//! Picking representations for the parameters of functions requires their
+//! types, which are removed by `pikelet_core::erase`, so unlike the other
+//! intermediate representations this is lowered from the typed core syntax.
+//! The parts of the term that would be erased, like annotations and
+//! parameters with a multiplicity of zero, are skipped along the way.
+//!
+//! As the representations of values are picked based on their types, only
+//! first-order, monomorphic code is supported for now. Functions can only be
+//! defined at the top level, or locally if they don't capture any local
+//! variables, and must always be applied to all of their arguments. Case
+//! expressions can only match on literals.
+
+use pikelet_core::nbe::Env;
+use pikelet_core::syntax::core::{Pattern, RcPattern, RcTerm, Term};
+use pikelet_core::syntax::{Multiplicity, VarIndex};
+
+use crate::syntax::{Expr, Function, FunctionId, LocalId, PrimOp, Program, Repr};
+
+/// An error produced while lowering a file
+#[derive(Debug, Clone, PartialEq, failure::Fail)]
+#[fail(display = "{}", message)]
+pub struct LowerError {
+    pub message: String,
+}
+
+impl LowerError {
+    pub fn new(message: impl Into<String>) -> LowerError {
+        LowerError {
+            message: message.into(),
+        }
+    }
+}
+
+/// Lower an elaborated file to a first-order program
+pub fn lower_module(env: &dyn Env, term: &RcTerm) -> Result<Program, LowerError> {
+    let mut lower = Lower {
+        env,
+        functions: Vec::new(),
+    };
+    let mut bindings = Vec::new();
+    let mut exports = Vec::new();
+    let mut term = term;
+
+    loop {
+        match *term.inner {
+            Term::Ann(ref expr, _) => term = expr,
+            Term::Let(ref definitions, ref body) => {
+                for &(_, ref definition) in definitions {
+                    let binding = lower.lower_definition(&bindings, definition);
+                    bindings.push(binding);
+                }
+                term = body;
+            },
+            _ => break,
+        }
+    }
+
+    match *term.inner {
+        Term::RecordIntro(ref fields) => {
+            for &(ref label, multiplicity, ref field) in fields {
+                // Fields with a multiplicity of zero are erased
+                if multiplicity == Multiplicity::Zero {
+                    continue;
+                }
+
+                let binding = match *field.strip_ann().inner {
+                    Term::Var(index, _) => lookup(&bindings, index)?.clone(),
+                    _ => lower.lower_definition(&bindings, field),
+                };
+
+                match binding {
+                    Binding::Function(id, _, _) => exports.push((label.clone(), id)),
+                    Binding::Unsupported(error) => {
+                        return Err(LowerError::new(format!(
+                            "could not export `{}`: {}",
+                            label, error,
+                        )));
+                    },
+                    // Types and erased fields have nothing to export
+                    _ => {},
+                }
+            }
+        },
+        _ => return Err(LowerError::new("expected the file to end in a record")),
+    }
+
+    let functions = lower
+        .functions
+        .into_iter()
+        .map(|function| function.expect("function was never lowered"))
+        .collect();
+
+    Ok(Program { functions, exports })
+}
+
+/// The things that variables can refer to during lowering
+#[derive(Debug, Clone)]
+enum Binding {
+    /// A local variable of the function that is being lowered
+    Local(LocalId, Repr),
+    /// A local variable of an enclosing function, which would need to be
+    /// captured
+    Captured,
+    /// A function, along with the representations of its parameters (where
+    /// erased parameters are `None`) and its result
+    Function(FunctionId, Vec<Option<Repr>>, Repr),
+    /// A type that can be used in annotations
+    Type(Repr),
+    /// Something that is not needed at runtime, like an erased parameter
+    Erased,
+    /// A definition that could not be lowered. This is only reported if the
+    /// definition is used, so that files can contain definitions that are
+    /// only used at compile time.
+    Unsupported(LowerError),
+}
+
+/// The local variables of the function that is currently being lowered
+#[derive(Default)]
+struct Locals {
+    reprs: Vec<Repr>,
+}
+
+impl Locals {
+    fn fresh(&mut self, repr: Repr) -> LocalId {
+        self.reprs.push(repr);
+        LocalId(self.reprs.len() - 1)
+    }
+}
+
+struct Lower<'env> {
+    env: &'env dyn Env,
+    /// The functions in the program. These are `None` while they are being
+    /// lowered.
+    functions: Vec<Option<Function>>,
+}
+
+impl<'env> Lower<'env> {
+    /// Lower a top-level or local definition, returning what it should be
+    /// bound as
+    fn lower_definition(&mut self, bindings: &[Binding], term: &RcTerm) -> Binding {
+        let function_count = self.functions.len();
+
+        match self.try_lower_definition(bindings, term) {
+            Ok(binding) => binding,
+            Err(error) => {
+                // Remove any functions that were added along the way
+                self.functions.truncate(function_count);
+                Binding::Unsupported(error)
+            },
+        }
+    }
+
+    fn try_lower_definition(
+        &mut self,
+        bindings: &[Binding],
+        term: &RcTerm,
+    ) -> Result<Binding, LowerError> {
+        if is_type(term) {
+            return resolve_type(self.env, bindings, term).map(Binding::Type);
+        }
+
+        let term = term.strip_ann();
+        match *term.inner {
+            Term::Fix(_, _, ref ann, ref body) => {
+                let (params, result) = signature(self.env, bindings, ann)?;
+                let id = self.reserve_function();
+                let mut bindings = bindings.to_vec();
+                bindings.push(Binding::Function(id, params, result));
+                self.lower_function(id, &bindings, body)
+            },
+            _ => {
+                let id = self.reserve_function();
+                self.lower_function(id, bindings, term)
+            },
+        }
+    }
+
+    fn reserve_function(&mut self) -> FunctionId {
+        self.functions.push(None);
+        FunctionId(self.functions.len() - 1)
+    }
+
+    /// Lower a function, taking the parameters of the lambdas that the term
+    /// starts with
+    fn lower_function(
+        &mut self,
+        id: FunctionId,
+        bindings: &[Binding],
+        term: &RcTerm,
+    ) -> Result<Binding, LowerError> {
+        let mut bindings = bindings
+            .iter()
+            .map(|binding| match *binding {
+                Binding::Local(..) => Binding::Captured,
+                ref binding => binding.clone(),
+            })
+            .collect::<Vec<_>>();
+        let mut locals = Locals::default();
+        let mut params = Vec::new();
+        let mut term = term;
+
+        loop {
+            match *term.inner {
+                Term::Ann(ref expr, _) => term = expr,
+                Term::FunIntro(_, multiplicity, _, ref ann, ref body) => {
+                    if multiplicity == Multiplicity::Zero || is_universe(ann) {
+                        params.push(None);
+                        bindings.push(Binding::Erased);
+                    } else {
+                        let repr = resolve_type(self.env, &bindings, ann)?;
+                        let local = locals.fresh(repr.clone());
+                        params.push(Some(repr.clone()));
+                        bindings.push(Binding::Local(local, repr));
+                    }
+                    term = body;
+                },
+                _ => break,
+            }
+        }
+
+        let param_count = locals.reprs.len();
+        let body = self.lower_expr(&mut locals, &mut bindings, term)?;
+        let result = body.repr();
+
+        self.functions[id.0] = Some(Function {
+            param_count,
+            locals: locals.reprs,
+            result: result.clone(),
+            body,
+        });
+
+        Ok(Binding::Function(id, params, result))
+    }
+
+    fn lower_expr(
+        &mut self,
+        locals: &mut Locals,
+        bindings: &mut Vec<Binding>,
+        term: &RcTerm,
+    ) -> Result<Expr, LowerError> {
+        match *term.inner {
+            Term::Ann(ref expr, _) => self.lower_expr(locals, bindings, expr),
+            Term::Literal(ref literal) => match Repr::from_literal(literal) {
+                Some(_) => Ok(Expr::Literal(literal.clone())),
+                None => Err(LowerError::new("strings are not yet supported")),
+            },
+            Term::Var(index, _) => match *lookup(bindings, index)? {
+                Binding::Local(local, ref repr) => Ok(Expr::Local(local, repr.clone())),
+                Binding::Function(id, ref params, ref result) => {
+                    if params.iter().any(Option::is_some) {
+                        return Err(LowerError::new(
+                            "using functions as values is not yet supported",
+                        ));
+                    }
+                    Ok(Expr::Call(id, Vec::new(), result.clone()))
+                },
+                ref binding => Err(unavailable(binding)),
+            },
+            Term::Global(ref free_var, _) => match self.env.get_definition(free_var) {
+                Some(definition) => self.lower_expr(locals, &mut Vec::new(), definition),
+                None => Err(LowerError::new(format!(
+                    "unsupported global `{}`",
+                    free_var
+                ))),
+            },
+            Term::Meta(meta) => match self.env.get_meta_solution(meta) {
+                Some(solution) => self.lower_expr(locals, bindings, &solution),
+                None => Err(LowerError::new(format!("unsolved metavariable `{}`", meta))),
+            },
+            Term::Import(ref name) => Err(LowerError::new(format!(
+                "using the primitive `{}` as a value is not yet supported",
+                name,
+            ))),
+            Term::FunIntro(..) | Term::Fix(..) => Err(LowerError::new(
+                "using functions as values is not yet supported",
+            )),
+            Term::FunApp(..) => self.lower_app(locals, bindings, term),
+            Term::RecordIntro(ref fields) => Ok(Expr::RecordIntro(
+                fields
+                    .iter()
+                    .filter(|&&(_, multiplicity, _)| multiplicity != Multiplicity::Zero)
+                    .map(|&(ref label, _, ref field)| {
+                        Ok((label.clone(), self.lower_expr(locals, bindings, field)?))
+                    })
+                    .collect::<Result<_, LowerError>>()?,
+            )),
+            Term::RecordProj(ref expr, ref label, _, _) => {
+                let expr = self.lower_expr(locals, bindings, expr)?;
+                let fields = match expr.repr() {
+                    Repr::Record(fields) => fields,
+                    repr => {
+                        return Err(LowerError::new(format!(
+                            "projected on a non-record: {}",
+                            repr,
+                        )));
+                    },
+                };
+                match fields.into_iter().find(|&(ref other, _)| other == label) {
+                    Some((_, repr)) => Ok(Expr::RecordProj(Box::new(expr), label.clone(), repr)),
+                    None => Err(LowerError::new(format!("no field named `{}`", label))),
+                }
+            },
+            Term::Let(ref definitions, ref body) => {
+                let binding_count = bindings.len();
+                let mut assignments = Vec::new();
+
+                for &(_, ref definition) in definitions {
+                    let binding = if is_type(definition) {
+                        resolve_type(self.env, bindings, definition)
+                            .map(Binding::Type)
+                            .unwrap_or_else(Binding::Unsupported)
+                    } else {
+                        match *definition.strip_ann().inner {
+                            Term::FunIntro(..) | Term::Fix(..) => {
+                                self.lower_definition(bindings, definition)
+                            },
+                            _ => {
+                                let expr = self.lower_expr(locals, bindings, definition)?;
+                                let repr = expr.repr();
+                                let local = locals.fresh(repr.clone());
+                                assignments.push((local, expr));
+                                Binding::Local(local, repr)
+                            },
+                        }
+                    };
+                    bindings.push(binding);
+                }
+
+                let body = self.lower_expr(locals, bindings, body);
+                bindings.truncate(binding_count);
+
+                Ok(assignments
+                    .into_iter()
+                    .rev()
+                    .fold(body?, |body, (local, expr)| {
+                        Expr::Let(local, Box::new(expr), Box::new(body))
+                    }))
+            },
+            Term::Case(ref head, ref clauses) => self.lower_case(locals, bindings, head, clauses),
+            _ => Err(LowerError::new(format!("unsupported term: {}", term))),
+        }
+    }
+
+    /// Lower a function application, which must be saturated
+    fn lower_app(
+        &mut self,
+        locals: &mut Locals,
+        bindings: &mut Vec<Binding>,
+        term: &RcTerm,
+    ) -> Result<Expr, LowerError> {
+        let mut head = term;
+        let mut args = Vec::new();
+
+        loop {
+            match *head.inner {
+                Term::Ann(ref expr, _) => head = expr,
+                Term::FunApp(ref fun, multiplicity, ref arg) => {
+                    args.push((multiplicity, arg));
+                    head = fun;
+                },
+                _ => break,
+            }
+        }
+        args.reverse();
+
+        let binding = match *head.inner {
+            Term::Import(ref name) => {
+                let runtime_args = args
+                    .into_iter()
+                    .filter(|&(multiplicity, _)| multiplicity != Multiplicity::Zero)
+                    .map(|(_, arg)| arg)
+                    .collect::<Vec<_>>();
+                return self.lower_prim(locals, bindings, name, &runtime_args);
+            },
+            Term::Var(index, _) => lookup(bindings, index)?.clone(),
+            Term::FunIntro(..) | Term::Fix(..) => self.lower_definition(bindings, head),
+            _ => return Err(LowerError::new(format!("unsupported function: {}", head))),
+        };
+
+        let (id, params, result) = match binding {
+            Binding::Function(id, params, result) => (id, params, result),
+            binding => return Err(unavailable(&binding)),
+        };
+
+        if params.len() != args.len() {
+            return Err(LowerError::new(format!(
+                "expected a function to be applied to {} arguments, but found {}",
+                params.len(),
+                args.len(),
+            )));
+        }
+
+        let mut arg_exprs = Vec::with_capacity(args.len());
+        for (param, (_, arg)) in params.iter().zip(args) {
+            if param.is_some() {
+                arg_exprs.push(self.lower_expr(locals, bindings, arg)?);
+            }
+        }
+
+        Ok(Expr::Call(id, arg_exprs, result))
+    }
+
+    /// Lower an application of a primitive import
+    fn lower_prim(
+        &mut self,
+        locals: &mut Locals,
+        bindings: &mut Vec<Binding>,
+        name: &str,
+        args: &[&RcTerm],
+    ) -> Result<Expr, LowerError> {
+        let unsupported = || LowerError::new(format!("unsupported primitive `{}`", name));
+
+        let mut parts = name.split('/');
+        let (repr, op) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some("prim"), Some(ty), Some(op), None) => (
+                Repr::from_prim_name(ty).ok_or_else(unsupported)?,
+                PrimOp::from_name(op).ok_or_else(unsupported)?,
+            ),
+            (_, _, _, _) => return Err(unsupported()),
+        };
+
+        // Arithmetic is only defined on numbers
+        match repr {
+            Repr::Bool | Repr::Char if !op.is_comparison() => return Err(unsupported()),
+            _ => {},
+        }
+
+        if args.len() != 2 {
+            return Err(LowerError::new(format!(
+                "expected the primitive `{}` to be applied to 2 arguments, but found {}",
+                name,
+                args.len(),
+            )));
+        }
+
+        let args = args
+            .iter()
+            .map(|arg| self.lower_expr(locals, bindings, arg))
+            .collect::<Result<_, LowerError>>()?;
+
+        Ok(Expr::Prim(op, repr, args))
+    }
+
+    /// Lower a case expression on literals, assigning the head to a local
+    /// variable that the clauses are tested against
+    fn lower_case(
+        &mut self,
+        locals: &mut Locals,
+        bindings: &mut Vec<Binding>,
+        head: &RcTerm,
+        clauses: &[(RcPattern, RcTerm)],
+    ) -> Result<Expr, LowerError> {
+        let head = self.lower_expr(locals, bindings, head)?;
+        let head_repr = head.repr();
+        let head_local = locals.fresh(head_repr.clone());
+
+        let mut tests = Vec::with_capacity(clauses.len());
+        let mut default = None;
+
+        for &(ref pattern, ref body) in clauses {
+            let mut pattern = pattern;
+            while let Pattern::Ann(ref inner, _) = *pattern.inner {
+                pattern = inner;
+            }
+
+            let literal = match *pattern.inner {
+                Pattern::Literal(ref literal) => literal,
+                Pattern::Global(ref free_var, _) => self
+                    .env
+                    .get_literal(free_var)
+                    .ok_or_else(|| LowerError::new("only literal patterns are supported"))?,
+                Pattern::Binder(_) => {
+                    bindings.push(Binding::Local(head_local, head_repr.clone()));
+                    let body = self.lower_expr(locals, bindings, body);
+                    bindings.pop();
+
+                    // Clauses after a binder are never reached
+                    default = Some(Box::new(body?));
+                    break;
+                },
+                _ => return Err(LowerError::new("only literal patterns are supported")),
+            };
+
+            tests.push((literal.clone(), self.lower_expr(locals, bindings, body)?));
+        }
+
+        let repr = match (tests.first(), &default) {
+            (Some(&(_, ref body)), _) => body.repr(),
+            (None, &Some(ref body)) => body.repr(),
+            (None, &None) => {
+                return Err(LowerError::new("empty case expressions are not supported"));
+            },
+        };
+
+        Ok(Expr::Let(
+            head_local,
+            Box::new(head),
+            Box::new(Expr::Case(head_local, tests, default, repr)),
+        ))
+    }
+}
+
+/// Pick the representation of the values of a type
+fn resolve_type(env: &dyn Env, bindings: &[Binding], term: &RcTerm) -> Result<Repr, LowerError> {
+    match *term.inner {
+        Term::Ann(ref expr, _) => resolve_type(env, bindings, expr),
+        Term::Var(index, _) => match *lookup(bindings, index)? {
+            Binding::Type(ref repr) => Ok(repr.clone()),
+            ref binding => Err(unavailable(binding)),
+        },
+        Term::Global(ref free_var, _) => {
+            let name = free_var.pretty_name.as_ref().map(String::as_str);
+            match name.and_then(Repr::from_type_name) {
+                Some(repr) => Ok(repr),
+                None => match env.get_definition(free_var) {
+                    Some(definition) => resolve_type(env, &[], definition),
+                    None => Err(LowerError::new(format!("unsupported type `{}`", free_var))),
+                },
+            }
+        },
+        Term::Meta(meta) => match env.get_meta_solution(meta) {
+            Some(solution) => resolve_type(env, bindings, &solution),
+            None => Err(LowerError::new(format!("unsolved metavariable `{}`", meta))),
+        },
+        Term::RecordType(ref fields) => {
+            let mut bindings = bindings.to_vec();
+            let mut reprs = Vec::with_capacity(fields.len());
+
+            for &(ref label, _, multiplicity, ref ty) in fields {
+                // Fields with a multiplicity of zero are left out of the
+                // representation, as their values are erased
+                if multiplicity != Multiplicity::Zero {
+                    reprs.push((label.clone(), resolve_type(env, &bindings, ty)?));
+                }
+                bindings.push(Binding::Erased);
+            }
+
+            Ok(Repr::Record(reprs))
+        },
+        _ => Err(LowerError::new(format!("unsupported type: {}", term))),
+    }
+}
+
+/// Pick the representations of the parameters and the result of a function
+/// type, where erased parameters are `None`
+fn signature(
+    env: &dyn Env,
+    bindings: &[Binding],
+    term: &RcTerm,
+) -> Result<(Vec<Option<Repr>>, Repr), LowerError> {
+    let mut bindings = bindings.to_vec();
+    let mut params = Vec::new();
+    let mut term = term;
+
+    loop {
+        match *term.inner {
+            Term::Ann(ref expr, _) => term = expr,
+            Term::FunType(_, multiplicity, _, ref ann, ref body) => {
+                if multiplicity == Multiplicity::Zero || is_universe(ann) {
+                    params.push(None);
+                } else {
+                    params.push(Some(resolve_type(env, &bindings, ann)?));
+                }
+                bindings.push(Binding::Erased);
+                term = body;
+            },
+            _ => break,
+        }
+    }
+
+    Ok((params, resolve_type(env, &bindings, term)?))
+}
+
+fn lookup(bindings: &[Binding], index: VarIndex) -> Result<&Binding, LowerError> {
+    match index.to_level(bindings.len()) {
+        Some(level) => Ok(&bindings[level.0]),
+        None => Err(LowerError::new(format!("unbound variable `{}`", index))),
+    }
+}
+
+fn unavailable(binding: &Binding) -> LowerError {
+    match *binding {
+        Binding::Captured => LowerError::new("capturing local variables is not yet supported"),
+        Binding::Type(_) => LowerError::new("using types as values is not supported"),
+        Binding::Erased => LowerError::new("used an erased variable at runtime"),
+        Binding::Unsupported(ref error) => error.clone(),
+        Binding::Local(..) => {
+            LowerError::new("calling functions that are stored in locals is not yet supported")
+        },
+        Binding::Function(..) => LowerError::new("using functions as types is not supported"),
+    }
+}
+
+fn is_universe(term: &RcTerm) -> bool {
+    match *term.strip_ann().inner {
+        Term::Universe(_) | Term::UniverseAt(_) | Term::UniverseOmega => true,
+        _ => false,
+    }
+}
+
+/// Returns `true` if the term is obviously a type
+fn is_type(term: &RcTerm) -> bool {
+    match *term.inner {
+        Term::Ann(ref expr, ref ann) => is_universe(ann) || is_type(expr),
+        Term::Universe(_)
+        | Term::UniverseAt(_)
+        | Term::UniverseOmega
+        | Term::FunType(..)
+        | Term::RecordType(_)
+        | Term::VariantType(_)
+        | Term::EqType(..) => true,
+        Term::Global(ref free_var, _) => match free_var.pretty_name {
+            Some(ref name) => name == "String" || Repr::from_type_name(name).is_some(),
+            None => false,
+        },
+        _ => false,
+    }
+}
@@ -0,0 +1,413 @@
+//! The syntax of the first-order intermediate representation
+//!
+//! Programs are made up of functions that are only ever called directly, and
+//! that always take all of their parameters at once. Every local variable and
+//! every function is given the representation of the values that it holds,
+//! so backends can pick machine types for them without needing to look at the
+//! types of the core syntax.
+
+use std::fmt;
+
+use pikelet_core::syntax::{Label, Literal};
+
+/// The number of spaces to indent nested expressions by
+const INDENT_WIDTH: usize = 4;
+
+/// The representation of a runtime value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Repr {
+    Bool,
+    Char,
+    U8,
+    U16,
+    U32,
+    U64,
+    S8,
+    S16,
+    S32,
+    S64,
+    F32,
+    F64,
+    Record(Vec<(Label, Repr)>),
+}
+
+impl Repr {
+    /// Look up a representation by the name of its type, as it is defined
+    /// in the global environment
+    pub fn from_type_name(name: &str) -> Option<Repr> {
+        match name {
+            "Bool" => Some(Repr::Bool),
+            "Char" => Some(Repr::Char),
+            "U8" => Some(Repr::U8),
+            "U16" => Some(Repr::U16),
+            "U32" => Some(Repr::U32),
+            "U64" => Some(Repr::U64),
+            "S8" => Some(Repr::S8),
+            "S16" => Some(Repr::S16),
+            "S32" => Some(Repr::S32),
+            "S64" => Some(Repr::S64),
+            "F32" => Some(Repr::F32),
+            "F64" => Some(Repr::F64),
+            _ => None,
+        }
+    }
+
+    /// Look up a representation by the name that is used for its type in the
+    /// names of primitive imports, for example the `u8` in `prim/u8/add`
+    pub fn from_prim_name(name: &str) -> Option<Repr> {
+        match name {
+            "bool" => Some(Repr::Bool),
+            "char" => Some(Repr::Char),
+            "u8" => Some(Repr::U8),
+            "u16" => Some(Repr::U16),
+            "u32" => Some(Repr::U32),
+            "u64" => Some(Repr::U64),
+            "i8" => Some(Repr::S8),
+            "i16" => Some(Repr::S16),
+            "i32" => Some(Repr::S32),
+            "i64" => Some(Repr::S64),
+            "f32" => Some(Repr::F32),
+            "f64" => Some(Repr::F64),
+            _ => None,
+        }
+    }
+
+    /// The name that is used for the type in the names of primitive imports,
+    /// or `None` for records
+    pub fn prim_name(&self) -> Option<&'static str> {
+        match *self {
+            Repr::Bool => Some("bool"),
+            Repr::Char => Some("char"),
+            Repr::U8 => Some("u8"),
+            Repr::U16 => Some("u16"),
+            Repr::U32 => Some("u32"),
+            Repr::U64 => Some("u64"),
+            Repr::S8 => Some("i8"),
+            Repr::S16 => Some("i16"),
+            Repr::S32 => Some("i32"),
+            Repr::S64 => Some("i64"),
+            Repr::F32 => Some("f32"),
+            Repr::F64 => Some("f64"),
+            Repr::Record(_) => None,
+        }
+    }
+
+    /// The representation of a literal, or `None` for strings
+    pub fn from_literal(literal: &Literal) -> Option<Repr> {
+        match *literal {
+            Literal::Bool(_) => Some(Repr::Bool),
+            Literal::Char(_) => Some(Repr::Char),
+            Literal::U8(_) => Some(Repr::U8),
+            Literal::U16(_) => Some(Repr::U16),
+            Literal::U32(_) => Some(Repr::U32),
+            Literal::U64(_) => Some(Repr::U64),
+            Literal::S8(_) => Some(Repr::S8),
+            Literal::S16(_) => Some(Repr::S16),
+            Literal::S32(_) => Some(Repr::S32),
+            Literal::S64(_) => Some(Repr::S64),
+            Literal::F32(_) => Some(Repr::F32),
+            Literal::F64(_) => Some(Repr::F64),
+            Literal::String(_) => None,
+        }
+    }
+
+    /// Returns `true` if comparisons should treat the values as signed
+    pub fn is_signed(&self) -> bool {
+        match *self {
+            Repr::S8 | Repr::S16 | Repr::S32 | Repr::S64 => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Repr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Repr::Bool => write!(f, "Bool"),
+            Repr::Char => write!(f, "Char"),
+            Repr::U8 => write!(f, "U8"),
+            Repr::U16 => write!(f, "U16"),
+            Repr::U32 => write!(f, "U32"),
+            Repr::U64 => write!(f, "U64"),
+            Repr::S8 => write!(f, "S8"),
+            Repr::S16 => write!(f, "S16"),
+            Repr::S32 => write!(f, "S32"),
+            Repr::S64 => write!(f, "S64"),
+            Repr::F32 => write!(f, "F32"),
+            Repr::F64 => write!(f, "F64"),
+            Repr::Record(ref fields) if fields.is_empty() => write!(f, "Record {{}}"),
+            Repr::Record(ref fields) => {
+                write!(f, "Record {{ ")?;
+                for (index, &(ref label, ref repr)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{} : {}", label, repr)?;
+                }
+                write!(f, " }}")
+            },
+        }
+    }
+}
+
+/// A local variable, referred to by its position in the function that it
+/// belongs to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LocalId(pub usize);
+
+impl fmt::Display for LocalId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+/// A function, referred to by its position in the program
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FunctionId(pub usize);
+
+impl fmt::Display for FunctionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "function{}", self.0)
+    }
+}
+
+/// Primitive operations on scalars
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PrimOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl PrimOp {
+    /// Look up an operation by the name that is used for it in the names of
+    /// primitive imports, for example the `add` in `prim/u8/add`
+    pub fn from_name(name: &str) -> Option<PrimOp> {
+        match name {
+            "eq" => Some(PrimOp::Eq),
+            "ne" => Some(PrimOp::Ne),
+            "lt" => Some(PrimOp::Lt),
+            "le" => Some(PrimOp::Le),
+            "gt" => Some(PrimOp::Gt),
+            "ge" => Some(PrimOp::Ge),
+            "add" => Some(PrimOp::Add),
+            "sub" => Some(PrimOp::Sub),
+            "mul" => Some(PrimOp::Mul),
+            "div" => Some(PrimOp::Div),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PrimOp::Eq => "eq",
+            PrimOp::Ne => "ne",
+            PrimOp::Lt => "lt",
+            PrimOp::Le => "le",
+            PrimOp::Gt => "gt",
+            PrimOp::Ge => "ge",
+            PrimOp::Add => "add",
+            PrimOp::Sub => "sub",
+            PrimOp::Mul => "mul",
+            PrimOp::Div => "div",
+        }
+    }
+
+    /// Returns `true` if the operation compares its operands, returning a
+    /// boolean
+    pub fn is_comparison(self) -> bool {
+        match self {
+            PrimOp::Eq | PrimOp::Ne | PrimOp::Lt | PrimOp::Le | PrimOp::Gt | PrimOp::Ge => true,
+            PrimOp::Add | PrimOp::Sub | PrimOp::Mul | PrimOp::Div => false,
+        }
+    }
+}
+
+/// Expressions
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// Literals, which are never strings
+    Literal(Literal),
+    /// Local variables
+    Local(LocalId, Repr),
+    /// Calls to functions, which are always applied to all of their
+    /// parameters
+    Call(FunctionId, Vec<Expr>, Repr),
+    /// Primitive operations, along with the representation of their operands
+    Prim(PrimOp, Repr, Vec<Expr>),
+    /// Records
+    RecordIntro(Vec<(Label, Expr)>),
+    /// Record projections
+    RecordProj(Box<Expr>, Label, Repr),
+    /// Assign the value of an expression to a local variable
+    Let(LocalId, Box<Expr>, Box<Expr>),
+    /// Case expressions on the value of a local variable, testing the
+    /// literals of the clauses in order, and falling back to the default
+    /// clause if none of them are equal
+    ///
+    /// If there is no default clause then one of the literals must match.
+    Case(LocalId, Vec<(Literal, Expr)>, Option<Box<Expr>>, Repr),
+}
+
+impl Expr {
+    /// The representation of the value of the expression
+    pub fn repr(&self) -> Repr {
+        match *self {
+            Expr::Literal(ref literal) => {
+                Repr::from_literal(literal).expect("string literals are not supported")
+            },
+            Expr::Local(_, ref repr)
+            | Expr::Call(_, _, ref repr)
+            | Expr::RecordProj(_, _, ref repr)
+            | Expr::Case(_, _, _, ref repr) => repr.clone(),
+            Expr::Prim(op, _, _) if op.is_comparison() => Repr::Bool,
+            Expr::Prim(_, ref repr, _) => repr.clone(),
+            Expr::RecordIntro(ref fields) => Repr::Record(
+                fields
+                    .iter()
+                    .map(|&(ref label, ref expr)| (label.clone(), expr.repr()))
+                    .collect(),
+            ),
+            Expr::Let(_, _, ref body) => body.repr(),
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter, indent: usize) -> fmt::Result {
+        match *self {
+            Expr::Literal(ref literal) => write!(f, "{}", literal),
+            Expr::Local(local, _) => write!(f, "{}", local),
+            Expr::Call(function, ref args, _) => {
+                write!(f, "{}", function)?;
+                fmt_args(f, indent, args)
+            },
+            Expr::Prim(op, ref repr, ref args) => {
+                let ty = repr.prim_name().unwrap_or("record");
+                write!(f, "prim/{}/{}", ty, op.name())?;
+                fmt_args(f, indent, args)
+            },
+            Expr::RecordIntro(ref fields) if fields.is_empty() => write!(f, "record {{}}"),
+            Expr::RecordIntro(ref fields) => {
+                write!(f, "record {{ ")?;
+                for (index, &(ref label, ref expr)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, "; ")?;
+                    }
+                    write!(f, "{} = ", label)?;
+                    expr.fmt_indented(f, indent)?;
+                }
+                write!(f, " }}")
+            },
+            Expr::RecordProj(ref expr, ref label, _) => {
+                expr.fmt_indented(f, indent)?;
+                write!(f, ".{}", label)
+            },
+            Expr::Let(local, ref expr, ref body) => {
+                write!(f, "let {} : {} = ", local, expr.repr())?;
+                expr.fmt_indented(f, indent)?;
+                writeln!(f, ";")?;
+                write!(f, "{:indent$}", "", indent = indent)?;
+                body.fmt_indented(f, indent)
+            },
+            Expr::Case(local, ref clauses, ref default, _) => {
+                let clause_indent = indent + INDENT_WIDTH;
+                writeln!(f, "case {} {{", local)?;
+                for &(ref literal, ref body) in clauses {
+                    write!(f, "{:indent$}{} => ", "", literal, indent = clause_indent)?;
+                    body.fmt_indented(f, clause_indent)?;
+                    writeln!(f, ",")?;
+                }
+                if let Some(ref body) = *default {
+                    write!(f, "{:indent$}_ => ", "", indent = clause_indent)?;
+                    body.fmt_indented(f, clause_indent)?;
+                    writeln!(f, ",")?;
+                }
+                write!(f, "{:indent$}}}", "", indent = indent)
+            },
+        }
+    }
+}
+
+fn fmt_args(f: &mut fmt::Formatter, indent: usize, args: &[Expr]) -> fmt::Result {
+    write!(f, "(")?;
+    for (index, arg) in args.iter().enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        arg.fmt_indented(f, indent)?;
+    }
+    write!(f, ")")
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// A function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// The number of parameters that the function takes
+    pub param_count: usize,
+    /// The representations of the local variables of the function, starting
+    /// with its parameters
+    pub locals: Vec<Repr>,
+    pub result: Repr,
+    pub body: Expr,
+}
+
+impl Function {
+    /// The representations of the parameters of the function
+    pub fn params(&self) -> &[Repr] {
+        &self.locals[..self.param_count]
+    }
+}
+
+/// A program, made up of functions, some of which are exported by name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    /// The functions in the program, indexed by `FunctionId`
+    pub functions: Vec<Function>,
+    pub exports: Vec<(Label, FunctionId)>,
+}
+
+impl Program {
+    pub fn function(&self, id: FunctionId) -> &Function {
+        &self.functions[id.0]
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, function) in self.functions.iter().enumerate() {
+            write!(f, "{}(", FunctionId(index))?;
+            for (param, repr) in function.params().iter().enumerate() {
+                if param > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{} : {}", LocalId(param), repr)?;
+            }
+            write!(
+                f,
+                ") : {} =\n{:indent$}",
+                function.result,
+                "",
+                indent = INDENT_WIDTH
+            )?;
+            function.body.fmt_indented(f, INDENT_WIDTH)?;
+            writeln!(f)?;
+            writeln!(f)?;
+        }
+        for &(ref label, function) in &self.exports {
+            writeln!(f, "export {} = {}", label, function)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+use pretty_assertions::assert_eq;
+
+use pikelet_concrete::elaborate::Context;
+use pikelet_mono::lower::{self, LowerError};
+use pikelet_test_support::{parse_infer_term, write_golden};
+
+fn golden(filename: &str, literal: &str) {
+    let context = Context::default();
+
+    let term = parse_infer_term(&context, literal);
+    let program = match lower::lower_module(&context, &term) {
+        Ok(program) => program,
+        Err(error) => panic!("lower error: {}", error),
+    };
+
+    write_golden(filename, &program);
+}
+
+fn lower_src(src: &str) -> Result<(), LowerError> {
+    let context = Context::default();
+    let term = parse_infer_term(&context, src);
+    lower::lower_module(&context, &term).map(|_| ())
+}
+
+#[test]
+fn constants() {
+    golden(
+        "constants",
+        r#"
+            let
+                answer : U32;
+                answer = 42;
+
+                yes = true;
+            in
+                record { answer; yes; big = 1000000000000 : S64 }
+        "#,
+    );
+}
+
+#[test]
+fn case_literal() {
+    golden(
+        "case_literal",
+        r#"
+            let
+                describe : U32 -> Char;
+                describe n = case n {
+                    0 => 'z';
+                    1 => 'o';
+                    _ => 'm';
+                };
+            in
+                record { describe }
+        "#,
+    );
+}
+
+#[test]
+fn recursive() {
+    golden(
+        "recursive",
+        r#"
+            let
+                partial fact : U64 -> U64;
+                fact n =
+                    if (import "prim/u64/eq") n 0 then 1 else
+                        (import "prim/u64/mul") n (fact ((import "prim/u64/sub") n 1));
+            in
+                record { fact }
+        "#,
+    );
+}
+
+#[test]
+fn records() {
+    golden(
+        "records",
+        r#"
+            let
+                Point = Record { x : F64; y : F64 };
+
+                scale : F64 -> Point -> Point;
+                scale k p = record {
+                    x = (import "prim/f64/mul") k p.x;
+                    y = (import "prim/f64/mul") k p.y;
+                };
+            in
+                record { Point; scale }
+        "#,
+    );
+}
+
+#[test]
+fn export_string() {
+    assert_eq!(
+        lower_src(r#"record { greeting = "hello" }"#),
+        Err(LowerError::new(
+            "could not export `greeting`: strings are not yet supported"
+        )),
+    );
+}
+
+#[test]
+fn export_capturing_function() {
+    assert_eq!(
+        lower_src(
+            r#"
+                let
+                    add : U32 -> U32 -> U32;
+                    add x y =
+                        let add-x = \(y : U32) => (import "prim/u32/add") x y;
+                        in add-x y;
+                in
+                    record { add }
+            "#
+        ),
+        Err(LowerError::new(
+            "could not export `add`: capturing local variables is not yet supported"
+        )),
+    );
+}
@@ -77,6 +77,7 @@ fn print_help_text() {
         ":core         <term>           print the core representation of a term",
         ":let          <name> = <term>  add a named term to the REPL context",
         ":q :quit                       quit the repl",
+        ":run          <term>           run a term, without normalizing under binders",
         ":t :type      <term>           infer the type of a term",
         "",
     ];
@@ -117,7 +118,9 @@ pub fn run(opts: Opts) -> Result<(), Error> {
         let mut src = String::new();
         file.read_to_string(&mut src)?;
 
-        if let Err(diagnostics) = driver.register_file(internal_path, external_path, src) {
+        let result = driver.register_file(internal_path, external_path, src);
+        driver.emit(writer.lock(), &driver.take_warnings()).unwrap();
+        if let Err(diagnostics) = result {
             driver.emit(writer.lock(), &diagnostics).unwrap();
             return Err(failure::format_err!("encountered an error!"));
         }
@@ -138,7 +141,9 @@ pub fn run(opts: Opts) -> Result<(), Error> {
                     },
                 };
 
-                match eval_print(&mut driver, repl_command) {
+                let result = eval_print(&mut driver, repl_command);
+                driver.emit(writer.lock(), &driver.take_warnings()).unwrap();
+                match result {
                     Ok(ControlFlow::Continue) => {},
                     Ok(ControlFlow::Break) => break,
                     Err(diagnostics) => driver.emit(writer.lock(), &diagnostics).unwrap(),
@@ -203,6 +208,12 @@ pub enum ReplCommand {
     /// :quit
     /// ```
     Quit,
+    /// Run a term using the call-by-value evaluator
+    ///
+    /// ```text
+    /// :run <term>
+    /// ```
+    Run(String),
     /// Print the type of the term
     ///
     /// ```text
@@ -238,6 +249,12 @@ impl FromStr for ReplCommand {
                     ))
                     .map(|_| ReplCommand::Help),
                 ),
+                attempt(
+                    string("run")
+                        .with(spaces1())
+                        .with(anys1())
+                        .map(ReplCommand::Run),
+                ),
                 attempt(
                     choice((attempt(string("quit")), attempt(string("q"))))
                         .map(|_| ReplCommand::Quit),
@@ -311,10 +328,10 @@ fn eval_print(
             println!("{}", ann_term.to_doc().group().pretty(term_width()));
         },
         ReplCommand::Core(term_src) => {
-            use pikelet_core::syntax::core::{RcTerm, Term};
+            use pikelet_core::syntax::core::Term;
 
             let (term, inferred) = driver.infer_file(file_name, term_src)?;
-            let ann_term = Term::Ann(term, RcTerm::from(Term::from(&*inferred)));
+            let ann_term = Term::Ann(term, driver.read_back(&inferred)?);
 
             println!("{}", ann_term.to_doc().group().pretty(term_width()));
         },
@@ -331,6 +348,18 @@ fn eval_print(
 
             return Ok(ControlFlow::Continue);
         },
+        ReplCommand::Run(term_src) => {
+            let (term, inferred) = driver.infer_file(file_name, term_src)?;
+            let (value, stats) = driver.run_term(&term)?;
+            let inferred = driver.resugar(&inferred);
+
+            println!(
+                "{} : {}",
+                value.to_doc().group().pretty(term_width()),
+                inferred.to_doc().group().pretty(term_width()),
+            );
+            println!("({})", stats);
+        },
         ReplCommand::TypeOf(term_src) => {
             let (_, inferred) = driver.infer_file(file_name, term_src)?;
             let inferred = driver.resugar(&inferred);
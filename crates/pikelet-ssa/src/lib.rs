@@ -0,0 +1,8 @@
+//! An intermediate representation in static single assignment form
+//!
+//! This is the last of the intermediate representations, and is the one that
+//! the native code generators are intended to be built on. Control flow is
+//! made explicit with basic blocks, and every value is assigned exactly once.
+
+pub mod lower;
+pub mod syntax;
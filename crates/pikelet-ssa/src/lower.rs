@@ -0,0 +1,477 @@
+//! Lowering of closure converted programs to static single assignment form
+//!
+//! Each lifted function becomes a function that takes the closure it was
+//! called through and its argument, and the expression of the program
+//! becomes the main function. Variables are mapped to the values that they
+//! were bound to, so no copies are needed.
+//!
+//! Case expressions are split into basic blocks, with the results of their
+//! clauses passed to a join block as a block argument:
+//!
+//! - case expressions on literals are lowered to a single `switch`
+//! - case expressions on variants are lowered to a single `switch_variant`,
+//!   as long as the variants only bind the values they hold
+//! - any other case expression is lowered to a sequence of tests, falling
+//!   through to the next clause if a test fails
+//!
+//! Records are lowered to aggregates that are accessed by label, as the
+//! layout of a record can't always be known once types have been erased.
+
+use std::collections::HashMap;
+
+use pikelet_anf::lower::LowerError as AnfLowerError;
+use pikelet_cc::convert::ConvertError;
+use pikelet_cc::syntax as cc;
+use pikelet_core::syntax::core::RcTerm;
+use pikelet_core::syntax::{Label, Literal};
+
+use crate::syntax::{Block, BlockId, Function, FunctionId, Inst, Program, Terminator, ValueId};
+
+/// An error produced while lowering a program
+#[derive(Debug, Clone, PartialEq, failure::Fail)]
+#[fail(display = "{}", message)]
+pub struct LowerError {
+    pub message: String,
+}
+
+impl LowerError {
+    pub fn new(message: impl Into<String>) -> LowerError {
+        LowerError {
+            message: message.into(),
+        }
+    }
+}
+
+impl From<AnfLowerError> for LowerError {
+    fn from(src: AnfLowerError) -> LowerError {
+        LowerError::new(src.message)
+    }
+}
+
+impl From<ConvertError> for LowerError {
+    fn from(src: ConvertError) -> LowerError {
+        LowerError::new(src.message)
+    }
+}
+
+/// Lower a closed term to static single assignment form, going by way of
+/// A-normal form and closure conversion
+pub fn lower_term(term: &RcTerm) -> Result<Program, LowerError> {
+    let expr = pikelet_anf::lower::lower_term(term)?;
+    let program = pikelet_cc::convert::convert_program(&expr)?;
+
+    lower_program(&program)
+}
+
+/// Lower a closure converted program to static single assignment form
+pub fn lower_program(program: &cc::Program) -> Result<Program, LowerError> {
+    let functions = program
+        .codes
+        .iter()
+        .map(lower_code)
+        .collect::<Result<_, LowerError>>()?;
+
+    let mut builder = Builder::new(Vec::new());
+    let result = builder.lower_expr(&program.main)?;
+    builder.terminate(Terminator::Return(result));
+
+    Ok(Program {
+        functions,
+        main: builder.finish(),
+    })
+}
+
+fn lower_code(code: &cc::Code) -> Result<Function, LowerError> {
+    let closure = ValueId(0);
+    let param = ValueId(1);
+
+    let mut builder = Builder::new(vec![closure, param]);
+    builder.closure = Some(closure);
+    if let Some(ref this) = code.this {
+        builder.vars.insert(this.id, closure);
+    }
+    builder.vars.insert(code.param.id, param);
+
+    let result = builder.lower_expr(&code.body)?;
+    builder.terminate(Terminator::Return(result));
+
+    Ok(builder.finish())
+}
+
+/// A basic block that is still being built
+struct PartialBlock {
+    params: Vec<ValueId>,
+    insts: Vec<(ValueId, Inst)>,
+    terminator: Option<Terminator>,
+}
+
+/// Builds up the blocks of a function
+struct Builder {
+    blocks: Vec<PartialBlock>,
+    /// The block that instructions are currently being added to
+    current: BlockId,
+    next_value: usize,
+    /// The values that the variables in scope are bound to
+    vars: HashMap<usize, ValueId>,
+    /// The closure that the function was called through, if any
+    closure: Option<ValueId>,
+}
+
+impl Builder {
+    fn new(params: Vec<ValueId>) -> Builder {
+        let next_value = params.len();
+        let entry = PartialBlock {
+            params,
+            insts: Vec::new(),
+            terminator: None,
+        };
+
+        Builder {
+            blocks: vec![entry],
+            current: BlockId(0),
+            next_value,
+            vars: HashMap::new(),
+            closure: None,
+        }
+    }
+
+    fn finish(self) -> Function {
+        let blocks = self
+            .blocks
+            .into_iter()
+            .map(|block| Block {
+                params: block.params,
+                insts: block.insts,
+                terminator: block.terminator.expect("unterminated block"),
+            })
+            .collect();
+
+        Function { blocks }
+    }
+
+    fn fresh_value(&mut self) -> ValueId {
+        let value = ValueId(self.next_value);
+        self.next_value += 1;
+        value
+    }
+
+    /// Create a new block, with the given number of parameters
+    fn new_block(&mut self, param_count: usize) -> BlockId {
+        let params = (0..param_count).map(|_| self.fresh_value()).collect();
+        let id = BlockId(self.blocks.len());
+        self.blocks.push(PartialBlock {
+            params,
+            insts: Vec::new(),
+            terminator: None,
+        });
+        id
+    }
+
+    fn block_params(&self, block: BlockId) -> &[ValueId] {
+        &self.blocks[block.0].params
+    }
+
+    fn switch_to(&mut self, block: BlockId) {
+        self.current = block;
+    }
+
+    fn emit(&mut self, inst: Inst) -> ValueId {
+        let value = self.fresh_value();
+        self.blocks[self.current.0].insts.push((value, inst));
+        value
+    }
+
+    fn terminate(&mut self, terminator: Terminator) {
+        self.blocks[self.current.0].terminator = Some(terminator);
+    }
+
+    fn lower_expr(&mut self, expr: &cc::Expr) -> Result<ValueId, LowerError> {
+        for &(ref var, ref comp) in &expr.bindings {
+            let value = self.lower_comp(comp)?;
+            self.vars.insert(var.id, value);
+        }
+
+        self.lower_comp(&expr.body)
+    }
+
+    fn lower_comp(&mut self, comp: &cc::Comp) -> Result<ValueId, LowerError> {
+        match *comp {
+            cc::Comp::Atom(ref atom) => self.lower_atom(atom),
+            cc::Comp::ClosureIntro(code, ref atoms) => {
+                let env = self.lower_atoms(atoms)?;
+                Ok(self.emit(Inst::ClosureIntro(FunctionId(code.0), env)))
+            },
+            cc::Comp::EnvProj(index) => match self.closure {
+                Some(closure) => Ok(self.emit(Inst::ClosureEnv(closure, index))),
+                None => Err(LowerError::new(
+                    "projected on the environment outside of a function",
+                )),
+            },
+            cc::Comp::FunApp(ref head, ref arg) => {
+                let head = self.lower_atom(head)?;
+                let arg = self.lower_atom(arg)?;
+                Ok(self.emit(Inst::Call(head, arg)))
+            },
+            cc::Comp::RecordIntro(ref fields) => {
+                let fields = self.lower_fields(fields)?;
+                Ok(self.emit(Inst::RecordIntro(fields)))
+            },
+            cc::Comp::RecordProj(ref atom, ref label) => {
+                let record = self.lower_atom(atom)?;
+                Ok(self.emit(Inst::RecordProj(record, label.clone())))
+            },
+            cc::Comp::RecordExtend(ref atom, ref fields) => {
+                let record = self.lower_atom(atom)?;
+                let fields = self.lower_fields(fields)?;
+                Ok(self.emit(Inst::RecordExtend(record, fields)))
+            },
+            cc::Comp::VariantIntro(ref label, ref atom) => {
+                let value = self.lower_atom(atom)?;
+                Ok(self.emit(Inst::VariantIntro(label.clone(), value)))
+            },
+            cc::Comp::ArrayIntro(ref atoms) => {
+                let elems = self.lower_atoms(atoms)?;
+                Ok(self.emit(Inst::ArrayIntro(elems)))
+            },
+            cc::Comp::Case(ref head, ref clauses) => {
+                let head = self.lower_atom(head)?;
+                self.lower_case(head, clauses)
+            },
+        }
+    }
+
+    fn lower_atom(&mut self, atom: &cc::Atom) -> Result<ValueId, LowerError> {
+        match *atom {
+            cc::Atom::Var(ref var) => match self.vars.get(&var.id) {
+                Some(&value) => Ok(value),
+                None => Err(LowerError::new(format!("unbound variable `{}`", var))),
+            },
+            cc::Atom::Global(ref free_var) => Err(LowerError::new(format!(
+                "unsupported global `{}`",
+                free_var
+                    .pretty_name
+                    .as_ref()
+                    .map_or("<global>", String::as_str)
+            ))),
+            cc::Atom::Import(ref name) => Ok(self.emit(Inst::Import(name.clone()))),
+            cc::Atom::Literal(ref literal) => Ok(self.emit(Inst::Literal(literal.clone()))),
+            cc::Atom::Erased => Ok(self.emit(Inst::Erased)),
+        }
+    }
+
+    fn lower_atoms(&mut self, atoms: &[cc::Atom]) -> Result<Vec<ValueId>, LowerError> {
+        atoms.iter().map(|atom| self.lower_atom(atom)).collect()
+    }
+
+    fn lower_fields(
+        &mut self,
+        fields: &[(Label, cc::Atom)],
+    ) -> Result<Vec<(Label, ValueId)>, LowerError> {
+        fields
+            .iter()
+            .map(|&(ref label, ref atom)| Ok((label.clone(), self.lower_atom(atom)?)))
+            .collect()
+    }
+
+    /// Lower a case expression, returning the value of the clause that was
+    /// taken
+    fn lower_case(
+        &mut self,
+        head: ValueId,
+        clauses: &[(cc::Pattern, cc::Expr)],
+    ) -> Result<ValueId, LowerError> {
+        let join = self.new_block(1);
+        let result = self.block_params(join)[0];
+
+        // The clause that catches everything that the others don't, if any
+        let (tests, fallback) = match clauses.split_last() {
+            Some((&(cc::Pattern::Binder(_), _), tests)) => (tests, clauses.last()),
+            _ => (clauses, None),
+        };
+
+        let switch = self.current;
+        if let Some(cases) = literal_cases(tests) {
+            let cases = cases
+                .into_iter()
+                .zip(tests)
+                .map(|(literal, &(_, ref body))| {
+                    let block = self.new_block(0);
+                    self.switch_to(block);
+                    self.lower_clause_body(body, join)?;
+                    Ok((literal.clone(), block))
+                })
+                .collect::<Result<_, LowerError>>()?;
+            let default = self.lower_fallback(head, fallback, join)?;
+            self.terminate_at(switch, Terminator::Switch(head, cases, default));
+        } else if let Some(cases) = variant_cases(tests) {
+            let cases = cases
+                .into_iter()
+                .zip(tests)
+                .map(|((label, var), &(_, ref body))| {
+                    let block = self.new_block(1);
+                    let value = self.block_params(block)[0];
+                    self.vars.insert(var.id, value);
+                    self.switch_to(block);
+                    self.lower_clause_body(body, join)?;
+                    Ok((label.clone(), block))
+                })
+                .collect::<Result<_, LowerError>>()?;
+            let default = self.lower_fallback(head, fallback, join)?;
+            self.terminate_at(switch, Terminator::SwitchVariant(head, cases, default));
+        } else {
+            for &(ref pattern, ref body) in clauses {
+                let next = self.new_block(0);
+                self.lower_pattern(pattern, head, next)?;
+                self.lower_clause_body(body, join)?;
+                self.switch_to(next);
+            }
+            self.terminate(Terminator::Unreachable);
+        }
+
+        self.switch_to(join);
+        Ok(result)
+    }
+
+    /// Lower the clause that is taken when none of the cases of a switch
+    /// match, returning the block that it starts in
+    fn lower_fallback(
+        &mut self,
+        head: ValueId,
+        fallback: Option<&(cc::Pattern, cc::Expr)>,
+        join: BlockId,
+    ) -> Result<BlockId, LowerError> {
+        let block = self.new_block(0);
+        self.switch_to(block);
+
+        match fallback {
+            Some(&(ref pattern, ref body)) => {
+                self.lower_pattern(pattern, head, block)?;
+                self.lower_clause_body(body, join)?;
+            },
+            None => self.terminate(Terminator::Unreachable),
+        }
+
+        Ok(block)
+    }
+
+    fn lower_clause_body(&mut self, body: &cc::Expr, join: BlockId) -> Result<(), LowerError> {
+        let value = self.lower_expr(body)?;
+        self.terminate(Terminator::Jump(join, vec![value]));
+        Ok(())
+    }
+
+    fn terminate_at(&mut self, block: BlockId, terminator: Terminator) {
+        self.blocks[block.0].terminator = Some(terminator);
+    }
+
+    /// Test a value against a pattern, binding the variables in the pattern
+    /// and continuing in a new block if it matches, or jumping to `fail` if
+    /// it doesn't
+    fn lower_pattern(
+        &mut self,
+        pattern: &cc::Pattern,
+        value: ValueId,
+        fail: BlockId,
+    ) -> Result<(), LowerError> {
+        match *pattern {
+            cc::Pattern::Binder(ref var) => {
+                self.vars.insert(var.id, value);
+            },
+            cc::Pattern::Atom(cc::Atom::Literal(ref literal)) => {
+                let matched = self.new_block(0);
+                self.terminate(Terminator::Switch(
+                    value,
+                    vec![(literal.clone(), matched)],
+                    fail,
+                ));
+                self.switch_to(matched);
+            },
+            cc::Pattern::Atom(ref atom) => {
+                let other = self.lower_atom(atom)?;
+                let eq = self.emit(Inst::Eq(value, other));
+                let matched = self.new_block(0);
+                self.terminate(Terminator::Switch(
+                    eq,
+                    vec![(Literal::Bool(true), matched)],
+                    fail,
+                ));
+                self.switch_to(matched);
+            },
+            cc::Pattern::VariantIntro(ref label, ref pattern) => {
+                let matched = self.new_block(1);
+                let arg = self.block_params(matched)[0];
+                self.terminate(Terminator::SwitchVariant(
+                    value,
+                    vec![(label.clone(), matched)],
+                    fail,
+                ));
+                self.switch_to(matched);
+                self.lower_pattern(pattern, arg, fail)?;
+            },
+            cc::Pattern::RecordIntro(ref fields) => {
+                for &(ref label, ref pattern) in fields {
+                    let field = self.emit(Inst::RecordProj(value, label.clone()));
+                    self.lower_pattern(pattern, field, fail)?;
+                }
+            },
+            // The lengths of arrays are checked by the type checker
+            cc::Pattern::ArrayIntro(ref patterns) => {
+                for (index, pattern) in patterns.iter().enumerate() {
+                    let elem = self.emit(Inst::ArrayIndex(value, index));
+                    self.lower_pattern(pattern, elem, fail)?;
+                }
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the literals that the clauses test for, if they only test for
+/// distinct literals
+fn literal_cases(clauses: &[(cc::Pattern, cc::Expr)]) -> Option<Vec<&Literal>> {
+    let literals = clauses
+        .iter()
+        .map(|&(ref pattern, _)| match *pattern {
+            cc::Pattern::Atom(cc::Atom::Literal(ref literal)) => Some(literal),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    if is_distinct(&literals) {
+        Some(literals)
+    } else {
+        None
+    }
+}
+
+/// Returns the labels that the clauses test for, along with the variables
+/// that they bind, if they only test for distinct variants
+fn variant_cases(clauses: &[(cc::Pattern, cc::Expr)]) -> Option<Vec<(&Label, &cc::Var)>> {
+    let cases = clauses
+        .iter()
+        .map(|&(ref pattern, _)| match *pattern {
+            cc::Pattern::VariantIntro(ref label, ref pattern) => match **pattern {
+                cc::Pattern::Binder(ref var) => Some((label, var)),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let labels = cases.iter().map(|&(label, _)| label).collect::<Vec<_>>();
+
+    if is_distinct(&labels) {
+        Some(cases)
+    } else {
+        None
+    }
+}
+
+/// Returns `true` if there is at least one item, and none of them are equal
+fn is_distinct<T: PartialEq>(items: &[T]) -> bool {
+    !items.is_empty()
+        && items
+            .iter()
+            .enumerate()
+            .all(|(index, item)| !items[..index].contains(item))
+}
@@ -0,0 +1,339 @@
+//! The syntax of the static single assignment intermediate representation
+//!
+//! Functions are made up of basic blocks, each of which is a sequence of
+//! instructions followed by a terminator that transfers control to other
+//! blocks, or back to the caller. Values are only ever assigned once, and
+//! instead of phi nodes, blocks take parameters that are supplied by the
+//! jumps into them.
+
+use pretty::{BoxDoc, Doc};
+use std::fmt;
+
+use pikelet_core::syntax::{Label, Literal, PRETTY_FALLBACK_WIDTH};
+
+/// The number of spaces to indent nested items by
+const PRETTY_INDENT_WIDTH: usize = 4;
+
+/// A value, which is assigned exactly once in the function it belongs to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ValueId(pub usize);
+
+impl fmt::Display for ValueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+/// A basic block, referred to by its position in the function that it
+/// belongs to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockId(pub usize);
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "block{}", self.0)
+    }
+}
+
+/// A function, referred to by its position in the program
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FunctionId(pub usize);
+
+impl fmt::Display for FunctionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "function{}", self.0)
+    }
+}
+
+/// Instructions, which compute a single value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inst {
+    /// Literals
+    Literal(Literal),
+    /// Types, and anything else that is not needed at runtime
+    Erased,
+    /// Primitive imports
+    Import(String),
+    /// Allocate a closure, made up of a pointer to the code of a function and
+    /// the values of its free variables
+    ClosureIntro(FunctionId, Vec<ValueId>),
+    /// Load a value from the environment of a closure
+    ClosureEnv(ValueId, usize),
+    /// Call a closure, or a primitive, with an argument
+    Call(ValueId, ValueId),
+    /// Allocate a record
+    RecordIntro(Vec<(Label, ValueId)>),
+    /// Load a field from a record
+    RecordProj(ValueId, Label),
+    /// Allocate a copy of a record with some extra fields
+    RecordExtend(ValueId, Vec<(Label, ValueId)>),
+    /// Allocate a variant
+    VariantIntro(Label, ValueId),
+    /// Allocate an array
+    ArrayIntro(Vec<ValueId>),
+    /// Load an element from an array
+    ArrayIndex(ValueId, usize),
+    /// Compare two values for equality, returning a boolean
+    Eq(ValueId, ValueId),
+}
+
+impl Inst {
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        match *self {
+            Inst::Literal(ref literal) => literal.to_doc(),
+            Inst::Erased => Doc::text("erased"),
+            Inst::Import(ref name) => Doc::text(format!("import {:?}", name)),
+            Inst::ClosureIntro(function, ref env) => Doc::nil()
+                .append("closure ")
+                .append(Doc::as_string(function))
+                .append(" ")
+                .append(values_to_doc(env)),
+            Inst::ClosureEnv(closure, index) => {
+                Doc::text(format!("closure_env {} {}", closure, index))
+            },
+            Inst::Call(function, arg) => Doc::text(format!("call {} {}", function, arg)),
+            Inst::RecordIntro(ref fields) => fields_to_doc("record", fields),
+            Inst::RecordProj(record, ref label) => Doc::text(format!("{}.{}", record, label)),
+            Inst::RecordExtend(record, ref fields) => Doc::nil()
+                .append(Doc::as_string(record))
+                .append(" ")
+                .append(fields_to_doc("with", fields)),
+            Inst::VariantIntro(ref label, value) => {
+                Doc::text(format!("variant {} {}", label, value))
+            },
+            Inst::ArrayIntro(ref elems) => values_to_doc(elems),
+            Inst::ArrayIndex(array, index) => Doc::text(format!("{}[{}]", array, index)),
+            Inst::Eq(lhs, rhs) => Doc::text(format!("eq {} {}", lhs, rhs)),
+        }
+    }
+}
+
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
+/// Terminators, which end a basic block by transferring control elsewhere
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    /// Return a value from the function
+    Return(ValueId),
+    /// Jump to a block, passing it arguments
+    Jump(BlockId, Vec<ValueId>),
+    /// Jump to the block for the literal that a value is equal to, or to the
+    /// default block if there isn't one
+    Switch(ValueId, Vec<(Literal, BlockId)>, BlockId),
+    /// Jump to the block for the label of a variant, or to the default block
+    /// if there isn't one
+    ///
+    /// The block for a label is passed the value that the variant holds.
+    SwitchVariant(ValueId, Vec<(Label, BlockId)>, BlockId),
+    /// Control can never reach the end of this block
+    Unreachable,
+}
+
+impl Terminator {
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        match *self {
+            Terminator::Return(value) => Doc::text(format!("return {}", value)),
+            Terminator::Jump(block, ref args) => Doc::nil()
+                .append("jump ")
+                .append(Doc::as_string(block))
+                .append(args_to_doc(args)),
+            Terminator::Switch(value, ref cases, default) => switch_to_doc(
+                "switch",
+                value,
+                cases
+                    .iter()
+                    .map(|&(ref literal, block)| (literal.to_doc(), block)),
+                default,
+            ),
+            Terminator::SwitchVariant(value, ref cases, default) => switch_to_doc(
+                "switch_variant",
+                value,
+                cases
+                    .iter()
+                    .map(|&(ref label, block)| (Doc::as_string(label), block)),
+                default,
+            ),
+            Terminator::Unreachable => Doc::text("unreachable"),
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
+/// A basic block
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub params: Vec<ValueId>,
+    pub insts: Vec<(ValueId, Inst)>,
+    pub terminator: Terminator,
+}
+
+impl Block {
+    pub fn to_doc(&self, id: BlockId) -> Doc<BoxDoc<()>> {
+        let insts = Doc::concat(self.insts.iter().map(|&(value, ref inst)| {
+            Doc::newline()
+                .append(Doc::as_string(value))
+                .append(" = ")
+                .append(inst.to_doc())
+        }));
+
+        Doc::nil()
+            .append(Doc::as_string(id))
+            .append(args_to_doc(&self.params))
+            .append(":")
+            .append(
+                insts
+                    .append(Doc::newline())
+                    .append(self.terminator.to_doc())
+                    .nest(PRETTY_INDENT_WIDTH),
+            )
+    }
+}
+
+/// A function, made up of basic blocks
+///
+/// The first block is the entry block of the function, and takes the same
+/// parameters as the function itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub blocks: Vec<Block>,
+}
+
+impl Function {
+    pub fn params(&self) -> &[ValueId] {
+        &self.blocks[0].params
+    }
+
+    pub fn block(&self, id: BlockId) -> &Block {
+        &self.blocks[id.0]
+    }
+
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        Doc::nil()
+            .append(params_to_doc(self.params()))
+            .append(":")
+            .append(
+                Doc::concat(
+                    self.blocks
+                        .iter()
+                        .enumerate()
+                        .map(|(index, block)| Doc::newline().append(block.to_doc(BlockId(index)))),
+                )
+                .nest(PRETTY_INDENT_WIDTH),
+            )
+    }
+}
+
+/// A program in static single assignment form
+///
+/// The functions in the program take two parameters: the closure that they
+/// were called through, and the argument that they were called with. The
+/// main function takes no parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    /// The functions in the program, indexed by `FunctionId`
+    pub functions: Vec<Function>,
+    /// The function that computes the result of the program
+    pub main: Function,
+}
+
+impl Program {
+    pub fn function(&self, id: FunctionId) -> &Function {
+        &self.functions[id.0]
+    }
+
+    pub fn to_doc(&self) -> Doc<BoxDoc<()>> {
+        Doc::concat(self.functions.iter().enumerate().map(|(index, function)| {
+            Doc::nil()
+                .append(Doc::as_string(FunctionId(index)))
+                .append(function.to_doc())
+                .append(Doc::newline())
+                .append(Doc::newline())
+        }))
+        .append("main")
+        .append(self.main.to_doc())
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.to_doc().group().render_fmt(PRETTY_FALLBACK_WIDTH, f)
+    }
+}
+
+fn params_to_doc(values: &[ValueId]) -> Doc<BoxDoc<()>> {
+    Doc::nil()
+        .append("(")
+        .append(Doc::intersperse(
+            values.iter().map(Doc::as_string),
+            Doc::text(", "),
+        ))
+        .append(")")
+}
+
+/// Like `params_to_doc`, but leaving out the parentheses if there are no
+/// values
+fn args_to_doc(values: &[ValueId]) -> Doc<BoxDoc<()>> {
+    if values.is_empty() {
+        Doc::nil()
+    } else {
+        params_to_doc(values)
+    }
+}
+
+fn values_to_doc(values: &[ValueId]) -> Doc<BoxDoc<()>> {
+    Doc::nil()
+        .append("[")
+        .append(Doc::intersperse(
+            values.iter().map(Doc::as_string),
+            Doc::text("; "),
+        ))
+        .append("]")
+}
+
+fn fields_to_doc<'a>(
+    keyword: &'static str,
+    fields: &'a [(Label, ValueId)],
+) -> Doc<'a, BoxDoc<'a, ()>> {
+    if fields.is_empty() {
+        return Doc::text(keyword).append(" {}");
+    }
+
+    Doc::nil()
+        .append(keyword)
+        .append(" { ")
+        .append(Doc::intersperse(
+            fields
+                .iter()
+                .map(|&(ref label, value)| Doc::text(format!("{} = {}", label, value))),
+            Doc::text("; "),
+        ))
+        .append(" }")
+}
+
+fn switch_to_doc<'a>(
+    keyword: &'static str,
+    value: ValueId,
+    cases: impl Iterator<Item = (Doc<'a, BoxDoc<'a, ()>>, BlockId)>,
+    default: BlockId,
+) -> Doc<'a, BoxDoc<'a, ()>> {
+    Doc::nil()
+        .append(keyword)
+        .append(" ")
+        .append(Doc::as_string(value))
+        .append(" [")
+        .append(Doc::intersperse(
+            cases.map(|(case, block)| case.append(" => ").append(Doc::as_string(block))),
+            Doc::text(", "),
+        ))
+        .append("] else ")
+        .append(Doc::as_string(default))
+}
@@ -0,0 +1,99 @@
+use pikelet_concrete::elaborate::Context;
+use pikelet_ssa::lower::{self, LowerError};
+use pikelet_test_support::{parse_infer_term, write_golden};
+
+fn golden(filename: &str, literal: &str) {
+    let context = Context::default();
+
+    let term = parse_infer_term(&context, literal);
+    let program = match lower::lower_term(&term) {
+        Ok(program) => program,
+        Err(error) => panic!("lower error: {}", error),
+    };
+
+    write_golden(filename, &program);
+}
+
+#[test]
+fn id() {
+    golden("id", r"\(0 a : Type) (x : a) => x");
+}
+
+#[test]
+fn capture_let() {
+    golden(
+        "capture_let",
+        r#"
+            let
+                add = import "prim/u64/add";
+                x = add 1 2;
+            in
+                \(y : U64) => add x y
+        "#,
+    );
+}
+
+#[test]
+fn fix() {
+    golden(
+        "fix",
+        r#"
+            let
+                partial loop : U64 -> U64;
+                loop n = loop n;
+            in
+                loop
+        "#,
+    );
+}
+
+#[test]
+fn record() {
+    golden(
+        "record",
+        r"\(r : Record { x : U64; y : U64 }) => record { a = r.x; b = r.y }",
+    );
+}
+
+#[test]
+fn case_literal() {
+    golden(
+        "case_literal",
+        r#"
+            \(x : U64) => case x {
+                0 => "zero";
+                _ => "other";
+            }
+        "#,
+    );
+}
+
+#[test]
+fn case_variant() {
+    golden(
+        "case_variant",
+        r#"
+            \(x : Variant { some : String; none : Record {} }) => case x {
+                variant some s => s;
+                variant none _ => "nothing";
+            }
+        "#,
+    );
+}
+
+#[test]
+fn env_proj_outside_function() {
+    use pikelet_cc::syntax::{Comp, Expr, Program};
+
+    let program = Program {
+        codes: vec![],
+        main: Expr::new(vec![], Comp::EnvProj(0)),
+    };
+
+    assert_eq!(
+        lower::lower_program(&program),
+        Err(LowerError::new(
+            "projected on the environment outside of a function"
+        )),
+    );
+}
@@ -0,0 +1,128 @@
+//! Helpers that are shared between the tests of the compiler backends, like
+//! the parsing of test programs, golden files, and comparing the results of
+//! running programs against the evaluator
+//!
+//! These are only intended to be used as development dependencies.
+
+use codespan::{CodeMap, FileName};
+use codespan_reporting::termcolor::{ColorChoice, StandardStream};
+use goldenfile::Mint;
+use pretty_assertions::assert_eq;
+use std::fmt;
+use std::io::Write;
+
+use pikelet_concrete::desugar::{Desugar, DesugarEnv};
+use pikelet_concrete::elaborate::{self, Context};
+use pikelet_concrete::parse;
+use pikelet_core::eval;
+use pikelet_core::syntax::core::RcTerm;
+use pikelet_core::syntax::{Label, Literal};
+
+/// Parse and elaborate a term, printing the diagnostics and panicking if it
+/// is not well formed
+pub fn parse_infer_term(context: &Context, src: &str) -> RcTerm {
+    let mut codemap = CodeMap::new();
+    let filemap = codemap.add_filemap(FileName::virtual_("test"), src.into());
+    let (concrete_term, _import_paths, errors) = parse::term(&filemap);
+
+    if !errors.is_empty() {
+        let writer = StandardStream::stdout(ColorChoice::Always);
+        for error in errors {
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &error.to_diagnostic()).unwrap();
+        }
+        panic!("parse error!")
+    }
+
+    let raw_term = concrete_term
+        .desugar(&DesugarEnv::new(context.mappings()))
+        .unwrap();
+
+    match elaborate::infer_term(context, &raw_term) {
+        Ok((term, _)) => term,
+        Err(error) => {
+            let writer = StandardStream::stdout(ColorChoice::Always);
+            codespan_reporting::emit(&mut writer.lock(), &codemap, &error.to_diagnostic()).unwrap();
+            panic!("type error!");
+        },
+    }
+}
+
+/// Compare the displayed output against the golden file of the given name in
+/// the `tests/goldenfiles` directory of the crate being tested
+pub fn write_golden(filename: &str, output: &impl fmt::Display) {
+    let path = "tests/goldenfiles";
+
+    let mut mint = Mint::new(path);
+    let mut file = mint.new_goldenfile(filename).unwrap();
+
+    write!(file, "{}", output).unwrap();
+}
+
+/// The result of running a program, as far as it can be seen from the outside
+///
+/// This is what the backends are compared on. Functions can only be told
+/// apart by applying them, so they are only compared on being functions.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Observation {
+    Literal(Literal),
+    Record(Vec<(Label, Observation)>),
+    Variant(Label, Box<Observation>),
+    Array(Vec<Observation>),
+    Function,
+    Erased,
+}
+
+impl Observation {
+    /// Observe a record, leaving out the fields that were erased, as not every
+    /// backend keeps them around
+    pub fn record(fields: impl IntoIterator<Item = (Label, Observation)>) -> Observation {
+        Observation::Record(
+            fields
+                .into_iter()
+                .filter(|&(_, ref field)| *field != Observation::Erased)
+                .collect(),
+        )
+    }
+
+    /// Observe a value that was produced by the call-by-value evaluator
+    pub fn from_eval(value: &eval::Value) -> Observation {
+        match *value {
+            eval::Value::Literal(ref literal) => Observation::Literal(literal.clone()),
+            eval::Value::FunIntro(_) | eval::Value::Fix(_) | eval::Value::Prim(_, _) => {
+                Observation::Function
+            },
+            eval::Value::RecordIntro(ref fields) => Observation::record(
+                fields
+                    .iter()
+                    .map(|&(ref label, ref value)| (label.clone(), Observation::from_eval(value))),
+            ),
+            eval::Value::VariantIntro(ref label, ref value) => {
+                Observation::Variant(label.clone(), Box::new(Observation::from_eval(value)))
+            },
+            eval::Value::ArrayIntro(ref elems) => Observation::Array(
+                elems
+                    .iter()
+                    .map(|elem| Observation::from_eval(elem))
+                    .collect(),
+            ),
+            eval::Value::EqIntro | eval::Value::Erased => Observation::Erased,
+        }
+    }
+}
+
+/// Check that running a program with a backend gives the same result as
+/// running it with the call-by-value evaluator
+///
+/// `run` is given the elaborated program, and should run it with the backend
+/// that is being tested.
+pub fn assert_agrees(src: &str, run: impl FnOnce(&Context, &RcTerm) -> Observation) {
+    let context = Context::default();
+
+    let term = parse_infer_term(&context, src);
+    let expected = match eval::run_term(&context, &term) {
+        Ok((value, _)) => Observation::from_eval(&value),
+        Err(error) => panic!("eval error: {}", error),
+    };
+
+    assert_eq!(run(&context, &term), expected);
+}
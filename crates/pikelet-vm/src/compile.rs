@@ -0,0 +1,603 @@
+//! Compilation of core terms to bytecode
+//!
+//! Terms are erased before they are compiled, so types and the arguments that
+//! are passed to parameters with a multiplicity of zero never make it into
+//! the bytecode. The compiler keeps track of how many values each function
+//! has on the stack at every point, so that local variables can be found at
+//! a fixed slot relative to the argument of the function. Variables from
+//! enclosing functions are copied into closures when they are created.
+//!
+//! Saturated applications of primitive imports are compiled to
+//! `Instruction::Prim`. When a primitive is used on its own, or only partially
+//! applied, it is wrapped in curried functions that call it once all of the
+//! arguments have been supplied.
+
+use std::collections::HashMap;
+
+use pikelet_core::erase::erase;
+use pikelet_core::nbe::Env;
+use pikelet_core::syntax::core::{Pattern, RcPattern, RcTerm, Term};
+use pikelet_core::syntax::{Import, Label};
+
+use crate::syntax::{Function, FunctionId, Instruction, Offset, PrimOp, Program};
+
+/// An error produced during compilation
+#[derive(Debug, Clone, PartialEq, failure::Fail)]
+#[fail(display = "{}", message)]
+pub struct CompileError {
+    pub message: String,
+}
+
+impl CompileError {
+    pub fn new(message: impl Into<String>) -> CompileError {
+        CompileError {
+            message: message.into(),
+        }
+    }
+}
+
+/// Compile a term that does not refer to any local variables to a program
+/// that evaluates it
+pub fn compile_program(env: &dyn Env, term: &RcTerm) -> Result<Program, CompileError> {
+    let mut compiler = Compiler {
+        env,
+        functions: vec![None],
+        prims: HashMap::new(),
+    };
+
+    let mut state = FunctionState::new(0);
+    compiler.compile_tail(&mut state, &[], &erase(term))?;
+    compiler.functions[Program::MAIN.0] = Some(Function {
+        name: "main".to_owned(),
+        code: state.code,
+    });
+
+    Ok(Program {
+        functions: compiler
+            .functions
+            .into_iter()
+            .map(|function| function.expect("function was never compiled"))
+            .collect(),
+    })
+}
+
+/// Where the value of a local variable can be found
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Binding {
+    /// In a slot of the current function's part of the stack
+    Local(usize),
+    /// In the captures of the current closure
+    Capture(usize),
+    /// The current closure itself, for recursive definitions
+    Current,
+}
+
+/// The local variables in scope, in the order that they were bound
+///
+/// Variables from enclosing functions that are not used by the current
+/// function are not captured, and so are `None`.
+type Scope = [Option<Binding>];
+
+/// The state of the function that is currently being compiled
+struct FunctionState {
+    code: Vec<Instruction>,
+    /// The number of values that will be on the stack, counting up from the
+    /// argument of the function
+    height: usize,
+}
+
+impl FunctionState {
+    fn new(height: usize) -> FunctionState {
+        FunctionState {
+            code: Vec::new(),
+            height,
+        }
+    }
+
+    /// Append an instruction, keeping track of its effect on the stack
+    fn emit(&mut self, instruction: Instruction) {
+        let (popped, pushed) = match instruction {
+            Instruction::Literal(_)
+            | Instruction::Erased
+            | Instruction::Local(_)
+            | Instruction::Capture(_)
+            | Instruction::Current => (0, 1),
+            Instruction::Slide(count) => (count + 1, 1),
+            Instruction::Closure(_, count) => (count, 1),
+            Instruction::Call => (2, 1),
+            // These never fall through to the next instruction
+            Instruction::TailCall | Instruction::Return | Instruction::NoMatch => (0, 0),
+            Instruction::Record(ref labels) => (labels.len(), 1),
+            Instruction::Project(_) => (1, 1),
+            Instruction::Extend(ref labels) => (labels.len() + 1, 1),
+            Instruction::MatchLiteral(_, _) => (1, 0),
+            Instruction::Jump(_) => (0, 0),
+            Instruction::Prim(op) => (op.arity(), 1),
+        };
+
+        self.height = self.height - popped + pushed;
+        self.code.push(instruction);
+    }
+
+    /// The offset of the next instruction
+    fn offset(&self) -> Offset {
+        Offset(self.code.len())
+    }
+
+    /// Point a previously emitted jump at the next instruction
+    fn patch(&mut self, jump: usize) {
+        let target = self.offset();
+        match self.code[jump] {
+            Instruction::MatchLiteral(_, ref mut offset) | Instruction::Jump(ref mut offset) => {
+                *offset = target;
+            },
+            _ => panic!("patched an instruction that is not a jump"),
+        }
+    }
+}
+
+struct Compiler<'env> {
+    env: &'env dyn Env,
+    /// The functions in the program. These are `None` while they are being
+    /// compiled.
+    functions: Vec<Option<Function>>,
+    /// The functions that wrap primitive imports, along with the number of
+    /// arguments that each of them has been applied to
+    prims: HashMap<(String, usize), FunctionId>,
+}
+
+impl<'env> Compiler<'env> {
+    fn reserve_function(&mut self) -> FunctionId {
+        self.functions.push(None);
+        FunctionId(self.functions.len() - 1)
+    }
+
+    /// Compile an expression in tail position, returning its value from the
+    /// current function
+    fn compile_tail(
+        &mut self,
+        state: &mut FunctionState,
+        scope: &Scope,
+        term: &RcTerm,
+    ) -> Result<(), CompileError> {
+        match *term.inner {
+            Term::FunApp(_, _, _) => {
+                if self.compile_app(state, scope, term)? {
+                    state.emit(Instruction::TailCall);
+                } else {
+                    state.emit(Instruction::Return);
+                }
+                Ok(())
+            },
+            Term::Let(ref bindings, ref body) => {
+                let mut scope = scope.to_vec();
+                for &(ref name, ref term) in bindings {
+                    let slot = state.height;
+                    self.compile_named(state, &scope, name.0.as_ref().map(String::as_str), term)?;
+                    scope.push(Some(Binding::Local(slot)));
+                }
+                // The bindings are removed along with the rest of the frame
+                self.compile_tail(state, &scope, body)
+            },
+            Term::Case(ref head, ref clauses) => {
+                self.compile_case(state, scope, head, clauses, true)
+            },
+            _ => {
+                self.compile_expr(state, scope, term)?;
+                state.emit(Instruction::Return);
+                Ok(())
+            },
+        }
+    }
+
+    /// Compile an expression, pushing its value onto the stack
+    fn compile_expr(
+        &mut self,
+        state: &mut FunctionState,
+        scope: &Scope,
+        term: &RcTerm,
+    ) -> Result<(), CompileError> {
+        let env = self.env;
+        match *term.inner {
+            Term::Ann(ref expr, _) => self.compile_expr(state, scope, expr),
+
+            Term::Universe(_)
+            | Term::UniverseAt(_)
+            | Term::UniverseOmega
+            | Term::LevelType
+            | Term::LevelIntro(_)
+            | Term::LevelMax(_, _)
+            | Term::LevelShift(_, _)
+            | Term::FunType(_, _, _, _, _)
+            | Term::RecordType(_)
+            | Term::VariantType(_)
+            | Term::EqType(_, _, _)
+            | Term::EqIntro
+            | Term::Erased => {
+                state.emit(Instruction::Erased);
+                Ok(())
+            },
+
+            Term::Literal(ref literal) => {
+                state.emit(Instruction::Literal(literal.clone()));
+                Ok(())
+            },
+
+            Term::Var(index, _) => {
+                let binding = index
+                    .to_level(scope.len())
+                    .and_then(|level| scope[level.0])
+                    .ok_or_else(|| {
+                        CompileError::new(format!("unbound local variable `{}`", index))
+                    })?;
+                state.emit(load(binding));
+                Ok(())
+            },
+            // Globals without definitions are the built-in types, and so
+            // are erased
+            Term::Global(ref free_var, _) => match env.get_definition(free_var) {
+                Some(term) => self.compile_expr(state, &[], &erase(term)),
+                None => {
+                    state.emit(Instruction::Erased);
+                    Ok(())
+                },
+            },
+            Term::Import(ref name) => match env.get_import(name) {
+                Some(&Import::Term(ref term)) => self.compile_expr(state, &[], &erase(term)),
+                Some(&Import::Prim(_, _)) => self.compile_prim_app(state, scope, name, &[]),
+                None => Err(CompileError::new(format!("unknown import `{}`", name))),
+            },
+            Term::Meta(meta) => match env.get_meta_solution(meta) {
+                Some(term) => self.compile_expr(state, &[], &erase(&term)),
+                None => Err(CompileError::new(format!(
+                    "unsolved metavariable `{}`",
+                    meta
+                ))),
+            },
+
+            Term::FunIntro(_, _, _, _, _) | Term::Fix(_, _, _, _) => {
+                self.compile_closure(state, scope, None, term)
+            },
+            Term::FunApp(_, _, _) => {
+                if self.compile_app(state, scope, term)? {
+                    state.emit(Instruction::Call);
+                }
+                Ok(())
+            },
+
+            Term::Let(ref bindings, ref body) => {
+                let mut scope = scope.to_vec();
+                for &(ref name, ref term) in bindings {
+                    let slot = state.height;
+                    self.compile_named(state, &scope, name.0.as_ref().map(String::as_str), term)?;
+                    scope.push(Some(Binding::Local(slot)));
+                }
+                self.compile_expr(state, &scope, body)?;
+                state.emit(Instruction::Slide(bindings.len()));
+                Ok(())
+            },
+
+            Term::RecordIntro(ref fields) => {
+                let fields = fields.iter().map(|&(ref label, _, ref term)| (label, term));
+                let labels = self.compile_fields(state, scope, fields)?;
+                state.emit(Instruction::Record(labels));
+                Ok(())
+            },
+            Term::RecordProj(ref expr, ref label, _, _) => {
+                self.compile_expr(state, scope, expr)?;
+                state.emit(Instruction::Project(label.clone()));
+                Ok(())
+            },
+            Term::RecordExtend(ref expr, ref fields) => {
+                self.compile_expr(state, scope, expr)?;
+                let fields = fields.iter().map(|&(ref label, ref term)| (label, term));
+                let labels = self.compile_fields(state, scope, fields)?;
+                state.emit(Instruction::Extend(labels));
+                Ok(())
+            },
+
+            Term::Case(ref head, ref clauses) => {
+                self.compile_case(state, scope, head, clauses, false)
+            },
+
+            // Rewriting doesn't change the value of the body
+            Term::EqElim(_, _, ref body) => self.compile_expr(state, scope, body),
+
+            Term::VariantIntro(_, _) => Err(CompileError::new("variants are not yet supported")),
+            Term::ArrayIntro(_) => Err(CompileError::new("arrays are not yet supported")),
+        }
+    }
+
+    /// Compile an expression that is bound to a name, using the name for the
+    /// function if it is one
+    fn compile_named(
+        &mut self,
+        state: &mut FunctionState,
+        scope: &Scope,
+        name: Option<&str>,
+        term: &RcTerm,
+    ) -> Result<(), CompileError> {
+        match *term.inner {
+            Term::FunIntro(_, _, _, _, _) | Term::Fix(_, _, _, _) => {
+                self.compile_closure(state, scope, name, term)
+            },
+            _ => self.compile_expr(state, scope, term),
+        }
+    }
+
+    /// Compile the fields of a record, leaving their values on the stack
+    fn compile_fields<'a>(
+        &mut self,
+        state: &mut FunctionState,
+        scope: &Scope,
+        fields: impl Iterator<Item = (&'a Label, &'a RcTerm)>,
+    ) -> Result<Vec<Label>, CompileError> {
+        fields
+            .map(|(label, term)| {
+                self.compile_named(state, scope, Some(label.0.as_str()), term)?;
+                Ok(label.clone())
+            })
+            .collect()
+    }
+
+    /// Compile a function or a recursive function to a closure, capturing
+    /// the variables from the current scope that it refers to
+    fn compile_closure(
+        &mut self,
+        state: &mut FunctionState,
+        scope: &Scope,
+        name: Option<&str>,
+        term: &RcTerm,
+    ) -> Result<(), CompileError> {
+        let (recursive, name, body) = match *term.inner {
+            Term::FunIntro(_, _, _, _, ref body) => (false, name, body),
+            Term::Fix(_, ref fix_name, _, ref fix_body) => match *fix_body.inner {
+                Term::FunIntro(_, _, _, _, ref body) => {
+                    (true, fix_name.0.as_ref().map(String::as_str).or(name), body)
+                },
+                _ => {
+                    return Err(CompileError::new("recursive definitions must be functions"));
+                },
+            },
+            _ => panic!("compiled a closure from a term that is not a function"),
+        };
+
+        // Find the variables from the current scope that are used in the
+        // function, as seen from outside of it
+        let mut captures = Vec::new();
+        term.subst_vars(&mut |index, shift| {
+            if let Some(level) = index.to_level(scope.len()) {
+                if !captures.contains(&level.0) {
+                    captures.push(level.0);
+                }
+            }
+            RcTerm::from(Term::Var(index, shift))
+        });
+        captures.sort();
+
+        let mut inner_scope = vec![None; scope.len()];
+        for (capture, &level) in captures.iter().enumerate() {
+            let binding = scope[level]
+                .ok_or_else(|| CompileError::new("captured a variable that is not in scope"))?;
+            state.emit(load(binding));
+            inner_scope[level] = Some(Binding::Capture(capture));
+        }
+        if recursive {
+            inner_scope.push(Some(Binding::Current));
+        }
+        inner_scope.push(Some(Binding::Local(0)));
+
+        let function = self.reserve_function();
+        let name = name.unwrap_or("lambda");
+        let mut inner_state = FunctionState::new(1);
+        match *body.inner {
+            // Keep the name for curried functions
+            Term::FunIntro(_, _, _, _, _) | Term::Fix(_, _, _, _) => {
+                self.compile_closure(&mut inner_state, &inner_scope, Some(name), body)?;
+                inner_state.emit(Instruction::Return);
+            },
+            _ => self.compile_tail(&mut inner_state, &inner_scope, body)?,
+        }
+        self.functions[function.0] = Some(Function {
+            name: name.to_owned(),
+            code: inner_state.code,
+        });
+
+        state.emit(Instruction::Closure(function, captures.len()));
+        Ok(())
+    }
+
+    /// Compile a function application, leaving the function and its last
+    /// argument on the stack, ready to be called
+    ///
+    /// Applications of primitive imports are compiled to their instructions
+    /// directly, in which case this returns `false`, as there is nothing
+    /// left to call.
+    fn compile_app(
+        &mut self,
+        state: &mut FunctionState,
+        scope: &Scope,
+        term: &RcTerm,
+    ) -> Result<bool, CompileError> {
+        let mut head = term;
+        let mut args = Vec::new();
+        while let Term::FunApp(ref next_head, _, ref arg) = *head.inner {
+            head = next_head;
+            args.push(arg.clone());
+        }
+        args.reverse();
+
+        if let Term::Import(ref name) = *head.inner {
+            if let Some(&Import::Prim(_, _)) = self.env.get_import(name) {
+                let arity = prim_op(name)?.arity();
+                if args.len() <= arity {
+                    self.compile_prim_app(state, scope, name, &args)?;
+                    return Ok(false);
+                }
+            }
+        }
+
+        let (last_arg, args) = args.split_last().expect("application without arguments");
+        self.compile_expr(state, scope, head)?;
+        for arg in args {
+            self.compile_expr(state, scope, arg)?;
+            state.emit(Instruction::Call);
+        }
+        self.compile_expr(state, scope, last_arg)?;
+
+        Ok(true)
+    }
+
+    /// Compile a primitive import, applied to at most as many arguments as
+    /// it takes
+    fn compile_prim_app(
+        &mut self,
+        state: &mut FunctionState,
+        scope: &Scope,
+        name: &str,
+        args: &[RcTerm],
+    ) -> Result<(), CompileError> {
+        let op = prim_op(name)?;
+        for arg in args {
+            self.compile_expr(state, scope, arg)?;
+        }
+
+        if args.len() == op.arity() {
+            state.emit(Instruction::Prim(op));
+        } else {
+            let function = self.prim_function(name, op, args.len());
+            state.emit(Instruction::Closure(function, args.len()));
+        }
+
+        Ok(())
+    }
+
+    /// Returns a function that takes the next argument of a primitive that
+    /// has already been applied to `applied` arguments, which are captured
+    fn prim_function(&mut self, name: &str, op: PrimOp, applied: usize) -> FunctionId {
+        let key = (name.to_owned(), applied);
+        if let Some(&function) = self.prims.get(&key) {
+            return function;
+        }
+
+        let function = self.reserve_function();
+        self.prims.insert(key, function);
+
+        let mut state = FunctionState::new(1);
+        for capture in 0..applied {
+            state.emit(Instruction::Capture(capture));
+        }
+        state.emit(Instruction::Local(0));
+        if applied + 1 == op.arity() {
+            state.emit(Instruction::Prim(op));
+        } else {
+            let next = self.prim_function(name, op, applied + 1);
+            state.emit(Instruction::Closure(next, applied + 1));
+        }
+        state.emit(Instruction::Return);
+
+        self.functions[function.0] = Some(Function {
+            name: name.to_owned(),
+            code: state.code,
+        });
+
+        function
+    }
+
+    /// Compile a case expression, testing each clause in turn
+    ///
+    /// The value of the head is kept on the stack while the clauses are
+    /// tested, so that binder patterns can refer to it.
+    fn compile_case(
+        &mut self,
+        state: &mut FunctionState,
+        scope: &Scope,
+        head: &RcTerm,
+        clauses: &[(RcPattern, RcTerm)],
+        tail: bool,
+    ) -> Result<(), CompileError> {
+        let env = self.env;
+        let head_slot = state.height;
+        self.compile_expr(state, scope, head)?;
+
+        let mut exits = Vec::with_capacity(clauses.len());
+        let mut exhaustive = false;
+
+        for &(ref pattern, ref body) in clauses {
+            let mut pattern = pattern;
+            while let Pattern::Ann(ref inner, _) = *pattern.inner {
+                pattern = inner;
+            }
+
+            let literal = match *pattern.inner {
+                Pattern::Literal(ref literal) => Some(literal),
+                Pattern::Global(ref free_var, _) => Some(
+                    env.get_literal(free_var)
+                        .ok_or_else(|| CompileError::new("only literal patterns are supported"))?,
+                ),
+                Pattern::Binder(_) => None,
+                _ => return Err(CompileError::new("only literal patterns are supported")),
+            };
+
+            let mut scope = scope.to_vec();
+            let test = match literal {
+                Some(literal) => {
+                    state.emit(Instruction::Local(head_slot));
+                    let test = state.code.len();
+                    state.emit(Instruction::MatchLiteral(literal.clone(), Offset(0)));
+                    Some(test)
+                },
+                None => {
+                    scope.push(Some(Binding::Local(head_slot)));
+                    None
+                },
+            };
+
+            if tail {
+                self.compile_tail(state, &scope, body)?;
+            } else {
+                self.compile_expr(state, &scope, body)?;
+                exits.push(state.code.len());
+                state.emit(Instruction::Jump(Offset(0)));
+            }
+            // The next clause starts with just the head on the stack
+            state.height = head_slot + 1;
+
+            match test {
+                Some(test) => state.patch(test),
+                // Clauses after a binder are never reached
+                None => {
+                    exhaustive = true;
+                    break;
+                },
+            }
+        }
+
+        if !exhaustive {
+            state.emit(Instruction::NoMatch);
+        }
+        if !tail {
+            for exit in exits {
+                state.patch(exit);
+            }
+            state.height = head_slot + 2;
+            state.emit(Instruction::Slide(1));
+        }
+
+        Ok(())
+    }
+}
+
+/// The instruction that loads the value of a variable
+fn load(binding: Binding) -> Instruction {
+    match binding {
+        Binding::Local(slot) => Instruction::Local(slot),
+        Binding::Capture(capture) => Instruction::Capture(capture),
+        Binding::Current => Instruction::Current,
+    }
+}
+
+fn prim_op(name: &str) -> Result<PrimOp, CompileError> {
+    PrimOp::from_import(name)
+        .ok_or_else(|| CompileError::new(format!("unsupported primitive `{}`", name)))
+}
@@ -0,0 +1,11 @@
+//! A bytecode compiler and virtual machine for Pikelet
+//!
+//! This provides a way to run programs that is faster than walking the core
+//! syntax, without needing a native code generator for the host platform.
+//! Programs are compiled from the erased core syntax to a compact stack-based
+//! instruction set, and are then run by a simple virtual machine. The
+//! disassembly of a program can be found by formatting it with `Display`.
+
+pub mod compile;
+pub mod syntax;
+pub mod vm;
@@ -0,0 +1,233 @@
+//! The instruction set of the virtual machine
+//!
+//! Programs are made up of functions, each of which is a sequence of
+//! instructions that operate on a stack of values. Functions take a single
+//! argument, which is found at the bottom of their part of the stack, and
+//! the local variables that they bind are pushed on top of it. Closures carry
+//! the values of the variables that they capture, which are accessed with
+//! `Instruction::Capture`.
+
+use std::fmt;
+
+use pikelet_core::syntax::{Label, Literal};
+
+/// A function, referred to by its position in the program
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FunctionId(pub usize);
+
+impl fmt::Display for FunctionId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "function{}", self.0)
+    }
+}
+
+/// The position of an instruction in the function that it belongs to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Offset(pub usize);
+
+impl fmt::Display for Offset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}", self.0)
+    }
+}
+
+/// Primitive operations, which work on literals
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum PrimOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    ToString,
+    Append,
+}
+
+impl PrimOp {
+    /// Look up the operation that implements a primitive import, for
+    /// example `prim/u8/add`
+    pub fn from_import(name: &str) -> Option<PrimOp> {
+        let mut parts = name.split('/');
+        let (ty, op) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some("prim"), Some(ty), Some(op), None) => (ty, op),
+            (_, _, _, _) => return None,
+        };
+
+        match ty {
+            "bool" | "char" | "string" | "u8" | "u16" | "u32" | "u64" | "i8" | "i16" | "i32"
+            | "i64" | "f32" | "f64" => {},
+            _ => return None,
+        }
+
+        match op {
+            "eq" => Some(PrimOp::Eq),
+            "ne" => Some(PrimOp::Ne),
+            "lt" => Some(PrimOp::Lt),
+            "le" => Some(PrimOp::Le),
+            "gt" => Some(PrimOp::Gt),
+            "ge" => Some(PrimOp::Ge),
+            "add" => Some(PrimOp::Add),
+            "sub" => Some(PrimOp::Sub),
+            "mul" => Some(PrimOp::Mul),
+            "div" => Some(PrimOp::Div),
+            "to-string" => Some(PrimOp::ToString),
+            "append" => Some(PrimOp::Append),
+            _ => None,
+        }
+    }
+
+    /// The number of arguments that the operation takes
+    pub fn arity(self) -> usize {
+        match self {
+            PrimOp::ToString => 1,
+            _ => 2,
+        }
+    }
+}
+
+impl fmt::Display for PrimOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            PrimOp::Eq => "eq",
+            PrimOp::Ne => "ne",
+            PrimOp::Lt => "lt",
+            PrimOp::Le => "le",
+            PrimOp::Gt => "gt",
+            PrimOp::Ge => "ge",
+            PrimOp::Add => "add",
+            PrimOp::Sub => "sub",
+            PrimOp::Mul => "mul",
+            PrimOp::Div => "div",
+            PrimOp::ToString => "to-string",
+            PrimOp::Append => "append",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Instructions
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    /// Push a literal
+    Literal(Literal),
+    /// Push a value that is not needed at runtime, like a type
+    Erased,
+    /// Push a copy of a local variable, counting from the argument of the
+    /// current function
+    Local(usize),
+    /// Push a copy of a variable that was captured by the current closure
+    Capture(usize),
+    /// Push the current closure, for recursive calls
+    Current,
+    /// Remove the given number of values from underneath the top of the
+    /// stack, for leaving the scope of local variables
+    Slide(usize),
+    /// Pop the given number of captured values, and push a closure
+    Closure(FunctionId, usize),
+    /// Pop an argument and a function, and call the function
+    Call,
+    /// Pop an argument and a function, and replace the current call with a
+    /// call to the function
+    TailCall,
+    /// Pop the result of the current function, and return to the caller
+    Return,
+    /// Pop the values of the fields, and push a record
+    Record(Vec<Label>),
+    /// Pop a record, and push the value of one of its fields
+    Project(Label),
+    /// Pop the values of some new fields and a record, and push the record
+    /// with the fields added to the end
+    Extend(Vec<Label>),
+    /// Pop a value, and jump if it is not equal to the literal
+    MatchLiteral(Literal, Offset),
+    /// Jump unconditionally
+    Jump(Offset),
+    /// Report that none of the clauses of a case expression matched
+    NoMatch,
+    /// Pop the arguments of a primitive operation, and push the result
+    Prim(PrimOp),
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Literal(ref literal) => write!(f, "literal {}", literal),
+            Instruction::Erased => write!(f, "erased"),
+            Instruction::Local(index) => write!(f, "local {}", index),
+            Instruction::Capture(index) => write!(f, "capture {}", index),
+            Instruction::Current => write!(f, "current"),
+            Instruction::Slide(count) => write!(f, "slide {}", count),
+            Instruction::Closure(function, count) => write!(f, "closure {} {}", function, count),
+            Instruction::Call => write!(f, "call"),
+            Instruction::TailCall => write!(f, "tail-call"),
+            Instruction::Return => write!(f, "return"),
+            Instruction::Record(ref labels) => write_labels(f, "record", labels),
+            Instruction::Project(ref label) => write!(f, "project {}", label),
+            Instruction::Extend(ref labels) => write_labels(f, "extend", labels),
+            Instruction::MatchLiteral(ref literal, offset) => {
+                write!(f, "match-literal {} {}", literal, offset)
+            },
+            Instruction::Jump(offset) => write!(f, "jump {}", offset),
+            Instruction::NoMatch => write!(f, "no-match"),
+            Instruction::Prim(op) => write!(f, "prim {}", op),
+        }
+    }
+}
+
+fn write_labels(f: &mut fmt::Formatter, name: &str, labels: &[Label]) -> fmt::Result {
+    write!(f, "{}", name)?;
+    for label in labels {
+        write!(f, " {}", label)?;
+    }
+    Ok(())
+}
+
+/// A function, taking a single argument
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// The name of the function, for use in the disassembly
+    pub name: String,
+    pub code: Vec<Instruction>,
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}:", self.name)?;
+        for (offset, instruction) in self.code.iter().enumerate() {
+            writeln!(f, "    {}  {}", Offset(offset), instruction)?;
+        }
+        Ok(())
+    }
+}
+
+/// A program
+///
+/// The first function is the entry point of the program, and is the only one
+/// that does not take an argument.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub functions: Vec<Function>,
+}
+
+impl Program {
+    /// The entry point of the program
+    pub const MAIN: FunctionId = FunctionId(0);
+}
+
+/// Disassemble the program, listing the instructions of each function
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, function) in self.functions.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{} {}", FunctionId(index), function)?;
+        }
+        Ok(())
+    }
+}
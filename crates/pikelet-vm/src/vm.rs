@@ -0,0 +1,400 @@
+//! A virtual machine for running compiled programs
+//!
+//! Values are kept on a single stack, which is shared between the frames of
+//! the functions that are currently being called. Records and closures are
+//! allocated on a heap, and are referred to from the stack by their position
+//! in it. Nothing is ever freed, so long running programs will want to set a
+//! heap limit.
+
+use pikelet_core::syntax::{Label, Literal};
+
+use crate::syntax::{FunctionId, Instruction, Offset, PrimOp, Program};
+
+/// An error produced while running a program
+#[derive(Debug, Clone, PartialEq, failure::Fail)]
+#[fail(display = "{}", message)]
+pub struct VmError {
+    pub message: String,
+}
+
+impl VmError {
+    pub fn new(message: impl Into<String>) -> VmError {
+        VmError {
+            message: message.into(),
+        }
+    }
+}
+
+/// A reference to an object on the heap
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ObjectRef(pub usize);
+
+/// Values that can be stored on the stack
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Literal(Literal),
+    /// Types, and anything else that is not needed at runtime
+    Erased,
+    Object(ObjectRef),
+}
+
+/// Objects that are allocated on the heap
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object {
+    Record(Vec<(Label, Value)>),
+    Closure(FunctionId, Vec<Value>),
+}
+
+/// The state of a function call
+#[derive(Debug, Copy, Clone)]
+struct Frame {
+    function: FunctionId,
+    /// The closure that was called, or `None` for the entry point
+    closure: Option<ObjectRef>,
+    /// The position of the argument on the stack
+    base: usize,
+    pc: usize,
+}
+
+/// The state of a running program
+pub struct Vm<'program> {
+    program: &'program Program,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    heap: Vec<Object>,
+    /// The number of values that the stack can hold
+    stack_limit: Option<usize>,
+    /// The number of objects that the heap can hold
+    heap_limit: Option<usize>,
+}
+
+impl<'program> Vm<'program> {
+    pub fn new(program: &'program Program) -> Vm<'program> {
+        Vm {
+            program,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            heap: Vec::new(),
+            stack_limit: None,
+            heap_limit: None,
+        }
+    }
+
+    /// Report an error if the stack grows past `limit` values
+    pub fn with_stack_limit(mut self, limit: usize) -> Vm<'program> {
+        self.stack_limit = Some(limit);
+        self
+    }
+
+    /// Report an error if more than `limit` objects are allocated
+    pub fn with_heap_limit(mut self, limit: usize) -> Vm<'program> {
+        self.heap_limit = Some(limit);
+        self
+    }
+
+    /// Look up an object on the heap
+    ///
+    /// # Panics
+    ///
+    /// Object references are only valid for the machine that created them.
+    pub fn object(&self, object: ObjectRef) -> &Object {
+        &self.heap[object.0]
+    }
+
+    /// Run the program from its entry point, returning the value that it
+    /// evaluates to
+    pub fn run(&mut self) -> Result<Value, VmError> {
+        self.stack.clear();
+        self.heap.clear();
+        self.frames = vec![Frame {
+            function: Program::MAIN,
+            closure: None,
+            base: 0,
+            pc: 0,
+        }];
+
+        let program = self.program;
+        loop {
+            let frame_index = self.frames.len() - 1;
+            let frame = self.frames[frame_index];
+            let instruction = match program.functions[frame.function.0].code.get(frame.pc) {
+                Some(instruction) => instruction,
+                None => return Err(VmError::new("ran off the end of a function")),
+            };
+            self.frames[frame_index].pc += 1;
+
+            match *instruction {
+                Instruction::Literal(ref literal) => self.push(Value::Literal(literal.clone()))?,
+                Instruction::Erased => self.push(Value::Erased)?,
+                Instruction::Local(slot) => {
+                    let value = self.stack[frame.base + slot].clone();
+                    self.push(value)?;
+                },
+                Instruction::Capture(capture) => {
+                    let value = match frame.closure.map(|closure| &self.heap[closure.0]) {
+                        Some(&Object::Closure(_, ref captures)) => captures[capture].clone(),
+                        _ => return Err(VmError::new("captured variable used outside a closure")),
+                    };
+                    self.push(value)?;
+                },
+                Instruction::Current => match frame.closure {
+                    Some(closure) => self.push(Value::Object(closure))?,
+                    None => return Err(VmError::new("current closure used outside a closure")),
+                },
+                Instruction::Slide(count) => {
+                    let value = self.pop();
+                    let len = self.stack.len();
+                    self.stack.truncate(len - count);
+                    self.push(value)?;
+                },
+                Instruction::Closure(function, count) => {
+                    let captures = self.pop_many(count);
+                    let closure = self.alloc(Object::Closure(function, captures))?;
+                    self.push(closure)?;
+                },
+                Instruction::Call | Instruction::TailCall => {
+                    let arg = self.pop();
+                    let (function, closure) = match self.pop() {
+                        Value::Object(closure) => match self.heap[closure.0] {
+                            Object::Closure(function, _) => (function, closure),
+                            Object::Record(_) => {
+                                return Err(VmError::new("argument applied to non function"));
+                            },
+                        },
+                        // Type constructors can still be applied in erased
+                        // terms
+                        Value::Erased => {
+                            self.push(Value::Erased)?;
+                            if let Instruction::TailCall = *instruction {
+                                if let Some(value) = self.return_value()? {
+                                    return Ok(value);
+                                }
+                            }
+                            continue;
+                        },
+                        Value::Literal(_) => {
+                            return Err(VmError::new("argument applied to non function"));
+                        },
+                    };
+
+                    let base = match *instruction {
+                        Instruction::TailCall => {
+                            self.stack.truncate(frame.base);
+                            self.frames.pop();
+                            frame.base
+                        },
+                        _ => self.stack.len(),
+                    };
+                    self.push(arg)?;
+                    self.frames.push(Frame {
+                        function,
+                        closure: Some(closure),
+                        base,
+                        pc: 0,
+                    });
+                },
+                Instruction::Return => {
+                    if let Some(value) = self.return_value()? {
+                        return Ok(value);
+                    }
+                },
+                Instruction::Record(ref labels) => {
+                    let values = self.pop_many(labels.len());
+                    let fields = Iterator::zip(labels.iter().cloned(), values).collect();
+                    let record = self.alloc(Object::Record(fields))?;
+                    self.push(record)?;
+                },
+                Instruction::Project(ref label) => {
+                    let record = self.pop();
+                    let value = self.record_proj(&record, label)?;
+                    self.push(value)?;
+                },
+                Instruction::Extend(ref labels) => {
+                    let values = self.pop_many(labels.len());
+                    let mut fields = match self.pop() {
+                        Value::Object(record) => match self.heap[record.0] {
+                            Object::Record(ref fields) => fields.clone(),
+                            Object::Closure(_, _) => {
+                                return Err(VmError::new("extended a value that is not a record"));
+                            },
+                        },
+                        Value::Literal(_) | Value::Erased => {
+                            return Err(VmError::new("extended a value that is not a record"));
+                        },
+                    };
+                    fields.extend(Iterator::zip(labels.iter().cloned(), values));
+                    let record = self.alloc(Object::Record(fields))?;
+                    self.push(record)?;
+                },
+                Instruction::MatchLiteral(ref literal, Offset(target)) => match self.pop() {
+                    Value::Literal(ref value) if value == literal => {},
+                    _ => self.frames[frame_index].pc = target,
+                },
+                Instruction::Jump(Offset(target)) => self.frames[frame_index].pc = target,
+                Instruction::NoMatch => return Err(VmError::new("no patterns applicable")),
+                Instruction::Prim(op) => {
+                    let args = self.pop_many(op.arity());
+                    let args = args
+                        .iter()
+                        .map(|arg| match *arg {
+                            Value::Literal(ref literal) => Ok(literal),
+                            Value::Erased | Value::Object(_) => Err(VmError::new(format!(
+                                "primitive `{}` applied to a value that is not a literal",
+                                op
+                            ))),
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let result = prim(op, &args)?;
+                    self.push(Value::Literal(result))?;
+                },
+            }
+        }
+    }
+
+    fn push(&mut self, value: Value) -> Result<(), VmError> {
+        match self.stack_limit {
+            Some(limit) if self.stack.len() >= limit => Err(VmError::new(format!(
+                "stack overflow: exceeded the stack limit of {}",
+                limit
+            ))),
+            Some(_) | None => {
+                self.stack.push(value);
+                Ok(())
+            },
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("popped from an empty stack")
+    }
+
+    /// Pop the given number of values, in the order that they were pushed
+    fn pop_many(&mut self, count: usize) -> Vec<Value> {
+        let len = self.stack.len();
+        self.stack.split_off(len - count)
+    }
+
+    fn alloc(&mut self, object: Object) -> Result<Value, VmError> {
+        match self.heap_limit {
+            Some(limit) if self.heap.len() >= limit => Err(VmError::new(format!(
+                "out of memory: exceeded the heap limit of {}",
+                limit
+            ))),
+            Some(_) | None => {
+                self.heap.push(object);
+                Ok(Value::Object(ObjectRef(self.heap.len() - 1)))
+            },
+        }
+    }
+
+    /// Return the value on the top of the stack from the current function,
+    /// returning it from the program if this was the entry point
+    fn return_value(&mut self) -> Result<Option<Value>, VmError> {
+        let value = self.pop();
+        let frame = self.frames.pop().expect("returned without a frame");
+        self.stack.truncate(frame.base);
+
+        if self.frames.is_empty() {
+            Ok(Some(value))
+        } else {
+            self.push(value)?;
+            Ok(None)
+        }
+    }
+
+    /// Project on a field of a record
+    fn record_proj(&self, record: &Value, label: &Label) -> Result<Value, VmError> {
+        if let Value::Object(record) = *record {
+            if let Object::Record(ref fields) = self.heap[record.0] {
+                for &(ref current_label, ref value) in fields {
+                    if current_label == label {
+                        return Ok(value.clone());
+                    }
+                }
+            }
+        }
+
+        Err(VmError::new(format!(
+            "projected on non existent field `{}`",
+            label
+        )))
+    }
+}
+
+/// Apply an arithmetic operator to two literals of the same type, wrapping
+/// around if integer arithmetic overflows
+macro_rules! arithmetic {
+    ($op:expr, $x:expr, $y:expr, $wrapping:ident, $operator:tt) => {
+        match ($x, $y) {
+            (&Literal::U8(x), &Literal::U8(y)) => Literal::U8(x.$wrapping(y)),
+            (&Literal::U16(x), &Literal::U16(y)) => Literal::U16(x.$wrapping(y)),
+            (&Literal::U32(x), &Literal::U32(y)) => Literal::U32(x.$wrapping(y)),
+            (&Literal::U64(x), &Literal::U64(y)) => Literal::U64(x.$wrapping(y)),
+            (&Literal::S8(x), &Literal::S8(y)) => Literal::S8(x.$wrapping(y)),
+            (&Literal::S16(x), &Literal::S16(y)) => Literal::S16(x.$wrapping(y)),
+            (&Literal::S32(x), &Literal::S32(y)) => Literal::S32(x.$wrapping(y)),
+            (&Literal::S64(x), &Literal::S64(y)) => Literal::S64(x.$wrapping(y)),
+            (&Literal::F32(x), &Literal::F32(y)) => Literal::F32(x $operator y),
+            (&Literal::F64(x), &Literal::F64(y)) => Literal::F64(x $operator y),
+            (_, _) => return Err(mismatch($op)),
+        }
+    };
+}
+
+/// Apply a primitive operation to some literals
+///
+/// Integer arithmetic wraps around on overflow, and reports an error on
+/// division by zero rather than panicking.
+fn prim(op: PrimOp, args: &[&Literal]) -> Result<Literal, VmError> {
+    let result = match (op, args) {
+        (PrimOp::Eq, &[x, y]) => Literal::Bool(x == y),
+        (PrimOp::Ne, &[x, y]) => Literal::Bool(x != y),
+        (PrimOp::Lt, &[x, y]) => Literal::Bool(x < y),
+        (PrimOp::Le, &[x, y]) => Literal::Bool(x <= y),
+        (PrimOp::Gt, &[x, y]) => Literal::Bool(x > y),
+        (PrimOp::Ge, &[x, y]) => Literal::Bool(x >= y),
+        (PrimOp::Add, &[x, y]) => arithmetic!(op, x, y, wrapping_add, +),
+        (PrimOp::Sub, &[x, y]) => arithmetic!(op, x, y, wrapping_sub, -),
+        (PrimOp::Mul, &[x, y]) => arithmetic!(op, x, y, wrapping_mul, *),
+        (PrimOp::Div, &[_, y]) if is_zero(y) => {
+            return Err(VmError::new(format!("primitive `{}` divided by zero", op)));
+        },
+        (PrimOp::Div, &[x, y]) => arithmetic!(op, x, y, wrapping_div, /),
+        (PrimOp::ToString, &[x]) => Literal::String(match *x {
+            Literal::Char(value) => value.to_string(),
+            Literal::U8(value) => value.to_string(),
+            Literal::U16(value) => value.to_string(),
+            Literal::U32(value) => value.to_string(),
+            Literal::U64(value) => value.to_string(),
+            Literal::S8(value) => value.to_string(),
+            Literal::S16(value) => value.to_string(),
+            Literal::S32(value) => value.to_string(),
+            Literal::S64(value) => value.to_string(),
+            Literal::F32(value) => value.to_string(),
+            Literal::F64(value) => value.to_string(),
+            Literal::Bool(_) | Literal::String(_) => return Err(mismatch(op)),
+        }),
+        (PrimOp::Append, &[&Literal::String(ref x), &Literal::String(ref y)]) => {
+            Literal::String(x.clone() + y)
+        },
+        (_, _) => return Err(mismatch(op)),
+    };
+
+    Ok(result)
+}
+
+/// Returns `true` if the literal is an integer that is equal to zero
+fn is_zero(literal: &Literal) -> bool {
+    match *literal {
+        Literal::U8(0) | Literal::U16(0) | Literal::U32(0) | Literal::U64(0) => true,
+        Literal::S8(0) | Literal::S16(0) | Literal::S32(0) | Literal::S64(0) => true,
+        _ => false,
+    }
+}
+
+fn mismatch(op: PrimOp) -> VmError {
+    VmError::new(format!(
+        "primitive `{}` applied to literals of the wrong type",
+        op
+    ))
+}
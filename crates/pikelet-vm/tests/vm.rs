@@ -0,0 +1,348 @@
+use pretty_assertions::assert_eq;
+
+use pikelet_concrete::elaborate::Context;
+use pikelet_core::syntax::Literal;
+use pikelet_test_support::{parse_infer_term, write_golden, Observation};
+use pikelet_vm::compile::{self, CompileError};
+use pikelet_vm::syntax::Program;
+use pikelet_vm::vm::{Object, Value, Vm, VmError};
+
+fn golden(filename: &str, literal: &str) {
+    let context = Context::default();
+    let program = compile_program(&context, literal);
+
+    write_golden(filename, &program);
+}
+
+fn compile_program(context: &Context, src: &str) -> Program {
+    let term = parse_infer_term(context, src);
+    match compile::compile_program(context, &term) {
+        Ok(program) => program,
+        Err(error) => panic!("compile error: {}", error),
+    }
+}
+
+/// Check that running the compiled program gives the same value as the
+/// evaluator does
+fn assert_agrees(src: &str) {
+    pikelet_test_support::assert_agrees(src, |context, term| {
+        let program = match compile::compile_program(context, term) {
+            Ok(program) => program,
+            Err(error) => panic!("compile error: {}", error),
+        };
+
+        let mut vm = Vm::new(&program);
+        match vm.run() {
+            Ok(value) => observe(&vm, &value),
+            Err(error) => panic!("vm error: {}", error),
+        }
+    });
+}
+
+fn observe(vm: &Vm<'_>, value: &Value) -> Observation {
+    match *value {
+        Value::Literal(ref literal) => Observation::Literal(literal.clone()),
+        Value::Erased => Observation::Erased,
+        Value::Object(object) => match *vm.object(object) {
+            Object::Record(ref fields) => Observation::record(
+                fields
+                    .iter()
+                    .map(|&(ref label, ref value)| (label.clone(), observe(vm, value))),
+            ),
+            Object::Closure(_, _) => Observation::Function,
+        },
+    }
+}
+
+#[test]
+fn capture_let() {
+    golden(
+        "capture_let",
+        r#"
+            let
+                add = import "prim/u64/add";
+                x = add 1 2;
+            in
+                \(y : U64) => add x y
+        "#,
+    );
+}
+
+#[test]
+fn case_literal() {
+    golden(
+        "case_literal",
+        r#"
+            \(n : U32) => case n {
+                0 => 'z';
+                1 => 'o';
+                _ => 'm';
+            }
+        "#,
+    );
+}
+
+#[test]
+fn fix() {
+    golden(
+        "fix",
+        r#"
+            let
+                partial fact : U64 -> U64;
+                fact n =
+                    if (import "prim/u64/eq") n 0 then 1 else
+                        (import "prim/u64/mul") n (fact ((import "prim/u64/sub") n 1));
+            in
+                fact
+        "#,
+    );
+}
+
+#[test]
+fn records() {
+    golden(
+        "records",
+        r#"
+            let
+                Point = Record { x : S32; y : S32 };
+
+                origin : Point;
+                origin = record { x = 0; y = 0 };
+            in
+                record {
+                    Point;
+                    origin;
+                    sum = (let x = origin.x; in (import "prim/i32/add") x origin.y);
+                }
+        "#,
+    );
+}
+
+#[test]
+fn variant_unsupported() {
+    let context = Context::default();
+    let term = parse_infer_term(
+        &context,
+        r#"variant some "hello" : Variant { some : String; none : Record {} }"#,
+    );
+
+    assert_eq!(
+        compile::compile_program(&context, &term),
+        Err(CompileError::new("variants are not yet supported")),
+    );
+}
+
+#[test]
+fn agrees_arithmetic() {
+    assert_agrees(
+        r#"
+            record {
+                add-u8 = (import "prim/u8/add") 200 100 : U8;
+                sub-s8 = (import "prim/i8/sub") ((import "prim/i8/sub") 0 100) 100 : S8;
+                mul-u16 = (import "prim/u16/mul") 65535 65535 : U16;
+                div-s8 = (import "prim/i8/div") ((import "prim/i8/sub") ((import "prim/i8/sub") 0 127) 1) ((import "prim/i8/sub") 0 1) : S8;
+                div-s32 = (import "prim/i32/div") ((import "prim/i32/sub") 1 8) 2 : S32;
+                mul-u64 = (import "prim/u64/mul") 4294967296 3 : U64;
+                div-f32 = (import "prim/f32/div") 1.0 3.0 : F32;
+                sub-f64 = (import "prim/f64/sub") 0.1 0.3 : F64;
+                lt-f64 = (import "prim/f64/lt") 0.1 0.3;
+                ge-u32 = (import "prim/u32/ge") 3 3;
+                ne-char = (import "prim/char/ne") 'a' 'b';
+                eq-string = (import "prim/string/eq") "hello" "hello";
+                greeting = (import "prim/string/append") "hello, " ((import "prim/u8/to-string") 42);
+            }
+        "#,
+    );
+}
+
+#[test]
+fn agrees_closures() {
+    assert_agrees(
+        r#"
+            let
+                compose : (U64 -> U64) -> (U64 -> U64) -> U64 -> U64;
+                compose f g x = f (g x);
+
+                add1 = (import "prim/u64/add") 1;
+                double = \(x : U64) => (import "prim/u64/mul") x 2;
+                add = import "prim/u64/add";
+            in
+                record {
+                    add1;
+                    composed = compose add1 double 20;
+                    curried = (compose (add 3)) (add 4) 5;
+                    adder = add 10;
+                }
+        "#,
+    );
+}
+
+#[test]
+fn agrees_case_literal() {
+    assert_agrees(
+        r#"
+            let
+                describe : U32 -> Char;
+                describe n = case n {
+                    0 => 'z';
+                    1 => 'o';
+                    _ => 'm';
+                };
+
+                not : Bool -> Bool;
+                not b = if b then false else true;
+            in
+                record {
+                    zero = describe 0;
+                    one = describe 1;
+                    many = describe 7;
+                    no = not true;
+                    sum = (import "prim/u32/add") 1 (case (5 : U32) { 4 => 10; n => n });
+                }
+        "#,
+    );
+}
+
+#[test]
+fn agrees_recursive() {
+    assert_agrees(
+        r#"
+            let
+                partial fact : U64 -> U64;
+                fact n =
+                    if (import "prim/u64/eq") n 0 then 1 else
+                        (import "prim/u64/mul") n (fact ((import "prim/u64/sub") n 1));
+
+                partial is-even : U64 -> Bool;
+                partial is-odd : U64 -> Bool;
+
+                is-even n =
+                    if (import "prim/u64/eq") n 0 then true else is-odd ((import "prim/u64/sub") n 1);
+                is-odd n =
+                    if (import "prim/u64/eq") n 0 then false else is-even ((import "prim/u64/sub") n 1);
+            in
+                record {
+                    fact-20 = fact 20;
+                    ten-is-even = is-even 10;
+                    seven-is-even = is-even 7;
+                }
+        "#,
+    );
+}
+
+#[test]
+fn agrees_records() {
+    assert_agrees(
+        r#"
+            let
+                Point = Record { x : S32; y : S32 };
+                Line = Record { start : Point; end : Point };
+
+                point : S32 -> S32 -> Point;
+                point x y = record { x = x; y = y };
+
+                swap : Line -> Line;
+                swap line = record { start = line.end; end = line.start };
+            in
+                let
+                    start = point 1 2;
+                    end = point 5 ((import "prim/i32/sub") 0 3);
+                in record {
+                    Point;
+                    swapped = swap (record { start = start; end = end });
+                    x = (swap (record { start = start; end = end })).start.x;
+                    empty = record {};
+                }
+        "#,
+    );
+}
+
+#[test]
+fn tail_calls() {
+    let context = Context::default();
+    let program = compile_program(
+        &context,
+        r#"
+            let
+                partial count-down : U64 -> U64;
+                count-down n = case n {
+                    0 => 42;
+                    n => count-down ((import "prim/u64/sub") n 1);
+                };
+            in
+                count-down 100000
+        "#,
+    );
+
+    assert_eq!(
+        Vm::new(&program).with_stack_limit(16).run(),
+        Ok(Value::Literal(Literal::U64(42))),
+    );
+}
+
+#[test]
+fn stack_limit() {
+    let context = Context::default();
+    let program = compile_program(
+        &context,
+        r#"
+            let
+                partial sum : U64 -> U64;
+                sum n = case n {
+                    0 => 0;
+                    n => (import "prim/u64/add") n (sum ((import "prim/u64/sub") n 1));
+                };
+            in
+                sum 100000
+        "#,
+    );
+
+    assert_eq!(
+        Vm::new(&program).with_stack_limit(1000).run(),
+        Err(VmError::new(
+            "stack overflow: exceeded the stack limit of 1000"
+        )),
+    );
+}
+
+#[test]
+fn heap_limit() {
+    let context = Context::default();
+    let program = compile_program(
+        &context,
+        r#"
+            let
+                partial count-down : U64 -> U64;
+                count-down n = case (record { n = n }).n {
+                    0 => 0;
+                    n => count-down ((import "prim/u64/sub") n 1);
+                };
+            in
+                count-down 100
+        "#,
+    );
+
+    assert_eq!(
+        Vm::new(&program).with_heap_limit(64).run(),
+        Err(VmError::new("out of memory: exceeded the heap limit of 64")),
+    );
+}
+
+#[test]
+fn arithmetic_overflow() {
+    let context = Context::default();
+    let program = compile_program(&context, r#"(import "prim/u8/add") 200 100 : U8"#);
+
+    assert_eq!(Vm::new(&program).run(), Ok(Value::Literal(Literal::U8(44))));
+}
+
+#[test]
+fn divide_by_zero() {
+    let context = Context::default();
+    let program = compile_program(&context, r#"(import "prim/u32/div") 1 0 : U32"#);
+
+    assert_eq!(
+        Vm::new(&program).run(),
+        Err(VmError::new("primitive `div` divided by zero")),
+    );
+}
@@ -0,0 +1,466 @@
+//! Compilation of first-order programs to WebAssembly modules
+//!
+//! Each function of the program is compiled to a WebAssembly function, and
+//! the exports of the program are exported from the module by name. The local
+//! variables of the functions become WebAssembly locals, with their
+//! parameters coming first.
+//!
+//! Records are allocated in linear memory by a bump allocator, which is also
+//! exported as `alloc`, so that the host can pass records to the exported
+//! functions. The memory is never freed.
+
+use parity_wasm::builder;
+use parity_wasm::elements::{BlockType, Instruction, Instructions, Local, ValueType};
+
+use pikelet_core::nbe::Env;
+use pikelet_core::syntax::core::RcTerm;
+use pikelet_core::syntax::Literal;
+use pikelet_mono::lower::LowerError;
+use pikelet_mono::syntax::{Expr, Function, FunctionId, PrimOp, Program, Repr};
+
+use crate::repr::{self, RecordLayout};
+
+/// The index of the allocation function
+const ALLOC: u32 = 0;
+
+/// The index of the global that points to the next free address in memory
+const HEAP_POINTER: u32 = 0;
+
+/// The alignment that all allocations are rounded up to, in bytes
+const ALLOC_ALIGN: i32 = 8;
+
+/// The size of a page of linear memory, as a power of two
+const PAGE_SIZE_LOG2: i32 = 16;
+
+/// An error produced while compiling a module
+#[derive(Debug, Clone, PartialEq, failure::Fail)]
+#[fail(display = "{}", message)]
+pub struct CompileError {
+    pub message: String,
+}
+
+impl CompileError {
+    pub fn new(message: impl Into<String>) -> CompileError {
+        CompileError {
+            message: message.into(),
+        }
+    }
+}
+
+impl From<LowerError> for CompileError {
+    fn from(src: LowerError) -> CompileError {
+        CompileError::new(src.message)
+    }
+}
+
+/// Compile an elaborated file to the bytes of a WebAssembly module, going by
+/// way of the first-order representation
+pub fn compile_module(env: &dyn Env, term: &RcTerm) -> Result<Vec<u8>, CompileError> {
+    let program = pikelet_mono::lower::lower_module(env, term)?;
+    compile_program(&program)
+}
+
+/// Compile a first-order program to the bytes of a WebAssembly module
+pub fn compile_program(program: &Program) -> Result<Vec<u8>, CompileError> {
+    let mut module = builder::module()
+        .memory()
+        .with_min(1)
+        .build()
+        .global()
+        .with_type(ValueType::I32)
+        .mutable()
+        .init_expr(Instruction::I32Const(0))
+        .build()
+        .export()
+        .field("memory")
+        .internal()
+        .memory(0)
+        .build();
+
+    let alloc = alloc_function();
+    module = module.with_function(alloc);
+    module = module
+        .export()
+        .field("alloc")
+        .internal()
+        .func(ALLOC)
+        .build();
+
+    for function in &program.functions {
+        module = module.with_function(compile_function(function)?);
+    }
+
+    for &(ref label, id) in &program.exports {
+        module = module
+            .export()
+            .field(&label.0)
+            .internal()
+            .func(function_index(id))
+            .build();
+    }
+
+    parity_wasm::serialize(module.build()).map_err(|error| CompileError::new(error.to_string()))
+}
+
+/// The index of a function of the program in the module, which comes after
+/// the allocation function
+fn function_index(id: FunctionId) -> u32 {
+    id.0 as u32 + 1
+}
+
+fn compile_function(function: &Function) -> Result<builder::FunctionDefinition, CompileError> {
+    let mut compiler = FunctionCompiler {
+        reprs: &function.locals,
+        temporaries: Vec::new(),
+        instructions: Vec::new(),
+    };
+    compiler.compile_expr(&function.body)?;
+    compiler.emit(Instruction::End);
+
+    let params = function.params().iter().map(repr::value_type).collect();
+    let locals = function.locals[function.param_count..]
+        .iter()
+        .map(repr::value_type)
+        .chain(compiler.temporaries)
+        .map(|value_type| Local::new(1, value_type))
+        .collect();
+
+    Ok(builder::function()
+        .signature()
+        .with_params(params)
+        .with_return_type(Some(repr::value_type(&function.result)))
+        .build()
+        .body()
+        .with_locals(locals)
+        .with_instructions(Instructions::new(compiler.instructions))
+        .build()
+        .build())
+}
+
+/// Builds up the instructions of a function
+struct FunctionCompiler<'a> {
+    /// The representations of the local variables of the function
+    reprs: &'a [Repr],
+    /// Locals that hold temporary values, which come after the local
+    /// variables of the function
+    temporaries: Vec<ValueType>,
+    instructions: Vec<Instruction>,
+}
+
+impl<'a> FunctionCompiler<'a> {
+    fn emit(&mut self, instruction: Instruction) {
+        self.instructions.push(instruction);
+    }
+
+    fn new_temporary(&mut self, value_type: ValueType) -> u32 {
+        let index = self.reprs.len() + self.temporaries.len();
+        self.temporaries.push(value_type);
+        index as u32
+    }
+
+    /// Compile an expression, leaving its value on the stack
+    fn compile_expr(&mut self, expr: &Expr) -> Result<(), CompileError> {
+        match *expr {
+            Expr::Literal(ref literal) => self.compile_literal(literal),
+            Expr::Local(local, _) => {
+                self.emit(Instruction::GetLocal(local.0 as u32));
+                Ok(())
+            },
+            Expr::Call(id, ref args, _) => {
+                for arg in args {
+                    self.compile_expr(arg)?;
+                }
+                self.emit(Instruction::Call(function_index(id)));
+                Ok(())
+            },
+            Expr::Prim(op, ref repr, ref args) => {
+                for arg in args {
+                    self.compile_expr(arg)?;
+                }
+                self.compile_prim(op, repr);
+                Ok(())
+            },
+            Expr::RecordIntro(ref fields) => {
+                let reprs = fields
+                    .iter()
+                    .map(|&(ref label, ref expr)| (label.clone(), expr.repr()))
+                    .collect::<Vec<_>>();
+                let layout = RecordLayout::new(&reprs);
+                let record = self.new_temporary(ValueType::I32);
+
+                self.emit(Instruction::I32Const(layout.size as i32));
+                self.emit(Instruction::Call(ALLOC));
+                self.emit(Instruction::SetLocal(record));
+
+                for ((&(_, ref expr), &(_, ref repr)), &offset) in
+                    fields.iter().zip(&reprs).zip(&layout.offsets)
+                {
+                    self.emit(Instruction::GetLocal(record));
+                    self.compile_expr(expr)?;
+                    self.emit(store(repr, offset));
+                }
+
+                self.emit(Instruction::GetLocal(record));
+                Ok(())
+            },
+            Expr::RecordProj(ref expr, ref label, _) => {
+                let fields = match expr.repr() {
+                    Repr::Record(fields) => fields,
+                    repr => {
+                        return Err(CompileError::new(format!(
+                            "projected on a non-record: {}",
+                            repr,
+                        )));
+                    },
+                };
+                let layout = RecordLayout::new(&fields);
+                match layout.field(&fields, label) {
+                    Some((offset, repr)) => {
+                        self.compile_expr(expr)?;
+                        self.emit(load(repr, offset));
+                        Ok(())
+                    },
+                    None => Err(CompileError::new(format!("no field named `{}`", label))),
+                }
+            },
+            Expr::Let(local, ref expr, ref body) => {
+                self.compile_expr(expr)?;
+                self.emit(Instruction::SetLocal(local.0 as u32));
+                self.compile_expr(body)
+            },
+            Expr::Case(local, ref clauses, ref default, ref repr) => {
+                let block_type = BlockType::Value(repr::value_type(repr));
+                let eq = match repr::value_type(&self.reprs[local.0]) {
+                    ValueType::I32 => Instruction::I32Eq,
+                    ValueType::I64 => Instruction::I64Eq,
+                    ValueType::F32 => Instruction::F32Eq,
+                    ValueType::F64 => Instruction::F64Eq,
+                };
+
+                // Compile the clauses to a chain of `if`s
+                for &(ref literal, ref body) in clauses {
+                    self.emit(Instruction::GetLocal(local.0 as u32));
+                    self.compile_literal(literal)?;
+                    self.emit(eq.clone());
+                    self.emit(Instruction::If(block_type));
+                    self.compile_expr(body)?;
+                    self.emit(Instruction::Else);
+                }
+                match *default {
+                    Some(ref body) => self.compile_expr(body)?,
+                    None => self.emit(Instruction::Unreachable),
+                }
+                for _ in clauses {
+                    self.emit(Instruction::End);
+                }
+
+                Ok(())
+            },
+        }
+    }
+
+    fn compile_literal(&mut self, literal: &Literal) -> Result<(), CompileError> {
+        let instruction = match *literal {
+            Literal::Bool(value) => Instruction::I32Const(value as i32),
+            Literal::Char(value) => Instruction::I32Const(value as i32),
+            Literal::U8(value) => Instruction::I32Const(i32::from(value)),
+            Literal::U16(value) => Instruction::I32Const(i32::from(value)),
+            Literal::U32(value) => Instruction::I32Const(value as i32),
+            Literal::U64(value) => Instruction::I64Const(value as i64),
+            Literal::S8(value) => Instruction::I32Const(i32::from(value)),
+            Literal::S16(value) => Instruction::I32Const(i32::from(value)),
+            Literal::S32(value) => Instruction::I32Const(value),
+            Literal::S64(value) => Instruction::I64Const(value),
+            Literal::F32(value) => Instruction::F32Const(value.to_bits()),
+            Literal::F64(value) => Instruction::F64Const(value.to_bits()),
+            Literal::String(_) => return Err(CompileError::new("strings are not yet supported")),
+        };
+
+        self.emit(instruction);
+        Ok(())
+    }
+
+    /// Compile a primitive operation, whose operands are on the stack
+    fn compile_prim(&mut self, op: PrimOp, repr: &Repr) {
+        use self::Instruction::*;
+
+        let signed = repr.is_signed();
+        if signed && op == PrimOp::Div {
+            self.signed_div(repr::value_type(repr));
+            self.wrap_int(repr);
+            return;
+        }
+
+        let (instruction, result) = match (repr::value_type(repr), op) {
+            (ValueType::I32, PrimOp::Eq) => (I32Eq, Repr::Bool),
+            (ValueType::I32, PrimOp::Ne) => (I32Ne, Repr::Bool),
+            (ValueType::I32, PrimOp::Lt) => (if signed { I32LtS } else { I32LtU }, Repr::Bool),
+            (ValueType::I32, PrimOp::Le) => (if signed { I32LeS } else { I32LeU }, Repr::Bool),
+            (ValueType::I32, PrimOp::Gt) => (if signed { I32GtS } else { I32GtU }, Repr::Bool),
+            (ValueType::I32, PrimOp::Ge) => (if signed { I32GeS } else { I32GeU }, Repr::Bool),
+            (ValueType::I32, PrimOp::Add) => (I32Add, repr.clone()),
+            (ValueType::I32, PrimOp::Sub) => (I32Sub, repr.clone()),
+            (ValueType::I32, PrimOp::Mul) => (I32Mul, repr.clone()),
+            (ValueType::I32, PrimOp::Div) => (I32DivU, repr.clone()),
+
+            (ValueType::I64, PrimOp::Eq) => (I64Eq, Repr::Bool),
+            (ValueType::I64, PrimOp::Ne) => (I64Ne, Repr::Bool),
+            (ValueType::I64, PrimOp::Lt) => (if signed { I64LtS } else { I64LtU }, Repr::Bool),
+            (ValueType::I64, PrimOp::Le) => (if signed { I64LeS } else { I64LeU }, Repr::Bool),
+            (ValueType::I64, PrimOp::Gt) => (if signed { I64GtS } else { I64GtU }, Repr::Bool),
+            (ValueType::I64, PrimOp::Ge) => (if signed { I64GeS } else { I64GeU }, Repr::Bool),
+            (ValueType::I64, PrimOp::Add) => (I64Add, repr.clone()),
+            (ValueType::I64, PrimOp::Sub) => (I64Sub, repr.clone()),
+            (ValueType::I64, PrimOp::Mul) => (I64Mul, repr.clone()),
+            (ValueType::I64, PrimOp::Div) => (I64DivU, repr.clone()),
+
+            (ValueType::F32, PrimOp::Eq) => (F32Eq, Repr::Bool),
+            (ValueType::F32, PrimOp::Ne) => (F32Ne, Repr::Bool),
+            (ValueType::F32, PrimOp::Lt) => (F32Lt, Repr::Bool),
+            (ValueType::F32, PrimOp::Le) => (F32Le, Repr::Bool),
+            (ValueType::F32, PrimOp::Gt) => (F32Gt, Repr::Bool),
+            (ValueType::F32, PrimOp::Ge) => (F32Ge, Repr::Bool),
+            (ValueType::F32, PrimOp::Add) => (F32Add, repr.clone()),
+            (ValueType::F32, PrimOp::Sub) => (F32Sub, repr.clone()),
+            (ValueType::F32, PrimOp::Mul) => (F32Mul, repr.clone()),
+            (ValueType::F32, PrimOp::Div) => (F32Div, repr.clone()),
+
+            (ValueType::F64, PrimOp::Eq) => (F64Eq, Repr::Bool),
+            (ValueType::F64, PrimOp::Ne) => (F64Ne, Repr::Bool),
+            (ValueType::F64, PrimOp::Lt) => (F64Lt, Repr::Bool),
+            (ValueType::F64, PrimOp::Le) => (F64Le, Repr::Bool),
+            (ValueType::F64, PrimOp::Gt) => (F64Gt, Repr::Bool),
+            (ValueType::F64, PrimOp::Ge) => (F64Ge, Repr::Bool),
+            (ValueType::F64, PrimOp::Add) => (F64Add, repr.clone()),
+            (ValueType::F64, PrimOp::Sub) => (F64Sub, repr.clone()),
+            (ValueType::F64, PrimOp::Mul) => (F64Mul, repr.clone()),
+            (ValueType::F64, PrimOp::Div) => (F64Div, repr.clone()),
+        };
+
+        self.emit(instruction);
+        self.wrap_int(&result);
+    }
+
+    /// Divide two signed integers that are on the stack, wrapping around when
+    /// the smallest integer is divided by `-1` rather than trapping like
+    /// `div_s` does
+    fn signed_div(&mut self, value_type: ValueType) {
+        use self::Instruction::*;
+
+        let x = self.new_temporary(value_type);
+        let y = self.new_temporary(value_type);
+        let (zero, minus_one, eq, sub, div) = match value_type {
+            ValueType::I64 => (I64Const(0), I64Const(-1), I64Eq, I64Sub, I64DivS),
+            _ => (I32Const(0), I32Const(-1), I32Eq, I32Sub, I32DivS),
+        };
+
+        self.instructions.extend(vec![
+            SetLocal(y),
+            SetLocal(x),
+            GetLocal(y),
+            minus_one,
+            eq,
+            If(BlockType::Value(value_type)),
+            // Negate, which wraps around for the smallest integer
+            zero,
+            GetLocal(x),
+            sub,
+            Else,
+            GetLocal(x),
+            GetLocal(y),
+            div,
+            End,
+        ]);
+    }
+
+    /// Bring the result of an arithmetic operation back into the range of
+    /// integers that are smaller than 32 bits
+    fn wrap_int(&mut self, repr: &Repr) {
+        use self::Instruction::*;
+
+        match *repr {
+            Repr::U8 => self.instructions.extend(vec![I32Const(0xff), I32And]),
+            Repr::U16 => self.instructions.extend(vec![I32Const(0xffff), I32And]),
+            Repr::S8 => self
+                .instructions
+                .extend(vec![I32Const(24), I32Shl, I32Const(24), I32ShrS]),
+            Repr::S16 => {
+                self.instructions
+                    .extend(vec![I32Const(16), I32Shl, I32Const(16), I32ShrS])
+            },
+            _ => {},
+        }
+    }
+}
+
+/// A function that allocates the given number of bytes, growing the memory
+/// if needed
+fn alloc_function() -> builder::FunctionDefinition {
+    use self::Instruction::*;
+
+    let size = 0;
+    let pointer = 1;
+
+    builder::function()
+        .signature()
+        .with_param(ValueType::I32)
+        .with_return_type(Some(ValueType::I32))
+        .build()
+        .body()
+        .with_locals(vec![Local::new(1, ValueType::I32)])
+        .with_instructions(Instructions::new(vec![
+            GetGlobal(HEAP_POINTER),
+            SetLocal(pointer),
+            // Bump the heap pointer, keeping it aligned
+            GetGlobal(HEAP_POINTER),
+            GetLocal(size),
+            I32Const(ALLOC_ALIGN - 1),
+            I32Add,
+            I32Const(-ALLOC_ALIGN),
+            I32And,
+            I32Add,
+            SetGlobal(HEAP_POINTER),
+            // Grow the memory if the heap pointer is now past the end of it
+            Block(BlockType::NoResult),
+            GetGlobal(HEAP_POINTER),
+            CurrentMemory(0),
+            I32Const(PAGE_SIZE_LOG2),
+            I32Shl,
+            I32LeU,
+            BrIf(0),
+            GetGlobal(HEAP_POINTER),
+            CurrentMemory(0),
+            I32Const(PAGE_SIZE_LOG2),
+            I32Shl,
+            I32Sub,
+            I32Const((1 << PAGE_SIZE_LOG2) - 1),
+            I32Add,
+            I32Const(PAGE_SIZE_LOG2),
+            I32ShrU,
+            GrowMemory(0),
+            Drop,
+            End,
+            GetLocal(pointer),
+            End,
+        ]))
+        .build()
+        .build()
+}
+
+fn load(repr: &Repr, offset: u32) -> Instruction {
+    match repr::value_type(repr) {
+        ValueType::I32 => Instruction::I32Load(2, offset),
+        ValueType::I64 => Instruction::I64Load(3, offset),
+        ValueType::F32 => Instruction::F32Load(2, offset),
+        ValueType::F64 => Instruction::F64Load(3, offset),
+    }
+}
+
+fn store(repr: &Repr, offset: u32) -> Instruction {
+    match repr::value_type(repr) {
+        ValueType::I32 => Instruction::I32Store(2, offset),
+        ValueType::I64 => Instruction::I64Store(3, offset),
+        ValueType::F32 => Instruction::F32Store(2, offset),
+        ValueType::F64 => Instruction::F64Store(3, offset),
+    }
+}
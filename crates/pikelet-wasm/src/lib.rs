@@ -0,0 +1,9 @@
+//! A WebAssembly backend for Pikelet
+//!
+//! This compiles files to WebAssembly modules, exporting the fields of the
+//! record at the end of the file as functions. Unlike the JIT, this goes by
+//! way of the first-order representation in `pikelet_mono`, because
+//! WebAssembly needs to know the types of values ahead of time.
+
+pub mod compile;
+pub mod repr;
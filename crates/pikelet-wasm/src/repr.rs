@@ -0,0 +1,72 @@
+//! The representations of runtime values in WebAssembly
+//!
+//! Booleans, characters, and integers of up to 32 bits are represented as
+//! `i32`s, with the smaller integers kept zero or sign extended, depending on
+//! their type. 64-bit integers are represented as `i64`s, and floating point
+//! numbers as `f32`s and `f64`s.
+//!
+//! Records are represented as `i32` pointers into linear memory, where their
+//! fields are laid out in order, each aligned to its own size. As records are
+//! only ever compatible with records that have the same fields, the layout of
+//! a record can always be known from its type.
+
+use parity_wasm::elements::ValueType;
+
+use pikelet_core::syntax::Label;
+use pikelet_mono::syntax::Repr;
+
+/// The WebAssembly type that values with a representation are stored in
+pub fn value_type(repr: &Repr) -> ValueType {
+    match *repr {
+        Repr::U64 | Repr::S64 => ValueType::I64,
+        Repr::F32 => ValueType::F32,
+        Repr::F64 => ValueType::F64,
+        _ => ValueType::I32,
+    }
+}
+
+/// The number of bytes that a value of the given type takes up in memory
+pub fn value_size(value_type: ValueType) -> u32 {
+    match value_type {
+        ValueType::I32 | ValueType::F32 => 4,
+        ValueType::I64 | ValueType::F64 => 8,
+    }
+}
+
+/// The layout of a record in linear memory
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordLayout {
+    /// The offsets of the fields, in bytes
+    pub offsets: Vec<u32>,
+    /// The size of the record, in bytes
+    pub size: u32,
+}
+
+impl RecordLayout {
+    pub fn new(fields: &[(Label, Repr)]) -> RecordLayout {
+        let mut offsets = Vec::with_capacity(fields.len());
+        let mut size = 0;
+
+        for &(_, ref repr) in fields {
+            let field_size = value_size(value_type(repr));
+            let offset = align_to(size, field_size);
+            offsets.push(offset);
+            size = offset + field_size;
+        }
+
+        RecordLayout { offsets, size }
+    }
+
+    /// Returns the offset of a field, along with its representation
+    pub fn field<'a>(&self, fields: &'a [(Label, Repr)], label: &Label) -> Option<(u32, &'a Repr)> {
+        fields
+            .iter()
+            .position(|&(ref other, _)| other == label)
+            .map(|index| (self.offsets[index], &fields[index].1))
+    }
+}
+
+/// Round an offset up to the next multiple of the alignment
+pub fn align_to(offset: u32, align: u32) -> u32 {
+    (offset + align - 1) / align * align
+}
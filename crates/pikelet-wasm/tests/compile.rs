@@ -0,0 +1,496 @@
+use pretty_assertions::assert_eq;
+use wasmi::{ImportsBuilder, MemoryRef, ModuleInstance, ModuleRef, NopExternals, RuntimeValue};
+
+use pikelet_concrete::elaborate::Context;
+use pikelet_core::syntax::{Label, Literal};
+use pikelet_mono::lower;
+use pikelet_mono::syntax::Repr;
+use pikelet_test_support::{parse_infer_term, Observation};
+use pikelet_wasm::compile::{self, CompileError};
+use pikelet_wasm::repr::RecordLayout;
+
+fn compile_src(src: &str) -> Result<Vec<u8>, CompileError> {
+    let context = Context::default();
+    let term = parse_infer_term(&context, src);
+    compile::compile_module(&context, &term)
+}
+
+/// Compile a file and instantiate it with `wasmi`
+fn instantiate(src: &str) -> ModuleRef {
+    match compile_src(src) {
+        Ok(bytes) => instantiate_bytes(&bytes),
+        Err(error) => panic!("compile error: {}", error),
+    }
+}
+
+fn instantiate_bytes(bytes: &[u8]) -> ModuleRef {
+    let module = wasmi::Module::from_buffer(bytes).unwrap();
+
+    ModuleInstance::new(&module, &ImportsBuilder::default())
+        .unwrap()
+        .assert_no_start()
+}
+
+fn invoke(instance: &ModuleRef, name: &str, args: &[RuntimeValue]) -> RuntimeValue {
+    match instance.invoke_export(name, args, &mut NopExternals) {
+        Ok(Some(value)) => value,
+        Ok(None) => panic!("no value returned from `{}`", name),
+        Err(error) => panic!("error invoking `{}`: {}", name, error),
+    }
+}
+
+/// Check that the values of the fields of the file, when compiled to
+/// WebAssembly and run, are the same as the ones found by the evaluator
+///
+/// Exported functions are only checked for being functions.
+fn assert_agrees(src: &str) {
+    pikelet_test_support::assert_agrees(src, |context, term| {
+        let program = match lower::lower_module(context, term) {
+            Ok(program) => program,
+            Err(error) => panic!("lower error: {}", error),
+        };
+        let instance = match compile::compile_program(&program) {
+            Ok(bytes) => instantiate_bytes(&bytes),
+            Err(error) => panic!("compile error: {}", error),
+        };
+        let memory = instance
+            .export_by_name("memory")
+            .unwrap()
+            .as_memory()
+            .unwrap()
+            .clone();
+
+        Observation::record(program.exports.iter().map(|&(ref label, id)| {
+            let function = program.function(id);
+            let observation = match function.param_count {
+                0 => observe(&memory, &function.result, invoke(&instance, &label.0, &[])),
+                _ => Observation::Function,
+            };
+            (label.clone(), observation)
+        }))
+    });
+}
+
+/// Read back a value of the given representation, following the pointers to
+/// records into linear memory
+fn observe(memory: &MemoryRef, repr: &Repr, value: RuntimeValue) -> Observation {
+    let int32 = || value.try_into::<i32>().unwrap();
+    let int64 = || value.try_into::<i64>().unwrap();
+
+    let literal = match *repr {
+        Repr::Bool => Literal::Bool(int32() != 0),
+        Repr::Char => Literal::Char(std::char::from_u32(int32() as u32).expect("invalid char")),
+        Repr::U8 => Literal::U8(int32() as u8),
+        Repr::U16 => Literal::U16(int32() as u16),
+        Repr::U32 => Literal::U32(int32() as u32),
+        Repr::U64 => Literal::U64(int64() as u64),
+        Repr::S8 => Literal::S8(int32() as i8),
+        Repr::S16 => Literal::S16(int32() as i16),
+        Repr::S32 => Literal::S32(int32()),
+        Repr::S64 => Literal::S64(int64()),
+        Repr::F32 => Literal::F32(value.try_into::<f32>().unwrap()),
+        Repr::F64 => Literal::F64(value.try_into::<f64>().unwrap()),
+        Repr::Record(ref fields) => {
+            let record = int32() as u32;
+            let layout = RecordLayout::new(fields);
+
+            return Observation::record(fields.iter().zip(&layout.offsets).map(
+                |(&(ref label, ref repr), &offset)| {
+                    let address = record + offset;
+                    let value = match *repr {
+                        Repr::U64 | Repr::S64 => {
+                            RuntimeValue::from(memory.get_value::<i64>(address).unwrap())
+                        },
+                        Repr::F32 => RuntimeValue::from(memory.get_value::<f32>(address).unwrap()),
+                        Repr::F64 => RuntimeValue::from(memory.get_value::<f64>(address).unwrap()),
+                        _ => RuntimeValue::from(memory.get_value::<i32>(address).unwrap()),
+                    };
+                    (label.clone(), observe(memory, repr, value))
+                },
+            ));
+        },
+    };
+
+    Observation::Literal(literal)
+}
+
+fn point_repr() -> Vec<(Label, Repr)> {
+    vec![
+        (Label("tag".to_owned()), Repr::U8),
+        (Label("x".to_owned()), Repr::F64),
+        (Label("y".to_owned()), Repr::F64),
+    ]
+}
+
+#[test]
+fn record_layout() {
+    let layout = RecordLayout::new(&point_repr());
+
+    assert_eq!(layout.offsets, vec![0, 8, 16]);
+    assert_eq!(layout.size, 24);
+}
+
+#[test]
+fn arithmetic() {
+    let instance = instantiate(
+        r#"
+            let
+                add-u32 : U32 -> U32 -> U32;
+                add-u32 x y = (import "prim/u32/add") x y;
+
+                sub-s8 : S8 -> S8 -> S8;
+                sub-s8 x y = (import "prim/i8/sub") x y;
+
+                add-u8 : U8 -> U8 -> U8;
+                add-u8 x y = (import "prim/u8/add") x y;
+
+                mul-u64 : U64 -> U64 -> U64;
+                mul-u64 x y = (import "prim/u64/mul") x y;
+
+                div-f64 : F64 -> F64 -> F64;
+                div-f64 x y = (import "prim/f64/div") x y;
+
+                lt-f32 : F32 -> F32 -> Bool;
+                lt-f32 x y = (import "prim/f32/lt") x y;
+            in
+                record { add-u32; sub-s8; add-u8; mul-u64; div-f64; lt-f32 }
+        "#,
+    );
+
+    assert_eq!(
+        invoke(
+            &instance,
+            "add-u32",
+            &[RuntimeValue::I32(1), RuntimeValue::I32(2)]
+        ),
+        RuntimeValue::I32(3),
+    );
+    assert_eq!(
+        invoke(
+            &instance,
+            "sub-s8",
+            &[RuntimeValue::I32(-100), RuntimeValue::I32(100)]
+        ),
+        RuntimeValue::I32(56),
+    );
+    assert_eq!(
+        invoke(
+            &instance,
+            "add-u8",
+            &[RuntimeValue::I32(200), RuntimeValue::I32(100)]
+        ),
+        RuntimeValue::I32(44),
+    );
+    assert_eq!(
+        invoke(
+            &instance,
+            "mul-u64",
+            &[RuntimeValue::I64(1 << 40), RuntimeValue::I64(3)]
+        ),
+        RuntimeValue::I64(3 << 40),
+    );
+    assert_eq!(
+        invoke(
+            &instance,
+            "div-f64",
+            &[RuntimeValue::from(3.0f64), RuntimeValue::from(2.0f64)]
+        )
+        .try_into::<f64>(),
+        Some(1.5),
+    );
+    assert_eq!(
+        invoke(
+            &instance,
+            "lt-f32",
+            &[RuntimeValue::from(1.0f32), RuntimeValue::from(0.5f32)]
+        ),
+        RuntimeValue::I32(0),
+    );
+}
+
+#[test]
+fn division() {
+    let instance = instantiate(
+        r#"
+            let
+                div-s32 : S32 -> S32 -> S32;
+                div-s32 x y = (import "prim/i32/div") x y;
+
+                div-s8 : S8 -> S8 -> S8;
+                div-s8 x y = (import "prim/i8/div") x y;
+
+                div-u32 : U32 -> U32 -> U32;
+                div-u32 x y = (import "prim/u32/div") x y;
+            in
+                record { div-s32; div-s8; div-u32 }
+        "#,
+    );
+
+    assert_eq!(
+        invoke(
+            &instance,
+            "div-s32",
+            &[RuntimeValue::I32(-7), RuntimeValue::I32(2)]
+        ),
+        RuntimeValue::I32(-3),
+    );
+    assert_eq!(
+        invoke(
+            &instance,
+            "div-s32",
+            &[RuntimeValue::I32(i32::min_value()), RuntimeValue::I32(-1)]
+        ),
+        RuntimeValue::I32(i32::min_value()),
+    );
+    assert_eq!(
+        invoke(
+            &instance,
+            "div-s8",
+            &[RuntimeValue::I32(-128), RuntimeValue::I32(-1)]
+        ),
+        RuntimeValue::I32(-128),
+    );
+    assert!(instance
+        .invoke_export(
+            "div-u32",
+            &[RuntimeValue::I32(1), RuntimeValue::I32(0)],
+            &mut NopExternals
+        )
+        .is_err());
+}
+
+#[test]
+fn constants() {
+    let instance = instantiate(
+        r#"
+            let
+                answer : U32;
+                answer = 42;
+
+                yes = true;
+            in
+                record { answer; yes; big = 1000000000000 : S64 }
+        "#,
+    );
+
+    assert_eq!(invoke(&instance, "answer", &[]), RuntimeValue::I32(42));
+    assert_eq!(invoke(&instance, "yes", &[]), RuntimeValue::I32(1));
+    assert_eq!(
+        invoke(&instance, "big", &[]),
+        RuntimeValue::I64(1_000_000_000_000)
+    );
+}
+
+#[test]
+fn case_literal() {
+    let instance = instantiate(
+        r#"
+            let
+                describe : U32 -> Char;
+                describe n = case n {
+                    0 => 'z';
+                    1 => 'o';
+                    _ => 'm';
+                };
+
+                not : Bool -> Bool;
+                not b = if b then false else true;
+            in
+                record { describe; not }
+        "#,
+    );
+
+    assert_eq!(
+        invoke(&instance, "describe", &[RuntimeValue::I32(0)]),
+        RuntimeValue::I32('z' as i32)
+    );
+    assert_eq!(
+        invoke(&instance, "describe", &[RuntimeValue::I32(1)]),
+        RuntimeValue::I32('o' as i32)
+    );
+    assert_eq!(
+        invoke(&instance, "describe", &[RuntimeValue::I32(7)]),
+        RuntimeValue::I32('m' as i32)
+    );
+    assert_eq!(
+        invoke(&instance, "not", &[RuntimeValue::I32(1)]),
+        RuntimeValue::I32(0)
+    );
+}
+
+#[test]
+fn recursive() {
+    let instance = instantiate(
+        r#"
+            let
+                partial fact : U64 -> U64;
+                fact n =
+                    if (import "prim/u64/eq") n 0 then 1 else
+                        (import "prim/u64/mul") n (fact ((import "prim/u64/sub") n 1));
+
+                partial is-even : U64 -> Bool;
+                partial is-odd : U64 -> Bool;
+
+                is-even n =
+                    if (import "prim/u64/eq") n 0 then true else is-odd ((import "prim/u64/sub") n 1);
+                is-odd n =
+                    if (import "prim/u64/eq") n 0 then false else is-even ((import "prim/u64/sub") n 1);
+            in
+                record { fact; is-even }
+        "#,
+    );
+
+    assert_eq!(
+        invoke(&instance, "fact", &[RuntimeValue::I64(5)]),
+        RuntimeValue::I64(120)
+    );
+    assert_eq!(
+        invoke(&instance, "is-even", &[RuntimeValue::I64(10)]),
+        RuntimeValue::I32(1)
+    );
+    assert_eq!(
+        invoke(&instance, "is-even", &[RuntimeValue::I64(7)]),
+        RuntimeValue::I32(0)
+    );
+}
+
+#[test]
+fn records() {
+    let instance = instantiate(
+        r#"
+            let
+                Point = Record { tag : U8; x : F64; y : F64 };
+
+                point : F64 -> F64 -> Point;
+                point x y = record { tag = 1; x = x; y = y };
+
+                dot : Point -> Point -> F64;
+                dot p q =
+                    (import "prim/f64/add")
+                        ((import "prim/f64/mul") p.x q.x)
+                        ((import "prim/f64/mul") p.y q.y);
+
+                norm-squared : Point -> F64;
+                norm-squared p = dot p p;
+            in
+                record { Point; point; norm-squared }
+        "#,
+    );
+    let memory = instance
+        .export_by_name("memory")
+        .unwrap()
+        .as_memory()
+        .unwrap()
+        .clone();
+    let layout = RecordLayout::new(&point_repr());
+
+    // Read a record that was allocated by compiled code
+    let point = invoke(
+        &instance,
+        "point",
+        &[RuntimeValue::from(3.0f64), RuntimeValue::from(4.0f64)],
+    );
+    let point = point.try_into::<i32>().unwrap() as u32;
+    assert_eq!(
+        memory.get_value::<u8>(point + layout.offsets[0]).unwrap(),
+        1
+    );
+    assert_eq!(
+        memory.get_value::<f64>(point + layout.offsets[1]).unwrap(),
+        3.0
+    );
+    assert_eq!(
+        memory.get_value::<f64>(point + layout.offsets[2]).unwrap(),
+        4.0
+    );
+
+    // Pass a record that was allocated by the host
+    let arg = invoke(&instance, "alloc", &[RuntimeValue::I32(layout.size as i32)]);
+    let arg = arg.try_into::<i32>().unwrap() as u32;
+    memory.set_value(arg + layout.offsets[1], 1.5f64).unwrap();
+    memory.set_value(arg + layout.offsets[2], 2.0f64).unwrap();
+    assert_eq!(
+        invoke(&instance, "norm-squared", &[RuntimeValue::I32(arg as i32)]).try_into::<f64>(),
+        Some(6.25),
+    );
+
+    assert!(instance.export_by_name("Point").is_none());
+}
+
+#[test]
+fn unused_unsupported_definitions() {
+    let instance = instantiate(
+        r#"
+            let
+                id : (a : Type) -> a -> a;
+                id a x = x;
+
+                greeting = "hello";
+
+                one : U8;
+                one = 1;
+            in
+                record { one }
+        "#,
+    );
+
+    assert_eq!(invoke(&instance, "one", &[]), RuntimeValue::I32(1));
+}
+
+#[test]
+fn export_string() {
+    assert_eq!(
+        compile_src(r#"record { greeting = "hello" }"#),
+        Err(CompileError::new(
+            "could not export `greeting`: strings are not yet supported"
+        )),
+    );
+}
+
+#[test]
+fn agrees_arithmetic() {
+    assert_agrees(
+        r#"
+            record {
+                add-u8 = (import "prim/u8/add") 200 100 : U8;
+                sub-s8 = (import "prim/i8/sub") ((import "prim/i8/sub") 0 100) 100 : S8;
+                mul-u16 = (import "prim/u16/mul") 65535 65535 : U16;
+                div-s8 = (import "prim/i8/div") ((import "prim/i8/sub") ((import "prim/i8/sub") 0 127) 1) ((import "prim/i8/sub") 0 1) : S8;
+                div-s32 = (import "prim/i32/div") ((import "prim/i32/sub") 1 8) 2 : S32;
+                mul-u64 = (import "prim/u64/mul") 4294967296 3 : U64;
+                sub-s64 = (import "prim/i64/sub") 1 2 : S64;
+                div-f32 = (import "prim/f32/div") 1.0 3.0 : F32;
+                sub-f64 = (import "prim/f64/sub") 0.1 0.3 : F64;
+                lt-f64 = (import "prim/f64/lt") 0.1 0.3;
+                eq-char = (import "prim/char/eq") 'a' 'b';
+            }
+        "#,
+    );
+}
+
+#[test]
+fn agrees_records() {
+    assert_agrees(
+        r#"
+            let
+                Point = Record { x : S32; y : S32 };
+                Line = Record { start : Point; end : Point };
+
+                point : S32 -> S32 -> Point;
+                point x y = record { x = x; y = y };
+
+                swap : Line -> Line;
+                swap line = record { start = line.end; end = line.start };
+            in
+                let
+                    start = point 1 2;
+                    end = point 5 ((import "prim/i32/sub") 0 3);
+                in record {
+                    Point;
+                    point;
+                    swapped = swap (record { start = start; end = end });
+                    empty = record {};
+                }
+        "#,
+    );
+}